@@ -0,0 +1,643 @@
+//! A compact, versioned binary encoding of whole graphs.
+//!
+//! The encoding is little-endian throughout:
+//!
+//! ```text
+//! magic      b"SASG"
+//! version    u32                 FORMAT_VERSION
+//! flags      u8                  bit 0: a checksum trailer follows
+//! root       u8 tag, [u64]       0 for none, 1 followed by the id
+//! next id    u64                 the id the next added node gets
+//! count      u64                 number of nodes, then the nodes by id
+//! checksum   [32 bytes]          see below
+//! ```
+//!
+//! A node is its id, a content tag (the index of its kind in
+//! [`NodeKind::ALL`]), the content's fields in declaration order, and its
+//! metadata. Strings are a `u64` byte length followed by UTF-8, lists a
+//! `u64` length followed by the items, and options a `0`/`1` tag followed by
+//! the value when present.
+//!
+//! A bare list of nodes, as exchanged by editors sending changes to part
+//! of a graph, is encoded by [`encode_nodes`] as [`NODES_MAGIC`], the
+//! version, and the nodes in the order given.
+//!
+//! The checksum is the BLAKE3 digest of every byte before it, header,
+//! metadata and `next id` included. It catches corruption that still
+//! decodes into a well-formed graph; anything else, such as a truncated
+//! file, already fails to decode.
+//!
+//! Version 1 used a 64-bit hash of the graph's terms as its checksum, which
+//! neither covered metadata nor stayed the same across Rust releases.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::graph::AsgGraph;
+use crate::nodes::{
+    AsgNode, EffectPerform, LiteralBool, LiteralInt, LiteralUnit, Metadata, NodeContent, NodeKind,
+    PrimitiveOp, SourceLocation, TermApplication, TermAssign, TermDeref, TermLambda,
+    TermMacroDefinition, TermMacroInvocation, TermRef, TermVariable, TypeKind, TypeNode,
+};
+
+/// The bytes every encoded graph starts with.
+pub const MAGIC: [u8; 4] = *b"SASG";
+
+/// The bytes every encoded node list starts with.
+pub const NODES_MAGIC: [u8; 4] = *b"SASN";
+
+/// The format version written by this library, and the only one it reads.
+pub const FORMAT_VERSION: u32 = 2;
+
+const FLAG_CHECKSUM: u8 = 1;
+
+/// Reasons an encoded graph cannot be read back.
+#[derive(Debug, Error)]
+pub enum BinaryError {
+    #[error("failed to access {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The data does not start with [`MAGIC`].
+    #[error("not an encoded ASG: bad magic number")]
+    BadMagic,
+
+    /// The data was written in a format version this library cannot read.
+    #[error("unsupported ASG format version {found} (supported: {supported})")]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    /// The data ends in the middle of the graph.
+    #[error("encoded ASG is truncated")]
+    Truncated,
+
+    /// The data decodes to something that is not a graph.
+    #[error("malformed encoded ASG: {0}")]
+    Malformed(String),
+
+    /// The data is not what was saved: its digest differs from the one
+    /// saved with it.
+    #[error(
+        "ASG checksum mismatch: expected {}, found {}",
+        hex(expected),
+        hex(found)
+    )]
+    ChecksumMismatch { expected: [u8; 32], found: [u8; 32] },
+}
+
+pub type Result<T> = std::result::Result<T, BinaryError>;
+
+/// Writes `graph` to `path`, with a checksum.
+pub fn save_asg_binary(graph: &AsgGraph, path: &Path) -> Result<()> {
+    std::fs::write(path, encode_asg(graph, true)).map_err(|source| BinaryError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Reads a graph written by [`save_asg_binary`] from `path`.
+pub fn load_asg_binary(path: &Path) -> Result<AsgGraph> {
+    let bytes = std::fs::read(path).map_err(|source| BinaryError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    decode_asg(&bytes)
+}
+
+/// Encodes `graph`, followed by a checksum if `checksum` is set.
+pub fn encode_asg(graph: &AsgGraph, checksum: bool) -> Vec<u8> {
+    let mut out = Encoder(MAGIC.to_vec());
+    out.u32(FORMAT_VERSION);
+    out.u8(if checksum { FLAG_CHECKSUM } else { 0 });
+    out.option(graph.root(), Encoder::u64);
+    out.u64(graph.next_id());
+    let mut nodes: Vec<&AsgNode> = graph.nodes().collect();
+    nodes.sort_unstable_by_key(|node| node.node_id);
+    out.list(&nodes, |out, node| out.node(node));
+    if checksum {
+        let digest = blake3::hash(&out.0);
+        out.0.extend_from_slice(digest.as_bytes());
+    }
+    out.0
+}
+
+/// Decodes a graph encoded by [`encode_asg`], verifying its checksum if it
+/// has one. Trailing bytes are rejected.
+pub fn decode_asg(bytes: &[u8]) -> Result<AsgGraph> {
+    let mut input = Decoder { bytes, pos: 0 };
+    input.header(MAGIC)?;
+    let flags = input.u8()?;
+    if flags & !FLAG_CHECKSUM != 0 {
+        return Err(malformed(format!("unknown flags {:#04x}", flags)));
+    }
+    let root = input.option(Decoder::u64)?;
+    let next_id = input.u64()?;
+    let mut nodes = HashMap::new();
+    for node in input.list(Decoder::node)? {
+        let node_id = node.node_id;
+        if nodes.insert(node_id, node).is_some() {
+            return Err(malformed(format!("node {} appears twice", node_id)));
+        }
+    }
+    if flags & FLAG_CHECKSUM != 0 {
+        let found = *blake3::hash(&bytes[..input.pos]).as_bytes();
+        let expected = input.array()?;
+        if expected != found {
+            return Err(BinaryError::ChecksumMismatch { expected, found });
+        }
+    }
+    input.finish()?;
+    Ok(AsgGraph::from_parts(nodes, root, next_id))
+}
+
+/// Encodes `nodes`, keeping their ids.
+pub fn encode_nodes(nodes: &[AsgNode]) -> Vec<u8> {
+    let mut out = Encoder(NODES_MAGIC.to_vec());
+    out.u32(FORMAT_VERSION);
+    out.list(nodes, Encoder::node);
+    out.0
+}
+
+/// Decodes nodes encoded by [`encode_nodes`].
+pub fn decode_nodes(bytes: &[u8]) -> Result<Vec<AsgNode>> {
+    let mut input = Decoder { bytes, pos: 0 };
+    input.header(NODES_MAGIC)?;
+    let nodes = input.list(Decoder::node)?;
+    input.finish()?;
+    Ok(nodes)
+}
+
+fn malformed(message: String) -> BinaryError {
+    BinaryError::Malformed(message)
+}
+
+fn hex(digest: &[u8; 32]) -> String {
+    digest.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{:02x}", byte);
+        out
+    })
+}
+
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i64(&mut self, value: i64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u64(value.len() as u64);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn option<T>(&mut self, value: Option<T>, mut f: impl FnMut(&mut Self, T)) {
+        match value {
+            None => self.u8(0),
+            Some(value) => {
+                self.u8(1);
+                f(self, value);
+            }
+        }
+    }
+
+    fn list<T>(&mut self, items: &[T], mut f: impl FnMut(&mut Self, &T)) {
+        self.u64(items.len() as u64);
+        for item in items {
+            f(self, item);
+        }
+    }
+
+    fn ids(&mut self, ids: &[u64]) {
+        self.list(ids, |out, &id| out.u64(id));
+    }
+
+    fn node(&mut self, node: &AsgNode) {
+        self.u64(node.node_id);
+        let tag = NodeKind::ALL
+            .iter()
+            .position(|&kind| kind == node.kind())
+            .expect("every kind is listed");
+        self.u8(tag as u8);
+        match &node.content {
+            NodeContent::TermVariable(var) => {
+                self.str(&var.name);
+                self.u64(var.definition_node_id);
+            }
+            NodeContent::TermLambda(lambda) => {
+                self.u64(lambda.binder_variable_node_id);
+                self.u64(lambda.body_node_id);
+                self.option(lambda.type_annotation_id, Self::u64);
+                self.option(lambda.effect_annotation.as_deref(), |out, effects| {
+                    out.list(effects, |out, effect| out.str(effect))
+                });
+            }
+            NodeContent::TermApplication(app) => {
+                self.u64(app.function_node_id);
+                self.u64(app.argument_node_id);
+            }
+            NodeContent::LiteralInt(lit) => self.i64(lit.value),
+            NodeContent::LiteralBool(lit) => self.bool(lit.value),
+            NodeContent::LiteralUnit(_) => {}
+            NodeContent::PrimitiveOp(op) => {
+                self.str(&op.op_name);
+                self.ids(&op.argument_node_ids);
+            }
+            NodeContent::TermRef(term) => self.u64(term.init_value_node_id),
+            NodeContent::TermDeref(term) => self.u64(term.ref_node_id),
+            NodeContent::TermAssign(term) => {
+                self.u64(term.ref_node_id);
+                self.u64(term.value_node_id);
+            }
+            NodeContent::EffectPerform(perform) => {
+                self.str(&perform.effect_name);
+                self.u64(perform.value_node_id);
+            }
+            NodeContent::TermMacroDefinition(definition) => {
+                self.str(&definition.name);
+                self.ids(&definition.parameter_node_ids);
+                self.u64(definition.body_node_id);
+            }
+            NodeContent::TermMacroInvocation(invocation) => {
+                self.str(&invocation.macro_name);
+                self.ids(&invocation.argument_node_ids);
+            }
+            NodeContent::TypeNode(ty) => match ty.kind {
+                TypeKind::Int => self.u8(0),
+                TypeKind::Bool => self.u8(1),
+                TypeKind::Unit => self.u8(2),
+                TypeKind::Function {
+                    param_type_id,
+                    return_type_id,
+                } => {
+                    self.u8(3);
+                    self.u64(param_type_id);
+                    self.u64(return_type_id);
+                }
+                TypeKind::Ref { element_type_id } => {
+                    self.u8(4);
+                    self.u64(element_type_id);
+                }
+            },
+        }
+        self.option(node.metadata.as_ref(), |out, metadata| {
+            out.option(metadata.source_location.as_ref(), |out, location| {
+                out.str(&location.filename);
+                for n in [
+                    location.start_line,
+                    location.start_col,
+                    location.end_line,
+                    location.end_col,
+                ] {
+                    out.u32(n);
+                }
+            });
+            out.option(metadata.inferred_type_id, Self::u64);
+        });
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    /// Checks the magic and the format version.
+    fn header(&mut self, magic: [u8; 4]) -> Result<()> {
+        if self.take(magic.len()).ok() != Some(&magic[..]) {
+            return Err(BinaryError::BadMagic);
+        }
+        let version = self.u32()?;
+        if version != FORMAT_VERSION {
+            return Err(BinaryError::UnsupportedVersion {
+                found: version,
+                supported: FORMAT_VERSION,
+            });
+        }
+        Ok(())
+    }
+
+    /// Rejects trailing bytes.
+    fn finish(&self) -> Result<()> {
+        if self.pos != self.bytes.len() {
+            return Err(malformed(format!(
+                "{} unexpected trailing byte(s)",
+                self.bytes.len() - self.pos
+            )));
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(BinaryError::Truncated)?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        self.array().map(i64::from_le_bytes)
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(malformed(format!("invalid boolean {}", other))),
+        }
+    }
+
+    /// A length, checked against the bytes left so a corrupt length cannot
+    /// make the decoder allocate more than the input could hold.
+    fn len(&mut self) -> Result<usize> {
+        let len = self.u64()?;
+        let left = (self.bytes.len() - self.pos) as u64;
+        if len > left {
+            return Err(BinaryError::Truncated);
+        }
+        Ok(len as usize)
+    }
+
+    fn str(&mut self) -> Result<String> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| malformed("invalid UTF-8".to_string()))
+    }
+
+    fn option<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<Option<T>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => f(self).map(Some),
+            other => Err(malformed(format!("invalid option tag {}", other))),
+        }
+    }
+
+    fn list<T>(&mut self, mut f: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        let len = self.len()?;
+        (0..len).map(|_| f(self)).collect()
+    }
+
+    fn ids(&mut self) -> Result<Vec<u64>> {
+        self.list(Self::u64)
+    }
+
+    fn node(&mut self) -> Result<AsgNode> {
+        let node_id = self.u64()?;
+        let tag = self.u8()?;
+        let kind = *NodeKind::ALL
+            .get(tag as usize)
+            .ok_or_else(|| malformed(format!("unknown node tag {}", tag)))?;
+        let content = match kind {
+            NodeKind::TermVariable => NodeContent::TermVariable(TermVariable {
+                name: self.str()?,
+                definition_node_id: self.u64()?,
+            }),
+            NodeKind::TermLambda => NodeContent::TermLambda(TermLambda {
+                binder_variable_node_id: self.u64()?,
+                body_node_id: self.u64()?,
+                type_annotation_id: self.option(Self::u64)?,
+                effect_annotation: self.option(|input| input.list(Self::str))?,
+            }),
+            NodeKind::TermApplication => NodeContent::TermApplication(TermApplication {
+                function_node_id: self.u64()?,
+                argument_node_id: self.u64()?,
+            }),
+            NodeKind::LiteralInt => NodeContent::LiteralInt(LiteralInt { value: self.i64()? }),
+            NodeKind::LiteralBool => NodeContent::LiteralBool(LiteralBool {
+                value: self.bool()?,
+            }),
+            NodeKind::LiteralUnit => NodeContent::LiteralUnit(LiteralUnit),
+            NodeKind::PrimitiveOp => NodeContent::PrimitiveOp(PrimitiveOp {
+                op_name: self.str()?,
+                argument_node_ids: self.ids()?,
+            }),
+            NodeKind::TermRef => NodeContent::TermRef(TermRef {
+                init_value_node_id: self.u64()?,
+            }),
+            NodeKind::TermDeref => NodeContent::TermDeref(TermDeref {
+                ref_node_id: self.u64()?,
+            }),
+            NodeKind::TermAssign => NodeContent::TermAssign(TermAssign {
+                ref_node_id: self.u64()?,
+                value_node_id: self.u64()?,
+            }),
+            NodeKind::EffectPerform => NodeContent::EffectPerform(EffectPerform {
+                effect_name: self.str()?,
+                value_node_id: self.u64()?,
+            }),
+            NodeKind::TermMacroDefinition => {
+                NodeContent::TermMacroDefinition(TermMacroDefinition {
+                    name: self.str()?,
+                    parameter_node_ids: self.ids()?,
+                    body_node_id: self.u64()?,
+                })
+            }
+            NodeKind::TermMacroInvocation => {
+                NodeContent::TermMacroInvocation(TermMacroInvocation {
+                    macro_name: self.str()?,
+                    argument_node_ids: self.ids()?,
+                })
+            }
+            NodeKind::TypeNode => NodeContent::TypeNode(TypeNode {
+                kind: match self.u8()? {
+                    0 => TypeKind::Int,
+                    1 => TypeKind::Bool,
+                    2 => TypeKind::Unit,
+                    3 => TypeKind::Function {
+                        param_type_id: self.u64()?,
+                        return_type_id: self.u64()?,
+                    },
+                    4 => TypeKind::Ref {
+                        element_type_id: self.u64()?,
+                    },
+                    other => return Err(malformed(format!("unknown type tag {}", other))),
+                },
+            }),
+        };
+        let metadata = self.option(|input| {
+            Ok(Metadata {
+                source_location: input.option(|input| {
+                    Ok(SourceLocation {
+                        filename: input.str()?,
+                        start_line: input.u32()?,
+                        start_col: input.u32()?,
+                        end_line: input.u32()?,
+                        end_col: input.u32()?,
+                    })
+                })?,
+                inferred_type_id: input.option(Self::u64)?,
+            })
+        })?;
+        Ok(AsgNode {
+            node_id,
+            content,
+            metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(x: Int) => x + 41`, typed, with a location and an unused node.
+    fn sample() -> AsgGraph {
+        let mut graph = AsgGraph::new();
+        let int = graph.add_node(NodeContent::TypeNode(TypeNode {
+            kind: TypeKind::Int,
+        }));
+        let lambda = graph.lambda("x", |graph, x| {
+            let x = x.reference(graph);
+            let n = graph.int(41);
+            graph.primitive("add", [x, n])
+        });
+        if let Some(NodeContent::TermLambda(term)) =
+            graph.get_node_mut(lambda).map(|node| &mut node.content)
+        {
+            term.type_annotation_id = Some(int);
+            term.effect_annotation = Some(vec!["IO".to_string()]);
+        }
+        graph.set_root(lambda);
+        graph.set_inferred_type(lambda, int);
+        graph.set_source_location(
+            lambda,
+            SourceLocation {
+                filename: "main.syn".to_string(),
+                start_line: 1,
+                start_col: 1,
+                end_line: 1,
+                end_col: 18,
+            },
+        );
+        let unused = graph.add_node(NodeContent::LiteralUnit(LiteralUnit));
+        graph.remove_node(unused);
+        graph
+    }
+
+    #[test]
+    fn graphs_round_trip_through_files() {
+        let graph = sample();
+        let path = std::env::temp_dir().join(format!("synapse_asg_{}.asgb", std::process::id()));
+        save_asg_binary(&graph, &path).unwrap();
+        let loaded = load_asg_binary(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, graph);
+
+        let mut nodes: Vec<AsgNode> = graph.nodes().cloned().collect();
+        nodes.sort_unstable_by_key(|node| node.node_id);
+        let encoded = encode_nodes(&nodes);
+        assert_eq!(decode_nodes(&encoded).unwrap(), nodes);
+        assert!(matches!(decode_asg(&encoded), Err(BinaryError::BadMagic)));
+
+        let plain = encode_asg(&graph, false);
+        assert_eq!(decode_asg(&plain).unwrap(), graph);
+        assert_eq!(plain.len() + 32, encode_asg(&graph, true).len());
+    }
+
+    #[test]
+    fn foreign_and_damaged_data_is_rejected() {
+        let bytes = encode_asg(&sample(), true);
+
+        assert!(matches!(
+            decode_asg(b"{\"nodes\": []}"),
+            Err(BinaryError::BadMagic)
+        ));
+        for version in [1, FORMAT_VERSION + 1] {
+            let mut other = bytes.clone();
+            other[4..8].copy_from_slice(&version.to_le_bytes());
+            assert!(matches!(
+                decode_asg(&other),
+                Err(BinaryError::UnsupportedVersion {
+                    found,
+                    supported: FORMAT_VERSION
+                }) if found == version
+            ));
+        }
+        for len in 4..bytes.len() {
+            assert!(
+                matches!(decode_asg(&bytes[..len]), Err(BinaryError::Truncated)),
+                "prefix of {} bytes",
+                len
+            );
+        }
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(matches!(
+            decode_asg(&longer),
+            Err(BinaryError::Malformed(_))
+        ));
+
+        // Changing the literal keeps the graph well formed but not the same.
+        let literal = 41i64.to_le_bytes();
+        let at = bytes
+            .windows(8)
+            .position(|window| window == literal)
+            .unwrap();
+        let mut changed = bytes.clone();
+        changed[at] = 2;
+        assert!(matches!(
+            decode_asg(&changed),
+            Err(BinaryError::ChecksumMismatch { .. })
+        ));
+
+        // So is damage outside the terms: metadata and the next id.
+        let at = bytes
+            .windows(8)
+            .position(|window| window == b"main.syn")
+            .unwrap();
+        let mut renamed = bytes.clone();
+        renamed[at] = b'M';
+        assert!(matches!(
+            decode_asg(&renamed),
+            Err(BinaryError::ChecksumMismatch { .. })
+        ));
+        let next_id = 4 + 4 + 1 + 1 + 8;
+        let mut renumbered = bytes.clone();
+        renumbered[next_id] ^= 1;
+        assert!(matches!(
+            decode_asg(&renumbered),
+            Err(BinaryError::ChecksumMismatch { .. })
+        ));
+    }
+}
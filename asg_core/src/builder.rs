@@ -0,0 +1,284 @@
+//! Helpers for constructing well-linked graph fragments.
+//!
+//! A lambda's node is created after its body, yet its binder and every use of
+//! its parameter must name the lambda in `definition_node_id`.
+//! [`LambdaBuilder`] hands out those variable nodes first and links them all
+//! when the lambda is finished, so callers never patch ids by hand.
+//!
+//! [`AsgGraph`] also gets one-call constructors for the common terms, so a
+//! whole program can be written as nested calls:
+//!
+//! ```
+//! use asg_core::AsgGraph;
+//!
+//! let mut graph = AsgGraph::new();
+//! // ((x) => x + 1)(41)
+//! let increment = graph.lambda("x", |graph, x| {
+//!     let x = x.reference(graph);
+//!     let one = graph.int(1);
+//!     graph.primitive("add", [x, one])
+//! });
+//! let argument = graph.int(41);
+//! let program = graph.apply(increment, argument);
+//! graph.set_root(program);
+//! ```
+
+use crate::graph::AsgGraph;
+use crate::nodes::{
+    LiteralBool, LiteralInt, LiteralUnit, NodeContent, PrimitiveOp, TermApplication, TermLambda,
+    TermVariable,
+};
+
+impl AsgGraph {
+    /// Adds the lambda `(name) => body`; see [`LambdaBuilder::build`].
+    pub fn lambda(
+        &mut self,
+        name: impl Into<String>,
+        body: impl FnOnce(&mut AsgGraph, &mut LambdaBuilder) -> u64,
+    ) -> u64 {
+        LambdaBuilder::build(self, name, body)
+    }
+
+    /// Adds the application `function(argument)`.
+    pub fn apply(&mut self, function_node_id: u64, argument_node_id: u64) -> u64 {
+        self.add_node(NodeContent::TermApplication(TermApplication {
+            function_node_id,
+            argument_node_id,
+        }))
+    }
+
+    /// Adds the primitive operation `op_name` applied to `arguments`.
+    pub fn primitive(
+        &mut self,
+        op_name: impl Into<String>,
+        arguments: impl IntoIterator<Item = u64>,
+    ) -> u64 {
+        self.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: op_name.into(),
+            argument_node_ids: arguments.into_iter().collect(),
+        }))
+    }
+
+    /// Adds an integer literal.
+    pub fn int(&mut self, value: i64) -> u64 {
+        self.add_node(NodeContent::LiteralInt(LiteralInt { value }))
+    }
+
+    /// Adds a boolean literal.
+    pub fn boolean(&mut self, value: bool) -> u64 {
+        self.add_node(NodeContent::LiteralBool(LiteralBool { value }))
+    }
+
+    /// Adds the unit literal.
+    pub fn unit(&mut self) -> u64 {
+        self.add_node(NodeContent::LiteralUnit(LiteralUnit))
+    }
+}
+
+/// A lambda under construction: its binder exists, its node does not yet.
+///
+/// ```
+/// use asg_core::{AsgGraph, LambdaBuilder};
+///
+/// let mut graph = AsgGraph::new();
+/// // (x) => x
+/// let identity = LambdaBuilder::build(&mut graph, "x", |graph, x| x.reference(graph));
+/// graph.set_root(identity);
+/// ```
+#[derive(Debug)]
+#[must_use = "the lambda is only added by `finish`"]
+pub struct LambdaBuilder {
+    name: String,
+    binder: u64,
+    uses: Vec<u64>,
+    type_annotation_id: Option<u64>,
+    effect_annotation: Option<Vec<String>>,
+}
+
+impl LambdaBuilder {
+    /// Adds the binder variable for a parameter called `name`.
+    pub fn new(graph: &mut AsgGraph, name: impl Into<String>) -> Self {
+        let name = name.into();
+        let binder = add_variable(graph, &name);
+        Self {
+            name,
+            binder,
+            uses: Vec::new(),
+            type_annotation_id: None,
+            effect_annotation: None,
+        }
+    }
+
+    /// Creates the lambda `(name) => body`, where `body` builds the body
+    /// using the builder to refer to the parameter. Returns the lambda id.
+    pub fn build(
+        graph: &mut AsgGraph,
+        name: impl Into<String>,
+        body: impl FnOnce(&mut AsgGraph, &mut LambdaBuilder) -> u64,
+    ) -> u64 {
+        let mut builder = Self::new(graph, name);
+        let body = body(graph, &mut builder);
+        builder.finish(graph, body)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The binder variable node.
+    pub fn binder(&self) -> u64 {
+        self.binder
+    }
+
+    /// Sets the `TypeNode` annotating the parameter.
+    pub fn set_annotation(&mut self, type_annotation_id: Option<u64>) {
+        self.type_annotation_id = type_annotation_id;
+    }
+
+    /// Sets the effects the body is declared to perform.
+    pub fn set_effects(&mut self, effect_annotation: Option<Vec<String>>) {
+        self.effect_annotation = effect_annotation;
+    }
+
+    /// Adds a use of the parameter, linked to the lambda on
+    /// [`LambdaBuilder::finish`].
+    pub fn reference(&mut self, graph: &mut AsgGraph) -> u64 {
+        let node_id = add_variable(graph, &self.name);
+        self.uses.push(node_id);
+        node_id
+    }
+
+    /// Adds the lambda node with `body_node_id` as its body, links the binder
+    /// and every use to it, and returns its id.
+    pub fn finish(self, graph: &mut AsgGraph, body_node_id: u64) -> u64 {
+        let lambda = graph.add_node(NodeContent::TermLambda(TermLambda {
+            binder_variable_node_id: self.binder,
+            body_node_id,
+            type_annotation_id: self.type_annotation_id,
+            effect_annotation: self.effect_annotation,
+        }));
+        for node_id in std::iter::once(self.binder).chain(self.uses) {
+            if let Some(NodeContent::TermVariable(var)) =
+                graph.get_node_mut(node_id).map(|node| &mut node.content)
+            {
+                var.definition_node_id = lambda;
+            }
+        }
+        lambda
+    }
+}
+
+fn add_variable(graph: &mut AsgGraph, name: &str) -> u64 {
+    graph.add_node(NodeContent::TermVariable(TermVariable {
+        name: name.to_string(),
+        definition_node_id: 0,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_graph;
+
+    fn definition_of(graph: &AsgGraph, node_id: u64) -> u64 {
+        match &graph.get_node(node_id).unwrap().content {
+            NodeContent::TermVariable(var) => var.definition_node_id,
+            other => panic!("expected a variable, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn binder_and_uses_point_back_at_the_lambda() {
+        let mut graph = AsgGraph::new();
+        let mut uses = Vec::new();
+        // (x) => x + x
+        let lambda = LambdaBuilder::build(&mut graph, "x", |graph, x| {
+            uses = vec![x.reference(graph), x.reference(graph)];
+            graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+                op_name: "add".to_string(),
+                argument_node_ids: uses.clone(),
+            }))
+        });
+
+        let NodeContent::TermLambda(term) = &graph.get_node(lambda).unwrap().content else {
+            panic!("expected a lambda");
+        };
+        assert_eq!(definition_of(&graph, term.binder_variable_node_id), lambda);
+        for use_site in uses {
+            assert_eq!(definition_of(&graph, use_site), lambda);
+        }
+        assert_eq!(graph.reference_index().uses_of(lambda).len(), 2);
+    }
+
+    #[test]
+    fn nested_builders_link_to_their_own_lambda() {
+        let mut graph = AsgGraph::new();
+        // (x) => (y) => x
+        let mut outer = LambdaBuilder::new(&mut graph, "x");
+        let inner = LambdaBuilder::new(&mut graph, "y");
+        let x = outer.reference(&mut graph);
+        let y_binder = inner.binder();
+        let inner_lambda = inner.finish(&mut graph, x);
+        let outer_lambda = outer.finish(&mut graph, inner_lambda);
+
+        assert_eq!(definition_of(&graph, x), outer_lambda);
+        assert_eq!(definition_of(&graph, y_binder), inner_lambda);
+    }
+
+    #[test]
+    fn graph_constructors_match_hand_linked_terms() {
+        // (f) => (x) => f(f(x)), built by hand with explicit links ...
+        let mut manual = AsgGraph::new();
+        let f_binder = add_variable(&mut manual, "f");
+        let x_binder = add_variable(&mut manual, "x");
+        let [f1, f2, x] = [
+            add_variable(&mut manual, "f"),
+            add_variable(&mut manual, "f"),
+            add_variable(&mut manual, "x"),
+        ];
+        let inner_call = manual.apply(f2, x);
+        let outer_call = manual.apply(f1, inner_call);
+        let x_lambda = manual.add_node(NodeContent::TermLambda(TermLambda {
+            binder_variable_node_id: x_binder,
+            body_node_id: outer_call,
+            type_annotation_id: None,
+            effect_annotation: None,
+        }));
+        let f_lambda = manual.add_node(NodeContent::TermLambda(TermLambda {
+            binder_variable_node_id: f_binder,
+            body_node_id: x_lambda,
+            type_annotation_id: None,
+            effect_annotation: None,
+        }));
+        for (var, lambda) in [(f_binder, f_lambda), (f1, f_lambda), (f2, f_lambda)]
+            .into_iter()
+            .chain([(x_binder, x_lambda), (x, x_lambda)])
+        {
+            if let NodeContent::TermVariable(v) = &mut manual.get_node_mut(var).unwrap().content {
+                v.definition_node_id = lambda;
+            }
+        }
+
+        // ... and with the builders, where no id is patched by hand.
+        let mut built = AsgGraph::new();
+        let twice = built.lambda("f", |graph, f| {
+            graph.lambda("x", |graph, x| {
+                let f1 = f.reference(graph);
+                let f2 = f.reference(graph);
+                let x = x.reference(graph);
+                let inner = graph.apply(f2, x);
+                graph.apply(f1, inner)
+            })
+        });
+        assert_eq!(hash_graph(&built, twice), hash_graph(&manual, f_lambda));
+        assert_eq!(built.reference_index().uses_of(twice).len(), 2);
+
+        let sum = built.primitive("add", [1, 2]);
+        let ints = [built.int(1), built.boolean(true), built.unit()];
+        assert_eq!(built.get_node(sum).unwrap().content.child_ids(), vec![1, 2]);
+        assert!(matches!(
+            built.get_node(ints[1]).unwrap().content,
+            NodeContent::LiteralBool(LiteralBool { value: true })
+        ));
+    }
+}
@@ -0,0 +1,67 @@
+//! Canonical effect names.
+//!
+//! `EffectPerform` stores its effect as a string. Names are case-sensitive:
+//! an effect is either one of the [`STANDARD_EFFECTS`] spelled exactly, or a
+//! user-defined effect written with the [`CUSTOM_PREFIX`], e.g.
+//! `Custom:Log`. Anything else is almost certainly a typo.
+
+/// Effects with built-in meaning, in canonical spelling.
+pub const STANDARD_EFFECTS: &[&str] = &["IO", "State", "Network", "Sleep"];
+
+/// Prefix that marks a user-defined effect name.
+pub const CUSTOM_PREFIX: &str = "Custom:";
+
+/// A recognised effect name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectName<'a> {
+    Standard(&'static str),
+    /// The part after [`CUSTOM_PREFIX`].
+    Custom(&'a str),
+}
+
+/// An effect name that is neither standard nor custom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownEffect {
+    pub name: String,
+    /// The standard effect the name matches ignoring case, if any.
+    pub suggestion: Option<&'static str>,
+}
+
+/// Classifies `name`, rejecting names that are not canonical.
+pub fn parse_effect_name(name: &str) -> Result<EffectName<'_>, UnknownEffect> {
+    if let Some(standard) = STANDARD_EFFECTS.iter().find(|known| **known == name) {
+        return Ok(EffectName::Standard(standard));
+    }
+    match name.strip_prefix(CUSTOM_PREFIX) {
+        Some(custom) if !custom.is_empty() => Ok(EffectName::Custom(custom)),
+        _ => Err(UnknownEffect {
+            name: name.to_string(),
+            suggestion: STANDARD_EFFECTS
+                .iter()
+                .copied()
+                .find(|known| known.eq_ignore_ascii_case(name)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_case_sensitive_with_a_custom_escape_hatch() {
+        assert_eq!(parse_effect_name("IO"), Ok(EffectName::Standard("IO")));
+        assert_eq!(
+            parse_effect_name("Custom:Log"),
+            Ok(EffectName::Custom("Log"))
+        );
+        assert_eq!(
+            parse_effect_name("Io"),
+            Err(UnknownEffect {
+                name: "Io".to_string(),
+                suggestion: Some("IO"),
+            })
+        );
+        assert_eq!(parse_effect_name("Custom:").unwrap_err().suggestion, None);
+    }
+}
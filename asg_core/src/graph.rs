@@ -0,0 +1,673 @@
+//! The in-memory graph container.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
+
+use crate::nodes::{AsgNode, Metadata, NodeContent, SourceLocation};
+use crate::references::{ReferenceIndex, reference_ids};
+
+/// A flat collection of ASG nodes with an optional entry point.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AsgGraph {
+    nodes: HashMap<u64, AsgNode>,
+    root_node_id: Option<u64>,
+    next_id: u64,
+}
+
+impl AsgGraph {
+    /// Creates an empty graph.
+    pub fn new() -> Self {
+        Self {
+            nodes: HashMap::new(),
+            root_node_id: None,
+            next_id: 1,
+        }
+    }
+
+    /// Reassembles a graph from its parts, as [`crate::binary`] stores them.
+    pub(crate) fn from_parts(
+        nodes: HashMap<u64, AsgNode>,
+        root_node_id: Option<u64>,
+        next_id: u64,
+    ) -> Self {
+        Self {
+            nodes,
+            root_node_id,
+            next_id,
+        }
+    }
+
+    /// The id the next added node will get, unless it is taken.
+    pub(crate) fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    fn generate_id(&mut self) -> u64 {
+        let id = self.next_id.max(1);
+        self.next_id = id + 1;
+        id
+    }
+
+    /// Adds a node with the given content and returns its freshly assigned id.
+    pub fn add_node(&mut self, content: NodeContent) -> u64 {
+        let node_id = self.generate_id();
+        self.nodes.insert(
+            node_id,
+            AsgNode {
+                node_id,
+                content,
+                metadata: None,
+            },
+        );
+        node_id
+    }
+
+    /// Inserts `node` under its own id, replacing and returning the node
+    /// that had the id before. Later [`AsgGraph::add_node`] calls never
+    /// reuse the id.
+    pub fn insert_node(&mut self, node: AsgNode) -> Option<AsgNode> {
+        self.next_id = self.next_id.max(node.node_id.saturating_add(1));
+        self.nodes.insert(node.node_id, node)
+    }
+
+    /// Returns the node with the given id.
+    pub fn get_node(&self, node_id: u64) -> Option<&AsgNode> {
+        self.nodes.get(&node_id)
+    }
+
+    /// Returns a mutable reference to the node with the given id.
+    pub fn get_node_mut(&mut self, node_id: u64) -> Option<&mut AsgNode> {
+        self.nodes.get_mut(&node_id)
+    }
+
+    /// Removes a node from the graph, returning it if it existed.
+    pub fn remove_node(&mut self, node_id: u64) -> Option<AsgNode> {
+        if self.root_node_id == Some(node_id) {
+            self.root_node_id = None;
+        }
+        self.nodes.remove(&node_id)
+    }
+
+    /// Removes a node only if no other node refers to it (see
+    /// [`AsgGraph::referrers`]). Otherwise the graph is left unchanged and
+    /// the referencing nodes are returned in id order.
+    pub fn remove_unreferenced_node(&mut self, node_id: u64) -> Result<Option<AsgNode>, Vec<u64>> {
+        let referrers = self.referrers(node_id);
+        if referrers.is_empty() {
+            Ok(self.remove_node(node_id))
+        } else {
+            Err(referrers)
+        }
+    }
+
+    /// The nodes other than `node_id` itself that have it as a structural
+    /// child or, for variables, as their binder, in id order.
+    pub fn referrers(&self, node_id: u64) -> Vec<u64> {
+        let mut referrers: Vec<u64> = self
+            .nodes
+            .values()
+            .filter(|node| {
+                node.node_id != node_id && reference_ids(&node.content).contains(&node_id)
+            })
+            .map(|node| node.node_id)
+            .collect();
+        referrers.sort_unstable();
+        referrers
+    }
+
+    /// Checks that every structural child id (see
+    /// [`NodeContent::child_ids`]) and every variable's binder names a node
+    /// of the graph. A free variable, with binder 0, refers to nothing. On
+    /// failure, returns the nodes holding a dangling reference, in id order.
+    pub fn validate(&self) -> Result<(), Vec<u64>> {
+        let mut dangling: Vec<u64> = self
+            .nodes
+            .values()
+            .filter(|node| {
+                reference_ids(&node.content)
+                    .iter()
+                    .any(|child| !self.nodes.contains_key(child))
+            })
+            .map(|node| node.node_id)
+            .collect();
+        if dangling.is_empty() {
+            return Ok(());
+        }
+        dangling.sort_unstable();
+        Err(dangling)
+    }
+
+    /// Sets the entry point of the graph.
+    pub fn set_root(&mut self, node_id: u64) {
+        self.root_node_id = Some(node_id);
+    }
+
+    /// The entry point of the graph, if one has been set.
+    pub fn root(&self) -> Option<u64> {
+        self.root_node_id
+    }
+
+    /// Iterates over all nodes in unspecified order.
+    pub fn nodes(&self) -> impl Iterator<Item = &AsgNode> {
+        self.nodes.values()
+    }
+
+    /// Number of nodes in the graph.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the graph has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Records the source location of a node, creating its metadata if needed.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_source_location(&mut self, node_id: u64, location: SourceLocation) -> bool {
+        match self.nodes.get_mut(&node_id) {
+            Some(node) => {
+                node.metadata
+                    .get_or_insert_with(Metadata::default)
+                    .source_location = Some(location);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the source location of a node, if known.
+    pub fn source_location(&self, node_id: u64) -> Option<&SourceLocation> {
+        self.nodes.get(&node_id)?.source_location()
+    }
+
+    /// Records the `TypeNode` holding a node's inferred type, creating its
+    /// metadata if needed.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_inferred_type(&mut self, node_id: u64, type_id: u64) -> bool {
+        match self.nodes.get_mut(&node_id) {
+            Some(node) => {
+                node.metadata
+                    .get_or_insert_with(Metadata::default)
+                    .inferred_type_id = Some(type_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the `TypeNode` holding a node's inferred type, if recorded.
+    pub fn inferred_type(&self, node_id: u64) -> Option<u64> {
+        self.nodes.get(&node_id)?.inferred_type_id()
+    }
+
+    /// Indexes which nodes refer to each node; see [`ReferenceIndex`].
+    pub fn reference_index(&self) -> ReferenceIndex {
+        ReferenceIndex::build(self)
+    }
+
+    /// All node ids in a deterministic topological order: every node comes
+    /// after its structural children, and ties are broken by smaller id.
+    ///
+    /// Nodes on a cycle (which a well-formed graph never has) are appended
+    /// in id order at the end.
+    pub fn topological_order(&self) -> Vec<u64> {
+        let mut pending_children: HashMap<u64, usize> = HashMap::new();
+        let mut parents: HashMap<u64, Vec<u64>> = HashMap::new();
+        for node in self.nodes.values() {
+            let mut children = node.content.child_ids();
+            children.sort_unstable();
+            children.dedup();
+            children.retain(|child| self.nodes.contains_key(child));
+            pending_children.insert(node.node_id, children.len());
+            for child in children {
+                parents.entry(child).or_default().push(node.node_id);
+            }
+        }
+
+        let mut ready: BinaryHeap<Reverse<u64>> = pending_children
+            .iter()
+            .filter(|&(_, &count)| count == 0)
+            .map(|(&node_id, _)| Reverse(node_id))
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(Reverse(node_id)) = ready.pop() {
+            order.push(node_id);
+            for parent in parents.get(&node_id).into_iter().flatten() {
+                let count = pending_children.get_mut(parent).expect("parent is a node");
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(Reverse(*parent));
+                }
+            }
+        }
+
+        if order.len() < self.nodes.len() {
+            let mut cyclic: Vec<u64> = pending_children
+                .into_iter()
+                .filter(|&(_, count)| count > 0)
+                .map(|(node_id, _)| node_id)
+                .collect();
+            cyclic.sort_unstable();
+            order.extend(cyclic);
+        }
+        order
+    }
+
+    /// Calls `f` once for every node, in [`AsgGraph::topological_order`], so
+    /// a node's children are visited before it. The order is fixed before
+    /// the first call; nodes `f` links in later are not visited.
+    pub fn map_nodes<F>(&mut self, f: F)
+    where
+        F: FnMut(u64, &mut AsgNode),
+    {
+        let order = self.topological_order();
+        self.visit_in_order(order, f);
+    }
+
+    /// Like [`AsgGraph::map_nodes`], but only visits nodes structurally
+    /// reachable from the root. Does nothing without a root.
+    pub fn transform_reachable<F>(&mut self, f: F)
+    where
+        F: FnMut(u64, &mut AsgNode),
+    {
+        let reachable = self.reachable_from_root();
+        let mut order = self.topological_order();
+        order.retain(|node_id| reachable.contains(node_id));
+        self.visit_in_order(order, f);
+    }
+
+    fn visit_in_order<F>(&mut self, order: Vec<u64>, mut f: F)
+    where
+        F: FnMut(u64, &mut AsgNode),
+    {
+        for node_id in order {
+            if let Some(node) = self.nodes.get_mut(&node_id) {
+                f(node_id, node);
+            }
+        }
+    }
+
+    /// Ids of the nodes structurally reachable from the root.
+    fn reachable_from_root(&self) -> HashSet<u64> {
+        let mut reachable = HashSet::new();
+        let mut pending: Vec<u64> = self.root_node_id.into_iter().collect();
+        while let Some(node_id) = pending.pop() {
+            if reachable.insert(node_id)
+                && let Some(node) = self.nodes.get(&node_id)
+            {
+                pending.extend(node.content.child_ids());
+            }
+        }
+        reachable
+    }
+
+    /// Removes every node not structurally reachable from the root and
+    /// returns how many were removed. The inferred types of the nodes kept
+    /// are kept too. A graph without a root is left as is.
+    pub fn collect_garbage(&mut self) -> usize {
+        if self.root_node_id.is_none() {
+            return 0;
+        }
+        let mut reachable = self.reachable_from_root();
+        let mut pending: Vec<u64> = reachable
+            .iter()
+            .filter_map(|&node_id| self.inferred_type(node_id))
+            .collect();
+        while let Some(node_id) = pending.pop() {
+            if reachable.insert(node_id)
+                && let Some(node) = self.nodes.get(&node_id)
+            {
+                pending.extend(node.content.child_ids());
+            }
+        }
+        let before = self.nodes.len();
+        self.nodes.retain(|node_id, _| reachable.contains(node_id));
+        before - self.nodes.len()
+    }
+
+    /// Deep-copies the nodes structurally reachable from `root` under fresh
+    /// ids and returns the id of the copy of `root`, with the map from each
+    /// copied id to its copy. A node shared within the subgraph is copied
+    /// once, so the copy shares it the same way.
+    ///
+    /// Child references, variable links and inferred types pointing into
+    /// the subgraph are rewritten through the map; those pointing outside
+    /// it, such as a use of an enclosing lambda, are kept as they are.
+    /// Metadata is copied along. If a reachable node is missing, the graph
+    /// is left unchanged.
+    pub fn clone_subgraph(&mut self, root: u64) -> Result<(u64, HashMap<u64, u64>), MissingNode> {
+        let mut order = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pending = vec![root];
+        while let Some(node_id) = pending.pop() {
+            if !seen.insert(node_id) {
+                continue;
+            }
+            let node = self.nodes.get(&node_id).ok_or(MissingNode(node_id))?;
+            order.push(node_id);
+            pending.extend(node.content.child_ids().into_iter().rev());
+        }
+
+        let ids: HashMap<u64, u64> = order.iter().map(|&old| (old, self.generate_id())).collect();
+        let remap = |id: u64| ids.get(&id).copied().unwrap_or(id);
+        for &old in &order {
+            let mut node = self.nodes[&old].clone();
+            node.node_id = ids[&old];
+            node.content.map_child_ids(remap);
+            if let NodeContent::TermVariable(var) = &mut node.content {
+                var.definition_node_id = remap(var.definition_node_id);
+            }
+            if let Some(type_id) = node
+                .metadata
+                .as_mut()
+                .and_then(|m| m.inferred_type_id.as_mut())
+            {
+                *type_id = remap(*type_id);
+            }
+            self.nodes.insert(node.node_id, node);
+        }
+        Ok((ids[&root], ids))
+    }
+}
+
+/// A node id that names no node of the graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingNode(pub u64);
+
+impl fmt::Display for MissingNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {} does not exist", self.0)
+    }
+}
+
+impl std::error::Error for MissingNode {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::LambdaBuilder;
+    use crate::nodes::{
+        LiteralInt, LiteralUnit, PrimitiveOp, TermLambda, TermVariable, TypeKind, TypeNode,
+    };
+
+    #[test]
+    fn node_creation_and_retrieval() {
+        let mut graph = AsgGraph::new();
+        let binder = graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: "x".to_string(),
+            definition_node_id: 0,
+        }));
+        let body = graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: "x".to_string(),
+            definition_node_id: 0,
+        }));
+        let lambda = graph.add_node(NodeContent::TermLambda(TermLambda {
+            binder_variable_node_id: binder,
+            body_node_id: body,
+            type_annotation_id: None,
+            effect_annotation: None,
+        }));
+        graph.set_root(lambda);
+
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph.root(), Some(lambda));
+        assert_eq!(
+            graph.get_node(lambda).unwrap().content.child_ids(),
+            vec![binder, body]
+        );
+
+        if let Some(NodeContent::TermVariable(var)) =
+            graph.get_node_mut(body).map(|node| &mut node.content)
+        {
+            var.definition_node_id = lambda;
+        }
+        match &graph.get_node(body).unwrap().content {
+            NodeContent::TermVariable(var) => assert_eq!(var.definition_node_id, lambda),
+            other => panic!("unexpected content {:?}", other),
+        }
+
+        let literal = AsgNode {
+            node_id: 10,
+            content: NodeContent::LiteralInt(LiteralInt { value: 1 }),
+            metadata: None,
+        };
+        assert_eq!(graph.insert_node(literal.clone()), None);
+        assert_eq!(graph.insert_node(literal), graph.get_node(10).cloned());
+        assert_eq!(graph.add_node(NodeContent::LiteralUnit(LiteralUnit)), 11);
+    }
+
+    #[test]
+    fn dangling_references_are_found_and_removal_can_be_guarded() {
+        let mut graph = AsgGraph::new();
+        let one = graph.int(1);
+        let two = graph.int(2);
+        let sum = graph.primitive("add", [one, two]);
+        let double = graph.primitive("add", [sum, sum]);
+        assert_eq!(graph.validate(), Ok(()));
+
+        assert_eq!(graph.referrers(sum), [double]);
+        assert_eq!(graph.remove_unreferenced_node(sum), Err(vec![double]));
+        assert_eq!(graph.len(), 4);
+
+        graph.remove_node(one);
+        graph.remove_node(sum);
+        assert_eq!(graph.validate(), Err(vec![double]));
+        assert!(graph.remove_unreferenced_node(double).unwrap().is_some());
+        assert_eq!(graph.remove_unreferenced_node(double), Ok(None));
+        assert_eq!(graph.validate(), Ok(()));
+    }
+
+    #[test]
+    fn variables_keep_their_binding_lambda_referenced() {
+        let mut graph = AsgGraph::new();
+        let mut scope = LambdaBuilder::new(&mut graph, "x".to_string());
+        let x = scope.reference(&mut graph);
+        let lambda = scope.finish(&mut graph, x);
+        graph.set_root(lambda);
+        let binder = match &graph.get_node(lambda).unwrap().content {
+            NodeContent::TermLambda(lambda) => lambda.binder_variable_node_id,
+            other => panic!("unexpected content {:?}", other),
+        };
+        assert_eq!(graph.validate(), Ok(()));
+
+        // The root lambda is no node's child, but its variables name it.
+        let mut expected = vec![binder, x];
+        expected.sort_unstable();
+        assert_eq!(
+            graph.remove_unreferenced_node(lambda),
+            Err(expected.clone())
+        );
+        assert_eq!(graph.root(), Some(lambda));
+
+        graph.remove_node(lambda);
+        assert_eq!(graph.validate(), Err(expected));
+    }
+
+    #[test]
+    fn source_locations_are_stored_in_metadata() {
+        let mut graph = AsgGraph::new();
+        let lit = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+        let op = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "add".to_string(),
+            argument_node_ids: vec![lit, lit],
+        }));
+        let location = SourceLocation {
+            filename: "main.syn".to_string(),
+            start_line: 2,
+            start_col: 5,
+            end_line: 2,
+            end_col: 10,
+        };
+
+        assert!(graph.set_source_location(op, location.clone()));
+        assert!(!graph.set_source_location(999, location.clone()));
+        assert_eq!(graph.source_location(op), Some(&location));
+        assert_eq!(graph.source_location(lit), None);
+    }
+
+    #[test]
+    fn garbage_collection_keeps_only_reachable_nodes() {
+        let mut graph = AsgGraph::new();
+        let kept = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+        let orphan = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 2 }));
+        let op = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "not".to_string(),
+            argument_node_ids: vec![kept],
+        }));
+        assert_eq!(graph.collect_garbage(), 0);
+
+        graph.set_root(op);
+        assert_eq!(graph.collect_garbage(), 1);
+        assert!(graph.get_node(orphan).is_none());
+        assert_eq!(graph.len(), 2);
+    }
+
+    #[test]
+    fn topological_order_puts_children_first_then_smaller_ids() {
+        let mut graph = AsgGraph::new();
+        let op = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "add".to_string(),
+            argument_node_ids: vec![3, 2],
+        }));
+        let lhs = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+        let rhs = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 2 }));
+        let other = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 3 }));
+
+        assert_eq!(graph.topological_order(), vec![lhs, rhs, op, other]);
+    }
+
+    fn literal_value(graph: &AsgGraph, node_id: u64) -> i64 {
+        match graph.get_node(node_id).unwrap().content {
+            NodeContent::LiteralInt(LiteralInt { value }) => value,
+            ref other => panic!("expected a literal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn map_nodes_visits_shared_nodes_exactly_once() {
+        // `shared` is an operand of both additions.
+        let mut graph = AsgGraph::new();
+        let shared = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+        let inner = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "add".to_string(),
+            argument_node_ids: vec![shared, shared],
+        }));
+        let outer = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "add".to_string(),
+            argument_node_ids: vec![inner, shared],
+        }));
+        let orphan = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 10 }));
+        graph.set_root(outer);
+
+        let increment = |_: u64, node: &mut AsgNode| {
+            if let NodeContent::LiteralInt(lit) = &mut node.content {
+                lit.value += 1;
+            }
+        };
+        let mut visited = Vec::new();
+        graph.map_nodes(|node_id, node| {
+            visited.push(node_id);
+            increment(node_id, node);
+        });
+        assert_eq!(visited, vec![shared, inner, outer, orphan]);
+        assert_eq!(literal_value(&graph, shared), 2);
+        assert_eq!(literal_value(&graph, orphan), 11);
+
+        graph.transform_reachable(increment);
+        assert_eq!(literal_value(&graph, shared), 3);
+        assert_eq!(literal_value(&graph, orphan), 11);
+    }
+
+    #[test]
+    fn removing_root_clears_entry_point() {
+        let mut graph = AsgGraph::new();
+        let lit = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 7 }));
+        graph.set_root(lit);
+        assert!(graph.remove_node(lit).is_some());
+        assert_eq!(graph.root(), None);
+        assert!(graph.is_empty());
+    }
+
+    #[test]
+    fn garbage_collection_keeps_inferred_types() {
+        let mut graph = AsgGraph::new();
+        let int = graph.add_node(NodeContent::TypeNode(TypeNode {
+            kind: TypeKind::Int,
+        }));
+        let unused = graph.add_node(NodeContent::TypeNode(TypeNode {
+            kind: TypeKind::Bool,
+        }));
+        let lit = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 7 }));
+        graph.set_root(lit);
+        assert!(graph.set_inferred_type(lit, int));
+        assert!(!graph.set_inferred_type(99, int));
+
+        assert_eq!(graph.collect_garbage(), 1);
+        assert_eq!(graph.inferred_type(lit), Some(int));
+        assert!(graph.get_node(int).is_some());
+        assert!(graph.get_node(unused).is_none());
+    }
+
+    #[test]
+    fn cloned_subgraphs_keep_external_links_and_sharing() {
+        let mut graph = AsgGraph::new();
+        let mut inner = 0;
+        let mut sum = 0;
+        // (x) => (y) => (x + y) * (x + y), with the sum shared.
+        let outer = graph.lambda("x", |graph, x| {
+            inner = graph.lambda("y", |graph, y| {
+                let x = x.reference(graph);
+                let y = y.reference(graph);
+                sum = graph.primitive("add", [x, y]);
+                graph.primitive("mul", [sum, sum])
+            });
+            inner
+        });
+        let location = SourceLocation {
+            filename: "main.syn".to_string(),
+            start_line: 1,
+            start_col: 15,
+            end_line: 1,
+            end_col: 20,
+        };
+        graph.set_source_location(sum, location.clone());
+        let before = graph.len();
+
+        let (copy, ids) = graph.clone_subgraph(inner).unwrap();
+        assert_eq!(ids[&inner], copy);
+        assert_eq!(ids.len(), 6);
+        assert_eq!(graph.len(), before + 6);
+        assert!(graph.validate().is_ok());
+        assert!(ids.values().all(|new| !ids.contains_key(new)));
+
+        let NodeContent::PrimitiveOp(add) = &graph.get_node(ids[&sum]).unwrap().content else {
+            panic!("expected the copied sum");
+        };
+        let [x_use, y_use] = add.argument_node_ids[..] else {
+            panic!("expected two operands");
+        };
+        let link = |id| match &graph.get_node(id).unwrap().content {
+            NodeContent::TermVariable(var) => var.definition_node_id,
+            other => panic!("expected a variable, found {:?}", other),
+        };
+        assert_eq!(link(x_use), outer);
+        assert_eq!(link(y_use), copy);
+        assert_eq!(graph.source_location(ids[&sum]), Some(&location));
+        assert_eq!(
+            crate::scope::free_variables(&graph, copy),
+            HashSet::from([outer])
+        );
+
+        let dangling = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "neg".to_string(),
+            argument_node_ids: vec![99],
+        }));
+        let before = graph.len();
+        assert_eq!(graph.clone_subgraph(dangling), Err(MissingNode(99)));
+        assert_eq!(graph.len(), before);
+    }
+}
@@ -0,0 +1,241 @@
+//! Structural hashing of terms.
+//!
+//! Two terms hash alike when they have the same shape, whatever their node
+//! ids. A variable is hashed by its name and by which enclosing lambda binds
+//! it, counted outward, so terms that differ only in how a name resolves
+//! hash differently.
+
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
+use crate::graph::AsgGraph;
+use crate::nodes::{NodeContent, TypeKind};
+
+/// The structural hash of the term rooted at `root_id`. Missing nodes and
+/// back edges of cycles hash as fixed markers rather than failing.
+///
+/// The hash is only meant for comparing terms within one process: its
+/// values may change between Rust releases. Use [`content_digest`] for
+/// anything stored or sent elsewhere.
+pub fn hash_graph(graph: &AsgGraph, root_id: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    Encoder::new(graph, &mut hasher).node(root_id);
+    hasher.finish()
+}
+
+/// The BLAKE3 digest of the term rooted at `root_id`, over the same
+/// id-independent encoding [`hash_graph`] hashes. Unlike that hash it is
+/// collision resistant and stable, so it can be stored, e.g. in lock files.
+pub fn content_digest(graph: &AsgGraph, root_id: u64) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    Encoder::new(graph, &mut hasher).node(root_id);
+    *hasher.finalize().as_bytes()
+}
+
+/// Where the encoding of a term is written.
+trait Sink {
+    fn bytes(&mut self, bytes: &[u8]);
+}
+
+impl Sink for DefaultHasher {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.write(bytes);
+    }
+}
+
+impl Sink for blake3::Hasher {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+}
+
+/// Writes a term as a canonical byte string: integers little-endian,
+/// strings and lists prefixed with their length, and node kinds by name,
+/// so the encoding depends on neither node ids nor the compiler.
+struct Encoder<'g, 's, S> {
+    graph: &'g AsgGraph,
+    sink: &'s mut S,
+    /// Lambdas enclosing the current node, innermost last.
+    binders: Vec<u64>,
+    visiting: HashSet<u64>,
+}
+
+impl<'g, 's, S: Sink> Encoder<'g, 's, S> {
+    fn new(graph: &'g AsgGraph, sink: &'s mut S) -> Self {
+        Self {
+            graph,
+            sink,
+            binders: Vec::new(),
+            visiting: HashSet::new(),
+        }
+    }
+
+    fn byte(&mut self, value: u8) {
+        self.sink.bytes(&[value]);
+    }
+
+    fn int(&mut self, value: u64) {
+        self.sink.bytes(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.int(value.len() as u64);
+        self.sink.bytes(value.as_bytes());
+    }
+
+    fn node(&mut self, node_id: u64) {
+        let Some(node) = self.graph.get_node(node_id) else {
+            self.str("missing");
+            return;
+        };
+        if !self.visiting.insert(node_id) {
+            self.str("cycle");
+            return;
+        }
+        let content = &node.content;
+        self.str(content.kind().name());
+        match content {
+            NodeContent::TermVariable(var) => {
+                self.str(&var.name);
+                let depth = self
+                    .binders
+                    .iter()
+                    .rev()
+                    .position(|&lambda| lambda == var.definition_node_id);
+                match depth {
+                    Some(depth) => {
+                        self.byte(1);
+                        self.int(depth as u64);
+                    }
+                    None => self.byte(0),
+                }
+            }
+            NodeContent::TermLambda(lambda) => {
+                match self.graph.get_node(lambda.binder_variable_node_id) {
+                    Some(binder) => match &binder.content {
+                        NodeContent::TermVariable(var) => {
+                            self.byte(0);
+                            self.str(&var.name);
+                        }
+                        _ => self.byte(1),
+                    },
+                    None => self.byte(2),
+                }
+                match lambda.type_annotation_id {
+                    Some(annotation) => {
+                        self.byte(1);
+                        self.node(annotation);
+                    }
+                    None => self.byte(0),
+                }
+                match &lambda.effect_annotation {
+                    Some(effects) => {
+                        self.byte(1);
+                        self.int(effects.len() as u64);
+                        for effect in effects {
+                            self.str(effect);
+                        }
+                    }
+                    None => self.byte(0),
+                }
+                self.binders.push(node_id);
+                self.node(lambda.body_node_id);
+                self.binders.pop();
+            }
+            other => {
+                match other {
+                    NodeContent::LiteralInt(lit) => self.int(lit.value as u64),
+                    NodeContent::LiteralBool(lit) => self.byte(u8::from(lit.value)),
+                    NodeContent::PrimitiveOp(op) => self.str(&op.op_name),
+                    NodeContent::EffectPerform(perform) => self.str(&perform.effect_name),
+                    NodeContent::TermMacroDefinition(definition) => self.str(&definition.name),
+                    NodeContent::TermMacroInvocation(invocation) => {
+                        self.str(&invocation.macro_name)
+                    }
+                    NodeContent::TypeNode(ty) => self.byte(match ty.kind {
+                        TypeKind::Int => 0,
+                        TypeKind::Bool => 1,
+                        TypeKind::Unit => 2,
+                        TypeKind::Function { .. } => 3,
+                        TypeKind::Ref { .. } => 4,
+                    }),
+                    _ => {}
+                }
+                let children = other.child_ids();
+                self.int(children.len() as u64);
+                for child in children {
+                    self.node(child);
+                }
+            }
+        }
+        self.visiting.remove(&node_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::LambdaBuilder;
+    use crate::nodes::{LiteralInt, PrimitiveOp};
+
+    /// `(x) => (y) => x + <literal>`, with `extra` unrelated nodes first so
+    /// ids differ between copies.
+    fn sample(extra: usize, use_outer: bool, literal: i64) -> (AsgGraph, u64) {
+        let mut graph = AsgGraph::new();
+        for _ in 0..extra {
+            graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 0 }));
+        }
+        let root = LambdaBuilder::build(&mut graph, "x", |graph, x| {
+            LambdaBuilder::build(graph, "y", |graph, y| {
+                let var = if use_outer {
+                    x.reference(graph)
+                } else {
+                    y.reference(graph)
+                };
+                let literal =
+                    graph.add_node(NodeContent::LiteralInt(LiteralInt { value: literal }));
+                graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+                    op_name: "add".to_string(),
+                    argument_node_ids: vec![var, literal],
+                }))
+            })
+        });
+        (graph, root)
+    }
+
+    #[test]
+    fn equal_shapes_hash_alike_regardless_of_ids() {
+        let (a, a_root) = sample(0, true, 1);
+        let (b, b_root) = sample(7, true, 1);
+        assert_eq!(hash_graph(&a, a_root), hash_graph(&b, b_root));
+
+        let (c, c_root) = sample(0, true, 2);
+        assert_ne!(hash_graph(&a, a_root), hash_graph(&c, c_root));
+        let (d, d_root) = sample(0, false, 1);
+        assert_ne!(hash_graph(&a, a_root), hash_graph(&d, d_root));
+    }
+
+    #[test]
+    fn content_digest_is_id_independent_and_stable() {
+        let (a, a_root) = sample(0, true, 1);
+        let (b, b_root) = sample(7, true, 1);
+        assert_eq!(content_digest(&a, a_root), content_digest(&b, b_root));
+        let (c, c_root) = sample(0, true, 2);
+        assert_ne!(content_digest(&a, a_root), content_digest(&c, c_root));
+
+        // Pinned, so a change to the encoding is noticed: it would
+        // invalidate every stored digest.
+        let mut graph = AsgGraph::new();
+        let root = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+        let mut expected = blake3::Hasher::new();
+        expected.update(&10u64.to_le_bytes());
+        expected.update(b"LiteralInt");
+        expected.update(&1u64.to_le_bytes());
+        expected.update(&0u64.to_le_bytes());
+        assert_eq!(
+            content_digest(&graph, root),
+            *expected.finalize().as_bytes()
+        );
+    }
+}
@@ -1,14 +1,33 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Core Abstract Semantic Graph (ASG) library for Synapse.
+//!
+//! The ASG is the canonical representation of Synapse programs. Nodes live in
+//! a flat map keyed by `u64` ids and refer to each other by id, so the same
+//! structure can be projected into several concrete syntaxes and manipulated
+//! by tools without re-parsing.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod binary;
+pub mod builder;
+pub mod effects;
+pub mod graph;
+pub mod hash;
+pub mod nodes;
+pub mod references;
+pub mod scope;
+pub mod simplify;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use binary::{
+    BinaryError, FORMAT_VERSION, decode_asg, decode_nodes, encode_asg, encode_nodes,
+    load_asg_binary, save_asg_binary,
+};
+pub use builder::LambdaBuilder;
+pub use effects::{EffectName, UnknownEffect, parse_effect_name};
+pub use graph::{AsgGraph, MissingNode};
+pub use hash::{content_digest, hash_graph};
+pub use nodes::{
+    AsgNode, EffectPerform, LiteralBool, LiteralInt, LiteralUnit, Metadata, NodeContent, NodeKind,
+    PrimitiveOp, SourceLocation, TermApplication, TermAssign, TermDeref, TermLambda,
+    TermMacroDefinition, TermMacroInvocation, TermRef, TermVariable, TypeKind, TypeNode,
+};
+pub use references::ReferenceIndex;
+pub use scope::{free_variables, is_closed};
+pub use simplify::simplify;
@@ -0,0 +1,511 @@
+//! Node definitions for the ASG.
+//!
+//! Each term and type construct of the core semantics has a dedicated content
+//! struct. Edges between nodes are stored as node ids.
+
+use std::fmt;
+
+/// A span in a source file. Lines and columns are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub filename: String,
+    pub start_line: u32,
+    pub start_col: u32,
+    pub end_line: u32,
+    pub end_col: u32,
+}
+
+/// Auxiliary information attached to a node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Where the node came from in the source text, if known.
+    pub source_location: Option<SourceLocation>,
+    /// The `TypeNode` holding the type inferred for the node, once the graph
+    /// has been type checked.
+    pub inferred_type_id: Option<u64>,
+}
+
+/// A variable occurrence, linked to the node that binds it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermVariable {
+    pub name: String,
+    /// Id of the binding site (e.g. the lambda introducing the variable).
+    pub definition_node_id: u64,
+}
+
+/// A single-parameter function abstraction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermLambda {
+    /// The `TermVariable` node naming the parameter.
+    pub binder_variable_node_id: u64,
+    pub body_node_id: u64,
+    /// Optional `TypeNode` annotating the parameter type.
+    pub type_annotation_id: Option<u64>,
+    /// Effects the body may perform, from a `with [..]` annotation.
+    pub effect_annotation: Option<Vec<String>>,
+}
+
+/// Function application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermApplication {
+    pub function_node_id: u64,
+    pub argument_node_id: u64,
+}
+
+/// An integer constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiteralInt {
+    pub value: i64,
+}
+
+/// A boolean constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiteralBool {
+    pub value: bool,
+}
+
+/// The unit value `()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LiteralUnit;
+
+/// A built-in operation such as `add` or `eq`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimitiveOp {
+    pub op_name: String,
+    pub argument_node_ids: Vec<u64>,
+}
+
+/// Reference cell creation (`ref t`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermRef {
+    pub init_value_node_id: u64,
+}
+
+/// Reference dereference (`!t`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermDeref {
+    pub ref_node_id: u64,
+}
+
+/// Reference assignment (`t1 := t2`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermAssign {
+    pub ref_node_id: u64,
+    pub value_node_id: u64,
+}
+
+/// Performing an effect with a payload value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectPerform {
+    pub effect_name: String,
+    pub value_node_id: u64,
+}
+
+/// A function macro, expanded away before type checking.
+///
+/// Its parameters are `TermVariable` nodes bound to the definition, as are
+/// their uses in the body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermMacroDefinition {
+    pub name: String,
+    pub parameter_node_ids: Vec<u64>,
+    pub body_node_id: u64,
+}
+
+/// A use of a function macro, replaced by its expansion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermMacroInvocation {
+    pub macro_name: String,
+    pub argument_node_ids: Vec<u64>,
+}
+
+/// The shape of a type node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeKind {
+    Int,
+    Bool,
+    Unit,
+    Function {
+        param_type_id: u64,
+        return_type_id: u64,
+    },
+    Ref {
+        element_type_id: u64,
+    },
+}
+
+/// A type expression, used for annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeNode {
+    pub kind: TypeKind,
+}
+
+/// The kind of an ASG node, without its payload.
+///
+/// Use this to ask what a node is rather than matching on [`NodeContent`]
+/// with wildcard patterns; its names are stable and match the variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum NodeKind {
+    TermVariable,
+    TermLambda,
+    TermApplication,
+    LiteralInt,
+    LiteralBool,
+    LiteralUnit,
+    PrimitiveOp,
+    TermRef,
+    TermDeref,
+    TermAssign,
+    EffectPerform,
+    TermMacroDefinition,
+    TermMacroInvocation,
+    TypeNode,
+}
+
+impl NodeKind {
+    /// Every kind, in declaration order.
+    pub const ALL: [NodeKind; 14] = [
+        NodeKind::TermVariable,
+        NodeKind::TermLambda,
+        NodeKind::TermApplication,
+        NodeKind::LiteralInt,
+        NodeKind::LiteralBool,
+        NodeKind::LiteralUnit,
+        NodeKind::PrimitiveOp,
+        NodeKind::TermRef,
+        NodeKind::TermDeref,
+        NodeKind::TermAssign,
+        NodeKind::EffectPerform,
+        NodeKind::TermMacroDefinition,
+        NodeKind::TermMacroInvocation,
+        NodeKind::TypeNode,
+    ];
+
+    /// The kind's name, e.g. `"TermLambda"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            NodeKind::TermVariable => "TermVariable",
+            NodeKind::TermLambda => "TermLambda",
+            NodeKind::TermApplication => "TermApplication",
+            NodeKind::LiteralInt => "LiteralInt",
+            NodeKind::LiteralBool => "LiteralBool",
+            NodeKind::LiteralUnit => "LiteralUnit",
+            NodeKind::PrimitiveOp => "PrimitiveOp",
+            NodeKind::TermRef => "TermRef",
+            NodeKind::TermDeref => "TermDeref",
+            NodeKind::TermAssign => "TermAssign",
+            NodeKind::EffectPerform => "EffectPerform",
+            NodeKind::TermMacroDefinition => "TermMacroDefinition",
+            NodeKind::TermMacroInvocation => "TermMacroInvocation",
+            NodeKind::TypeNode => "TypeNode",
+        }
+    }
+
+    /// Whether nodes of this kind are terms, i.e. anything but a type.
+    pub fn is_term(self) -> bool {
+        self != NodeKind::TypeNode
+    }
+}
+
+impl fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The content of an ASG node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeContent {
+    TermVariable(TermVariable),
+    TermLambda(TermLambda),
+    TermApplication(TermApplication),
+    LiteralInt(LiteralInt),
+    LiteralBool(LiteralBool),
+    LiteralUnit(LiteralUnit),
+    PrimitiveOp(PrimitiveOp),
+    TermRef(TermRef),
+    TermDeref(TermDeref),
+    TermAssign(TermAssign),
+    EffectPerform(EffectPerform),
+    TermMacroDefinition(TermMacroDefinition),
+    TermMacroInvocation(TermMacroInvocation),
+    TypeNode(TypeNode),
+}
+
+macro_rules! content_from {
+    ($($variant:ident),* $(,)?) => {
+        $(
+            impl From<$variant> for NodeContent {
+                fn from(content: $variant) -> Self {
+                    NodeContent::$variant(content)
+                }
+            }
+        )*
+    };
+}
+
+content_from!(
+    TermVariable,
+    TermLambda,
+    TermApplication,
+    LiteralInt,
+    LiteralBool,
+    LiteralUnit,
+    PrimitiveOp,
+    TermRef,
+    TermDeref,
+    TermAssign,
+    EffectPerform,
+    TermMacroDefinition,
+    TermMacroInvocation,
+    TypeNode,
+);
+
+impl NodeContent {
+    pub fn kind(&self) -> NodeKind {
+        match self {
+            NodeContent::TermVariable(_) => NodeKind::TermVariable,
+            NodeContent::TermLambda(_) => NodeKind::TermLambda,
+            NodeContent::TermApplication(_) => NodeKind::TermApplication,
+            NodeContent::LiteralInt(_) => NodeKind::LiteralInt,
+            NodeContent::LiteralBool(_) => NodeKind::LiteralBool,
+            NodeContent::LiteralUnit(_) => NodeKind::LiteralUnit,
+            NodeContent::PrimitiveOp(_) => NodeKind::PrimitiveOp,
+            NodeContent::TermRef(_) => NodeKind::TermRef,
+            NodeContent::TermDeref(_) => NodeKind::TermDeref,
+            NodeContent::TermAssign(_) => NodeKind::TermAssign,
+            NodeContent::EffectPerform(_) => NodeKind::EffectPerform,
+            NodeContent::TermMacroDefinition(_) => NodeKind::TermMacroDefinition,
+            NodeContent::TermMacroInvocation(_) => NodeKind::TermMacroInvocation,
+            NodeContent::TypeNode(_) => NodeKind::TypeNode,
+        }
+    }
+
+    /// Ids of the nodes this content structurally contains, in source order.
+    ///
+    /// A variable's `definition_node_id` is a back-reference to its binder and
+    /// is deliberately not reported as a child.
+    pub fn child_ids(&self) -> Vec<u64> {
+        match self {
+            NodeContent::TermVariable(_)
+            | NodeContent::LiteralInt(_)
+            | NodeContent::LiteralBool(_)
+            | NodeContent::LiteralUnit(_) => Vec::new(),
+            NodeContent::TermLambda(lambda) => {
+                let mut children = vec![lambda.binder_variable_node_id];
+                children.extend(lambda.type_annotation_id);
+                children.push(lambda.body_node_id);
+                children
+            }
+            NodeContent::TermApplication(app) => {
+                vec![app.function_node_id, app.argument_node_id]
+            }
+            NodeContent::PrimitiveOp(op) => op.argument_node_ids.clone(),
+            NodeContent::TermRef(term) => vec![term.init_value_node_id],
+            NodeContent::TermDeref(term) => vec![term.ref_node_id],
+            NodeContent::TermAssign(term) => vec![term.ref_node_id, term.value_node_id],
+            NodeContent::EffectPerform(perform) => vec![perform.value_node_id],
+            NodeContent::TermMacroDefinition(definition) => {
+                let mut children = definition.parameter_node_ids.clone();
+                children.push(definition.body_node_id);
+                children
+            }
+            NodeContent::TermMacroInvocation(invocation) => invocation.argument_node_ids.clone(),
+            NodeContent::TypeNode(ty) => match ty.kind {
+                TypeKind::Int | TypeKind::Bool | TypeKind::Unit => Vec::new(),
+                TypeKind::Function {
+                    param_type_id,
+                    return_type_id,
+                } => vec![param_type_id, return_type_id],
+                TypeKind::Ref { element_type_id } => vec![element_type_id],
+            },
+        }
+    }
+
+    /// Rewrites every structural child id through `f`, in place.
+    ///
+    /// Visits exactly the ids reported by [`NodeContent::child_ids`].
+    pub fn map_child_ids(&mut self, mut f: impl FnMut(u64) -> u64) {
+        match self {
+            NodeContent::TermVariable(_)
+            | NodeContent::LiteralInt(_)
+            | NodeContent::LiteralBool(_)
+            | NodeContent::LiteralUnit(_) => {}
+            NodeContent::TermLambda(lambda) => {
+                lambda.binder_variable_node_id = f(lambda.binder_variable_node_id);
+                lambda.type_annotation_id = lambda.type_annotation_id.map(&mut f);
+                lambda.body_node_id = f(lambda.body_node_id);
+            }
+            NodeContent::TermApplication(app) => {
+                app.function_node_id = f(app.function_node_id);
+                app.argument_node_id = f(app.argument_node_id);
+            }
+            NodeContent::PrimitiveOp(op) => {
+                for arg in &mut op.argument_node_ids {
+                    *arg = f(*arg);
+                }
+            }
+            NodeContent::TermRef(term) => term.init_value_node_id = f(term.init_value_node_id),
+            NodeContent::TermDeref(term) => term.ref_node_id = f(term.ref_node_id),
+            NodeContent::TermAssign(term) => {
+                term.ref_node_id = f(term.ref_node_id);
+                term.value_node_id = f(term.value_node_id);
+            }
+            NodeContent::EffectPerform(perform) => perform.value_node_id = f(perform.value_node_id),
+            NodeContent::TermMacroDefinition(definition) => {
+                for param in &mut definition.parameter_node_ids {
+                    *param = f(*param);
+                }
+                definition.body_node_id = f(definition.body_node_id);
+            }
+            NodeContent::TermMacroInvocation(invocation) => {
+                for arg in &mut invocation.argument_node_ids {
+                    *arg = f(*arg);
+                }
+            }
+            NodeContent::TypeNode(ty) => match &mut ty.kind {
+                TypeKind::Int | TypeKind::Bool | TypeKind::Unit => {}
+                TypeKind::Function {
+                    param_type_id,
+                    return_type_id,
+                } => {
+                    *param_type_id = f(*param_type_id);
+                    *return_type_id = f(*return_type_id);
+                }
+                TypeKind::Ref { element_type_id } => *element_type_id = f(*element_type_id),
+            },
+        }
+    }
+}
+
+/// A node in the ASG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsgNode {
+    pub node_id: u64,
+    pub content: NodeContent,
+    pub metadata: Option<Metadata>,
+}
+
+impl AsgNode {
+    pub fn kind(&self) -> NodeKind {
+        self.content.kind()
+    }
+
+    /// Source location recorded in the node's metadata, if any.
+    pub fn source_location(&self) -> Option<&SourceLocation> {
+        self.metadata.as_ref()?.source_location.as_ref()
+    }
+
+    /// The inferred type recorded in the node's metadata, if any.
+    pub fn inferred_type_id(&self) -> Option<u64> {
+        self.metadata.as_ref()?.inferred_type_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::AsgGraph;
+
+    #[test]
+    fn every_node_reports_its_kind() {
+        let samples = [
+            (
+                NodeContent::TermVariable(TermVariable {
+                    name: "x".to_string(),
+                    definition_node_id: 0,
+                }),
+                NodeKind::TermVariable,
+            ),
+            (
+                NodeContent::TermLambda(TermLambda {
+                    binder_variable_node_id: 1,
+                    body_node_id: 1,
+                    type_annotation_id: None,
+                    effect_annotation: None,
+                }),
+                NodeKind::TermLambda,
+            ),
+            (
+                NodeContent::TermApplication(TermApplication {
+                    function_node_id: 1,
+                    argument_node_id: 1,
+                }),
+                NodeKind::TermApplication,
+            ),
+            (
+                NodeContent::LiteralInt(LiteralInt { value: 1 }),
+                NodeKind::LiteralInt,
+            ),
+            (
+                NodeContent::LiteralBool(LiteralBool { value: true }),
+                NodeKind::LiteralBool,
+            ),
+            (NodeContent::LiteralUnit(LiteralUnit), NodeKind::LiteralUnit),
+            (
+                NodeContent::PrimitiveOp(PrimitiveOp {
+                    op_name: "not".to_string(),
+                    argument_node_ids: vec![1],
+                }),
+                NodeKind::PrimitiveOp,
+            ),
+            (
+                NodeContent::TermRef(TermRef {
+                    init_value_node_id: 1,
+                }),
+                NodeKind::TermRef,
+            ),
+            (
+                NodeContent::TermDeref(TermDeref { ref_node_id: 1 }),
+                NodeKind::TermDeref,
+            ),
+            (
+                NodeContent::TermAssign(TermAssign {
+                    ref_node_id: 1,
+                    value_node_id: 1,
+                }),
+                NodeKind::TermAssign,
+            ),
+            (
+                NodeContent::EffectPerform(EffectPerform {
+                    effect_name: "IO".to_string(),
+                    value_node_id: 1,
+                }),
+                NodeKind::EffectPerform,
+            ),
+            (
+                TermMacroDefinition {
+                    name: "twice".to_string(),
+                    parameter_node_ids: vec![1],
+                    body_node_id: 1,
+                }
+                .into(),
+                NodeKind::TermMacroDefinition,
+            ),
+            (
+                TermMacroInvocation {
+                    macro_name: "twice".to_string(),
+                    argument_node_ids: vec![1],
+                }
+                .into(),
+                NodeKind::TermMacroInvocation,
+            ),
+            (
+                NodeContent::TypeNode(TypeNode {
+                    kind: TypeKind::Int,
+                }),
+                NodeKind::TypeNode,
+            ),
+        ];
+
+        let mut graph = AsgGraph::new();
+        for (content, kind) in &samples {
+            let node_id = graph.add_node(content.clone());
+            assert_eq!(graph.get_node(node_id).unwrap().kind(), *kind);
+            assert_eq!(kind.to_string(), kind.name());
+        }
+        let reported: Vec<_> = samples.iter().map(|(content, _)| content.kind()).collect();
+        assert_eq!(reported, NodeKind::ALL);
+        assert!(!NodeKind::TypeNode.is_term());
+    }
+}
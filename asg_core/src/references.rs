@@ -0,0 +1,135 @@
+//! Reverse references: which nodes refer to a node.
+//!
+//! A node refers to its structural children (see [`NodeContent::child_ids`])
+//! and, if it is a bound variable, to the lambda named by its
+//! `definition_node_id`. [`ReferenceIndex`] inverts both in one pass, so
+//! questions such as "is this parameter ever used?" do not rescan the graph.
+
+use std::collections::HashMap;
+
+use crate::graph::AsgGraph;
+use crate::nodes::NodeContent;
+
+/// The ids `content` refers to: its structural children, followed by the
+/// binder of a bound variable. A free variable, with binder 0, names none.
+pub(crate) fn reference_ids(content: &NodeContent) -> Vec<u64> {
+    let mut ids = content.child_ids();
+    if let NodeContent::TermVariable(var) = content
+        && var.definition_node_id != 0
+    {
+        ids.push(var.definition_node_id);
+    }
+    ids
+}
+
+/// For every node, the nodes referring to it, and for every lambda, the
+/// variables using its parameter. Built from a snapshot of the graph; it is
+/// not updated when the graph changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReferenceIndex {
+    referrers: HashMap<u64, Vec<u64>>,
+    uses: HashMap<u64, Vec<u64>>,
+}
+
+impl ReferenceIndex {
+    /// Indexes every reference in `graph`. Lists are in id order.
+    pub fn build(graph: &AsgGraph) -> Self {
+        let mut index = Self::default();
+        for node in graph.nodes() {
+            if let NodeContent::TermLambda(_) = &node.content {
+                index.uses.entry(node.node_id).or_default();
+            }
+        }
+        for node in graph.nodes() {
+            let mut targets = reference_ids(&node.content);
+            targets.sort_unstable();
+            targets.dedup();
+            for target in targets {
+                if target != node.node_id {
+                    index
+                        .referrers
+                        .entry(target)
+                        .or_default()
+                        .push(node.node_id);
+                }
+            }
+            if let NodeContent::TermVariable(var) = &node.content
+                && !is_binder_of(graph, var.definition_node_id, node.node_id)
+                && let Some(uses) = index.uses.get_mut(&var.definition_node_id)
+            {
+                uses.push(node.node_id);
+            }
+        }
+        for list in index.referrers.values_mut().chain(index.uses.values_mut()) {
+            list.sort_unstable();
+        }
+        index
+    }
+
+    /// The nodes other than `node_id` that refer to it.
+    pub fn referrers(&self, node_id: u64) -> &[u64] {
+        self.referrers.get(&node_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The variables referring to the parameter of lambda `lambda_id`. The
+    /// lambda's own binder is not a use. Empty for anything but a lambda.
+    pub fn uses_of(&self, lambda_id: u64) -> &[u64] {
+        self.uses.get(&lambda_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The lambdas whose parameter is never used, in id order.
+    pub fn unused_lambdas(&self) -> Vec<u64> {
+        let mut unused: Vec<u64> = self
+            .uses
+            .iter()
+            .filter(|(_, uses)| uses.is_empty())
+            .map(|(&lambda_id, _)| lambda_id)
+            .collect();
+        unused.sort_unstable();
+        unused
+    }
+}
+
+/// Whether `variable_id` is the binder of lambda `lambda_id`.
+fn is_binder_of(graph: &AsgGraph, lambda_id: u64, variable_id: u64) -> bool {
+    matches!(
+        graph.get_node(lambda_id).map(|node| &node.content),
+        Some(NodeContent::TermLambda(lambda)) if lambda.binder_variable_node_id == variable_id
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters_map_to_their_uses() {
+        // (f) => (x) => f(1), with `x` unused.
+        let mut graph = AsgGraph::new();
+        let mut call = None;
+        let outer = graph.lambda("f", |graph, f| {
+            graph.lambda("x", |graph, _| {
+                let f = f.reference(graph);
+                let one = graph.int(1);
+                let applied = graph.apply(f, one);
+                call = Some((f, one, applied));
+                applied
+            })
+        });
+        let (f, one, applied) = call.unwrap();
+        let NodeContent::TermLambda(lambda) = &graph.get_node(outer).unwrap().content else {
+            panic!("expected a lambda");
+        };
+        let (binder, inner) = (lambda.binder_variable_node_id, lambda.body_node_id);
+
+        let index = ReferenceIndex::build(&graph);
+        assert_eq!(index.uses_of(outer), [f]);
+        assert_eq!(index.uses_of(inner), []);
+        assert_eq!(index.unused_lambdas(), [inner]);
+        assert_eq!(index.referrers(one), [applied]);
+        let mut referrers = vec![binder, f];
+        referrers.sort_unstable();
+        assert_eq!(index.referrers(outer), referrers);
+        assert_eq!(index.uses_of(one), []);
+    }
+}
@@ -0,0 +1,123 @@
+//! Scope analysis: which bindings a term refers to from outside itself.
+//!
+//! A variable names its binding site through `definition_node_id`, the id
+//! of the lambda binding it. A use is bound within a term when that lambda
+//! encloses the use inside the term, and free otherwise. Names play no
+//! part, so shadowing and hygienically renamed copies are handled exactly.
+
+use std::collections::HashSet;
+
+use crate::graph::AsgGraph;
+use crate::nodes::NodeContent;
+
+/// The binding sites referred to by variables in the term at `node_id` that
+/// are not bound within it: the ids of enclosing lambdas outside the term.
+/// A variable not linked to any lambda contributes `0`. Missing nodes are
+/// skipped, and a node on a cycle is only entered once per path.
+pub fn free_variables(graph: &AsgGraph, node_id: u64) -> HashSet<u64> {
+    let mut walk = ScopeWalk {
+        graph,
+        scopes: Vec::new(),
+        on_path: HashSet::new(),
+        free: HashSet::new(),
+    };
+    walk.visit(node_id);
+    walk.free
+}
+
+/// Whether the term at `node_id` refers to no binding outside itself.
+pub fn is_closed(graph: &AsgGraph, node_id: u64) -> bool {
+    free_variables(graph, node_id).is_empty()
+}
+
+struct ScopeWalk<'g> {
+    graph: &'g AsgGraph,
+    /// Lambdas enclosing the current node within the term, innermost last.
+    scopes: Vec<u64>,
+    on_path: HashSet<u64>,
+    free: HashSet<u64>,
+}
+
+impl ScopeWalk<'_> {
+    fn visit(&mut self, node_id: u64) {
+        let Some(node) = self.graph.get_node(node_id) else {
+            return;
+        };
+        if !self.on_path.insert(node_id) {
+            return;
+        }
+        match &node.content {
+            NodeContent::TermVariable(var) => {
+                if !self.scopes.contains(&var.definition_node_id) {
+                    self.free.insert(var.definition_node_id);
+                }
+            }
+            NodeContent::TermLambda(lambda) => {
+                // The binder declares the parameter rather than using it.
+                if let Some(annotation) = lambda.type_annotation_id {
+                    self.visit(annotation);
+                }
+                self.scopes.push(node_id);
+                self.visit(lambda.body_node_id);
+                self.scopes.pop();
+            }
+            content => {
+                for child in content.child_ids() {
+                    self.visit(child);
+                }
+            }
+        }
+        self.on_path.remove(&node_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::{NodeContent, TermVariable};
+
+    #[test]
+    fn uses_of_outer_lambdas_are_free_in_inner_terms() {
+        let mut graph = AsgGraph::new();
+        let mut inner = 0;
+        let mut body = 0;
+        // (x) => (y) => x + y
+        let outer = graph.lambda("x", |graph, x| {
+            inner = graph.lambda("y", |graph, y| {
+                let x = x.reference(graph);
+                let y = y.reference(graph);
+                body = graph.primitive("add", [x, y]);
+                body
+            });
+            inner
+        });
+
+        assert!(is_closed(&graph, outer));
+        assert_eq!(free_variables(&graph, inner), HashSet::from([outer]));
+        assert_eq!(free_variables(&graph, body), HashSet::from([outer, inner]));
+        let literal = graph.int(1);
+        assert!(is_closed(&graph, literal));
+    }
+
+    #[test]
+    fn shadowing_is_resolved_by_link_not_by_name() {
+        let mut graph = AsgGraph::new();
+        let mut inner = 0;
+        // (x) => (x) => x, where the use is linked to the outer lambda, as a
+        // hygienic rename would leave it.
+        let outer = graph.lambda("x", |graph, x| {
+            let use_site = x.reference(graph);
+            inner = graph.lambda("x", |_, _| use_site);
+            inner
+        });
+        assert_eq!(free_variables(&graph, inner), HashSet::from([outer]));
+        assert!(is_closed(&graph, outer));
+
+        let unbound = graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: "z".to_string(),
+            definition_node_id: 0,
+        }));
+        let call = graph.apply(outer, unbound);
+        assert_eq!(free_variables(&graph, call), HashSet::from([0]));
+    }
+}
@@ -0,0 +1,352 @@
+//! Constant folding and algebraic simplification of primitive operations.
+//!
+//! The pass rewrites literal arithmetic (`2 + 3` to `5`) and identities such
+//! as `x + 0`, `x * 1`, `true && e` and `e || false`. Rewrites that would
+//! discard an operand (`e * 0`, `e && false`) only fire when that operand is
+//! pure, so a `perform`, an assignment or a call is never dropped. Division
+//! by a literal zero is left alone to keep its runtime error, and since any
+//! division may trap, `x / 0 * 0` is not reduced to `0` either.
+//!
+//! Simplifying may make a term more general (in `(x) => x + 0`, `x` is no
+//! longer forced to be `Int`), so the pass belongs after type checking.
+
+use std::collections::HashMap;
+
+use crate::graph::AsgGraph;
+use crate::nodes::{LiteralBool, LiteralInt, NodeContent};
+
+/// Simplifies the term rooted at the graph's root in place and removes the
+/// nodes that became unreachable. Returns the number of rewrites applied.
+pub fn simplify(graph: &mut AsgGraph) -> usize {
+    let Some(root) = graph.root() else {
+        return 0;
+    };
+    let mut simplifier = Simplifier {
+        graph,
+        replacements: HashMap::new(),
+        rewrites: 0,
+    };
+    let new_root = simplifier.visit(root);
+    let rewrites = simplifier.rewrites;
+    graph.set_root(new_root);
+    graph.collect_garbage();
+    rewrites
+}
+
+/// A literal operand, as seen by the folding rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Constant {
+    Int(i64),
+    Bool(bool),
+}
+
+/// What to replace a primitive operation with.
+enum Rewrite {
+    /// Turn the operation node itself into this literal.
+    Fold(Constant),
+    /// Use this existing node instead of the operation.
+    Forward(u64),
+}
+
+struct Simplifier<'g> {
+    graph: &'g mut AsgGraph,
+    /// Result of already visited nodes, so shared subterms are visited once.
+    replacements: HashMap<u64, u64>,
+    rewrites: usize,
+}
+
+impl Simplifier<'_> {
+    /// Simplifies the subterm `node_id` bottom-up and returns the id that
+    /// should take its place.
+    fn visit(&mut self, node_id: u64) -> u64 {
+        if let Some(&replacement) = self.replacements.get(&node_id) {
+            return replacement;
+        }
+        let Some(node) = self.graph.get_node(node_id) else {
+            return node_id;
+        };
+        let mut content = node.content.clone();
+        if content.kind().is_term() {
+            content.map_child_ids(|child| self.visit(child));
+        }
+
+        let replacement = match self.rewrite(&content) {
+            Some(Rewrite::Fold(constant)) => {
+                content = match constant {
+                    Constant::Int(value) => NodeContent::LiteralInt(LiteralInt { value }),
+                    Constant::Bool(value) => NodeContent::LiteralBool(LiteralBool { value }),
+                };
+                self.rewrites += 1;
+                node_id
+            }
+            Some(Rewrite::Forward(operand)) => {
+                self.rewrites += 1;
+                operand
+            }
+            None => node_id,
+        };
+        if let Some(node) = self.graph.get_node_mut(node_id) {
+            node.content = content;
+        }
+        self.replacements.insert(node_id, replacement);
+        replacement
+    }
+
+    fn rewrite(&self, content: &NodeContent) -> Option<Rewrite> {
+        let NodeContent::PrimitiveOp(op) = content else {
+            return None;
+        };
+        let args = &op.argument_node_ids;
+        let constants: Vec<_> = args.iter().map(|&arg| self.constant(arg)).collect();
+        if let Some(constants) = constants.iter().copied().collect::<Option<Vec<_>>>() {
+            return fold(&op.op_name, &constants).map(Rewrite::Fold);
+        }
+
+        use Constant::{Bool, Int};
+        let (&[lhs, rhs], &[lhs_constant, rhs_constant]) = (&args[..], &constants[..]) else {
+            return None;
+        };
+        let rewrite = match (op.op_name.as_str(), lhs_constant, rhs_constant) {
+            ("add", Some(Int(0)), _) | ("mul", Some(Int(1)), _) => Rewrite::Forward(rhs),
+            ("add" | "sub", _, Some(Int(0))) | ("mul" | "div", _, Some(Int(1))) => {
+                Rewrite::Forward(lhs)
+            }
+            ("and", Some(Bool(true)), _) | ("or", Some(Bool(false)), _) => Rewrite::Forward(rhs),
+            ("and", _, Some(Bool(true))) | ("or", _, Some(Bool(false))) => Rewrite::Forward(lhs),
+            // The remaining rules discard the other operand.
+            ("mul", Some(Int(0)), _)
+            | ("and", Some(Bool(false)), _)
+            | ("or", Some(Bool(true)), _)
+                if self.is_pure(rhs) =>
+            {
+                Rewrite::Forward(lhs)
+            }
+            ("mul", _, Some(Int(0)))
+            | ("and", _, Some(Bool(false)))
+            | ("or", _, Some(Bool(true)))
+                if self.is_pure(lhs) =>
+            {
+                Rewrite::Forward(rhs)
+            }
+            _ => return None,
+        };
+        Some(rewrite)
+    }
+
+    fn constant(&self, node_id: u64) -> Option<Constant> {
+        match &self.graph.get_node(node_id)?.content {
+            NodeContent::LiteralInt(lit) => Some(Constant::Int(lit.value)),
+            NodeContent::LiteralBool(lit) => Some(Constant::Bool(lit.value)),
+            _ => None,
+        }
+    }
+
+    /// Whether evaluating `node_id` can neither perform an effect, write the
+    /// store, call a function nor fail, so dropping it is unobservable.
+    fn is_pure(&self, node_id: u64) -> bool {
+        let Some(node) = self.graph.get_node(node_id) else {
+            return false;
+        };
+        match &node.content {
+            NodeContent::TermVariable(_)
+            | NodeContent::TermLambda(_)
+            | NodeContent::LiteralInt(_)
+            | NodeContent::LiteralBool(_)
+            | NodeContent::LiteralUnit(_) => true,
+            // `fix` calls the generator of a `letrec`.
+            NodeContent::PrimitiveOp(op) => {
+                !matches!(op.op_name.as_str(), "div" | "mod" | "fix")
+                    && op.argument_node_ids.iter().all(|&arg| self.is_pure(arg))
+            }
+            NodeContent::TermDeref(term) => self.is_pure(term.ref_node_id),
+            NodeContent::TermApplication(_)
+            | NodeContent::TermRef(_)
+            | NodeContent::TermAssign(_)
+            | NodeContent::EffectPerform(_)
+            | NodeContent::TermMacroDefinition(_)
+            | NodeContent::TermMacroInvocation(_)
+            | NodeContent::TypeNode(_) => false,
+        }
+    }
+}
+
+/// Evaluates a primitive over literal operands, with the runtime's wrapping
+/// integer semantics. Division by zero is not folded.
+fn fold(op_name: &str, args: &[Constant]) -> Option<Constant> {
+    use Constant::{Bool, Int};
+
+    let value = match (op_name, args) {
+        ("add", [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+        ("sub", [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+        ("mul", [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+        ("div" | "mod", [Int(_), Int(0)]) => return None,
+        ("div", [Int(a), Int(b)]) => Int(a.wrapping_div(*b)),
+        ("mod", [Int(a), Int(b)]) => Int(a.wrapping_rem(*b)),
+        ("lt", [Int(a), Int(b)]) => Bool(a < b),
+        ("le", [Int(a), Int(b)]) => Bool(a <= b),
+        ("gt", [Int(a), Int(b)]) => Bool(a > b),
+        ("ge", [Int(a), Int(b)]) => Bool(a >= b),
+        ("eq", [a, b]) => Bool(a == b),
+        ("ne", [a, b]) => Bool(a != b),
+        ("and", [Bool(a), Bool(b)]) => Bool(*a && *b),
+        ("or", [Bool(a), Bool(b)]) => Bool(*a || *b),
+        ("not", [Bool(a)]) => Bool(!a),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::{EffectPerform, LiteralUnit, PrimitiveOp, TermLambda, TermVariable};
+
+    fn op(graph: &mut AsgGraph, op_name: &str, argument_node_ids: Vec<u64>) -> u64 {
+        graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: op_name.to_string(),
+            argument_node_ids,
+        }))
+    }
+
+    fn int(graph: &mut AsgGraph, value: i64) -> u64 {
+        graph.add_node(NodeContent::LiteralInt(LiteralInt { value }))
+    }
+
+    fn boolean(graph: &mut AsgGraph, value: bool) -> u64 {
+        graph.add_node(NodeContent::LiteralBool(LiteralBool { value }))
+    }
+
+    fn perform(graph: &mut AsgGraph) -> u64 {
+        let unit = graph.add_node(NodeContent::LiteralUnit(LiteralUnit));
+        graph.add_node(NodeContent::EffectPerform(EffectPerform {
+            effect_name: "Ask".to_string(),
+            value_node_id: unit,
+        }))
+    }
+
+    fn root_content(graph: &AsgGraph) -> &NodeContent {
+        &graph.get_node(graph.root().unwrap()).unwrap().content
+    }
+
+    #[test]
+    fn literal_arithmetic_folds_to_a_single_literal() {
+        let mut graph = AsgGraph::new();
+        let two = int(&mut graph, 2);
+        let three = int(&mut graph, 3);
+        let sum = op(&mut graph, "add", vec![two, three]);
+        graph.set_root(sum);
+
+        assert_eq!(simplify(&mut graph), 1);
+        assert_eq!(graph.root(), Some(sum));
+        assert_eq!(
+            root_content(&graph),
+            &NodeContent::LiteralInt(LiteralInt { value: 5 })
+        );
+        assert_eq!(graph.len(), 1);
+    }
+
+    #[test]
+    fn identities_forward_to_the_effectful_operand() {
+        let mut graph = AsgGraph::new();
+        let effect = perform(&mut graph);
+        let yes = boolean(&mut graph, true);
+        let conjunction = op(&mut graph, "and", vec![effect, yes]);
+        graph.set_root(conjunction);
+
+        assert_eq!(simplify(&mut graph), 1);
+        assert_eq!(graph.root(), Some(effect));
+        assert!(matches!(
+            root_content(&graph),
+            NodeContent::EffectPerform(_)
+        ));
+        assert_eq!(graph.len(), 2);
+    }
+
+    #[test]
+    fn effectful_operands_are_never_dropped() {
+        let mut graph = AsgGraph::new();
+        let effect = perform(&mut graph);
+        let no = boolean(&mut graph, false);
+        let conjunction = op(&mut graph, "and", vec![effect, no]);
+        graph.set_root(conjunction);
+        assert_eq!(simplify(&mut graph), 0);
+        assert_eq!(graph.len(), 4);
+
+        let zero = int(&mut graph, 0);
+        let one = int(&mut graph, 1);
+        let division = op(&mut graph, "div", vec![one, zero]);
+        graph.set_root(division);
+        assert_eq!(simplify(&mut graph), 0);
+        assert_eq!(graph.len(), 3);
+    }
+
+    #[test]
+    fn division_of_a_variable_by_zero_is_kept() {
+        // (x) => x / 0 * 0
+        let mut graph = AsgGraph::new();
+        let binder = graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: "x".to_string(),
+            definition_node_id: 0,
+        }));
+        let x = graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: "x".to_string(),
+            definition_node_id: 0,
+        }));
+        let zero = int(&mut graph, 0);
+        let division = op(&mut graph, "div", vec![x, zero]);
+        let other_zero = int(&mut graph, 0);
+        let product = op(&mut graph, "mul", vec![division, other_zero]);
+        let lambda = graph.add_node(NodeContent::TermLambda(TermLambda {
+            binder_variable_node_id: binder,
+            body_node_id: product,
+            type_annotation_id: None,
+            effect_annotation: None,
+        }));
+        graph.set_root(lambda);
+
+        assert_eq!(simplify(&mut graph), 0);
+        assert!(matches!(
+            graph.get_node(division).unwrap().content,
+            NodeContent::PrimitiveOp(_)
+        ));
+        assert_eq!(graph.len(), 7);
+    }
+
+    #[test]
+    fn simplification_rewires_parents_inside_lambdas() {
+        // (x) => (x + 0) * (1 + 1)
+        let mut graph = AsgGraph::new();
+        let binder = graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: "x".to_string(),
+            definition_node_id: 0,
+        }));
+        let x = graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: "x".to_string(),
+            definition_node_id: 0,
+        }));
+        let zero = int(&mut graph, 0);
+        let sum = op(&mut graph, "add", vec![x, zero]);
+        let one = int(&mut graph, 1);
+        let other_one = int(&mut graph, 1);
+        let two = op(&mut graph, "add", vec![one, other_one]);
+        let product = op(&mut graph, "mul", vec![sum, two]);
+        let lambda = graph.add_node(NodeContent::TermLambda(TermLambda {
+            binder_variable_node_id: binder,
+            body_node_id: product,
+            type_annotation_id: None,
+            effect_annotation: None,
+        }));
+        graph.set_root(lambda);
+
+        assert_eq!(simplify(&mut graph), 2);
+        let NodeContent::PrimitiveOp(product) = &graph.get_node(product).unwrap().content else {
+            panic!("product was rewritten");
+        };
+        assert_eq!(product.argument_node_ids, vec![x, two]);
+        assert_eq!(
+            graph.get_node(two).unwrap().content,
+            NodeContent::LiteralInt(LiteralInt { value: 2 })
+        );
+        assert_eq!(graph.len(), 5);
+    }
+}
@@ -0,0 +1,57 @@
+//! Errors produced while lowering the ASG to UPIR.
+
+use asg_core::NodeKind;
+use thiserror::Error;
+
+/// Reasons lowering can fail.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LoweringError {
+    /// The graph has no root node to lower.
+    #[error("graph has no root node")]
+    MissingRoot,
+
+    /// A node referenced during lowering is not in the graph.
+    #[error("node {0} referenced but not present in the graph")]
+    MissingNode(u64),
+
+    /// The node is valid ASG but has no UPIR lowering yet.
+    #[error("cannot lower node {node_id}: {reason}")]
+    Unsupported { node_id: u64, reason: String },
+
+    /// The node's kind has no UPIR lowering, e.g. an `EffectPerform` or a
+    /// macro node left unexpanded.
+    #[error("cannot lower node {node_id}: no lowering for {kind} nodes")]
+    UnsupportedNode { node_id: u64, kind: NodeKind },
+
+    /// A term has neither a type annotation nor a type recorded by the
+    /// checker, so its UPIR type is unknown.
+    #[error("node {node_id} has no annotated or inferred type")]
+    UnresolvedType { node_id: u64 },
+}
+
+impl LoweringError {
+    /// Stable diagnostic code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoweringError::MissingRoot => "U001",
+            LoweringError::MissingNode(_) => "U002",
+            LoweringError::Unsupported { .. } => "U003",
+            LoweringError::UnsupportedNode { .. } => "U004",
+            LoweringError::UnresolvedType { .. } => "U005",
+        }
+    }
+
+    /// The node the error is reported against, if any.
+    pub fn node_id(&self) -> Option<u64> {
+        match self {
+            LoweringError::MissingRoot => None,
+            LoweringError::MissingNode(node_id)
+            | LoweringError::Unsupported { node_id, .. }
+            | LoweringError::UnsupportedNode { node_id, .. }
+            | LoweringError::UnresolvedType { node_id } => Some(*node_id),
+        }
+    }
+}
+
+/// Convenience alias for lowering results.
+pub type Result<T> = std::result::Result<T, LoweringError>;
@@ -0,0 +1,154 @@
+//! Golden tests: the exact UPIR text lowered from small programs.
+//!
+//! When a lowering change alters one of these on purpose, update the
+//! expected text so the difference shows up in review.
+
+use std::error::Error;
+
+use crate::lower::lower_graph_to_upir;
+
+/// Parses, type-checks and lowers `source`, returning the printed module.
+/// Lowered modules must always pass [`upir_core::verify`] and read back
+/// from their text unchanged.
+pub(crate) fn lower_and_print(source: &str) -> Result<String, Box<dyn Error>> {
+    let graph = parser_core::parse_source("golden.syn", source)?;
+    type_checker_l1::check_graph(&graph)?;
+    let module = lower_graph_to_upir(&graph)?;
+    if let Err(errors) = upir_core::verify(&module) {
+        panic!("lowered {:?} to invalid UPIR: {:?}", source, errors);
+    }
+    let text = upir_core::print_module(&module);
+    assert_eq!(upir_core::parse_module(&text), Ok(module));
+    Ok(text)
+}
+
+fn assert_golden(source: &str, expected: &str) {
+    let actual = lower_and_print(source).unwrap();
+    assert_eq!(
+        actual, expected,
+        "UPIR for {:?} changed:\n{}",
+        source, actual
+    );
+}
+
+#[test]
+fn arithmetic() {
+    assert_golden(
+        "1 + 2 * 3",
+        r#"module @main {
+  func @main() -> i64 {
+  ^entry:
+    %0 = const {location = loc("golden.syn":1:1), value = 1} : i64
+    %1 = const {location = loc("golden.syn":1:5), value = 2} : i64
+    %2 = const {location = loc("golden.syn":1:9), value = 3} : i64
+    %3 = mul %1, %2 {location = loc("golden.syn":1:5)} : i64
+    %4 = add %0, %3 {location = loc("golden.syn":1:1)} : i64
+    return %4 {location = loc("golden.syn":1:1)}
+  }
+}
+"#,
+    );
+}
+
+#[test]
+fn applied_lambda() {
+    assert_golden(
+        "((x: Int) => x * 2 == 4)(2)",
+        r#"module @main {
+  func @main() -> bool {
+  ^entry:
+    %0 = const {location = loc("golden.syn":1:26), value = 2} : i64
+    %1 = call %0 {callee = @lambda_8, location = loc("golden.syn":1:1)} : bool
+    return %1 {location = loc("golden.syn":1:1)}
+  }
+  func @lambda_8(%0: i64) -> bool {
+  ^entry:
+    %1 = const {location = loc("golden.syn":1:18), value = 2} : i64
+    %2 = mul %0, %1 {location = loc("golden.syn":1:14)} : i64
+    %3 = const {location = loc("golden.syn":1:23), value = 4} : i64
+    %4 = eq %2, %3 {location = loc("golden.syn":1:14)} : bool
+    return %4 {location = loc("golden.syn":1:14)}
+  }
+}
+"#,
+    );
+}
+
+#[test]
+fn higher_order_application() {
+    assert_golden(
+        "((f: Int -> Int) => f(1))((x: Int) => x - 1)",
+        r#"module @main {
+  func @main() -> i64 {
+  ^entry:
+    %0 = func_ref {callee = @lambda_14, location = loc("golden.syn":1:27)} : fn(i64) -> i64
+    %1 = call %0 {callee = @lambda_8, location = loc("golden.syn":1:1)} : i64
+    return %1 {location = loc("golden.syn":1:1)}
+  }
+  func @lambda_8(%0: fn(i64) -> i64) -> i64 {
+  ^entry:
+    %1 = const {location = loc("golden.syn":1:23), value = 1} : i64
+    %2 = call_indirect %0, %1 {location = loc("golden.syn":1:21)} : i64
+    return %2 {location = loc("golden.syn":1:21)}
+  }
+  func @lambda_14(%0: i64) -> i64 {
+  ^entry:
+    %1 = const {location = loc("golden.syn":1:43), value = 1} : i64
+    %2 = sub %0, %1 {location = loc("golden.syn":1:39)} : i64
+    return %2 {location = loc("golden.syn":1:39)}
+  }
+}
+"#,
+    );
+}
+
+#[test]
+fn references() {
+    assert_golden(
+        "((r: Ref Int) => r := !r + 1)(ref 41)",
+        r#"module @main {
+  func @main() -> unit {
+  ^entry:
+    %0 = const {location = loc("golden.syn":1:35), value = 41} : i64
+    %1 = ref %0 {location = loc("golden.syn":1:31)} : ref<i64>
+    %2 = call %1 {callee = @lambda_10, location = loc("golden.syn":1:1)} : unit
+    return %2 {location = loc("golden.syn":1:1)}
+  }
+  func @lambda_10(%0: ref<i64>) -> unit {
+  ^entry:
+    %1 = deref %0 {location = loc("golden.syn":1:23)} : i64
+    %2 = const {location = loc("golden.syn":1:28), value = 1} : i64
+    %3 = add %1, %2 {location = loc("golden.syn":1:23)} : i64
+    %4 = assign %0, %3 {location = loc("golden.syn":1:18)} : unit
+    return %4 {location = loc("golden.syn":1:18)}
+  }
+}
+"#,
+    );
+}
+
+#[test]
+fn short_circuit_or() {
+    assert_golden(
+        "1 < 2 || 3 == 4",
+        r#"module @main {
+  func @main() -> bool {
+  ^entry:
+    %0 = const {location = loc("golden.syn":1:1), value = 1} : i64
+    %1 = const {location = loc("golden.syn":1:5), value = 2} : i64
+    %2 = lt %0, %1 {location = loc("golden.syn":1:1)} : bool
+    cond_br %2 {else = ^or_rhs_7, location = loc("golden.syn":1:1), then = ^or_short_7}
+  ^or_rhs_7:
+    %3 = const {location = loc("golden.syn":1:10), value = 3} : i64
+    %4 = const {location = loc("golden.syn":1:15), value = 4} : i64
+    %5 = eq %3, %4 {location = loc("golden.syn":1:10)} : bool
+    br %5 {location = loc("golden.syn":1:1), target = ^or_end_7}
+  ^or_short_7:
+    br %2 {location = loc("golden.syn":1:1), target = ^or_end_7}
+  ^or_end_7(%6: bool):
+    return %6 {location = loc("golden.syn":1:1)}
+  }
+}
+"#,
+    );
+}
@@ -1,14 +1,14 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Lowering from the ASG to UPIR.
+//!
+//! The root expression of the graph becomes a `main` function. Lambdas are
+//! lifted into top-level functions; each lowered operation carries a
+//! `location` attribute pointing back at the ASG node it came from.
+
+pub mod error;
+pub mod lower;
 
 #[cfg(test)]
-mod tests {
-    use super::*;
+mod golden;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use error::{LoweringError, Result};
+pub use lower::lower_graph_to_upir;
@@ -0,0 +1,673 @@
+//! The ASG → UPIR lowering pass.
+
+use std::collections::HashMap;
+
+use asg_core::{AsgGraph, AsgNode, NodeContent, TermLambda, TypeKind};
+use upir_core::{Attribute, Block, Function, Location, Module, Operation, Type, Value, ValueId};
+
+use crate::error::{LoweringError, Result};
+
+/// Lowers the expression rooted at the graph's root node into a UPIR module.
+///
+/// The root becomes the body of a `main` function. Lambdas are lifted into
+/// functions named `lambda_<node id>`; lambdas capturing variables from an
+/// enclosing scope are not supported yet. A lambda's parameter type is its
+/// annotation or, failing that, the type the checker recorded for its binder
+/// (see `type_checker_l1::check_and_annotate_graph`). `and` and `or` short-circuit: the
+/// right operand is lowered into its own block, reached by a `cond_br` on the
+/// left one, and both paths meet in a block taking the result as parameter.
+///
+/// The output depends only on the graph's structure: `main` comes first and
+/// lifted functions follow in [`AsgGraph::topological_order`], so the same
+/// program always prints byte-identical UPIR.
+pub fn lower_graph_to_upir(graph: &AsgGraph) -> Result<Module> {
+    let root = graph.root().ok_or(LoweringError::MissingRoot)?;
+    let mut lifted = Vec::new();
+    let main = FunctionLowerer::new(graph, &mut lifted).lower_body("main", Vec::new(), root)?;
+
+    let position: HashMap<String, usize> = graph
+        .topological_order()
+        .into_iter()
+        .enumerate()
+        .map(|(index, node_id)| (lambda_name(node_id), index))
+        .collect();
+    lifted.sort_by_key(|function| position.get(&function.name).copied());
+
+    let mut module = Module::new("main");
+    module.functions.push(main);
+    module.functions.extend(lifted);
+    Ok(module)
+}
+
+/// Lowers one function body, pushing any lambdas it encounters onto `lifted`.
+struct FunctionLowerer<'a> {
+    graph: &'a AsgGraph,
+    lifted: &'a mut Vec<Function>,
+    /// Maps a binding lambda's node id to the SSA value of its parameter.
+    env: HashMap<u64, ValueId>,
+    value_types: HashMap<ValueId, Type>,
+    /// Finished blocks, in order.
+    blocks: Vec<Block>,
+    /// The block operations are currently appended to.
+    current: Block,
+    next_value: ValueId,
+}
+
+impl<'a> FunctionLowerer<'a> {
+    fn new(graph: &'a AsgGraph, lifted: &'a mut Vec<Function>) -> Self {
+        Self {
+            graph,
+            lifted,
+            env: HashMap::new(),
+            value_types: HashMap::new(),
+            blocks: Vec::new(),
+            current: Block {
+                label: "entry".to_string(),
+                params: Vec::new(),
+                operations: Vec::new(),
+            },
+            next_value: 0,
+        }
+    }
+
+    fn lower_body(mut self, name: &str, params: Vec<Value>, body: u64) -> Result<Function> {
+        let result = self.lower_node(body)?;
+        let return_type = self.value_types[&result].clone();
+        let ret = Operation::new("return").with_operands(vec![result]);
+        self.emit(body, ret);
+        self.blocks.push(self.current);
+
+        Ok(Function {
+            name: name.to_string(),
+            params,
+            return_type,
+            blocks: self.blocks,
+        })
+    }
+
+    /// Finishes the current block and continues in a new one.
+    fn start_block(&mut self, label: String, params: Vec<Value>) {
+        let finished = std::mem::replace(
+            &mut self.current,
+            Block {
+                label,
+                params,
+                operations: Vec::new(),
+            },
+        );
+        self.blocks.push(finished);
+    }
+
+    fn node(&self, node_id: u64) -> Result<&'a AsgNode> {
+        self.graph
+            .get_node(node_id)
+            .ok_or(LoweringError::MissingNode(node_id))
+    }
+
+    fn fresh_value(&mut self, ty: Type) -> Value {
+        let id = self.next_value;
+        self.next_value += 1;
+        self.value_types.insert(id, ty.clone());
+        Value { id, ty }
+    }
+
+    /// Appends `op`, tagging it with the source location of `node_id`.
+    fn emit(&mut self, node_id: u64, mut op: Operation) -> Option<ValueId> {
+        if let Some(loc) = self.graph.source_location(node_id) {
+            op.attributes.insert(
+                "location".to_string(),
+                Attribute::Location(Location {
+                    file: loc.filename.clone(),
+                    line: loc.start_line,
+                    column: loc.start_col,
+                }),
+            );
+        }
+        let result = op.result.as_ref().map(|value| value.id);
+        self.current.operations.push(op);
+        result
+    }
+
+    fn emit_value(&mut self, node_id: u64, op: Operation, ty: Type) -> ValueId {
+        let result = self.fresh_value(ty);
+        self.emit(node_id, op.with_result(result))
+            .expect("operation was given a result")
+    }
+
+    fn lower_node(&mut self, node_id: u64) -> Result<ValueId> {
+        let node = self.node(node_id)?;
+        match &node.content {
+            NodeContent::LiteralInt(lit) => Ok(self.emit_value(
+                node_id,
+                Operation::new("const").with_attribute("value", Attribute::Int(lit.value)),
+                Type::I64,
+            )),
+            NodeContent::LiteralBool(lit) => Ok(self.emit_value(
+                node_id,
+                Operation::new("const").with_attribute("value", Attribute::Bool(lit.value)),
+                Type::Bool,
+            )),
+            NodeContent::LiteralUnit(_) => {
+                Ok(self.emit_value(node_id, Operation::new("unit"), Type::Unit))
+            }
+            NodeContent::TermVariable(var) => {
+                self.env
+                    .get(&var.definition_node_id)
+                    .copied()
+                    .ok_or_else(|| LoweringError::Unsupported {
+                        node_id,
+                        reason: format!(
+                            "variable '{}' is captured from an enclosing scope",
+                            var.name
+                        ),
+                    })
+            }
+            NodeContent::PrimitiveOp(op) if matches!(op.op_name.as_str(), "and" | "or") => {
+                let [lhs, rhs] = op.argument_node_ids[..] else {
+                    return Err(LoweringError::Unsupported {
+                        node_id,
+                        reason: format!("'{}' takes two operands", op.op_name),
+                    });
+                };
+                self.lower_short_circuit(node_id, &op.op_name, lhs, rhs)
+            }
+            NodeContent::PrimitiveOp(op) if op.op_name == "if" => {
+                let [condition, yes, no] = op.argument_node_ids[..] else {
+                    return Err(LoweringError::Unsupported {
+                        node_id,
+                        reason: "'if' takes a condition and two branches".to_string(),
+                    });
+                };
+                self.lower_conditional(node_id, condition, yes, no)
+            }
+            NodeContent::PrimitiveOp(op) if op.op_name == "fix" => {
+                Err(LoweringError::Unsupported {
+                    node_id,
+                    reason: "recursive functions ('letrec') are not lowered yet".to_string(),
+                })
+            }
+            NodeContent::PrimitiveOp(op) => {
+                let result_type = primitive_result_type(&op.op_name).ok_or_else(|| {
+                    LoweringError::Unsupported {
+                        node_id,
+                        reason: format!("unknown primitive operation '{}'", op.op_name),
+                    }
+                })?;
+                let operands = op
+                    .argument_node_ids
+                    .iter()
+                    .map(|&arg| self.lower_node(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(self.emit_value(
+                    node_id,
+                    Operation::new(op.op_name.clone()).with_operands(operands),
+                    result_type,
+                ))
+            }
+            NodeContent::TermLambda(lambda) => {
+                let func_type = self.lift_lambda(node_id, lambda)?;
+                Ok(self.emit_value(
+                    node_id,
+                    Operation::new("func_ref")
+                        .with_attribute("callee", Attribute::Symbol(lambda_name(node_id))),
+                    func_type,
+                ))
+            }
+            NodeContent::TermApplication(app) => {
+                let callee = self.node(app.function_node_id)?;
+                if let NodeContent::TermLambda(lambda) = &callee.content {
+                    let func_type = self.lift_lambda(callee.node_id, lambda)?;
+                    let argument = self.lower_node(app.argument_node_id)?;
+                    let ret = return_type_of(&func_type, node_id)?;
+                    return Ok(self.emit_value(
+                        node_id,
+                        Operation::new("call")
+                            .with_operands(vec![argument])
+                            .with_attribute(
+                                "callee",
+                                Attribute::Symbol(lambda_name(callee.node_id)),
+                            ),
+                        ret,
+                    ));
+                }
+
+                let function = self.lower_node(app.function_node_id)?;
+                let argument = self.lower_node(app.argument_node_id)?;
+                let ret = return_type_of(&self.value_types[&function], node_id)?;
+                Ok(self.emit_value(
+                    node_id,
+                    Operation::new("call_indirect").with_operands(vec![function, argument]),
+                    ret,
+                ))
+            }
+            NodeContent::TermRef(term) => {
+                let init = self.lower_node(term.init_value_node_id)?;
+                let element = self.value_types[&init].clone();
+                Ok(self.emit_value(
+                    node_id,
+                    Operation::new("ref").with_operands(vec![init]),
+                    Type::Ref(Box::new(element)),
+                ))
+            }
+            NodeContent::TermDeref(term) => {
+                let cell = self.lower_node(term.ref_node_id)?;
+                let element = element_type_of(&self.value_types[&cell], node_id)?;
+                Ok(self.emit_value(
+                    node_id,
+                    Operation::new("deref").with_operands(vec![cell]),
+                    element,
+                ))
+            }
+            NodeContent::TermAssign(term) => {
+                let cell = self.lower_node(term.ref_node_id)?;
+                element_type_of(&self.value_types[&cell], node_id)?;
+                let value = self.lower_node(term.value_node_id)?;
+                Ok(self.emit_value(
+                    node_id,
+                    Operation::new("assign").with_operands(vec![cell, value]),
+                    Type::Unit,
+                ))
+            }
+            other => Err(LoweringError::UnsupportedNode {
+                node_id,
+                kind: other.kind(),
+            }),
+        }
+    }
+
+    /// Lowers `lhs and rhs` or `lhs or rhs` so that `rhs` is only evaluated
+    /// when `lhs` does not already decide the result:
+    ///
+    /// ```text
+    ///   cond_br %lhs {else = ^and_short_N, then = ^and_rhs_N}
+    /// ^and_rhs_N:
+    ///   %rhs = ...
+    ///   br %rhs {target = ^and_end_N}
+    /// ^and_short_N:
+    ///   br %lhs {target = ^and_end_N}
+    /// ^and_end_N(%result: bool):
+    /// ```
+    fn lower_short_circuit(
+        &mut self,
+        node_id: u64,
+        op: &str,
+        lhs: u64,
+        rhs: u64,
+    ) -> Result<ValueId> {
+        let lhs = self.lower_node(lhs)?;
+        let label = |part: &str| format!("{}_{}_{}", op, part, node_id);
+        let (rhs_label, short_label) = (label("rhs"), label("short"));
+        let (then_label, else_label) = if op == "and" {
+            (&rhs_label, &short_label)
+        } else {
+            (&short_label, &rhs_label)
+        };
+        let branch = Operation::new("cond_br")
+            .with_operands(vec![lhs])
+            .with_attribute("then", Attribute::Block(then_label.clone()))
+            .with_attribute("else", Attribute::Block(else_label.clone()));
+        self.emit(node_id, branch);
+
+        let end = Attribute::Block(label("end"));
+        self.start_block(rhs_label, Vec::new());
+        let rhs = self.lower_node(rhs)?;
+        let to_end = Operation::new("br").with_attribute("target", end.clone());
+        self.emit(node_id, to_end.clone().with_operands(vec![rhs]));
+        self.start_block(short_label, Vec::new());
+        self.emit(node_id, to_end.with_operands(vec![lhs]));
+
+        let result = self.fresh_value(Type::Bool);
+        let id = result.id;
+        self.start_block(label("end"), vec![result]);
+        Ok(id)
+    }
+
+    /// Lowers `if condition then yes else no`, evaluating only the branch
+    /// the condition selects:
+    ///
+    /// ```text
+    ///   cond_br %condition {else = ^if_else_N, then = ^if_then_N}
+    /// ^if_then_N:
+    ///   %yes = ...
+    ///   br %yes {target = ^if_end_N}
+    /// ^if_else_N:
+    ///   %no = ...
+    ///   br %no {target = ^if_end_N}
+    /// ^if_end_N(%result: T):
+    /// ```
+    fn lower_conditional(
+        &mut self,
+        node_id: u64,
+        condition: u64,
+        yes: u64,
+        no: u64,
+    ) -> Result<ValueId> {
+        let condition = self.lower_node(condition)?;
+        let label = |part: &str| format!("if_{}_{}", part, node_id);
+        let branch = Operation::new("cond_br")
+            .with_operands(vec![condition])
+            .with_attribute("then", Attribute::Block(label("then")))
+            .with_attribute("else", Attribute::Block(label("else")));
+        self.emit(node_id, branch);
+
+        let to_end = Operation::new("br").with_attribute("target", Attribute::Block(label("end")));
+        self.start_block(label("then"), Vec::new());
+        let yes = self.lower_node(yes)?;
+        self.emit(node_id, to_end.clone().with_operands(vec![yes]));
+        self.start_block(label("else"), Vec::new());
+        let no = self.lower_node(no)?;
+        self.emit(node_id, to_end.with_operands(vec![no]));
+
+        let result = self.fresh_value(self.value_types[&yes].clone());
+        let id = result.id;
+        self.start_block(label("end"), vec![result]);
+        Ok(id)
+    }
+
+    /// Lifts a lambda into a top-level function (once) and returns its type.
+    fn lift_lambda(&mut self, lambda_id: u64, lambda: &TermLambda) -> Result<Type> {
+        let name = lambda_name(lambda_id);
+        if let Some(existing) = self.lifted.iter().find(|func| func.name == name) {
+            return Ok(function_type(existing));
+        }
+
+        let annotation = lambda
+            .type_annotation_id
+            .or_else(|| self.graph.inferred_type(lambda.binder_variable_node_id))
+            .ok_or(LoweringError::UnresolvedType { node_id: lambda_id })?;
+        let param_type = lower_type_node(self.graph, annotation)?;
+
+        let mut inner = FunctionLowerer::new(self.graph, self.lifted);
+        let param = inner.fresh_value(param_type);
+        inner.env.insert(lambda_id, param.id);
+        let function = inner.lower_body(&name, vec![param], lambda.body_node_id)?;
+
+        let func_type = function_type(&function);
+        self.lifted.push(function);
+        Ok(func_type)
+    }
+}
+
+fn lambda_name(node_id: u64) -> String {
+    format!("lambda_{}", node_id)
+}
+
+fn function_type(function: &Function) -> Type {
+    Type::Function {
+        params: function.params.iter().map(|param| param.ty.clone()).collect(),
+        ret: Box::new(function.return_type.clone()),
+    }
+}
+
+fn return_type_of(ty: &Type, node_id: u64) -> Result<Type> {
+    match ty {
+        Type::Function { ret, .. } => Ok((**ret).clone()),
+        other => Err(LoweringError::Unsupported {
+            node_id,
+            reason: format!("cannot apply a value of type {}", other),
+        }),
+    }
+}
+
+fn element_type_of(ty: &Type, node_id: u64) -> Result<Type> {
+    match ty {
+        Type::Ref(element) => Ok((**element).clone()),
+        other => Err(LoweringError::Unsupported {
+            node_id,
+            reason: format!("cannot dereference a value of type {}", other),
+        }),
+    }
+}
+
+/// Result type of a primitive operation, or `None` if the op is unknown.
+fn primitive_result_type(op_name: &str) -> Option<Type> {
+    match op_name {
+        "add" | "sub" | "mul" | "div" | "mod" => Some(Type::I64),
+        "eq" | "ne" | "lt" | "le" | "gt" | "ge" | "and" | "or" | "not" => Some(Type::Bool),
+        _ => None,
+    }
+}
+
+/// Converts an ASG type annotation into a UPIR type.
+fn lower_type_node(graph: &AsgGraph, type_id: u64) -> Result<Type> {
+    let node = graph
+        .get_node(type_id)
+        .ok_or(LoweringError::MissingNode(type_id))?;
+    let NodeContent::TypeNode(type_node) = &node.content else {
+        return Err(LoweringError::Unsupported {
+            node_id: type_id,
+            reason: "type annotation does not point at a type node".to_string(),
+        });
+    };
+    Ok(match type_node.kind {
+        TypeKind::Int => Type::I64,
+        TypeKind::Bool => Type::Bool,
+        TypeKind::Unit => Type::Unit,
+        TypeKind::Function {
+            param_type_id,
+            return_type_id,
+        } => Type::Function {
+            params: vec![lower_type_node(graph, param_type_id)?],
+            ret: Box::new(lower_type_node(graph, return_type_id)?),
+        },
+        TypeKind::Ref { element_type_id } => {
+            Type::Ref(Box::new(lower_type_node(graph, element_type_id)?))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asg_core::{
+        LambdaBuilder, LiteralInt, PrimitiveOp, SourceLocation, TermApplication, TypeNode,
+    };
+
+    fn location(line: u32, col: u32) -> SourceLocation {
+        SourceLocation {
+            filename: "main.syn".to_string(),
+            start_line: line,
+            start_col: col,
+            end_line: line,
+            end_col: col + 5,
+        }
+    }
+
+    #[test]
+    fn located_expression_carries_location_attribute() {
+        let mut graph = AsgGraph::new();
+        let one = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+        let two = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 2 }));
+        let add = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "add".to_string(),
+            argument_node_ids: vec![one, two],
+        }));
+        graph.set_source_location(one, location(3, 1));
+        graph.set_source_location(add, location(3, 3));
+        graph.set_root(add);
+
+        let module = lower_graph_to_upir(&graph).unwrap();
+        let ops = &module.function("main").unwrap().blocks[0].operations;
+
+        let add_op = ops.iter().find(|op| op.name == "add").unwrap();
+        let loc = add_op.location().expect("add carries a location");
+        assert_eq!((loc.file.as_str(), loc.line, loc.column), ("main.syn", 3, 3));
+        assert_eq!(ops[0].location().unwrap().line, 3);
+        // The literal `2` has no metadata, so no location is invented for it.
+        assert!(ops[1].location().is_none());
+
+        let printed = upir_core::print_module(&module);
+        assert!(printed.contains("location = loc(\"main.syn\":3:3)"));
+    }
+
+    #[test]
+    fn applied_lambda_is_lifted_into_a_function() {
+        let mut graph = AsgGraph::new();
+        let int_ty = graph.add_node(NodeContent::TypeNode(TypeNode {
+            kind: TypeKind::Int,
+        }));
+        let lambda = LambdaBuilder::build(&mut graph, "x", |graph, x| {
+            x.set_annotation(Some(int_ty));
+            x.reference(graph)
+        });
+        let arg = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 5 }));
+        let app = graph.add_node(NodeContent::TermApplication(TermApplication {
+            function_node_id: lambda,
+            argument_node_id: arg,
+        }));
+        graph.set_root(app);
+
+        let module = lower_graph_to_upir(&graph).unwrap();
+        let lifted = module.function(&lambda_name(lambda)).unwrap();
+        assert_eq!(lifted.params.len(), 1);
+        assert_eq!(lifted.return_type, Type::I64);
+        assert_eq!(module.function("main").unwrap().return_type, Type::I64);
+    }
+
+    #[test]
+    fn lowering_the_same_program_twice_prints_identical_upir() {
+        // ((f: Int -> Int) => f(1))((x: Int) => x * 2 + 1), built twice so
+        // each graph gets its own hash map state.
+        let build = || {
+            let mut graph = AsgGraph::new();
+            let int_ty = || {
+                NodeContent::TypeNode(TypeNode {
+                    kind: TypeKind::Int,
+                })
+            };
+            let (param, ret) = (graph.add_node(int_ty()), graph.add_node(int_ty()));
+            let fn_ty = graph.add_node(NodeContent::TypeNode(TypeNode {
+                kind: TypeKind::Function {
+                    param_type_id: param,
+                    return_type_id: ret,
+                },
+            }));
+            let outer = LambdaBuilder::build(&mut graph, "f", |graph, f| {
+                f.set_annotation(Some(fn_ty));
+                let f_use = f.reference(graph);
+                let one = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+                graph.add_node(NodeContent::TermApplication(TermApplication {
+                    function_node_id: f_use,
+                    argument_node_id: one,
+                }))
+            });
+
+            let x_ty = graph.add_node(int_ty());
+            let inner = LambdaBuilder::build(&mut graph, "x", |graph, x| {
+                x.set_annotation(Some(x_ty));
+                let x_use = x.reference(graph);
+                let two = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 2 }));
+                let product = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+                    op_name: "mul".to_string(),
+                    argument_node_ids: vec![x_use, two],
+                }));
+                let inner_one = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+                graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+                    op_name: "add".to_string(),
+                    argument_node_ids: vec![product, inner_one],
+                }))
+            });
+            let app = graph.add_node(NodeContent::TermApplication(TermApplication {
+                function_node_id: outer,
+                argument_node_id: inner,
+            }));
+            graph.set_root(app);
+            graph
+        };
+
+        let first = upir_core::print_module(&lower_graph_to_upir(&build()).unwrap());
+        let second = upir_core::print_module(&lower_graph_to_upir(&build()).unwrap());
+        assert_eq!(first, second);
+        let names: Vec<_> = lower_graph_to_upir(&build())
+            .unwrap()
+            .functions
+            .into_iter()
+            .map(|function| function.name)
+            .collect();
+        assert_eq!(names, vec!["main", "lambda_8", "lambda_16"]);
+    }
+
+    #[test]
+    fn unannotated_parameters_take_their_inferred_type() {
+        let mut graph = parser_core::parse_str("((x) => x + 1)(2)").unwrap();
+        let lambda = graph
+            .nodes()
+            .find(|node| matches!(node.content, NodeContent::TermLambda(_)))
+            .unwrap()
+            .node_id;
+        assert_eq!(
+            lower_graph_to_upir(&graph),
+            Err(LoweringError::UnresolvedType { node_id: lambda })
+        );
+
+        type_checker_l1::check_and_annotate_graph(&mut graph).unwrap();
+        let module = lower_graph_to_upir(&graph).unwrap();
+        let lifted = module.function(&lambda_name(lambda)).unwrap();
+        assert_eq!(lifted.params[0].ty, Type::I64);
+    }
+
+    #[test]
+    fn unsupported_nodes_report_their_kind() {
+        let graph = parser_core::parse_str("perform('IO', 1)").unwrap();
+        let error = lower_graph_to_upir(&graph).unwrap_err();
+        assert_eq!(
+            error,
+            LoweringError::UnsupportedNode {
+                node_id: graph.root().unwrap(),
+                kind: asg_core::NodeKind::EffectPerform,
+            }
+        );
+        assert_eq!(error.code(), "U004");
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "cannot lower node {}: no lowering for EffectPerform nodes",
+                graph.root().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn conditionals_branch_and_letrec_is_unsupported() {
+        let graph = parser_core::parse_str("if 1 < 2 then 3 else 4").unwrap();
+        let module = lower_graph_to_upir(&graph).unwrap();
+        let main = module.function("main").unwrap();
+        let labels: Vec<_> = main
+            .blocks
+            .iter()
+            .map(|block| block.label.clone())
+            .collect();
+        let root = graph.root().unwrap();
+        assert_eq!(
+            labels[1..],
+            [
+                format!("if_then_{}", root),
+                format!("if_else_{}", root),
+                format!("if_end_{}", root),
+            ]
+        );
+        assert_eq!(main.return_type, Type::I64);
+
+        let mut graph = parser_core::parse_str("letrec f = (x: Int) => f(x) in f").unwrap();
+        let fix = graph
+            .nodes()
+            .find(
+                |node| matches!(&node.content, NodeContent::PrimitiveOp(op) if op.op_name == "fix"),
+            )
+            .unwrap()
+            .node_id;
+        graph.set_root(fix);
+        assert!(matches!(
+            lower_graph_to_upir(&graph),
+            Err(LoweringError::Unsupported { node_id, .. }) if node_id == fix
+        ));
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        assert_eq!(
+            lower_graph_to_upir(&AsgGraph::new()),
+            Err(LoweringError::MissingRoot)
+        );
+    }
+}
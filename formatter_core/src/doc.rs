@@ -0,0 +1,114 @@
+//! A Wadler-style pretty-printing algebra.
+//!
+//! A [`Doc`] describes text with optional line breaks. A [`Doc::group`] is
+//! laid out on one line if it fits in the remaining width, with every line
+//! break inside it printed flat; otherwise its own breaks become newlines
+//! and nested groups get the same choice. Breaks are indented by the sum of
+//! the enclosing [`Doc::nest`]s.
+
+/// A document to lay out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Doc {
+    Text(String),
+    /// A line break, printed as `flat` when its group fits on one line.
+    Line {
+        flat: &'static str,
+    },
+    Nest(usize, Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+impl Doc {
+    pub(crate) fn text(text: impl Into<String>) -> Doc {
+        Doc::Text(text.into())
+    }
+
+    /// A break that is a space when flat.
+    pub(crate) fn line() -> Doc {
+        Doc::Line { flat: " " }
+    }
+
+    /// A break that disappears when flat.
+    pub(crate) fn softline() -> Doc {
+        Doc::Line { flat: "" }
+    }
+
+    pub(crate) fn nest(indent: usize, doc: Doc) -> Doc {
+        Doc::Nest(indent, Box::new(doc))
+    }
+
+    pub(crate) fn group(doc: Doc) -> Doc {
+        Doc::Group(Box::new(doc))
+    }
+
+    pub(crate) fn concat(docs: impl IntoIterator<Item = Doc>) -> Doc {
+        Doc::Concat(docs.into_iter().collect())
+    }
+
+    /// Lays the document out in `width` columns. Text wider than that is
+    /// never split, so lines may still overflow.
+    pub(crate) fn render(&self, width: usize) -> String {
+        let width = isize::try_from(width).unwrap_or(isize::MAX);
+        let mut output = String::new();
+        let mut column = 0;
+        let mut stack = vec![(0, Mode::Break, self)];
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    output.push_str(text);
+                    column += text.chars().count();
+                }
+                Doc::Line { flat } if mode == Mode::Flat => {
+                    output.push_str(flat);
+                    column += flat.len();
+                }
+                Doc::Line { .. } => {
+                    output.push('\n');
+                    output.extend(std::iter::repeat_n(' ', indent));
+                    column = indent;
+                }
+                Doc::Nest(extra, doc) => stack.push((indent + extra, mode, doc)),
+                Doc::Group(doc) => {
+                    let flat = mode == Mode::Flat
+                        || fits(width.saturating_sub(column as isize), doc, &stack);
+                    let mode = if flat { Mode::Flat } else { Mode::Break };
+                    stack.push((indent, mode, doc));
+                }
+                Doc::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+                }
+            }
+        }
+        output
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Whether `doc` laid out flat, followed by the rest of the line from
+/// `rest`, fits in `width` columns.
+fn fits(mut width: isize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+    while width >= 0 {
+        let Some((mode, doc)) = stack
+            .pop()
+            .or_else(|| rest.next().map(|&(_, mode, doc)| (mode, doc)))
+        else {
+            return true;
+        };
+        match doc {
+            Doc::Text(text) => width -= text.chars().count() as isize,
+            Doc::Line { flat } if mode == Mode::Flat => width -= flat.len() as isize,
+            Doc::Line { .. } => return true,
+            Doc::Nest(_, doc) | Doc::Group(doc) => stack.push((mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+        }
+    }
+    false
+}
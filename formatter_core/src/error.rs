@@ -0,0 +1,30 @@
+//! Errors reported while formatting an ASG.
+
+use thiserror::Error;
+
+/// Reasons a graph cannot be printed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FormatError {
+    /// A referenced node is not in the graph.
+    #[error("node {0} referenced but not present in the graph")]
+    MissingNode(u64),
+
+    /// A type node appears where a term is expected.
+    #[error("node {0} is a type where a term is expected")]
+    NotATerm(u64),
+
+    /// A term appears where a type annotation is expected.
+    #[error("node {0} is not a type node")]
+    NotAType(u64),
+
+    /// Macro definitions have no concrete syntax.
+    #[error("node {0} is a macro definition, which cannot be printed")]
+    MacroDefinition(u64),
+
+    /// The node is reachable from itself, so the term would be infinite.
+    #[error("node {0} is part of a cycle")]
+    CycleDetected(u64),
+}
+
+/// Convenience alias for formatting results.
+pub type Result<T> = std::result::Result<T, FormatError>;
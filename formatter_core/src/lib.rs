@@ -1,14 +1,801 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
+//! Pretty printer from the ASG back to the minimal Synapse text format.
+//!
+//! The output uses the same concrete syntax that `parser_core` accepts, with
+//! the fewest parentheses needed to preserve the tree's structure. Subterms
+//! shared by several parents are printed once, bound with `let`, and an
+//! application of a lambda literal, which is what the parser makes of a
+//! `let`, is printed as one. A `let` of `fix((f) => value)` under the same
+//! name is printed as the `letrec` it came from.
+
+mod doc;
+pub mod error;
+mod sharing;
+
+use std::collections::{HashMap, HashSet};
+
+use asg_core::{AsgGraph, NodeContent, TermApplication, TermLambda, TypeKind, hash_graph};
+
+pub use error::{FormatError, Result};
+
+use crate::doc::Doc;
+use crate::sharing::Sharing;
+
+/// Layout options for [`format_asg_with_config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatConfig {
+    /// Columns a line may take before terms are broken across lines.
+    pub max_width: usize,
+    /// Extra spaces for each level of nesting on continuation lines.
+    pub indent: usize,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            max_width: 80,
+            indent: 4,
+        }
+    }
+}
+
+/// Formats the term rooted at `root_id` on a single line.
+pub fn format_asg(graph: &AsgGraph, root_id: u64) -> Result<String> {
+    let config = FormatConfig {
+        max_width: usize::MAX,
+        ..FormatConfig::default()
+    };
+    format_asg_with_config(graph, root_id, &config)
+}
+
+/// Formats the term rooted at `root_id`, breaking lambdas, applications and
+/// operators across lines where a line would exceed `config.max_width`.
+/// Each term stays on one line if it fits; a broken operator chain puts its
+/// operators at the start of the continuation lines.
+pub fn format_asg_with_config(
+    graph: &AsgGraph,
+    root_id: u64,
+    config: &FormatConfig,
+) -> Result<String> {
+    let mut printer = PrettyPrinter {
+        graph,
+        indent: config.indent,
+        sharing: Sharing::analyse(graph, root_id)?,
+        names: HashMap::new(),
+        taken: graph
+            .nodes()
+            .filter_map(|node| match &node.content {
+                NodeContent::TermVariable(var) => Some(var.name.as_str()),
+                _ => None,
+            })
+            .collect(),
+        next_temporary: 0,
+    };
+    let doc = printer.term(root_id, Precedence::Expr)?;
+    Ok(doc.render(config.max_width))
+}
+
+/// Whether formatting the term at `root_id` and parsing the text back gives
+/// a structurally identical term, as compared by [`hash_graph`]. Text that
+/// does not parse counts as a difference. Shared subterms come back as
+/// `let` applications, so graphs with sharing never compare equal.
+pub fn format_and_reparse_equivalent(graph: &AsgGraph, root_id: u64) -> Result<bool> {
+    let formatted = format_asg(graph, root_id)?;
+    let Ok(reparsed) = parser_core::parse_str(&formatted) else {
+        return Ok(false);
+    };
+    Ok(reparsed
+        .root()
+        .is_some_and(|root| hash_graph(&reparsed, root) == hash_graph(graph, root_id)))
+}
+
+/// Binding strength of a syntactic position, loosest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    /// Lambdas and assignments.
+    Expr,
+    Or,
+    And,
+    Comparison,
+    Additive,
+    Multiplicative,
+    Unary,
+    Postfix,
+    Atom,
+}
+
+impl Precedence {
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::Expr => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Additive,
+            Precedence::Additive => Precedence::Multiplicative,
+            Precedence::Multiplicative => Precedence::Unary,
+            Precedence::Unary => Precedence::Postfix,
+            Precedence::Postfix | Precedence::Atom => Precedence::Atom,
+        }
+    }
+}
+
+/// Operator symbol and precedence of a binary primitive.
+fn binary_operator(op_name: &str) -> Option<(&'static str, Precedence)> {
+    let operator = match op_name {
+        "or" => ("||", Precedence::Or),
+        "and" => ("&&", Precedence::And),
+        "eq" => ("==", Precedence::Comparison),
+        "ne" => ("!=", Precedence::Comparison),
+        "lt" => ("<", Precedence::Comparison),
+        "le" => ("<=", Precedence::Comparison),
+        "gt" => (">", Precedence::Comparison),
+        "ge" => (">=", Precedence::Comparison),
+        "add" => ("+", Precedence::Additive),
+        "sub" => ("-", Precedence::Additive),
+        "mul" => ("*", Precedence::Multiplicative),
+        "div" => ("/", Precedence::Multiplicative),
+        "mod" => ("%", Precedence::Multiplicative),
+        _ => return None,
+    };
+    Some(operator)
+}
+
+struct PrettyPrinter<'g> {
+    graph: &'g AsgGraph,
+    indent: usize,
+    sharing: Sharing,
+    /// Names of the shared terms bound so far.
+    names: HashMap<u64, String>,
+    /// Variable names in the graph, which temporaries must not shadow.
+    taken: HashSet<&'g str>,
+    next_temporary: usize,
+}
+
+impl<'g> PrettyPrinter<'g> {
+    fn content(&self, node_id: u64) -> Result<&'g NodeContent> {
+        self.graph
+            .get_node(node_id)
+            .map(|node| &node.content)
+            .ok_or(FormatError::MissingNode(node_id))
+    }
+
+    /// Lays out the term `node_id` for a position requiring at least
+    /// `context`, parenthesising it if it binds more loosely.
+    fn term(&mut self, node_id: u64, context: Precedence) -> Result<Doc> {
+        if let Some(name) = self.names.get(&node_id) {
+            return Ok(Doc::text(name.clone()));
+        }
+        let content = self.content(node_id)?;
+        let bindings = self.sharing.bindings(node_id).to_vec();
+        let precedence = if bindings.is_empty() && self.let_binding(content)?.is_none() {
+            term_precedence(content)
+        } else {
+            Precedence::Expr
+        };
+
+        let mut doc = if bindings.is_empty() {
+            self.term_content(node_id, content)?
+        } else {
+            let mut docs = Vec::new();
+            for shared in bindings {
+                let name = self.temporary();
+                let value = self.term(shared, Precedence::Expr)?;
+                docs.push(Doc::group(Doc::concat([
+                    Doc::text(format!("let {} =", name)),
+                    self.indented(Doc::concat([Doc::line(), value])),
+                    Doc::text(" in"),
+                ])));
+                docs.push(Doc::line());
+                self.names.insert(shared, name);
+            }
+            docs.push(self.term_content(node_id, content)?);
+            Doc::group(Doc::concat(docs))
+        };
+        if precedence < context {
+            doc = Doc::concat([Doc::text("("), doc, Doc::text(")")]);
+        }
+        Ok(doc)
+    }
+
+    /// The lambda of an application that reads as `let x = value in body`:
+    /// one whose callee is a lambda literal printed in place, without
+    /// declared effects, which `let` has no syntax for.
+    fn let_binding(&self, content: &'g NodeContent) -> Result<Option<&'g TermLambda>> {
+        let NodeContent::TermApplication(TermApplication {
+            function_node_id, ..
+        }) = content
+        else {
+            return Ok(None);
+        };
+        let in_place = !self.names.contains_key(function_node_id)
+            && !self.sharing.is_shared(*function_node_id)
+            && self.sharing.bindings(*function_node_id).is_empty();
+        Ok(match self.content(*function_node_id)? {
+            NodeContent::TermLambda(lambda) if in_place && lambda.effect_annotation.is_none() => {
+                Some(lambda)
+            }
+            _ => None,
+        })
+    }
+
+    /// A fresh `tmp_N` name that no variable in the graph uses.
+    fn temporary(&mut self) -> String {
+        loop {
+            let name = format!("tmp_{}", self.next_temporary);
+            self.next_temporary += 1;
+            if !self.taken.contains(name.as_str()) {
+                return name;
+            }
+        }
+    }
+
+    fn indented(&self, doc: Doc) -> Doc {
+        Doc::nest(self.indent, doc)
+    }
+
+    /// `open`, then `items` separated by commas, then `close`; broken, each
+    /// item goes on its own indented line.
+    fn bracketed(&self, open: String, items: Vec<Doc>, close: &str) -> Doc {
+        let mut inner = vec![Doc::softline()];
+        for (index, item) in items.into_iter().enumerate() {
+            if index > 0 {
+                inner.extend([Doc::text(","), Doc::line()]);
+            }
+            inner.push(item);
+        }
+        Doc::group(Doc::concat([
+            Doc::text(open),
+            self.indented(Doc::concat(inner)),
+            Doc::softline(),
+            Doc::text(close),
+        ]))
+    }
+
+    fn term_content(&mut self, node_id: u64, content: &'g NodeContent) -> Result<Doc> {
+        let doc = match content {
+            NodeContent::TermVariable(var) => Doc::text(var.name.clone()),
+            NodeContent::LiteralInt(lit) => Doc::text(lit.value.to_string()),
+            NodeContent::LiteralBool(lit) => Doc::text(lit.value.to_string()),
+            NodeContent::LiteralUnit(_) => Doc::text("()"),
+            NodeContent::TermLambda(lambda) => {
+                // Directly nested lambdas collapse into `(x)(y) => body`,
+                // unless an inner one declares effects of its own or is
+                // printed with bindings or by name.
+                let effects = &lambda.effect_annotation;
+                let mut lambda = lambda;
+                let mut header = Vec::new();
+                loop {
+                    header.push(Doc::text("("));
+                    header.push(self.term(lambda.binder_variable_node_id, Precedence::Atom)?);
+                    if let Some(annotation) = lambda.type_annotation_id {
+                        let mut ty = String::from(": ");
+                        self.type_node(annotation, TypePosition::Anywhere, &mut ty)?;
+                        header.push(Doc::text(ty));
+                    }
+                    header.push(Doc::text(")"));
+                    match self.content(lambda.body_node_id)? {
+                        NodeContent::TermLambda(inner)
+                            if inner.effect_annotation.is_none()
+                                && !self.sharing.is_shared(lambda.body_node_id)
+                                && self.sharing.bindings(lambda.body_node_id).is_empty() =>
+                        {
+                            lambda = inner
+                        }
+                        _ => break,
+                    }
+                }
+                if let Some(effects) = effects {
+                    let effects: Vec<_> = effects.iter().map(|name| effect_name(name)).collect();
+                    header.push(Doc::text(format!(" with [{}]", effects.join(", "))));
+                }
+                header.push(Doc::text(" =>"));
+                let body = self.term(lambda.body_node_id, Precedence::Expr)?;
+                header.push(self.indented(Doc::concat([Doc::line(), body])));
+                Doc::group(Doc::concat(header))
+            }
+            NodeContent::TermApplication(app) => match self.let_binding(content)? {
+                Some(lambda) => self.let_term(lambda, app.argument_node_id)?,
+                None => {
+                    let function = self.term(app.function_node_id, Precedence::Postfix)?;
+                    let argument = self.term(app.argument_node_id, Precedence::Expr)?;
+                    Doc::concat([
+                        function,
+                        self.bracketed("(".to_string(), vec![argument], ")"),
+                    ])
+                }
+            },
+            NodeContent::PrimitiveOp(op) => {
+                match (binary_operator(&op.op_name), &op.argument_node_ids[..]) {
+                    (Some((symbol, precedence)), [lhs, rhs]) => {
+                        // Left associative; comparisons do not chain at all.
+                        let lhs_context = if precedence == Precedence::Comparison {
+                            precedence.next()
+                        } else {
+                            precedence
+                        };
+                        let lhs = self.term(*lhs, lhs_context)?;
+                        let rhs = self.term(*rhs, precedence.next())?;
+                        // A chain nests to the left, so each operator breaks
+                        // only if the chain up to it does not fit.
+                        Doc::group(Doc::concat([
+                            lhs,
+                            self.indented(Doc::concat([
+                                Doc::line(),
+                                Doc::text(format!("{} ", symbol)),
+                                rhs,
+                            ])),
+                        ]))
+                    }
+                    (None, [operand]) if op.op_name == "not" => {
+                        Doc::concat([Doc::text("not "), self.term(*operand, Precedence::Unary)?])
+                    }
+                    (None, [condition, yes, no]) if op.op_name == "if" => {
+                        let condition = self.term(*condition, Precedence::Expr)?;
+                        let yes = self.term(*yes, Precedence::Expr)?;
+                        let no = self.term(*no, Precedence::Expr)?;
+                        Doc::group(Doc::concat([
+                            Doc::text("if "),
+                            condition,
+                            self.indented(Doc::concat([Doc::line(), Doc::text("then "), yes])),
+                            self.indented(Doc::concat([Doc::line(), Doc::text("else "), no])),
+                        ]))
+                    }
+                    (_, arguments) => {
+                        let arguments = arguments
+                            .iter()
+                            .map(|argument| self.term(*argument, Precedence::Expr))
+                            .collect::<Result<Vec<_>>>()?;
+                        self.bracketed(format!("{}(", op.op_name), arguments, ")")
+                    }
+                }
+            }
+            NodeContent::TermRef(term) => Doc::concat([
+                Doc::text("ref "),
+                self.term(term.init_value_node_id, Precedence::Unary)?,
+            ]),
+            NodeContent::TermDeref(term) => Doc::concat([
+                Doc::text("!"),
+                self.term(term.ref_node_id, Precedence::Unary)?,
+            ]),
+            NodeContent::TermAssign(term) => {
+                let target = self.term(term.ref_node_id, Precedence::Or)?;
+                let value = self.term(term.value_node_id, Precedence::Expr)?;
+                Doc::group(Doc::concat([
+                    target,
+                    Doc::text(" :="),
+                    self.indented(Doc::concat([Doc::line(), value])),
+                ]))
+            }
+            NodeContent::EffectPerform(perform) => {
+                let value = self.term(perform.value_node_id, Precedence::Expr)?;
+                Doc::group(Doc::concat([
+                    Doc::text(format!("perform({},", quote(&perform.effect_name))),
+                    self.indented(Doc::concat([Doc::line(), value])),
+                    Doc::text(")"),
+                ]))
+            }
+            // Written the way the macro expander recognises a call.
+            NodeContent::TermMacroInvocation(invocation) => {
+                let mut doc = vec![Doc::text(invocation.macro_name.clone())];
+                for argument in &invocation.argument_node_ids {
+                    let argument = self.term(*argument, Precedence::Expr)?;
+                    doc.push(self.bracketed("(".to_string(), vec![argument], ")"));
+                }
+                Doc::concat(doc)
+            }
+            NodeContent::TermMacroDefinition(_) => {
+                return Err(FormatError::MacroDefinition(node_id));
+            }
+            NodeContent::TypeNode(_) => return Err(FormatError::NotATerm(node_id)),
+        };
+        Ok(doc)
+    }
+
+    /// The generator of a `letrec` binding `lambda`'s parameter to
+    /// `value_id`: a lambda literal printed in place, binding the same name,
+    /// passed to `fix`.
+    fn letrec_generator(
+        &self,
+        lambda: &'g TermLambda,
+        value_id: u64,
+    ) -> Result<Option<&'g TermLambda>> {
+        let NodeContent::PrimitiveOp(op) = self.content(value_id)? else {
+            return Ok(None);
+        };
+        let (&[generator_id], "fix") = (&op.argument_node_ids[..], op.op_name.as_str()) else {
+            return Ok(None);
+        };
+        let in_place = [value_id, generator_id].iter().all(|id| {
+            !self.names.contains_key(id)
+                && !self.sharing.is_shared(*id)
+                && self.sharing.bindings(*id).is_empty()
+        });
+        let NodeContent::TermLambda(generator) = self.content(generator_id)? else {
+            return Ok(None);
+        };
+        let same_name = matches!(
+            (
+                self.content(lambda.binder_variable_node_id)?,
+                self.content(generator.binder_variable_node_id)?,
+            ),
+            (NodeContent::TermVariable(name), NodeContent::TermVariable(recursive))
+                if name.name == recursive.name
+        );
+        Ok((in_place && same_name && generator.effect_annotation.is_none()).then_some(generator))
+    }
+
+    /// `let x = value in body` for `lambda` applied to `value_id`, or
+    /// `letrec` if the value is the fixed point of a generator for `x`.
+    fn let_term(&mut self, lambda: &'g TermLambda, value_id: u64) -> Result<Doc> {
+        let (keyword, value_id) = match self.letrec_generator(lambda, value_id)? {
+            Some(generator) => ("letrec ", generator.body_node_id),
+            None => ("let ", value_id),
+        };
+        let mut header = vec![
+            Doc::text(keyword),
+            self.term(lambda.binder_variable_node_id, Precedence::Atom)?,
+        ];
+        if let Some(annotation) = lambda.type_annotation_id {
+            let mut ty = String::from(": ");
+            self.type_node(annotation, TypePosition::Anywhere, &mut ty)?;
+            header.push(Doc::text(ty));
+        }
+        header.push(Doc::text(" ="));
+        let value = self.term(value_id, Precedence::Expr)?;
+        header.push(self.indented(Doc::concat([Doc::line(), value])));
+        header.push(Doc::text(" in"));
+        let body = self.term(lambda.body_node_id, Precedence::Expr)?;
+        Ok(Doc::group(Doc::concat([
+            Doc::group(Doc::concat(header)),
+            Doc::line(),
+            body,
+        ])))
+    }
+
+    /// Writes a type in `position` to `out`. Types are never broken across
+    /// lines.
+    fn type_node(&self, node_id: u64, position: TypePosition, out: &mut String) -> Result<()> {
+        let NodeContent::TypeNode(ty) = self.content(node_id)? else {
+            return Err(FormatError::NotAType(node_id));
+        };
+        match ty.kind {
+            TypeKind::Int => out.push_str("Int"),
+            TypeKind::Bool => out.push_str("Bool"),
+            TypeKind::Unit => out.push_str("Unit"),
+            TypeKind::Ref { element_type_id } => {
+                // `Ref` takes a whole type, arrows included, so on the left
+                // of an arrow it must be closed off.
+                let parenthesise = position == TypePosition::Parameter;
+                if parenthesise {
+                    out.push('(');
+                }
+                out.push_str("Ref ");
+                self.type_node(element_type_id, TypePosition::RefElement, out)?;
+                if parenthesise {
+                    out.push(')');
+                }
+            }
+            TypeKind::Function {
+                param_type_id,
+                return_type_id,
+            } => {
+                let parenthesise = position != TypePosition::Anywhere;
+                if parenthesise {
+                    out.push('(');
+                }
+                self.type_node(param_type_id, TypePosition::Parameter, out)?;
+                out.push_str(" -> ");
+                self.type_node(return_type_id, TypePosition::Anywhere, out)?;
+                if parenthesise {
+                    out.push(')');
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a type appears, for deciding on parentheses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TypePosition {
+    Anywhere,
+    /// The left of an arrow.
+    Parameter,
+    /// The element of `Ref`.
+    RefElement,
+}
+
+/// A string literal for `text`. Strings have no escapes, so a name with a
+/// single quote is written in double quotes.
+fn quote(text: &str) -> String {
+    if text.contains('\'') {
+        format!("\"{}\"", text)
+    } else {
+        format!("'{}'", text)
+    }
+}
+
+/// An effect name in a `with [..]` list, quoted unless it is an identifier.
+fn effect_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if identifier {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+fn term_precedence(content: &NodeContent) -> Precedence {
+    match content {
+        NodeContent::TermLambda(_) | NodeContent::TermAssign(_) => Precedence::Expr,
+        NodeContent::PrimitiveOp(op) => match binary_operator(&op.op_name) {
+            Some((_, precedence)) if op.argument_node_ids.len() == 2 => precedence,
+            _ if op.op_name == "not" && op.argument_node_ids.len() == 1 => Precedence::Unary,
+            _ if op.op_name == "if" && op.argument_node_ids.len() == 3 => Precedence::Expr,
+            _ => Precedence::Postfix,
+        },
+        NodeContent::TermRef(_) | NodeContent::TermDeref(_) => Precedence::Unary,
+        NodeContent::LiteralInt(lit) if lit.value < 0 => Precedence::Unary,
+        NodeContent::TermApplication(_) => Precedence::Postfix,
+        NodeContent::TermMacroInvocation(invocation)
+            if !invocation.argument_node_ids.is_empty() =>
+        {
+            Precedence::Postfix
+        }
+        _ => Precedence::Atom,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn round_trip(source: &str) -> String {
+        let graph = parser_core::parse_str(source).unwrap();
+        format_asg(&graph, graph.root().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn canonical_programs_round_trip() {
+        for source in [
+            "(x: Int) => x + 1",
+            "(f: (Int -> Int) -> Bool) => f((y) => y * 2)",
+            "(r: Ref Int) => r := !r - 1",
+            "perform('IO', ())",
+            "(a) => (a + 1) * 2 == 6 && not false",
+            "1 - (2 - 3)",
+        ] {
+            assert_eq!(round_trip(source), source);
+        }
+    }
+
+    #[test]
+    fn redundant_parentheses_are_dropped() {
+        assert_eq!(round_trip("(f) => (f)((1 * 2) + 3)"), "(f) => f(1 * 2 + 3)");
+    }
+
+    #[test]
+    fn applied_lambdas_print_as_let() {
+        for source in [
+            "let x = ref 1 in x := !x + 1",
+            "let f: Int -> Int = (y) => y * 2 in let z = f(1) in z + z",
+            "(let x = 1 in x) + 2",
+            "(f) => let y = f in y",
+            "letrec f = (x) => if x == 0 then 0 else f(x - 1) in f(3)",
+            "(if true then 1 else 2) + 1",
+        ] {
+            assert_eq!(round_trip(source), source);
+        }
+        assert_eq!(
+            round_trip("((x) => (x))((1 * 2) + 3)"),
+            "let x = 1 * 2 + 3 in x"
+        );
+        // `let` has no syntax for effects, so the application stays.
+        assert_eq!(
+            round_trip("((x) with [IO] => x)(1)"),
+            "((x) with [IO] => x)(1)"
+        );
+    }
+
+    #[test]
+    fn nested_lambdas_collapse_into_multi_parameter_sugar() {
+        assert_eq!(
+            round_trip("(x: Int)(y: Int) => x + y"),
+            "(x: Int)(y: Int) => x + y"
+        );
+        assert_eq!(round_trip("(x) => (y) => x"), "(x)(y) => x");
+    }
+
+    #[test]
+    fn effect_annotations_stay_on_their_lambda() {
+        assert_eq!(
+            round_trip("(x)(y) with [IO, State] => x"),
+            "(x)(y) with [IO, State] => x"
+        );
+        assert_eq!(
+            round_trip("(x) => (y) with [IO] => perform('IO', x)"),
+            "(x) => (y) with [IO] => perform('IO', x)"
+        );
+    }
+
+    #[test]
+    fn shared_subterms_are_bound_once_with_let() {
+        use asg_core::{LiteralInt, PrimitiveOp};
+
+        // Each level adds the previous one to itself, so inlining would
+        // print the innermost sum 2^depth times. Each binding sits at the
+        // only term using it.
+        let mut graph = AsgGraph::new();
+        let mut term = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+        for _ in 0..3 {
+            term = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+                op_name: "add".to_string(),
+                argument_node_ids: vec![term, term],
+            }));
+        }
+        let doubled = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "mul".to_string(),
+            argument_node_ids: vec![term, term],
+        }));
+        let formatted = format_asg(&graph, doubled).unwrap();
+        assert_eq!(
+            formatted,
+            "let tmp_0 = let tmp_1 = let tmp_2 = 1 + 1 in tmp_2 + tmp_2 in tmp_1 + tmp_1 \
+             in tmp_0 * tmp_0"
+        );
+
+        // The output parses back, with each binding a lambda application.
+        let reparsed = parser_core::parse_str(&formatted).unwrap();
+        assert!(format_asg(&reparsed, reparsed.root().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn bindings_under_a_lambda_stay_inside_its_scope() {
+        use asg_core::{LambdaBuilder, PrimitiveOp};
+
+        let mut graph = AsgGraph::new();
+        let mut scope = LambdaBuilder::new(&mut graph, "x".to_string());
+        let x = scope.reference(&mut graph);
+        let square = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "mul".to_string(),
+            argument_node_ids: vec![x, x],
+        }));
+        let body = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "sub".to_string(),
+            argument_node_ids: vec![square, square],
+        }));
+        let lambda = scope.finish(&mut graph, body);
+        assert_eq!(
+            format_asg(&graph, lambda).unwrap(),
+            "(x) => let tmp_0 = x * x in tmp_0 - tmp_0"
+        );
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        use asg_core::TermDeref;
+
+        let mut graph = AsgGraph::new();
+        let node = graph.add_node(NodeContent::TermDeref(TermDeref { ref_node_id: 0 }));
+        if let Some(NodeContent::TermDeref(term)) =
+            graph.get_node_mut(node).map(|node| &mut node.content)
+        {
+            term.ref_node_id = node;
+        }
+        assert_eq!(
+            format_asg(&graph, node),
+            Err(FormatError::CycleDetected(node))
+        );
+    }
+
+    #[test]
+    fn long_terms_break_at_the_configured_width() {
+        let graph = parser_core::parse_str(
+            "(first)(second) => first(first + second + first * second)(second - first)",
+        )
+        .unwrap();
+        let config = FormatConfig {
+            max_width: 40,
+            indent: 2,
+        };
+        assert_eq!(
+            format_asg_with_config(&graph, graph.root().unwrap(), &config).unwrap(),
+            "(first)(second) =>\n  first(\n    first + second + first * second\n  )(second - first)"
+        );
+        let config = FormatConfig {
+            max_width: 24,
+            indent: 2,
+        };
+        assert_eq!(
+            format_asg_with_config(&graph, graph.root().unwrap(), &config).unwrap(),
+            "(first)(second) =>\n  first(\n    first + second\n      + first * second\n  )(second - first)"
+        );
+    }
+
+    #[test]
+    fn broken_output_parses_to_the_same_program() {
+        let source = "(f: Int -> Int)(r: Ref Int) => r := f(!r + 1) * f(perform('IO', !r))";
+        let graph = parser_core::parse_str(source).unwrap();
+        for max_width in [10, 30, 60] {
+            let config = FormatConfig {
+                max_width,
+                ..FormatConfig::default()
+            };
+            let formatted = format_asg_with_config(&graph, graph.root().unwrap(), &config).unwrap();
+            assert!(formatted.contains('\n'));
+            assert_eq!(round_trip(&formatted), source);
+        }
+    }
+
+    #[test]
+    fn sampled_terms_survive_a_round_trip() {
+        use asg_core::{EffectPerform, LiteralInt, PrimitiveOp, TermAssign, TermDeref, TermRef};
+
+        for source in [
+            "ref (ref x)",
+            "!(!r)",
+            "(r := 1) := 2",
+            "r := s := 1",
+            "(!r)(1)",
+            "!r(1)",
+            "f(-1)(ref -2)",
+            "1 - -2",
+            "-1 * -(3 + x)",
+            "not (a == b) || not not c",
+            "(a < b) == (c < d)",
+            "((x) => x) + ((r := 1) - 2)",
+            "(f: Ref (Int -> Int) -> Unit) with ['Custom:Log', IO] => ()",
+            "(f: (Ref Int) -> Ref Ref Int) => f",
+            "perform(\"Can't\", perform('IO', 1))",
+            "let x = ref 1 in x := !x + 1",
+        ] {
+            let graph = parser_core::parse_str(source).unwrap();
+            let root = graph.root().unwrap();
+            assert!(
+                format_and_reparse_equivalent(&graph, root).unwrap(),
+                "{} formatted as {}",
+                source,
+                format_asg(&graph, root).unwrap()
+            );
+        }
+
+        // Forms the parser does not produce directly.
+        let mut graph = AsgGraph::new();
+        let int = |graph: &mut AsgGraph, value| {
+            graph.add_node(NodeContent::LiteralInt(LiteralInt { value }))
+        };
+        let negative = int(&mut graph, -4);
+        let two = int(&mut graph, 2);
+        let deref = graph.add_node(NodeContent::TermDeref(TermDeref {
+            ref_node_id: negative,
+        }));
+        let reference = graph.add_node(NodeContent::TermRef(TermRef {
+            init_value_node_id: deref,
+        }));
+        let assign = graph.add_node(NodeContent::TermAssign(TermAssign {
+            ref_node_id: reference,
+            value_node_id: two,
+        }));
+        let nested = graph.add_node(NodeContent::TermAssign(TermAssign {
+            ref_node_id: assign,
+            value_node_id: negative,
+        }));
+        let perform = graph.add_node(NodeContent::EffectPerform(EffectPerform {
+            effect_name: "IO".to_string(),
+            value_node_id: nested,
+        }));
+        let negated = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "sub".to_string(),
+            argument_node_ids: vec![perform, negative],
+        }));
+        assert!(format_and_reparse_equivalent(&graph, negated).unwrap());
+    }
+
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn unit_literal_prints_as_empty_parentheses() {
+        assert_eq!(round_trip("( )"), "()");
     }
 }
@@ -0,0 +1,147 @@
+//! Finding subterms reachable along more than one path.
+//!
+//! The ASG is a DAG, so a subterm can have several parents. Printing it at
+//! each of them would duplicate it, exponentially so for nested sharing.
+//! Instead each shared term is bound once with `let`, placed at its
+//! immediate dominator: the closest node every path from the root to the
+//! term goes through, so the binding is in scope at all of its uses.
+
+use std::collections::{HashMap, HashSet};
+
+use asg_core::{AsgGraph, NodeContent};
+
+use crate::error::{FormatError, Result};
+
+/// Where the printer binds shared terms.
+#[derive(Debug, Default)]
+pub(crate) struct Sharing {
+    /// Shared terms to bind just before printing a node, keyed by that node,
+    /// each after the shared terms it contains.
+    bindings: HashMap<u64, Vec<u64>>,
+    shared: HashSet<u64>,
+}
+
+impl Sharing {
+    /// Analyses the graph reachable from `root_id`, failing on a cycle.
+    pub(crate) fn analyse(graph: &AsgGraph, root_id: u64) -> Result<Sharing> {
+        let mut walk = Walk {
+            graph,
+            visiting: HashSet::new(),
+            visited: HashSet::new(),
+            postorder: Vec::new(),
+            parents: HashMap::new(),
+        };
+        walk.visit(root_id)?;
+
+        // In a DAG every parent precedes its children in reverse postorder,
+        // so a node's dominator is the common dominator of its parents.
+        let position: HashMap<u64, usize> = walk
+            .postorder
+            .iter()
+            .enumerate()
+            .map(|(index, &node_id)| (node_id, index))
+            .collect();
+        let mut idom: HashMap<u64, u64> = HashMap::from([(root_id, root_id)]);
+        for &node_id in walk.postorder.iter().rev().skip(1) {
+            let dominator = walk.parents[&node_id]
+                .iter()
+                .copied()
+                .reduce(|a, b| common_dominator(&idom, &position, a, b))
+                .expect("reachable nodes other than the root have parents");
+            idom.insert(node_id, dominator);
+        }
+
+        let mut sharing = Sharing::default();
+        for &node_id in &walk.postorder {
+            let content = walk.content(node_id)?;
+            if walk.parents.get(&node_id).map_or(0, Vec::len) < 2 || !worth_binding(content) {
+                continue;
+            }
+            // A lambda's parameter is not in scope around the lambda itself,
+            // so bindings under a lambda go on its body.
+            let mut site = idom[&node_id];
+            if let NodeContent::TermLambda(lambda) = walk.content(site)? {
+                site = lambda.body_node_id;
+            }
+            sharing.bindings.entry(site).or_default().push(node_id);
+            sharing.shared.insert(node_id);
+        }
+        Ok(sharing)
+    }
+
+    /// The shared terms bound around `node_id`, innermost first.
+    pub(crate) fn bindings(&self, node_id: u64) -> &[u64] {
+        self.bindings.get(&node_id).map_or(&[], Vec::as_slice)
+    }
+
+    pub(crate) fn is_shared(&self, node_id: u64) -> bool {
+        self.shared.contains(&node_id)
+    }
+}
+
+/// Variables and literals are as short as any name, so repeating them is
+/// fine; types are not terms.
+fn worth_binding(content: &NodeContent) -> bool {
+    !matches!(
+        content,
+        NodeContent::TermVariable(_)
+            | NodeContent::LiteralInt(_)
+            | NodeContent::LiteralBool(_)
+            | NodeContent::LiteralUnit(_)
+            | NodeContent::TypeNode(_)
+    )
+}
+
+/// The closest common ancestor of `a` and `b` in the dominator tree, found
+/// by walking the later of the two up until they meet.
+fn common_dominator(
+    idom: &HashMap<u64, u64>,
+    position: &HashMap<u64, usize>,
+    mut a: u64,
+    mut b: u64,
+) -> u64 {
+    while a != b {
+        // Dominators sit later in postorder than the nodes they dominate.
+        if position[&a] < position[&b] {
+            a = idom[&a];
+        } else {
+            b = idom[&b];
+        }
+    }
+    a
+}
+
+struct Walk<'g> {
+    graph: &'g AsgGraph,
+    visiting: HashSet<u64>,
+    visited: HashSet<u64>,
+    postorder: Vec<u64>,
+    /// One entry per edge, so a node used twice by one parent counts twice.
+    parents: HashMap<u64, Vec<u64>>,
+}
+
+impl<'g> Walk<'g> {
+    fn content(&self, node_id: u64) -> Result<&'g NodeContent> {
+        self.graph
+            .get_node(node_id)
+            .map(|node| &node.content)
+            .ok_or(FormatError::MissingNode(node_id))
+    }
+
+    fn visit(&mut self, node_id: u64) -> Result<()> {
+        if self.visited.contains(&node_id) {
+            return Ok(());
+        }
+        if !self.visiting.insert(node_id) {
+            return Err(FormatError::CycleDetected(node_id));
+        }
+        for child in self.content(node_id)?.child_ids() {
+            self.parents.entry(child).or_default().push(node_id);
+            self.visit(child)?;
+        }
+        self.visiting.remove(&node_id);
+        self.visited.insert(node_id);
+        self.postorder.push(node_id);
+        Ok(())
+    }
+}
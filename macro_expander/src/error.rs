@@ -0,0 +1,43 @@
+//! Errors reported while expanding macros.
+
+use thiserror::Error;
+
+/// Reasons a macro cannot be defined or expanded.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MacroError {
+    /// A macro body graph has no root to expand.
+    #[error("macro '{0}' has no body")]
+    MissingBody(String),
+
+    /// An invocation names a macro that is not defined.
+    #[error("macro '{0}' is not defined")]
+    UndefinedMacro(String),
+
+    /// An invocation passes the wrong number of arguments.
+    #[error("macro '{name}' takes {expected} argument(s) but was given {found}")]
+    WrongArity {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// A node registered as a macro is not a `TermMacroDefinition`.
+    #[error("node {0} is not a macro definition")]
+    NotADefinition(u64),
+
+    /// A node referenced by a macro body or an invocation does not exist.
+    #[error("node {0} does not exist")]
+    MissingNode(u64),
+
+    /// A macro's expansion invokes the macro again, directly or through
+    /// others. `cycle` lists the macros from the first to its invocation.
+    #[error("recursive macro expansion: {}", cycle.join(" -> "))]
+    ExpansionError { cycle: Vec<String> },
+
+    /// Expansion produced more invocations than the expander allows.
+    #[error("macro expansion did not finish after {0} expansions")]
+    ExpansionLimit(usize),
+}
+
+/// Convenience alias for macro expansion results.
+pub type Result<T> = std::result::Result<T, MacroError>;
@@ -0,0 +1,590 @@
+//! Finding and expanding macro invocations.
+
+use std::collections::{HashMap, HashSet};
+
+use asg_core::{AsgGraph, NodeContent};
+
+use crate::error::{MacroError, Result};
+
+/// Expansions allowed in one [`MacroExpander::expand`] call by default.
+const DEFAULT_EXPANSION_LIMIT: usize = 10_000;
+
+/// A function macro: a body term and the names of its parameters.
+#[derive(Debug, Clone)]
+pub struct MacroDefinition {
+    name: String,
+    param_names: Vec<String>,
+    body: AsgGraph,
+    body_node_id: u64,
+}
+
+impl MacroDefinition {
+    /// A macro called `name` whose body is the root of `body`. Unbound
+    /// variables in the body named in `param_names` are its parameters;
+    /// other unbound variables are copied as they are.
+    pub fn new(
+        name: impl Into<String>,
+        param_names: impl IntoIterator<Item = impl Into<String>>,
+        body: AsgGraph,
+    ) -> Result<Self> {
+        let name = name.into();
+        let Some(body_node_id) = body.root() else {
+            return Err(MacroError::MissingBody(name));
+        };
+        Ok(Self {
+            name,
+            param_names: param_names.into_iter().map(Into::into).collect(),
+            body,
+            body_node_id,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn param_names(&self) -> &[String] {
+        &self.param_names
+    }
+
+    /// The index of the parameter `content` refers to, if it is one.
+    fn param_index(&self, content: &NodeContent) -> Option<usize> {
+        match content {
+            NodeContent::TermVariable(var) if var.definition_node_id == 0 => {
+                self.param_names.iter().position(|name| *name == var.name)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Expands invocations of the macros registered with it.
+#[derive(Debug)]
+pub struct MacroExpander {
+    macros: HashMap<String, MacroDefinition>,
+    expansion_limit: usize,
+    /// Suffix of the last generated name.
+    gensyms: u64,
+}
+
+impl Default for MacroExpander {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MacroExpander {
+    pub fn new() -> Self {
+        Self {
+            macros: HashMap::new(),
+            expansion_limit: DEFAULT_EXPANSION_LIMIT,
+            gensyms: 0,
+        }
+    }
+
+    /// Sets how many expansions one [`MacroExpander::expand`] call may
+    /// perform before giving up.
+    pub fn with_expansion_limit(mut self, limit: usize) -> Self {
+        self.expansion_limit = limit;
+        self
+    }
+
+    /// Registers `definition`, replacing any macro with the same name.
+    pub fn register(&mut self, definition: MacroDefinition) {
+        self.macros.insert(definition.name.clone(), definition);
+    }
+
+    /// Registers the `TermMacroDefinition` node `definition_node_id` of
+    /// `graph`, taking its name, parameters and body from the node.
+    pub fn register_function_macro(
+        &mut self,
+        graph: &AsgGraph,
+        definition_node_id: u64,
+    ) -> Result<()> {
+        let Some(NodeContent::TermMacroDefinition(node)) = content(graph, definition_node_id)
+        else {
+            return Err(MacroError::NotADefinition(definition_node_id));
+        };
+        let param_names = node
+            .parameter_node_ids
+            .iter()
+            .map(|&param| match content(graph, param) {
+                Some(NodeContent::TermVariable(var)) => Ok(var.name.clone()),
+                _ => Err(MacroError::MissingNode(param)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let body = extract_body(graph, definition_node_id, node.body_node_id)?;
+        self.register(MacroDefinition::new(node.name.clone(), param_names, body)?);
+        Ok(())
+    }
+
+    /// Expands every invocation reachable from the root, including those
+    /// that expansions introduce, and returns how many were expanded.
+    ///
+    /// Outer invocations are expanded before the invocations in their
+    /// arguments. The nodes of an expanded invocation are left in the graph
+    /// for [`AsgGraph::collect_garbage`].
+    ///
+    /// Each node an expansion creates remembers the chain of macros whose
+    /// expansion produced it. An invocation of a macro already in its chain
+    /// would recurse forever, and is reported with the cycle at once.
+    pub fn expand(&mut self, graph: &mut AsgGraph) -> Result<usize> {
+        let mut chains: HashMap<u64, Vec<String>> = HashMap::new();
+        let mut expanded = 0;
+        while let Some((node_id, name, args)) = self.find_invocation(graph)? {
+            let mut chain = chains.get(&node_id).cloned().unwrap_or_default();
+            if let Some(start) = chain.iter().position(|outer| *outer == name) {
+                let mut cycle = chain.split_off(start);
+                cycle.push(name);
+                return Err(MacroError::ExpansionError { cycle });
+            }
+            if expanded == self.expansion_limit {
+                return Err(MacroError::ExpansionLimit(expanded));
+            }
+            let copies = self.expand_function_macro(graph, node_id, &name, &args)?;
+            chain.push(name);
+            for copy in copies {
+                chains.insert(copy, chain.clone());
+            }
+            expanded += 1;
+        }
+        Ok(expanded)
+    }
+
+    /// The first invocation reachable from the root in pre-order, with the
+    /// macro's name and the argument nodes.
+    fn find_invocation(&self, graph: &AsgGraph) -> Result<Option<(u64, String, Vec<u64>)>> {
+        let mut seen = HashSet::new();
+        let mut pending: Vec<u64> = graph.root().into_iter().collect();
+        while let Some(node_id) = pending.pop() {
+            if !seen.insert(node_id) {
+                continue;
+            }
+            if let Some((name, args)) = self.invocation(graph, node_id)? {
+                return Ok(Some((node_id, name, args)));
+            }
+            if let Some(node) = graph.get_node(node_id) {
+                pending.extend(node.content.child_ids().into_iter().rev());
+            }
+        }
+        Ok(None)
+    }
+
+    /// The macro `node_id` invokes and its arguments, if it is an
+    /// invocation. A `TermMacroInvocation` must name a registered macro and
+    /// pass it the right number of arguments.
+    fn invocation(&self, graph: &AsgGraph, node_id: u64) -> Result<Option<(String, Vec<u64>)>> {
+        let Some(NodeContent::TermMacroInvocation(invocation)) = content(graph, node_id) else {
+            return Ok(self.applied_macro(graph, node_id));
+        };
+        let name = &invocation.macro_name;
+        let definition = self
+            .macros
+            .get(name)
+            .ok_or_else(|| MacroError::UndefinedMacro(name.clone()))?;
+        let args = &invocation.argument_node_ids;
+        if definition.param_names.len() != args.len() {
+            return Err(MacroError::WrongArity {
+                name: name.clone(),
+                expected: definition.param_names.len(),
+                found: args.len(),
+            });
+        }
+        Ok(Some((name.clone(), args.clone())))
+    }
+
+    /// Whether `node_id` applies an unbound variable naming a macro to as
+    /// many arguments as the macro has parameters.
+    fn applied_macro(&self, graph: &AsgGraph, node_id: u64) -> Option<(String, Vec<u64>)> {
+        let mut args = Vec::new();
+        let mut head = node_id;
+        loop {
+            match &graph.get_node(head)?.content {
+                NodeContent::TermApplication(app) => {
+                    args.push(app.argument_node_id);
+                    head = app.function_node_id;
+                }
+                NodeContent::TermVariable(var) if var.definition_node_id == 0 => {
+                    let definition = self.macros.get(&var.name)?;
+                    args.reverse();
+                    return (definition.param_names.len() == args.len())
+                        .then(|| (var.name.clone(), args));
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Replaces the invocation `node_id` of macro `name` with a copy of its
+    /// body in which parameter `i` is `args[i]`.
+    ///
+    /// Every lambda the body contains gets a fresh parameter name, so the
+    /// copy neither captures nor shadows a name from the call site. The
+    /// copies are located at the invocation. Returns the copies.
+    fn expand_function_macro(
+        &mut self,
+        graph: &mut AsgGraph,
+        node_id: u64,
+        name: &str,
+        args: &[u64],
+    ) -> Result<Vec<u64>> {
+        let definition = &self.macros[name];
+        let body = &definition.body;
+        let body_nodes = reachable(body, definition.body_node_id)?;
+
+        let mut used = variable_names(graph);
+        used.extend(variable_names(body));
+        let mut fresh_names = HashMap::new();
+        for &old in &body_nodes {
+            if let Some(NodeContent::TermLambda(lambda)) = content(body, old)
+                && let Some(NodeContent::TermVariable(binder)) =
+                    content(body, lambda.binder_variable_node_id)
+            {
+                self.gensyms += 1;
+                let mut fresh = format!("{}_{}", binder.name, self.gensyms);
+                while !used.insert(fresh.clone()) {
+                    self.gensyms += 1;
+                    fresh = format!("{}_{}", binder.name, self.gensyms);
+                }
+                fresh_names.insert(old, fresh);
+            }
+        }
+
+        let mut ids = HashMap::new();
+        let mut copies = Vec::new();
+        for &old in &body_nodes {
+            let Some(content) = content(body, old) else {
+                continue;
+            };
+            let new = match definition.param_index(content) {
+                Some(index) => args[index],
+                None => {
+                    let new = graph.add_node(content.clone());
+                    copies.push(new);
+                    new
+                }
+            };
+            ids.insert(old, new);
+        }
+
+        let location = graph.source_location(node_id).cloned();
+        for &new in &copies {
+            if let Some(node) = graph.get_node_mut(new) {
+                update_references(&mut node.content, &ids, &fresh_names);
+            }
+            if let Some(location) = &location {
+                graph.set_source_location(new, location.clone());
+            }
+        }
+        replace_references(graph, node_id, ids[&definition.body_node_id]);
+        Ok(copies)
+    }
+}
+
+/// Registers every macro defined in `graph`, in node id order, and expands
+/// the invocations reachable from the root. Returns how many were expanded.
+pub fn expand_macros(graph: &mut AsgGraph) -> Result<usize> {
+    let mut definitions: Vec<u64> = graph
+        .nodes()
+        .filter(|node| matches!(node.content, NodeContent::TermMacroDefinition(_)))
+        .map(|node| node.node_id)
+        .collect();
+    definitions.sort_unstable();
+    let mut expander = MacroExpander::new();
+    for definition in definitions {
+        expander.register_function_macro(graph, definition)?;
+    }
+    expander.expand(graph)
+}
+
+/// Copies the body `body_node_id` of the macro defined at
+/// `definition_node_id` into a graph of its own, rooted at the body.
+/// Parameter uses become unbound variables, as [`MacroDefinition::new`]
+/// expects.
+fn extract_body(graph: &AsgGraph, definition_node_id: u64, body_node_id: u64) -> Result<AsgGraph> {
+    let mut body = AsgGraph::new();
+    let mut ids = HashMap::new();
+    let nodes = reachable(graph, body_node_id)?;
+    for &old in &nodes {
+        if let Some(content) = content(graph, old) {
+            ids.insert(old, body.add_node(content.clone()));
+        }
+    }
+    for &new in ids.values() {
+        let Some(node) = body.get_node_mut(new) else {
+            continue;
+        };
+        if let NodeContent::TermVariable(var) = &mut node.content {
+            if var.definition_node_id == definition_node_id {
+                var.definition_node_id = 0;
+            } else if let Some(&binder) = ids.get(&var.definition_node_id) {
+                var.definition_node_id = binder;
+            }
+        }
+        node.content
+            .map_child_ids(|child| ids.get(&child).copied().unwrap_or(child));
+    }
+    body.set_root(ids[&body_node_id]);
+    Ok(body)
+}
+
+/// Nodes structurally reachable from `root_id`, in pre-order.
+fn reachable(graph: &AsgGraph, root_id: u64) -> Result<Vec<u64>> {
+    let mut seen = HashSet::new();
+    let mut order = Vec::new();
+    let mut pending = vec![root_id];
+    while let Some(node_id) = pending.pop() {
+        if !seen.insert(node_id) {
+            continue;
+        }
+        let node = graph
+            .get_node(node_id)
+            .ok_or(MacroError::MissingNode(node_id))?;
+        order.push(node_id);
+        pending.extend(node.content.child_ids().into_iter().rev());
+    }
+    Ok(order)
+}
+
+fn content(graph: &AsgGraph, node_id: u64) -> Option<&NodeContent> {
+    graph.get_node(node_id).map(|node| &node.content)
+}
+
+fn variable_names(graph: &AsgGraph) -> HashSet<String> {
+    graph
+        .nodes()
+        .filter_map(|node| match &node.content {
+            NodeContent::TermVariable(var) => Some(var.name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Points a copied node at the copies of its children, and renames and
+/// relinks a variable bound by a lambda of the body. `ids` maps body nodes
+/// to their copies and `fresh_names` maps body lambdas to their new
+/// parameter names.
+fn update_references(
+    content: &mut NodeContent,
+    ids: &HashMap<u64, u64>,
+    fresh_names: &HashMap<u64, String>,
+) {
+    if let NodeContent::TermVariable(var) = content
+        && let Some(fresh) = fresh_names.get(&var.definition_node_id)
+    {
+        var.name = fresh.clone();
+        var.definition_node_id = ids[&var.definition_node_id];
+    }
+    content.map_child_ids(|child| ids.get(&child).copied().unwrap_or(child));
+}
+
+/// Points every reference to `from`, including the root, at `to`.
+fn replace_references(graph: &mut AsgGraph, from: u64, to: u64) {
+    graph.map_nodes(|_, node| {
+        node.content
+            .map_child_ids(|child| if child == from { to } else { child });
+    });
+    if graph.root() == Some(from) {
+        graph.set_root(to);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asg_core::{
+        LambdaBuilder, LiteralInt, PrimitiveOp, TermApplication, TermMacroDefinition,
+        TermMacroInvocation, TermVariable,
+    };
+    use synapse_runtime::{Value, evaluate};
+
+    fn expander(name: &str, params: &[&str], body: &str) -> MacroExpander {
+        let body = parser_core::parse_str(body).unwrap();
+        let mut expander = MacroExpander::new();
+        expander.register(MacroDefinition::new(name, params.iter().copied(), body).unwrap());
+        expander
+    }
+
+    fn binder_names(graph: &AsgGraph) -> Vec<String> {
+        let mut names: Vec<String> = reachable(graph, graph.root().unwrap())
+            .unwrap()
+            .into_iter()
+            .filter_map(|node_id| match content(graph, node_id)? {
+                NodeContent::TermLambda(lambda) => {
+                    match content(graph, lambda.binder_variable_node_id)? {
+                        NodeContent::TermVariable(var) => Some(var.name.clone()),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn body_bindings_do_not_capture_call_site_names() {
+        let mut expander = expander("my_macro", &["a"], "let x = 2 in x + a");
+        let mut graph = parser_core::parse_str("let x = 1 in my_macro(x)").unwrap();
+
+        assert_eq!(expander.expand(&mut graph), Ok(1));
+        // Had the body's `x` captured the argument, this would be 2 + 2.
+        assert_eq!(evaluate(&graph), Ok(Value::Int(3)));
+        assert_eq!(binder_names(&graph), ["x", "x_1"]);
+    }
+
+    #[test]
+    fn arguments_replace_parameters_by_position_in_every_node_kind() {
+        let mut expander = expander(
+            "bump",
+            &["r", "by"],
+            "let old = !r in let ignored = (r := old + by) in !r",
+        );
+        let mut graph =
+            parser_core::parse_str("let counter = ref 1 in bump(counter)(5) * 2").unwrap();
+
+        assert_eq!(expander.expand(&mut graph), Ok(1));
+        assert_eq!(evaluate(&graph), Ok(Value::Int(12)));
+        assert_eq!(binder_names(&graph), ["counter", "ignored_2", "old_1"]);
+    }
+
+    #[test]
+    fn partial_and_nested_invocations() {
+        let mut expander = expander("twice", &["f", "v"], "f(f(v))");
+        // `twice` with one argument is not an invocation, so it stays unbound.
+        let mut graph = parser_core::parse_str("twice((n) => n)").unwrap();
+        assert_eq!(expander.expand(&mut graph), Ok(0));
+
+        let mut graph =
+            parser_core::parse_str("twice((n) => n * 2)(twice((n) => n + 1)(1))").unwrap();
+        assert_eq!(expander.expand(&mut graph), Ok(2));
+        assert_eq!(evaluate(&graph), Ok(Value::Int(12)));
+    }
+
+    /// A graph defining `double(a) = (n) => a + n`, whose root invokes it
+    /// as `name(20)(1)`.
+    fn graph_with_definition(name: &str) -> AsgGraph {
+        let mut graph = AsgGraph::new();
+        let param = graph.add_node(variable("a", 0));
+        let use_a = graph.add_node(variable("a", 0));
+        let body = LambdaBuilder::build(&mut graph, "n", |graph, n| {
+            let n = n.reference(graph);
+            graph.add_node(
+                PrimitiveOp {
+                    op_name: "add".to_string(),
+                    argument_node_ids: vec![use_a, n],
+                }
+                .into(),
+            )
+        });
+        let definition = graph.add_node(
+            TermMacroDefinition {
+                name: "double".to_string(),
+                parameter_node_ids: vec![param],
+                body_node_id: body,
+            }
+            .into(),
+        );
+        for node_id in [param, use_a] {
+            if let Some(NodeContent::TermVariable(var)) =
+                graph.get_node_mut(node_id).map(|node| &mut node.content)
+            {
+                var.definition_node_id = definition;
+            }
+        }
+
+        let twenty = graph.add_node(LiteralInt { value: 20 }.into());
+        let invocation = graph.add_node(
+            TermMacroInvocation {
+                macro_name: name.to_string(),
+                argument_node_ids: vec![twenty],
+            }
+            .into(),
+        );
+        let one = graph.add_node(LiteralInt { value: 1 }.into());
+        let root = graph.add_node(
+            TermApplication {
+                function_node_id: invocation,
+                argument_node_id: one,
+            }
+            .into(),
+        );
+        graph.set_root(root);
+        graph
+    }
+
+    fn variable(name: &str, definition_node_id: u64) -> NodeContent {
+        TermVariable {
+            name: name.to_string(),
+            definition_node_id,
+        }
+        .into()
+    }
+
+    #[test]
+    fn definitions_and_invocations_are_read_from_the_graph() {
+        let mut graph = graph_with_definition("double");
+        assert_eq!(expand_macros(&mut graph), Ok(1));
+        assert_eq!(evaluate(&graph), Ok(Value::Int(21)));
+        assert_eq!(binder_names(&graph), ["n_1"]);
+
+        let mut graph = graph_with_definition("triple");
+        assert_eq!(
+            expand_macros(&mut graph),
+            Err(MacroError::UndefinedMacro("triple".to_string()))
+        );
+    }
+
+    #[test]
+    fn recursive_macros_report_the_cycle() {
+        let mut expander = expander("forever", &["a"], "forever(a) + 1");
+        let mut graph = parser_core::parse_str("forever(0)").unwrap();
+        assert_eq!(
+            expander.expand(&mut graph),
+            Err(MacroError::ExpansionError {
+                cycle: vec!["forever".to_string(), "forever".to_string()]
+            })
+        );
+
+        for (name, body) in [("a", "b(x)"), ("b", "c(x) + 1"), ("c", "a(x)")] {
+            let body = parser_core::parse_str(body).unwrap();
+            expander.register(MacroDefinition::new(name, ["x"], body).unwrap());
+        }
+        let mut graph = parser_core::parse_str("1 + b(2)").unwrap();
+        let error = expander.expand(&mut graph).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "recursive macro expansion: b -> c -> a -> b"
+        );
+    }
+
+    #[test]
+    fn deep_finite_expansion_succeeds() {
+        let mut expander = MacroExpander::new();
+        for level in 0..50 {
+            let body = if level == 0 {
+                "x + 1".to_string()
+            } else {
+                format!("m{}(x) + 1", level - 1)
+            };
+            let body = parser_core::parse_str(&body).unwrap();
+            expander.register(MacroDefinition::new(format!("m{}", level), ["x"], body).unwrap());
+        }
+        // The same macro nested in its own argument is not recursion.
+        let mut graph = parser_core::parse_str("m49(m49(0))").unwrap();
+        assert_eq!(expander.expand(&mut graph), Ok(100));
+        assert_eq!(evaluate(&graph), Ok(Value::Int(100)));
+    }
+
+    #[test]
+    fn expansion_stops_at_the_limit() {
+        let mut expander = expander("twice", &["f", "v"], "f(f(v))").with_expansion_limit(1);
+        let mut graph =
+            parser_core::parse_str("twice((n) => n * 2)(twice((n) => n + 1)(1))").unwrap();
+        assert_eq!(
+            expander.expand(&mut graph),
+            Err(MacroError::ExpansionLimit(1))
+        );
+    }
+}
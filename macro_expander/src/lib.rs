@@ -1,14 +1,16 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Hygienic expansion of function macros over the ASG.
+//!
+//! A macro is defined by a `TermMacroDefinition` node, or registered
+//! directly as a term with free parameter variables. It is invoked by a
+//! `TermMacroInvocation` node, or in source text like a function whose head
+//! is an unbound variable naming the macro, so `my_macro(a)(b)` invokes a
+//! two-parameter `my_macro`. Expansion copies the body into the graph with
+//! the arguments in place of the parameters, and renames the variables the
+//! body binds so they cannot capture or be confused with names at the call
+//! site.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod error;
+pub mod expand;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use error::{MacroError, Result};
+pub use expand::{MacroDefinition, MacroExpander, expand_macros};
@@ -0,0 +1,119 @@
+//! Intermediate syntax tree produced by the parser.
+//!
+//! The tree mirrors the concrete syntax closely and keeps names unresolved;
+//! [`crate::builder::build_asg`] resolves scopes while producing the ASG.
+
+use crate::lexer::Position;
+
+/// The source range covered by a syntax element; `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+/// An expression with its source span.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+/// Expression forms of the concrete syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprKind {
+    Var(String),
+    Int(i64),
+    Bool(bool),
+    /// `()`.
+    Unit,
+    /// `(x) => body` or `(x: T) => body`, optionally declaring the effects
+    /// its body may perform: `(x) with [IO] => body`.
+    Lambda {
+        param: Param,
+        effects: Option<Vec<String>>,
+        body: Box<Expr>,
+    },
+    /// `let x = value in body`, with an optional annotation as on a
+    /// lambda parameter: `let x: Int = value in body`.
+    Let {
+        param: Param,
+        value: Box<Expr>,
+        body: Box<Expr>,
+    },
+    /// `letrec f = value in body`: like `let`, but `f` is also in scope
+    /// in `value`, so a function can call itself.
+    LetRec {
+        param: Param,
+        value: Box<Expr>,
+        body: Box<Expr>,
+    },
+    /// `if condition then yes else no`.
+    If {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
+    /// `f(x)`.
+    Apply {
+        function: Box<Expr>,
+        argument: Box<Expr>,
+    },
+    /// A built-in operation written with operator syntax; `op` is the
+    /// primitive name used in the ASG, e.g. `add` for `+`.
+    Primitive {
+        op: String,
+        args: Vec<Expr>,
+    },
+    /// `ref e`.
+    Ref(Box<Expr>),
+    /// `!e`.
+    Deref(Box<Expr>),
+    /// `r := e`.
+    Assign {
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+    /// `perform('Effect', e)`.
+    Perform {
+        effect: String,
+        value: Box<Expr>,
+    },
+}
+
+/// A lambda parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    pub span: Span,
+    pub annotation: Option<TypeExpr>,
+}
+
+/// A type written in an annotation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeExpr {
+    pub kind: TypeExprKind,
+    pub span: Span,
+}
+
+/// Type forms of the concrete syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeExprKind {
+    Int,
+    Bool,
+    Unit,
+    /// `Ref T`.
+    Ref(Box<TypeExpr>),
+    /// `A -> B`, right associative.
+    Function(Box<TypeExpr>, Box<TypeExpr>),
+}
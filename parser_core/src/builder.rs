@@ -0,0 +1,187 @@
+//! Conversion from the syntax tree to an [`AsgGraph`].
+//!
+//! Names are resolved here: every variable occurrence, including a lambda's
+//! binder, links to the lambda that binds it. Each lambda in scope is a
+//! [`LambdaBuilder`], which links the occurrences once the lambda's node
+//! exists. Unbound variables keep definition id `0` and are left for the
+//! checkers to report.
+
+use asg_core::{
+    AsgGraph, EffectPerform, LambdaBuilder, LiteralBool, LiteralInt, LiteralUnit, NodeContent,
+    PrimitiveOp, SourceLocation, TermApplication, TermAssign, TermDeref, TermRef, TermVariable,
+    TypeKind, TypeNode,
+};
+
+use crate::ast::{Expr, ExprKind, Param, Span, TypeExpr, TypeExprKind};
+
+/// Builds the ASG for `expr`, recording spans against `filename`.
+///
+/// The resulting graph's root is the node for `expr`.
+pub fn build_asg(expr: &Expr, filename: &str) -> AsgGraph {
+    let mut builder = Builder {
+        graph: AsgGraph::new(),
+        filename,
+        scopes: Vec::new(),
+    };
+    let root = builder.expr(expr);
+    builder.graph.set_root(root);
+    builder.graph
+}
+
+struct Builder<'a> {
+    graph: AsgGraph,
+    filename: &'a str,
+    /// Lambdas whose parameter is in scope, innermost last.
+    scopes: Vec<LambdaBuilder>,
+}
+
+impl Builder<'_> {
+    fn add(&mut self, content: NodeContent, span: Span) -> u64 {
+        let node_id = self.graph.add_node(content);
+        self.locate(node_id, span);
+        node_id
+    }
+
+    fn locate(&mut self, node_id: u64, span: Span) {
+        self.graph.set_source_location(
+            node_id,
+            SourceLocation {
+                filename: self.filename.to_string(),
+                start_line: span.start.line,
+                start_col: span.start.column,
+                end_line: span.end.line,
+                end_col: span.end.column,
+            },
+        );
+    }
+
+    fn variable(&mut self, name: &str, span: Span) -> u64 {
+        let Some(scope) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find(|scope| scope.name() == name)
+        else {
+            return self.add(
+                NodeContent::TermVariable(TermVariable {
+                    name: name.to_string(),
+                    definition_node_id: 0,
+                }),
+                span,
+            );
+        };
+        let node_id = scope.reference(&mut self.graph);
+        self.locate(node_id, span);
+        node_id
+    }
+
+    fn expr(&mut self, expr: &Expr) -> u64 {
+        let content = match &expr.kind {
+            ExprKind::Var(name) => return self.variable(name, expr.span),
+            ExprKind::Int(value) => NodeContent::LiteralInt(LiteralInt { value: *value }),
+            ExprKind::Bool(value) => NodeContent::LiteralBool(LiteralBool { value: *value }),
+            ExprKind::Unit => NodeContent::LiteralUnit(LiteralUnit),
+            ExprKind::Lambda {
+                param,
+                effects,
+                body,
+            } => return self.lambda(param, effects.clone(), body, expr.span),
+            // `let x = value in body` becomes `((x) => body)(value)`, so the
+            // binding is an ordinary lambda parameter and shadows like one.
+            // The lambda spans only the body, where the binding is in scope.
+            ExprKind::Let { param, value, body } => NodeContent::TermApplication(TermApplication {
+                function_node_id: self.lambda(param, None, body, body.span),
+                argument_node_id: self.expr(value),
+            }),
+            // `letrec f = value in body` becomes
+            // `let f = fix((f) => value) in body`: the recursive uses in
+            // `value` are bound by the lambda passed to `fix`.
+            ExprKind::LetRec { param, value, body } => {
+                let generator = self.lambda(param, None, value, value.span);
+                let fixed = self.add(
+                    NodeContent::PrimitiveOp(PrimitiveOp {
+                        op_name: "fix".to_string(),
+                        argument_node_ids: vec![generator],
+                    }),
+                    value.span,
+                );
+                NodeContent::TermApplication(TermApplication {
+                    function_node_id: self.lambda(param, None, body, body.span),
+                    argument_node_id: fixed,
+                })
+            }
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => NodeContent::PrimitiveOp(PrimitiveOp {
+                op_name: "if".to_string(),
+                argument_node_ids: vec![
+                    self.expr(condition),
+                    self.expr(then_branch),
+                    self.expr(else_branch),
+                ],
+            }),
+            ExprKind::Apply { function, argument } => {
+                NodeContent::TermApplication(TermApplication {
+                    function_node_id: self.expr(function),
+                    argument_node_id: self.expr(argument),
+                })
+            }
+            ExprKind::Primitive { op, args } => NodeContent::PrimitiveOp(PrimitiveOp {
+                op_name: op.clone(),
+                argument_node_ids: args.iter().map(|arg| self.expr(arg)).collect(),
+            }),
+            ExprKind::Ref(init) => NodeContent::TermRef(TermRef {
+                init_value_node_id: self.expr(init),
+            }),
+            ExprKind::Deref(reference) => NodeContent::TermDeref(TermDeref {
+                ref_node_id: self.expr(reference),
+            }),
+            ExprKind::Assign { target, value } => NodeContent::TermAssign(TermAssign {
+                ref_node_id: self.expr(target),
+                value_node_id: self.expr(value),
+            }),
+            ExprKind::Perform { effect, value } => NodeContent::EffectPerform(EffectPerform {
+                effect_name: effect.clone(),
+                value_node_id: self.expr(value),
+            }),
+        };
+        self.add(content, expr.span)
+    }
+
+    fn lambda(
+        &mut self,
+        param: &Param,
+        effects: Option<Vec<String>>,
+        body: &Expr,
+        span: Span,
+    ) -> u64 {
+        let scope = LambdaBuilder::new(&mut self.graph, param.name.clone());
+        self.locate(scope.binder(), param.span);
+        self.scopes.push(scope);
+        let body = self.expr(body);
+        let mut scope = self.scopes.pop().expect("pushed above");
+        scope.set_annotation(param.annotation.as_ref().map(|ty| self.type_expr(ty)));
+        scope.set_effects(effects);
+        let lambda = scope.finish(&mut self.graph, body);
+        self.locate(lambda, span);
+        lambda
+    }
+
+    fn type_expr(&mut self, ty: &TypeExpr) -> u64 {
+        let kind = match &ty.kind {
+            TypeExprKind::Int => TypeKind::Int,
+            TypeExprKind::Bool => TypeKind::Bool,
+            TypeExprKind::Unit => TypeKind::Unit,
+            TypeExprKind::Ref(element) => TypeKind::Ref {
+                element_type_id: self.type_expr(element),
+            },
+            TypeExprKind::Function(param, ret) => TypeKind::Function {
+                param_type_id: self.type_expr(param),
+                return_type_id: self.type_expr(ret),
+            },
+        };
+        self.add(NodeContent::TypeNode(TypeNode { kind }), ty.span)
+    }
+}
@@ -0,0 +1,115 @@
+//! Errors reported while reading source text.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// A failure to turn source text into an ASG.
+#[derive(Debug, Error)]
+pub enum ParseError {
+    /// The text does not match the grammar. Positions are 1-based and
+    /// count characters, not bytes. `expected` describes what the parser
+    /// would have accepted at that point, and is empty when the input is
+    /// malformed rather than merely out of place.
+    #[error("{line}:{column}: {message}")]
+    Syntax {
+        message: String,
+        line: u32,
+        column: u32,
+        expected: Vec<String>,
+    },
+
+    /// The input file could not be read.
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+impl ParseError {
+    pub(crate) fn syntax(message: impl Into<String>, line: u32, column: u32) -> Self {
+        ParseError::Syntax {
+            message: message.into(),
+            line,
+            column,
+            expected: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_expected(mut self, what: impl Into<String>) -> Self {
+        if let ParseError::Syntax { expected, .. } = &mut self {
+            expected.push(what.into());
+        }
+        self
+    }
+
+    /// Stable diagnostic code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::Syntax { .. } => "P001",
+            ParseError::Io { .. } => "P002",
+        }
+    }
+
+    /// The `(line, column)` a syntax error points at.
+    pub fn position(&self) -> Option<(u32, u32)> {
+        match self {
+            ParseError::Syntax { line, column, .. } => Some((*line, *column)),
+            ParseError::Io { .. } => None,
+        }
+    }
+
+    /// Displays the error with the offending line of `source`, the text it
+    /// was parsed from, and a caret under the position.
+    pub fn snippet<'a>(&'a self, source: &'a str) -> Snippet<'a> {
+        Snippet {
+            error: self,
+            source,
+        }
+    }
+}
+
+/// A [`ParseError`] shown against its source; see [`ParseError::snippet`].
+///
+/// ```text
+/// 1:4: expected an expression, found end of input
+///   |
+/// 1 | 1 +
+///   |    ^
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Snippet<'a> {
+    error: &'a ParseError,
+    source: &'a str,
+}
+
+impl fmt::Display for Snippet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        let Some((line, column)) = self.error.position() else {
+            return Ok(());
+        };
+        let Some(text) = (line as usize)
+            .checked_sub(1)
+            .and_then(|index| self.source.lines().nth(index))
+        else {
+            return Ok(());
+        };
+        let text = text.trim_end_matches('\r');
+        let number = line.to_string();
+        let gutter = " ".repeat(number.len());
+        // Tabs are copied so the caret lines up however they are shown.
+        let pad: String = text
+            .chars()
+            .take(column.saturating_sub(1) as usize)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        write!(f, "\n{gutter} |\n{number} | {text}\n{gutter} | {pad}^")
+    }
+}
+
+/// Convenience alias for parser results.
+pub type Result<T> = std::result::Result<T, ParseError>;
@@ -0,0 +1,333 @@
+//! Tokenizer for the minimal concrete syntax.
+
+use crate::error::{ParseError, Result};
+
+/// A 1-based line/column position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Token kinds of the concrete syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident(String),
+    Int(i64),
+    Str(String),
+    True,
+    False,
+    Ref,
+    Not,
+    Perform,
+    With,
+    Let,
+    LetRec,
+    In,
+    If,
+    Then,
+    Else,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Colon,
+    Comma,
+    /// `=`, in `let` bindings.
+    Eq,
+    /// `=>`
+    FatArrow,
+    /// `->`
+    Arrow,
+    /// `:=`
+    ColonEq,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    EqEq,
+    NotEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    AndAnd,
+    OrOr,
+    /// `!`, dereference.
+    Bang,
+    Eof,
+}
+
+impl TokenKind {
+    /// How the token is described in error messages.
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Ident(name) => format!("identifier '{}'", name),
+            TokenKind::Int(value) => format!("integer {}", value),
+            TokenKind::Str(value) => format!("string {:?}", value),
+            TokenKind::Eof => "end of input".to_string(),
+            other => format!("'{}'", other.symbol()),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            TokenKind::True => "true",
+            TokenKind::False => "false",
+            TokenKind::Ref => "ref",
+            TokenKind::Not => "not",
+            TokenKind::Perform => "perform",
+            TokenKind::With => "with",
+            TokenKind::Let => "let",
+            TokenKind::LetRec => "letrec",
+            TokenKind::In => "in",
+            TokenKind::If => "if",
+            TokenKind::Then => "then",
+            TokenKind::Else => "else",
+            TokenKind::LParen => "(",
+            TokenKind::RParen => ")",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
+            TokenKind::Colon => ":",
+            TokenKind::Comma => ",",
+            TokenKind::Eq => "=",
+            TokenKind::FatArrow => "=>",
+            TokenKind::Arrow => "->",
+            TokenKind::ColonEq => ":=",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Star => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Percent => "%",
+            TokenKind::EqEq => "==",
+            TokenKind::NotEq => "!=",
+            TokenKind::Lt => "<",
+            TokenKind::Le => "<=",
+            TokenKind::Gt => ">",
+            TokenKind::Ge => ">=",
+            TokenKind::AndAnd => "&&",
+            TokenKind::OrOr => "||",
+            TokenKind::Bang => "!",
+            TokenKind::Ident(_) | TokenKind::Int(_) | TokenKind::Str(_) | TokenKind::Eof => "",
+        }
+    }
+}
+
+/// A token with the span it covers; `end` is exclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: Position,
+    pub end: Position,
+}
+
+/// Splits `source` into tokens, ending with a single `Eof` token.
+///
+/// Whitespace and `//` line comments are skipped.
+pub fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut lexer = Lexer {
+        chars: source.chars().collect(),
+        index: 0,
+        position: Position { line: 1, column: 1 },
+    };
+    let mut tokens = Vec::new();
+    loop {
+        lexer.skip_trivia();
+        let start = lexer.position;
+        let Some(c) = lexer.bump() else {
+            tokens.push(Token {
+                kind: TokenKind::Eof,
+                start,
+                end: start,
+            });
+            return Ok(tokens);
+        };
+        let kind = lexer.token_kind(c, start)?;
+        tokens.push(Token {
+            kind,
+            start,
+            end: lexer.position,
+        });
+    }
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    index: usize,
+    position: Position,
+}
+
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '/' && self.chars.get(self.index + 1) == Some(&'/') {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.bump();
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token_kind(&mut self, c: char, start: Position) -> Result<TokenKind> {
+        let kind = match c {
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            ',' => TokenKind::Comma,
+            '+' => TokenKind::Plus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            ':' if self.eat('=') => TokenKind::ColonEq,
+            ':' => TokenKind::Colon,
+            '-' if self.eat('>') => TokenKind::Arrow,
+            '-' => TokenKind::Minus,
+            '=' if self.eat('>') => TokenKind::FatArrow,
+            '=' if self.eat('=') => TokenKind::EqEq,
+            '=' => TokenKind::Eq,
+            '!' if self.eat('=') => TokenKind::NotEq,
+            '!' => TokenKind::Bang,
+            '<' if self.eat('=') => TokenKind::Le,
+            '<' => TokenKind::Lt,
+            '>' if self.eat('=') => TokenKind::Ge,
+            '>' => TokenKind::Gt,
+            '&' if self.eat('&') => TokenKind::AndAnd,
+            '|' if self.eat('|') => TokenKind::OrOr,
+            '\'' | '"' => self.string(c, start)?,
+            c if c.is_ascii_digit() => self.integer(c, start)?,
+            c if c.is_alphabetic() || c == '_' => self.word(c),
+            other => {
+                return Err(ParseError::syntax(
+                    format!("unexpected character '{}'", other),
+                    start.line,
+                    start.column,
+                ));
+            }
+        };
+        Ok(kind)
+    }
+
+    fn string(&mut self, quote: char, start: Position) -> Result<TokenKind> {
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some(c) if c == quote => return Ok(TokenKind::Str(value)),
+                Some('\n') | None => {
+                    return Err(ParseError::syntax(
+                        "unterminated string literal",
+                        start.line,
+                        start.column,
+                    ));
+                }
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn integer(&mut self, first: char, start: Position) -> Result<TokenKind> {
+        let mut digits = first.to_string();
+        while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.bump();
+        }
+        digits.parse().map(TokenKind::Int).map_err(|_| {
+            ParseError::syntax(
+                format!("integer literal {} is out of range", digits),
+                start.line,
+                start.column,
+            )
+        })
+    }
+
+    fn word(&mut self, first: char) -> TokenKind {
+        let mut word = first.to_string();
+        while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_') {
+            word.push(c);
+            self.bump();
+        }
+        match word.as_str() {
+            "true" => TokenKind::True,
+            "false" => TokenKind::False,
+            "ref" => TokenKind::Ref,
+            "not" => TokenKind::Not,
+            "perform" => TokenKind::Perform,
+            "with" => TokenKind::With,
+            "let" => TokenKind::Let,
+            "letrec" => TokenKind::LetRec,
+            "in" => TokenKind::In,
+            "if" => TokenKind::If,
+            "then" => TokenKind::Then,
+            "else" => TokenKind::Else,
+            _ => TokenKind::Ident(word),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_carry_positions() {
+        let tokens = tokenize("(x) =>\n  x := 10 // done").unwrap();
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::LParen,
+                TokenKind::Ident("x".into()),
+                TokenKind::RParen,
+                TokenKind::FatArrow,
+                TokenKind::Ident("x".into()),
+                TokenKind::ColonEq,
+                TokenKind::Int(10),
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(tokens[4].start, Position { line: 2, column: 3 });
+        assert_eq!(
+            tokens[6].end,
+            Position {
+                line: 2,
+                column: 10
+            }
+        );
+    }
+
+    #[test]
+    fn unterminated_string_points_at_the_quote() {
+        let error = tokenize("perform('IO, 1)").unwrap_err();
+        assert_eq!(error.position(), Some((1, 9)));
+    }
+}
@@ -1,14 +1,138 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
+//! Parser for the minimal Synapse text format.
+//!
+//! Source text is tokenized, parsed into an intermediate [`ast`], and then
+//! converted into an [`AsgGraph`] whose nodes carry source locations. See
+//! [`parser`] for the grammar.
+
+pub mod ast;
+pub mod builder;
+pub mod error;
+pub mod lexer;
+pub mod parser;
+
+use std::path::Path;
+
+use asg_core::AsgGraph;
+
+pub use builder::build_asg;
+pub use error::{ParseError, Result, Snippet};
+pub use parser::parse_program;
+
+/// Filename recorded in source locations for text without a file.
+pub const DEFAULT_FILENAME: &str = "<input>";
+
+/// Parses `source` into an ASG.
+pub fn parse_str(source: &str) -> Result<AsgGraph> {
+    parse_source(DEFAULT_FILENAME, source)
+}
+
+/// Parses `source`, recording `filename` in node source locations.
+pub fn parse_source(filename: &str, source: &str) -> Result<AsgGraph> {
+    let expr = parse_program(source)?;
+    Ok(build_asg(&expr, filename))
+}
+
+/// Reads and parses the file at `path`.
+pub fn parse_file(path: impl AsRef<Path>) -> Result<AsgGraph> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path).map_err(|source| ParseError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_source(&path.display().to_string(), &source)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use asg_core::NodeContent;
+
+    #[test]
+    fn variables_link_to_their_binding_lambda() {
+        let graph = parse_str("(x) => (y) => x").unwrap();
+        let outer = graph.root().unwrap();
+        let NodeContent::TermLambda(outer_lambda) = &graph.get_node(outer).unwrap().content else {
+            panic!("root should be a lambda");
+        };
+        let NodeContent::TermLambda(inner_lambda) =
+            &graph.get_node(outer_lambda.body_node_id).unwrap().content
+        else {
+            panic!("body should be a lambda");
+        };
+        let definition = |node_id| match &graph.get_node(node_id).unwrap().content {
+            NodeContent::TermVariable(var) => var.definition_node_id,
+            other => panic!("expected a variable, got {:?}", other),
+        };
+        assert_eq!(definition(outer_lambda.binder_variable_node_id), outer);
+        assert_eq!(
+            definition(inner_lambda.binder_variable_node_id),
+            outer_lambda.body_node_id
+        );
+        assert_eq!(definition(inner_lambda.body_node_id), outer);
+    }
+
+    #[test]
+    fn let_bindings_resolve_like_lambda_parameters() {
+        let graph = parse_str("let x = 1 in let x = x + 1 in x").unwrap();
+        let application = |node_id| match &graph.get_node(node_id).unwrap().content {
+            NodeContent::TermApplication(app) => (app.function_node_id, app.argument_node_id),
+            other => panic!("expected an application, got {:?}", other),
+        };
+        let body = |node_id| match &graph.get_node(node_id).unwrap().content {
+            NodeContent::TermLambda(lambda) => lambda.body_node_id,
+            other => panic!("expected a lambda, got {:?}", other),
+        };
+        let definition = |node_id| match &graph.get_node(node_id).unwrap().content {
+            NodeContent::TermVariable(var) => var.definition_node_id,
+            other => panic!("expected a variable, got {:?}", other),
+        };
+
+        let (outer, _) = application(graph.root().unwrap());
+        let (inner, value) = application(body(outer));
+        let NodeContent::PrimitiveOp(add) = &graph.get_node(value).unwrap().content else {
+            panic!("expected x + 1");
+        };
+        // The inner value sees the outer x; the inner body sees the inner x.
+        assert_eq!(definition(add.argument_node_ids[0]), outer);
+        assert_eq!(definition(body(inner)), inner);
+
+        // A binding is not in scope in its own value.
+        let graph = parse_str("let x = x in x").unwrap();
+        let unbound = graph
+            .nodes()
+            .filter(|node| matches!(&node.content, NodeContent::TermVariable(var) if var.definition_node_id == 0))
+            .count();
+        assert_eq!(unbound, 1);
+    }
+
+    #[test]
+    fn unbound_variables_keep_definition_zero() {
+        let graph = parse_str("y + 1").unwrap();
+        let unbound = graph.nodes().find_map(|node| match &node.content {
+            NodeContent::TermVariable(var) => Some(var.definition_node_id),
+            _ => None,
+        });
+        assert_eq!(unbound, Some(0));
+    }
+
+    #[test]
+    fn nodes_carry_source_spans() {
+        let graph = parse_source("inc.syn", "(x) =>\n  x + 1").unwrap();
+        let root = graph.root().unwrap();
+        let location = graph.source_location(root).unwrap();
+        assert_eq!(location.filename, "inc.syn");
+        assert_eq!((location.start_line, location.start_col), (1, 1));
+        assert_eq!((location.end_line, location.end_col), (2, 8));
+        assert!(
+            graph
+                .nodes()
+                .all(|node| graph.source_location(node.node_id).is_some())
+        );
+    }
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn missing_file_is_an_io_error() {
+        let error = parse_file("/nonexistent/program.syn").unwrap_err();
+        assert!(matches!(error, ParseError::Io { .. }));
     }
 }
@@ -0,0 +1,717 @@
+//! Recursive-descent parser for the minimal concrete syntax.
+//!
+//! ```text
+//! expr    := let | if | lambda | assign
+//! let     := ('let' | 'letrec') IDENT (':' type)? '=' expr 'in' expr
+//! if      := 'if' expr 'then' expr 'else' expr
+//! lambda  := param+ effects? '=>' expr
+//! param   := '(' IDENT (':' type)? ')'
+//! effects := 'with' '[' (effect (',' effect)*)? ']'
+//! effect  := IDENT | STRING
+//! assign  := or (':=' expr)?
+//! or      := and ('||' and)*
+//! and     := cmp ('&&' cmp)*
+//! cmp     := add (('==' | '!=' | '<' | '<=' | '>' | '>=') add)?
+//! add     := mul (('+' | '-') mul)*
+//! mul     := unary (('*' | '/' | '%') unary)*
+//! unary   := ('!' | '-' | 'not' | 'ref') unary | postfix
+//! postfix := primary ('(' expr ')')*
+//! primary := INT | 'true' | 'false' | '(' ')' | IDENT | '(' expr ')'
+//!          | 'perform' '(' STRING ',' expr ')'
+//! type    := ('Int' | 'Bool' | 'Unit' | 'Ref' type | '(' type ')') ('->' type)?
+//! ```
+//!
+//! A lambda with several parameters is sugar for nested single-parameter
+//! lambdas: `(x)(y) => e` parses exactly like `(x) => (y) => e`. An effect
+//! annotation belongs to the outermost of them.
+
+use crate::ast::{Expr, ExprKind, Param, Span, TypeExpr, TypeExprKind};
+use crate::error::{ParseError, Result};
+use crate::lexer::{Token, TokenKind, tokenize};
+
+/// Maximum nesting depth accepted before giving up, so that adversarial input
+/// cannot overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Parses a whole program, which is a single expression.
+pub fn parse_program(source: &str) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        index: 0,
+        depth: 0,
+    };
+    let expr = parser.expr()?;
+    parser.expect(TokenKind::Eof)?;
+    Ok(expr)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index]
+    }
+
+    fn peek_kind_at(&self, offset: usize) -> &TokenKind {
+        let last = self.tokens.len() - 1;
+        &self.tokens[(self.index + offset).min(last)].kind
+    }
+
+    fn bump(&mut self) -> Token {
+        let token = self.tokens[self.index].clone();
+        if token.kind != TokenKind::Eof {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> Option<Token> {
+        (&self.peek().kind == kind).then(|| self.bump())
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token> {
+        match self.eat(&kind) {
+            Some(token) => Ok(token),
+            None => Err(self.unexpected(&kind.describe())),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        unexpected_token(self.peek(), expected)
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            let token = self.peek();
+            return Err(ParseError::syntax(
+                "expression nested too deeply",
+                token.start.line,
+                token.start.column,
+            ));
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        self.enter()?;
+        let result = if matches!(self.peek().kind, TokenKind::Let | TokenKind::LetRec) {
+            self.let_binding()
+        } else if self.peek().kind == TokenKind::If {
+            self.conditional()
+        } else if self.at_lambda() {
+            self.lambda()
+        } else {
+            self.assign()
+        };
+        self.depth -= 1;
+        result
+    }
+
+    /// A lambda starts with `(` IDENT `:`, or with one or more `(` IDENT `)`
+    /// groups followed by `=>` or `with`; anything else in parentheses is a
+    /// grouped expression or, after the first group, an application.
+    fn at_lambda(&self) -> bool {
+        let mut offset = 0;
+        loop {
+            if *self.peek_kind_at(offset) != TokenKind::LParen
+                || !matches!(self.peek_kind_at(offset + 1), TokenKind::Ident(_))
+            {
+                return false;
+            }
+            match self.peek_kind_at(offset + 2) {
+                TokenKind::Colon => return true,
+                TokenKind::RParen => offset += 3,
+                _ => return false,
+            }
+            if matches!(
+                self.peek_kind_at(offset),
+                TokenKind::FatArrow | TokenKind::With
+            ) {
+                return true;
+            }
+        }
+    }
+
+    fn lambda(&mut self) -> Result<Expr> {
+        let mut params = vec![self.param()?];
+        while self.peek().kind == TokenKind::LParen {
+            params.push(self.param()?);
+        }
+        let mut effects = match self.eat(&TokenKind::With) {
+            Some(_) => Some(self.effects()?),
+            None => None,
+        };
+        self.expect(TokenKind::FatArrow)?;
+        let mut lambda = self.expr()?;
+        for (index, (open, param)) in params.into_iter().enumerate().rev() {
+            lambda = Expr {
+                span: open.to(lambda.span),
+                kind: ExprKind::Lambda {
+                    param,
+                    effects: if index == 0 { effects.take() } else { None },
+                    body: Box::new(lambda),
+                },
+            };
+        }
+        Ok(lambda)
+    }
+
+    /// `let x = value in body`; `value` is outside the binding's scope.
+    /// With `letrec` it is inside, for recursive functions.
+    fn let_binding(&mut self) -> Result<Expr> {
+        let keyword = self.bump();
+        let start = span_of(&keyword);
+        let name_token = self.bump();
+        let name_span = span_of(&name_token);
+        let TokenKind::Ident(name) = name_token.kind else {
+            return Err(unexpected_token(&name_token, "a variable name"));
+        };
+        let annotation = match self.eat(&TokenKind::Colon) {
+            Some(_) => Some(self.type_expr()?),
+            None => None,
+        };
+        self.expect(TokenKind::Eq)?;
+        let value = self.expr()?;
+        self.expect(TokenKind::In)?;
+        let body = self.expr()?;
+        let param = Param {
+            name,
+            span: name_span,
+            annotation,
+        };
+        let (value, body) = (Box::new(value), Box::new(body));
+        Ok(Expr {
+            span: start.to(body.span),
+            kind: if keyword.kind == TokenKind::LetRec {
+                ExprKind::LetRec { param, value, body }
+            } else {
+                ExprKind::Let { param, value, body }
+            },
+        })
+    }
+
+    /// `if condition then yes else no`; the `else` branch extends as far
+    /// right as possible, like a `let` body.
+    fn conditional(&mut self) -> Result<Expr> {
+        let start = span_of(&self.expect(TokenKind::If)?);
+        let condition = self.expr()?;
+        self.expect(TokenKind::Then)?;
+        let then_branch = self.expr()?;
+        self.expect(TokenKind::Else)?;
+        let else_branch = self.expr()?;
+        Ok(Expr {
+            span: start.to(else_branch.span),
+            kind: ExprKind::If {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            },
+        })
+    }
+
+    /// The bracketed effect names after `with`. Names that are not
+    /// identifiers, such as `'Custom:Log'`, are written as strings.
+    fn effects(&mut self) -> Result<Vec<String>> {
+        self.expect(TokenKind::LBracket)?;
+        let mut effects = Vec::new();
+        while self.eat(&TokenKind::RBracket).is_none() {
+            if !effects.is_empty() {
+                self.expect(TokenKind::Comma)?;
+            }
+            let token = self.bump();
+            let (TokenKind::Ident(name) | TokenKind::Str(name)) = token.kind else {
+                return Err(unexpected_token(&token, "an effect name"));
+            };
+            effects.push(name);
+        }
+        Ok(effects)
+    }
+
+    /// One parenthesised lambda parameter, with the span of its `(`.
+    fn param(&mut self) -> Result<(Span, Param)> {
+        let open = self.expect(TokenKind::LParen)?;
+        let name_token = self.bump();
+        let name_span = span_of(&name_token);
+        let TokenKind::Ident(name) = name_token.kind else {
+            return Err(unexpected_token(&name_token, "a parameter name"));
+        };
+        let annotation = match self.eat(&TokenKind::Colon) {
+            Some(_) => Some(self.type_expr()?),
+            None => None,
+        };
+        self.expect(TokenKind::RParen)?;
+        Ok((
+            span_of(&open),
+            Param {
+                name,
+                span: name_span,
+                annotation,
+            },
+        ))
+    }
+
+    fn assign(&mut self) -> Result<Expr> {
+        let target = self.binary(0)?;
+        if self.eat(&TokenKind::ColonEq).is_none() {
+            return Ok(target);
+        }
+        let value = self.expr()?;
+        Ok(Expr {
+            span: target.span.to(value.span),
+            kind: ExprKind::Assign {
+                target: Box::new(target),
+                value: Box::new(value),
+            },
+        })
+    }
+
+    /// Parses binary operators by precedence climbing; `min_precedence`
+    /// is the loosest operator the caller accepts.
+    fn binary(&mut self, min_precedence: u8) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        let mut compared = false;
+        while let Some((precedence, op)) = binary_operator(&self.peek().kind)
+            .filter(|(precedence, _)| *precedence >= min_precedence)
+        {
+            if precedence == COMPARISON_PRECEDENCE {
+                // Comparisons do not chain: `a < b < c` is rejected.
+                if compared {
+                    break;
+                }
+                compared = true;
+            }
+            self.bump();
+            self.enter()?;
+            let rhs = self.binary(precedence + 1);
+            self.depth -= 1;
+            let rhs = rhs?;
+            lhs = Expr {
+                span: lhs.span.to(rhs.span),
+                kind: ExprKind::Primitive {
+                    op: op.to_string(),
+                    args: vec![lhs, rhs],
+                },
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let token = self.peek().clone();
+        let wrap = |operand: Expr, kind: fn(Box<Expr>) -> ExprKind| Expr {
+            span: span_of(&token).to(operand.span),
+            kind: kind(Box::new(operand)),
+        };
+        match token.kind {
+            TokenKind::Bang => {
+                self.bump();
+                let operand = self.nested_unary()?;
+                Ok(wrap(operand, ExprKind::Deref))
+            }
+            TokenKind::Ref => {
+                self.bump();
+                let operand = self.nested_unary()?;
+                Ok(wrap(operand, ExprKind::Ref))
+            }
+            TokenKind::Not => {
+                self.bump();
+                let operand = self.nested_unary()?;
+                Ok(Expr {
+                    span: span_of(&token).to(operand.span),
+                    kind: ExprKind::Primitive {
+                        op: "not".to_string(),
+                        args: vec![operand],
+                    },
+                })
+            }
+            TokenKind::Minus => {
+                self.bump();
+                let operand = self.nested_unary()?;
+                let span = span_of(&token).to(operand.span);
+                let kind = match operand.kind {
+                    ExprKind::Int(value) => ExprKind::Int(-value),
+                    _ => ExprKind::Primitive {
+                        op: "sub".to_string(),
+                        args: vec![
+                            Expr {
+                                kind: ExprKind::Int(0),
+                                span: span_of(&token),
+                            },
+                            operand,
+                        ],
+                    },
+                };
+                Ok(Expr { kind, span })
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn nested_unary(&mut self) -> Result<Expr> {
+        self.enter()?;
+        let result = self.unary();
+        self.depth -= 1;
+        result
+    }
+
+    fn postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+        while self.eat(&TokenKind::LParen).is_some() {
+            let argument = self.expr()?;
+            let close = self.expect(TokenKind::RParen)?;
+            expr = Expr {
+                span: expr.span.to(span_of(&close)),
+                kind: ExprKind::Apply {
+                    function: Box::new(expr),
+                    argument: Box::new(argument),
+                },
+            };
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let token = self.peek().clone();
+        let kind = match token.kind {
+            TokenKind::Int(value) => ExprKind::Int(value),
+            TokenKind::True => ExprKind::Bool(true),
+            TokenKind::False => ExprKind::Bool(false),
+            TokenKind::Ident(ref name) => ExprKind::Var(name.clone()),
+            TokenKind::LParen if *self.peek_kind_at(1) == TokenKind::RParen => {
+                self.bump();
+                let close = self.bump();
+                return Ok(Expr {
+                    kind: ExprKind::Unit,
+                    span: span_of(&token).to(span_of(&close)),
+                });
+            }
+            TokenKind::LParen => {
+                self.bump();
+                let inner = self.expr()?;
+                let close = self.expect(TokenKind::RParen)?;
+                return Ok(Expr {
+                    kind: inner.kind,
+                    span: span_of(&token).to(span_of(&close)),
+                });
+            }
+            TokenKind::Perform => return self.perform(),
+            _ => return Err(self.unexpected("an expression")),
+        };
+        self.bump();
+        Ok(Expr {
+            kind,
+            span: span_of(&token),
+        })
+    }
+
+    fn perform(&mut self) -> Result<Expr> {
+        let keyword = self.expect(TokenKind::Perform)?;
+        self.expect(TokenKind::LParen)?;
+        let effect = match self.peek().kind.clone() {
+            TokenKind::Str(effect) => {
+                self.bump();
+                effect
+            }
+            _ => return Err(self.unexpected("an effect name string")),
+        };
+        self.expect(TokenKind::Comma)?;
+        let value = self.expr()?;
+        let close = self.expect(TokenKind::RParen)?;
+        Ok(Expr {
+            span: span_of(&keyword).to(span_of(&close)),
+            kind: ExprKind::Perform {
+                effect,
+                value: Box::new(value),
+            },
+        })
+    }
+
+    fn type_expr(&mut self) -> Result<TypeExpr> {
+        self.enter()?;
+        let result = self.type_expr_inner();
+        self.depth -= 1;
+        result
+    }
+
+    fn type_expr_inner(&mut self) -> Result<TypeExpr> {
+        let token = self.bump();
+        let mut ty = match &token.kind {
+            TokenKind::Ident(name) if name == "Int" => TypeExpr {
+                kind: TypeExprKind::Int,
+                span: span_of(&token),
+            },
+            TokenKind::Ident(name) if name == "Bool" => TypeExpr {
+                kind: TypeExprKind::Bool,
+                span: span_of(&token),
+            },
+            TokenKind::Ident(name) if name == "Unit" => TypeExpr {
+                kind: TypeExprKind::Unit,
+                span: span_of(&token),
+            },
+            TokenKind::Ident(name) if name == "Ref" => {
+                let element = self.type_atom()?;
+                TypeExpr {
+                    span: span_of(&token).to(element.span),
+                    kind: TypeExprKind::Ref(Box::new(element)),
+                }
+            }
+            TokenKind::LParen => {
+                let inner = self.type_expr()?;
+                let close = self.expect(TokenKind::RParen)?;
+                TypeExpr {
+                    kind: inner.kind,
+                    span: span_of(&token).to(span_of(&close)),
+                }
+            }
+            _ => return Err(unexpected_token(&token, "a type")),
+        };
+        if self.eat(&TokenKind::Arrow).is_some() {
+            let ret = self.type_expr()?;
+            ty = TypeExpr {
+                span: ty.span.to(ret.span),
+                kind: TypeExprKind::Function(Box::new(ty), Box::new(ret)),
+            };
+        }
+        Ok(ty)
+    }
+
+    /// The operand of `Ref`: a type without a trailing arrow, so that
+    /// `Ref Int -> Int` reads as `(Ref Int) -> Int`.
+    fn type_atom(&mut self) -> Result<TypeExpr> {
+        if self.peek().kind == TokenKind::LParen {
+            return self.type_expr_inner();
+        }
+        let token = self.peek().clone();
+        let kind = match &token.kind {
+            TokenKind::Ident(name) if name == "Int" => TypeExprKind::Int,
+            TokenKind::Ident(name) if name == "Bool" => TypeExprKind::Bool,
+            TokenKind::Ident(name) if name == "Unit" => TypeExprKind::Unit,
+            TokenKind::Ident(name) if name == "Ref" => {
+                self.bump();
+                self.enter()?;
+                let element = self.type_atom();
+                self.depth -= 1;
+                let element = element?;
+                return Ok(TypeExpr {
+                    span: span_of(&token).to(element.span),
+                    kind: TypeExprKind::Ref(Box::new(element)),
+                });
+            }
+            _ => return Err(self.unexpected("a type")),
+        };
+        self.bump();
+        Ok(TypeExpr {
+            kind,
+            span: span_of(&token),
+        })
+    }
+}
+
+const COMPARISON_PRECEDENCE: u8 = 2;
+
+/// Precedence (higher binds tighter) and primitive name of a binary operator.
+fn binary_operator(kind: &TokenKind) -> Option<(u8, &'static str)> {
+    let operator = match kind {
+        TokenKind::OrOr => (0, "or"),
+        TokenKind::AndAnd => (1, "and"),
+        TokenKind::EqEq => (COMPARISON_PRECEDENCE, "eq"),
+        TokenKind::NotEq => (COMPARISON_PRECEDENCE, "ne"),
+        TokenKind::Lt => (COMPARISON_PRECEDENCE, "lt"),
+        TokenKind::Le => (COMPARISON_PRECEDENCE, "le"),
+        TokenKind::Gt => (COMPARISON_PRECEDENCE, "gt"),
+        TokenKind::Ge => (COMPARISON_PRECEDENCE, "ge"),
+        TokenKind::Plus => (3, "add"),
+        TokenKind::Minus => (3, "sub"),
+        TokenKind::Star => (4, "mul"),
+        TokenKind::Slash => (4, "div"),
+        TokenKind::Percent => (4, "mod"),
+        _ => return None,
+    };
+    Some(operator)
+}
+
+/// An error for finding `token` where `expected` should be.
+fn unexpected_token(token: &Token, expected: &str) -> ParseError {
+    ParseError::syntax(
+        format!("expected {}, found {}", expected, token.kind.describe()),
+        token.start.line,
+        token.start.column,
+    )
+    .with_expected(expected)
+}
+
+fn span_of(token: &Token) -> Span {
+    Span {
+        start: token.start,
+        end: token.end,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op_name(expr: &Expr) -> &str {
+        match &expr.kind {
+            ExprKind::Primitive { op, .. } => op,
+            other => panic!("expected a primitive, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        let expr = parse_program("1 + 2 * 3").unwrap();
+        assert_eq!(op_name(&expr), "add");
+        let ExprKind::Primitive { args, .. } = &expr.kind else {
+            unreachable!()
+        };
+        assert_eq!(op_name(&args[1]), "mul");
+    }
+
+    #[test]
+    fn comparisons_sit_between_arithmetic_and_logic() {
+        let expr = parse_program("a + 1 < b && not c").unwrap();
+        assert_eq!(op_name(&expr), "and");
+        assert!(parse_program("a < b < c").is_err());
+    }
+
+    #[test]
+    fn lambda_body_extends_to_the_right() {
+        let expr = parse_program("(x: Int -> Int) => x(1) + 1").unwrap();
+        let ExprKind::Lambda { param, body, .. } = &expr.kind else {
+            panic!("expected a lambda");
+        };
+        assert!(matches!(
+            param.annotation.as_ref().unwrap().kind,
+            TypeExprKind::Function(..)
+        ));
+        assert_eq!(op_name(body), "add");
+    }
+
+    #[test]
+    fn multiple_parameters_nest_single_parameter_lambdas() {
+        let expr = parse_program("(x: Int)(y) => x + y").unwrap();
+        let ExprKind::Lambda { param, body, .. } = &expr.kind else {
+            panic!("expected a lambda");
+        };
+        assert_eq!(param.name, "x");
+        let ExprKind::Lambda { param, body, .. } = &body.kind else {
+            panic!("expected a nested lambda");
+        };
+        assert_eq!(param.name, "y");
+        assert_eq!(body.span.start.column, 16);
+        assert_eq!(op_name(body), "add");
+        assert!(matches!(
+            parse_program("(f)(x)").unwrap().kind,
+            ExprKind::Apply { .. }
+        ));
+        assert!(parse_program("(x)(1) => x").is_err());
+    }
+
+    #[test]
+    fn effect_annotation_belongs_to_the_outermost_lambda() {
+        let expr = parse_program("(x)(y) with [IO, State] => perform('IO', x)").unwrap();
+        let ExprKind::Lambda { effects, body, .. } = &expr.kind else {
+            panic!("expected a lambda");
+        };
+        assert_eq!(
+            effects.as_deref(),
+            Some(&["IO".to_string(), "State".to_string()][..])
+        );
+        assert!(matches!(body.kind, ExprKind::Lambda { effects: None, .. }));
+        assert!(parse_program("(x) with [] => x").is_ok());
+        assert!(parse_program("(x) with ['Custom:Log'] => x").is_ok());
+        assert!(parse_program("(x) with [IO,] => x").is_err());
+    }
+
+    #[test]
+    fn let_body_extends_to_the_right() {
+        let expr = parse_program("let x = 1 + 2 in let y = x in x * y").unwrap();
+        let ExprKind::Let { param, value, body } = &expr.kind else {
+            panic!("expected a let");
+        };
+        assert_eq!(param.name, "x");
+        assert_eq!(op_name(value), "add");
+        assert!(matches!(body.kind, ExprKind::Let { .. }));
+        assert!(parse_program("let x = 1 x").is_err());
+    }
+
+    #[test]
+    fn letrec_and_if_parse_like_let() {
+        let expr =
+            parse_program("letrec f = (x) => if x == 0 then 0 else f(x - 1) in f(3)").unwrap();
+        let ExprKind::LetRec { param, value, body } = &expr.kind else {
+            panic!("expected a letrec");
+        };
+        assert_eq!(param.name, "f");
+        assert!(matches!(body.kind, ExprKind::Apply { .. }));
+        let ExprKind::Lambda { body, .. } = &value.kind else {
+            panic!("expected a lambda");
+        };
+        let ExprKind::If { else_branch, .. } = &body.kind else {
+            panic!("expected an if");
+        };
+        assert!(matches!(else_branch.kind, ExprKind::Apply { .. }));
+        assert!(parse_program("if true then 1").is_err());
+    }
+
+    #[test]
+    fn parenthesised_variable_is_not_a_lambda() {
+        let expr = parse_program("(f)(1)").unwrap();
+        assert!(matches!(expr.kind, ExprKind::Apply { .. }));
+    }
+
+    #[test]
+    fn empty_parentheses_are_the_unit_literal() {
+        let expr = parse_program("f(())").unwrap();
+        let ExprKind::Apply { argument, .. } = expr.kind else {
+            panic!("expected an application");
+        };
+        assert_eq!(argument.kind, ExprKind::Unit);
+        assert!(parse_program("( )").is_ok());
+        assert!(parse_program("(1 + )").is_err());
+    }
+
+    #[test]
+    fn missing_operand_reports_its_position() {
+        let error = parse_program("(x) =>\n  x +").unwrap_err();
+        assert_eq!(error.position(), Some((2, 6)));
+        assert!(error.to_string().contains("expected an expression"));
+    }
+
+    #[test]
+    fn errors_list_what_was_expected_and_point_past_wide_characters() {
+        let source = "let é = 1 in\n  é +";
+        let error = parse_program(source).unwrap_err();
+        let ParseError::Syntax { expected, .. } = &error else {
+            panic!("expected a syntax error, got {:?}", error);
+        };
+        assert_eq!(expected, &["an expression"]);
+        assert_eq!(error.position(), Some((2, 6)));
+        assert_eq!(
+            error.snippet(source).to_string(),
+            "2:6: expected an expression, found end of input\n  |\n2 |   é +\n  |      ^"
+        );
+
+        let error = parse_program("(ü) => ü ?").unwrap_err();
+        assert_eq!(error.position(), Some((1, 10)));
+        assert!(
+            error
+                .snippet("(ü) => ü ?")
+                .to_string()
+                .ends_with("\n  |          ^")
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected_instead_of_overflowing() {
+        let source = format!("{}1{}", "(".repeat(10_000), ")".repeat(10_000));
+        let error = parse_program(&source).unwrap_err();
+        assert!(error.to_string().contains("nested too deeply"));
+    }
+}
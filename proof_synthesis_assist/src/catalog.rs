@@ -0,0 +1,191 @@
+//! Message catalogs for localized explanations.
+//!
+//! Explanations are rendered from templates looked up by a stable message key
+//! (`<error code>.<part>`, e.g. `T001.explanation`). Templates use `{name}`
+//! placeholders filled in by the explainer. Keys missing from a catalog fall
+//! back to the built-in English text, so a translation can be partial.
+
+use std::collections::HashMap;
+
+/// Built-in English templates, keyed by message key.
+const ENGLISH: &[(&str, &str)] = &[
+    (
+        "T000.explanation",
+        "The type checker does not support this yet: {what}.",
+    ),
+    (
+        "T000.fix",
+        "Rewrite this part without {what}, using only constructs the checker supports.",
+    ),
+    (
+        "T001.explanation",
+        "This expression has type {found} but the context requires {expected}.",
+    ),
+    (
+        "T001.fix",
+        "Change the expression so that it produces a value of type {expected}.",
+    ),
+    (
+        "T002.explanation",
+        "The type T{var} would have to contain itself ({ty}), which describes an infinite type. \
+         This usually means a function is applied to itself.",
+    ),
+    (
+        "T002.fix",
+        "Avoid self-application; pass a separate function value instead.",
+    ),
+    (
+        "T003.explanation",
+        "The variable '{name}' is not bound by any enclosing lambda.",
+    ),
+    (
+        "T003.fix",
+        "Introduce '{name}' as a parameter, e.g. ({name}) => ..., or fix the spelling.",
+    ),
+    ("T003.fix_rename", "Did you mean '{candidate}'?"),
+    (
+        "T004.explanation",
+        "This expression applies a value that is not a function (node {node}), \
+         so there is nothing to call.",
+    ),
+    (
+        "T004.fix",
+        "Check the expression being applied; remove the argument or apply a function instead, \
+         e.g. f(x) where f is a lambda.",
+    ),
+    (
+        "T005.explanation",
+        "The parameter is annotated as {annotated} but the function body uses it as {inferred}.",
+    ),
+    ("T005.fix", "Change the annotation to {inferred}."),
+    (
+        "T005.fix_manual",
+        "Change the annotation to match how the parameter is used.",
+    ),
+    (
+        "T006.explanation",
+        "This match does not handle every possible value; these cases are missing: {missing}.",
+    ),
+    (
+        "T006.fix",
+        "Add an arm for each missing case ({missing}), or a catch-all `_` arm.",
+    ),
+    (
+        "T007.explanation",
+        "The program refers to node {node}, which is not in the graph. \
+         The graph was probably edited or loaded incompletely.",
+    ),
+    (
+        "T007.fix",
+        "Regenerate the graph from source, or restore node {node} before checking again.",
+    ),
+    (
+        "E001.explanation",
+        "This expression performs the '{effect}' effect, which is not allowed here. \
+         Allowed effects: {allowed}.",
+    ),
+    (
+        "E001.fix",
+        "Allow the '{effect}' effect for this code, or remove the perform.",
+    ),
+];
+
+/// A set of message templates for one locale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageCatalog {
+    locale: String,
+    templates: HashMap<String, String>,
+}
+
+impl MessageCatalog {
+    /// Creates an empty catalog; every lookup falls back to English.
+    pub fn new(locale: impl Into<String>) -> Self {
+        Self {
+            locale: locale.into(),
+            templates: HashMap::new(),
+        }
+    }
+
+    /// The built-in English catalog.
+    pub fn english() -> Self {
+        let mut catalog = Self::new("en");
+        for (key, template) in ENGLISH {
+            catalog.insert(*key, *template);
+        }
+        catalog
+    }
+
+    /// Locale identifier of the catalog, e.g. `en` or `fr`.
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// Adds or replaces the template for `key`.
+    pub fn insert(&mut self, key: impl Into<String>, template: impl Into<String>) {
+        self.templates.insert(key.into(), template.into());
+    }
+
+    /// Builder-style variant of [`MessageCatalog::insert`].
+    pub fn with_message(mut self, key: impl Into<String>, template: impl Into<String>) -> Self {
+        self.insert(key, template);
+        self
+    }
+
+    /// Renders the template for `key`, substituting `{name}` placeholders.
+    ///
+    /// Falls back to the English template when the catalog lacks the key, and
+    /// to the key itself if no template exists at all.
+    pub fn render(&self, key: &str, args: &[(&str, String)]) -> String {
+        let template = self
+            .templates
+            .get(key)
+            .map(String::as_str)
+            .or_else(|| {
+                ENGLISH
+                    .iter()
+                    .find(|(english_key, _)| *english_key == key)
+                    .map(|(_, template)| *template)
+            })
+            .unwrap_or(key);
+
+        args.iter()
+            .fold(template.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), value)
+            })
+    }
+}
+
+impl Default for MessageCatalog {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_substitutes_every_occurrence() {
+        let catalog = MessageCatalog::english();
+        assert_eq!(
+            catalog.render("T003.fix", &[("name", "y".to_string())]),
+            "Introduce 'y' as a parameter, e.g. (y) => ..., or fix the spelling."
+        );
+    }
+
+    #[test]
+    fn missing_keys_fall_back_to_english() {
+        let catalog =
+            MessageCatalog::new("fr").with_message("T002.fix", "Évitez l'auto-application.");
+        assert_eq!(
+            catalog.render("T002.fix", &[]),
+            "Évitez l'auto-application."
+        );
+        assert_eq!(
+            catalog.render("T005.fix", &[("inferred", "Int".to_string())]),
+            "Change the annotation to Int."
+        );
+        assert_eq!(catalog.render("X999.explanation", &[]), "X999.explanation");
+    }
+}
@@ -0,0 +1,23 @@
+//! Errors produced by the explainer and patch application.
+
+use thiserror::Error;
+
+/// Failure to explain an error.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ExplainError {
+    /// The error kind has no explanation yet.
+    #[error("no explanation available for {0}")]
+    UnknownErrorType(String),
+}
+
+/// Failure to apply an [`AsgPatch`](crate::AsgPatch).
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PatchError {
+    /// The patch edits a node that is not in the graph.
+    #[error("patch targets missing node {0}")]
+    MissingNode(u64),
+
+    /// The patch refers to a placeholder that no earlier edit inserted.
+    #[error("patch refers to undeclared placeholder {0}")]
+    UnknownPlaceholder(u64),
+}
@@ -0,0 +1,549 @@
+//! Explanations for Level 1 type errors.
+
+use std::collections::HashSet;
+
+use asg_core::{AsgGraph, NodeContent, TermVariable};
+use type_checker_l1::TypeError;
+
+use crate::catalog::MessageCatalog;
+use crate::error::ExplainError;
+use crate::patch::{AsgPatch, PatchEdit};
+
+/// A user-facing account of an error and how to fix it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// Stable identifier of the error kind, e.g. `T001`. Never localized.
+    pub error_code: String,
+    /// The node the error is attached to, if any.
+    pub node_id: Option<u64>,
+    /// What went wrong, in prose.
+    pub explanation: String,
+    /// A suggested fix, in prose or as a code snippet.
+    pub code_fix: Option<String>,
+    /// A mechanical fix, present only when one can be derived safely.
+    pub patch: Option<AsgPatch>,
+}
+
+/// Produces explanations using a particular [`MessageCatalog`].
+#[derive(Debug, Clone, Default)]
+pub struct Explainer {
+    catalog: MessageCatalog,
+}
+
+impl Explainer {
+    /// Creates an explainer that renders messages from `catalog`.
+    pub fn new(catalog: MessageCatalog) -> Self {
+        Self { catalog }
+    }
+
+    /// The catalog messages are rendered from.
+    pub fn catalog(&self) -> &MessageCatalog {
+        &self.catalog
+    }
+
+    /// Explains a type error using only the information carried by the error.
+    pub fn explain_type_error(&self, error: &TypeError) -> Result<Explanation, ExplainError> {
+        let catalog = &self.catalog;
+        match error {
+            TypeError::UnificationFail {
+                node_id,
+                expected,
+                found,
+            } => {
+                let args = [
+                    ("expected", expected.to_string()),
+                    ("found", found.to_string()),
+                ];
+                Ok(Explanation {
+                    error_code: "T001".to_string(),
+                    node_id: Some(*node_id),
+                    explanation: catalog.render("T001.explanation", &args),
+                    code_fix: Some(catalog.render("T001.fix", &args)),
+                    patch: None,
+                })
+            }
+            TypeError::OccursCheck { node_id, var, ty } => {
+                let args = [("var", var.to_string()), ("ty", ty.to_string())];
+                Ok(Explanation {
+                    error_code: "T002".to_string(),
+                    node_id: Some(*node_id),
+                    explanation: catalog.render("T002.explanation", &args),
+                    code_fix: Some(catalog.render("T002.fix", &args)),
+                    patch: None,
+                })
+            }
+            TypeError::UndefinedVariable { node_id, name } => {
+                let args = [("name", name.clone())];
+                Ok(Explanation {
+                    error_code: "T003".to_string(),
+                    node_id: Some(*node_id),
+                    explanation: catalog.render("T003.explanation", &args),
+                    code_fix: Some(catalog.render("T003.fix", &args)),
+                    patch: None,
+                })
+            }
+            TypeError::AnnotationMismatch {
+                node_id,
+                annotation_id,
+                annotated,
+                inferred,
+            } => {
+                let patch = AsgPatch::replace_type_node(*annotation_id, inferred);
+                let args = [
+                    ("annotated", annotated.to_string()),
+                    ("inferred", inferred.to_string()),
+                ];
+                let fix_key = if patch.is_some() {
+                    "T005.fix"
+                } else {
+                    "T005.fix_manual"
+                };
+                Ok(Explanation {
+                    error_code: "T005".to_string(),
+                    node_id: Some(*node_id),
+                    explanation: catalog.render("T005.explanation", &args),
+                    code_fix: Some(catalog.render(fix_key, &args)),
+                    patch,
+                })
+            }
+            TypeError::Unimplemented(what) => {
+                let args = [("what", what.clone())];
+                Ok(Explanation {
+                    error_code: "T000".to_string(),
+                    node_id: None,
+                    explanation: catalog.render("T000.explanation", &args),
+                    code_fix: Some(catalog.render("T000.fix", &args)),
+                    patch: None,
+                })
+            }
+            TypeError::ApplicationMismatch(node_id) => {
+                let args = [("node", node_id.to_string())];
+                Ok(Explanation {
+                    error_code: "T004".to_string(),
+                    node_id: Some(*node_id),
+                    explanation: catalog.render("T004.explanation", &args),
+                    code_fix: Some(catalog.render("T004.fix", &args)),
+                    patch: None,
+                })
+            }
+            TypeError::NonExhaustiveMatch { node_id, missing } => {
+                let args = [("missing", missing.join(", "))];
+                Ok(Explanation {
+                    error_code: "T006".to_string(),
+                    node_id: Some(*node_id),
+                    explanation: catalog.render("T006.explanation", &args),
+                    code_fix: Some(catalog.render("T006.fix", &args)),
+                    patch: None,
+                })
+            }
+            TypeError::EffectNotAllowed {
+                node_id,
+                effect,
+                allowed,
+            } => Ok(self.explain_effect_error(*node_id, effect, allowed)),
+            TypeError::MissingNode(node_id) => {
+                let args = [("node", node_id.to_string())];
+                Ok(Explanation {
+                    error_code: "T007".to_string(),
+                    node_id: Some(*node_id),
+                    explanation: catalog.render("T007.explanation", &args),
+                    code_fix: Some(catalog.render("T007.fix", &args)),
+                    patch: None,
+                })
+            }
+        }
+    }
+
+    /// Explains a type error, using `graph` to derive patches that need context.
+    ///
+    /// For an undefined variable whose name is a near miss for an enclosing
+    /// parameter, the patch renames it and links it to that parameter.
+    pub fn explain_type_error_in_graph(
+        &self,
+        error: &TypeError,
+        graph: &AsgGraph,
+    ) -> Result<Explanation, ExplainError> {
+        let mut explanation = self.explain_type_error(error)?;
+        if let TypeError::UndefinedVariable { node_id, name } = error
+            && let Some((binder_name, lambda_id)) = closest_enclosing_binder(graph, *node_id, name)
+        {
+            explanation.code_fix = Some(
+                self.catalog
+                    .render("T003.fix_rename", &[("candidate", binder_name.clone())]),
+            );
+            explanation.patch = Some(AsgPatch {
+                edits: vec![PatchEdit::Replace {
+                    node_id: *node_id,
+                    content: NodeContent::TermVariable(TermVariable {
+                        name: binder_name,
+                        definition_node_id: lambda_id,
+                    }),
+                }],
+            });
+        }
+        Ok(explanation)
+    }
+
+    /// Explains why performing `effect` at `node_id` is not permitted.
+    pub fn explain_effect_error(
+        &self,
+        node_id: u64,
+        effect: &str,
+        allowed: &[String],
+    ) -> Explanation {
+        let allowed = if allowed.is_empty() {
+            "none".to_string()
+        } else {
+            allowed.join(", ")
+        };
+        let args = [("effect", effect.to_string()), ("allowed", allowed)];
+        Explanation {
+            error_code: "E001".to_string(),
+            node_id: Some(node_id),
+            explanation: self.catalog.render("E001.explanation", &args),
+            code_fix: Some(self.catalog.render("E001.fix", &args)),
+            patch: None,
+        }
+    }
+}
+
+/// Explains a type error in English. See [`Explainer::explain_type_error`].
+pub fn explain_type_error(error: &TypeError) -> Result<Explanation, ExplainError> {
+    Explainer::default().explain_type_error(error)
+}
+
+/// Explains a type error in English with graph context. See
+/// [`Explainer::explain_type_error_in_graph`].
+pub fn explain_type_error_in_graph(
+    error: &TypeError,
+    graph: &AsgGraph,
+) -> Result<Explanation, ExplainError> {
+    Explainer::default().explain_type_error_in_graph(error, graph)
+}
+
+/// Explains a disallowed effect in English. See [`Explainer::explain_effect_error`].
+pub fn explain_effect_error(node_id: u64, effect: &str, allowed: &[String]) -> Explanation {
+    Explainer::default().explain_effect_error(node_id, effect, allowed)
+}
+
+/// Maximum edit distance for a binder name to count as a likely typo.
+const MAX_TYPO_DISTANCE: usize = 2;
+
+/// Finds the enclosing parameter whose name is closest to `name`.
+///
+/// Ties go to the innermost binder.
+fn closest_enclosing_binder(graph: &AsgGraph, node_id: u64, name: &str) -> Option<(String, u64)> {
+    let root = graph.root()?;
+    let mut path = Vec::new();
+    if !path_to(graph, root, node_id, &mut path, &mut HashSet::new()) {
+        return None;
+    }
+
+    path.iter()
+        .rev()
+        .filter_map(|&ancestor| match &graph.get_node(ancestor)?.content {
+            NodeContent::TermLambda(lambda) => {
+                match &graph.get_node(lambda.binder_variable_node_id)?.content {
+                    NodeContent::TermVariable(binder) => Some((binder.name.clone(), ancestor)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .map(|(binder, lambda)| (edit_distance(&binder, name), binder, lambda))
+        .filter(|(distance, _, _)| *distance <= MAX_TYPO_DISTANCE)
+        .min_by_key(|(distance, _, _)| *distance)
+        .map(|(_, binder, lambda)| (binder, lambda))
+}
+
+/// Collects the ancestors of `target` (excluding it) on a path from `current`.
+fn path_to(
+    graph: &AsgGraph,
+    current: u64,
+    target: u64,
+    path: &mut Vec<u64>,
+    visited: &mut HashSet<u64>,
+) -> bool {
+    if current == target {
+        return true;
+    }
+    if !visited.insert(current) {
+        return false;
+    }
+    let Some(node) = graph.get_node(current) else {
+        return false;
+    };
+    path.push(current);
+    for child in node.content.child_ids() {
+        if path_to(graph, child, target, path, visited) {
+            return true;
+        }
+    }
+    path.pop();
+    false
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asg_core::{LiteralInt, PrimitiveOp, TermLambda, TypeKind, TypeNode};
+    use type_checker_l1::{Type, check_graph};
+
+    /// Builds `(x: <annotation>) => <use> + 1`, where the use site is named `use_name`.
+    fn increment(annotation: TypeKind, use_name: &str) -> (AsgGraph, u64) {
+        let mut graph = AsgGraph::new();
+        let binder = graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: "x".to_string(),
+            definition_node_id: 0,
+        }));
+        let use_site = graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: use_name.to_string(),
+            definition_node_id: 0,
+        }));
+        let one = graph.add_node(NodeContent::LiteralInt(LiteralInt { value: 1 }));
+        let add = graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "add".to_string(),
+            argument_node_ids: vec![use_site, one],
+        }));
+        let annotation = graph.add_node(NodeContent::TypeNode(TypeNode { kind: annotation }));
+        let lambda = graph.add_node(NodeContent::TermLambda(TermLambda {
+            binder_variable_node_id: binder,
+            body_node_id: add,
+            type_annotation_id: Some(annotation),
+            effect_annotation: None,
+        }));
+        let mut bound = vec![binder];
+        if use_name == "x" {
+            bound.push(use_site);
+        }
+        for var in bound {
+            if let NodeContent::TermVariable(v) = &mut graph.get_node_mut(var).unwrap().content {
+                v.definition_node_id = lambda;
+            }
+        }
+        graph.set_root(lambda);
+        (graph, use_site)
+    }
+
+    #[test]
+    fn annotation_mismatch_includes_applicable_patch() {
+        let (mut graph, _) = increment(TypeKind::Bool, "x");
+        let error = check_graph(&graph).unwrap_err();
+
+        let explanation = explain_type_error(&error).unwrap();
+        assert_eq!(explanation.error_code, "T005");
+        assert!(explanation.explanation.contains("Bool"));
+        let patch = explanation
+            .patch
+            .expect("annotation mismatch is auto-fixable");
+
+        patch.apply(&mut graph).unwrap();
+        let types = check_graph(&graph).unwrap();
+        assert_eq!(
+            types[&graph.root().unwrap()],
+            Type::function(Type::Int, Type::Int)
+        );
+    }
+
+    #[test]
+    fn misspelled_variable_is_rebound_to_enclosing_parameter() {
+        let (mut graph, use_site) = increment(TypeKind::Int, "xx");
+        let error = check_graph(&graph).unwrap_err();
+        assert!(matches!(error, TypeError::UndefinedVariable { .. }));
+
+        let explanation = explain_type_error_in_graph(&error, &graph).unwrap();
+        assert_eq!(explanation.node_id, Some(use_site));
+        explanation.patch.unwrap().apply(&mut graph).unwrap();
+        assert!(check_graph(&graph).is_ok());
+    }
+
+    #[test]
+    fn unrelated_variable_gets_prose_only() {
+        let (graph, _) = increment(TypeKind::Int, "counter");
+        let error = check_graph(&graph).unwrap_err();
+
+        let explanation = explain_type_error_in_graph(&error, &graph).unwrap();
+        assert!(explanation.patch.is_none());
+        assert!(explanation.code_fix.unwrap().contains("counter"));
+    }
+
+    #[test]
+    fn french_catalog_localizes_prose_but_not_codes() {
+        let french = MessageCatalog::new("fr").with_message(
+            "T001.explanation",
+            "Cette expression a le type {found} alors que le contexte exige {expected}.",
+        );
+        let error = TypeError::UnificationFail {
+            node_id: 7,
+            expected: Type::Int,
+            found: Type::Bool,
+        };
+
+        let explanation = Explainer::new(french).explain_type_error(&error).unwrap();
+        assert_eq!(
+            explanation.explanation,
+            "Cette expression a le type Bool alors que le contexte exige Int."
+        );
+        assert_eq!(explanation.error_code, "T001");
+        assert_eq!(explanation.node_id, Some(7));
+        // Untranslated parts fall back to English.
+        assert_eq!(
+            explanation.code_fix.unwrap(),
+            "Change the expression so that it produces a value of type Int."
+        );
+    }
+
+    #[test]
+    fn effect_error_lists_allowed_effects() {
+        let explanation = explain_effect_error(3, "IO", &["State".to_string()]);
+        assert_eq!(explanation.error_code, "E001");
+        assert!(explanation.explanation.contains("'IO'"));
+        assert!(explanation.explanation.contains("Allowed effects: State."));
+    }
+
+    #[test]
+    fn application_mismatch_points_at_the_application() {
+        let explanation = explain_type_error(&TypeError::ApplicationMismatch(12)).unwrap();
+        assert_eq!(explanation.error_code, "T004");
+        assert_eq!(explanation.node_id, Some(12));
+        assert!(explanation.explanation.contains("not a function"));
+        assert!(explanation.code_fix.unwrap().contains("apply a function"));
+    }
+
+    #[test]
+    fn annotation_mismatch_names_both_types() {
+        let explanation = explain_type_error(&TypeError::AnnotationMismatch {
+            node_id: 4,
+            annotation_id: 5,
+            annotated: Type::Bool,
+            inferred: Type::Int,
+        })
+        .unwrap();
+        assert_eq!(explanation.error_code, "T005");
+        assert!(explanation.explanation.contains("annotated as Bool"));
+        assert!(explanation.explanation.contains("uses it as Int"));
+        assert_eq!(
+            explanation.code_fix.unwrap(),
+            "Change the annotation to Int."
+        );
+    }
+
+    #[test]
+    fn non_exhaustive_match_lists_missing_cases() {
+        let explanation = explain_type_error(&TypeError::NonExhaustiveMatch {
+            node_id: 9,
+            missing: vec!["None".to_string(), "Some".to_string()],
+        })
+        .unwrap();
+        assert_eq!(explanation.error_code, "T006");
+        assert!(explanation.explanation.contains("None, Some"));
+        assert!(explanation.code_fix.unwrap().contains("catch-all"));
+    }
+
+    #[test]
+    fn effect_not_allowed_matches_effect_explanation() {
+        let error = TypeError::EffectNotAllowed {
+            node_id: 3,
+            effect: "IO".to_string(),
+            allowed: vec![],
+        };
+        let explanation = explain_type_error(&error).unwrap();
+        assert_eq!(explanation, explain_effect_error(3, "IO", &[]));
+        assert!(explanation.explanation.contains("Allowed effects: none."));
+    }
+
+    /// One error of every kind. Matching without a wildcard makes adding a
+    /// variant fail to compile until it is listed here.
+    fn every_type_error() -> Vec<TypeError> {
+        let errors = vec![
+            TypeError::Unimplemented("records".to_string()),
+            TypeError::UnificationFail {
+                node_id: 1,
+                expected: Type::Int,
+                found: Type::Bool,
+            },
+            TypeError::OccursCheck {
+                node_id: 2,
+                var: 0,
+                ty: Type::function(Type::Var(0), Type::Int),
+            },
+            TypeError::UndefinedVariable {
+                node_id: 3,
+                name: "y".to_string(),
+            },
+            TypeError::ApplicationMismatch(4),
+            TypeError::AnnotationMismatch {
+                node_id: 5,
+                annotation_id: 6,
+                annotated: Type::Bool,
+                inferred: Type::Int,
+            },
+            TypeError::NonExhaustiveMatch {
+                node_id: 7,
+                missing: vec!["None".to_string()],
+            },
+            TypeError::EffectNotAllowed {
+                node_id: 8,
+                effect: "IO".to_string(),
+                allowed: vec![],
+            },
+            TypeError::MissingNode(9),
+        ];
+        for error in &errors {
+            match error {
+                TypeError::Unimplemented(_)
+                | TypeError::UnificationFail { .. }
+                | TypeError::OccursCheck { .. }
+                | TypeError::UndefinedVariable { .. }
+                | TypeError::ApplicationMismatch(_)
+                | TypeError::AnnotationMismatch { .. }
+                | TypeError::NonExhaustiveMatch { .. }
+                | TypeError::EffectNotAllowed { .. }
+                | TypeError::MissingNode(_) => {}
+            }
+        }
+        errors
+    }
+
+    #[test]
+    fn every_type_error_has_an_explanation_and_fix() {
+        let mut fixes = HashSet::new();
+        for error in every_type_error() {
+            let explanation = explain_type_error(&error)
+                .unwrap_or_else(|e| panic!("{:?} is not explained: {}", error, e));
+            assert_eq!(explanation.error_code, error.code());
+            assert_eq!(explanation.node_id, error.node_id());
+            let fix = explanation.code_fix.expect("every error suggests a fix");
+            assert!(fixes.insert(fix), "fix for {:?} is not specific", error);
+        }
+    }
+
+    #[test]
+    fn missing_node_names_the_node() {
+        let explanation = explain_type_error(&TypeError::MissingNode(42)).unwrap();
+        assert_eq!(explanation.error_code, "T007");
+        assert!(explanation.explanation.contains("node 42"));
+        assert!(explanation.code_fix.unwrap().contains("node 42"));
+    }
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("x", "x"), 0);
+        assert_eq!(edit_distance("xx", "x"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
@@ -1,14 +1,19 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Human-oriented explanations and fixes for checker errors.
+//!
+//! [`explain_type_error`] turns a [`type_checker_l1::TypeError`] into prose
+//! plus, where the fix is mechanical, an [`AsgPatch`] that tools such as the
+//! LSP code actions can apply deterministically. Prose is rendered from a
+//! [`MessageCatalog`], so it can be translated while error codes and node ids
+//! stay stable for tooling.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod catalog;
+pub mod error;
+pub mod explain;
+pub mod patch;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use catalog::MessageCatalog;
+pub use error::{ExplainError, PatchError};
+pub use explain::{
+    Explainer, Explanation, explain_effect_error, explain_type_error, explain_type_error_in_graph,
+};
+pub use patch::{AsgPatch, PatchEdit};
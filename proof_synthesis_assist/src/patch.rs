@@ -0,0 +1,199 @@
+//! Structured, machine-applicable ASG edits.
+
+use std::collections::HashMap;
+
+use asg_core::{AsgGraph, NodeContent, TypeKind, TypeNode};
+use type_checker_l1::Type;
+
+use crate::error::PatchError;
+
+/// Ids at or above this value are patch-local placeholders, not graph ids.
+pub const PLACEHOLDER_BASE: u64 = 1 << 63;
+
+/// A single edit within an [`AsgPatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchEdit {
+    /// Adds a new node. Later edits refer to it by `placeholder`.
+    Insert {
+        placeholder: u64,
+        content: NodeContent,
+    },
+    /// Replaces the content of an existing node, keeping its id and metadata.
+    Replace { node_id: u64, content: NodeContent },
+}
+
+/// An ordered list of edits that fixes an error when applied to the graph it
+/// was computed for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AsgPatch {
+    pub edits: Vec<PatchEdit>,
+}
+
+impl AsgPatch {
+    /// Applies the edits in order.
+    ///
+    /// Returns the mapping from placeholders to the ids of inserted nodes.
+    /// Validation happens before any edit is made, so a failing patch leaves
+    /// the graph untouched.
+    pub fn apply(&self, graph: &mut AsgGraph) -> Result<HashMap<u64, u64>, PatchError> {
+        let mut declared = Vec::new();
+        for edit in &self.edits {
+            let content = match edit {
+                PatchEdit::Insert {
+                    placeholder,
+                    content,
+                } => {
+                    declared.push(*placeholder);
+                    content
+                }
+                PatchEdit::Replace { node_id, content } => {
+                    if graph.get_node(*node_id).is_none() {
+                        return Err(PatchError::MissingNode(*node_id));
+                    }
+                    content
+                }
+            };
+            for child in content.child_ids() {
+                if child >= PLACEHOLDER_BASE && !declared.contains(&child) {
+                    return Err(PatchError::UnknownPlaceholder(child));
+                }
+            }
+        }
+
+        let mut placeholders = HashMap::new();
+        for edit in &self.edits {
+            match edit {
+                PatchEdit::Insert {
+                    placeholder,
+                    content,
+                } => {
+                    let id = graph.add_node(resolve(content, &placeholders));
+                    placeholders.insert(*placeholder, id);
+                }
+                PatchEdit::Replace { node_id, content } => {
+                    let node = graph
+                        .get_node_mut(*node_id)
+                        .ok_or(PatchError::MissingNode(*node_id))?;
+                    node.content = resolve(content, &placeholders);
+                }
+            }
+        }
+        Ok(placeholders)
+    }
+
+    /// Builds a patch that overwrites the type node `target` with `ty`.
+    ///
+    /// Returns `None` if `ty` contains type variables or quantifiers, which
+    /// annotations cannot express.
+    pub fn replace_type_node(target: u64, ty: &Type) -> Option<AsgPatch> {
+        let mut patch = AsgPatch::default();
+        let kind = patch.type_kind(ty)?;
+        patch.edits.push(PatchEdit::Replace {
+            node_id: target,
+            content: NodeContent::TypeNode(TypeNode { kind }),
+        });
+        Some(patch)
+    }
+
+    /// Emits inserts for the components of `ty` and returns its top-level kind.
+    fn type_kind(&mut self, ty: &Type) -> Option<TypeKind> {
+        Some(match ty {
+            Type::Int => TypeKind::Int,
+            Type::Bool => TypeKind::Bool,
+            Type::Unit => TypeKind::Unit,
+            Type::Function(param, ret) => TypeKind::Function {
+                param_type_id: self.insert_type(param)?,
+                return_type_id: self.insert_type(ret)?,
+            },
+            Type::Ref(inner) => TypeKind::Ref {
+                element_type_id: self.insert_type(inner)?,
+            },
+            Type::Var(_) | Type::ForAll(..) => return None,
+        })
+    }
+
+    fn insert_type(&mut self, ty: &Type) -> Option<u64> {
+        let kind = self.type_kind(ty)?;
+        let placeholder = PLACEHOLDER_BASE + self.edits.len() as u64;
+        self.edits.push(PatchEdit::Insert {
+            placeholder,
+            content: NodeContent::TypeNode(TypeNode { kind }),
+        });
+        Some(placeholder)
+    }
+}
+
+fn resolve(content: &NodeContent, placeholders: &HashMap<u64, u64>) -> NodeContent {
+    let mut content = content.clone();
+    content.map_child_ids(|id| placeholders.get(&id).copied().unwrap_or(id));
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_type_node_builds_nested_types() {
+        let mut graph = AsgGraph::new();
+        let target = graph.add_node(NodeContent::TypeNode(TypeNode {
+            kind: TypeKind::Bool,
+        }));
+
+        let patch =
+            AsgPatch::replace_type_node(target, &Type::function(Type::Int, Type::Bool)).unwrap();
+        let inserted = patch.apply(&mut graph).unwrap();
+        assert_eq!(inserted.len(), 2);
+
+        let NodeContent::TypeNode(TypeNode {
+            kind:
+                TypeKind::Function {
+                    param_type_id,
+                    return_type_id,
+                },
+        }) = graph.get_node(target).unwrap().content
+        else {
+            panic!("target was not replaced by a function type");
+        };
+        assert_eq!(
+            graph.get_node(param_type_id).unwrap().content,
+            NodeContent::TypeNode(TypeNode {
+                kind: TypeKind::Int
+            })
+        );
+        assert_eq!(
+            graph.get_node(return_type_id).unwrap().content,
+            NodeContent::TypeNode(TypeNode {
+                kind: TypeKind::Bool
+            })
+        );
+    }
+
+    #[test]
+    fn type_variables_cannot_be_patched_in() {
+        assert!(AsgPatch::replace_type_node(1, &Type::Var(0)).is_none());
+    }
+
+    #[test]
+    fn invalid_patch_leaves_graph_untouched() {
+        let mut graph = AsgGraph::new();
+        let patch = AsgPatch {
+            edits: vec![
+                PatchEdit::Insert {
+                    placeholder: PLACEHOLDER_BASE,
+                    content: NodeContent::TypeNode(TypeNode {
+                        kind: TypeKind::Int,
+                    }),
+                },
+                PatchEdit::Replace {
+                    node_id: 42,
+                    content: NodeContent::TypeNode(TypeNode {
+                        kind: TypeKind::Int,
+                    }),
+                },
+            ],
+        };
+        assert_eq!(patch.apply(&mut graph), Err(PatchError::MissingNode(42)));
+        assert!(graph.is_empty());
+    }
+}
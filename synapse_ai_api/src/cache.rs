@@ -0,0 +1,470 @@
+//! Graphs kept between requests, so an editor can send the nodes it
+//! changed rather than the whole graph on every keystroke.
+//!
+//! Each cached graph remembers the nodes edited since it was last checked.
+//! It is type checked again, as a whole, when one of them is reachable from
+//! the root; inference is global, so a subterm cannot be re-checked on its
+//! own. Only the reporting is scoped: an edit reports diagnostics for the
+//! affected nodes, which are the edited nodes, the terms containing them,
+//! the terms they contain, and every use of a binding whose value or body
+//! contains an edit, together with the terms containing those uses.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use asg_core::{AsgGraph, AsgNode, NodeContent, decode_nodes};
+use serde::{Deserialize, Serialize};
+use type_checker_l1::{TypeCheckMap, TypeError, check_graph_collect};
+
+use crate::diagnostic::Diagnostic;
+use crate::service::{Code, Status};
+
+/// A node-level change to a cached graph.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplyEditRequest {
+    pub graph_id: String,
+    /// New nodes, encoded with [`asg_core::encode_nodes`], or empty for
+    /// none. Their ids must not be in use.
+    pub added: Vec<u8>,
+    /// Replacements for existing nodes, encoded the same way.
+    pub modified: Vec<u8>,
+    pub removed: Vec<u64>,
+    /// The new root, if the edit moves it.
+    pub root: Option<u64>,
+}
+
+/// What an edit changed the diagnostics of.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApplyEditResponse {
+    /// The nodes whose diagnostics were recomputed, in id order.
+    pub affected: Vec<u64>,
+    /// Every current diagnostic of the affected nodes.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Decoded graphs by id, safe to share between request handlers.
+#[derive(Debug, Default)]
+pub struct AsgCache {
+    graphs: Mutex<HashMap<String, CachedGraph>>,
+}
+
+#[derive(Debug)]
+struct CachedGraph {
+    graph: AsgGraph,
+    /// Nodes edited since the graph was last checked.
+    dirty: HashSet<u64>,
+    /// The result of the last check, kept while no edit reaches the root.
+    checked: Option<(TypeCheckMap, Vec<TypeError>)>,
+}
+
+impl AsgCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores `graph` under `graph_id`, replacing any graph stored there.
+    pub fn store(&self, graph_id: impl Into<String>, graph: AsgGraph) {
+        let cached = CachedGraph {
+            graph,
+            dirty: HashSet::new(),
+            checked: None,
+        };
+        self.lock().insert(graph_id.into(), cached);
+    }
+
+    /// A copy of the graph stored under `graph_id`.
+    pub fn get(&self, graph_id: &str) -> Option<AsgGraph> {
+        self.lock().get(graph_id).map(|cached| cached.graph.clone())
+    }
+
+    /// Applies `edit` to its graph in place and re-checks what it affects.
+    ///
+    /// The edit is applied entirely or not at all: it fails with
+    /// [`Code::NotFound`] if the graph or a modified, removed or root node
+    /// does not exist, and with [`Code::InvalidArgument`] if the nodes do
+    /// not decode, an added id is in use, or a node is edited twice.
+    pub fn apply_edit(&self, edit: &ApplyEditRequest) -> Result<ApplyEditResponse, Status> {
+        let added = nodes(&edit.added)?;
+        let modified = nodes(&edit.modified)?;
+
+        let mut graphs = self.lock();
+        let cached = graphs.get_mut(&edit.graph_id).ok_or_else(|| {
+            let message = format!("no graph is stored as '{}'", edit.graph_id);
+            Status::new(Code::NotFound, message)
+        })?;
+
+        let mut edited = HashSet::new();
+        let ids = added
+            .iter()
+            .chain(&modified)
+            .map(|node| node.node_id)
+            .chain(edit.removed.iter().copied());
+        for node_id in ids {
+            if !edited.insert(node_id) {
+                let message = format!("node {} is edited more than once", node_id);
+                return Err(Status::new(Code::InvalidArgument, message));
+            }
+        }
+        if let Some(node) = added
+            .iter()
+            .find(|node| cached.graph.get_node(node.node_id).is_some())
+        {
+            let message = format!("node {} already exists", node.node_id);
+            return Err(Status::new(Code::InvalidArgument, message));
+        }
+        let existing = modified
+            .iter()
+            .map(|node| node.node_id)
+            .chain(edit.removed.iter().copied());
+        for node_id in existing {
+            if cached.graph.get_node(node_id).is_none() {
+                let message = format!("node {} does not exist", node_id);
+                return Err(Status::new(Code::NotFound, message));
+            }
+        }
+        if let Some(root) = edit.root
+            && cached.graph.get_node(root).is_none()
+            && !added.iter().any(|node| node.node_id == root)
+        {
+            let message = format!("root node {} does not exist", root);
+            return Err(Status::new(Code::NotFound, message));
+        }
+
+        for node in added.into_iter().chain(modified) {
+            cached.graph.insert_node(node);
+        }
+        for &node_id in &edit.removed {
+            cached.graph.remove_node(node_id);
+        }
+        if let Some(root) = edit.root {
+            cached.graph.set_root(root);
+            // A new root changes which terms are checked at all.
+            cached.checked = None;
+        }
+        cached.dirty.extend(&edited);
+        Ok(cached.refresh(&edit.graph_id, &edited))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CachedGraph>> {
+        self.graphs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Decodes the nodes of an edit; no bytes at all stand for no nodes.
+fn nodes(bytes: &[u8]) -> Result<Vec<AsgNode>, Status> {
+    if bytes.is_empty() {
+        return Ok(Vec::new());
+    }
+    decode_nodes(bytes).map_err(|error| Status::new(Code::InvalidArgument, error.to_string()))
+}
+
+impl CachedGraph {
+    /// Re-checks the graph if an edit since the last check is reachable
+    /// from the root, and reports the diagnostics of the nodes affected by
+    /// editing `edited`.
+    fn refresh(&mut self, graph_id: &str, edited: &HashSet<u64>) -> ApplyEditResponse {
+        let (parents, reachable) = self.structure();
+        if self.checked.is_none() || self.dirty.iter().any(|id| reachable.contains(id)) {
+            self.checked = Some(check_graph_collect(&self.graph));
+        }
+        self.dirty.clear();
+
+        let uses = self.uses();
+        let mut affected = HashSet::new();
+        // The terms containing an edited node now contain something else,
+        // and a binding containing one may now have a different type at
+        // each of its uses, and so on outward.
+        let mut pending: Vec<u64> = edited.iter().copied().collect();
+        while let Some(node_id) = pending.pop() {
+            if !affected.insert(node_id) {
+                continue;
+            }
+            pending.extend(parents.get(&node_id).into_iter().flatten());
+            match self.graph.get_node(node_id).map(|node| &node.content) {
+                Some(NodeContent::TermLambda(_)) => {
+                    pending.extend(uses.get(&node_id).into_iter().flatten());
+                }
+                // `let x = value in body`: the value's type is the type of
+                // `x` in the body.
+                Some(NodeContent::TermApplication(app))
+                    if matches!(
+                        self.graph
+                            .get_node(app.function_node_id)
+                            .map(|n| &n.content),
+                        Some(NodeContent::TermLambda(_))
+                    ) =>
+                {
+                    pending.push(app.function_node_id);
+                }
+                _ => {}
+            }
+        }
+        // The terms an edited node contains may now be checked differently.
+        let mut pending: Vec<u64> = edited.iter().copied().collect();
+        let mut below = HashSet::new();
+        while let Some(node_id) = pending.pop() {
+            if below.insert(node_id)
+                && let Some(node) = self.graph.get_node(node_id)
+            {
+                pending.extend(node.content.child_ids());
+            }
+        }
+        affected.extend(
+            below
+                .into_iter()
+                .filter(|id| self.graph.get_node(*id).is_some()),
+        );
+
+        let (_, errors) = self.checked.as_ref().expect("checked above");
+        let diagnostics = errors
+            .iter()
+            .filter(|error| error.node_id().is_none_or(|id| affected.contains(&id)))
+            .map(|error| Diagnostic::from_type_error(error, &self.graph, graph_id))
+            .collect();
+        let mut affected: Vec<u64> = affected.into_iter().collect();
+        affected.sort_unstable();
+        ApplyEditResponse {
+            affected,
+            diagnostics,
+        }
+    }
+
+    /// The variables referring to each lambda.
+    fn uses(&self) -> HashMap<u64, Vec<u64>> {
+        let mut uses: HashMap<u64, Vec<u64>> = HashMap::new();
+        for node in self.graph.nodes() {
+            if let NodeContent::TermVariable(var) = &node.content {
+                uses.entry(var.definition_node_id)
+                    .or_default()
+                    .push(node.node_id);
+            }
+        }
+        uses
+    }
+
+    /// The nodes having each node as a child, and the nodes reachable from
+    /// the root. Children that no longer exist are included as keys, so
+    /// their former parents can still be found.
+    fn structure(&self) -> (HashMap<u64, Vec<u64>>, HashSet<u64>) {
+        let mut parents: HashMap<u64, Vec<u64>> = HashMap::new();
+        for node in self.graph.nodes() {
+            for child in node.content.child_ids() {
+                parents.entry(child).or_default().push(node.node_id);
+            }
+        }
+        let mut reachable = HashSet::new();
+        let mut pending: Vec<u64> = self.graph.root().into_iter().collect();
+        while let Some(node_id) = pending.pop() {
+            if reachable.insert(node_id)
+                && let Some(node) = self.graph.get_node(node_id)
+            {
+                pending.extend(node.content.child_ids());
+            }
+        }
+        (parents, reachable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asg_core::{LiteralBool, LiteralInt, encode_nodes};
+
+    /// `(x) => x + 1`, stored as `main`, and the id of the literal.
+    fn stored() -> (AsgCache, u64) {
+        let graph = parser_core::parse_source("main.syn", "(x) => x + 1").unwrap();
+        let literal = graph
+            .nodes()
+            .find(|node| matches!(node.content, NodeContent::LiteralInt(_)))
+            .unwrap()
+            .node_id;
+        let cache = AsgCache::new();
+        cache.store("main", graph);
+        (cache, literal)
+    }
+
+    fn node(node_id: u64, content: NodeContent) -> AsgNode {
+        AsgNode {
+            node_id,
+            content,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn edits_report_diagnostics_of_the_affected_terms_only() {
+        let (cache, literal) = stored();
+        let graph = cache.get("main").unwrap();
+        let root = graph.root().unwrap();
+        let sum = graph.referrers(literal)[0];
+
+        // The editor sends the replacement with the location it keeps.
+        let replacement = AsgNode {
+            content: NodeContent::LiteralBool(LiteralBool { value: true }),
+            ..graph.get_node(literal).unwrap().clone()
+        };
+        let edit = ApplyEditRequest {
+            graph_id: "main".to_string(),
+            modified: encode_nodes(&[replacement]),
+            ..ApplyEditRequest::default()
+        };
+        let response = cache.apply_edit(&edit).unwrap();
+        // The parameter's type may change with the body, so its binder and
+        // use are affected too.
+        let variables = graph
+            .nodes()
+            .filter(|node| matches!(node.content, NodeContent::TermVariable(_)))
+            .map(|node| node.node_id);
+        let mut expected: Vec<u64> = [root, sum, literal].into_iter().chain(variables).collect();
+        expected.sort_unstable();
+        assert_eq!(response.affected, expected);
+        let codes: Vec<_> = response
+            .diagnostics
+            .iter()
+            .map(|d| d.code.as_str())
+            .collect();
+        assert_eq!(codes, ["T001"]);
+        assert_eq!(response.diagnostics[0].file, "main.syn");
+        assert!(response.diagnostics[0].range.is_some());
+
+        // An unreachable node is neither part of the affected terms nor
+        // the reason to check again; the error above is still reported
+        // where it is.
+        let spare = 100;
+        let edit = ApplyEditRequest {
+            graph_id: "main".to_string(),
+            added: encode_nodes(&[node(
+                spare,
+                NodeContent::LiteralInt(LiteralInt { value: 2 }),
+            )]),
+            ..ApplyEditRequest::default()
+        };
+        let response = cache.apply_edit(&edit).unwrap();
+        assert_eq!(response.affected, [spare]);
+        assert!(response.diagnostics.is_empty());
+
+        let edit = ApplyEditRequest {
+            graph_id: "main".to_string(),
+            modified: encode_nodes(&[node(
+                literal,
+                NodeContent::LiteralInt(LiteralInt { value: 1 }),
+            )]),
+            ..ApplyEditRequest::default()
+        };
+        assert!(cache.apply_edit(&edit).unwrap().diagnostics.is_empty());
+    }
+
+    #[test]
+    fn editing_a_let_value_reports_errors_at_its_uses() {
+        let graph =
+            parser_core::parse_source("main.syn", "let f = (x) => x in\nnot f(true)").unwrap();
+        let body = graph
+            .nodes()
+            .find_map(|node| match &node.content {
+                NodeContent::TermLambda(lambda) => match &graph
+                    .get_node(lambda.binder_variable_node_id)?
+                    .content
+                {
+                    NodeContent::TermVariable(var) if var.name == "x" => Some(lambda.body_node_id),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .unwrap();
+        let cache = AsgCache::new();
+        cache.store("main", graph.clone());
+        let edit = |content| ApplyEditRequest {
+            graph_id: "main".to_string(),
+            modified: encode_nodes(&[AsgNode {
+                content,
+                ..graph.get_node(body).unwrap().clone()
+            }]),
+            ..ApplyEditRequest::default()
+        };
+
+        // `f` now returns an Int, which `not` rejects on the other side of
+        // the `let`.
+        let response = cache
+            .apply_edit(&edit(NodeContent::LiteralInt(LiteralInt { value: 1 })))
+            .unwrap();
+        let codes: Vec<_> = response
+            .diagnostics
+            .iter()
+            .map(|d| d.code.as_str())
+            .collect();
+        assert_eq!(codes, ["T001"]);
+        assert_eq!(response.diagnostics[0].range.unwrap().start.line, 2);
+
+        // Undoing the edit clears it again.
+        let restored = graph.get_node(body).unwrap().content.clone();
+        let response = cache.apply_edit(&edit(restored)).unwrap();
+        assert!(response.diagnostics.is_empty());
+        let not = graph
+            .nodes()
+            .find(
+                |node| matches!(&node.content, NodeContent::PrimitiveOp(op) if op.op_name == "not"),
+            )
+            .unwrap();
+        assert!(response.affected.contains(&not.node_id));
+    }
+
+    #[test]
+    fn invalid_edits_leave_the_graph_unchanged() {
+        let (cache, literal) = stored();
+        let before = cache.get("main").unwrap();
+        let unit = NodeContent::LiteralInt(LiteralInt { value: 0 });
+
+        let cases = [
+            (
+                ApplyEditRequest {
+                    graph_id: "other".to_string(),
+                    ..ApplyEditRequest::default()
+                },
+                Code::NotFound,
+            ),
+            (
+                ApplyEditRequest {
+                    graph_id: "main".to_string(),
+                    added: encode_nodes(&[node(literal, unit.clone())]),
+                    ..ApplyEditRequest::default()
+                },
+                Code::InvalidArgument,
+            ),
+            (
+                ApplyEditRequest {
+                    graph_id: "main".to_string(),
+                    modified: encode_nodes(&[node(literal, unit.clone())]),
+                    removed: vec![999],
+                    ..ApplyEditRequest::default()
+                },
+                Code::NotFound,
+            ),
+            (
+                ApplyEditRequest {
+                    graph_id: "main".to_string(),
+                    modified: encode_nodes(&[node(literal, unit)]),
+                    removed: vec![literal],
+                    ..ApplyEditRequest::default()
+                },
+                Code::InvalidArgument,
+            ),
+            (
+                ApplyEditRequest {
+                    graph_id: "main".to_string(),
+                    added: b"{}".to_vec(),
+                    ..ApplyEditRequest::default()
+                },
+                Code::InvalidArgument,
+            ),
+        ];
+        for (edit, code) in cases {
+            assert_eq!(
+                cache.apply_edit(&edit).unwrap_err().code,
+                code,
+                "{:?}",
+                edit
+            );
+        }
+        assert_eq!(cache.get("main").unwrap(), before);
+    }
+}
@@ -0,0 +1,137 @@
+//! Structured diagnostics exchanged with tools and AI agents.
+//!
+//! The serialized shape is part of the public interface: fields are only
+//! ever added, never renamed.
+
+use asg_core::{AsgGraph, SourceLocation};
+use parser_core::ParseError;
+use serde::{Deserialize, Serialize};
+use type_checker_l1::TypeError;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+/// A 1-based line/column position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A source range; `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    /// The empty range at `position`.
+    pub fn at(position: Position) -> Self {
+        Range {
+            start: position,
+            end: position,
+        }
+    }
+}
+
+impl From<&SourceLocation> for Range {
+    fn from(location: &SourceLocation) -> Self {
+        Range {
+            start: Position {
+                line: location.start_line,
+                column: location.start_col,
+            },
+            end: Position {
+                line: location.end_line,
+                column: location.end_col,
+            },
+        }
+    }
+}
+
+/// A suggested edit that resolves a diagnostic, offered as a code action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fix {
+    /// Short imperative description, e.g. "rename to `_x`".
+    pub title: String,
+    /// The text to replace, in the diagnostic's file.
+    pub range: Range,
+    pub replacement: String,
+}
+
+/// A single problem report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Stable code such as `P001` or `T003`.
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    pub file: String,
+    /// Where the problem is, when it can be tied to source text.
+    pub range: Option<Range>,
+    /// Edits that resolve the problem; omitted from JSON when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixes: Vec<Fix>,
+}
+
+impl Diagnostic {
+    /// Creates an error diagnostic with no range.
+    pub fn error(
+        code: impl Into<String>,
+        message: impl Into<String>,
+        file: impl Into<String>,
+    ) -> Self {
+        Self {
+            code: code.into(),
+            severity: Severity::Error,
+            message: message.into(),
+            file: file.into(),
+            range: None,
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Reports a parse error of `file`, at the position the parser stopped
+    /// at. An error reading the file has no range.
+    pub fn from_parse_error(error: &ParseError, file: impl Into<String>) -> Self {
+        let diagnostic = Self::error(error.code(), error.to_string(), file);
+        match error.position() {
+            Some((line, column)) => diagnostic.with_range(Range::at(Position { line, column })),
+            None => diagnostic,
+        }
+    }
+
+    /// Reports a type error found in `graph`, at the source location of
+    /// the offending node and in the file it came from. Without a recorded
+    /// location, it is reported against `file` with no range.
+    pub fn from_type_error(error: &TypeError, graph: &AsgGraph, file: impl Into<String>) -> Self {
+        let diagnostic = Self::error(error.code(), error.to_string(), file);
+        match error.node_id().and_then(|id| graph.source_location(id)) {
+            Some(location) => Diagnostic {
+                file: location.filename.clone(),
+                ..diagnostic.with_range(location.into())
+            },
+            None => diagnostic,
+        }
+    }
+
+    /// Sets the range covered by the diagnostic.
+    pub fn with_range(mut self, range: Range) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Adds a suggested fix.
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fixes.push(fix);
+        self
+    }
+}
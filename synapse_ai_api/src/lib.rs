@@ -1,14 +1,12 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Programmatic interface to Synapse for tools and AI agents.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod cache;
+pub mod diagnostic;
+pub mod service;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use cache::{ApplyEditRequest, ApplyEditResponse, AsgCache};
+pub use diagnostic::{Diagnostic, Fix, Position, Range, Severity};
+pub use service::{
+    CheckSourceRequest, CheckSourceResponse, Code, Status, SynapseAIService, TypeDescription,
+    TypeQueryRequest, TypeQueryResponse,
+};
@@ -0,0 +1,323 @@
+//! Request handlers of the AI service.
+//!
+//! Handlers are plain methods taking their request, so any transport can
+//! serve them. Graphs travel in the binary encoding of
+//! [`asg_core::binary`], and are sent once and then edited in the service's
+//! [`AsgCache`]. Failures are reported as a [`Status`] whose
+//! [`Code`] follows the gRPC status codes.
+
+use std::fmt;
+
+use asg_core::{decode_asg, encode_asg};
+use serde::{Deserialize, Serialize};
+use type_checker_l1::{Type, check_graph, check_graph_collect};
+
+use crate::cache::{ApplyEditRequest, ApplyEditResponse, AsgCache};
+use crate::diagnostic::Diagnostic;
+
+/// Why a request failed, in gRPC terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Code {
+    /// The request itself is unusable, e.g. the graph does not decode.
+    InvalidArgument,
+    /// The request names something that does not exist.
+    NotFound,
+    /// The request is well formed, but the graph is not in a state that
+    /// allows answering it, e.g. it does not type check.
+    FailedPrecondition,
+}
+
+impl Code {
+    /// The numeric gRPC status code.
+    pub fn value(self) -> i32 {
+        match self {
+            Code::InvalidArgument => 3,
+            Code::NotFound => 5,
+            Code::FailedPrecondition => 9,
+        }
+    }
+}
+
+/// A failed request: a status code and a reason meant for the caller.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    pub code: Code,
+    pub message: String,
+}
+
+impl Status {
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for Status {}
+
+/// Source text to parse and check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckSourceRequest {
+    /// The path reported in diagnostics and source locations.
+    pub filename: String,
+    pub text: String,
+}
+
+/// The graph parsed from the text and everything wrong with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckSourceResponse {
+    /// The graph, encoded with [`asg_core::encode_asg`]; empty if the text
+    /// does not parse.
+    pub asg_graph: Vec<u8>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Asks for the type inferred for one node of a graph.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeQueryRequest {
+    /// The graph, encoded with [`asg_core::encode_asg`].
+    pub asg_graph: Vec<u8>,
+    pub node_id: u64,
+}
+
+/// The type inferred for the queried node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeQueryResponse {
+    /// The type as the checker prints it, e.g. `Int -> Int`.
+    pub type_string: String,
+    pub type_node: TypeDescription,
+}
+
+/// A type in structured form, serialized with its `kind` alongside its
+/// parts, e.g. `{"kind": "Ref", "element": {"kind": "Int"}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum TypeDescription {
+    Int,
+    Bool,
+    Unit,
+    /// A type variable the checker left unconstrained.
+    Var {
+        id: u64,
+    },
+    Function {
+        param: Box<TypeDescription>,
+        result: Box<TypeDescription>,
+    },
+    Ref {
+        element: Box<TypeDescription>,
+    },
+    ForAll {
+        vars: Vec<u64>,
+        body: Box<TypeDescription>,
+    },
+}
+
+impl From<&Type> for TypeDescription {
+    fn from(ty: &Type) -> Self {
+        match ty {
+            Type::Int => TypeDescription::Int,
+            Type::Bool => TypeDescription::Bool,
+            Type::Unit => TypeDescription::Unit,
+            Type::Var(id) => TypeDescription::Var { id: *id },
+            Type::Function(param, result) => TypeDescription::Function {
+                param: Box::new(param.as_ref().into()),
+                result: Box::new(result.as_ref().into()),
+            },
+            Type::Ref(element) => TypeDescription::Ref {
+                element: Box::new(element.as_ref().into()),
+            },
+            Type::ForAll(vars, body) => TypeDescription::ForAll {
+                vars: vars.clone(),
+                body: Box::new(body.as_ref().into()),
+            },
+        }
+    }
+}
+
+/// The AI service. Its only state is the graphs stored in it.
+#[derive(Debug, Default)]
+pub struct SynapseAIService {
+    cache: AsgCache,
+}
+
+impl SynapseAIService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes `asg_graph` and stores it as `graph_id` for later edits,
+    /// replacing any graph stored there. Fails with
+    /// [`Code::InvalidArgument`] if the graph does not decode.
+    pub fn store_asg(&self, graph_id: impl Into<String>, asg_graph: &[u8]) -> Result<(), Status> {
+        let graph = decode_asg(asg_graph)
+            .map_err(|error| Status::new(Code::InvalidArgument, error.to_string()))?;
+        self.cache.store(graph_id, graph);
+        Ok(())
+    }
+
+    /// Edits a stored graph in place; see [`AsgCache::apply_edit`].
+    pub fn apply_edit(&self, request: &ApplyEditRequest) -> Result<ApplyEditResponse, Status> {
+        self.cache.apply_edit(request)
+    }
+
+    /// Parses and type checks `text`, reporting the parse error or every
+    /// type error with the range it applies to.
+    pub fn check_source(&self, request: &CheckSourceRequest) -> CheckSourceResponse {
+        let graph = match parser_core::parse_source(&request.filename, &request.text) {
+            Ok(graph) => graph,
+            Err(error) => {
+                return CheckSourceResponse {
+                    asg_graph: Vec::new(),
+                    diagnostics: vec![Diagnostic::from_parse_error(&error, &request.filename)],
+                };
+            }
+        };
+        let (_, errors) = check_graph_collect(&graph);
+        let diagnostics = errors
+            .iter()
+            .map(|error| Diagnostic::from_type_error(error, &graph, &request.filename))
+            .collect();
+        CheckSourceResponse {
+            asg_graph: encode_asg(&graph, true),
+            diagnostics,
+        }
+    }
+
+    /// Type checks the graph and returns the type inferred for the node.
+    ///
+    /// Fails with [`Code::InvalidArgument`] if the graph does not decode,
+    /// [`Code::NotFound`] if the node does not exist or is not a term
+    /// reachable from the root, and [`Code::FailedPrecondition`] if the
+    /// graph does not type check.
+    pub fn query_type(&self, request: &TypeQueryRequest) -> Result<TypeQueryResponse, Status> {
+        let graph = decode_asg(&request.asg_graph)
+            .map_err(|error| Status::new(Code::InvalidArgument, error.to_string()))?;
+        let node_id = request.node_id;
+        if graph.get_node(node_id).is_none() {
+            let message = format!("node {} does not exist", node_id);
+            return Err(Status::new(Code::NotFound, message));
+        }
+        let types = check_graph(&graph).map_err(|error| {
+            let message = format!("type checking failed: [{}] {}", error.code(), error);
+            Status::new(Code::FailedPrecondition, message)
+        })?;
+        let ty = types.get(&node_id).ok_or_else(|| {
+            let message = format!("node {} has no inferred type", node_id);
+            Status::new(Code::NotFound, message)
+        })?;
+        Ok(TypeQueryResponse {
+            type_string: ty.to_string(),
+            type_node: ty.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Severity;
+    use asg_core::{AsgGraph, NodeContent};
+
+    fn request(source: &str, node_id: impl FnOnce(&AsgGraph) -> u64) -> TypeQueryRequest {
+        let graph = parser_core::parse_source("main.syn", source).unwrap();
+        TypeQueryRequest {
+            node_id: node_id(&graph),
+            asg_graph: encode_asg(&graph, true),
+        }
+    }
+
+    #[test]
+    fn query_type_returns_the_inferred_type() {
+        let service = SynapseAIService::new();
+        let root = request("(x) => ref (x + 1)", |graph| graph.root().unwrap());
+        let response = service.query_type(&root).unwrap();
+        assert_eq!(response.type_string, "Int -> Ref Int");
+        assert_eq!(
+            serde_json::to_value(&response.type_node).unwrap(),
+            serde_json::json!({
+                "kind": "Function",
+                "param": {"kind": "Int"},
+                "result": {"kind": "Ref", "element": {"kind": "Int"}},
+            })
+        );
+
+        let literal = request("(x) => x == true", |graph| {
+            graph
+                .nodes()
+                .find(|node| matches!(node.content, NodeContent::LiteralBool(_)))
+                .unwrap()
+                .node_id
+        });
+        let response = service.query_type(&literal).unwrap();
+        assert_eq!(response.type_node, TypeDescription::Bool);
+    }
+
+    #[test]
+    fn check_source_places_diagnostics_in_the_source() {
+        let service = SynapseAIService::new();
+        let check = |text: &str| {
+            service.check_source(&CheckSourceRequest {
+                filename: "src/main.syn".to_string(),
+                text: text.to_string(),
+            })
+        };
+
+        let parsed = check("(x) =>\n  x +");
+        assert!(parsed.asg_graph.is_empty());
+        let [diagnostic] = &parsed.diagnostics[..] else {
+            panic!("expected one diagnostic: {:?}", parsed.diagnostics);
+        };
+        assert_eq!(diagnostic.code, "P001");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.file, "src/main.syn");
+        assert_eq!(diagnostic.range.unwrap().start.line, 2);
+
+        let checked = check("(x) =>\n  x + true");
+        assert!(decode_asg(&checked.asg_graph).is_ok());
+        let codes: Vec<_> = checked
+            .diagnostics
+            .iter()
+            .map(|d| d.code.as_str())
+            .collect();
+        assert_eq!(codes, ["T001"]);
+        let range = checked.diagnostics[0].range.unwrap();
+        assert_eq!(range.start.line, 2);
+        assert!(range.start.column > 1);
+        assert_eq!(checked.diagnostics[0].file, "src/main.syn");
+
+        assert!(check("(x) => x + 1").diagnostics.is_empty());
+    }
+
+    #[test]
+    fn failures_carry_a_status_and_reason() {
+        let service = SynapseAIService::new();
+        let error = service
+            .query_type(&request("(x) => x", |_| 999))
+            .unwrap_err();
+        assert_eq!(error.code, Code::NotFound);
+        assert_eq!(error.message, "node 999 does not exist");
+
+        let error = service
+            .query_type(&request("(x) => x + true", |graph| graph.root().unwrap()))
+            .unwrap_err();
+        assert_eq!(error.code, Code::FailedPrecondition);
+        assert!(error.message.contains("[T001]"), "{}", error.message);
+
+        let garbage = TypeQueryRequest {
+            asg_graph: b"Int".to_vec(),
+            node_id: 1,
+        };
+        let error = service.query_type(&garbage).unwrap_err();
+        assert_eq!(error.code, Code::InvalidArgument);
+        assert_eq!(error.code.value(), 3);
+    }
+}
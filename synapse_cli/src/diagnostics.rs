@@ -0,0 +1,157 @@
+//! Reporting compile errors to users and tools.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use synapse_ai_api::{Diagnostic, Fix, Position, Range};
+
+use crate::error::CompileError;
+use crate::linter::LintError;
+use crate::render::Renderer;
+
+/// How diagnostics are printed, selected by `--message-format`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+    /// Prose on stderr.
+    #[default]
+    Human,
+    /// A JSON array of diagnostics on stdout.
+    Json,
+}
+
+/// Converts `error` into a diagnostic; `file` is used when the error does
+/// not carry a location of its own.
+pub fn to_diagnostic(error: &CompileError, file: &Path) -> Diagnostic {
+    let diagnostic = Diagnostic::error(error.code(), error.message(), file.display().to_string());
+    match error {
+        CompileError::Parse(parse_error) => match parse_error.position() {
+            Some((line, column)) => diagnostic.with_range(Range::at(Position { line, column })),
+            None => diagnostic,
+        },
+        CompileError::Type {
+            location: Some(location),
+            ..
+        }
+        | CompileError::Lower {
+            location: Some(location),
+            ..
+        } => Diagnostic {
+            file: location.filename.clone(),
+            ..diagnostic.with_range(location.into())
+        },
+        _ => diagnostic,
+    }
+}
+
+/// Converts a lint finding into a diagnostic.
+pub fn lint_diagnostic(error: &LintError, file: &Path) -> Diagnostic {
+    let mut diagnostic = Diagnostic::error(
+        error.code,
+        error.message.clone(),
+        file.display().to_string(),
+    );
+    diagnostic.severity = error.severity;
+    let Some(location) = &error.location else {
+        return diagnostic;
+    };
+    let range = Range::from(location);
+    let mut diagnostic = Diagnostic {
+        file: location.filename.clone(),
+        ..diagnostic.with_range(range)
+    };
+    if let Some(fix) = &error.fix {
+        diagnostic = diagnostic.with_fix(Fix {
+            title: fix.title.clone(),
+            range,
+            replacement: fix.replacement.clone(),
+        });
+    }
+    diagnostic
+}
+
+/// Prints diagnostics as they are reported (human) or collects them to
+/// print as one array at the end (JSON).
+#[derive(Debug)]
+pub struct Reporter {
+    format: MessageFormat,
+    renderer: Renderer,
+    collected: Vec<Diagnostic>,
+}
+
+impl Reporter {
+    pub fn new(format: MessageFormat) -> Self {
+        Self {
+            format,
+            renderer: Renderer::for_stderr(),
+            collected: Vec::new(),
+        }
+    }
+
+    pub fn format(&self) -> MessageFormat {
+        self.format
+    }
+
+    /// Reports a diagnostic that does not by itself stop the command.
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        match self.format {
+            MessageFormat::Human => {
+                let source = std::fs::read_to_string(&diagnostic.file).ok();
+                eprintln!("{}", self.renderer.render(&diagnostic, source.as_deref()));
+            }
+            MessageFormat::Json => self.collected.push(diagnostic),
+        }
+    }
+
+    /// Reports the error that ended the command.
+    pub fn fail(&mut self, error: &CompileError, file: &Path) {
+        self.report(to_diagnostic(error, file));
+    }
+
+    /// Prints collected JSON diagnostics. With `always`, an empty array is
+    /// printed too, so diagnostic-only commands always produce JSON.
+    pub fn finish(self, always: bool) {
+        if self.format == MessageFormat::Json && (always || !self.collected.is_empty()) {
+            println!("{}", to_json(&self.collected));
+        }
+    }
+}
+
+/// Serializes diagnostics as a JSON array.
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    serde_json::to_string_pretty(diagnostics).expect("diagnostics always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline;
+
+    #[test]
+    fn type_error_serializes_with_code_and_range() {
+        let path = std::env::temp_dir().join(format!("synapse_json_{}.syn", std::process::id()));
+        std::fs::write(&path, "(x: Int) =>\n  x + true").unwrap();
+        let error = pipeline::check_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        let json = to_json(&[to_diagnostic(&error, &path)]);
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let diagnostic = &parsed[0];
+        assert_eq!(diagnostic["code"], "T001");
+        assert_eq!(diagnostic["severity"], "error");
+        assert_eq!(diagnostic["file"], path.display().to_string());
+        assert_eq!(diagnostic["range"]["start"]["line"], 2);
+        assert!(diagnostic["message"].as_str().unwrap().contains("Bool"));
+    }
+
+    #[test]
+    fn parse_error_points_at_the_offending_token() {
+        let error = CompileError::Parse(parser_core::parse_str("1 +").unwrap_err());
+        let diagnostic = to_diagnostic(&error, Path::new("a.syn"));
+        assert_eq!(diagnostic.code, "P001");
+        assert_eq!(diagnostic.file, "a.syn");
+        assert_eq!(
+            diagnostic.range.unwrap().start,
+            Position { line: 1, column: 4 }
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! `graph`: render the static structure of an ASG as a Graphviz digraph.
+
+use std::fmt::Write;
+
+use asg_core::{AsgGraph, NodeContent, TypeKind};
+
+/// Renders `graph` as DOT: one node per ASG node, labelled with its kind and
+/// salient content, and one edge per structural child.
+///
+/// Nodes are emitted in id order so the output is stable across runs.
+pub fn asg_to_dot(graph: &AsgGraph) -> String {
+    let mut nodes: Vec<_> = graph.nodes().collect();
+    nodes.sort_by_key(|node| node.node_id);
+
+    let mut dot = String::from("digraph asg {\n  node [shape=box, fontname=\"monospace\"];\n");
+    for node in &nodes {
+        let shape = if graph.root() == Some(node.node_id) {
+            ", peripheries=2"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            dot,
+            "  n{} [label=\"{}\"{}];",
+            node.node_id,
+            escape(&label(node.node_id, &node.content)),
+            shape
+        );
+    }
+    for node in &nodes {
+        for child in node.content.child_ids() {
+            let _ = writeln!(dot, "  n{} -> n{};", node.node_id, child);
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn label(node_id: u64, content: &NodeContent) -> String {
+    let detail = match content {
+        NodeContent::TermVariable(var) => var.name.clone(),
+        NodeContent::LiteralInt(lit) => lit.value.to_string(),
+        NodeContent::LiteralBool(lit) => lit.value.to_string(),
+        NodeContent::LiteralUnit(_) => "()".to_string(),
+        NodeContent::PrimitiveOp(op) => op.op_name.clone(),
+        NodeContent::EffectPerform(perform) => perform.effect_name.clone(),
+        NodeContent::TermMacroDefinition(definition) => definition.name.clone(),
+        NodeContent::TermMacroInvocation(invocation) => invocation.macro_name.clone(),
+        NodeContent::TypeNode(ty) => match ty.kind {
+            TypeKind::Int => "Int",
+            TypeKind::Bool => "Bool",
+            TypeKind::Unit => "Unit",
+            TypeKind::Function { .. } => "->",
+            TypeKind::Ref { .. } => "Ref",
+        }
+        .to_string(),
+        NodeContent::TermLambda(_)
+        | NodeContent::TermApplication(_)
+        | NodeContent::TermRef(_)
+        | NodeContent::TermDeref(_)
+        | NodeContent::TermAssign(_) => String::new(),
+    };
+    let kind = content.kind();
+    if detail.is_empty() {
+        format!("#{} {}", node_id, kind)
+    } else {
+        format!("#{} {}\n{}", node_id, kind, detail)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn increment_lambda_renders_every_node_and_edge() {
+        let graph = parser_core::parse_str("(x) => x + 1").unwrap();
+        let dot = asg_to_dot(&graph);
+        // Ids follow creation order: binder, use of x, literal, add, lambda.
+        assert!(dot.starts_with("digraph asg {"));
+        assert!(dot.contains("n1 [label=\"#1 TermVariable\\nx\"];"));
+        assert!(dot.contains("n2 [label=\"#2 TermVariable\\nx\"];"));
+        assert!(dot.contains("n3 [label=\"#3 LiteralInt\\n1\"];"));
+        assert!(dot.contains("n4 [label=\"#4 PrimitiveOp\\nadd\"];"));
+        assert!(dot.contains("n5 [label=\"#5 TermLambda\", peripheries=2];"));
+        for edge in ["n5 -> n1;", "n5 -> n4;", "n4 -> n2;", "n4 -> n3;"] {
+            assert!(dot.contains(edge), "missing edge {}", edge);
+        }
+        assert_eq!(dot.matches("->").count(), 4);
+    }
+}
@@ -0,0 +1,101 @@
+//! Errors surfaced by CLI commands.
+
+use std::path::PathBuf;
+
+use asg_core::SourceLocation;
+use asg_to_upir::LoweringError;
+use formatter_core::FormatError;
+use parser_core::ParseError;
+use thiserror::Error;
+use type_checker_l1::TypeError;
+use upir_core::VerifyError;
+use upir_to_llvm::CodegenError;
+
+/// A failure in one of the compilation stages.
+#[derive(Debug, Error)]
+pub enum CompileError {
+    #[error("parse error: {0}")]
+    Parse(#[from] ParseError),
+
+    #[error("type error: {error}")]
+    Type {
+        #[source]
+        error: TypeError,
+        /// Location of the node the error was reported against.
+        location: Option<SourceLocation>,
+    },
+
+    #[error("lowering error: {error}")]
+    Lower {
+        #[source]
+        error: LoweringError,
+        location: Option<SourceLocation>,
+    },
+
+    #[error("code generation error: {0}")]
+    Codegen(#[from] CodegenError),
+
+    #[error("failed to write {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("watch error: {0}")]
+    Watch(#[from] notify::Error),
+
+    #[error("console error: {0}")]
+    Console(#[source] std::io::Error),
+
+    #[error("the tutor does not know the concept '{0}'")]
+    UnknownConcept(String),
+
+    #[error("format error: {0}")]
+    Format(#[from] FormatError),
+
+    #[error("{} is not formatted", .0.display())]
+    Unformatted(PathBuf),
+
+    #[error("found {0} lint error(s)")]
+    LintFailed(usize),
+
+    /// The compiler produced a malformed UPIR module.
+    #[error("internal error: invalid UPIR: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidUpir(Vec<VerifyError>),
+}
+
+impl CompileError {
+    /// Stable diagnostic code of the underlying error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompileError::Parse(error) => error.code(),
+            CompileError::Type { error, .. } => error.code(),
+            CompileError::Lower { error, .. } => error.code(),
+            CompileError::Codegen(error) => error.code(),
+            CompileError::Io { .. } => "C001",
+            CompileError::Watch(_) => "C002",
+            CompileError::Console(_) => "C003",
+            CompileError::UnknownConcept(_) => "C004",
+            CompileError::Format(_) => "C005",
+            CompileError::Unformatted(_) => "C006",
+            CompileError::LintFailed(_) => "C007",
+            CompileError::InvalidUpir(_) => "C008",
+        }
+    }
+
+    /// The message without the stage prefix used by `Display`.
+    pub fn message(&self) -> String {
+        match self {
+            CompileError::Parse(ParseError::Syntax { message, .. }) => message.clone(),
+            CompileError::Parse(error) => error.to_string(),
+            CompileError::Type { error, .. } => error.to_string(),
+            CompileError::Lower { error, .. } => error.to_string(),
+            CompileError::Codegen(error) => error.to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Convenience alias for CLI results.
+pub type Result<T> = std::result::Result<T, CompileError>;
@@ -0,0 +1,116 @@
+//! `format`: pretty-print a source file with `formatter_core`.
+
+use std::path::Path;
+
+use formatter_core::FormatConfig;
+use parser_core::ParseError;
+
+use crate::error::{CompileError, Result};
+
+/// What to do with the formatted text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatMode {
+    /// Print it to stdout.
+    Print,
+    /// Replace the file with it, if it differs.
+    Write,
+    /// Fail if it differs from the file, without touching the file.
+    Check,
+}
+
+/// Formats in-memory `source`, attributed to `filename`. The text ends in
+/// a newline; a program without a root formats as the empty string.
+pub fn format_source(filename: &str, source: &str, config: &FormatConfig) -> Result<String> {
+    let graph = parser_core::parse_source(filename, source)?;
+    let Some(root) = graph.root() else {
+        return Ok(String::new());
+    };
+    let mut formatted = formatter_core::format_asg_with_config(&graph, root, config)?;
+    formatted.push('\n');
+    Ok(formatted)
+}
+
+/// Formats the file at `path` according to `mode` and returns whether it
+/// was already formatted. In [`FormatMode::Check`], an unformatted file is
+/// reported as [`CompileError::Unformatted`].
+pub fn format_file(path: &Path, mode: FormatMode, config: &FormatConfig) -> Result<bool> {
+    let source = std::fs::read_to_string(path).map_err(|source| ParseError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let formatted = format_source(&path.display().to_string(), &source, config)?;
+    let unchanged = formatted == source;
+    match mode {
+        FormatMode::Print => print!("{}", formatted),
+        FormatMode::Write if !unchanged => {
+            std::fs::write(path, formatted).map_err(|source| CompileError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?
+        }
+        FormatMode::Write => {}
+        FormatMode::Check if !unchanged => {
+            return Err(CompileError::Unformatted(path.to_path_buf()));
+        }
+        FormatMode::Check => {}
+    }
+    Ok(unchanged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_formats_in_place_and_check_then_passes() {
+        let path = std::env::temp_dir().join(format!("synapse_fmt_{}.syn", std::process::id()));
+        std::fs::write(&path, "(x)=>x+   1").unwrap();
+        let config = FormatConfig::default();
+
+        let error = format_file(&path, FormatMode::Check, &config).unwrap_err();
+        assert!(matches!(error, CompileError::Unformatted(_)));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "(x)=>x+   1");
+
+        assert!(!format_file(&path, FormatMode::Write, &config).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "(x) => x + 1\n");
+        assert!(format_file(&path, FormatMode::Check, &config).unwrap());
+
+        std::fs::write(&path, "(x) =>").unwrap();
+        assert!(matches!(
+            format_file(&path, FormatMode::Write, &config),
+            Err(CompileError::Parse(_))
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "(x) =>");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn let_bindings_are_left_as_written() {
+        let path = std::env::temp_dir().join(format!("synapse_fmt_let_{}.syn", std::process::id()));
+        let source = "let x = ref 1 in x := !x + 1\n";
+        std::fs::write(&path, source).unwrap();
+        let config = FormatConfig::default();
+        assert!(format_file(&path, FormatMode::Check, &config).unwrap());
+        assert!(format_file(&path, FormatMode::Write, &config).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), source);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn width_controls_line_breaking() {
+        let source = "(first) => (second) => first + second + first + second";
+        let wide = format_source("main.syn", source, &FormatConfig::default()).unwrap();
+        assert_eq!(wide.lines().count(), 1);
+        let narrow = FormatConfig {
+            max_width: 20,
+            ..FormatConfig::default()
+        };
+        assert!(
+            format_source("main.syn", source, &narrow)
+                .unwrap()
+                .lines()
+                .count()
+                > 1
+        );
+    }
+}
@@ -0,0 +1,456 @@
+//! Level 0 linter: structural and scope checks that need no type inference.
+
+use asg_core::{AsgGraph, NodeContent, SourceLocation, parse_effect_name};
+use synapse_ai_api::Severity;
+
+/// A problem found by the linter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintError {
+    /// Stable code, e.g. `L002`.
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub node_id: u64,
+    pub location: Option<SourceLocation>,
+    /// A second node involved in the problem, such as a shadowed binder.
+    pub related: Option<(u64, Option<SourceLocation>)>,
+    /// An edit that silences the problem.
+    pub fix: Option<LintFix>,
+}
+
+/// Replacement text for the source at a [`LintError`]'s location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFix {
+    pub title: String,
+    pub replacement: String,
+}
+
+/// Which optional lints to run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LintOptions {
+    /// Report binders that reuse a name already in scope (`L005`).
+    pub shadowing: bool,
+    /// Report recursive calls that pass the parameter unchanged (`L007`).
+    pub recursion: bool,
+}
+
+/// Runs the default lints plus those enabled in `options`.
+pub fn lint_graph_with(graph: &AsgGraph, options: LintOptions) -> Vec<LintError> {
+    let mut linter = Linter {
+        graph,
+        options,
+        errors: Vec::new(),
+    };
+    linter.check_structure();
+    linter.check_effect_names();
+    linter.check_unused_bindings();
+    if options.recursion {
+        linter.check_recursive_calls();
+    }
+    if let Some(root) = graph.root() {
+        linter.check_variable_scopes(root, &mut Vec::new());
+    }
+    // Shared subterms are visited once per parent, so the same problem can
+    // be found more than once.
+    linter
+        .errors
+        .sort_by(|a, b| (a.node_id, a.code, &a.message).cmp(&(b.node_id, b.code, &b.message)));
+    linter.errors.dedup();
+    linter.errors
+}
+
+/// A binder visible at some point of the traversal.
+struct ScopeEntry<'g> {
+    name: &'g str,
+    lambda_id: u64,
+    binder_id: u64,
+}
+
+struct Linter<'g> {
+    graph: &'g AsgGraph,
+    options: LintOptions,
+    errors: Vec<LintError>,
+}
+
+impl<'g> Linter<'g> {
+    fn report(&mut self, code: &'static str, severity: Severity, node_id: u64, message: String) {
+        self.errors.push(LintError {
+            code,
+            severity,
+            message,
+            node_id,
+            location: self.graph.source_location(node_id).cloned(),
+            related: None,
+            fix: None,
+        });
+    }
+
+    /// L001: every referenced node id must exist.
+    fn check_structure(&mut self) {
+        let Err(dangling) = self.graph.validate() else {
+            return;
+        };
+        for node in dangling
+            .into_iter()
+            .filter_map(|id| self.graph.get_node(id))
+        {
+            for child in node.content.child_ids() {
+                if self.graph.get_node(child).is_none() {
+                    self.report(
+                        "L001",
+                        Severity::Error,
+                        node.node_id,
+                        format!("node {} refers to missing node {}", node.node_id, child),
+                    );
+                }
+            }
+        }
+    }
+
+    /// L008: effect names must be canonical (see [`asg_core::effects`]).
+    fn check_effect_names(&mut self) {
+        let mut performs: Vec<_> = self
+            .graph
+            .nodes()
+            .filter_map(|node| match &node.content {
+                NodeContent::EffectPerform(perform) => Some((node.node_id, &perform.effect_name)),
+                _ => None,
+            })
+            .collect();
+        performs.sort_by_key(|(node_id, _)| *node_id);
+        for (node_id, effect_name) in performs {
+            if let Err(unknown) = parse_effect_name(effect_name) {
+                let message = match unknown.suggestion {
+                    Some(known) => format!(
+                        "unknown effect '{}'; did you mean '{}'?",
+                        unknown.name, known
+                    ),
+                    None => format!(
+                        "unknown effect '{}'; user-defined effects are written '{}{}'",
+                        unknown.name,
+                        asg_core::effects::CUSTOM_PREFIX,
+                        unknown.name
+                    ),
+                };
+                self.report("L008", Severity::Warning, node_id, message);
+            }
+        }
+    }
+
+    /// L006 (UnusedBinding): lambda parameters should be referenced. A
+    /// `let` is parsed as a lambda applied to the bound value, so its
+    /// binding is covered as that lambda's parameter. Names starting with
+    /// `_` are deliberately unused and exempt; the fix renames to that form.
+    fn check_unused_bindings(&mut self) {
+        let index = self.graph.reference_index();
+        for lambda_id in index.unused_lambdas() {
+            let Some(NodeContent::TermLambda(lambda)) =
+                self.graph.get_node(lambda_id).map(|node| &node.content)
+            else {
+                continue;
+            };
+            let binder_id = lambda.binder_variable_node_id;
+            let Some(NodeContent::TermVariable(binder)) =
+                self.graph.get_node(binder_id).map(|node| &node.content)
+            else {
+                continue;
+            };
+            if binder.name.starts_with('_') {
+                continue;
+            }
+            let is_let = index.referrers(lambda_id).iter().any(|&referrer| {
+                matches!(
+                    self.graph.get_node(referrer).map(|node| &node.content),
+                    Some(NodeContent::TermApplication(app)) if app.function_node_id == lambda_id
+                )
+            });
+            let what = if is_let { "let binding" } else { "parameter" };
+            let replacement = format!("_{}", binder.name);
+            self.errors.push(LintError {
+                code: "L006",
+                severity: Severity::Warning,
+                message: format!("{} '{}' is never used", what, binder.name),
+                node_id: binder_id,
+                location: self.graph.source_location(binder_id).cloned(),
+                related: None,
+                fix: Some(LintFix {
+                    title: format!("rename to `{}`", replacement),
+                    replacement,
+                }),
+            });
+        }
+    }
+
+    /// L007: a `letrec` function calling itself with its own parameter
+    /// unchanged, as in `letrec f = (x) => f(x)`, makes no progress towards
+    /// a base case. `letrec` binds the function's name as the parameter of
+    /// the generator passed to `fix`, so the calls are found among that
+    /// parameter's uses. Best effort only: any other argument, even one no
+    /// smaller, is accepted, and no call is proven to terminate.
+    fn check_recursive_calls(&mut self) {
+        let mut generators: Vec<u64> = self
+            .graph
+            .nodes()
+            .filter_map(|node| match &node.content {
+                NodeContent::PrimitiveOp(op) if op.op_name == "fix" => {
+                    op.argument_node_ids.first().copied()
+                }
+                _ => None,
+            })
+            .collect();
+        generators.sort_unstable();
+        let content = |node_id| self.graph.get_node(node_id).map(|node| &node.content);
+        let index = self.graph.reference_index();
+        let mut calls = Vec::new();
+        for generator_id in generators {
+            let Some(NodeContent::TermLambda(generator)) = content(generator_id) else {
+                continue;
+            };
+            let function_id = generator.body_node_id;
+            let (Some(NodeContent::TermVariable(name)), Some(NodeContent::TermLambda(_))) = (
+                content(generator.binder_variable_node_id),
+                content(function_id),
+            ) else {
+                continue;
+            };
+            for &use_id in index.uses_of(generator_id) {
+                for &call_id in index.referrers(use_id) {
+                    if let Some(NodeContent::TermApplication(call)) = content(call_id)
+                        && call.function_node_id == use_id
+                        && let Some(NodeContent::TermVariable(argument)) =
+                            content(call.argument_node_id)
+                        && argument.definition_node_id == function_id
+                    {
+                        calls.push((call_id, name.name.clone(), argument.name.clone()));
+                    }
+                }
+            }
+        }
+        for (call_id, name, parameter) in calls {
+            let message = format!(
+                "'{}' calls itself with '{}' unchanged, so the recursion may never end",
+                name, parameter
+            );
+            self.report("L007", Severity::Warning, call_id, message);
+        }
+    }
+
+    /// L002: variables must link to an enclosing lambda. With
+    /// [`LintOptions::shadowing`], also L005 for binders hiding an outer one.
+    fn check_variable_scopes(&mut self, node_id: u64, scopes: &mut Vec<ScopeEntry<'g>>) {
+        let Some(node) = self.graph.get_node(node_id) else {
+            return;
+        };
+        match &node.content {
+            NodeContent::TermVariable(var) => {
+                let bound = scopes.iter().rev().find(|entry| entry.name == var.name);
+                if bound.map(|entry| entry.lambda_id) != Some(var.definition_node_id) {
+                    let message = if var.definition_node_id == 0 {
+                        format!(
+                            "variable '{}' is not bound by any enclosing lambda",
+                            var.name
+                        )
+                    } else {
+                        format!(
+                            "variable '{}' links to node {}, which is not its enclosing binder",
+                            var.name, var.definition_node_id
+                        )
+                    };
+                    self.report("L002", Severity::Error, node_id, message);
+                }
+            }
+            NodeContent::TermLambda(lambda) => {
+                let Some(NodeContent::TermVariable(binder)) = self
+                    .graph
+                    .get_node(lambda.binder_variable_node_id)
+                    .map(|node| &node.content)
+                else {
+                    self.report(
+                        "L002",
+                        Severity::Error,
+                        node_id,
+                        format!("lambda {} has no variable binder", node_id),
+                    );
+                    return;
+                };
+                if self.options.shadowing
+                    && let Some(outer) = scopes.iter().rev().find(|entry| entry.name == binder.name)
+                {
+                    let shadowed = self.graph.source_location(outer.binder_id).cloned();
+                    let message = match &shadowed {
+                        Some(loc) => format!(
+                            "binder '{}' shadows the binding at {}:{}",
+                            binder.name, loc.start_line, loc.start_col
+                        ),
+                        None => format!("binder '{}' shadows an outer binding", binder.name),
+                    };
+                    self.errors.push(LintError {
+                        code: "L005",
+                        severity: Severity::Warning,
+                        message,
+                        node_id: lambda.binder_variable_node_id,
+                        location: self
+                            .graph
+                            .source_location(lambda.binder_variable_node_id)
+                            .cloned(),
+                        related: Some((outer.binder_id, shadowed)),
+                        fix: None,
+                    });
+                }
+                scopes.push(ScopeEntry {
+                    name: &binder.name,
+                    lambda_id: node_id,
+                    binder_id: lambda.binder_variable_node_id,
+                });
+                self.check_variable_scopes(lambda.binder_variable_node_id, scopes);
+                self.check_variable_scopes(lambda.body_node_id, scopes);
+                scopes.pop();
+            }
+            content => {
+                for child in content.child_ids() {
+                    self.check_variable_scopes(child, scopes);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_graph(graph: &AsgGraph) -> Vec<LintError> {
+        lint_graph_with(graph, LintOptions::default())
+    }
+
+    #[test]
+    fn well_scoped_program_is_clean() {
+        let graph = parser_core::parse_str("(x) => (y) => x + y").unwrap();
+        assert_eq!(lint_graph(&graph), Vec::new());
+    }
+
+    #[test]
+    fn unbound_variable_is_l002() {
+        let graph = parser_core::parse_str("(x) => x + y").unwrap();
+        let errors = lint_graph(&graph);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "L002");
+        assert!(errors[0].message.contains("'y'"));
+    }
+
+    #[test]
+    fn dangling_child_is_l001() {
+        let mut graph = parser_core::parse_str("1 + 2").unwrap();
+        graph.remove_node(1);
+        let codes: Vec<_> = lint_graph(&graph).iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec!["L001"]);
+    }
+
+    #[test]
+    fn identical_errors_on_a_node_are_reported_once() {
+        let mut graph = parser_core::parse_str("1 + 2").unwrap();
+        let add = graph.root().unwrap();
+        graph
+            .get_node_mut(add)
+            .unwrap()
+            .content
+            .map_child_ids(|_| 1);
+        graph.remove_node(1);
+        let errors = lint_graph(&graph);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "L001");
+        assert_eq!(errors[0].node_id, add);
+    }
+
+    #[test]
+    fn misspelled_effect_is_l008() {
+        let graph = parser_core::parse_str("perform('IO', 1)").unwrap();
+        assert_eq!(lint_graph(&graph), Vec::new());
+
+        let graph = parser_core::parse_str("perform('Io', 1)").unwrap();
+        let errors = lint_graph(&graph);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "L008");
+        assert_eq!(errors[0].severity, Severity::Warning);
+        assert_eq!(errors[0].message, "unknown effect 'Io'; did you mean 'IO'?");
+    }
+
+    #[test]
+    fn recursion_lint_flags_an_unchanged_argument() {
+        let options = LintOptions {
+            recursion: true,
+            ..LintOptions::default()
+        };
+        let graph = parser_core::parse_str("letrec f = (x) => f(x) in f(1)").unwrap();
+        assert_eq!(lint_graph(&graph), Vec::new());
+        let errors = lint_graph_with(&graph, options);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "L007");
+        assert_eq!(errors[0].severity, Severity::Warning);
+        assert_eq!(
+            errors[0].message,
+            "'f' calls itself with 'x' unchanged, so the recursion may never end"
+        );
+        // Reported at the call site.
+        let location = errors[0].location.as_ref().unwrap();
+        assert_eq!((location.start_col, location.end_col), (19, 23));
+    }
+
+    #[test]
+    fn recursion_lint_accepts_a_changed_argument() {
+        let options = LintOptions {
+            recursion: true,
+            ..LintOptions::default()
+        };
+        let source = "letrec f = (x) => if x == 0 then 0 else f(x - 1) in f(3)";
+        let graph = parser_core::parse_str(source).unwrap();
+        assert_eq!(lint_graph_with(&graph, options), Vec::new());
+    }
+
+    #[test]
+    fn shadowing_is_opt_in_and_reports_both_binders() {
+        // The hidden outer `x` is also unused, which is always reported.
+        let graph = parser_core::parse_str("(x) => (x) => x").unwrap();
+        let codes = |errors: Vec<LintError>| errors.iter().map(|e| e.code).collect::<Vec<_>>();
+        assert_eq!(codes(lint_graph(&graph)), ["L006"]);
+
+        let options = LintOptions {
+            shadowing: true,
+            ..LintOptions::default()
+        };
+        let errors = lint_graph_with(&graph, options);
+        assert_eq!(codes(errors.clone()), ["L006", "L005"]);
+        let warning = &errors[1];
+        assert_eq!(warning.code, "L005");
+        assert_eq!(warning.severity, Severity::Warning);
+        // Ids follow creation order: outer binder 1, inner binder 2.
+        assert_eq!(warning.node_id, 2);
+        assert_eq!(warning.location.as_ref().unwrap().start_col, 9);
+        let (shadowed, location) = warning.related.as_ref().unwrap();
+        assert_eq!(*shadowed, 1);
+        assert_eq!(location.as_ref().unwrap().start_col, 2);
+    }
+
+    #[test]
+    fn unused_binding_is_l006_with_a_rename_fix() {
+        let graph = parser_core::parse_str("(x) => x").unwrap();
+        assert_eq!(lint_graph(&graph), Vec::new());
+        let graph = parser_core::parse_str("(_x) => 1").unwrap();
+        assert_eq!(lint_graph(&graph), Vec::new());
+
+        let graph = parser_core::parse_str("(x) => 1").unwrap();
+        let errors = lint_graph(&graph);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "L006");
+        assert_eq!(errors[0].severity, Severity::Warning);
+        assert_eq!(errors[0].message, "parameter 'x' is never used");
+        assert_eq!(errors[0].location.as_ref().unwrap().start_col, 2);
+        assert_eq!(errors[0].fix.as_ref().unwrap().replacement, "_x");
+
+        let graph = parser_core::parse_str("let y = 1 in 2").unwrap();
+        let errors = lint_graph(&graph);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "let binding 'y' is never used");
+        assert_eq!(errors[0].location.as_ref().unwrap().start_col, 5);
+    }
+}
@@ -1,3 +1,347 @@
-fn main() {
-    println!("Hello, world!");
+//! Command-line interface for the Synapse toolchain.
+
+mod diagnostics;
+mod dot;
+mod error;
+mod format;
+mod linter;
+mod pipeline;
+mod render;
+mod tutor;
+mod watch;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+use synapse_ai_api::Severity;
+
+use crate::diagnostics::{MessageFormat, Reporter};
+use crate::error::{CompileError, Result};
+use crate::format::FormatMode;
+use crate::linter::LintOptions;
+
+#[derive(Debug, Parser)]
+#[command(name = "synapse", version, about = "The Synapse compiler toolchain")]
+struct Cli {
+    /// How to print diagnostics.
+    #[arg(
+        long,
+        visible_alias = "format",
+        global = true,
+        value_enum,
+        default_value_t
+    )]
+    message_format: MessageFormat,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Parse a source file and report syntax errors.
+    Parse { input_file: PathBuf },
+
+    /// Parse, lint and type-check a source file, printing the program's type.
+    Check {
+        input_file: PathBuf,
+        /// Warn when a binder shadows a name already in scope (L005).
+        #[arg(long)]
+        warn_shadowing: bool,
+        /// Warn when a `letrec` function calls itself with its parameter
+        /// unchanged (L007).
+        #[arg(long)]
+        warn_recursion: bool,
+    },
+
+    /// Run the structural and scope lints on a source file, failing if any
+    /// reports an error.
+    Lint {
+        input_file: PathBuf,
+        /// Warn when a binder shadows a name already in scope (L005).
+        #[arg(long)]
+        warn_shadowing: bool,
+        /// Warn when a `letrec` function calls itself with its parameter
+        /// unchanged (L007).
+        #[arg(long)]
+        warn_recursion: bool,
+    },
+
+    /// Type-check a source file and check the effects it performs (Level 2).
+    CheckEffects {
+        input_file: PathBuf,
+        /// Allow an effect everywhere in the program; may be repeated.
+        #[arg(long = "allow-effect", value_name = "EFFECT")]
+        allow_effects: Vec<String>,
+    },
+
+    /// Compile a source file to UPIR and print it.
+    Lower {
+        input_file: PathBuf,
+        #[command(flatten)]
+        opt: OptArgs,
+    },
+
+    /// Compile a source file to LLVM IR (`.ll`).
+    EmitLlvm {
+        input_file: PathBuf,
+        #[command(flatten)]
+        opt: OptArgs,
+        /// Attach DWARF line tables so debuggers can map back to the source.
+        #[arg(long)]
+        debug: bool,
+        /// Write the IR to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Dump the ASG of a source file as a Graphviz DOT digraph.
+    Graph {
+        input_file: PathBuf,
+        /// Write the DOT to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Pretty-print a source file.
+    Format {
+        input_file: PathBuf,
+        /// Rewrite the file in place instead of printing it.
+        #[arg(long, conflicts_with = "check")]
+        write: bool,
+        /// Fail if the file is not already formatted, without changing it.
+        #[arg(long)]
+        check: bool,
+        /// Columns a line may take before terms are broken across lines.
+        #[arg(long, default_value_t = formatter_core::FormatConfig::default().max_width)]
+        width: usize,
+    },
+
+    /// Start the interactive tutor.
+    Tutor,
+
+    /// Answer one tutor quiz question about a concept.
+    Quiz { concept: String },
+
+    /// Recompile a source file every time it changes, until interrupted.
+    Watch {
+        input_file: PathBuf,
+        /// Quiet period in milliseconds before a change triggers a recompile.
+        #[arg(long, default_value_t = watch::DEFAULT_DEBOUNCE.as_millis() as u64)]
+        debounce_ms: u64,
+    },
+}
+
+/// How much to optimize the UPIR before printing or code generation.
+#[derive(Debug, Args)]
+struct OptArgs {
+    /// 0 runs no passes, 1 folds constants, 2 also removes dead code.
+    #[arg(
+        short = 'O',
+        long,
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u8).range(0..=2)
+    )]
+    opt_level: u8,
+    /// Print each optimization pass as it runs.
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+impl OptArgs {
+    fn optimize(&self, module: &mut upir_core::Module) -> Result<()> {
+        for pass in pipeline::optimize(module, self.opt_level)? {
+            if self.verbose {
+                eprintln!("ran pass {}", pass);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Commands {
+    fn input_file(&self) -> Option<&Path> {
+        match self {
+            Commands::Parse { input_file }
+            | Commands::Check { input_file, .. }
+            | Commands::Lint { input_file, .. }
+            | Commands::CheckEffects { input_file, .. }
+            | Commands::Lower { input_file, .. }
+            | Commands::EmitLlvm { input_file, .. }
+            | Commands::Graph { input_file, .. }
+            | Commands::Format { input_file, .. }
+            | Commands::Watch { input_file, .. } => Some(input_file),
+            Commands::Tutor | Commands::Quiz { .. } => None,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut reporter = Reporter::new(cli.message_format);
+    let input_file = cli.command.input_file().map(Path::to_path_buf);
+    let diagnostic_only = matches!(
+        cli.command,
+        Commands::Parse { .. }
+            | Commands::Check { .. }
+            | Commands::Lint { .. }
+            | Commands::CheckEffects { .. }
+    );
+    let result = run(cli.command, &mut reporter);
+    // In JSON, the lint diagnostics already say everything a failed lint
+    // run has to say.
+    let reported = matches!(result, Err(CompileError::LintFailed(_)))
+        && reporter.format() == MessageFormat::Json;
+    if let Err(error) = &result
+        && !reported
+    {
+        reporter.fail(error, input_file.as_deref().unwrap_or(Path::new("")));
+    }
+    reporter.finish(diagnostic_only);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+fn run(command: Commands, reporter: &mut Reporter) -> Result<()> {
+    let human = reporter.format() == MessageFormat::Human;
+    match command {
+        Commands::Parse { input_file } => {
+            let graph = parser_core::parse_file(&input_file)?;
+            if human {
+                println!("{}: parsed {} nodes", input_file.display(), graph.len());
+            }
+        }
+        Commands::Check {
+            input_file,
+            warn_shadowing,
+            warn_recursion,
+        } => {
+            let graph = parser_core::parse_file(&input_file)?;
+            let options = LintOptions {
+                shadowing: warn_shadowing,
+                recursion: warn_recursion,
+            };
+            for lint in linter::lint_graph_with(&graph, options) {
+                reporter.report(diagnostics::lint_diagnostic(&lint, &input_file));
+            }
+            let (graph, types) = pipeline::check_graph(graph)?;
+            if human && let Some(ty) = graph.root().and_then(|root| types.get(&root)) {
+                println!("{}: {}", input_file.display(), ty);
+            }
+        }
+        Commands::Lint {
+            input_file,
+            warn_shadowing,
+            warn_recursion,
+        } => {
+            let graph = parser_core::parse_file(&input_file)?;
+            let options = LintOptions {
+                shadowing: warn_shadowing,
+                recursion: warn_recursion,
+            };
+            let lints = linter::lint_graph_with(&graph, options);
+            for lint in &lints {
+                reporter.report(diagnostics::lint_diagnostic(lint, &input_file));
+            }
+            let errors = lints
+                .iter()
+                .filter(|lint| lint.severity == Severity::Error)
+                .count();
+            if errors > 0 {
+                return Err(CompileError::LintFailed(errors));
+            }
+            if human {
+                println!("{}: {} warning(s)", input_file.display(), lints.len());
+            }
+        }
+        Commands::CheckEffects {
+            input_file,
+            allow_effects,
+        } => {
+            let (graph, _) = pipeline::check_file(&input_file)?;
+            pipeline::check_effects(&graph, &allow_effects)?;
+            if human {
+                println!("{}: effects ok", input_file.display());
+            }
+        }
+        Commands::Lower { input_file, opt } => {
+            let mut compilation = pipeline::compile_file(&input_file)?;
+            opt.optimize(&mut compilation.module)?;
+            print!("{}", upir_core::print_module(&compilation.module));
+        }
+        Commands::EmitLlvm {
+            input_file,
+            opt,
+            debug,
+            output,
+        } => {
+            let mut compilation = pipeline::compile_file(&input_file)?;
+            opt.optimize(&mut compilation.module)?;
+            let options = upir_to_llvm::EmitOptions { debug_info: debug };
+            let ir = upir_to_llvm::emit_module(&compilation.module, options)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, ir).map_err(|source| CompileError::Io { path, source })?
+                }
+                None => print!("{}", ir),
+            }
+        }
+        Commands::Graph { input_file, output } => {
+            let graph = parser_core::parse_file(&input_file)?;
+            let dot = dot::asg_to_dot(&graph);
+            match output {
+                Some(path) => std::fs::write(&path, dot)
+                    .map_err(|source| CompileError::Io { path, source })?,
+                None => print!("{}", dot),
+            }
+        }
+        Commands::Format {
+            input_file,
+            write,
+            check,
+            width,
+        } => {
+            let mode = match (write, check) {
+                (true, _) => FormatMode::Write,
+                (_, true) => FormatMode::Check,
+                _ => FormatMode::Print,
+            };
+            let config = formatter_core::FormatConfig {
+                max_width: width,
+                ..Default::default()
+            };
+            format::format_file(&input_file, mode, &config)?;
+        }
+        Commands::Tutor => {
+            let session = tutor::run_repl(&mut std::io::stdin().lock(), &mut std::io::stdout())
+                .map_err(CompileError::Console)?;
+            println!("Final score: {}", session.score());
+        }
+        Commands::Quiz { concept } => {
+            let Some(concept) = tutor::concept(&concept) else {
+                return Err(CompileError::UnknownConcept(concept));
+            };
+            let question = tutor::Question::for_concept(concept);
+            print!("{}answer> ", question.render());
+            std::io::stdout().flush().map_err(CompileError::Console)?;
+            let mut reply = String::new();
+            std::io::stdin()
+                .read_line(&mut reply)
+                .map_err(CompileError::Console)?;
+            println!(
+                "{}",
+                tutor::Session::default().answer(&question, &reply).feedback
+            );
+        }
+        Commands::Watch {
+            input_file,
+            debounce_ms,
+        } => watch::watch(&input_file, Duration::from_millis(debounce_ms))?,
+    }
+    Ok(())
 }
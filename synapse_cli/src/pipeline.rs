@@ -0,0 +1,128 @@
+//! The parse → type-check → lower pipeline shared by CLI commands.
+
+use std::path::Path;
+
+use asg_core::{AsgGraph, SourceLocation};
+use type_checker_l1::TypeCheckMap;
+use upir_core::{Module, PassManager};
+
+use crate::error::{CompileError, Result};
+
+/// Everything produced by a successful run of the pipeline.
+#[derive(Debug)]
+pub struct Compilation {
+    pub graph: AsgGraph,
+    pub types: TypeCheckMap,
+    pub module: Module,
+}
+
+/// Parses and type-checks the file at `path`.
+pub fn check_file(path: &Path) -> Result<(AsgGraph, TypeCheckMap)> {
+    check_graph(parser_core::parse_file(path)?)
+}
+
+/// Parses and type-checks in-memory `source`, attributed to `filename`.
+pub fn check_source(filename: &str, source: &str) -> Result<(AsgGraph, TypeCheckMap)> {
+    check_graph(parser_core::parse_source(filename, source)?)
+}
+
+/// Type-checks an already parsed graph and records the inferred types in
+/// it.
+pub fn check_graph(mut graph: AsgGraph) -> Result<(AsgGraph, TypeCheckMap)> {
+    let types = type_checker_l1::check_and_annotate_graph(&mut graph).map_err(|error| {
+        CompileError::Type {
+            location: location_of(&graph, error.node_id()),
+            error,
+        }
+    })?;
+    Ok((graph, types))
+}
+
+/// Checks the effects performed by an already type-checked graph. `allowed`
+/// holds the effects permitted everywhere; lambdas annotated `with [..]`
+/// permit more within their bodies.
+pub fn check_effects(graph: &AsgGraph, allowed: &[String]) -> Result<()> {
+    type_checker_l2::check_effects(graph, allowed).map_err(|error| CompileError::Type {
+        location: location_of(graph, error.node_id()),
+        error,
+    })
+}
+
+/// Runs every stage on the file at `path`, stopping at the first error.
+pub fn compile_file(path: &Path) -> Result<Compilation> {
+    let (graph, types) = check_file(path)?;
+    let module = asg_to_upir::lower_graph_to_upir(&graph).map_err(|error| CompileError::Lower {
+        location: location_of(&graph, error.node_id()),
+        error,
+    })?;
+    upir_core::verify(&module).map_err(CompileError::InvalidUpir)?;
+    Ok(Compilation {
+        graph,
+        types,
+        module,
+    })
+}
+
+/// Runs the UPIR passes of optimization level `level` over `module`,
+/// checks the result is still well formed, and returns the names of the
+/// passes in the order they ran.
+pub fn optimize(module: &mut Module, level: u8) -> Result<Vec<&'static str>> {
+    let manager = PassManager::for_level(level);
+    if manager.run(module) {
+        upir_core::verify(module).map_err(CompileError::InvalidUpir)?;
+    }
+    Ok(manager.pass_names())
+}
+
+fn location_of(graph: &AsgGraph, node_id: Option<u64>) -> Option<SourceLocation> {
+    graph.source_location(node_id?).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use type_checker_l1::TypeError;
+
+    #[test]
+    fn annotated_function_may_perform_without_global_allowances() {
+        let (graph, _) =
+            check_source("main.syn", "(x: Int) with [IO] => perform('IO', x)").unwrap();
+        assert!(check_effects(&graph, &[]).is_ok());
+
+        let (graph, _) = check_source("main.syn", "(x: Int) => perform('IO', x)").unwrap();
+        let error = check_effects(&graph, &[]).unwrap_err();
+        assert!(matches!(
+            error,
+            CompileError::Type {
+                location: Some(SourceLocation { start_col: 13, .. }),
+                ..
+            }
+        ));
+        assert!(check_effects(&graph, &["IO".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn effect_errors_name_the_effect_and_the_allowed_set() {
+        let (graph, _) =
+            check_source("main.syn", "(x: Int) with [State] => perform('IO', x)").unwrap();
+        let error = check_effects(&graph, &["Net".to_string()]).unwrap_err();
+        let CompileError::Type {
+            error:
+                TypeError::EffectNotAllowed {
+                    node_id,
+                    effect,
+                    allowed,
+                },
+            ..
+        } = error
+        else {
+            panic!("expected an effect error, got {:?}", error);
+        };
+        assert!(matches!(
+            graph.get_node(node_id).map(|node| &node.content),
+            Some(asg_core::NodeContent::EffectPerform(_))
+        ));
+        assert_eq!(effect, "IO");
+        assert_eq!(allowed, ["Net", "State"]);
+    }
+}
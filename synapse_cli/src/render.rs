@@ -0,0 +1,193 @@
+//! Human-readable diagnostics with the offending source line underlined.
+//!
+//! ```text
+//! error[T001]: type mismatch at node 3: expected Int, found Bool
+//!   --> main.syn:2:7
+//!    |
+//!  2 |   x + true
+//!    |       ^^^^
+//! ```
+
+use std::io::IsTerminal;
+
+use synapse_ai_api::{Diagnostic, Range, Severity};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+/// Renders diagnostics, optionally with ANSI colors.
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    pub fn new(color: bool) -> Self {
+        Self { color }
+    }
+
+    /// Colors output when stderr is a terminal and `NO_COLOR` is unset or
+    /// empty.
+    pub fn for_stderr() -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Self::new(std::io::stderr().is_terminal() && !no_color)
+    }
+
+    /// Renders `diagnostic`. When `source` holds the text of the file and the
+    /// diagnostic has a range, the first line of the range is shown beneath
+    /// the header with the range underlined.
+    pub fn render(&self, diagnostic: &Diagnostic, source: Option<&str>) -> String {
+        let (label, accent) = match diagnostic.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+            Severity::Info => ("info", BLUE),
+            Severity::Hint => ("hint", CYAN),
+        };
+        let mut text = format!(
+            "{}{}[{}]{}{}: {}{}",
+            self.paint(accent),
+            label,
+            diagnostic.code,
+            self.paint(RESET),
+            self.paint(BOLD),
+            diagnostic.message,
+            self.paint(RESET),
+        );
+        let Some(range) = diagnostic.range else {
+            text.push_str(&format!(
+                "\n{}  -->{} {}",
+                self.paint(BLUE),
+                self.paint(RESET),
+                diagnostic.file
+            ));
+            return text;
+        };
+        text.push_str(&format!(
+            "\n{}  -->{} {}:{}:{}",
+            self.paint(BLUE),
+            self.paint(RESET),
+            diagnostic.file,
+            range.start.line,
+            range.start.column
+        ));
+        if let Some(line) = source.and_then(|source| source_line(source, range.start.line)) {
+            let number = range.start.line.to_string();
+            let gutter = " ".repeat(number.len());
+            let (start, width) = underline(line, range);
+            text.push_str(&format!(
+                "\n{blue} {gutter} |{reset}\n{blue} {number} |{reset} {line}\n{blue} {gutter} |{reset} {pad}{accent}{carets}{reset}",
+                blue = self.paint(BLUE),
+                reset = self.paint(RESET),
+                accent = self.paint(accent),
+                pad = " ".repeat(start),
+                carets = "^".repeat(width),
+            ));
+        }
+        for fix in &diagnostic.fixes {
+            text.push_str(&format!(
+                "\n{}  = help:{} {}",
+                self.paint(BLUE),
+                self.paint(RESET),
+                fix.title
+            ));
+        }
+        text
+    }
+
+    fn paint(&self, code: &'static str) -> &'static str {
+        if self.color { code } else { "" }
+    }
+}
+
+/// The 1-based `line` of `source`, without its line terminator.
+fn source_line(source: &str, line: u32) -> Option<&str> {
+    let index = (line as usize).checked_sub(1)?;
+    source
+        .lines()
+        .nth(index)
+        .map(|text| text.trim_end_matches('\r'))
+}
+
+/// The column offset and width of the carets under `line`. A range that
+/// continues onto later lines is underlined to the end of its first line; an
+/// empty range gets a single caret.
+fn underline(line: &str, range: Range) -> (usize, usize) {
+    let length = line.chars().count();
+    let start = (range.start.column.max(1) as usize - 1).min(length);
+    let end = if range.end.line == range.start.line {
+        (range.end.column.max(1) as usize - 1).min(length)
+    } else {
+        length
+    };
+    (start, end.saturating_sub(start).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{lint_diagnostic, to_diagnostic};
+    use crate::linter::{self, LintOptions};
+    use crate::pipeline;
+    use std::path::Path;
+
+    #[test]
+    fn type_error_underlines_the_offending_term() {
+        let source = "(x: Int) =>\n  x + true";
+        let error = pipeline::check_source("main.syn", source).unwrap_err();
+        let diagnostic = to_diagnostic(&error, Path::new("main.syn"));
+
+        assert_eq!(
+            Renderer::new(false).render(&diagnostic, Some(source)),
+            "\
+error[T001]: type mismatch at node 3: expected Int, found Bool
+  --> main.syn:2:7
+   |
+ 2 |   x + true
+   |       ^^^^"
+        );
+    }
+
+    #[test]
+    fn parse_and_lint_diagnostics_point_into_the_source() {
+        let source = "(x) =>\n  x +";
+        let error = pipeline::check_source("main.syn", source).unwrap_err();
+        let rendered = Renderer::new(false)
+            .render(&to_diagnostic(&error, Path::new("main.syn")), Some(source));
+        assert!(rendered.starts_with("error[P001]: "), "{}", rendered);
+        assert!(rendered.contains("\n  --> main.syn:2:"), "{}", rendered);
+        assert!(rendered.contains("\n 2 |   x +\n   | "), "{}", rendered);
+
+        let source = "(unused) => 1";
+        let graph = parser_core::parse_source("main.syn", source).unwrap();
+        let lint = &linter::lint_graph_with(&graph, LintOptions::default())[0];
+        assert_eq!(
+            Renderer::new(false)
+                .render(&lint_diagnostic(lint, Path::new("main.syn")), Some(source)),
+            "\
+warning[L006]: parameter 'unused' is never used
+  --> main.syn:1:2
+   |
+ 1 | (unused) => 1
+   |  ^^^^^^
+  = help: rename to `_unused`"
+        );
+    }
+
+    #[test]
+    fn colors_are_only_added_on_request() {
+        let diagnostic = Diagnostic::error("L001", "unused", "a.syn".to_string());
+        assert_eq!(
+            Renderer::new(false).render(&diagnostic, None),
+            "error[L001]: unused\n  --> a.syn"
+        );
+        assert!(
+            Renderer::new(true)
+                .render(&diagnostic, None)
+                .starts_with("\x1b[1;31merror[L001]")
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! An interactive tutor that explains language concepts and quizzes on them.
+
+use std::io::{self, BufRead, Write};
+
+use crate::error::Result;
+use crate::pipeline;
+
+/// A language concept the tutor can teach.
+#[derive(Debug)]
+pub struct Concept {
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// Where to read more, as item paths in the workspace.
+    pub references: &'static [&'static str],
+    /// A small program illustrating the concept; see [`example`].
+    pub example: &'static str,
+    pub quiz: QuizEntry,
+}
+
+/// A multiple-choice question stored with a concept.
+#[derive(Debug)]
+pub struct QuizEntry {
+    pub prompt: &'static str,
+    pub choices: &'static [&'static str],
+    /// Index of the correct choice.
+    pub answer: usize,
+    /// Why the correct choice is correct.
+    pub explanation: &'static str,
+}
+
+/// Built-in concepts, in the order they are listed to learners.
+pub const KNOWLEDGE_BASE: &[Concept] = &[
+    Concept {
+        name: "functions",
+        summary: "Functions are values written `(x) => body`. Applying one with `f(a)` \
+                  substitutes the argument for the parameter in the body. `(x)(y) => body` \
+                  is shorthand for `(x) => (y) => body`, applied as `f(a)(b)`.",
+        references: &["asg_core::TermLambda", "asg_core::TermApplication"],
+        example: "((x: Int) => x + 1)(41)",
+        quiz: QuizEntry {
+            prompt: "What does `((x) => x + 1)(41)` evaluate to?",
+            choices: &["41", "42", "a function", "a type error"],
+            answer: 1,
+            explanation: "Application substitutes 41 for x in `x + 1`, giving 42.",
+        },
+    },
+    Concept {
+        name: "polymorphism",
+        summary: "A function that never inspects its argument, like `(x) => x`, works at \
+                  every type; the checker infers `T0 -> T0`, where T0 stands for any type.",
+        references: &["type_checker_l1::infer"],
+        example: "(x) => x",
+        quiz: QuizEntry {
+            prompt: "Which type does the checker infer for `(x) => x`?",
+            choices: &["Int -> Int", "Bool -> Bool", "T0 -> T0 for any T0", "Unit"],
+            answer: 2,
+            explanation: "The body only returns x, so nothing constrains its type.",
+        },
+    },
+    Concept {
+        name: "references",
+        summary: "`ref e` allocates a mutable cell, `!r` reads it and `r := e` overwrites it. \
+                  Assignment produces Unit.",
+        references: &["asg_core::TermRef", "asg_core::TermAssign"],
+        example: "(r: Ref Int) => r := !r + 1",
+        quiz: QuizEntry {
+            prompt: "What is the type of `(r: Ref Int) => r := 1`?",
+            choices: &[
+                "Ref Int -> Int",
+                "Ref Int -> Unit",
+                "Int -> Unit",
+                "Ref Int -> Ref Int",
+            ],
+            answer: 1,
+            explanation: "Assignment updates the cell and returns Unit.",
+        },
+    },
+    Concept {
+        name: "effects",
+        summary: "`perform('IO', e)` asks the surrounding handler to carry out the IO effect. \
+                  The Level 2 checker tracks which effects a term may perform.",
+        references: &["asg_core::EffectPerform", "type_checker_l2"],
+        example: "(x: Int) => perform('IO', x)",
+        quiz: QuizEntry {
+            prompt: "Which expression performs the IO effect with payload 1?",
+            choices: &["IO(1)", "perform('IO', 1)", "ref IO", "effect IO 1"],
+            answer: 1,
+            explanation: "Effects are performed with `perform`, naming the effect and a payload.",
+        },
+    },
+];
+
+/// Looks up a concept by name, ignoring case.
+pub fn concept(name: &str) -> Option<&'static Concept> {
+    KNOWLEDGE_BASE
+        .iter()
+        .find(|concept| concept.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Returns the example program for `concept` after checking that it parses
+/// and type-checks, so the tutor never shows code that does not compile.
+pub fn example(concept: &Concept) -> Result<&'static str> {
+    pipeline::check_source(&format!("<example:{}>", concept.name), concept.example)?;
+    Ok(concept.example)
+}
+
+/// A question put to the learner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    pub concept: &'static str,
+    pub prompt: &'static str,
+    pub choices: Vec<&'static str>,
+    answer: usize,
+}
+
+impl Question {
+    /// Builds the quiz question for `concept`.
+    pub fn for_concept(concept: &'static Concept) -> Self {
+        Question {
+            concept: concept.name,
+            prompt: concept.quiz.prompt,
+            choices: concept.quiz.choices.to_vec(),
+            answer: concept.quiz.answer,
+        }
+    }
+
+    /// Renders the prompt with lettered choices.
+    pub fn render(&self) -> String {
+        let mut text = format!("{}\n", self.prompt);
+        for (index, choice) in self.choices.iter().enumerate() {
+            text.push_str(&format!("  {}) {}\n", choice_letter(index), choice));
+        }
+        text
+    }
+
+    /// Interprets a reply given as a letter, a 1-based number or the choice text.
+    fn parse_reply(&self, reply: &str) -> Option<usize> {
+        let reply = reply.trim();
+        let mut chars = reply.chars();
+        if let (Some(letter), None) = (chars.next(), chars.next())
+            && letter.is_ascii_alphabetic()
+        {
+            let index = (letter.to_ascii_lowercase() as u8 - b'a') as usize;
+            return (index < self.choices.len()).then_some(index);
+        }
+        if let Ok(number) = reply.parse::<usize>() {
+            return (1..=self.choices.len())
+                .contains(&number)
+                .then(|| number - 1);
+        }
+        self.choices
+            .iter()
+            .position(|choice| choice.eq_ignore_ascii_case(reply))
+    }
+}
+
+/// Feedback on one answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    pub correct: bool,
+    pub feedback: String,
+}
+
+/// Per-session tutor state.
+#[derive(Debug, Default)]
+pub struct Session {
+    pub correct: u32,
+    pub asked: u32,
+}
+
+impl Session {
+    /// Scores `reply` to `question` and explains the right answer.
+    pub fn answer(&mut self, question: &Question, reply: &str) -> Verdict {
+        let concept = concept(question.concept).expect("questions come from the knowledge base");
+        let correct = question.parse_reply(reply) == Some(question.answer);
+        self.asked += 1;
+        if correct {
+            self.correct += 1;
+        }
+        let opening = if correct {
+            "Correct!".to_string()
+        } else {
+            format!(
+                "Not quite; the answer is {}) {}.",
+                choice_letter(question.answer),
+                question.choices[question.answer]
+            )
+        };
+        Verdict {
+            correct,
+            feedback: format!(
+                "{} {}\nSee: {}\nScore: {}",
+                opening,
+                concept.quiz.explanation,
+                concept.references.join("; "),
+                self.score()
+            ),
+        }
+    }
+
+    /// The running score, e.g. `2/3`.
+    pub fn score(&self) -> String {
+        format!("{}/{}", self.correct, self.asked)
+    }
+}
+
+fn choice_letter(index: usize) -> char {
+    (b'a' + index as u8) as char
+}
+
+const HELP: &str = "Commands:
+  /concepts          list the concepts the tutor knows
+  /explain <concept> explain a concept
+  /example <concept> show a program illustrating a concept
+  /quiz <concept>    answer a question about a concept
+  /score             show your score for this session
+  /quit              leave the tutor";
+
+/// Runs the tutor REPL over `input`, writing to `output`, until `/quit` or EOF.
+pub fn run_repl(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<Session> {
+    let mut session = Session::default();
+    writeln!(output, "Synapse tutor. Type /help for commands.")?;
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+        let Some(line) = read_line(input)? else {
+            break;
+        };
+        let (command, argument) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+        match command {
+            "" => {}
+            "/quit" => break,
+            "/help" => writeln!(output, "{}", HELP)?,
+            "/concepts" => {
+                for concept in KNOWLEDGE_BASE {
+                    writeln!(output, "  {}", concept.name)?;
+                }
+            }
+            "/score" => writeln!(output, "Score: {}", session.score())?,
+            "/explain" | "/example" | "/quiz" => {
+                let Some(concept) = concept(argument) else {
+                    writeln!(
+                        output,
+                        "Unknown concept '{}'; try /concepts.",
+                        argument.trim()
+                    )?;
+                    continue;
+                };
+                if command == "/explain" {
+                    writeln!(output, "{}", concept.summary)?;
+                    writeln!(output, "See: {}", concept.references.join("; "))?;
+                    continue;
+                }
+                if command == "/example" {
+                    match example(concept) {
+                        Ok(program) => writeln!(output, "{}", program)?,
+                        Err(error) => writeln!(output, "No valid example available: {}", error)?,
+                    }
+                    continue;
+                }
+                let question = Question::for_concept(concept);
+                write!(output, "{}answer> ", question.render())?;
+                output.flush()?;
+                let reply = read_line(input)?.unwrap_or_default();
+                writeln!(output, "{}", session.answer(&question, &reply).feedback)?;
+            }
+            other => writeln!(output, "Unknown command '{}'; try /help.", other)?,
+        }
+    }
+    Ok(session)
+}
+
+fn read_line(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effects_quiz_is_well_formed_and_scores_correct_answers() {
+        let question = Question::for_concept(concept("effects").unwrap());
+        assert!(question.choices.len() >= 2);
+        assert!(question.answer < question.choices.len());
+        assert!(question.render().contains("b) perform('IO', 1)"));
+
+        let mut session = Session::default();
+        let verdict = session.answer(&question, "b");
+        assert!(verdict.correct);
+        assert!(verdict.feedback.contains("See: asg_core::EffectPerform"));
+        assert_eq!(session.score(), "1/1");
+
+        assert!(!session.answer(&question, "IO(1)").correct);
+        assert!(session.answer(&question, "2").correct);
+        assert_eq!(session.score(), "2/3");
+    }
+
+    #[test]
+    fn every_example_parses_and_type_checks() {
+        for concept in KNOWLEDGE_BASE {
+            let program = example(concept).unwrap_or_else(|error| {
+                panic!("example for {} is invalid: {}", concept.name, error)
+            });
+            assert!(!program.is_empty());
+        }
+        assert_eq!(
+            example(concept("polymorphism").unwrap()).unwrap(),
+            "(x) => x"
+        );
+        assert!(
+            example(concept("effects").unwrap())
+                .unwrap()
+                .contains("perform('IO'")
+        );
+    }
+
+    #[test]
+    fn repl_quiz_tracks_score_across_questions() {
+        let mut input = "/quiz effects\nb\n/quiz functions\na\n/score\n/quit\n".as_bytes();
+        let mut output = Vec::new();
+        let session = run_repl(&mut input, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(session.score(), "1/2");
+        assert!(output.contains("Correct!"));
+        assert!(output.contains("Not quite; the answer is b) 42."));
+        assert!(output.contains("Score: 1/2"));
+    }
+}
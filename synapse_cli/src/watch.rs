@@ -0,0 +1,150 @@
+//! `watch`: recompile a file every time it is saved.
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::error::Result;
+use crate::pipeline;
+
+/// How long the file must stay quiet before a save triggers a recompile.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// ANSI sequence clearing the terminal and moving the cursor home.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Watches `path` and reruns the pipeline after each save until interrupted.
+pub fn watch(path: &Path, debounce: Duration) -> Result<()> {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    // Editors often save by renaming a temporary file over the original, so
+    // watch the directory rather than the file itself.
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+
+    let report = |path: &Path| {
+        print!("{}{}", CLEAR_SCREEN, recompile(path));
+        let _ = std::io::stdout().flush();
+    };
+    report(path);
+    run_debounced(
+        &events,
+        debounce,
+        |event| touches(event, path),
+        || report(path),
+    );
+    Ok(())
+}
+
+/// Runs the pipeline on `path` and renders the outcome for display.
+pub fn recompile(path: &Path) -> String {
+    match pipeline::compile_file(path) {
+        Ok(compilation) => {
+            let root_type = compilation
+                .graph
+                .root()
+                .and_then(|root| compilation.types.get(&root))
+                .map(ToString::to_string)
+                .unwrap_or_else(|| "<empty>".to_string());
+            format!(
+                "{}: ok, {} lowered to {} function(s)\n",
+                path.display(),
+                root_type,
+                compilation.module.functions.len()
+            )
+        }
+        Err(error) => format!("{}: {}\n", path.display(), error),
+    }
+}
+
+/// Calls `on_change` once per burst of relevant events.
+///
+/// A burst ends when no event arrives for `delay`. Returns when the sending
+/// side of `events` is dropped.
+pub fn run_debounced<T>(
+    events: &Receiver<T>,
+    delay: Duration,
+    mut relevant: impl FnMut(&T) -> bool,
+    mut on_change: impl FnMut(),
+) {
+    while let Ok(event) = events.recv() {
+        if !relevant(&event) {
+            continue;
+        }
+        loop {
+            match events.recv_timeout(delay) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    on_change();
+                    return;
+                }
+            }
+        }
+        on_change();
+    }
+}
+
+fn touches(event: &notify::Result<Event>, path: &Path) -> bool {
+    let Ok(event) = event else {
+        return false;
+    };
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event
+            .paths
+            .iter()
+            .any(|changed| changed.file_name() == path.file_name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn rapid_saves_trigger_exactly_one_recheck() {
+        let path = std::env::temp_dir().join(format!("synapse_watch_{}.syn", std::process::id()));
+        std::fs::write(&path, "(x: Int) => x").unwrap();
+        assert!(recompile(&path).contains("ok, Int -> Int"));
+
+        let (sender, events) = mpsc::channel();
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                for source in ["(x: Int) => x +", "(x: Int) => x + 1", "(x: Int) => x < 1"] {
+                    std::fs::write(&path, source).unwrap();
+                    sender.send(()).unwrap();
+                    thread::sleep(Duration::from_millis(5));
+                }
+            })
+        };
+
+        let mut reports = Vec::new();
+        run_debounced(
+            &events,
+            Duration::from_millis(100),
+            |_| true,
+            || reports.push(recompile(&path)),
+        );
+        writer.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].contains("ok, Int -> Bool"));
+    }
+
+    #[test]
+    fn parse_errors_are_reported_not_fatal() {
+        let path = std::env::temp_dir().join(format!("synapse_bad_{}.syn", std::process::id()));
+        std::fs::write(&path, "(x) =>").unwrap();
+        let report = recompile(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(report.contains("parse error: 1:7"));
+    }
+}
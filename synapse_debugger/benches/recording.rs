@@ -0,0 +1,64 @@
+//! Recording throughput: one event at a time versus in batches.
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use synapse_debugger::{EventData, FileTraceStorage, TraceEventSpec, TraceStream};
+
+const EVENTS: u64 = 10_000;
+const MEMORY_CAP: usize = 1_000;
+
+fn specs() -> Vec<TraceEventSpec> {
+    (0..EVENTS)
+        .map(|node_id| TraceEventSpec {
+            thread_id: 1,
+            cause: node_id.checked_sub(1),
+            data: EventData::FunctionCall {
+                function: "f".to_string(),
+                node_id,
+            },
+        })
+        .collect()
+}
+
+fn file_trace() -> TraceStream {
+    let path = std::env::temp_dir().join(format!("synapse_bench_{}.jsonl", std::process::id()));
+    let storage = FileTraceStorage::create(path).unwrap();
+    TraceStream::with_storage(Box::new(storage), MEMORY_CAP)
+}
+
+fn recording(c: &mut Criterion) {
+    let mut group = c.benchmark_group("record");
+    group.bench_function("sequential", |b| {
+        b.iter_batched(
+            || (file_trace(), specs()),
+            |(mut trace, specs)| {
+                for spec in specs {
+                    trace.record_event(spec.thread_id, spec.cause, spec.data);
+                }
+                trace.flush().unwrap();
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("batched", |b| {
+        b.iter_batched(
+            || {
+                let batches: Vec<Vec<_>> = specs()
+                    .chunks(MEMORY_CAP)
+                    .map(|chunk| chunk.to_vec())
+                    .collect();
+                (file_trace(), batches)
+            },
+            |(mut trace, batches)| {
+                for batch in batches {
+                    trace.record_events_batch(batch);
+                }
+                trace.flush().unwrap();
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, recording);
+criterion_main!(benches);
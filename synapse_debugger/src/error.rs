@@ -0,0 +1,33 @@
+//! Errors reported by the debugger.
+
+use synapse_runtime::EvalError;
+use thiserror::Error;
+
+use crate::replay::Divergence;
+
+/// Reasons a trace operation can fail.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DebuggerError {
+    /// No event with this id is in the trace.
+    #[error("no event with id {0} in the trace")]
+    InvalidEventId(u64),
+
+    /// No trace session with this name is active.
+    #[error("no trace session named '{0}' is active")]
+    UnknownSession(String),
+
+    /// An event could not be recorded or stored.
+    #[error("recording failed: {0}")]
+    RecordingFailed(String),
+
+    /// Replaying a trace produced a different event than was recorded.
+    #[error("replay diverged from the trace: {0}")]
+    Diverged(Box<Divergence>),
+
+    /// The program failed during replay for a reason other than divergence.
+    #[error("replay failed: {0}")]
+    ReplayFailed(EvalError),
+}
+
+/// Convenience alias for debugger results.
+pub type Result<T> = std::result::Result<T, DebuggerError>;
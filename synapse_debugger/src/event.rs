@@ -0,0 +1,128 @@
+//! Events captured while a traced program runs.
+
+use serde::{Deserialize, Serialize};
+
+/// Identifier of an event within its trace, assigned in recording order.
+pub type EventId = u64;
+
+/// Coarse kind of an event, used for filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventCategory {
+    FunctionCall,
+    FunctionReturn,
+    EffectPerformed,
+    MemoryAllocation,
+    MemoryDeallocation,
+    LoopIteration,
+}
+
+/// Details of a performed effect.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectData {
+    pub effect_name: String,
+    /// The payload, rendered as text.
+    pub argument: String,
+    /// The `EffectPerform` node that performed the effect.
+    pub node_id: u64,
+}
+
+/// A loop starting another pass of its body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoopIterationData {
+    /// The node of the loop.
+    pub loop_node_id: u64,
+    /// Passes started so far, counting this one.
+    pub iteration: u64,
+}
+
+/// What happened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventData {
+    FunctionCall { function: String, node_id: u64 },
+    FunctionReturn { function: String, value: String },
+    EffectPerformed(EffectData),
+    MemoryAllocation { address: u64, size: usize },
+    MemoryDeallocation { address: u64 },
+    LoopIteration(LoopIterationData),
+}
+
+impl EventData {
+    pub fn category(&self) -> EventCategory {
+        match self {
+            EventData::FunctionCall { .. } => EventCategory::FunctionCall,
+            EventData::FunctionReturn { .. } => EventCategory::FunctionReturn,
+            EventData::EffectPerformed(_) => EventCategory::EffectPerformed,
+            EventData::MemoryAllocation { .. } => EventCategory::MemoryAllocation,
+            EventData::MemoryDeallocation { .. } => EventCategory::MemoryDeallocation,
+            EventData::LoopIteration(_) => EventCategory::LoopIteration,
+        }
+    }
+}
+
+/// A recorded event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    pub id: EventId,
+    /// Position in the trace's total order; strictly increasing.
+    pub logical_time: u64,
+    pub thread_id: u64,
+    /// The previous event on the same thread, if any.
+    pub cause: Option<EventId>,
+    pub data: EventData,
+}
+
+impl TraceEvent {
+    pub fn category(&self) -> EventCategory {
+        self.data.category()
+    }
+}
+
+/// An event to record, before the trace assigns its id and logical time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEventSpec {
+    pub thread_id: u64,
+    pub cause: Option<EventId>,
+    pub data: EventData,
+}
+
+/// Which events to select from a stored trace. Unset criteria match any
+/// event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceQuery {
+    pub category: Option<EventCategory>,
+    pub thread_id: Option<u64>,
+    /// Inclusive bounds on logical time.
+    pub time_range: Option<(u64, u64)>,
+}
+
+impl TraceQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_category(mut self, category: EventCategory) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn with_thread(mut self, thread_id: u64) -> Self {
+        self.thread_id = Some(thread_id);
+        self
+    }
+
+    pub fn with_time_range(mut self, start: u64, end: u64) -> Self {
+        self.time_range = Some((start, end));
+        self
+    }
+
+    pub fn matches(&self, event: &TraceEvent) -> bool {
+        self.category
+            .is_none_or(|category| event.category() == category)
+            && self
+                .thread_id
+                .is_none_or(|thread| event.thread_id == thread)
+            && self
+                .time_range
+                .is_none_or(|(start, end)| (start..=end).contains(&event.logical_time))
+    }
+}
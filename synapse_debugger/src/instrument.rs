@@ -0,0 +1,83 @@
+//! Hooks that record interpreter activity into a trace.
+
+use synapse_runtime::{EffectHandler, Value};
+
+use crate::manager::ThreadContext;
+
+/// An effect handler that records every performed effect before passing it
+/// on to `inner`.
+pub struct TracingHandler<H> {
+    context: ThreadContext,
+    inner: H,
+}
+
+impl<H: EffectHandler> TracingHandler<H> {
+    pub fn new(context: ThreadContext, inner: H) -> Self {
+        Self { context, inner }
+    }
+
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+impl<H: EffectHandler> EffectHandler for TracingHandler<H> {
+    fn perform(
+        &mut self,
+        node_id: u64,
+        effect: &str,
+        payload: &Value,
+    ) -> synapse_runtime::Result<Value> {
+        self.context
+            .record_effect(effect, &payload.to_string(), node_id);
+        self.inner.perform(node_id, effect, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{EffectData, EventCategory, EventData};
+    use crate::manager::TraceManager;
+    use synapse_runtime::{Interpreter, RecordingHandler};
+
+    #[test]
+    fn performed_effect_is_recorded_under_an_active_trace() {
+        let graph = parser_core::parse_str("perform('IO', 1)").unwrap();
+        let perform_node = graph.root().unwrap();
+        let manager = TraceManager::new();
+        manager.start_trace();
+
+        let handler = TracingHandler::new(
+            manager.current_thread_context(),
+            RecordingHandler::default(),
+        );
+        let mut interpreter = Interpreter::with_handler(&graph, handler);
+        assert_eq!(interpreter.run(), Ok(Value::Unit));
+        assert_eq!(interpreter.handler().inner().performed.len(), 1);
+
+        let trace = manager.stop_trace().unwrap();
+        let effects = trace.filter_events(EventCategory::EffectPerformed).unwrap();
+        assert_eq!(effects.len(), 1);
+        assert_eq!(
+            effects[0].data,
+            EventData::EffectPerformed(EffectData {
+                effect_name: "IO".to_string(),
+                argument: "1".to_string(),
+                node_id: perform_node,
+            })
+        );
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_a_trace() {
+        let graph = parser_core::parse_str("perform('IO', 1)").unwrap();
+        let manager = TraceManager::new();
+        let handler = TracingHandler::new(
+            manager.current_thread_context(),
+            RecordingHandler::default(),
+        );
+        Interpreter::with_handler(&graph, handler).run().unwrap();
+        assert!(manager.stop_trace().is_none());
+    }
+}
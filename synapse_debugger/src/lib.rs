@@ -1,14 +1,32 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Holographic debugger for Synapse programs.
+//!
+//! A [`TraceManager`] collects [`TraceEvent`]s into a [`TraceStream`] while a
+//! trace is active, and can run named [`TraceSession`]s alongside it. Each
+//! thread records through its own [`ThreadContext`], which links consecutive
+//! events so their causal history can be followed.
+//! Long traces can be backed by a [`TraceStorage`] such as
+//! [`FileTraceStorage`] so that only recent events stay in memory.
+//! [`TracingHandler`] instruments the interpreter's effect handling, and
+//! [`replay`] re-runs a program to check it against a recorded trace.
+//! [`StateReconstructor`] rebuilds the program state at any point of a trace.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod error;
+pub mod event;
+pub mod instrument;
+pub mod manager;
+pub mod reconstruct;
+pub mod replay;
+pub mod storage;
+pub mod trace;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use error::{DebuggerError, Result};
+pub use event::{
+    EffectData, EventCategory, EventData, EventId, LoopIterationData, TraceEvent, TraceEventSpec,
+    TraceQuery,
+};
+pub use instrument::TracingHandler;
+pub use manager::{ThreadContext, TraceManager, TraceSession};
+pub use reconstruct::{CallStackFrame, ProgramState, StateReconstructor};
+pub use replay::{Divergence, StateSnapshot, replay};
+pub use storage::{FileTraceStorage, TraceStorage};
+pub use trace::{EventIter, TraceStream};
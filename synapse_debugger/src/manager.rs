@@ -0,0 +1,299 @@
+//! Starting and stopping traces, and recording into the active ones.
+//!
+//! Besides the default trace, any number of named sessions can be active at
+//! once, so independent components can each capture their own trace.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::error::{DebuggerError, Result};
+use crate::event::{EffectData, EventData, EventId, LoopIterationData, TraceEventSpec};
+use crate::trace::TraceStream;
+
+/// Owns the active traces, if any. Recording while no trace is active is a
+/// cheap no-op, so instrumentation can stay in place permanently.
+#[derive(Debug, Default)]
+pub struct TraceManager {
+    active_trace: Mutex<Option<TraceStream>>,
+    sessions: Mutex<HashMap<String, TraceStream>>,
+    /// The session each thread first recorded into.
+    thread_sessions: Mutex<HashMap<u64, String>>,
+}
+
+impl TraceManager {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Starts a fresh trace, discarding any trace still active.
+    pub fn start_trace(&self) {
+        *self.active_trace.lock().unwrap() = Some(TraceStream::new());
+    }
+
+    /// Ends the active trace and returns it.
+    pub fn stop_trace(&self) -> Option<TraceStream> {
+        self.active_trace.lock().unwrap().take()
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.active_trace.lock().unwrap().is_some()
+    }
+
+    /// Starts a fresh session called `name`, discarding any session still
+    /// active under that name.
+    pub fn start_trace_named(self: &Arc<Self>, name: &str) -> TraceSession {
+        self.start_trace_named_with(name, TraceStream::new())
+    }
+
+    /// Starts a session called `name` that records into `trace`, for
+    /// instance one backed by storage.
+    pub fn start_trace_named_with(
+        self: &Arc<Self>,
+        name: &str,
+        trace: TraceStream,
+    ) -> TraceSession {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), trace);
+        TraceSession {
+            manager: Arc::clone(self),
+            name: name.to_string(),
+        }
+    }
+
+    /// Ends the session called `name`, flushes its storage and returns its
+    /// trace. Threads associated with it are released. Other sessions and
+    /// the default trace keep recording.
+    pub fn stop_trace_named(&self, name: &str) -> Result<TraceStream> {
+        let mut trace = self
+            .sessions
+            .lock()
+            .unwrap()
+            .remove(name)
+            .ok_or_else(|| DebuggerError::UnknownSession(name.to_string()))?;
+        self.thread_sessions
+            .lock()
+            .unwrap()
+            .retain(|_, session| session != name);
+        trace.flush()?;
+        Ok(trace)
+    }
+
+    /// Records into the active trace; returns `None` if there is none.
+    pub fn record_event(
+        &self,
+        thread_id: u64,
+        cause: Option<EventId>,
+        data: EventData,
+    ) -> Option<EventId> {
+        self.record_event_in(None, thread_id, cause, data)
+    }
+
+    /// Records into the session called `session`, or into the default trace
+    /// if `session` is `None`; returns `None` if that trace is not active.
+    /// The first session a thread records into becomes its session.
+    pub fn record_event_in(
+        &self,
+        session: Option<&str>,
+        thread_id: u64,
+        cause: Option<EventId>,
+        data: EventData,
+    ) -> Option<EventId> {
+        let Some(name) = session else {
+            return self
+                .active_trace
+                .lock()
+                .unwrap()
+                .as_mut()
+                .map(|trace| trace.record_event(thread_id, cause, data));
+        };
+        let id = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get_mut(name)?
+            .record_event(thread_id, cause, data);
+        self.thread_sessions
+            .lock()
+            .unwrap()
+            .entry(thread_id)
+            .or_insert_with(|| name.to_string());
+        Some(id)
+    }
+
+    /// Records `specs` into the active trace under a single lock; returns
+    /// `None` if there is none.
+    pub fn record_events_batch(&self, specs: Vec<TraceEventSpec>) -> Option<Range<EventId>> {
+        self.active_trace
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(|trace| trace.record_events_batch(specs))
+    }
+
+    /// A recording context for the calling thread. It records into the
+    /// session the thread first recorded into, if that session is still
+    /// active, and into the default trace otherwise.
+    ///
+    /// A thread can still record into other sessions through their own
+    /// contexts, from [`TraceSession::current_thread_context`]; that does not
+    /// change which session this method picks.
+    pub fn current_thread_context(self: &Arc<Self>) -> ThreadContext {
+        let thread_id = current_thread_id();
+        let session = self
+            .thread_sessions
+            .lock()
+            .unwrap()
+            .get(&thread_id)
+            .cloned();
+        ThreadContext {
+            manager: Arc::clone(self),
+            thread_id,
+            session,
+            last_event: None,
+        }
+    }
+}
+
+/// A named trace session started by [`TraceManager::start_trace_named`].
+#[derive(Debug, Clone)]
+pub struct TraceSession {
+    manager: Arc<TraceManager>,
+    name: String,
+}
+
+impl TraceSession {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A context that records the calling thread into this session.
+    pub fn current_thread_context(&self) -> ThreadContext {
+        ThreadContext {
+            manager: Arc::clone(&self.manager),
+            thread_id: current_thread_id(),
+            session: Some(self.name.clone()),
+            last_event: None,
+        }
+    }
+
+    /// Ends the session; see [`TraceManager::stop_trace_named`].
+    pub fn stop(self) -> Result<TraceStream> {
+        self.manager.stop_trace_named(&self.name)
+    }
+}
+
+fn current_thread_id() -> u64 {
+    static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+    }
+    THREAD_ID.with(|id| *id)
+}
+
+/// Records events for one thread, linking each to the thread's previous
+/// event as its cause.
+#[derive(Debug, Clone)]
+pub struct ThreadContext {
+    manager: Arc<TraceManager>,
+    thread_id: u64,
+    /// The session recorded into; `None` for the default trace.
+    session: Option<String>,
+    last_event: Option<EventId>,
+}
+
+impl ThreadContext {
+    pub fn thread_id(&self) -> u64 {
+        self.thread_id
+    }
+
+    pub fn session(&self) -> Option<&str> {
+        self.session.as_deref()
+    }
+
+    /// Records `data` if a trace is active.
+    pub fn record(&mut self, data: EventData) -> Option<EventId> {
+        let id = self.manager.record_event_in(
+            self.session.as_deref(),
+            self.thread_id,
+            self.last_event,
+            data,
+        )?;
+        self.last_event = Some(id);
+        Some(id)
+    }
+
+    /// Records that node `node_id` performed `effect_name` with `argument`.
+    pub fn record_effect(
+        &mut self,
+        effect_name: &str,
+        argument: &str,
+        node_id: u64,
+    ) -> Option<EventId> {
+        self.record(EventData::EffectPerformed(EffectData {
+            effect_name: effect_name.to_string(),
+            argument: argument.to_string(),
+            node_id,
+        }))
+    }
+
+    /// Records that the loop at `loop_node_id` began pass `iteration`.
+    pub fn record_loop_iteration(&mut self, loop_node_id: u64, iteration: u64) -> Option<EventId> {
+        self.record(EventData::LoopIteration(LoopIterationData {
+            loop_node_id,
+            iteration,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventCategory;
+
+    #[test]
+    fn named_sessions_capture_disjoint_traces() {
+        let manager = TraceManager::new();
+        manager.start_trace();
+        let parser = manager.start_trace_named("parser");
+        let runtime = manager.start_trace_named("runtime");
+
+        let mut parsing = parser.current_thread_context();
+        parsing.record_effect("IO", "1", 0);
+        parsing.record_effect("IO", "2", 1);
+        runtime
+            .current_thread_context()
+            .record_effect("State", "3", 2);
+
+        // The thread first recorded into the parser session, so that is
+        // where its plain context goes.
+        let mut plain = manager.current_thread_context();
+        assert_eq!(plain.session(), Some("parser"));
+        plain.record(EventData::MemoryDeallocation { address: 8 });
+
+        let parser_trace = parser.stop().unwrap();
+        assert_eq!(parser_trace.len(), 3);
+        assert_eq!(
+            parser_trace
+                .filter_events(EventCategory::EffectPerformed)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(matches!(
+            manager.stop_trace_named("parser"),
+            Err(DebuggerError::UnknownSession(_))
+        ));
+
+        // With its session gone the thread falls back to the default trace.
+        assert_eq!(manager.current_thread_context().session(), None);
+        manager
+            .current_thread_context()
+            .record(EventData::MemoryDeallocation { address: 9 });
+        assert_eq!(runtime.stop().unwrap().len(), 1);
+        assert_eq!(manager.stop_trace().unwrap().len(), 1);
+    }
+}
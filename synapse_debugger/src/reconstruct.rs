@@ -0,0 +1,449 @@
+//! Program state at any point of a trace.
+//!
+//! A [`StateReconstructor`] folds a trace's events into a [`ProgramState`].
+//! It keeps a cursor at the last requested time together with an undo log,
+//! so stepping one event forward or back costs one event, and caches a
+//! checkpoint every [`CHECKPOINT_INTERVAL`] events so a jump only replays
+//! from the nearest checkpoint.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::error::{DebuggerError, Result};
+use crate::event::{EffectData, EventData, EventId, LoopIterationData, TraceEvent};
+use crate::trace::TraceStream;
+
+/// Events between cached checkpoints.
+pub const CHECKPOINT_INTERVAL: u64 = 64;
+
+/// What the traced program looked like after an event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramState {
+    /// Logical time of the last event applied.
+    pub logical_time: u64,
+    /// Functions entered and not yet returned from, innermost last. Threads
+    /// with nothing on their stack are absent.
+    pub call_stacks: BTreeMap<u64, Vec<CallStackFrame>>,
+    /// Live allocations, from address to size.
+    pub allocations: BTreeMap<u64, usize>,
+    /// Effects performed so far, in order.
+    pub effects: Vec<EffectData>,
+}
+
+/// A function on a thread's call stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallStackFrame {
+    pub function: String,
+    /// The latest iteration of each loop run by this call, by loop node.
+    /// Loops in functions it calls are counted in their own frames.
+    pub loop_counters: HashMap<u64, u64>,
+}
+
+impl CallStackFrame {
+    fn new(function: String) -> Self {
+        Self {
+            function,
+            loop_counters: HashMap::new(),
+        }
+    }
+}
+
+/// How to take back one applied event.
+#[derive(Debug, Clone)]
+struct Undo {
+    previous_time: u64,
+    kind: UndoKind,
+}
+
+#[derive(Debug, Clone)]
+enum UndoKind {
+    PopCall {
+        thread_id: u64,
+    },
+    /// Puts back the frame a return popped, if there was one.
+    PushCall {
+        thread_id: u64,
+        frame: Option<CallStackFrame>,
+    },
+    /// Restores a loop counter of the innermost frame.
+    RestoreLoopCounter {
+        thread_id: u64,
+        loop_node_id: u64,
+        previous: Option<u64>,
+    },
+    Nothing,
+    PopEffect,
+    /// Restores what an allocation overwrote or a deallocation removed.
+    RestoreAllocation {
+        address: u64,
+        previous: Option<usize>,
+    },
+}
+
+impl ProgramState {
+    /// Applies `event` and returns how to undo it.
+    fn apply_event_to_state(&mut self, event: &TraceEvent) -> Undo {
+        let previous_time = self.logical_time;
+        self.logical_time = event.logical_time;
+        let thread_id = event.thread_id;
+        let kind = match &event.data {
+            EventData::FunctionCall { function, .. } => {
+                self.call_stacks
+                    .entry(thread_id)
+                    .or_default()
+                    .push(CallStackFrame::new(function.clone()));
+                UndoKind::PopCall { thread_id }
+            }
+            EventData::FunctionReturn { .. } => UndoKind::PushCall {
+                thread_id,
+                frame: self.pop_call(thread_id),
+            },
+            EventData::EffectPerformed(effect) => {
+                self.effects.push(effect.clone());
+                UndoKind::PopEffect
+            }
+            EventData::MemoryAllocation { address, size } => UndoKind::RestoreAllocation {
+                address: *address,
+                previous: self.allocations.insert(*address, *size),
+            },
+            EventData::MemoryDeallocation { address } => UndoKind::RestoreAllocation {
+                address: *address,
+                previous: self.allocations.remove(address),
+            },
+            // An iteration outside any recorded call has no frame to count in.
+            EventData::LoopIteration(LoopIterationData {
+                loop_node_id,
+                iteration,
+            }) => match self.innermost_frame(thread_id) {
+                Some(frame) => UndoKind::RestoreLoopCounter {
+                    thread_id,
+                    loop_node_id: *loop_node_id,
+                    previous: frame.loop_counters.insert(*loop_node_id, *iteration),
+                },
+                None => UndoKind::Nothing,
+            },
+        };
+        Undo {
+            previous_time,
+            kind,
+        }
+    }
+
+    fn unapply(&mut self, undo: Undo) {
+        self.logical_time = undo.previous_time;
+        match undo.kind {
+            UndoKind::PopCall { thread_id } => {
+                self.pop_call(thread_id);
+            }
+            UndoKind::PushCall {
+                thread_id,
+                frame: Some(frame),
+            } => self.call_stacks.entry(thread_id).or_default().push(frame),
+            UndoKind::PushCall { frame: None, .. } | UndoKind::Nothing => {}
+            UndoKind::RestoreLoopCounter {
+                thread_id,
+                loop_node_id,
+                previous,
+            } => {
+                let frame = self
+                    .innermost_frame(thread_id)
+                    .expect("the counted frame is innermost again");
+                match previous {
+                    Some(iteration) => frame.loop_counters.insert(loop_node_id, iteration),
+                    None => frame.loop_counters.remove(&loop_node_id),
+                };
+            }
+            UndoKind::PopEffect => {
+                self.effects.pop();
+            }
+            UndoKind::RestoreAllocation { address, previous } => match previous {
+                Some(size) => {
+                    self.allocations.insert(address, size);
+                }
+                None => {
+                    self.allocations.remove(&address);
+                }
+            },
+        }
+    }
+
+    fn pop_call(&mut self, thread_id: u64) -> Option<CallStackFrame> {
+        let stack = self.call_stacks.get_mut(&thread_id)?;
+        let frame = stack.pop();
+        if stack.is_empty() {
+            self.call_stacks.remove(&thread_id);
+        }
+        frame
+    }
+
+    fn innermost_frame(&mut self, thread_id: u64) -> Option<&mut CallStackFrame> {
+        self.call_stacks.get_mut(&thread_id)?.last_mut()
+    }
+}
+
+/// The state after event `next - 1`, with the undo information for the
+/// events applied since the cursor last jumped.
+struct Cursor {
+    state: ProgramState,
+    next: EventId,
+    undo: Vec<Undo>,
+}
+
+/// Reconstructs [`ProgramState`]s from a trace that may still be growing.
+pub struct StateReconstructor {
+    trace: Arc<Mutex<TraceStream>>,
+    /// Checkpoints keyed by the id of the last event they include.
+    state_cache: BTreeMap<EventId, Arc<ProgramState>>,
+    cursor: Option<Cursor>,
+    /// How many events the ring buffer had dropped when the cache was built.
+    evicted: u64,
+}
+
+impl StateReconstructor {
+    pub fn new(trace: Arc<Mutex<TraceStream>>) -> Self {
+        Self {
+            trace,
+            state_cache: BTreeMap::new(),
+            cursor: None,
+            evicted: 0,
+        }
+    }
+
+    /// The state after the event with logical time `time`, counting only the
+    /// events the trace still retains.
+    pub fn reconstruct_at(&mut self, time: u64) -> Result<Arc<ProgramState>> {
+        let trace = Arc::clone(&self.trace);
+        let trace = trace.lock().unwrap();
+        self.invalidate_if_evicted(&trace);
+        let first = trace
+            .iter_events()
+            .next()
+            .transpose()?
+            .map(|event| event.id);
+        if first.is_none_or(|first| time < first) || time as usize >= trace.len() {
+            return Err(DebuggerError::InvalidEventId(time));
+        }
+        if let Some(state) = self.state_cache.get(&time) {
+            return Ok(Arc::clone(state));
+        }
+
+        let target = time + 1;
+        let checkpoint = self
+            .state_cache
+            .range(..target)
+            .next_back()
+            .map(|(&id, state)| (id + 1, state));
+        let cursor = match self.cursor.take() {
+            // Step back through the undo log.
+            Some(cursor)
+                if cursor.next >= target && cursor.next - target <= cursor.undo.len() as u64 =>
+            {
+                cursor
+            }
+            // Step forward, unless a checkpoint is closer.
+            Some(cursor)
+                if cursor.next < target
+                    && checkpoint.is_none_or(|(next, _)| next <= cursor.next) =>
+            {
+                cursor
+            }
+            _ => match checkpoint {
+                Some((next, state)) => Cursor {
+                    state: ProgramState::clone(state),
+                    next,
+                    undo: Vec::new(),
+                },
+                None => Cursor {
+                    state: ProgramState::default(),
+                    next: first.unwrap_or_default(),
+                    undo: Vec::new(),
+                },
+            },
+        };
+        let cursor = self.cursor.insert(cursor);
+
+        while cursor.next > target {
+            let undo = cursor.undo.pop().expect("undo log covers the step back");
+            cursor.state.unapply(undo);
+            cursor.next -= 1;
+        }
+        while cursor.next < target {
+            let event = trace.get_event(cursor.next)?;
+            cursor.undo.push(cursor.state.apply_event_to_state(&event));
+            if (event.id + 1).is_multiple_of(CHECKPOINT_INTERVAL) {
+                self.state_cache
+                    .entry(event.id)
+                    .or_insert_with(|| Arc::new(cursor.state.clone()));
+            }
+            cursor.next += 1;
+        }
+        Ok(Arc::new(cursor.state.clone()))
+    }
+
+    /// The state one event before `current`.
+    pub fn reconstruct_prev(&mut self, current: u64) -> Result<Arc<ProgramState>> {
+        let previous = current
+            .checked_sub(1)
+            .ok_or(DebuggerError::InvalidEventId(current))?;
+        self.reconstruct_at(previous)
+    }
+
+    /// The state one event after `current`.
+    pub fn reconstruct_next(&mut self, current: u64) -> Result<Arc<ProgramState>> {
+        self.reconstruct_at(current + 1)
+    }
+
+    /// Appending events leaves earlier states as they were, so growth alone
+    /// keeps the cache. Once a ring buffer drops events, though, states are
+    /// rebuilt from a later first event and everything cached is stale.
+    fn invalidate_if_evicted(&mut self, trace: &TraceStream) {
+        if trace.evicted_count() != self.evicted {
+            self.evicted = trace.evicted_count();
+            self.state_cache.clear();
+            self.cursor = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::TraceManager;
+
+    /// Two threads calling, allocating, performing and returning.
+    fn record(trace: &mut TraceStream, count: u64) {
+        for step in 0..count {
+            let thread_id = step % 2;
+            let data = match step % 8 {
+                0 | 3 => EventData::FunctionCall {
+                    function: format!("f{}", step),
+                    node_id: step,
+                },
+                1 => EventData::MemoryAllocation {
+                    address: step % 5,
+                    size: step as usize,
+                },
+                2 => EventData::EffectPerformed(EffectData {
+                    effect_name: "IO".to_string(),
+                    argument: step.to_string(),
+                    node_id: step,
+                }),
+                4 => EventData::MemoryDeallocation { address: step % 5 },
+                5 => EventData::LoopIteration(LoopIterationData {
+                    loop_node_id: step % 3,
+                    iteration: step,
+                }),
+                _ => EventData::FunctionReturn {
+                    function: String::new(),
+                    value: "()".to_string(),
+                },
+            };
+            trace.record_event(thread_id, None, data);
+        }
+    }
+
+    fn from_scratch(trace: &TraceStream, time: u64) -> ProgramState {
+        let mut state = ProgramState::default();
+        for event in trace.iter_events().map(Result::unwrap) {
+            if event.id <= time {
+                state.apply_event_to_state(&event);
+            }
+        }
+        state
+    }
+
+    #[test]
+    fn stepping_back_and_forth_matches_rebuilding_from_scratch() {
+        let mut trace = TraceStream::new();
+        record(&mut trace, 200);
+        let expected: Vec<_> = (0..200).map(|time| from_scratch(&trace, time)).collect();
+        let mut reconstructor = StateReconstructor::new(Arc::new(Mutex::new(trace)));
+
+        assert_eq!(*reconstructor.reconstruct_at(199).unwrap(), expected[199]);
+        for time in (0..199).rev() {
+            let state = reconstructor.reconstruct_prev(time + 1).unwrap();
+            assert_eq!(*state, expected[time as usize], "at {}", time);
+        }
+        for time in 1..200 {
+            let state = reconstructor.reconstruct_next(time - 1).unwrap();
+            assert_eq!(*state, expected[time as usize], "at {}", time);
+        }
+        assert_eq!(*reconstructor.reconstruct_at(70).unwrap(), expected[70]);
+        assert_eq!(*reconstructor.reconstruct_at(3).unwrap(), expected[3]);
+        assert_eq!(
+            reconstructor.reconstruct_prev(0),
+            Err(DebuggerError::InvalidEventId(0))
+        );
+        assert_eq!(
+            reconstructor.reconstruct_next(199),
+            Err(DebuggerError::InvalidEventId(200))
+        );
+    }
+
+    #[test]
+    fn loop_counters_stay_with_their_frame_across_nested_calls() {
+        let manager = TraceManager::new();
+        manager.start_trace();
+        let mut context = manager.current_thread_context();
+        let call = |function: &str| EventData::FunctionCall {
+            function: function.to_string(),
+            node_id: 0,
+        };
+        let ret = EventData::FunctionReturn {
+            function: String::new(),
+            value: "()".to_string(),
+        };
+        context.record(call("outer"));
+        context.record_loop_iteration(10, 1);
+        context.record(call("body"));
+        context.record_loop_iteration(20, 1);
+        context.record_loop_iteration(20, 2);
+        context.record(ret.clone());
+        context.record_loop_iteration(10, 2);
+        let trace = manager.stop_trace().unwrap();
+        let thread_id = context.thread_id();
+        let mut reconstructor = StateReconstructor::new(Arc::new(Mutex::new(trace)));
+
+        let counters = |state: &ProgramState| -> Vec<Vec<(u64, u64)>> {
+            state.call_stacks[&thread_id]
+                .iter()
+                .map(|frame| {
+                    let mut counters: Vec<_> = frame.loop_counters.clone().into_iter().collect();
+                    counters.sort();
+                    counters
+                })
+                .collect()
+        };
+        let state = reconstructor.reconstruct_at(4).unwrap();
+        assert_eq!(counters(&state), vec![vec![(10, 1)], vec![(20, 2)]]);
+        let state = reconstructor.reconstruct_at(6).unwrap();
+        assert_eq!(counters(&state), vec![vec![(10, 2)]]);
+        // Undoing the return brings the inner frame back with its counters.
+        let state = reconstructor.reconstruct_prev(5).unwrap();
+        assert_eq!(counters(&state), vec![vec![(10, 1)], vec![(20, 2)]]);
+        let state = reconstructor.reconstruct_prev(2).unwrap();
+        assert_eq!(counters(&state), vec![vec![(10, 1)]]);
+    }
+
+    #[test]
+    fn cache_follows_a_growing_trace() {
+        let trace = Arc::new(Mutex::new(TraceStream::with_capacity(100)));
+        record(&mut trace.lock().unwrap(), 90);
+        let mut reconstructor = StateReconstructor::new(Arc::clone(&trace));
+        reconstructor.reconstruct_at(89).unwrap();
+
+        // Growth within capacity: earlier states stay valid.
+        record(&mut trace.lock().unwrap(), 10);
+        let state = reconstructor.reconstruct_next(89).unwrap();
+        assert_eq!(*state, from_scratch(&trace.lock().unwrap(), 90));
+
+        // Growth past capacity drops the first events, changing every state.
+        record(&mut trace.lock().unwrap(), 30);
+        let state = reconstructor.reconstruct_at(100).unwrap();
+        assert_eq!(*state, from_scratch(&trace.lock().unwrap(), 100));
+        assert_eq!(
+            reconstructor.reconstruct_at(20),
+            Err(DebuggerError::InvalidEventId(20))
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Re-running a program against a recorded trace.
+//!
+//! [`replay`] evaluates the program again with a handler that produces the
+//! same events [`crate::TracingHandler`] records and compares each with the
+//! trace, in order. The first mismatch stops the run and is reported as a
+//! [`Divergence`], which points at nondeterminism in the program or a bug in
+//! the instrumentation.
+
+use std::fmt;
+
+use asg_core::AsgGraph;
+use synapse_runtime::{EffectHandler, EvalError, Interpreter, Value};
+
+use crate::error::{DebuggerError, Result};
+use crate::event::{EffectData, EventCategory, EventData};
+
+/// The state a replayed program finished in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateSnapshot {
+    /// The value of the program's root term.
+    pub value: Value,
+    /// Every reference cell, indexed by location.
+    pub store: Vec<Value>,
+    /// How many recorded events were matched.
+    pub events_replayed: usize,
+}
+
+/// The first point where a replay disagreed with the trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the event among the replayed ones.
+    pub position: usize,
+    /// The recorded event, or `None` if the trace had ended.
+    pub expected: Option<EventData>,
+    /// The event the replay produced, or `None` if the program finished
+    /// before producing it.
+    pub found: Option<EventData>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "event {}: expected {:?}, found {:?}",
+            self.position, self.expected, self.found
+        )
+    }
+}
+
+/// Re-executes `graph` and checks the events it produces against `trace`.
+///
+/// Only effect events are compared, since those are what the interpreter is
+/// instrumented to record; the trace is expected to come from a single
+/// thread. Effects are not carried out again: each returns unit, as the
+/// built-in handlers do.
+pub fn replay(trace: &crate::TraceStream, graph: &AsgGraph) -> Result<StateSnapshot> {
+    let recorded = trace.filter_events(EventCategory::EffectPerformed)?;
+    let handler = ReplayHandler {
+        recorded: recorded.into_iter().map(|event| event.data).collect(),
+        position: 0,
+        divergence: None,
+    };
+    let mut interpreter = Interpreter::with_handler(graph, handler);
+    let result = interpreter.run();
+    let handler = interpreter.handler();
+    if let Some(divergence) = &handler.divergence {
+        return Err(DebuggerError::Diverged(Box::new(divergence.clone())));
+    }
+    let value = result.map_err(DebuggerError::ReplayFailed)?;
+    if let Some(expected) = handler.recorded.get(handler.position) {
+        return Err(DebuggerError::Diverged(Box::new(Divergence {
+            position: handler.position,
+            expected: Some(expected.clone()),
+            found: None,
+        })));
+    }
+    Ok(StateSnapshot {
+        value,
+        store: interpreter.store().to_vec(),
+        events_replayed: handler.position,
+    })
+}
+
+/// Matches performed effects against the recorded ones.
+struct ReplayHandler {
+    recorded: Vec<EventData>,
+    position: usize,
+    divergence: Option<Divergence>,
+}
+
+impl EffectHandler for ReplayHandler {
+    fn perform(
+        &mut self,
+        node_id: u64,
+        effect: &str,
+        payload: &Value,
+    ) -> synapse_runtime::Result<Value> {
+        let found = EventData::EffectPerformed(EffectData {
+            effect_name: effect.to_string(),
+            argument: payload.to_string(),
+            node_id,
+        });
+        let expected = self.recorded.get(self.position);
+        if expected != Some(&found) {
+            self.divergence = Some(Divergence {
+                position: self.position,
+                expected: expected.cloned(),
+                found: Some(found),
+            });
+            return Err(EvalError::Effect {
+                node_id,
+                effect: effect.to_string(),
+                reason: "replay diverged from the trace".to_string(),
+            });
+        }
+        self.position += 1;
+        Ok(Value::Unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instrument::TracingHandler;
+    use crate::manager::TraceManager;
+    use crate::trace::TraceStream;
+    use synapse_runtime::RecordingHandler;
+
+    /// Logs 7, bumps a counter from 1 to 42, logs it and returns it.
+    const PROGRAM: &str = "((a: Unit) =>
+        ((r: Ref Int) =>
+            ((u: Unit) => ((v: Unit) => !r)(perform('IO', !r)))(r := !r + 41)
+        )(ref 1)
+    )(perform('State', 7))";
+
+    fn record(graph: &AsgGraph) -> (TraceStream, Value) {
+        let manager = TraceManager::new();
+        manager.start_trace();
+        let handler = TracingHandler::new(
+            manager.current_thread_context(),
+            RecordingHandler::default(),
+        );
+        let value = Interpreter::with_handler(graph, handler).run().unwrap();
+        (manager.stop_trace().unwrap(), value)
+    }
+
+    #[test]
+    fn replaying_a_deterministic_program_matches_its_trace() {
+        let graph = parser_core::parse_str(PROGRAM).unwrap();
+        let (trace, value) = record(&graph);
+
+        let snapshot = replay(&trace, &graph).unwrap();
+        assert_eq!(snapshot.events_replayed, 2);
+        assert_eq!(snapshot.value, value);
+        assert_eq!(snapshot.value, Value::Int(42));
+        assert_eq!(snapshot.store, vec![Value::Int(42)]);
+    }
+
+    #[test]
+    fn first_divergence_is_reported() {
+        let graph = parser_core::parse_str(PROGRAM).unwrap();
+        let (trace, _) = record(&graph);
+
+        let other = parser_core::parse_str("perform('State', 8)").unwrap();
+        let Err(DebuggerError::Diverged(divergence)) = replay(&trace, &other) else {
+            panic!("expected a divergence");
+        };
+        assert_eq!(divergence.position, 0);
+        assert!(matches!(
+            divergence.found,
+            Some(EventData::EffectPerformed(EffectData { ref argument, .. })) if argument == "8"
+        ));
+
+        let shorter = parser_core::parse_str("1").unwrap();
+        let Err(DebuggerError::Diverged(divergence)) = replay(&trace, &shorter) else {
+            panic!("expected a divergence");
+        };
+        assert_eq!((divergence.position, divergence.found), (0, None));
+    }
+}
@@ -0,0 +1,316 @@
+//! Persistent backends for traces.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{DebuggerError, Result};
+use crate::event::{EventCategory, EventId, TraceEvent, TraceQuery};
+
+/// Somewhere a [`TraceStream`](crate::TraceStream) can put events so they
+/// need not stay in memory. Events are stored in id order without gaps.
+pub trait TraceStorage: Send {
+    /// Appends `event`, which may be buffered until [`TraceStorage::flush`].
+    fn store_event(&mut self, event: &TraceEvent) -> Result<()>;
+
+    /// Appends consecutive `events`. Backends should override this when a
+    /// single write is cheaper than one per event.
+    fn store_events(&mut self, events: &[TraceEvent]) -> Result<()> {
+        events.iter().try_for_each(|event| self.store_event(event))
+    }
+
+    /// Makes every stored event durable and readable.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Reads up to `limit` flushed events starting with id `start`.
+    fn read_events(&self, start: EventId, limit: usize) -> Result<Vec<TraceEvent>>;
+
+    /// Stored events matching `query`, in id order. Pending events are
+    /// flushed first.
+    fn query_events(&mut self, query: &TraceQuery) -> Result<Vec<TraceEvent>> {
+        let _ = query;
+        Err(DebuggerError::RecordingFailed(
+            "querying not supported".to_string(),
+        ))
+    }
+}
+
+/// Stores events as JSON lines in a file.
+pub struct FileTraceStorage {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    /// Byte offset of each event's line, indexed by event id.
+    offsets: Vec<u64>,
+    written: u64,
+    /// Built by the first query, then kept up to date as events are stored.
+    index: Option<QueryIndex>,
+}
+
+/// Line offsets of the stored events, by the criteria queries narrow on.
+#[derive(Debug, Default)]
+struct QueryIndex {
+    all: Vec<u64>,
+    by_category: HashMap<EventCategory, Vec<u64>>,
+    by_thread: HashMap<u64, Vec<u64>>,
+}
+
+impl QueryIndex {
+    fn add(&mut self, offset: u64, event: &TraceEvent) {
+        self.all.push(offset);
+        self.by_category
+            .entry(event.category())
+            .or_default()
+            .push(offset);
+        self.by_thread
+            .entry(event.thread_id)
+            .or_default()
+            .push(offset);
+    }
+
+    /// Offsets of the events that can match `query`, in file order.
+    fn candidates(&self, query: &TraceQuery) -> Vec<u64> {
+        let by_category = query.category.map(|category| {
+            self.by_category
+                .get(&category)
+                .map_or(&[][..], Vec::as_slice)
+        });
+        let by_thread = query
+            .thread_id
+            .map(|thread| self.by_thread.get(&thread).map_or(&[][..], Vec::as_slice));
+        match (by_category, by_thread) {
+            (Some(category), Some(thread)) => intersect(category, thread),
+            (Some(offsets), None) | (None, Some(offsets)) => offsets.to_vec(),
+            (None, None) => self.all.clone(),
+        }
+    }
+}
+
+/// The offsets present in both ascending lists.
+fn intersect(left: &[u64], right: &[u64]) -> Vec<u64> {
+    let mut both = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        match left[i].cmp(&right[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                both.push(left[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    both
+}
+
+impl FileTraceStorage {
+    /// Creates (or truncates) the file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path).map_err(|e| io_failure(&path, e))?;
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+            offsets: Vec::new(),
+            written: 0,
+            index: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Streams the file once to index every event in it. Lines that do not
+    /// parse, such as one cut short by a crash, are left out.
+    fn build_index(&self) -> Result<QueryIndex> {
+        let file = File::open(&self.path).map_err(|e| io_failure(&self.path, e))?;
+        let mut reader = BufReader::new(file);
+        let mut index = QueryIndex::default();
+        let mut offset = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = reader
+                .read_line(&mut line)
+                .map_err(|e| io_failure(&self.path, e))?;
+            if read == 0 {
+                return Ok(index);
+            }
+            if let Ok(event) = serde_json::from_str::<TraceEvent>(&line) {
+                index.add(offset, &event);
+            }
+            offset += read as u64;
+        }
+    }
+}
+
+impl TraceStorage for FileTraceStorage {
+    fn store_event(&mut self, event: &TraceEvent) -> Result<()> {
+        self.store_events(std::slice::from_ref(event))
+    }
+
+    /// Serializes the whole batch into one buffer and writes it at once.
+    fn store_events(&mut self, events: &[TraceEvent]) -> Result<()> {
+        let mut batch = String::new();
+        let mut offsets = Vec::with_capacity(events.len());
+        for (index, event) in events.iter().enumerate() {
+            let expected = (self.offsets.len() + index) as EventId;
+            if event.id != expected {
+                return Err(DebuggerError::RecordingFailed(format!(
+                    "expected event {} but got event {}",
+                    expected, event.id
+                )));
+            }
+            offsets.push(self.written + batch.len() as u64);
+            batch.push_str(
+                &serde_json::to_string(event)
+                    .map_err(|e| DebuggerError::RecordingFailed(e.to_string()))?,
+            );
+            batch.push('\n');
+        }
+        self.writer
+            .write_all(batch.as_bytes())
+            .map_err(|e| io_failure(&self.path, e))?;
+        if let Some(index) = &mut self.index {
+            for (&offset, event) in offsets.iter().zip(events) {
+                index.add(offset, event);
+            }
+        }
+        self.offsets.extend(offsets);
+        self.written += batch.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush().map_err(|e| io_failure(&self.path, e))
+    }
+
+    fn read_events(&self, start: EventId, limit: usize) -> Result<Vec<TraceEvent>> {
+        let Some(&offset) = self.offsets.get(start as usize) else {
+            return Err(DebuggerError::InvalidEventId(start));
+        };
+        let mut file = File::open(&self.path).map_err(|e| io_failure(&self.path, e))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| io_failure(&self.path, e))?;
+        BufReader::new(file)
+            .lines()
+            .take(limit.min(self.offsets.len() - start as usize))
+            .map(|line| {
+                let line = line.map_err(|e| io_failure(&self.path, e))?;
+                serde_json::from_str(&line).map_err(|e| {
+                    DebuggerError::RecordingFailed(format!(
+                        "corrupt event in {}: {}",
+                        self.path.display(),
+                        e
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Answers from an index of line offsets, so only lines that can match
+    /// are read. Lines that do not parse are skipped.
+    fn query_events(&mut self, query: &TraceQuery) -> Result<Vec<TraceEvent>> {
+        self.flush()?;
+        if self.index.is_none() {
+            self.index = Some(self.build_index()?);
+        }
+        let candidates = self
+            .index
+            .as_ref()
+            .map_or_else(Vec::new, |index| index.candidates(query));
+
+        let file = File::open(&self.path).map_err(|e| io_failure(&self.path, e))?;
+        let mut reader = BufReader::new(file);
+        let mut position = 0;
+        let mut line = String::new();
+        let mut events = Vec::new();
+        for offset in candidates {
+            reader
+                .seek_relative((offset - position) as i64)
+                .map_err(|e| io_failure(&self.path, e))?;
+            line.clear();
+            let read = reader
+                .read_line(&mut line)
+                .map_err(|e| io_failure(&self.path, e))?;
+            position = offset + read as u64;
+            if let Ok(event) = serde_json::from_str::<TraceEvent>(&line)
+                && query.matches(&event)
+            {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+}
+
+fn io_failure(path: &Path, error: std::io::Error) -> DebuggerError {
+    DebuggerError::RecordingFailed(format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventData;
+
+    fn event(id: EventId, thread_id: u64, data: EventData) -> TraceEvent {
+        TraceEvent {
+            id,
+            logical_time: id,
+            thread_id,
+            cause: None,
+            data,
+        }
+    }
+
+    #[test]
+    fn queries_use_the_index_and_skip_a_torn_last_line() {
+        let path = std::env::temp_dir().join(format!("synapse_query_{}.jsonl", std::process::id()));
+        let mut storage = FileTraceStorage::create(&path).unwrap();
+        let events: Vec<_> = (0..30)
+            .map(|id| {
+                let data = if id % 2 == 0 {
+                    EventData::MemoryAllocation {
+                        address: id,
+                        size: 8,
+                    }
+                } else {
+                    EventData::MemoryDeallocation { address: id }
+                };
+                event(id, id % 3, data)
+            })
+            .collect();
+        storage.store_events(&events[..20]).unwrap();
+
+        let query = TraceQuery::new()
+            .with_category(EventCategory::MemoryAllocation)
+            .with_thread(1);
+        let ids = |found: Vec<TraceEvent>| found.iter().map(|event| event.id).collect::<Vec<_>>();
+        assert_eq!(ids(storage.query_events(&query).unwrap()), vec![4, 10, 16]);
+
+        // Events stored after the index was built are indexed as they arrive.
+        storage.store_events(&events[20..]).unwrap();
+        assert_eq!(
+            ids(storage.query_events(&query).unwrap()),
+            vec![4, 10, 16, 22, 28]
+        );
+        assert_eq!(
+            ids(storage
+                .query_events(&TraceQuery::new().with_thread(2).with_time_range(10, 20))
+                .unwrap()),
+            vec![11, 14, 17, 20]
+        );
+
+        // A crash mid-write leaves a partial line; a fresh index ignores it.
+        storage
+            .writer
+            .write_all(b"{\"id\":30,\"logical_ti")
+            .unwrap();
+        storage.index = None;
+        assert_eq!(storage.query_events(&TraceQuery::new()).unwrap().len(), 30);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,433 @@
+//! Event traces, held in memory and optionally backed by storage.
+//!
+//! Without storage a [`TraceStream`] keeps every event in memory. With a
+//! [`TraceStorage`] attached, events are written to it on
+//! [`TraceStream::flush`], and once a memory cap is set the oldest flushed
+//! events are evicted from memory. Queries go through
+//! [`TraceStream::iter_events`], which reads evicted events back from storage
+//! in chunks, so they answer the same either way.
+//!
+//! A trace made with [`TraceStream::with_capacity`] has no storage and acts
+//! as a ring buffer instead: the oldest events are dropped for good, and
+//! queries answer from what remains.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Range;
+
+use crate::error::{DebuggerError, Result};
+use crate::event::{EventCategory, EventData, EventId, TraceEvent, TraceEventSpec};
+use crate::storage::TraceStorage;
+
+/// How many evicted events [`TraceStream::iter_events`] reads at a time.
+const READ_CHUNK: usize = 256;
+
+/// The events of one trace in recording order.
+#[derive(Default)]
+pub struct TraceStream {
+    /// Events still in memory; the first has id `first_resident`.
+    events: VecDeque<TraceEvent>,
+    first_resident: EventId,
+    next_id: EventId,
+    storage: Option<Box<dyn TraceStorage>>,
+    /// Events with smaller ids have been written to storage.
+    stored_until: EventId,
+    memory_cap: Option<usize>,
+    /// Ring-buffer size of a trace without storage.
+    capacity: Option<usize>,
+    /// Events dropped by the ring buffer.
+    evicted: u64,
+}
+
+impl TraceStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a trace that writes to `storage` and keeps at most
+    /// `memory_cap` events in memory once they have been flushed.
+    pub fn with_storage(storage: Box<dyn TraceStorage>, memory_cap: usize) -> Self {
+        Self {
+            storage: Some(storage),
+            memory_cap: Some(memory_cap),
+            ..Self::default()
+        }
+    }
+
+    /// Creates a trace that keeps only the latest `max_events` events,
+    /// dropping the oldest to make room for each new one. At least one
+    /// event is always kept.
+    pub fn with_capacity(max_events: usize) -> Self {
+        let max_events = max_events.max(1);
+        Self {
+            events: VecDeque::with_capacity(max_events),
+            capacity: Some(max_events),
+            ..Self::default()
+        }
+    }
+
+    /// Appends an event and returns its id.
+    ///
+    /// When the memory cap is exceeded the trace flushes to storage. If that
+    /// fails the events simply stay in memory; the error is reported by the
+    /// next explicit [`TraceStream::flush`].
+    pub fn record_event(
+        &mut self,
+        thread_id: u64,
+        cause: Option<EventId>,
+        data: EventData,
+    ) -> EventId {
+        let id = self.push(thread_id, cause, data);
+        self.flush_over_cap();
+        id
+    }
+
+    /// Appends several events in order and returns their ids. The result is
+    /// the same as recording them one by one, but the memory cap is checked
+    /// once, so storage receives the batch in a single write.
+    pub fn record_events_batch(&mut self, specs: Vec<TraceEventSpec>) -> Range<EventId> {
+        let start = self.next_id;
+        let reserve = match self.capacity {
+            Some(capacity) => specs.len().min(capacity),
+            None => specs.len(),
+        };
+        self.events.reserve(reserve);
+        for spec in specs {
+            self.push(spec.thread_id, spec.cause, spec.data);
+        }
+        self.flush_over_cap();
+        start..self.next_id
+    }
+
+    fn push(&mut self, thread_id: u64, cause: Option<EventId>, data: EventData) -> EventId {
+        if self.capacity == Some(self.events.len()) {
+            self.events.pop_front();
+            self.first_resident += 1;
+            self.evicted += 1;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.events.push_back(TraceEvent {
+            id,
+            logical_time: id,
+            thread_id,
+            cause,
+            data,
+        });
+        id
+    }
+
+    fn flush_over_cap(&mut self) {
+        if self.memory_cap.is_some_and(|cap| self.events.len() > cap) {
+            let _ = self.flush();
+        }
+    }
+
+    /// Writes unstored events to storage, then evicts flushed events beyond
+    /// the memory cap. Does nothing without storage.
+    pub fn flush(&mut self) -> Result<()> {
+        let Some(storage) = self.storage.as_mut() else {
+            return Ok(());
+        };
+        let unstored = (self.stored_until - self.first_resident) as usize;
+        storage.store_events(&self.events.make_contiguous()[unstored..])?;
+        self.stored_until = self.next_id;
+        storage.flush()?;
+
+        if let Some(cap) = self.memory_cap
+            && self.events.len() > cap
+        {
+            let evicted = self.events.len() - cap;
+            self.events.drain(..evicted);
+            self.first_resident += evicted as EventId;
+        }
+        Ok(())
+    }
+
+    /// Looks up an event, reading it back from storage if it was evicted.
+    /// Events dropped by a ring buffer are reported as unknown ids.
+    pub fn get_event(&self, id: EventId) -> Result<TraceEvent> {
+        if id >= self.next_id {
+            return Err(DebuggerError::InvalidEventId(id));
+        }
+        if id >= self.first_resident {
+            return Ok(self.events[(id - self.first_resident) as usize].clone());
+        }
+        self.read_stored(id, 1)?
+            .pop()
+            .ok_or(DebuggerError::InvalidEventId(id))
+    }
+
+    /// Every event in recording order, evicted ones included.
+    pub fn iter_events(&self) -> EventIter<'_> {
+        EventIter {
+            trace: self,
+            next_id: self.first_retained(),
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// The events currently held in memory.
+    pub fn resident_events(&self) -> impl ExactSizeIterator<Item = &TraceEvent> {
+        self.events.iter()
+    }
+
+    /// Number of events a ring buffer has dropped. Events evicted to
+    /// storage are not counted, since they can still be read back.
+    pub fn evicted_count(&self) -> u64 {
+        self.evicted
+    }
+
+    /// The id of the oldest event that can still be looked up.
+    fn first_retained(&self) -> EventId {
+        if self.storage.is_some() {
+            0
+        } else {
+            self.first_resident
+        }
+    }
+
+    /// Number of events recorded, evicted ones included.
+    pub fn len(&self) -> usize {
+        self.next_id as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next_id == 0
+    }
+
+    /// Events of the given category, in recording order.
+    pub fn filter_events(&self, category: EventCategory) -> Result<Vec<TraceEvent>> {
+        self.collect_where(|event| event.category() == category)
+    }
+
+    /// Events whose logical time lies in `start..=end`.
+    pub fn events_in_time_range(&self, start: u64, end: u64) -> Result<Vec<TraceEvent>> {
+        self.collect_where(|event| (start..=end).contains(&event.logical_time))
+    }
+
+    /// The chain of causes leading to `id`, oldest first, ending with `id`.
+    /// If a ring buffer has dropped part of the chain, it starts at the
+    /// earliest ancestor still retained.
+    pub fn causal_history(&self, id: EventId) -> Result<Vec<TraceEvent>> {
+        let mut history = vec![self.get_event(id)?];
+        while let Some(cause) = history.last().and_then(|event| event.cause) {
+            if cause < self.first_retained() {
+                break;
+            }
+            history.push(self.get_event(cause)?);
+        }
+        history.reverse();
+        Ok(history)
+    }
+
+    fn collect_where(&self, keep: impl Fn(&TraceEvent) -> bool) -> Result<Vec<TraceEvent>> {
+        self.iter_events()
+            .filter(|event| event.as_ref().map_or(true, &keep))
+            .collect()
+    }
+
+    fn read_stored(&self, start: EventId, limit: usize) -> Result<Vec<TraceEvent>> {
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or(DebuggerError::InvalidEventId(start))?;
+        storage.read_events(start, limit)
+    }
+}
+
+impl fmt::Debug for TraceStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceStream")
+            .field("len", &self.len())
+            .field("resident", &self.events.len())
+            .field("evicted", &self.evicted)
+            .field("has_storage", &self.storage.is_some())
+            .finish()
+    }
+}
+
+/// Iterator returned by [`TraceStream::iter_events`].
+pub struct EventIter<'a> {
+    trace: &'a TraceStream,
+    next_id: EventId,
+    /// Events read ahead from storage.
+    buffer: VecDeque<TraceEvent>,
+}
+
+impl Iterator for EventIter<'_> {
+    type Item = Result<TraceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let trace = self.trace;
+        if self.next_id >= trace.next_id {
+            return None;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        if id >= trace.first_resident {
+            return Some(Ok(
+                trace.events[(id - trace.first_resident) as usize].clone()
+            ));
+        }
+        if self.buffer.is_empty() {
+            let limit = READ_CHUNK.min((trace.first_resident - id) as usize);
+            match trace.read_stored(id, limit) {
+                Ok(events) => self.buffer.extend(events),
+                Err(error) => {
+                    // Stop after reporting the error rather than repeat it.
+                    self.next_id = trace.next_id;
+                    return Some(Err(error));
+                }
+            }
+        }
+        self.buffer
+            .pop_front()
+            .map(Ok)
+            .or(Some(Err(DebuggerError::InvalidEventId(id))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileTraceStorage;
+
+    fn call(node_id: u64) -> EventData {
+        EventData::FunctionCall {
+            function: "f".to_string(),
+            node_id,
+        }
+    }
+
+    #[test]
+    fn causal_history_follows_a_thread() {
+        let mut trace = TraceStream::new();
+        let first = trace.record_event(1, None, call(3));
+        trace.record_event(2, None, EventData::MemoryDeallocation { address: 8 });
+        let ret = trace.record_event(
+            1,
+            Some(first),
+            EventData::FunctionReturn {
+                function: "f".to_string(),
+                value: "()".to_string(),
+            },
+        );
+
+        let history: Vec<_> = trace
+            .causal_history(ret)
+            .unwrap()
+            .iter()
+            .map(|event| event.id)
+            .collect();
+        assert_eq!(history, vec![first, ret]);
+        assert_eq!(
+            trace
+                .filter_events(EventCategory::MemoryDeallocation)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(trace.events_in_time_range(1, 5).unwrap().len(), 2);
+        assert_eq!(trace.get_event(9), Err(DebuggerError::InvalidEventId(9)));
+    }
+
+    #[test]
+    fn batch_recording_matches_sequential_recording() {
+        let specs: Vec<_> = (0..1000)
+            .map(|node_id| TraceEventSpec {
+                thread_id: node_id % 3,
+                cause: node_id.checked_sub(1),
+                data: call(node_id),
+            })
+            .collect();
+
+        let mut sequential = TraceStream::new();
+        for spec in specs.clone() {
+            sequential.record_event(spec.thread_id, spec.cause, spec.data);
+        }
+        let path = std::env::temp_dir().join(format!("synapse_batch_{}.jsonl", std::process::id()));
+        let storage = FileTraceStorage::create(&path).unwrap();
+        let mut batched = TraceStream::with_storage(Box::new(storage), 100);
+        batched.record_event(0, None, call(0));
+        assert_eq!(batched.record_events_batch(specs[1..].to_vec()), 1..1000);
+
+        assert_eq!(batched.resident_events().len(), 100);
+        let batched_events: Vec<_> = batched.iter_events().map(Result::unwrap).collect();
+        let sequential_events: Vec<_> = sequential.iter_events().map(Result::unwrap).collect();
+        assert_eq!(batched_events, sequential_events);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ring_buffer_drops_the_oldest_events() {
+        let mut trace = TraceStream::with_capacity(3);
+        let mut previous = None;
+        for node_id in 0..10 {
+            previous = Some(trace.record_event(1, previous, call(node_id)));
+        }
+
+        assert_eq!(trace.len(), 10);
+        assert_eq!(trace.evicted_count(), 7);
+        assert_eq!(trace.resident_events().len(), 3);
+        assert_eq!(trace.get_event(6), Err(DebuggerError::InvalidEventId(6)));
+        assert_eq!(trace.get_event(7).unwrap().data, call(7));
+
+        let history: Vec<_> = trace
+            .causal_history(9)
+            .unwrap()
+            .iter()
+            .map(|event| event.id)
+            .collect();
+        assert_eq!(history, vec![7, 8, 9]);
+        let ids: Vec<_> = trace.iter_events().map(|event| event.unwrap().id).collect();
+        assert_eq!(ids, vec![7, 8, 9]);
+        assert_eq!(trace.events_in_time_range(0, 8).unwrap().len(), 2);
+        assert!(trace.events_in_time_range(0, 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn capped_trace_answers_queries_from_file_storage() {
+        let path = std::env::temp_dir().join(format!("synapse_trace_{}.jsonl", std::process::id()));
+        let storage = FileTraceStorage::create(&path).unwrap();
+        let mut trace = TraceStream::with_storage(Box::new(storage), 4);
+
+        let mut previous = None;
+        for node_id in 0..600 {
+            let data = if node_id % 3 == 0 {
+                EventData::MemoryAllocation {
+                    address: node_id,
+                    size: 8,
+                }
+            } else {
+                call(node_id)
+            };
+            previous = Some(trace.record_event(1, previous, data));
+        }
+        trace.flush().unwrap();
+
+        assert_eq!(trace.resident_events().len(), 4);
+        assert_eq!(trace.len(), 600);
+        assert_eq!(
+            trace.get_event(0).unwrap().data,
+            EventData::MemoryAllocation {
+                address: 0,
+                size: 8
+            }
+        );
+        assert_eq!(trace.get_event(598).unwrap().data, call(598));
+        let ids: Vec<_> = trace.iter_events().map(|event| event.unwrap().id).collect();
+        assert_eq!(ids, (0..600).collect::<Vec<_>>());
+        assert_eq!(
+            trace
+                .filter_events(EventCategory::MemoryAllocation)
+                .unwrap()
+                .len(),
+            200
+        );
+        assert_eq!(trace.causal_history(599).unwrap().len(), 600);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,24 @@
+//! What the server offers, as announced in its `initialize` response.
+
+use crate::document::{TEXT_DOCUMENT_SYNC, TextDocumentSyncKind};
+
+/// The features the server announces to the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerCapabilities {
+    pub text_document_sync: TextDocumentSyncKind,
+    pub hover_provider: bool,
+    pub definition_provider: bool,
+    pub completion_provider: bool,
+    pub code_action_provider: bool,
+}
+
+/// The capabilities of this server.
+pub fn server_capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: TEXT_DOCUMENT_SYNC,
+        hover_provider: true,
+        definition_provider: true,
+        completion_provider: true,
+        code_action_provider: true,
+    }
+}
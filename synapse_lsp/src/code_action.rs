@@ -0,0 +1,101 @@
+//! Code actions: quick fixes offered for a range of a document.
+//!
+//! The only fix so far silences the linter's `L006 UnusedBinding` warning
+//! by renaming an unused parameter or `let` binding to `_name`. Unused
+//! binders are found with [`asg_core::ReferenceIndex`], as the linter finds
+//! them.
+
+use asg_core::{AsgGraph, NodeContent};
+
+use crate::diagnostics::{LspRange, lsp_range};
+
+/// The kind of every action offered here, as named in the protocol.
+pub const QUICK_FIX: &str = "quickfix";
+
+/// Replacement of the text in `range`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: LspRange,
+    pub new_text: String,
+}
+
+/// A fix the client can apply: a title to show and the edit it makes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeAction {
+    pub title: String,
+    pub kind: &'static str,
+    /// The `L0xx` lint the action silences.
+    pub code: &'static str,
+    pub edit: TextEdit,
+}
+
+/// Renames of the unused binders of `graph` whose span touches `range`, in
+/// document order. Binders already starting with `_` are left alone.
+pub fn unused_binding_fixes(source: &str, graph: &AsgGraph, range: LspRange) -> Vec<CodeAction> {
+    let index = graph.reference_index();
+    let mut actions: Vec<CodeAction> = index
+        .unused_lambdas()
+        .into_iter()
+        .filter_map(|lambda_id| {
+            let NodeContent::TermLambda(lambda) = &graph.get_node(lambda_id)?.content else {
+                return None;
+            };
+            let binder_id = lambda.binder_variable_node_id;
+            let NodeContent::TermVariable(binder) = &graph.get_node(binder_id)?.content else {
+                return None;
+            };
+            if binder.name.starts_with('_') {
+                return None;
+            }
+            let binder_range = lsp_range(source, graph.source_location(binder_id)?);
+            if binder_range.end < range.start || range.end < binder_range.start {
+                return None;
+            }
+            let new_text = format!("_{}", binder.name);
+            Some(CodeAction {
+                title: format!("rename to `{}`", new_text),
+                kind: QUICK_FIX,
+                code: "L006",
+                edit: TextEdit {
+                    range: binder_range,
+                    new_text,
+                },
+            })
+        })
+        .collect();
+    actions.sort_by_key(|action| action.edit.range);
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::LspPosition;
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> LspRange {
+        let at = |(line, character)| LspPosition { line, character };
+        LspRange {
+            start: at(start),
+            end: at(end),
+        }
+    }
+
+    #[test]
+    fn unused_binders_in_range_are_offered_a_rename() {
+        let source = "let y = 1 in\n(x) => (z) => z";
+        let graph = parser_core::parse_str(source).unwrap();
+
+        let everything = unused_binding_fixes(source, &graph, range((0, 0), (2, 0)));
+        let titles: Vec<_> = everything.iter().map(|action| &action.title).collect();
+        assert_eq!(titles, ["rename to `_y`", "rename to `_x`"]);
+        assert_eq!(everything[1].edit.range, range((1, 1), (1, 2)));
+        assert_eq!(everything[1].edit.new_text, "_x");
+
+        let on_x = unused_binding_fixes(source, &graph, range((1, 1), (1, 1)));
+        assert_eq!(on_x, everything[1..]);
+        assert_eq!(
+            unused_binding_fixes(source, &graph, range((1, 8), (1, 15))),
+            []
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Completion candidates at a cursor position.
+//!
+//! Outside a `perform(` call the candidates are the variables in scope,
+//! innermost binder first and annotated with their inferred type, followed
+//! by the keywords. Directly after `perform(` only effect names are offered,
+//! quoted as the call expects.
+
+use std::collections::HashSet;
+
+use asg_core::{AsgGraph, NodeContent, SourceLocation};
+use parser_core::lexer::Position;
+use type_checker_l1::{TypeCheckMap, check_graph};
+
+use crate::hover::pretty_type;
+
+/// Keywords that may start or continue an expression.
+pub const KEYWORDS: &[&str] = &[
+    "true", "false", "ref", "not", "perform", "with", "let", "letrec", "in", "if", "then", "else",
+];
+
+/// What a completion candidate stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Variable,
+    Effect,
+}
+
+/// One completion candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    /// The text inserted when the candidate is accepted.
+    pub label: String,
+    pub kind: CompletionKind,
+    /// Shown beside the label; for a variable, its inferred type.
+    pub detail: Option<String>,
+}
+
+impl CompletionItem {
+    fn new(label: impl Into<String>, kind: CompletionKind) -> Self {
+        Self {
+            label: label.into(),
+            kind,
+            detail: None,
+        }
+    }
+}
+
+/// Completion candidates for the cursor at `position` in `source`.
+///
+/// `effects` are the effect names to offer, typically
+/// [`asg_core::effects::STANDARD_EFFECTS`] plus the runtime's
+/// `EffectSystem::registered_effects`. Variables are only offered when `source`
+/// parses, and carry their type only when it also type checks; otherwise
+/// the keywords still are offered.
+pub fn complete(source: &str, position: Position, effects: &[&str]) -> Vec<CompletionItem> {
+    let graph = parser_core::parse_str(source).ok();
+    let types = graph
+        .as_ref()
+        .and_then(|graph| check_graph(graph).ok())
+        .unwrap_or_default();
+    complete_checked(source, graph.as_ref(), &types, position, effects)
+}
+
+/// [`complete`] against a graph already parsed from `source` and the types
+/// found by checking it.
+pub(crate) fn complete_checked(
+    source: &str,
+    graph: Option<&AsgGraph>,
+    types: &TypeCheckMap,
+    position: Position,
+    effects: &[&str],
+) -> Vec<CompletionItem> {
+    if in_perform_effect(source, position) {
+        return effects
+            .iter()
+            .map(|effect| CompletionItem::new(format!("'{}'", effect), CompletionKind::Effect))
+            .collect();
+    }
+    let mut items: Vec<_> = graph
+        .map(|graph| binders_in_scope(graph, position))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, binder)| CompletionItem {
+            detail: types.get(&binder).map(pretty_type),
+            ..CompletionItem::new(name, CompletionKind::Variable)
+        })
+        .collect();
+    items.extend(
+        KEYWORDS
+            .iter()
+            .map(|keyword| CompletionItem::new(*keyword, CompletionKind::Keyword)),
+    );
+    items
+}
+
+/// Names bound by the lambdas enclosing `position`, innermost first, with
+/// shadowed names listed once.
+pub fn variables_in_scope(graph: &AsgGraph, position: Position) -> Vec<String> {
+    binders_in_scope(graph, position)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// The names in scope at `position`, as [`variables_in_scope`], each with
+/// the id of the binder node it refers to.
+fn binders_in_scope(graph: &AsgGraph, position: Position) -> Vec<(String, u64)> {
+    let mut walk = ScopeWalk {
+        graph,
+        position,
+        seen: HashSet::new(),
+        path: Vec::new(),
+        deepest: Vec::new(),
+    };
+    if let Some(root) = graph.root() {
+        walk.visit(root);
+    }
+    let enclosing = walk.deepest;
+
+    let mut binders: Vec<(String, u64)> = Vec::new();
+    for lambda in enclosing.into_iter().rev() {
+        let Some(NodeContent::TermLambda(lambda)) = graph.get_node(lambda).map(|n| &n.content)
+        else {
+            continue;
+        };
+        let binder = lambda.binder_variable_node_id;
+        if let Some(NodeContent::TermVariable(var)) = graph.get_node(binder).map(|n| &n.content)
+            && !binders.iter().any(|(name, _)| *name == var.name)
+        {
+            binders.push((var.name.clone(), binder));
+        }
+    }
+    binders
+}
+
+/// A walk from the root collecting the lambdas whose span contains
+/// `position`. Span alone cannot order them, as a `let` shares its span
+/// with its body, but the path down to the cursor does.
+struct ScopeWalk<'g> {
+    graph: &'g AsgGraph,
+    position: Position,
+    seen: HashSet<u64>,
+    /// The enclosing lambdas above the current node, outermost first.
+    path: Vec<u64>,
+    /// The longest such path found so far.
+    deepest: Vec<u64>,
+}
+
+impl ScopeWalk<'_> {
+    fn visit(&mut self, node_id: u64) {
+        if !self.seen.insert(node_id) {
+            return;
+        }
+        let Some(node) = self.graph.get_node(node_id) else {
+            return;
+        };
+        let encloses = matches!(node.content, NodeContent::TermLambda(_))
+            && self
+                .graph
+                .source_location(node_id)
+                .is_some_and(|location| contains(location, self.position));
+        if encloses {
+            self.path.push(node_id);
+            if self.path.len() > self.deepest.len() {
+                self.deepest.clone_from(&self.path);
+            }
+        }
+        for child in node.content.child_ids() {
+            self.visit(child);
+        }
+        if encloses {
+            self.path.pop();
+        }
+    }
+}
+
+/// Whether `position` lies within `location`; a cursor just past the end
+/// still counts, since that is where typing continues.
+fn contains(location: &SourceLocation, position: Position) -> bool {
+    let start = (location.start_line, location.start_col);
+    let end = (location.end_line, location.end_col);
+    let cursor = (position.line, position.column);
+    start <= cursor && cursor <= end
+}
+
+/// Whether the cursor sits where `perform(` expects its effect name,
+/// possibly after an opening quote and part of the name.
+fn in_perform_effect(source: &str, position: Position) -> bool {
+    let Some(line) = source
+        .lines()
+        .nth((position.line as usize).saturating_sub(1))
+    else {
+        return false;
+    };
+    let prefix: String = line
+        .chars()
+        .take((position.column as usize).saturating_sub(1))
+        .collect();
+    let prefix = prefix.trim_end_matches(|c: char| c.is_alphanumeric() || c == ':' || c == '_');
+    let prefix = prefix.strip_suffix(['\'', '"']).unwrap_or(prefix);
+    prefix.trim_end().ends_with("perform(")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Document;
+
+    fn at(line: u32, column: u32) -> Position {
+        Position { line, column }
+    }
+
+    fn labels(items: &[CompletionItem], kind: CompletionKind) -> Vec<&str> {
+        items
+            .iter()
+            .filter(|item| item.kind == kind)
+            .map(|item| item.label.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn lambda_body_offers_the_parameter() {
+        let source = "(count: Int) =>\n  count + 1";
+        let items = complete(source, at(2, 3), &[]);
+        assert_eq!(labels(&items, CompletionKind::Variable), vec!["count"]);
+        assert_eq!(labels(&items, CompletionKind::Keyword), KEYWORDS);
+        // Variables come first, with their type.
+        assert_eq!(items[0].label, "count");
+        assert_eq!(items[0].detail.as_deref(), Some("Int"));
+
+        // Outside the lambda the parameter is not in scope.
+        let items = complete("((x: Int) => x)(1) + 2", at(1, 21), &[]);
+        assert!(labels(&items, CompletionKind::Variable).is_empty());
+    }
+
+    #[test]
+    fn variables_carry_their_inferred_types() {
+        let document = Document::new("let id = (x) => x in\n(n) => id(n) + 1");
+        let items = document.complete(at(2, 10), &[]);
+        let typed: Vec<_> = items
+            .iter()
+            .take_while(|item| item.kind == CompletionKind::Variable)
+            .map(|item| (item.label.as_str(), item.detail.as_deref()))
+            .collect();
+        assert_eq!(
+            typed,
+            [("n", Some("Int")), ("id", Some("forall T0. T0 -> T0"))]
+        );
+
+        // Without a type check the names are still offered, untyped.
+        let items = complete("(b) => b + true", at(1, 9), &[]);
+        assert_eq!(items[0].label, "b");
+        assert_eq!(items[0].detail, None);
+    }
+
+    #[test]
+    fn outside_any_expression_only_keywords_are_offered() {
+        for source in ["", "(x) => x +"] {
+            let items = complete(source, at(1, 1), &[]);
+            assert_eq!(labels(&items, CompletionKind::Keyword), KEYWORDS);
+            assert_eq!(items.len(), KEYWORDS.len());
+        }
+    }
+
+    #[test]
+    fn inner_binders_come_first_and_shadow_outer_ones() {
+        let source = "(x)(y) => (x) => y";
+        assert_eq!(
+            variables_in_scope(&parser_core::parse_str(source).unwrap(), at(1, 18)),
+            vec!["x", "y"]
+        );
+    }
+
+    #[test]
+    fn perform_offers_quoted_effect_names() {
+        let effects = asg_core::effects::STANDARD_EFFECTS;
+        let items = complete("perform(", at(1, 9), effects);
+        assert_eq!(
+            labels(&items, CompletionKind::Effect),
+            vec!["'IO'", "'State'", "'Network'", "'Sleep'"]
+        );
+        assert_eq!(complete("perform('St", at(1, 12), effects).len(), 4);
+        assert!(
+            labels(
+                &complete("perform('IO', ", at(1, 15), effects),
+                CompletionKind::Effect
+            )
+            .is_empty()
+        );
+    }
+}
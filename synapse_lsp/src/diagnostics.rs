@@ -0,0 +1,245 @@
+//! Diagnostics published for a document.
+//!
+//! The parser and the checker count lines and columns from 1 in
+//! characters; the protocol counts both from 0, with columns in UTF-16 code
+//! units. Positions are converted against the document text, so a range
+//! lands on the same characters in the editor whatever the line contains.
+
+use asg_core::{AsgGraph, SourceLocation};
+use parser_core::ParseError;
+use parser_core::lexer::Position;
+use type_checker_l1::{TypeError, check_graph_collect};
+
+/// A position as the protocol counts it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LspPosition {
+    pub line: u32,
+    /// UTF-16 code units from the start of the line.
+    pub character: u32,
+}
+
+/// A range as the protocol counts it; `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+/// Severity, numbered as in the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+/// One problem in a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LspDiagnostic {
+    pub range: LspRange,
+    pub severity: DiagnosticSeverity,
+    /// Stable code such as `P001` or `T003`.
+    pub code: String,
+    pub message: String,
+}
+
+/// Every problem in `source`: the parse error if it does not parse, and
+/// otherwise each type error, in document order. A type error is placed on
+/// the node it is about; one without a known location is placed at the
+/// start of the document.
+pub fn diagnostics(source: &str) -> Vec<LspDiagnostic> {
+    let graph = match parser_core::parse_str(source) {
+        Ok(graph) => graph,
+        Err(error) => return vec![diagnostic_from_parse_error(source, &error)],
+    };
+    let (_, errors) = check_graph_collect(&graph);
+    let mut diagnostics: Vec<_> = errors
+        .iter()
+        .map(|error| diagnostic_from_type_error(source, &graph, error))
+        .collect();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.range);
+    diagnostics.dedup();
+    diagnostics
+}
+
+/// Places a parse error on the character the parser stopped at, or at the
+/// end of the line when it stopped there.
+pub fn diagnostic_from_parse_error(source: &str, error: &ParseError) -> LspDiagnostic {
+    let range = match error.position() {
+        Some((line, column)) => LspRange {
+            start: lsp_position(source, line, column),
+            end: lsp_position(source, line, column + 1),
+        },
+        None => LspRange::default(),
+    };
+    LspDiagnostic {
+        range,
+        severity: DiagnosticSeverity::Error,
+        code: error.code().to_string(),
+        message: error.to_string(),
+    }
+}
+
+/// Places a type error on the source span of the node it is about.
+pub fn diagnostic_from_type_error(
+    source: &str,
+    graph: &AsgGraph,
+    error: &TypeError,
+) -> LspDiagnostic {
+    let range = error
+        .node_id()
+        .and_then(|node_id| graph.source_location(node_id))
+        .map(|location| lsp_range(source, location))
+        .unwrap_or_default();
+    LspDiagnostic {
+        range,
+        severity: DiagnosticSeverity::Error,
+        code: error.code().to_string(),
+        message: error.to_string(),
+    }
+}
+
+/// Converts a parser span of `source` into a protocol range.
+pub fn lsp_range(source: &str, location: &SourceLocation) -> LspRange {
+    LspRange {
+        start: lsp_position(source, location.start_line, location.start_col),
+        end: lsp_position(source, location.end_line, location.end_col),
+    }
+}
+
+/// Converts a 1-based line and character column of `source` into a
+/// protocol position. Columns past the end of the line are clamped to it.
+pub fn lsp_position(source: &str, line: u32, column: u32) -> LspPosition {
+    let line = line.max(1);
+    let text = source.lines().nth(line as usize - 1).unwrap_or("");
+    let character = text
+        .chars()
+        .take(column.saturating_sub(1) as usize)
+        .map(char::len_utf16)
+        .sum::<usize>();
+    LspPosition {
+        line: line - 1,
+        character: character as u32,
+    }
+}
+
+/// The byte offset in `source` of a protocol position: the inverse of
+/// [`lsp_position`]. Lines past the end clamp to the end of the text, and
+/// characters past the end of a line to the end of the line.
+pub fn source_offset(source: &str, position: LspPosition) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match source[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return source.len(),
+        }
+    }
+    let line = &source[line_start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + offset;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// The parser position of a protocol position in `source`: 1-based, in
+/// characters. Clamped as [`source_offset`] clamps.
+pub fn source_position(source: &str, position: LspPosition) -> Position {
+    offset_position(source, source_offset(source, position))
+}
+
+/// The parser position of byte `offset` in `source`.
+pub(crate) fn offset_position(source: &str, offset: usize) -> Position {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    Position {
+        line: before.matches('\n').count() as u32 + 1,
+        column: before[line_start..].chars().count() as u32 + 1,
+    }
+}
+
+impl Default for LspRange {
+    /// The empty range at the start of the document.
+    fn default() -> Self {
+        let start = LspPosition {
+            line: 0,
+            character: 0,
+        };
+        LspRange { start, end: start }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line: u32, character: u32) -> LspPosition {
+        LspPosition { line, character }
+    }
+
+    #[test]
+    fn each_type_error_is_placed_on_its_own_term() {
+        let source = "let f = (x) => x + true in\nlet g = (y) => not 1 in\nf";
+        let found = diagnostics(source);
+        let placed: Vec<_> = found
+            .iter()
+            .map(|d| (d.code.as_str(), d.range.start.line))
+            .collect();
+        assert_eq!(placed, [("T001", 0), ("T001", 1)], "{:?}", found);
+        assert!(found.iter().all(|d| d.range.start < d.range.end));
+        assert!(
+            found
+                .iter()
+                .all(|d| d.severity == DiagnosticSeverity::Error)
+        );
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_offending_character() {
+        let found = diagnostics("(x) =>\n  x + )");
+        let [diagnostic] = &found[..] else {
+            panic!("expected one diagnostic: {:?}", found);
+        };
+        assert_eq!(diagnostic.code, "P001");
+        assert_eq!(
+            diagnostic.range,
+            LspRange {
+                start: at(1, 6),
+                end: at(1, 7)
+            }
+        );
+    }
+
+    #[test]
+    fn columns_count_utf16_code_units() {
+        // `é` is one unit, `𝑥` two.
+        let source = "é𝑥 + y";
+        assert_eq!(lsp_position(source, 1, 3), at(0, 3));
+        assert_eq!(lsp_position(source, 1, 99), at(0, 7));
+        assert_eq!(lsp_position(source, 5, 1), at(4, 0));
+    }
+
+    #[test]
+    fn source_offset_inverts_lsp_position() {
+        let source = "é𝑥 + y\r\nz";
+        assert_eq!(source_offset(source, at(0, 3)), "é𝑥".len());
+        assert_eq!(source_offset(source, at(0, 99)), "é𝑥 + y".len());
+        assert_eq!(source_offset(source, at(1, 0)), source.len() - 1);
+        assert_eq!(source_offset(source, at(7, 0)), source.len());
+
+        let position = source_position(source, at(0, 3));
+        assert_eq!((position.line, position.column), (1, 3));
+        assert_eq!(
+            lsp_position(source, position.line, position.column),
+            at(0, 3)
+        );
+        let position = source_position(source, at(1, 1));
+        assert_eq!((position.line, position.column), (2, 2));
+    }
+}
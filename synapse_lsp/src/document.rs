@@ -0,0 +1,420 @@
+//! Open documents: their text and what checking it found.
+//!
+//! A [`Document`] is parsed and type checked whenever its text changes, as
+//! on `didOpen` and `didChange`; requests such as hover then only look
+//! nodes up in what was found. Changes arrive as ranged edits, as
+//! [`TEXT_DOCUMENT_SYNC`] asks of the client, and an edit inside one term
+//! has only that term checked again when the rest of the program cannot
+//! have changed.
+
+use std::collections::HashMap;
+use std::mem;
+
+use asg_core::{AsgGraph, NodeContent};
+use parser_core::lexer::Position;
+use type_checker_l1::{TypeCheckMap, annotate_graph, check_and_annotate_graph, recheck_subterm};
+
+use crate::code_action::{CodeAction, unused_binding_fixes};
+use crate::completion::{CompletionItem, complete_checked};
+use crate::diagnostics::{
+    LspDiagnostic, LspRange, diagnostics, lsp_range, offset_position, source_offset,
+};
+
+/// How the client sends document changes, numbered as in the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextDocumentSyncKind {
+    None = 0,
+    /// Every change sends the whole text.
+    Full = 1,
+    /// Changes send only the edited ranges.
+    Incremental = 2,
+}
+
+/// The sync kind the server advertises. [`Document::apply_changes`] takes
+/// full replacements as well, so clients that only send those still work.
+pub const TEXT_DOCUMENT_SYNC: TextDocumentSyncKind = TextDocumentSyncKind::Incremental;
+
+/// One change of a `didChange` notification: `text` replaces `range`, or
+/// the whole document when there is no range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextDocumentContentChangeEvent {
+    pub range: Option<LspRange>,
+    pub text: String,
+}
+use crate::hover::{Hover, pretty_type};
+use crate::navigation::{find_definition, find_node_at_pos};
+
+/// Where one ranged change edited the text, as parser positions: the
+/// replaced region of the old text and the inserted one of the new.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Edit {
+    old: (Position, Position),
+    new: (Position, Position),
+}
+
+/// An open document and what is known about it.
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    source: String,
+    /// The parsed text, unless it does not parse.
+    graph: Option<AsgGraph>,
+    /// The type of every term, unless the text does not type check.
+    types: TypeCheckMap,
+}
+
+impl Document {
+    /// A document opened with `source`.
+    pub fn new(source: impl Into<String>) -> Self {
+        let mut document = Self::default();
+        document.update(source);
+        document
+    }
+
+    /// Replaces the text, as on `didOpen` or a full `didChange`, and checks
+    /// it. When the new text is the same program as the old one up to
+    /// names, literal values and layout, the old types are kept instead.
+    pub fn update(&mut self, source: impl Into<String>) {
+        self.replace(source.into(), None);
+    }
+
+    /// Applies the changes of a `didChange` notification in order, each to
+    /// the text the previous one left, then checks the result once. A
+    /// single ranged change that leaves the rest of the program as it was
+    /// has only the term around it checked again.
+    pub fn apply_changes(&mut self, changes: &[TextDocumentContentChangeEvent]) {
+        let mut source = mem::take(&mut self.source);
+        let mut edit = None;
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let start = source_offset(&source, range.start);
+                    let end = source_offset(&source, range.end).max(start);
+                    let old = (
+                        offset_position(&source, start),
+                        offset_position(&source, end),
+                    );
+                    source.replace_range(start..end, &change.text);
+                    let new = (old.0, offset_position(&source, start + change.text.len()));
+                    edit = Some(Edit { old, new });
+                }
+                None => source.clone_from(&change.text),
+            }
+        }
+        if changes.len() != 1 {
+            edit = None;
+        }
+        self.replace(source, edit);
+    }
+
+    /// Replaces the text with `source`, reusing what checking the old text
+    /// found where `edit` cannot have changed it.
+    fn replace(&mut self, source: String, edit: Option<Edit>) {
+        self.source = source;
+        let previous = self.graph.take();
+        self.graph = parser_core::parse_str(&self.source).ok();
+        let types = match (&previous, &mut self.graph) {
+            (Some(previous), Some(graph)) if !self.types.is_empty() => {
+                carry_types(previous, &self.types, graph, None)
+                    .or_else(|| recheck_edit(previous, &self.types, graph, edit?))
+            }
+            _ => None,
+        };
+        self.types = types.unwrap_or_else(|| {
+            self.graph
+                .as_mut()
+                .and_then(|graph| check_and_annotate_graph(graph).ok())
+                .unwrap_or_default()
+        });
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// What to publish for the document: its parse error or type errors.
+    /// A document that checked has none, so only one that did not is
+    /// checked again, collecting every error.
+    pub fn diagnostics(&self) -> Vec<LspDiagnostic> {
+        if self.graph.is_some() && !self.types.is_empty() {
+            return Vec::new();
+        }
+        diagnostics(&self.source)
+    }
+
+    /// The span of the binder the variable at `position` refers to, for
+    /// `textDocument/definition`. `None` when the position is not on a
+    /// bound variable or the text does not parse.
+    pub fn definition(&self, position: Position) -> Option<LspRange> {
+        let graph = self.graph.as_ref()?;
+        let location = find_definition(graph, position)?;
+        Some(lsp_range(&self.source, location))
+    }
+
+    /// Completion candidates at `position`; see
+    /// [`complete`](crate::completion::complete).
+    pub fn complete(&self, position: Position, effects: &[&str]) -> Vec<CompletionItem> {
+        complete_checked(
+            &self.source,
+            self.graph.as_ref(),
+            &self.types,
+            position,
+            effects,
+        )
+    }
+
+    /// Quick fixes for `textDocument/codeAction` on `range`; none when the
+    /// text does not parse.
+    pub fn code_actions(&self, range: LspRange) -> Vec<CodeAction> {
+        match &self.graph {
+            Some(graph) => unused_binding_fixes(&self.source, graph, range),
+            None => Vec::new(),
+        }
+    }
+
+    /// The type of the innermost term at `position`, if it has one.
+    pub fn hover(&self, position: Position) -> Option<Hover> {
+        let graph = self.graph.as_ref()?;
+        let node_id = find_node_at_pos(graph, position)?;
+        let ty = self.types.get(&node_id)?;
+        let location = graph.source_location(node_id)?;
+        Some(Hover {
+            contents: pretty_type(ty),
+            range: lsp_range(&self.source, location),
+        })
+    }
+}
+
+/// The types of `graph`, edited from `previous` by `edit`, found by
+/// re-checking only the term around the edit, and recorded in `graph`.
+/// `None` if the rest of the program changed too or the term cannot be
+/// checked alone; see [`recheck_subterm`].
+fn recheck_edit(
+    previous: &AsgGraph,
+    types: &TypeCheckMap,
+    graph: &mut AsgGraph,
+    edit: Edit,
+) -> Option<TypeCheckMap> {
+    let (old_id, new_id) = edited_subterm(previous, graph, edit)?;
+    if Some(new_id) == graph.root() {
+        return None;
+    }
+    let carried = carry_types(previous, types, graph, Some((old_id, new_id)))?;
+    let types = recheck_subterm(graph, new_id, &carried)?;
+    annotate_graph(graph, &types);
+    Some(types)
+}
+
+/// The innermost pair of matching terms, one of `previous` and one of
+/// `graph`, spanning the old and the new region of `edit`. Found going down
+/// both trees together, so everything outside the pair is laid out alike.
+fn edited_subterm(previous: &AsgGraph, graph: &AsgGraph, edit: Edit) -> Option<(u64, u64)> {
+    let spans = |graph: &AsgGraph, node_id: u64, (start, end): (Position, Position)| {
+        graph.source_location(node_id).is_some_and(|location| {
+            (location.start_line, location.start_col) <= (start.line, start.column)
+                && (end.line, end.column) <= (location.end_line, location.end_col)
+        })
+    };
+    let mut pair = (previous.root()?, graph.root()?);
+    'descend: loop {
+        let old_children = previous.get_node(pair.0)?.content.child_ids();
+        let new_children = graph.get_node(pair.1)?.content.child_ids();
+        if old_children.len() == new_children.len() {
+            for (old_id, new_id) in old_children.into_iter().zip(new_children) {
+                if spans(previous, old_id, edit.old) && spans(graph, new_id, edit.new) {
+                    pair = (old_id, new_id);
+                    continue 'descend;
+                }
+            }
+        }
+        return Some(pair);
+    }
+}
+
+/// `types`, checked for `previous`, moved onto the nodes of `graph`, if
+/// `graph` types exactly as `previous` did: the two match node for node,
+/// with the same operators, effects and annotations, and each variable
+/// bound by the matching binder. Names and literal values may differ, since
+/// no type depends on them.
+///
+/// The terms of a `hole` pair are matched without comparing what is inside
+/// them; only their own types are carried, for [`recheck_subterm`].
+fn carry_types(
+    previous: &AsgGraph,
+    types: &TypeCheckMap,
+    graph: &AsgGraph,
+    hole: Option<(u64, u64)>,
+) -> Option<TypeCheckMap> {
+    let mut matched: HashMap<u64, u64> = HashMap::new();
+    // Parents are matched before their children, so a variable's binder is
+    // matched before the variable.
+    let mut pending = vec![(previous.root()?, graph.root()?)];
+    while let Some((old_id, new_id)) = pending.pop() {
+        if let Some(&matched_id) = matched.get(&old_id) {
+            if matched_id != new_id {
+                return None;
+            }
+            continue;
+        }
+        if hole == Some((old_id, new_id)) {
+            matched.insert(old_id, new_id);
+            continue;
+        }
+        let old = &previous.get_node(old_id)?.content;
+        let new = &graph.get_node(new_id)?.content;
+        if !types_alike(old, new, &matched) {
+            return None;
+        }
+        let (old_children, new_children) = (old.child_ids(), new.child_ids());
+        if old_children.len() != new_children.len() {
+            return None;
+        }
+        matched.insert(old_id, new_id);
+        pending.extend(old_children.into_iter().zip(new_children));
+    }
+    Some(
+        matched
+            .into_iter()
+            .filter_map(|(old_id, new_id)| Some((new_id, types.get(&old_id)?.clone())))
+            .collect(),
+    )
+}
+
+/// Whether two nodes type alike given alike children, with `matched`
+/// pairing up the nodes already compared.
+fn types_alike(old: &NodeContent, new: &NodeContent, matched: &HashMap<u64, u64>) -> bool {
+    match (old, new) {
+        (NodeContent::TermVariable(old), NodeContent::TermVariable(new)) => {
+            match matched.get(&old.definition_node_id) {
+                Some(&definition) => definition == new.definition_node_id,
+                // Unbound, or the binder of a lambda: what the name refers
+                // to is then decided by the name.
+                None => old.name == new.name,
+            }
+        }
+        (NodeContent::TermLambda(old), NodeContent::TermLambda(new)) => {
+            old.effect_annotation == new.effect_annotation
+                && old.type_annotation_id.is_some() == new.type_annotation_id.is_some()
+        }
+        (NodeContent::PrimitiveOp(old), NodeContent::PrimitiveOp(new)) => {
+            old.op_name == new.op_name
+        }
+        (NodeContent::EffectPerform(old), NodeContent::EffectPerform(new)) => {
+            old.effect_name == new.effect_name
+        }
+        // Expanded away before checking, so there is nothing to compare.
+        (NodeContent::TermMacroDefinition(_) | NodeContent::TermMacroInvocation(_), _) => false,
+        (NodeContent::TypeNode(old), NodeContent::TypeNode(new)) => {
+            mem::discriminant(&old.kind) == mem::discriminant(&new.kind)
+        }
+        _ => mem::discriminant(old) == mem::discriminant(new),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::LspPosition;
+    use type_checker_l1::check_graph;
+
+    #[test]
+    fn definition_is_the_binder_range() {
+        let mut document = Document::new("let café = 1 in\ncafé + 1");
+        let range = document
+            .definition(Position { line: 2, column: 2 })
+            .unwrap();
+        assert_eq!((range.start.line, range.start.character), (0, 4));
+        assert_eq!((range.end.line, range.end.character), (0, 8));
+        assert_eq!(document.definition(Position { line: 2, column: 8 }), None);
+
+        document.update("let café = 1 in\ncafé +");
+        assert_eq!(document.definition(Position { line: 2, column: 2 }), None);
+    }
+
+    fn edit(range: ((u32, u32), (u32, u32)), text: &str) -> TextDocumentContentChangeEvent {
+        let at = |(line, character)| LspPosition { line, character };
+        TextDocumentContentChangeEvent {
+            range: Some(LspRange {
+                start: at(range.0),
+                end: at(range.1),
+            }),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn ranged_changes_apply_in_order() {
+        let mut document = Document::new("let 𝑥 = 1 in\n𝑥 + 2");
+        document.apply_changes(&[
+            edit(((1, 5), (1, 6)), "40"),
+            // Counted in the text the first edit left; `𝑥` is two units.
+            edit(((0, 0), (0, 3)), "let y = 1 in\nlet"),
+            edit(((2, 0), (2, 2)), "y"),
+        ]);
+        assert_eq!(document.source(), "let y = 1 in\nlet 𝑥 = 1 in\ny + 40");
+        let hover = document.hover(Position { line: 3, column: 1 }).unwrap();
+        assert_eq!(hover.contents, "Int");
+
+        document.apply_changes(&[TextDocumentContentChangeEvent {
+            range: None,
+            text: "(x) => x".to_string(),
+        }]);
+        assert_eq!(document.source(), "(x) => x");
+        assert!(document.hover(Position { line: 1, column: 1 }).is_some());
+    }
+
+    #[test]
+    fn types_are_carried_over_edits_that_cannot_change_them() {
+        let graph = |source| parser_core::parse_str(source).unwrap();
+        let before = graph("let f = (x) => x + 1 in\nf(2)");
+        let types = check_graph(&before).unwrap();
+
+        let after = graph("let g = (y) =>\n  y + 10 in g(3)");
+        let carried = carry_types(&before, &types, &after, None).unwrap();
+        assert_eq!(carried, check_graph(&after).unwrap());
+
+        for changed in [
+            "let f = (x) => x + 1 in\nf(true)",
+            "let f = (x) => x == 1 in\nf(2)",
+            "let f = (x) => f + 1 in\nf(2)",
+            "let f = (x) => x + 1 in\nf",
+        ] {
+            assert_eq!(
+                carry_types(&before, &types, &graph(changed), None),
+                None,
+                "{}",
+                changed
+            );
+        }
+    }
+
+    #[test]
+    fn an_edit_inside_a_term_is_rechecked_alone() {
+        let source = "let f = (x) => x + 1 in\nf(2)";
+        let previous = parser_core::parse_str(source).unwrap();
+        let types = check_graph(&previous).unwrap();
+        // The `+` on line 1.
+        let at = |column| Position { line: 1, column };
+        let plus = Edit {
+            old: (at(18), at(19)),
+            new: (at(18), at(19)),
+        };
+
+        let mut graph = parser_core::parse_str("let f = (x) => x * 1 in\nf(2)").unwrap();
+        let rechecked = recheck_edit(&previous, &types, &mut graph, plus).unwrap();
+        assert_eq!(rechecked, check_graph(&graph).unwrap());
+        assert!(graph.inferred_type(graph.root().unwrap()).is_some());
+
+        // `f` would take a Bool.
+        let mut graph = parser_core::parse_str("let f = (x) => x && true in\nf(2)").unwrap();
+        let and = Edit {
+            new: (at(18), at(20)),
+            ..plus
+        };
+        assert_eq!(recheck_edit(&previous, &types, &mut graph, and), None);
+
+        let mut document = Document::new(source);
+        document.apply_changes(&[edit(((0, 17), (0, 18)), "==")]);
+        let hover = document.hover(Position { line: 1, column: 5 }).unwrap();
+        assert_eq!(hover.contents, "Int -> Bool");
+    }
+}
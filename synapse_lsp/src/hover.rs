@@ -0,0 +1,120 @@
+//! Hover: the type of the term under the cursor, as found when its
+//! [`Document`](crate::document::Document) was last checked.
+
+use std::collections::HashMap;
+
+use type_checker_l1::Type;
+use type_checker_l1::types::TypeVarId;
+
+use crate::diagnostics::LspRange;
+
+/// What hovering shows: a type and the span of the term it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hover {
+    pub contents: String,
+    pub range: LspRange,
+}
+
+/// Displays `ty` with its type variables renamed `T0`, `T1`, ... in order
+/// of appearance. Only the schemes the checker generalized, which it gives
+/// to `let`-bound names, are shown with `forall`; any other variable is
+/// free, as in the parameter of a lambda nothing has applied yet.
+pub fn pretty_type(ty: &Type) -> String {
+    let mut order = Vec::new();
+    variables_in_order(ty, &mut order);
+    let names: HashMap<TypeVarId, TypeVarId> = order.into_iter().zip(0..).collect();
+    rename(ty, &names).to_string()
+}
+
+fn variables_in_order(ty: &Type, order: &mut Vec<TypeVarId>) {
+    match ty {
+        Type::Int | Type::Bool | Type::Unit => {}
+        Type::Var(var) => {
+            if !order.contains(var) {
+                order.push(*var);
+            }
+        }
+        Type::Function(param, ret) => {
+            variables_in_order(param, order);
+            variables_in_order(ret, order);
+        }
+        Type::Ref(inner) => variables_in_order(inner, order),
+        Type::ForAll(bound, body) => {
+            for var in bound {
+                if !order.contains(var) {
+                    order.push(*var);
+                }
+            }
+            variables_in_order(body, order);
+        }
+    }
+}
+
+fn rename(ty: &Type, names: &HashMap<TypeVarId, TypeVarId>) -> Type {
+    match ty {
+        Type::Int | Type::Bool | Type::Unit => ty.clone(),
+        Type::Var(var) => Type::Var(names[var]),
+        Type::Function(param, ret) => Type::function(rename(param, names), rename(ret, names)),
+        Type::Ref(inner) => Type::Ref(Box::new(rename(inner, names))),
+        Type::ForAll(bound, body) => Type::ForAll(
+            bound.iter().map(|var| names[var]).collect(),
+            Box::new(rename(body, names)),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Document;
+    use parser_core::lexer::Position;
+
+    fn at(line: u32, column: u32) -> Position {
+        Position { line, column }
+    }
+
+    #[test]
+    fn hover_shows_inferred_types() {
+        let mut document = Document::new("let id = (x) => x in\nid(1) + 2");
+        let shown =
+            |document: &Document, position| document.hover(position).map(|hover| hover.contents);
+        assert_eq!(
+            shown(&document, at(1, 5)).as_deref(),
+            Some("forall T0. T0 -> T0")
+        );
+        assert_eq!(shown(&document, at(1, 10)).as_deref(), Some("T0 -> T0"));
+        assert_eq!(shown(&document, at(2, 1)).as_deref(), Some("Int -> Int"));
+        assert_eq!(shown(&document, at(2, 9)).as_deref(), Some("Int"));
+        let hover = document.hover(at(2, 4)).unwrap();
+        assert_eq!(hover.contents, "Int");
+        assert_eq!(
+            (hover.range.start.line, hover.range.start.character),
+            (1, 3)
+        );
+
+        document.update("(x) => x +");
+        assert_eq!(document.hover(at(1, 1)), None);
+        document.update("(x) => ref x");
+        assert_eq!(shown(&document, at(1, 1)).as_deref(), Some("T0 -> Ref T0"));
+    }
+
+    #[test]
+    fn lambda_parameters_are_not_quantified() {
+        let document = Document::new("(x) => x");
+        let hover = document.hover(at(1, 2)).unwrap();
+        assert_eq!(hover.contents, "T0");
+        assert_eq!(
+            (hover.range.start.character, hover.range.end.character),
+            (1, 2)
+        );
+    }
+
+    #[test]
+    fn pretty_type_numbers_variables_by_appearance() {
+        let ty = Type::function(Type::Var(7), Type::function(Type::Var(3), Type::Var(7)));
+        assert_eq!(pretty_type(&ty), "T0 -> T1 -> T0");
+        let scheme = Type::ForAll(vec![4], Box::new(Type::Ref(Box::new(Type::Var(4)))));
+        assert_eq!(pretty_type(&scheme), "forall T0. Ref T0");
+        assert_eq!(pretty_type(&Type::Int), "Int");
+    }
+}
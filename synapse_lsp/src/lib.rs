@@ -0,0 +1,28 @@
+//! Language server support for Synapse.
+//!
+//! The editor-facing features are plain functions over source text so they
+//! can be tested without a running server; [`server`] speaks the protocol
+//! over stdio and dispatches to them.
+
+pub mod capabilities;
+pub mod code_action;
+pub mod completion;
+pub mod diagnostics;
+pub mod document;
+pub mod hover;
+pub mod navigation;
+pub mod server;
+
+pub use capabilities::{ServerCapabilities, server_capabilities};
+pub use code_action::{CodeAction, TextEdit, unused_binding_fixes};
+pub use completion::{CompletionItem, CompletionKind, complete};
+pub use diagnostics::{
+    DiagnosticSeverity, LspDiagnostic, LspPosition, LspRange, diagnostics, lsp_range,
+    source_offset, source_position,
+};
+pub use document::{
+    Document, TEXT_DOCUMENT_SYNC, TextDocumentContentChangeEvent, TextDocumentSyncKind,
+};
+pub use hover::{Hover, pretty_type};
+pub use navigation::{find_definition, find_node_at_pos};
+pub use server::{Server, run};
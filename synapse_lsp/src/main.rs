@@ -1,3 +1,14 @@
-fn main() {
-    println!("Hello, world!");
+use std::io;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    match synapse_lsp::run(io::stdin().lock(), io::stdout().lock()) {
+        Ok(true) => ExitCode::SUCCESS,
+        // `exit` without `shutdown` first.
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("synapse_lsp: {}", error);
+            ExitCode::FAILURE
+        }
+    }
 }
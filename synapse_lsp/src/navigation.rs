@@ -0,0 +1,127 @@
+//! Finding the node under the cursor, and where a variable is bound.
+
+use asg_core::{AsgGraph, NodeContent, SourceLocation};
+use parser_core::lexer::Position;
+
+/// The innermost node whose source span contains `position`, if any.
+///
+/// Every node the parser builds carries a span, and a construct's span
+/// covers all of it, so pointing anywhere inside a multi-line lambda finds
+/// the lambda or something within it. Of nodes with the same span, the one
+/// built first is innermost.
+pub fn find_node_at_pos(graph: &AsgGraph, position: Position) -> Option<u64> {
+    graph
+        .nodes()
+        .filter_map(|node| {
+            graph
+                .source_location(node.node_id)
+                .filter(|location| covers(location, position))
+                .map(|location| (location, node.node_id))
+        })
+        .min_by_key(|(location, node_id)| {
+            let start = (location.start_line, location.start_col);
+            let end = (location.end_line, location.end_col);
+            (std::cmp::Reverse(start), end, *node_id)
+        })
+        .map(|(_, node_id)| node_id)
+}
+
+/// Where the variable at `position` is bound: the source span of the
+/// binder of the lambda its `definition_node_id` names. `None` when the
+/// position is not on a variable, or the variable is unbound.
+pub fn find_definition(graph: &AsgGraph, position: Position) -> Option<&SourceLocation> {
+    let node_id = find_node_at_pos(graph, position)?;
+    let NodeContent::TermVariable(var) = &graph.get_node(node_id)?.content else {
+        return None;
+    };
+    let NodeContent::TermLambda(lambda) = &graph.get_node(var.definition_node_id)?.content else {
+        return None;
+    };
+    graph.source_location(lambda.binder_variable_node_id)
+}
+
+/// Whether the character at `position` lies within `location`.
+fn covers(location: &SourceLocation, position: Position) -> bool {
+    let start = (location.start_line, location.start_col);
+    let end = (location.end_line, location.end_col);
+    let cursor = (position.line, position.column);
+    start <= cursor && cursor < end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(graph: &AsgGraph, line: u32, column: u32) -> Option<&NodeContent> {
+        find_node_at_pos(graph, Position { line, column })
+            .and_then(|node_id| graph.get_node(node_id))
+            .map(|node| &node.content)
+    }
+
+    #[test]
+    fn multi_line_lambda_is_found_from_anywhere_inside() {
+        let source = "(x: Int)\n    (y: Int) =>\n\n  x   +   y";
+        let graph = parser_core::parse_str(source).unwrap();
+
+        // The blank line and the gaps around `+` belong to the enclosing
+        // constructs rather than to any leaf.
+        assert!(matches!(at(&graph, 3, 1), Some(NodeContent::TermLambda(_))));
+        assert!(matches!(
+            at(&graph, 4, 5),
+            Some(NodeContent::PrimitiveOp(_))
+        ));
+        assert!(matches!(
+            at(&graph, 4, 3),
+            Some(NodeContent::TermVariable(var)) if var.name == "x"
+        ));
+        assert!(matches!(at(&graph, 1, 5), Some(NodeContent::TypeNode(_))));
+        assert_eq!(
+            find_node_at_pos(&graph, Position { line: 9, column: 1 }),
+            None
+        );
+    }
+
+    #[test]
+    fn let_value_is_outside_the_binding() {
+        let graph = parser_core::parse_str("let x = 1 + 2 in x").unwrap();
+        assert!(matches!(
+            at(&graph, 1, 11),
+            Some(NodeContent::PrimitiveOp(_))
+        ));
+        assert!(matches!(
+            crate::completion::variables_in_scope(
+                &graph,
+                Position {
+                    line: 1,
+                    column: 11
+                }
+            )[..],
+            []
+        ));
+        assert_eq!(
+            crate::completion::variables_in_scope(
+                &graph,
+                Position {
+                    line: 1,
+                    column: 18
+                }
+            ),
+            vec!["x".to_string()]
+        );
+    }
+
+    #[test]
+    fn definitions_are_the_binders_variables_link_to() {
+        let source = "let x = 1 in\n(y) => (x) => x + y";
+        let graph = parser_core::parse_str(source).unwrap();
+        let definition = |line, column| {
+            find_definition(&graph, Position { line, column })
+                .map(|location| (location.start_line, location.start_col))
+        };
+        // The inner `x` shadows the let-bound one.
+        assert_eq!(definition(2, 15), Some((2, 9)));
+        assert_eq!(definition(2, 19), Some((2, 2)));
+        assert_eq!(definition(1, 9), None);
+        assert_eq!(definition(2, 17), None);
+    }
+}
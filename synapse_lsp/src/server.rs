@@ -0,0 +1,460 @@
+//! The language server: JSON-RPC 2.0 over stdio.
+//!
+//! Each message is a `Content-Length` header, a blank line and a JSON body.
+//! A [`Server`] keeps the open [`Document`]s by URI and turns each message
+//! it receives into the messages to send back: the response to a request,
+//! and the diagnostics of a document that was opened or changed. [`run`]
+//! connects it to a reader and a writer until the client says `exit`.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use asg_core::effects::STANDARD_EFFECTS;
+use parser_core::lexer::Position;
+use serde_json::{Value, json};
+
+use crate::capabilities::{ServerCapabilities, server_capabilities};
+use crate::code_action::CodeAction;
+use crate::completion::{CompletionItem, CompletionKind};
+use crate::diagnostics::{LspDiagnostic, LspPosition, LspRange, source_position};
+use crate::document::{Document, TextDocumentContentChangeEvent};
+
+/// JSON-RPC error codes, as numbered in the specification.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// The code and message of an error response.
+type ResponseError = (i64, String);
+
+/// The open documents and where the session is in its lifecycle.
+#[derive(Debug, Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The open document at `uri`.
+    pub fn document(&self, uri: &str) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    /// Whether the client has sent `exit`.
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Handles one message and returns the messages to send in reply.
+    /// Notifications are never answered, even when malformed; responses
+    /// from the client are ignored, as the server sends no requests.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let params = &message["params"];
+        match (message.get("id"), message["method"].as_str()) {
+            (Some(id), Some(method)) => vec![match self.request(method, params) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => error_response(id, code, message),
+            }],
+            (None, Some(method)) => self.notification(method, params),
+            (_, None) => Vec::new(),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, ResponseError> {
+        if self.shut_down {
+            return Err((INVALID_REQUEST, format!("'{}' after shutdown", method)));
+        }
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": capabilities_json(&server_capabilities()),
+                "serverInfo": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            })),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let Some((document, position)) = self.document_at(params)? else {
+                    return Ok(Value::Null);
+                };
+                Ok(document.hover(position).map_or(Value::Null, |hover| {
+                    json!({
+                        "contents": { "kind": "plaintext", "value": hover.contents },
+                        "range": range_json(hover.range),
+                    })
+                }))
+            }
+            "textDocument/definition" => {
+                let Some((document, position)) = self.document_at(params)? else {
+                    return Ok(Value::Null);
+                };
+                Ok(document.definition(position).map_or(Value::Null, |range| {
+                    json!({ "uri": params["textDocument"]["uri"], "range": range_json(range) })
+                }))
+            }
+            "textDocument/completion" => {
+                let Some((document, position)) = self.document_at(params)? else {
+                    return Ok(json!([]));
+                };
+                let items = document.complete(position, STANDARD_EFFECTS);
+                Ok(items.iter().map(completion_json).collect())
+            }
+            "textDocument/codeAction" => {
+                let uri = text_document_uri(params)?;
+                let range = range_from_json(&params["range"])
+                    .ok_or_else(|| invalid_params("a range is required"))?;
+                let actions = match self.documents.get(uri) {
+                    Some(document) => document.code_actions(range),
+                    None => Vec::new(),
+                };
+                Ok(actions
+                    .iter()
+                    .map(|action| code_action_json(uri, action))
+                    .collect())
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        match method {
+            "exit" => {
+                self.exited = true;
+                Vec::new()
+            }
+            "textDocument/didOpen" => {
+                let opened = &params["textDocument"];
+                let (Some(uri), Some(text)) = (opened["uri"].as_str(), opened["text"].as_str())
+                else {
+                    return Vec::new();
+                };
+                let document = Document::new(text);
+                let published = publish_diagnostics(uri, &document.diagnostics());
+                self.documents.insert(uri.to_string(), document);
+                vec![published]
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array().and_then(|changes| {
+                    changes
+                        .iter()
+                        .map(change_from_json)
+                        .collect::<Option<Vec<_>>>()
+                });
+                let (Ok(uri), Some(changes)) = (text_document_uri(params), changes) else {
+                    return Vec::new();
+                };
+                let Some(document) = self.documents.get_mut(uri) else {
+                    return Vec::new();
+                };
+                document.apply_changes(&changes);
+                vec![publish_diagnostics(uri, &document.diagnostics())]
+            }
+            "textDocument/didClose" => match text_document_uri(params) {
+                Ok(uri) if self.documents.remove(uri).is_some() => {
+                    vec![publish_diagnostics(uri, &[])]
+                }
+                _ => Vec::new(),
+            },
+            // `initialized`, `$/cancelRequest` and the like need nothing.
+            _ => Vec::new(),
+        }
+    }
+
+    /// The open document and the position named by text document position
+    /// params, or `None` if the document is not open.
+    fn document_at(&self, params: &Value) -> Result<Option<(&Document, Position)>, ResponseError> {
+        let uri = text_document_uri(params)?;
+        let position = position_from_json(&params["position"])
+            .ok_or_else(|| invalid_params("a position is required"))?;
+        Ok(self
+            .documents
+            .get(uri)
+            .map(|document| (document, source_position(document.source(), position))))
+    }
+}
+
+/// Serves the protocol on `reader` and `writer` until `exit` or the end of
+/// the input. Returns whether the client shut the server down before it
+/// left, which the protocol makes the exit status.
+pub fn run(mut reader: impl BufRead, mut writer: impl Write) -> io::Result<bool> {
+    let mut server = Server::new();
+    while !server.has_exited() {
+        let Some(body) = read_message(&mut reader)? else {
+            break;
+        };
+        let replies = match serde_json::from_str::<Value>(&body) {
+            Ok(message) => server.handle(&message),
+            Err(error) => vec![error_response(&Value::Null, PARSE_ERROR, error.to_string())],
+        };
+        for reply in &replies {
+            write_message(&mut writer, reply)?;
+        }
+    }
+    Ok(server.shut_down)
+}
+
+/// Reads the body of the next message, or `None` at the end of the input.
+/// Headers other than `Content-Length` are skipped.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let invalid = |error: String| io::Error::new(io::ErrorKind::InvalidData, error);
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            let value = value.trim();
+            length = Some(
+                value
+                    .parse::<usize>()
+                    .map_err(|_| invalid(format!("invalid Content-Length '{}'", value)))?,
+            );
+        }
+    }
+    let length = length.ok_or_else(|| invalid("message without a Content-Length".to_string()))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|error| invalid(error.to_string()))
+}
+
+/// Writes `message` with its header and flushes it.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+fn error_response(id: &Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn invalid_params(message: &str) -> ResponseError {
+    (INVALID_PARAMS, message.to_string())
+}
+
+fn text_document_uri(params: &Value) -> Result<&str, ResponseError> {
+    params["textDocument"]["uri"]
+        .as_str()
+        .ok_or_else(|| invalid_params("a text document URI is required"))
+}
+
+fn capabilities_json(capabilities: &ServerCapabilities) -> Value {
+    let mut json = json!({
+        "textDocumentSync": capabilities.text_document_sync as u8,
+        "hoverProvider": capabilities.hover_provider,
+        "definitionProvider": capabilities.definition_provider,
+        "codeActionProvider": capabilities.code_action_provider,
+    });
+    if capabilities.completion_provider {
+        json["completionProvider"] = json!({});
+    }
+    json
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: &[LspDiagnostic]) -> Value {
+    let diagnostics: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "range": range_json(diagnostic.range),
+                "severity": diagnostic.severity as u8,
+                "code": diagnostic.code,
+                "source": "synapse",
+                "message": diagnostic.message,
+            })
+        })
+        .collect();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn completion_json(item: &CompletionItem) -> Value {
+    // CompletionItemKind: Variable, Keyword and, for quoted effect names,
+    // Constant.
+    let kind = match item.kind {
+        CompletionKind::Variable => 6,
+        CompletionKind::Keyword => 14,
+        CompletionKind::Effect => 21,
+    };
+    let mut json = json!({ "label": item.label, "kind": kind });
+    if let Some(detail) = &item.detail {
+        json["detail"] = json!(detail);
+    }
+    json
+}
+
+fn code_action_json(uri: &str, action: &CodeAction) -> Value {
+    let edit = json!({ "range": range_json(action.edit.range), "newText": action.edit.new_text });
+    json!({
+        "title": action.title,
+        "kind": action.kind,
+        "edit": { "changes": { uri: [edit] } },
+    })
+}
+
+fn range_json(range: LspRange) -> Value {
+    json!({ "start": position_json(range.start), "end": position_json(range.end) })
+}
+
+fn position_json(position: LspPosition) -> Value {
+    json!({ "line": position.line, "character": position.character })
+}
+
+fn position_from_json(json: &Value) -> Option<LspPosition> {
+    Some(LspPosition {
+        line: json["line"].as_u64()?.try_into().ok()?,
+        character: json["character"].as_u64()?.try_into().ok()?,
+    })
+}
+
+fn range_from_json(json: &Value) -> Option<LspRange> {
+    Some(LspRange {
+        start: position_from_json(&json["start"])?,
+        end: position_from_json(&json["end"])?,
+    })
+}
+
+fn change_from_json(json: &Value) -> Option<TextDocumentContentChangeEvent> {
+    let range = match json.get("range") {
+        Some(range) => Some(range_from_json(range)?),
+        None => None,
+    };
+    Some(TextDocumentContentChangeEvent {
+        range,
+        text: json["text"].as_str()?.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///id.syn";
+
+    fn framed(messages: &[Value]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        input
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "method": method, "params": params })
+    }
+
+    fn hover_at(id: u64, line: u32, character: u32) -> Value {
+        request(
+            id,
+            "textDocument/hover",
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    /// Replaces characters `start..end` of line 1.
+    fn replace(start: u32, end: u32, text: &str) -> Value {
+        let at = |character| json!({ "line": 1, "character": character });
+        notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "range": { "start": at(start), "end": at(end) }, "text": text }],
+            }),
+        )
+    }
+
+    #[test]
+    fn a_session_opens_edits_and_hovers_over_stdio() {
+        let input = framed(&[
+            request(1, "initialize", json!({ "capabilities": {} })),
+            notification("initialized", json!({})),
+            notification(
+                "textDocument/didOpen",
+                json!({
+                    "textDocument": {
+                        "uri": URI,
+                        "languageId": "synapse",
+                        "version": 1,
+                        "text": "let id = (x) => x in\nid(1)",
+                    },
+                }),
+            ),
+            hover_at(2, 0, 4),
+            // `id(1)` becomes `id(true)`, then `id(1 + true)`.
+            replace(3, 4, "true"),
+            hover_at(3, 1, 0),
+            replace(3, 3, "1 + "),
+            request(4, "textDocument/rename", json!({})),
+            request(5, "shutdown", Value::Null),
+            notification("exit", Value::Null),
+        ]);
+        let mut output = Vec::new();
+        assert!(run(&input[..], &mut output).unwrap());
+
+        let mut reader = &output[..];
+        let mut replies = Vec::new();
+        while let Some(body) = read_message(&mut reader).unwrap() {
+            replies.push(serde_json::from_str::<Value>(&body).unwrap());
+        }
+        assert_eq!(replies.len(), 8, "{:#?}", replies);
+
+        let capabilities = &replies[0]["result"]["capabilities"];
+        assert_eq!(replies[0]["id"], 1);
+        assert_eq!(capabilities["textDocumentSync"], 2);
+        assert_eq!(capabilities["hoverProvider"], true);
+
+        assert_eq!(replies[1]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(replies[1]["params"]["diagnostics"], json!([]));
+        assert_eq!(
+            replies[2]["result"]["contents"]["value"],
+            "forall T0. T0 -> T0"
+        );
+        assert_eq!(
+            replies[2]["result"]["range"],
+            json!({
+                "start": { "line": 0, "character": 4 },
+                "end": { "line": 0, "character": 6 },
+            })
+        );
+
+        assert_eq!(replies[3]["params"]["diagnostics"], json!([]));
+        assert_eq!(replies[4]["result"]["contents"]["value"], "Bool -> Bool");
+        let diagnostics = &replies[5]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(diagnostics[0]["code"], "T001");
+        assert_eq!(diagnostics[0]["severity"], 1);
+
+        assert_eq!(replies[6]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(replies[7]["id"], 5);
+        assert_eq!(replies[7]["result"], Value::Null);
+    }
+}
@@ -0,0 +1,107 @@
+//! Errors produced by the package manager.
+
+use std::fmt;
+
+use semver::{Version, VersionReq};
+use thiserror::Error;
+
+use crate::install::ContentHash;
+
+/// Why the package manager could not do what it was asked.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PkgError {
+    /// The manifest is not valid TOML or misses required fields.
+    #[error("invalid manifest: {0}")]
+    InvalidManifest(String),
+
+    /// A dependency names a package the registry does not have.
+    #[error("package '{name}' required by '{required_by}' is not in the registry")]
+    UnknownPackage { name: String, required_by: String },
+
+    /// No version of a package satisfies every requirement on it.
+    #[error("{0}")]
+    VersionConflict(Box<Conflict>),
+
+    /// A lock file is not valid TOML or misses required fields.
+    #[error("invalid lock file: {0}")]
+    InvalidLockFile(String),
+
+    /// The chosen packages depend on each other in a cycle, listed from
+    /// the first package back to itself.
+    #[error("dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+
+    /// An installed package does not hash to the value it was locked with.
+    /// The hashes are boxed to keep the error small.
+    #[error(
+        "package '{name} {version}' does not match its lock file: expected hash {expected}, found {actual}"
+    )]
+    Tampered {
+        name: String,
+        version: Version,
+        expected: Box<ContentHash>,
+        actual: Box<ContentHash>,
+    },
+
+    /// A fetched package has no root term to hash.
+    #[error("package '{name} {version}' is empty")]
+    EmptyPackage { name: String, version: Version },
+
+    /// The registry has no graph for a locked package.
+    #[error("could not fetch package '{name} {version}'")]
+    FetchFailed { name: String, version: Version },
+
+    /// A requested feature, or an entry in a feature's list, is neither a
+    /// feature nor an optional dependency of the package.
+    #[error(
+        "package '{package}' has no feature '{feature}'{}",
+        enabled_by.as_ref().map(|by| format!(" (enabled by '{}')", by)).unwrap_or_default()
+    )]
+    UndefinedFeature {
+        feature: String,
+        package: String,
+        enabled_by: Option<String>,
+    },
+}
+
+impl PkgError {
+    /// Stable diagnostic code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PkgError::InvalidManifest(_) => "P001",
+            PkgError::UnknownPackage { .. } => "P002",
+            PkgError::VersionConflict(_) => "P003",
+            PkgError::DependencyCycle(_) => "P004",
+            PkgError::InvalidLockFile(_) => "P005",
+            PkgError::Tampered { .. } => "P006",
+            PkgError::EmptyPackage { .. } => "P007",
+            PkgError::FetchFailed { .. } => "P008",
+            PkgError::UndefinedFeature { .. } => "P009",
+        }
+    }
+}
+
+/// The requirements on one package that no available version meets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub package: String,
+    /// Each requirement and the package that imposed it.
+    pub constraints: Vec<(VersionReq, String)>,
+    /// The versions the registry has, highest first.
+    pub available: Vec<Version>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no version of '{}' satisfies", self.package)?;
+        for (i, (req, required_by)) in self.constraints.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{} {} (required by '{}')", separator, req, required_by)?;
+        }
+        let available: Vec<String> = self.available.iter().map(Version::to_string).collect();
+        write!(f, "; available: {}", available.join(", "))
+    }
+}
+
+/// Convenience alias for package manager results.
+pub type Result<T> = std::result::Result<T, PkgError>;
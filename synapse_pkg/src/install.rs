@@ -0,0 +1,233 @@
+//! Installing locked packages and checking they are what was locked.
+//!
+//! A package is published as a compiled ASG. Its [`ContentHash`] is the
+//! BLAKE3 digest of the graph from [`asg_core::content_digest`], which
+//! ignores node ids, so a package rebuilt with different ids keeps its
+//! hash while any change to its terms changes it. The hash is taken when a
+//! package is published, copied into the lock file by the resolver, and
+//! checked against the graph actually fetched on install, which catches a
+//! compromised registry as well as a truncated download.
+
+use std::fmt;
+
+use asg_core::{AsgGraph, content_digest};
+use semver::Version;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{PkgError, Result};
+use crate::lockfile::LockFile;
+use crate::registry::Registry;
+
+/// The content digest of a package's compiled graph, written as 64 hex
+/// digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ContentHash(pub [u8; 32]);
+
+impl ContentHash {
+    /// The hash of the term at the root of `graph`, or `None` if the graph
+    /// has no root.
+    pub fn of(graph: &AsgGraph) -> Option<Self> {
+        graph.root().map(|root| Self(content_digest(graph, root)))
+    }
+
+    /// Parses the hex form [`Display`](fmt::Display) writes. The 16-digit
+    /// hashes of older lock files are rejected, since they were not
+    /// collision resistant.
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        if text.len() == 16 && text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(format!(
+                "content hash '{}' is in the old 64-bit format; delete the lock file and \
+                 resolve again to lock BLAKE3 hashes",
+                text
+            ));
+        }
+        let invalid = || format!("invalid content hash '{}': expected 64 hex digits", text);
+        if text.len() != 64 || !text.is_ascii() {
+            return Err(invalid());
+        }
+        let mut digest = [0; 32];
+        for (byte, pair) in digest.iter_mut().zip(text.as_bytes().chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(digest))
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for ContentHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Self::parse(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A package's compiled graph, fetched from a registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: Version,
+    pub graph: AsgGraph,
+}
+
+/// Checks that `package` hashes to `expected`.
+pub fn verify_installed(package: &InstalledPackage, expected: ContentHash) -> Result<()> {
+    let actual = ContentHash::of(&package.graph).ok_or_else(|| PkgError::EmptyPackage {
+        name: package.name.clone(),
+        version: package.version.clone(),
+    })?;
+    if actual != expected {
+        return Err(PkgError::Tampered {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            expected: Box::new(expected),
+            actual: Box::new(actual),
+        });
+    }
+    Ok(())
+}
+
+/// Fetches every package of `lock` except `root` from `registry`, checking
+/// each against the hash it was locked with. Packages locked without a
+/// hash are installed unchecked.
+pub fn install(
+    lock: &LockFile,
+    root: &str,
+    registry: &dyn Registry,
+) -> Result<Vec<InstalledPackage>> {
+    let mut installed = Vec::new();
+    for locked in lock.packages.iter().filter(|locked| locked.name != root) {
+        let graph = registry
+            .fetch(&locked.name, &locked.version)
+            .ok_or_else(|| PkgError::FetchFailed {
+                name: locked.name.clone(),
+                version: locked.version.clone(),
+            })?;
+        let package = InstalledPackage {
+            name: locked.name.clone(),
+            version: locked.version.clone(),
+            graph,
+        };
+        if let Some(expected) = locked.checksum {
+            verify_installed(&package, expected)?;
+        }
+        installed.push(package);
+    }
+    Ok(installed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+    use crate::registry::{MemoryRegistry, PackageSummary};
+    use crate::resolver::resolve;
+    use asg_core::NodeContent;
+    use semver::VersionReq;
+    use std::collections::BTreeMap;
+
+    fn summary(name: &str) -> PackageSummary {
+        PackageSummary {
+            name: name.to_string(),
+            version: Version::new(1, 0, 0),
+            dependencies: BTreeMap::new(),
+            checksum: None,
+        }
+    }
+
+    fn graph(source: &str) -> AsgGraph {
+        parser_core::parse_str(source).unwrap()
+    }
+
+    #[test]
+    fn installs_are_checked_against_the_locked_hash() {
+        let mut registry = MemoryRegistry::new();
+        registry.publish_graph(summary("math"), graph("(x) => x + 1"));
+        let manifest = Manifest::new("app", Version::new(0, 1, 0))
+            .with_dependency("math", VersionReq::parse("^1").unwrap());
+        let lock = resolve(&manifest, &registry).unwrap();
+        let locked = lock.get("math").unwrap().checksum.unwrap();
+        assert_eq!(ContentHash::of(&graph("(x) => x + 1")), Some(locked));
+        assert!(
+            lock.to_toml()
+                .contains(&format!("checksum = \"{}\"", locked))
+        );
+
+        let installed = install(&lock, "app", &registry).unwrap();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].name, "math");
+
+        // The registry now serves different code under the same version.
+        registry.replace_graph("math", &Version::new(1, 0, 0), graph("(x) => x + 2"));
+        let error = install(&lock, "app", &registry).unwrap_err();
+        assert!(matches!(
+            error,
+            PkgError::Tampered { ref name, ref expected, .. } if name == "math" && **expected == locked
+        ));
+        assert_eq!(error.code(), "P006");
+    }
+
+    #[test]
+    fn lock_files_carry_full_blake3_hashes() {
+        let hash = ContentHash::of(&graph("(x) => x + 1")).unwrap();
+        let text = hash.to_string();
+        assert_eq!(text.len(), 64);
+        assert_eq!(ContentHash::parse(&text), Ok(hash));
+        assert!(ContentHash::parse(&text[..63]).is_err());
+        assert!(ContentHash::parse(&"g".repeat(64)).is_err());
+
+        let old =
+            "[[package]]\nname = \"math\"\nversion = \"1.0.0\"\nchecksum = \"0123456789abcdef\"\n";
+        let error = LockFile::from_toml(old).unwrap_err();
+        assert!(error.to_string().contains("old 64-bit format"), "{}", error);
+    }
+
+    #[test]
+    fn hashes_ignore_node_ids_but_not_content() {
+        let original = graph("let double = (x) => x + x in double(2)");
+        // Copy the graph with every id shifted past a few unrelated nodes.
+        let mut renumbered = AsgGraph::new();
+        let padding = original.nodes().next().unwrap().content.clone();
+        for _ in 0..5 {
+            renumbered.add_node(padding.clone());
+        }
+        let mut ids: Vec<u64> = original.nodes().map(|node| node.node_id).collect();
+        ids.sort();
+        let offset = 5;
+        for id in ids {
+            let mut content = original.get_node(id).unwrap().content.clone();
+            content.map_child_ids(|child| child + offset);
+            if let NodeContent::TermVariable(var) = &mut content {
+                var.definition_node_id += offset;
+            }
+            assert_eq!(renumbered.add_node(content), id + offset);
+        }
+        renumbered.set_root(original.root().unwrap() + offset);
+        let package = |graph| InstalledPackage {
+            name: "double".to_string(),
+            version: Version::new(1, 0, 0),
+            graph,
+        };
+        let expected = ContentHash::of(&original).unwrap();
+        assert_eq!(verify_installed(&package(renumbered), expected), Ok(()));
+        assert!(verify_installed(&package(graph("2 + 2")), expected).is_err());
+        assert!(matches!(
+            verify_installed(&package(AsgGraph::new()), expected),
+            Err(PkgError::EmptyPackage { .. })
+        ));
+    }
+}
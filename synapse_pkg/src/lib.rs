@@ -0,0 +1,20 @@
+//! The Synapse package manager.
+//!
+//! A package declares its dependencies in a [`Manifest`]. The [`resolver`]
+//! picks one version of every package reachable from it out of a
+//! [`Registry`], and records the choice in a [`LockFile`] along with the
+//! content hash of each package, which [`install`] checks on the way in.
+
+pub mod error;
+pub mod install;
+pub mod lockfile;
+pub mod manifest;
+pub mod registry;
+pub mod resolver;
+
+pub use error::{Conflict, PkgError, Result};
+pub use install::{ContentHash, InstalledPackage, install, verify_installed};
+pub use lockfile::{LockFile, LockedPackage};
+pub use manifest::Manifest;
+pub use registry::{MemoryRegistry, PackageSummary, Registry};
+pub use resolver::{resolve, resolve_with_features};
@@ -0,0 +1,48 @@
+//! Lock files: the exact versions a resolution chose.
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PkgError, Result};
+use crate::install::ContentHash;
+
+/// A chosen package, the names of the packages it depends on, and the
+/// hash its contents must have when installed.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: Version,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<ContentHash>,
+}
+
+/// Every package of a resolution, the root included, sorted by name so
+/// equal resolutions serialize identically.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockFile {
+    #[serde(rename = "package", default)]
+    pub packages: Vec<LockedPackage>,
+}
+
+impl LockFile {
+    /// The locked entry for `name`.
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages
+            .binary_search_by(|package| package.name.as_str().cmp(name))
+            .ok()
+            .map(|index| &self.packages[index])
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("lock files always serialize")
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let mut lock: Self =
+            toml::from_str(text).map_err(|error| PkgError::InvalidLockFile(error.to_string()))?;
+        lock.packages.sort();
+        Ok(lock)
+    }
+}
@@ -0,0 +1,260 @@
+//! Package manifests.
+//!
+//! ```toml
+//! [package]
+//! name = "app"
+//! version = "0.1.0"
+//!
+//! [dependencies]
+//! json = "1.2"
+//! http = ">=0.3, <0.5"
+//! tls = { version = "2", optional = true }
+//!
+//! [features]
+//! default = ["secure"]
+//! secure = ["tls"]
+//! ```
+//!
+//! Requirements use Cargo's semver syntax, so a bare `1.2` means `^1.2`.
+//! Optional dependencies are only resolved when a feature enabling them is
+//! on. Each feature lists other features and optional dependencies, and
+//! the `default` feature applies when no features are requested.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+
+use crate::error::{PkgError, Result};
+
+/// A package's name, version and dependencies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub name: String,
+    pub version: Version,
+    /// Requirements on other packages, by package name.
+    pub dependencies: BTreeMap<String, VersionReq>,
+    /// The dependencies only resolved when a feature enables them.
+    pub optional: BTreeSet<String>,
+    /// What each feature enables: other features and optional dependencies.
+    pub features: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawManifest {
+    package: RawPackage,
+    #[serde(default)]
+    dependencies: BTreeMap<String, RawDependency>,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDependency {
+    Version(VersionReq),
+    Detailed(RawDetailedDependency),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDetailedDependency {
+    version: VersionReq,
+    #[serde(default)]
+    optional: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPackage {
+    name: String,
+    version: Version,
+}
+
+impl Manifest {
+    pub fn new(name: impl Into<String>, version: Version) -> Self {
+        Self {
+            name: name.into(),
+            version,
+            dependencies: BTreeMap::new(),
+            optional: BTreeSet::new(),
+            features: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a dependency on `name` with the requirement `req`.
+    pub fn with_dependency(mut self, name: impl Into<String>, req: VersionReq) -> Self {
+        self.dependencies.insert(name.into(), req);
+        self
+    }
+
+    /// Adds a dependency on `name` that is only resolved when a feature
+    /// enables it.
+    pub fn with_optional_dependency(mut self, name: impl Into<String>, req: VersionReq) -> Self {
+        let name = name.into();
+        self.optional.insert(name.clone());
+        self.dependencies.insert(name, req);
+        self
+    }
+
+    /// Declares the feature `name`, enabling each of `enables`.
+    pub fn with_feature<I, S>(mut self, name: impl Into<String>, enables: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let enables = enables.into_iter().map(Into::into).collect();
+        self.features.insert(name.into(), enables);
+        self
+    }
+
+    /// Expands `requested` into every feature it turns on, including the
+    /// optional dependencies those features enable. With nothing requested
+    /// the `default` feature is used, if declared.
+    ///
+    /// Fails with [`PkgError::UndefinedFeature`] if a requested feature, or
+    /// an entry of a feature's list, is neither a declared feature nor an
+    /// optional dependency.
+    pub fn resolve_features(&self, requested: &[String]) -> Result<HashSet<String>> {
+        let mut enabled = HashSet::new();
+        let mut pending: Vec<(&str, Option<&str>)> = if !requested.is_empty() {
+            requested
+                .iter()
+                .map(|feature| (feature.as_str(), None))
+                .collect()
+        } else if self.features.contains_key("default") {
+            vec![("default", None)]
+        } else {
+            Vec::new()
+        };
+        while let Some((feature, enabled_by)) = pending.pop() {
+            if enabled.contains(feature) {
+                continue;
+            }
+            if let Some(enables) = self.features.get(feature) {
+                pending.extend(enables.iter().map(|next| (next.as_str(), Some(feature))));
+            } else if !self.optional.contains(feature) {
+                return Err(PkgError::UndefinedFeature {
+                    feature: feature.to_string(),
+                    package: self.name.clone(),
+                    enabled_by: enabled_by.map(str::to_string),
+                });
+            }
+            enabled.insert(feature.to_string());
+        }
+        Ok(enabled)
+    }
+
+    /// The dependencies to resolve with `features` enabled: every required
+    /// dependency and the optional ones among `features`.
+    pub fn enabled_dependencies(&self, features: &HashSet<String>) -> BTreeMap<String, VersionReq> {
+        self.dependencies
+            .iter()
+            .filter(|(name, _)| !self.optional.contains(*name) || features.contains(*name))
+            .map(|(name, req)| (name.clone(), req.clone()))
+            .collect()
+    }
+
+    /// Parses a `synapse.toml` manifest.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let raw: RawManifest =
+            toml::from_str(text).map_err(|error| PkgError::InvalidManifest(error.to_string()))?;
+        let mut manifest = Self::new(raw.package.name, raw.package.version);
+        for (name, dependency) in raw.dependencies {
+            manifest = match dependency {
+                RawDependency::Version(req) => manifest.with_dependency(name, req),
+                RawDependency::Detailed(detail) if detail.optional => {
+                    manifest.with_optional_dependency(name, detail.version)
+                }
+                RawDependency::Detailed(detail) => manifest.with_dependency(name, detail.version),
+            };
+        }
+        manifest.features = raw.features;
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifests_parse_package_and_dependencies() {
+        let manifest = Manifest::from_toml(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [dependencies]\njson = \"1.2\"\nhttp = \">=0.3, <0.5\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest,
+            Manifest::new("app", Version::new(0, 1, 0))
+                .with_dependency("json", VersionReq::parse("^1.2").unwrap())
+                .with_dependency("http", VersionReq::parse(">=0.3, <0.5").unwrap())
+        );
+
+        let error =
+            Manifest::from_toml("[package]\nname = \"app\"\nversion = \"one\"\n").unwrap_err();
+        assert_eq!(error.code(), "P001");
+    }
+
+    fn features(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn features_expand_transitively_and_gate_optional_dependencies() {
+        let manifest = Manifest::from_toml(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [dependencies]\njson = \"1\"\ntls = { version = \"2\", optional = true }\n\
+             gzip = { version = \"0.4\", optional = true }\n\n\
+             [features]\ndefault = [\"secure\"]\nsecure = [\"tls\"]\n\
+             full = [\"secure\", \"gzip\"]\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.optional, ["gzip", "tls"].map(String::from).into());
+
+        let defaults = manifest.resolve_features(&[]).unwrap();
+        assert_eq!(defaults, features(&["default", "secure", "tls"]));
+        assert_eq!(
+            manifest
+                .enabled_dependencies(&defaults)
+                .into_keys()
+                .collect::<Vec<_>>(),
+            ["json", "tls"]
+        );
+
+        let full = manifest.resolve_features(&["full".to_string()]).unwrap();
+        assert_eq!(full, features(&["full", "secure", "tls", "gzip"]));
+        let only_gzip = manifest.resolve_features(&["gzip".to_string()]).unwrap();
+        assert_eq!(
+            manifest
+                .enabled_dependencies(&only_gzip)
+                .into_keys()
+                .collect::<Vec<_>>(),
+            ["gzip", "json"]
+        );
+    }
+
+    #[test]
+    fn undefined_features_are_reported() {
+        let manifest = Manifest::new("app", Version::new(0, 1, 0))
+            .with_dependency("json", VersionReq::parse("1").unwrap())
+            .with_feature("a", ["b"])
+            .with_feature("b", ["a", "json"]);
+        assert_eq!(
+            manifest.resolve_features(&["a".to_string()]),
+            Err(PkgError::UndefinedFeature {
+                feature: "json".to_string(),
+                package: "app".to_string(),
+                enabled_by: Some("b".to_string()),
+            })
+        );
+        let error = manifest
+            .resolve_features(&["nope".to_string()])
+            .unwrap_err();
+        assert_eq!(error.to_string(), "package 'app' has no feature 'nope'");
+        assert_eq!(error.code(), "P009");
+        assert_eq!(manifest.resolve_features(&[]), Ok(HashSet::new()));
+    }
+}
@@ -0,0 +1,83 @@
+//! Where published packages are looked up.
+
+use std::collections::BTreeMap;
+
+use asg_core::AsgGraph;
+use semver::{Version, VersionReq};
+
+use crate::install::ContentHash;
+
+/// One published version of a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageSummary {
+    pub name: String,
+    pub version: Version,
+    pub dependencies: BTreeMap<String, VersionReq>,
+    /// The hash of the published graph, if the registry serves one.
+    pub checksum: Option<ContentHash>,
+}
+
+/// A source of published packages.
+pub trait Registry {
+    /// Every published version of `name`, in any order; empty if the
+    /// package is unknown.
+    fn versions(&self, name: &str) -> Vec<PackageSummary>;
+
+    /// The compiled graph of one published version, if the registry has it.
+    fn fetch(&self, _name: &str, _version: &Version) -> Option<AsgGraph> {
+        None
+    }
+}
+
+/// A registry held in memory, for tests and local package sets.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryRegistry {
+    packages: BTreeMap<String, BTreeMap<Version, PackageSummary>>,
+    graphs: BTreeMap<(String, Version), AsgGraph>,
+}
+
+impl MemoryRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publishes `summary`, replacing an earlier publication of the same
+    /// version.
+    pub fn publish(&mut self, summary: PackageSummary) {
+        self.packages
+            .entry(summary.name.clone())
+            .or_default()
+            .insert(summary.version.clone(), summary);
+    }
+
+    /// Publishes `summary` together with its compiled graph, recording the
+    /// graph's hash in the summary.
+    pub fn publish_graph(&mut self, mut summary: PackageSummary, graph: AsgGraph) {
+        summary.checksum = ContentHash::of(&graph);
+        self.graphs
+            .insert((summary.name.clone(), summary.version.clone()), graph);
+        self.publish(summary);
+    }
+
+    /// Serves `graph` for a published version without updating its hash,
+    /// as a compromised registry would.
+    pub fn replace_graph(&mut self, name: &str, version: &Version, graph: AsgGraph) {
+        self.graphs
+            .insert((name.to_string(), version.clone()), graph);
+    }
+}
+
+impl Registry for MemoryRegistry {
+    fn versions(&self, name: &str) -> Vec<PackageSummary> {
+        self.packages
+            .get(name)
+            .map(|versions| versions.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn fetch(&self, name: &str, version: &Version) -> Option<AsgGraph> {
+        self.graphs
+            .get(&(name.to_string(), version.clone()))
+            .cloned()
+    }
+}
@@ -0,0 +1,318 @@
+//! Dependency resolution.
+//!
+//! [`resolve`] chooses one version of every package reachable from a
+//! manifest. It settles packages in name order, trying the highest version
+//! that meets every requirement seen so far; a choice that leads to an
+//! unsatisfiable requirement further down is undone and the next lower
+//! version tried. Everything is visited in a fixed order, so the same
+//! manifest and registry always give the same lock file.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use semver::VersionReq;
+
+use crate::error::{Conflict, PkgError, Result};
+use crate::lockfile::{LockFile, LockedPackage};
+use crate::manifest::Manifest;
+use crate::registry::{PackageSummary, Registry};
+
+/// Resolves `manifest` against `registry` with its default features.
+pub fn resolve(manifest: &Manifest, registry: &dyn Registry) -> Result<LockFile> {
+    resolve_with_features(manifest, &[], registry)
+}
+
+/// Resolves `manifest` against `registry` with `features` enabled, or its
+/// default features if `features` is empty. Optional dependencies no
+/// enabled feature asks for are left out.
+///
+/// When no choice of versions works, the error describes the first
+/// conflict met while trying the highest versions: the package, each
+/// requirement on it with the package that imposed it, and the versions
+/// on offer. Chosen packages that depend on each other in a cycle are
+/// reported as [`PkgError::DependencyCycle`].
+pub fn resolve_with_features(
+    manifest: &Manifest,
+    features: &[String],
+    registry: &dyn Registry,
+) -> Result<LockFile> {
+    let features = manifest.resolve_features(features)?;
+    let root = PackageSummary {
+        name: manifest.name.clone(),
+        version: manifest.version.clone(),
+        dependencies: manifest.enabled_dependencies(&features),
+        checksum: None,
+    };
+    let mut state = State::default();
+    state.activate(root, registry)?;
+    let state = search(state, registry)?;
+    if let Some(cycle) = state.find_cycle() {
+        return Err(PkgError::DependencyCycle(cycle));
+    }
+    Ok(state.into_lock_file())
+}
+
+/// A partial resolution.
+#[derive(Debug, Clone, Default)]
+struct State {
+    chosen: BTreeMap<String, PackageSummary>,
+    /// Every requirement on each package so far, with who imposed it.
+    constraints: BTreeMap<String, Vec<(VersionReq, String)>>,
+}
+
+fn search(state: State, registry: &dyn Registry) -> Result<State> {
+    let Some((name, constraints)) = state
+        .constraints
+        .iter()
+        .find(|(name, _)| !state.chosen.contains_key(*name))
+    else {
+        return Ok(state);
+    };
+    let mut versions = registry.versions(name);
+    if versions.is_empty() {
+        return Err(PkgError::UnknownPackage {
+            name: name.clone(),
+            required_by: constraints[0].1.clone(),
+        });
+    }
+    versions.sort_by(|a, b| b.version.cmp(&a.version));
+
+    let mut first_failure = None;
+    let candidates = versions.iter().filter(|candidate| {
+        constraints
+            .iter()
+            .all(|(req, _)| req.matches(&candidate.version))
+    });
+    for candidate in candidates {
+        let mut next = state.clone();
+        let attempt = next
+            .activate(candidate.clone(), registry)
+            .and_then(|()| search(next, registry));
+        match attempt {
+            Ok(resolved) => return Ok(resolved),
+            Err(error) => {
+                first_failure.get_or_insert(error);
+            }
+        }
+    }
+    Err(first_failure.unwrap_or_else(|| conflict(name, constraints, registry)))
+}
+
+impl State {
+    /// Chooses `summary` and records its requirements, failing if one rules
+    /// out a version chosen earlier.
+    fn activate(&mut self, summary: PackageSummary, registry: &dyn Registry) -> Result<()> {
+        for (dependency, req) in &summary.dependencies {
+            let constraints = self.constraints.entry(dependency.clone()).or_default();
+            constraints.push((req.clone(), summary.name.clone()));
+            if let Some(chosen) = self.chosen.get(dependency)
+                && !req.matches(&chosen.version)
+            {
+                return Err(conflict(dependency, constraints, registry));
+            }
+        }
+        self.chosen.insert(summary.name.clone(), summary);
+        Ok(())
+    }
+
+    /// A dependency cycle among the chosen packages, as the path from a
+    /// package back to itself.
+    fn find_cycle(&self) -> Option<Vec<String>> {
+        let mut finished = BTreeSet::new();
+        for name in self.chosen.keys() {
+            let mut path = Vec::new();
+            if let Some(cycle) = self.visit(name, &mut path, &mut finished) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    fn visit<'a>(
+        &'a self,
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+        finished: &mut BTreeSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|on_path| *on_path == name) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|n| n.to_string()).collect();
+            cycle.push(name.to_string());
+            return Some(cycle);
+        }
+        if finished.contains(name) {
+            return None;
+        }
+        path.push(name);
+        for dependency in self.chosen[name].dependencies.keys() {
+            if let Some(cycle) = self.visit(dependency, path, finished) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        finished.insert(name);
+        None
+    }
+
+    fn into_lock_file(self) -> LockFile {
+        LockFile {
+            packages: self
+                .chosen
+                .into_values()
+                .map(|summary| LockedPackage {
+                    name: summary.name,
+                    version: summary.version,
+                    dependencies: summary.dependencies.into_keys().collect(),
+                    checksum: summary.checksum,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn conflict(name: &str, constraints: &[(VersionReq, String)], registry: &dyn Registry) -> PkgError {
+    let mut available: Vec<_> = registry
+        .versions(name)
+        .into_iter()
+        .map(|summary| summary.version)
+        .collect();
+    available.sort_by(|a, b| b.cmp(a));
+    PkgError::VersionConflict(Box::new(Conflict {
+        package: name.to_string(),
+        constraints: constraints.to_vec(),
+        available,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::MemoryRegistry;
+    use semver::Version;
+
+    fn req(text: &str) -> VersionReq {
+        VersionReq::parse(text).unwrap()
+    }
+
+    /// A published version: name, version and `(dependency, req)` pairs.
+    type Published<'a> = (&'a str, &'a str, &'a [(&'a str, &'a str)]);
+
+    fn registry(packages: &[Published]) -> MemoryRegistry {
+        let mut registry = MemoryRegistry::new();
+        for (name, version, dependencies) in packages {
+            registry.publish(PackageSummary {
+                name: name.to_string(),
+                version: Version::parse(version).unwrap(),
+                dependencies: dependencies
+                    .iter()
+                    .map(|(dependency, text)| (dependency.to_string(), req(text)))
+                    .collect(),
+                checksum: None,
+            });
+        }
+        registry
+    }
+
+    fn app(dependencies: &[(&str, &str)]) -> Manifest {
+        dependencies.iter().fold(
+            Manifest::new("app", Version::new(0, 1, 0)),
+            |manifest, (name, text)| manifest.with_dependency(*name, req(text)),
+        )
+    }
+
+    fn versions(lock: &LockFile) -> Vec<String> {
+        lock.packages
+            .iter()
+            .map(|package| format!("{} {}", package.name, package.version))
+            .collect()
+    }
+
+    #[test]
+    fn highest_compatible_versions_are_chosen_with_backtracking() {
+        let registry = registry(&[
+            ("a", "1.0.0", &[("c", "^1")]),
+            ("a", "1.1.0", &[("c", "^2")]),
+            ("a", "2.0.0", &[]),
+            ("b", "1.0.0", &[("c", "^1.1")]),
+            ("c", "1.0.0", &[]),
+            ("c", "1.2.0", &[]),
+            ("c", "2.0.0", &[]),
+        ]);
+        let lock = resolve(&app(&[("a", "^1"), ("b", "^1")]), &registry).unwrap();
+        assert_eq!(
+            versions(&lock),
+            ["a 1.0.0", "app 0.1.0", "b 1.0.0", "c 1.2.0"]
+        );
+        assert_eq!(lock.get("app").unwrap().dependencies, ["a", "b"]);
+
+        let again = resolve(&app(&[("b", "^1"), ("a", "^1")]), &registry).unwrap();
+        assert_eq!(again.to_toml(), lock.to_toml());
+        assert_eq!(LockFile::from_toml(&lock.to_toml()), Ok(lock));
+    }
+
+    #[test]
+    fn conflicts_list_each_requirement_and_its_source() {
+        let registry = registry(&[
+            ("a", "1.0.0", &[("c", "^2")]),
+            ("b", "1.0.0", &[("c", "^1")]),
+            ("c", "1.0.0", &[]),
+            ("c", "2.0.0", &[]),
+        ]);
+        let error = resolve(&app(&[("a", "^1"), ("b", "^1")]), &registry).unwrap_err();
+        assert_eq!(
+            error,
+            PkgError::VersionConflict(Box::new(Conflict {
+                package: "c".to_string(),
+                constraints: vec![(req("^2"), "a".to_string()), (req("^1"), "b".to_string())],
+                available: vec![Version::new(2, 0, 0), Version::new(1, 0, 0)],
+            }))
+        );
+        assert_eq!(
+            error.to_string(),
+            "no version of 'c' satisfies ^2 (required by 'a'), ^1 (required by 'b'); \
+             available: 2.0.0, 1.0.0"
+        );
+        assert_eq!(
+            resolve(&app(&[("missing", "^1")]), &registry),
+            Err(PkgError::UnknownPackage {
+                name: "missing".to_string(),
+                required_by: "app".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn optional_dependencies_follow_the_enabled_features() {
+        let registry = registry(&[("json", "1.0.0", &[]), ("tls", "2.0.0", &[])]);
+        let manifest = app(&[("json", "^1")])
+            .with_optional_dependency("tls", req("^2"))
+            .with_feature("secure", ["tls"]);
+        let lock = resolve(&manifest, &registry).unwrap();
+        assert_eq!(versions(&lock), ["app 0.1.0", "json 1.0.0"]);
+
+        let secure = ["secure".to_string()];
+        let lock = resolve_with_features(&manifest, &secure, &registry).unwrap();
+        assert_eq!(versions(&lock), ["app 0.1.0", "json 1.0.0", "tls 2.0.0"]);
+
+        let defaults = manifest.with_feature("default", ["secure"]);
+        let lock = resolve(&defaults, &registry).unwrap();
+        assert_eq!(lock.get("app").unwrap().dependencies, ["json", "tls"]);
+        assert!(matches!(
+            resolve_with_features(&defaults, &["fast".to_string()], &registry),
+            Err(PkgError::UndefinedFeature { .. })
+        ));
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let registry = registry(&[
+            ("a", "1.0.0", &[("b", "^1")]),
+            ("b", "1.0.0", &[("c", "^1")]),
+            ("c", "1.0.0", &[("a", "^1")]),
+        ]);
+        assert_eq!(
+            resolve(&app(&[("a", "^1")]), &registry),
+            Err(PkgError::DependencyCycle(
+                ["a", "b", "c", "a"].map(String::from).to_vec()
+            ))
+        );
+    }
+}
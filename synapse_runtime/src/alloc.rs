@@ -0,0 +1,154 @@
+//! Heap cells for compiled programs, exported with the C ABI.
+//!
+//! Code generated by `upir_to_llvm` allocates each reference cell with
+//! [`synapse_alloc`]. Every cell belongs to the innermost region open on the
+//! allocating thread: [`synapse_region_enter`] opens a region and
+//! [`synapse_region_exit`] frees every cell still live in it. Cells made
+//! outside any region live until [`synapse_free`]d.
+//!
+//! Foreign functions called through `verified_ffi` return their results in
+//! cells too, which the caller bounds with [`synapse_alloc_size`] and frees.
+
+use std::alloc::{Layout, alloc, dealloc, handle_alloc_error};
+use std::cell::RefCell;
+
+/// Alignment of every cell; enough for any value a Synapse program stores.
+const CELL_ALIGN: usize = 16;
+
+/// A live cell, the size it was requested with, and its layout.
+struct Cell {
+    ptr: *mut u8,
+    size: usize,
+    layout: Layout,
+}
+
+/// The cells live on one thread, grouped by region. The first group holds
+/// cells made outside any region and is never exited.
+struct Heap {
+    regions: Vec<Vec<Cell>>,
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap {
+        regions: vec![Vec::new()],
+    });
+}
+
+/// Allocates an uninitialized cell of `size` bytes in the innermost region.
+///
+/// Never returns null: running out of memory aborts.
+#[unsafe(no_mangle)]
+pub extern "C" fn synapse_alloc(size: usize) -> *mut u8 {
+    // Zero-sized cells still get a distinct address.
+    let layout = Layout::from_size_align(size.max(1), CELL_ALIGN).expect("cell size overflows");
+    // SAFETY: the layout has a non-zero size.
+    let ptr = unsafe { alloc(layout) };
+    if ptr.is_null() {
+        handle_alloc_error(layout);
+    }
+    HEAP.with_borrow_mut(|heap| {
+        heap.regions
+            .last_mut()
+            .expect("the outermost group is never exited")
+            .push(Cell { ptr, size, layout })
+    });
+    ptr
+}
+
+/// Frees a cell before its region exits. Null and unknown pointers,
+/// including cells made on another thread, are ignored.
+///
+/// # Safety
+///
+/// `ptr` must not be used after this call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn synapse_free(ptr: *mut u8) {
+    let cell = HEAP.with_borrow_mut(|heap| {
+        heap.regions.iter_mut().rev().find_map(|cells| {
+            let index = cells.iter().position(|cell| cell.ptr == ptr)?;
+            Some(cells.swap_remove(index))
+        })
+    });
+    if let Some(cell) = cell {
+        // SAFETY: the cell came from `synapse_alloc` with this layout and
+        // has just been removed, so it is freed exactly once.
+        unsafe { dealloc(cell.ptr, cell.layout) };
+    }
+}
+
+/// Opens a region; cells allocated until the matching exit belong to it.
+#[unsafe(no_mangle)]
+pub extern "C" fn synapse_region_enter() {
+    HEAP.with_borrow_mut(|heap| heap.regions.push(Vec::new()));
+}
+
+/// Closes the innermost region, freeing every cell still live in it. Does
+/// nothing if no region is open.
+///
+/// # Safety
+///
+/// No pointer to a cell of the region may be used after this call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn synapse_region_exit() {
+    let cells = HEAP.with_borrow_mut(|heap| {
+        if heap.regions.len() > 1 {
+            heap.regions.pop().unwrap_or_default()
+        } else {
+            Vec::new()
+        }
+    });
+    for cell in cells {
+        // SAFETY: as in `synapse_free`; the region owned the cell.
+        unsafe { dealloc(cell.ptr, cell.layout) };
+    }
+}
+
+/// The size `ptr`'s cell was allocated with, or 0 if `ptr` is not a live
+/// cell of the current thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn synapse_alloc_size(ptr: *const u8) -> usize {
+    HEAP.with_borrow(|heap| {
+        heap.regions
+            .iter()
+            .flatten()
+            .find(|cell| cell.ptr.cast_const() == ptr)
+            .map_or(0, |cell| cell.size)
+    })
+}
+
+/// Number of cells live on the current thread, in any region.
+pub fn live_cells() -> usize {
+    HEAP.with_borrow(|heap| heap.regions.iter().map(Vec::len).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_exit_frees_the_cells_made_inside_it() {
+        let outside = synapse_alloc(8);
+        synapse_region_enter();
+        let cell = synapse_alloc(8);
+        // SAFETY: the cell is 8 bytes and suitably aligned.
+        unsafe {
+            cell.cast::<i64>().write(41);
+            assert_eq!(cell.cast::<i64>().read(), 41);
+        }
+        synapse_alloc(0);
+        assert_eq!(live_cells(), 3);
+        assert_eq!(synapse_alloc_size(cell), 8);
+        assert_eq!(synapse_alloc_size(std::ptr::null()), 0);
+        // SAFETY: the region's cells are not used again.
+        unsafe { synapse_region_exit() };
+        assert_eq!(live_cells(), 1);
+        assert_eq!(synapse_alloc_size(cell), 0);
+
+        // SAFETY: `outside` is not used again; unknown pointers are ignored.
+        unsafe {
+            synapse_free(outside);
+            synapse_free(outside);
+        }
+        assert_eq!(live_cells(), 0);
+    }
+}
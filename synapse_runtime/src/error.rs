@@ -0,0 +1,59 @@
+//! Errors raised while running Synapse programs.
+
+use thiserror::Error;
+
+/// A failure during evaluation.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EvalError {
+    /// The graph has no root term to evaluate.
+    #[error("graph has no root node")]
+    MissingRoot,
+
+    /// A referenced node is not in the graph.
+    #[error("node {0} referenced but not present in the graph")]
+    MissingNode(u64),
+
+    /// A type node was reached where a term was expected.
+    #[error("node {0} is a type, not a term")]
+    NotATerm(u64),
+
+    /// A macro node was reached; macros must be expanded before running.
+    #[error("macro node {0} was not expanded")]
+    UnexpandedMacro(u64),
+
+    /// A variable has no value in the current environment.
+    #[error("unbound variable '{name}' at node {node_id}")]
+    UnboundVariable { node_id: u64, name: String },
+
+    /// A value of the wrong kind reached an operation; the type checker
+    /// rules this out for well-typed programs.
+    #[error("expected {expected} at node {node_id}, found {found}")]
+    TypeMismatch {
+        node_id: u64,
+        expected: &'static str,
+        found: &'static str,
+    },
+
+    /// A primitive was given arguments it does not accept.
+    #[error("primitive '{op_name}' at node {node_id} cannot take these {arity} argument(s)")]
+    InvalidPrimitive {
+        node_id: u64,
+        op_name: String,
+        arity: usize,
+    },
+
+    /// Integer division or remainder by zero.
+    #[error("division by zero at node {0}")]
+    DivisionByZero(u64),
+
+    /// An effect handler refused or failed to handle an effect.
+    #[error("effect '{effect}' at node {node_id} failed: {reason}")]
+    Effect {
+        node_id: u64,
+        effect: String,
+        reason: String,
+    },
+}
+
+/// Convenience alias for evaluation results.
+pub type Result<T> = std::result::Result<T, EvalError>;
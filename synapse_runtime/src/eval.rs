@@ -0,0 +1,356 @@
+//! A reference interpreter that evaluates ASG terms directly.
+//!
+//! Evaluation is call-by-value and follows the core semantics: `ref`
+//! allocates a cell in the store, `!` reads it and `:=` overwrites it,
+//! returning unit. Performed effects are passed to an [`EffectHandler`].
+//! Only `if` is lazy: it evaluates the branch its condition selects.
+//!
+//! `Int` is a 64-bit two's complement integer. `add`, `sub` and `mul` wrap on
+//! overflow, as does `i64::MIN / -1`; `div` truncates toward zero and `mod`
+//! takes the sign of the dividend. Division or remainder by zero traps with
+//! [`EvalError::DivisionByZero`]. The constant folder in `asg_core` follows
+//! the same rules and never folds a division by zero away.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use asg_core::{AsgGraph, NodeContent};
+
+use crate::error::{EvalError, Result};
+
+/// Environment mapping a binding lambda's node id to the argument value.
+type Env = HashMap<u64, Value>;
+
+/// A runtime value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    Unit,
+    /// A lambda together with the environment it was created in.
+    Closure {
+        lambda_id: u64,
+        env: Env,
+    },
+    /// An index into the interpreter's store.
+    Ref(usize),
+    /// A function defined with `letrec`: the fixed point of the generator
+    /// lambda `lambda_id`, which is unfolded again on every call.
+    Recursive {
+        lambda_id: u64,
+        env: Env,
+    },
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "Int",
+            Value::Bool(_) => "Bool",
+            Value::Unit => "Unit",
+            Value::Closure { .. } | Value::Recursive { .. } => "function",
+            Value::Ref(_) => "Ref",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Unit => write!(f, "()"),
+            Value::Closure { lambda_id, .. } | Value::Recursive { lambda_id, .. } => {
+                write!(f, "<closure #{}>", lambda_id)
+            }
+            Value::Ref(location) => write!(f, "<ref {}>", location),
+        }
+    }
+}
+
+/// Carries out effects performed by the program.
+pub trait EffectHandler {
+    /// Handles `perform(effect, payload)` and returns its result.
+    fn perform(&mut self, node_id: u64, effect: &str, payload: &Value) -> Result<Value>;
+}
+
+/// Handler that accepts every effect, records it and returns unit.
+#[derive(Debug, Default)]
+pub struct RecordingHandler {
+    pub performed: Vec<(String, Value)>,
+}
+
+impl EffectHandler for RecordingHandler {
+    fn perform(&mut self, _node_id: u64, effect: &str, payload: &Value) -> Result<Value> {
+        self.performed.push((effect.to_string(), payload.clone()));
+        Ok(Value::Unit)
+    }
+}
+
+/// Evaluates ASG terms against a store of reference cells.
+pub struct Interpreter<'g, H> {
+    graph: &'g AsgGraph,
+    store: Vec<Value>,
+    handler: H,
+}
+
+impl<'g> Interpreter<'g, RecordingHandler> {
+    /// Creates an interpreter that records effects instead of running them.
+    pub fn new(graph: &'g AsgGraph) -> Self {
+        Self::with_handler(graph, RecordingHandler::default())
+    }
+}
+
+impl<'g, H: EffectHandler> Interpreter<'g, H> {
+    /// Creates an interpreter that passes effects to `handler`.
+    pub fn with_handler(graph: &'g AsgGraph, handler: H) -> Self {
+        Self {
+            graph,
+            store: Vec::new(),
+            handler,
+        }
+    }
+
+    /// The effect handler, e.g. to inspect what was recorded.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Current contents of the reference cell `location`.
+    pub fn load(&self, location: usize) -> Option<&Value> {
+        self.store.get(location)
+    }
+
+    /// Every reference cell allocated so far, indexed by location.
+    pub fn store(&self) -> &[Value] {
+        &self.store
+    }
+
+    /// Evaluates the graph's root term.
+    pub fn run(&mut self) -> Result<Value> {
+        let root = self.graph.root().ok_or(EvalError::MissingRoot)?;
+        self.eval(root, &Env::new())
+    }
+
+    fn eval(&mut self, node_id: u64, env: &Env) -> Result<Value> {
+        let node = self
+            .graph
+            .get_node(node_id)
+            .ok_or(EvalError::MissingNode(node_id))?;
+        match &node.content {
+            NodeContent::LiteralInt(lit) => Ok(Value::Int(lit.value)),
+            NodeContent::LiteralBool(lit) => Ok(Value::Bool(lit.value)),
+            NodeContent::LiteralUnit(_) => Ok(Value::Unit),
+            NodeContent::TermVariable(var) => {
+                env.get(&var.definition_node_id).cloned().ok_or_else(|| {
+                    EvalError::UnboundVariable {
+                        node_id,
+                        name: var.name.clone(),
+                    }
+                })
+            }
+            NodeContent::TermLambda(_) => Ok(Value::Closure {
+                lambda_id: node_id,
+                env: env.clone(),
+            }),
+            NodeContent::TermApplication(app) => {
+                let function = self.eval(app.function_node_id, env)?;
+                let argument = self.eval(app.argument_node_id, env)?;
+                self.apply(node_id, function, argument)
+            }
+            NodeContent::PrimitiveOp(op) => {
+                match (op.op_name.as_str(), op.argument_node_ids.as_slice()) {
+                    ("if", &[condition, yes, no]) => {
+                        return match self.eval(condition, env)? {
+                            Value::Bool(true) => self.eval(yes, env),
+                            Value::Bool(false) => self.eval(no, env),
+                            other => Err(mismatch(condition, "Bool", &other)),
+                        };
+                    }
+                    ("fix", &[generator]) => {
+                        let generator = self.eval(generator, env)?;
+                        return self.unfold(node_id, generator);
+                    }
+                    _ => {}
+                }
+                let args = op
+                    .argument_node_ids
+                    .iter()
+                    .map(|&arg| self.eval(arg, env))
+                    .collect::<Result<Vec<_>>>()?;
+                primitive(node_id, &op.op_name, &args)
+            }
+            NodeContent::TermRef(term) => {
+                let value = self.eval(term.init_value_node_id, env)?;
+                self.store.push(value);
+                Ok(Value::Ref(self.store.len() - 1))
+            }
+            NodeContent::TermDeref(term) => {
+                let location = self.eval_ref(term.ref_node_id, env)?;
+                Ok(self.store[location].clone())
+            }
+            NodeContent::TermAssign(term) => {
+                let location = self.eval_ref(term.ref_node_id, env)?;
+                let value = self.eval(term.value_node_id, env)?;
+                self.store[location] = value;
+                Ok(Value::Unit)
+            }
+            NodeContent::EffectPerform(perform) => {
+                let payload = self.eval(perform.value_node_id, env)?;
+                self.handler
+                    .perform(node_id, &perform.effect_name, &payload)
+            }
+            NodeContent::TermMacroDefinition(_) | NodeContent::TermMacroInvocation(_) => {
+                Err(EvalError::UnexpandedMacro(node_id))
+            }
+            NodeContent::TypeNode(_) => Err(EvalError::NotATerm(node_id)),
+        }
+    }
+
+    fn eval_ref(&mut self, node_id: u64, env: &Env) -> Result<usize> {
+        match self.eval(node_id, env)? {
+            Value::Ref(location) => Ok(location),
+            other => Err(mismatch(node_id, "Ref", &other)),
+        }
+    }
+
+    fn apply(&mut self, node_id: u64, function: Value, argument: Value) -> Result<Value> {
+        if let Value::Recursive { lambda_id, env } = function {
+            let function = self.unfold(node_id, Value::Closure { lambda_id, env })?;
+            return self.apply(node_id, function, argument);
+        }
+        let Value::Closure { lambda_id, mut env } = function else {
+            return Err(mismatch(node_id, "function", &function));
+        };
+        let Some(NodeContent::TermLambda(lambda)) =
+            self.graph.get_node(lambda_id).map(|node| &node.content)
+        else {
+            return Err(EvalError::MissingNode(lambda_id));
+        };
+        env.insert(lambda_id, argument);
+        self.eval(lambda.body_node_id, &env)
+    }
+
+    /// Applies the generator of a `letrec` to the recursive function it
+    /// defines, yielding the function's value with its own name bound.
+    fn unfold(&mut self, node_id: u64, generator: Value) -> Result<Value> {
+        let Value::Closure { lambda_id, env } = generator else {
+            return Err(mismatch(node_id, "function", &generator));
+        };
+        let itself = Value::Recursive {
+            lambda_id,
+            env: env.clone(),
+        };
+        self.apply(node_id, Value::Closure { lambda_id, env }, itself)
+    }
+}
+
+/// Evaluates the root of `graph`, recording any effects it performs.
+pub fn evaluate(graph: &AsgGraph) -> Result<Value> {
+    Interpreter::new(graph).run()
+}
+
+fn mismatch(node_id: u64, expected: &'static str, found: &Value) -> EvalError {
+    EvalError::TypeMismatch {
+        node_id,
+        expected,
+        found: found.kind(),
+    }
+}
+
+fn primitive(node_id: u64, op_name: &str, args: &[Value]) -> Result<Value> {
+    use Value::{Bool, Int};
+
+    let value = match (op_name, args) {
+        ("add", [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+        ("sub", [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+        ("mul", [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+        ("div" | "mod", [Int(_), Int(0)]) => return Err(EvalError::DivisionByZero(node_id)),
+        ("div", [Int(a), Int(b)]) => Int(a.wrapping_div(*b)),
+        ("mod", [Int(a), Int(b)]) => Int(a.wrapping_rem(*b)),
+        ("lt", [Int(a), Int(b)]) => Bool(a < b),
+        ("le", [Int(a), Int(b)]) => Bool(a <= b),
+        ("gt", [Int(a), Int(b)]) => Bool(a > b),
+        ("ge", [Int(a), Int(b)]) => Bool(a >= b),
+        ("eq", [a, b]) => Bool(a == b),
+        ("ne", [a, b]) => Bool(a != b),
+        ("and", [Bool(a), Bool(b)]) => Bool(*a && *b),
+        ("or", [Bool(a), Bool(b)]) => Bool(*a || *b),
+        ("not", [Bool(a)]) => Bool(!a),
+        _ => {
+            return Err(EvalError::InvalidPrimitive {
+                node_id,
+                op_name: op_name.to_string(),
+                arity: args.len(),
+            });
+        }
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> Result<Value> {
+        evaluate(&parser_core::parse_str(source).unwrap())
+    }
+
+    #[test]
+    fn applies_closures_call_by_value() {
+        assert_eq!(run("((x) => (y) => x - y)(10)(3)"), Ok(Value::Int(7)));
+        assert_eq!(run("1 + 2 * 3 == 7 && not false"), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn unit_literal_evaluates_to_unit() {
+        assert_eq!(run("()"), Ok(Value::Unit));
+        assert_eq!(run("((u: Unit) => u)(())"), Ok(Value::Unit));
+    }
+
+    #[test]
+    fn references_update_the_store() {
+        let graph =
+            parser_core::parse_str("((r) => ((ignored) => !r)(r := !r + 1))(ref 41)").unwrap();
+        let mut interpreter = Interpreter::new(&graph);
+        assert_eq!(interpreter.run(), Ok(Value::Int(42)));
+        assert_eq!(interpreter.load(0), Some(&Value::Int(42)));
+    }
+
+    #[test]
+    fn effects_reach_the_handler() {
+        let graph = parser_core::parse_str("perform('IO', 1 + 1)").unwrap();
+        let mut interpreter = Interpreter::new(&graph);
+        assert_eq!(interpreter.run(), Ok(Value::Unit));
+        assert_eq!(
+            interpreter.handler().performed,
+            vec![("IO".to_string(), Value::Int(2))]
+        );
+    }
+
+    #[test]
+    fn letrec_functions_call_themselves() {
+        let factorial = "letrec fact = (n) => if n == 0 then 1 else n * fact(n - 1) in fact(5)";
+        assert_eq!(run(factorial), Ok(Value::Int(120)));
+        // Only the selected branch runs.
+        assert_eq!(run("if 1 < 2 then 1 else 1 / 0"), Ok(Value::Int(1)));
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error_not_a_panic() {
+        assert!(matches!(run("1 / 0"), Err(EvalError::DivisionByZero(_))));
+        assert!(matches!(run("1(2)"), Err(EvalError::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn integer_arithmetic_wraps_on_overflow() {
+        assert_eq!(run("9223372036854775807 + 1"), Ok(Value::Int(i64::MIN)));
+        assert_eq!(
+            run("(0 - 9223372036854775807 - 1) / (0 - 1)"),
+            Ok(Value::Int(i64::MIN))
+        );
+        assert_eq!(run("(0 - 7) / 2"), Ok(Value::Int(-3)));
+        assert_eq!(run("(0 - 7) % 2"), Ok(Value::Int(-1)));
+        assert!(matches!(run("5 % 0"), Err(EvalError::DivisionByZero(_))));
+    }
+}
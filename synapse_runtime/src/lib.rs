@@ -1,14 +1,13 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Runtime support for Synapse programs.
+//!
+//! [`eval`] provides a reference interpreter over the ASG, used by tools
+//! that need to run programs without going through code generation.
+//! [`alloc`] is the other half: the C ABI functions compiled programs call
+//! to allocate reference cells.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod alloc;
+pub mod error;
+pub mod eval;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use error::{EvalError, Result};
+pub use eval::{EffectHandler, Interpreter, RecordingHandler, Value, evaluate};
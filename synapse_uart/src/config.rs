@@ -0,0 +1,310 @@
+//! Runtime configuration.
+//!
+//! A [`RuntimeConfig`] can be built in code or loaded from a TOML or JSON
+//! file or from environment variables. Settings left out keep their
+//! defaults, unknown keys are logged as warnings and ignored, and the
+//! result is [validated](RuntimeConfig::validate) before it is returned.
+//!
+//! ```toml
+//! [scheduler_config]
+//! worker_threads = 4
+//!
+//! [effect_config]
+//! strict_effects = false
+//! default_capabilities = ["IO"]
+//! ```
+
+use std::fmt::Display;
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::error::{Result, RuntimeError};
+use crate::fault::FaultConfig;
+use crate::memory::MemoryConfig;
+
+/// Settings for the task scheduler.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// Number of worker threads; must be at least one.
+    pub worker_threads: usize,
+    /// Preferred length of a scheduling quantum in milliseconds.
+    pub time_slice_ms: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            worker_threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            time_slice_ms: 10,
+        }
+    }
+}
+
+/// Policy applied when a program performs an effect.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct EffectConfig {
+    /// Reject effects the current task has not been granted explicitly.
+    pub strict_effects: bool,
+    /// Effects granted to every task when `strict_effects` is off.
+    pub default_capabilities: Vec<String>,
+}
+
+impl EffectConfig {
+    /// A permissive policy that grants `defaults` to every task.
+    pub fn permissive<S: Into<String>>(defaults: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            strict_effects: false,
+            default_capabilities: defaults.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Default for EffectConfig {
+    fn default() -> Self {
+        Self {
+            strict_effects: true,
+            default_capabilities: Vec::new(),
+        }
+    }
+}
+
+/// Configuration for a whole [`UartRuntime`](crate::UartRuntime).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    pub scheduler_config: SchedulerConfig,
+    pub effect_config: EffectConfig,
+    pub memory_config: MemoryConfig,
+    pub fault_config: FaultConfig,
+}
+
+/// Prefix of the variables read by [`RuntimeConfig::from_env`].
+pub const ENV_PREFIX: &str = "SYNAPSE_UART_";
+
+impl RuntimeConfig {
+    /// Checks that the settings can be used to start a runtime, naming the
+    /// first offending field otherwise.
+    pub fn validate(&self) -> Result<()> {
+        let at_least_one = |field: &str, value: u64| {
+            if value == 0 {
+                Err(RuntimeError::ConfigError(format!(
+                    "{} must be at least 1",
+                    field
+                )))
+            } else {
+                Ok(())
+            }
+        };
+        at_least_one(
+            "scheduler_config.worker_threads",
+            self.scheduler_config.worker_threads as u64,
+        )?;
+        at_least_one(
+            "scheduler_config.time_slice_ms",
+            self.scheduler_config.time_slice_ms,
+        )?;
+        at_least_one(
+            "fault_config.max_recorded_faults",
+            self.fault_config.max_recorded_faults as u64,
+        )
+    }
+
+    /// Loads the configuration in `path`: JSON if the file name ends in
+    /// `.json`, TOML otherwise.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|error| {
+            RuntimeError::ConfigError(format!("cannot read {}: {}", path.display(), error))
+        })?;
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+    }
+
+    /// Parses a TOML configuration.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Self::load(toml::Deserializer::new(text))
+    }
+
+    /// Parses a JSON configuration.
+    pub fn from_json(text: &str) -> Result<Self> {
+        let mut deserializer = serde_json::Deserializer::from_str(text);
+        let config = Self::load(&mut deserializer)?;
+        deserializer
+            .end()
+            .map_err(|error| RuntimeError::ConfigError(error.to_string()))?;
+        Ok(config)
+    }
+
+    /// Reads the configuration from the variables named [`ENV_PREFIX`]
+    /// followed by the setting's path in upper case, with `__` between
+    /// levels, e.g. `SYNAPSE_UART_SCHEDULER_CONFIG__WORKER_THREADS=4`.
+    /// Values are read as TOML values, or as strings if they are not one.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> Result<Self> {
+        Self::load(toml::Value::Table(env_table(vars)))
+    }
+
+    fn load<'de, D>(deserializer: D) -> Result<Self>
+    where
+        D: Deserializer<'de>,
+        D::Error: Display,
+    {
+        let (config, unknown) = Self::parse(deserializer)?;
+        for key in unknown {
+            log::warn!("ignoring unknown runtime configuration key `{}`", key);
+        }
+        Ok(config)
+    }
+
+    /// Deserializes and validates a configuration, returning the paths of
+    /// the keys it did not recognize.
+    fn parse<'de, D>(deserializer: D) -> Result<(Self, Vec<String>)>
+    where
+        D: Deserializer<'de>,
+        D::Error: Display,
+    {
+        let mut unknown = Vec::new();
+        let config: Self =
+            serde_ignored::deserialize(deserializer, |path| unknown.push(path.to_string()))
+                .map_err(|error| RuntimeError::ConfigError(error.to_string()))?;
+        config.validate()?;
+        Ok((config, unknown))
+    }
+}
+
+/// Nests the [`ENV_PREFIX`]ed variables in `vars` into a TOML table.
+fn env_table(vars: impl IntoIterator<Item = (String, String)>) -> toml::Table {
+    let mut root = toml::Table::new();
+    for (name, raw) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let mut path: Vec<String> = key.split("__").map(str::to_lowercase).collect();
+        let field = path.pop().unwrap_or_default();
+        let mut table = &mut root;
+        for section in path {
+            let entry = table
+                .entry(section)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if !entry.is_table() {
+                *entry = toml::Value::Table(toml::Table::new());
+            }
+            table = entry.as_table_mut().expect("entry was just made a table");
+        }
+        let value = format!("value = {}", raw)
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|mut parsed| parsed.remove("value"))
+            .unwrap_or(toml::Value::String(raw));
+        table.insert(field, value);
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn files_override_only_the_settings_they_name() {
+        let path = std::env::temp_dir().join(format!("synapse_uart_{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[scheduler_config]\nworker_threads = 3\n\n[effect_config]\n\
+             strict_effects = false\ndefault_capabilities = [\"IO\"]\n",
+        )
+        .unwrap();
+        let config = RuntimeConfig::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.scheduler_config.worker_threads, 3);
+        assert_eq!(config.scheduler_config.time_slice_ms, 10);
+        assert_eq!(config.effect_config, EffectConfig::permissive(["IO"]));
+        assert_eq!(config.memory_config, MemoryConfig::default());
+        assert!(matches!(
+            RuntimeConfig::from_file(path),
+            Err(RuntimeError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn json_and_environment_sources_read_the_same_settings() {
+        let json = RuntimeConfig::from_json(
+            r#"{"fault_config": {"max_recorded_faults": 5}, "memory_config": {"max_memory": 64}}"#,
+        )
+        .unwrap();
+        let env = RuntimeConfig::from_vars(vars(&[
+            ("SYNAPSE_UART_FAULT_CONFIG__MAX_RECORDED_FAULTS", "5"),
+            ("SYNAPSE_UART_MEMORY_CONFIG__MAX_MEMORY", "64"),
+            ("PATH", "/usr/bin"),
+        ]))
+        .unwrap();
+        assert_eq!(json, env);
+        assert_eq!(json.fault_config.max_recorded_faults, 5);
+        assert_eq!(json.memory_config.max_memory, Some(64));
+
+        let env = RuntimeConfig::from_vars(vars(&[(
+            "SYNAPSE_UART_EFFECT_CONFIG__DEFAULT_CAPABILITIES",
+            r#"["IO", "Sleep"]"#,
+        )]))
+        .unwrap();
+        assert_eq!(env.effect_config.default_capabilities, ["IO", "Sleep"]);
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_without_failing() {
+        let (config, unknown) = RuntimeConfig::parse(toml::Deserializer::new(
+            "colour = \"blue\"\n[scheduler_config]\nworker_threads = 2\nstealing = true\n",
+        ))
+        .unwrap();
+        assert_eq!(config.scheduler_config.worker_threads, 2);
+        assert_eq!(unknown, ["colour", "scheduler_config.stealing"]);
+    }
+
+    #[test]
+    fn out_of_range_values_name_their_field() {
+        let error = |result: Result<RuntimeConfig>| match result {
+            Err(RuntimeError::ConfigError(message)) => message,
+            other => panic!("expected a configuration error, got {:?}", other),
+        };
+        assert!(
+            error(RuntimeConfig::from_toml(
+                "[scheduler_config]\nworker_threads = 0\n"
+            ))
+            .contains("scheduler_config.worker_threads")
+        );
+        assert!(
+            error(RuntimeConfig::from_vars(vars(&[(
+                "SYNAPSE_UART_SCHEDULER_CONFIG__TIME_SLICE_MS",
+                "0"
+            )])))
+            .contains("scheduler_config.time_slice_ms")
+        );
+        assert!(
+            error(RuntimeConfig::from_json(
+                r#"{"scheduler_config": {"worker_threads": -1}}"#
+            ))
+            .contains("invalid value")
+        );
+    }
+}
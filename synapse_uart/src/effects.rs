@@ -0,0 +1,478 @@
+//! Effect capabilities and handler dispatch.
+//!
+//! A program performs an effect through [`EffectSystem::invoke`], which
+//! checks that the current thread holds the matching capability and passes
+//! an [`EffectInvocation`] to the handler registered for the effect. The
+//! invocation carries the calling task's [`CancellationToken`] so handlers
+//! doing blocking work can stop as soon as the task is cancelled.
+//!
+//! Capabilities are granted for the extent of a [`with_effects`] block,
+//! which may also install handlers of its own with
+//! [`register_scoped_handler`]. A scoped handler shadows any outer or
+//! registered handler for its effect until the block ends, however it ends.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use synapse_runtime::{EvalError, Value};
+
+use crate::config::EffectConfig;
+use crate::error::{Result, RuntimeError};
+use crate::scheduler::{CancellationToken, helpers};
+
+/// Broad category of an effect capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CapabilityType {
+    IO,
+    State,
+    Network,
+    Custom,
+}
+
+/// Permission to perform the effect `name`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EffectCap {
+    pub name: String,
+    pub cap_type: CapabilityType,
+}
+
+impl EffectCap {
+    pub fn new(name: impl Into<String>, cap_type: CapabilityType) -> Self {
+        Self {
+            name: name.into(),
+            cap_type,
+        }
+    }
+
+    /// Console and file input/output (`IO`).
+    pub fn io() -> Self {
+        Self::new("IO", CapabilityType::IO)
+    }
+
+    /// Mutable state (`State`).
+    pub fn state() -> Self {
+        Self::new("State", CapabilityType::State)
+    }
+}
+
+type ScopedHandler = (String, Arc<dyn EffectHandler>);
+
+thread_local! {
+    static CURRENT_EFFECTS: RefCell<Vec<EffectCap>> = const { RefCell::new(Vec::new()) };
+    /// Handlers installed by the open `with_effects` blocks, innermost last.
+    static SCOPED_HANDLERS: RefCell<Vec<ScopedHandler>> = const { RefCell::new(Vec::new()) };
+    static SCOPE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Restores the thread's capabilities and scoped handlers when a
+/// `with_effects` block ends, including by unwinding.
+struct EffectScope {
+    previous_caps: usize,
+    previous_handlers: usize,
+}
+
+impl EffectScope {
+    fn enter(caps: &[EffectCap]) -> Self {
+        let previous_caps = CURRENT_EFFECTS.with_borrow_mut(|current| {
+            let previous = current.len();
+            current.extend_from_slice(caps);
+            previous
+        });
+        SCOPE_DEPTH.set(SCOPE_DEPTH.get() + 1);
+        Self {
+            previous_caps,
+            previous_handlers: SCOPED_HANDLERS.with_borrow(Vec::len),
+        }
+    }
+}
+
+impl Drop for EffectScope {
+    fn drop(&mut self) {
+        CURRENT_EFFECTS.with_borrow_mut(|current| current.truncate(self.previous_caps));
+        // Dropping handlers runs their destructors, which must not find the
+        // thread-local borrowed.
+        let removed =
+            SCOPED_HANDLERS.with_borrow_mut(|handlers| handlers.split_off(self.previous_handlers));
+        SCOPE_DEPTH.set(SCOPE_DEPTH.get() - 1);
+        drop(removed);
+    }
+}
+
+/// Runs `f` with `caps` granted on the current thread in addition to the
+/// capabilities already held. The grants, and any handler `f` installs
+/// with [`register_scoped_handler`], end with `f`, even if it panics.
+pub fn with_effects<R>(caps: &[EffectCap], f: impl FnOnce() -> R) -> R {
+    let _scope = EffectScope::enter(caps);
+    f()
+}
+
+/// Installs `handler` for `effect` until the innermost enclosing
+/// [`with_effects`] block ends, shadowing handlers installed outside it.
+///
+/// Fails if the current thread is not inside a `with_effects` block.
+pub fn register_scoped_handler(
+    effect: impl Into<String>,
+    handler: Arc<dyn EffectHandler>,
+) -> Result<()> {
+    let effect = effect.into();
+    if SCOPE_DEPTH.get() == 0 {
+        return Err(RuntimeError::EffectError(format!(
+            "scoped handler for '{}' installed outside any with_effects block",
+            effect
+        )));
+    }
+    SCOPED_HANDLERS.with_borrow_mut(|handlers| handlers.push((effect, handler)));
+    Ok(())
+}
+
+/// The innermost scoped handler for `effect` on the current thread.
+fn scoped_handler(effect: &str) -> Option<Arc<dyn EffectHandler>> {
+    SCOPED_HANDLERS.with_borrow(|handlers| {
+        handlers
+            .iter()
+            .rev()
+            .find(|(name, _)| name == effect)
+            .map(|(_, handler)| Arc::clone(handler))
+    })
+}
+
+/// Whether the current thread holds a capability for the effect `name`.
+///
+/// Capabilities match by name alone; their [`CapabilityType`] only
+/// categorizes them, so `EffectCap::io()` authorizes the effect `"IO"`.
+pub fn has_capability(name: &str) -> bool {
+    CURRENT_EFFECTS.with(|current| current.borrow().iter().any(|cap| cap.name == name))
+}
+
+/// One request to perform an effect.
+#[derive(Debug, Clone)]
+pub struct EffectInvocation {
+    pub effect: String,
+    pub payload: Value,
+    /// Cancelled when the task performing the effect is cancelled.
+    pub cancellation: CancellationToken,
+}
+
+/// Carries out one kind of effect.
+pub trait EffectHandler: Send + Sync {
+    /// Performs `invocation`. Handlers that block should poll
+    /// `invocation.cancellation` and give up once it is cancelled.
+    fn handle(&self, invocation: &EffectInvocation) -> Result<Value>;
+}
+
+/// Registry of effect handlers plus the capability policy.
+pub struct EffectSystem {
+    config: EffectConfig,
+    handlers: RwLock<HashMap<String, Arc<dyn EffectHandler>>>,
+}
+
+impl EffectSystem {
+    /// Creates an effect system with no handlers.
+    pub fn new(config: EffectConfig) -> Self {
+        Self {
+            config,
+            handlers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Creates an effect system with the built-in `IO` and `Sleep` handlers.
+    pub fn with_builtins(config: EffectConfig) -> Self {
+        let system = Self::new(config);
+        system.register_handler("IO", Arc::new(PrintHandler));
+        system.register_handler("Sleep", Arc::new(SleepHandler));
+        system
+    }
+
+    pub fn config(&self) -> &EffectConfig {
+        &self.config
+    }
+
+    /// Installs `handler` for `effect`, replacing any previous one.
+    pub fn register_handler(&self, effect: impl Into<String>, handler: Arc<dyn EffectHandler>) {
+        self.handlers
+            .write()
+            .unwrap()
+            .insert(effect.into(), handler);
+    }
+
+    /// Names of the effects with a registered handler, sorted.
+    pub fn registered_effects(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.handlers.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Performs `effect` with `payload` on behalf of the current task.
+    pub fn invoke(&self, effect: &str, payload: Value) -> Result<Value> {
+        let cancellation = helpers::current_cancellation();
+        cancellation.check()?;
+        if !self.is_allowed(effect) {
+            return Err(RuntimeError::EffectError(format!(
+                "effect '{}' is not permitted here",
+                effect
+            )));
+        }
+        let handler = scoped_handler(effect)
+            .or_else(|| self.handlers.read().unwrap().get(effect).cloned())
+            .ok_or_else(|| {
+                RuntimeError::EffectError(format!("no handler for effect '{}'", effect))
+            })?;
+        handler.handle(&EffectInvocation {
+            effect: effect.to_string(),
+            payload,
+            cancellation,
+        })
+    }
+
+    /// Whether `effect` may be performed here: the thread holds its
+    /// capability, or the policy is permissive and grants it by default.
+    fn is_allowed(&self, effect: &str) -> bool {
+        has_capability(effect)
+            || (!self.config.strict_effects
+                && self
+                    .config
+                    .default_capabilities
+                    .iter()
+                    .any(|name| name == effect))
+    }
+}
+
+/// Lets the reference interpreter perform its effects through the runtime.
+impl synapse_runtime::EffectHandler for &EffectSystem {
+    fn perform(
+        &mut self,
+        node_id: u64,
+        effect: &str,
+        payload: &Value,
+    ) -> synapse_runtime::Result<Value> {
+        self.invoke(effect, payload.clone())
+            .map_err(|error| EvalError::Effect {
+                node_id,
+                effect: effect.to_string(),
+                reason: error.to_string(),
+            })
+    }
+}
+
+/// `IO`: writes the payload to standard output.
+struct PrintHandler;
+
+impl EffectHandler for PrintHandler {
+    fn handle(&self, invocation: &EffectInvocation) -> Result<Value> {
+        invocation.cancellation.check()?;
+        writeln!(std::io::stdout(), "{}", invocation.payload)
+            .map_err(|e| RuntimeError::EffectError(format!("IO failed: {}", e)))?;
+        Ok(Value::Unit)
+    }
+}
+
+/// `Sleep`: blocks for the payload's number of milliseconds, waking up
+/// regularly to check for cancellation.
+struct SleepHandler;
+
+impl SleepHandler {
+    const SLICE: Duration = Duration::from_millis(5);
+}
+
+impl EffectHandler for SleepHandler {
+    fn handle(&self, invocation: &EffectInvocation) -> Result<Value> {
+        let Value::Int(millis) = invocation.payload else {
+            return Err(RuntimeError::EffectError(
+                "Sleep expects an Int number of milliseconds".to_string(),
+            ));
+        };
+        let mut remaining = Duration::from_millis(millis.max(0) as u64);
+        while !remaining.is_zero() {
+            invocation.cancellation.check()?;
+            let slice = remaining.min(Self::SLICE);
+            std::thread::sleep(slice);
+            remaining -= slice;
+        }
+        Ok(Value::Unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SchedulerConfig;
+    use crate::scheduler::Scheduler;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::{Mutex, mpsc};
+
+    /// Polls until cancelled, reporting on `started` once it is running.
+    struct BlockingHandler {
+        started: Mutex<mpsc::Sender<()>>,
+    }
+
+    impl EffectHandler for BlockingHandler {
+        fn handle(&self, invocation: &EffectInvocation) -> Result<Value> {
+            self.started.lock().unwrap().send(()).unwrap();
+            loop {
+                invocation.cancellation.check()?;
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+    #[test]
+    fn cancelling_a_task_stops_its_blocking_effect() {
+        let (started, running) = mpsc::channel();
+        let effects = Arc::new(EffectSystem::new(EffectConfig::default()));
+        effects.register_handler(
+            "Net",
+            Arc::new(BlockingHandler {
+                started: Mutex::new(started),
+            }),
+        );
+        let scheduler = Scheduler::new(SchedulerConfig {
+            worker_threads: 1,
+            ..SchedulerConfig::default()
+        })
+        .unwrap();
+        scheduler.start();
+
+        let task_effects = Arc::clone(&effects);
+        let handle = scheduler.spawn(move || {
+            with_effects(&[EffectCap::new("Net", CapabilityType::Network)], || {
+                task_effects.invoke("Net", Value::Unit).map(|_| ())
+            })
+        });
+        running.recv_timeout(Duration::from_secs(5)).unwrap();
+        handle.cancel();
+
+        assert!(matches!(handle.join(), Err(RuntimeError::Cancelled(_))));
+    }
+
+    #[test]
+    fn builtin_sleep_checks_for_cancellation() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let invocation = EffectInvocation {
+            effect: "Sleep".to_string(),
+            payload: Value::Int(60_000),
+            cancellation: token,
+        };
+        assert!(matches!(
+            SleepHandler.handle(&invocation),
+            Err(RuntimeError::Cancelled(_))
+        ));
+    }
+
+    #[test]
+    fn interpreter_performs_effects_through_the_runtime() {
+        let graph = parser_core::parse_str("perform('Sleep', 1)").unwrap();
+        let effects = EffectSystem::with_builtins(EffectConfig::default());
+        let denied = synapse_runtime::Interpreter::with_handler(&graph, &effects).run();
+        assert!(matches!(denied, Err(EvalError::Effect { effect, .. }) if effect == "Sleep"));
+
+        let allowed = with_effects(&[EffectCap::new("Sleep", CapabilityType::Custom)], || {
+            synapse_runtime::Interpreter::with_handler(&graph, &effects).run()
+        });
+        assert_eq!(allowed, Ok(Value::Unit));
+    }
+
+    #[test]
+    fn permissive_mode_grants_default_capabilities() {
+        let permissive = EffectSystem::with_builtins(EffectConfig::permissive(["IO"]));
+        assert_eq!(permissive.invoke("IO", Value::Int(1)), Ok(Value::Unit));
+        assert!(matches!(
+            permissive.invoke("Sleep", Value::Int(0)),
+            Err(RuntimeError::EffectError(_))
+        ));
+
+        let strict = EffectSystem::with_builtins(EffectConfig {
+            strict_effects: true,
+            ..EffectConfig::permissive(["IO"])
+        });
+        assert!(matches!(
+            strict.invoke("IO", Value::Int(1)),
+            Err(RuntimeError::EffectError(_))
+        ));
+    }
+
+    #[test]
+    fn strict_mode_requires_a_granted_capability() {
+        let effects = EffectSystem::with_builtins(EffectConfig::default());
+        assert_eq!(effects.registered_effects(), ["IO", "Sleep"]);
+        assert!(matches!(
+            effects.invoke("Sleep", Value::Int(0)),
+            Err(RuntimeError::EffectError(_))
+        ));
+        let granted = with_effects(&[EffectCap::new("Sleep", CapabilityType::Custom)], || {
+            effects.invoke("Sleep", Value::Int(0))
+        });
+        assert_eq!(granted, Ok(Value::Unit));
+        assert!(!has_capability("Sleep"));
+    }
+
+    /// Answers every invocation with a fixed value.
+    struct ConstantHandler(i64);
+
+    impl EffectHandler for ConstantHandler {
+        fn handle(&self, _: &EffectInvocation) -> Result<Value> {
+            Ok(Value::Int(self.0))
+        }
+    }
+
+    #[test]
+    fn capabilities_are_restored_when_a_block_panics() {
+        let outcome = std::panic::catch_unwind(|| {
+            with_effects(&[EffectCap::io()], || {
+                assert!(has_capability("IO"));
+                panic!("unwinding");
+            })
+        });
+        assert!(outcome.is_err());
+        assert!(!has_capability("IO"));
+    }
+
+    #[test]
+    fn scoped_handlers_shadow_outer_ones_until_their_block_ends() {
+        let effects = EffectSystem::new(EffectConfig::default());
+        effects.register_handler("Ask", Arc::new(ConstantHandler(0)));
+        let ask = || effects.invoke("Ask", Value::Unit);
+        let cap = [EffectCap::new("Ask", CapabilityType::Custom)];
+
+        with_effects(&cap, || {
+            register_scoped_handler("Ask", Arc::new(ConstantHandler(1))).unwrap();
+            assert_eq!(ask(), Ok(Value::Int(1)));
+            with_effects(&[], || {
+                register_scoped_handler("Ask", Arc::new(ConstantHandler(2))).unwrap();
+                assert_eq!(ask(), Ok(Value::Int(2)));
+            });
+            assert_eq!(ask(), Ok(Value::Int(1)));
+            let unwound = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                with_effects(&[], || {
+                    register_scoped_handler("Ask", Arc::new(ConstantHandler(3))).unwrap();
+                    panic!("unwinding");
+                })
+            }));
+            assert!(unwound.is_err());
+            assert_eq!(ask(), Ok(Value::Int(1)));
+        });
+        with_effects(&cap, || assert_eq!(ask(), Ok(Value::Int(0))));
+        assert!(matches!(
+            register_scoped_handler("Ask", Arc::new(ConstantHandler(4))),
+            Err(RuntimeError::EffectError(_))
+        ));
+    }
+
+    #[test]
+    fn typed_capabilities_authorize_effects_of_their_name() {
+        let effects = EffectSystem::with_builtins(EffectConfig::default());
+        assert!(effects.config().strict_effects);
+        let printed = with_effects(&[EffectCap::io()], || {
+            assert!(has_capability("IO"));
+            effects.invoke("IO", Value::Int(1))
+        });
+        assert_eq!(printed, Ok(Value::Unit));
+        let slept = with_effects(&[EffectCap::state()], || {
+            effects.invoke("Sleep", Value::Int(0))
+        });
+        assert!(matches!(slept, Err(RuntimeError::EffectError(_))));
+    }
+}
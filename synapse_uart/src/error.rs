@@ -0,0 +1,30 @@
+//! Error types shared by the UART runtime subsystems.
+
+use thiserror::Error;
+
+/// Errors produced by the UART runtime.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RuntimeError {
+    /// An allocation or deallocation request could not be satisfied.
+    #[error("memory error: {0}")]
+    MemoryError(String),
+
+    /// The runtime configuration is invalid for the requested operation.
+    #[error("configuration error: {0}")]
+    ConfigError(String),
+
+    /// The work was cancelled before it could finish.
+    #[error("cancelled: {0}")]
+    Cancelled(String),
+
+    /// An effect could not be performed.
+    #[error("effect error: {0}")]
+    EffectError(String),
+
+    /// A task panicked while running.
+    #[error("task panicked: {0}")]
+    TaskPanicked(String),
+}
+
+/// Convenience alias for results produced by the UART runtime.
+pub type Result<T> = std::result::Result<T, RuntimeError>;
@@ -0,0 +1,543 @@
+//! Fault recording.
+//!
+//! A [`FaultManager`] keeps the most recent faults the runtime has seen.
+//! Once [`started`](FaultManager::start), it also records every panic in
+//! the process, including panics in scheduler workers, which the scheduler
+//! itself turns into [`RuntimeError::TaskPanicked`] results. Panics reach
+//! managers through a single process-wide hook that forwards to every
+//! started manager and then to the hook that was installed before it.
+//!
+//! [`FaultManager::with_retry`] reruns failing operations under a
+//! [`RetryPolicy`]: exponential backoff, optionally jittered so tasks that
+//! failed together do not retry in lockstep.
+
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, RandomState};
+use std::panic::{self, PanicHookInfo};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, Once, PoisonError, Weak};
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+
+use crate::error::{Result, RuntimeError};
+
+/// What kind of fault was recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultType {
+    /// A thread panicked.
+    Panic,
+    /// An operation failed with a [`RuntimeError`].
+    Error,
+}
+
+/// One recorded fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaultRecord {
+    /// Identifier of the record within its manager, increasing over time.
+    pub id: u64,
+    pub fault_type: FaultType,
+    pub message: String,
+    /// `file:line:column` of the fault, when known.
+    pub location: Option<String>,
+    /// Name of the thread the fault happened on, if it has one.
+    pub thread: Option<String>,
+    pub timestamp: SystemTime,
+}
+
+/// Settings for a [`FaultManager`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct FaultConfig {
+    /// Record panics once the manager is started.
+    pub recover_from_panics: bool,
+    /// Number of records kept; older ones are dropped first.
+    pub max_recorded_faults: usize,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            recover_from_panics: true,
+            max_recorded_faults: 100,
+        }
+    }
+}
+
+/// How [`FaultManager::with_retry`] reruns a failing operation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts made in total, including the first; at least one.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound on any delay, jitter included.
+    pub max_backoff: Duration,
+    /// Factor the delay grows by after each retry.
+    pub multiplier: f64,
+    /// Fraction of each delay that is randomized, in `[0, 1]`.
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// A policy making `max_attempts` attempts with the default backoff.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the first delay and the bound on every delay.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Sets the factor the delay grows by after each retry.
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Spreads each delay uniformly over `fraction` of its length either
+    /// side of it. `fraction` is clamped to `[0, 1]`.
+    pub fn with_jitter(mut self, fraction: f64) -> Self {
+        self.jitter = if fraction.is_nan() {
+            0.0
+        } else {
+            fraction.clamp(0.0, 1.0)
+        };
+        self
+    }
+
+    /// The delays slept before each retry, freshly jittered on every call.
+    pub fn delays(&self) -> impl Iterator<Item = Duration> + use<> {
+        let mut random = Xorshift::seeded();
+        let (multiplier, jitter) = (self.multiplier, self.jitter);
+        let max = self.max_backoff.as_secs_f64();
+        let mut base = self.initial_backoff.as_secs_f64().min(max);
+        (1..self.max_attempts).map(move |_| {
+            let spread = base * jitter * (2.0 * random.next_unit() - 1.0);
+            let delay = Duration::from_secs_f64((base + spread).clamp(0.0, max));
+            base = (base * multiplier).min(max);
+            delay
+        })
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            multiplier: 2.0,
+            jitter: 0.0,
+        }
+    }
+}
+
+/// A small generator for jitter; it need only differ between callers.
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn seeded() -> Self {
+        // Every `RandomState` is keyed differently, so this differs per call.
+        Self(RandomState::new().hash_one(0u8) | 1)
+    }
+
+    /// A number uniformly distributed in `[0, 1)`.
+    fn next_unit(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Longest uninterrupted sleep while waiting out a cancellable backoff.
+const CANCEL_POLL: Duration = Duration::from_millis(5);
+
+struct Inner {
+    config: FaultConfig,
+    faults: Mutex<VecDeque<FaultRecord>>,
+    next_fault_id: AtomicU64,
+}
+
+impl Inner {
+    fn record(&self, fault_type: FaultType, message: String, location: Option<String>) -> u64 {
+        let id = self.next_fault_id.fetch_add(1, Ordering::SeqCst);
+        let record = FaultRecord {
+            id,
+            fault_type,
+            message,
+            location,
+            thread: std::thread::current().name().map(str::to_string),
+            timestamp: SystemTime::now(),
+        };
+        let mut faults = self.faults.lock().unwrap_or_else(|e| e.into_inner());
+        faults.push_back(record);
+        while faults.len() > self.config.max_recorded_faults {
+            faults.pop_front();
+        }
+        id
+    }
+}
+
+/// Started managers, keyed by the address of their shared state. Entries
+/// are weak so a dropped manager stops receiving panics without `stop`.
+static STARTED: LazyLock<Mutex<HashMap<usize, Weak<Inner>>>> = LazyLock::new(Default::default);
+static INSTALL_HOOK: Once = Once::new();
+
+thread_local! {
+    /// Set while this thread runs the panic hook, so a panic raised while
+    /// recording another one is not recorded by taking the locks again.
+    static IN_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            record_panic(info);
+            previous(info);
+        }));
+    });
+}
+
+fn record_panic(info: &PanicHookInfo<'_>) {
+    if IN_HOOK.with(|in_hook| in_hook.replace(true)) {
+        return;
+    }
+    record_panic_with_managers(info);
+    IN_HOOK.with(|in_hook| in_hook.set(false));
+}
+
+fn record_panic_with_managers(info: &PanicHookInfo<'_>) {
+    // Another thread may be starting or stopping a manager; waiting for it
+    // is brief, and skipping would lose the panic.
+    let managers: Vec<Arc<Inner>> = STARTED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect();
+    if managers.is_empty() {
+        return;
+    }
+    let message = info
+        .payload_as_str()
+        .unwrap_or("non-string panic payload")
+        .to_string();
+    let location = info.location().map(ToString::to_string);
+    for inner in managers {
+        inner.record(FaultType::Panic, message.clone(), location.clone());
+    }
+}
+
+/// Keeps the runtime's recent faults.
+///
+/// Cloning a manager is cheap and yields a handle to the same records.
+#[derive(Clone)]
+pub struct FaultManager {
+    inner: Arc<Inner>,
+}
+
+impl FaultManager {
+    /// Creates a manager that records nothing until faults are reported or
+    /// it is started.
+    pub fn new(config: FaultConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                faults: Mutex::new(VecDeque::new()),
+                next_fault_id: AtomicU64::new(1),
+            }),
+        }
+    }
+
+    /// The configuration this manager was created with.
+    pub fn config(&self) -> &FaultConfig {
+        &self.inner.config
+    }
+
+    /// Starts recording panics, installing the process-wide panic hook on
+    /// first use. Fails if `recover_from_panics` is off.
+    pub fn start(&self) -> Result<()> {
+        if !self.inner.config.recover_from_panics {
+            return Err(RuntimeError::ConfigError(
+                "panic recovery is disabled in the fault configuration".to_string(),
+            ));
+        }
+        install_hook();
+        let mut started = STARTED.lock().unwrap_or_else(|e| e.into_inner());
+        started.retain(|_, inner| inner.strong_count() > 0);
+        started.insert(self.key(), Arc::downgrade(&self.inner));
+        Ok(())
+    }
+
+    /// Stops recording panics. Records already kept stay available.
+    pub fn stop(&self) {
+        STARTED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key());
+    }
+
+    /// Records a fault and returns its id.
+    pub fn record_fault(
+        &self,
+        fault_type: FaultType,
+        message: impl Into<String>,
+        location: Option<String>,
+    ) -> u64 {
+        self.inner.record(fault_type, message.into(), location)
+    }
+
+    /// Records a runtime error as a [`FaultType::Error`] fault.
+    pub fn record_error(&self, error: &RuntimeError) -> u64 {
+        self.record_fault(FaultType::Error, error.to_string(), None)
+    }
+
+    /// Runs `operation` until it succeeds or `policy` runs out of attempts,
+    /// recording each failure and sleeping out the backoff in between.
+    /// Returns the last error if every attempt fails.
+    pub fn with_retry<T>(
+        &self,
+        policy: &RetryPolicy,
+        operation: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        self.retry(policy, None, operation)
+    }
+
+    /// Like [`with_retry`](Self::with_retry), but stops with
+    /// [`RuntimeError::Cancelled`] as soon as `cancelled` is set, checking
+    /// it before each attempt and while backing off.
+    pub fn with_retry_cancellable<T>(
+        &self,
+        policy: &RetryPolicy,
+        cancelled: Arc<AtomicBool>,
+        operation: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        self.retry(policy, Some(&cancelled), operation)
+    }
+
+    fn retry<T>(
+        &self,
+        policy: &RetryPolicy,
+        cancelled: Option<&AtomicBool>,
+        mut operation: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        let is_cancelled = || cancelled.is_some_and(|flag| flag.load(Ordering::SeqCst));
+        let cancellation = || RuntimeError::Cancelled("retry loop was cancelled".to_string());
+        let mut delays = policy.delays();
+        loop {
+            if is_cancelled() {
+                return Err(cancellation());
+            }
+            let error = match operation() {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            self.record_error(&error);
+            let Some(delay) = delays.next() else {
+                return Err(error);
+            };
+            if cancelled.is_none() {
+                std::thread::sleep(delay);
+                continue;
+            }
+            let mut remaining = delay;
+            while !remaining.is_zero() && !is_cancelled() {
+                let slice = remaining.min(CANCEL_POLL);
+                std::thread::sleep(slice);
+                remaining -= slice;
+            }
+        }
+    }
+
+    /// The `count` most recent faults, oldest first.
+    pub fn recent_faults(&self, count: usize) -> Vec<FaultRecord> {
+        let faults = self.inner.faults.lock().unwrap_or_else(|e| e.into_inner());
+        let skip = faults.len().saturating_sub(count);
+        faults.iter().skip(skip).cloned().collect()
+    }
+
+    fn key(&self) -> usize {
+        Arc::as_ptr(&self.inner) as usize
+    }
+}
+
+impl Default for FaultManager {
+    fn default() -> Self {
+        Self::new(FaultConfig::default())
+    }
+}
+
+impl std::fmt::Debug for FaultManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FaultManager")
+            .field("config", &self.inner.config)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SchedulerConfig;
+    use crate::scheduler::Scheduler;
+
+    /// Faults recorded by `manager` whose message is `message`; other tests
+    /// panic concurrently and their panics reach every started manager.
+    fn faults_with_message(manager: &FaultManager, message: &str) -> Vec<FaultRecord> {
+        manager
+            .recent_faults(usize::MAX)
+            .into_iter()
+            .filter(|fault| fault.message == message)
+            .collect()
+    }
+
+    #[test]
+    fn worker_panics_are_recorded_and_the_scheduler_survives() {
+        let manager = FaultManager::default();
+        manager.start().unwrap();
+        let scheduler = Scheduler::new(SchedulerConfig {
+            worker_threads: 1,
+            ..SchedulerConfig::default()
+        })
+        .unwrap();
+        scheduler.start();
+
+        let failed = scheduler.spawn(|| panic!("worker fault under test"));
+        assert_eq!(
+            failed.join(),
+            Err(RuntimeError::TaskPanicked(
+                "worker fault under test".to_string()
+            ))
+        );
+        assert_eq!(scheduler.spawn(|| Ok(())).join(), Ok(()));
+
+        let faults = faults_with_message(&manager, "worker fault under test");
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].fault_type, FaultType::Panic);
+        assert!(faults[0].location.as_ref().unwrap().contains("fault.rs"));
+    }
+
+    #[test]
+    fn panics_while_the_registry_is_busy_are_still_recorded() {
+        let manager = FaultManager::default();
+        manager.start().unwrap();
+        let busy = STARTED.lock().unwrap_or_else(PoisonError::into_inner);
+        let worker = std::thread::spawn(|| panic!("contended fault under test"));
+        std::thread::sleep(Duration::from_millis(20));
+        drop(busy);
+        assert!(worker.join().is_err());
+        assert_eq!(
+            faults_with_message(&manager, "contended fault under test").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn stopped_managers_record_no_panics() {
+        let manager = FaultManager::default();
+        manager.start().unwrap();
+        manager.stop();
+        let _ = std::thread::spawn(|| panic!("unrecorded fault under test")).join();
+        assert!(faults_with_message(&manager, "unrecorded fault under test").is_empty());
+
+        let disabled = FaultManager::new(FaultConfig {
+            recover_from_panics: false,
+            ..FaultConfig::default()
+        });
+        assert!(matches!(
+            disabled.start(),
+            Err(RuntimeError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn retries_until_the_operation_succeeds() {
+        let manager = FaultManager::default();
+        let policy = RetryPolicy::new(3).with_backoff(Duration::ZERO, Duration::ZERO);
+        let mut attempts = 0;
+        let result = manager.with_retry(&policy, || {
+            attempts += 1;
+            if attempts < 3 {
+                Err(RuntimeError::EffectError(format!("attempt {}", attempts)))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result, Ok(3));
+        assert_eq!(manager.recent_faults(10).len(), 2);
+
+        let mut attempts = 0;
+        let result: Result<()> = manager.with_retry(&RetryPolicy::new(1), || {
+            attempts += 1;
+            Err(RuntimeError::EffectError("always".to_string()))
+        });
+        assert_eq!(result, Err(RuntimeError::EffectError("always".to_string())));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn jittered_delays_differ_and_stay_within_bounds() {
+        let policy = RetryPolicy::new(8)
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(500))
+            .with_jitter(1.0);
+        let first: Vec<_> = policy.delays().collect();
+        let second: Vec<_> = policy.clone().delays().collect();
+        assert_eq!(first.len(), 7);
+        assert_ne!(first, second);
+        for delay in first.iter().chain(&second) {
+            assert!(*delay <= Duration::from_millis(500));
+        }
+
+        let steady = RetryPolicy::new(5)
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(500))
+            .with_jitter(-3.0);
+        assert_eq!(
+            steady.delays().collect::<Vec<_>>(),
+            [100, 200, 400, 500].map(Duration::from_millis)
+        );
+    }
+
+    #[test]
+    fn cancellation_ends_the_retry_loop_during_a_backoff() {
+        let manager = FaultManager::default();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let policy = RetryPolicy::new(u32::MAX)
+            .with_backoff(Duration::from_secs(60), Duration::from_secs(60));
+        let mut attempts = 0;
+        let flag = Arc::clone(&cancelled);
+        let result: Result<()> = manager.with_retry_cancellable(&policy, cancelled, || {
+            attempts += 1;
+            flag.store(true, Ordering::SeqCst);
+            Err(RuntimeError::EffectError("down".to_string()))
+        });
+        assert!(matches!(result, Err(RuntimeError::Cancelled(_))));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn only_the_most_recent_faults_are_kept() {
+        let manager = FaultManager::new(FaultConfig {
+            max_recorded_faults: 2,
+            ..FaultConfig::default()
+        });
+        manager.record_fault(FaultType::Error, "first", None);
+        manager.record_error(&RuntimeError::MemoryError("second".to_string()));
+        let last = manager.record_fault(FaultType::Panic, "third", Some("here".to_string()));
+
+        let faults = manager.recent_faults(10);
+        assert_eq!(faults.len(), 2);
+        assert_eq!(faults[0].message, "memory error: second");
+        assert_eq!(faults[1].id, last);
+        assert_eq!(manager.recent_faults(1)[0].message, "third");
+    }
+}
@@ -1,14 +1,24 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Synapse UART: the Universal Abstract Representation Translator runtime.
+//!
+//! This crate hosts the runtime services compiled Synapse programs rely on:
+//! tracked memory management, a task scheduler with cooperative
+//! cancellation, capability-checked effects, and fault recording.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod config;
+pub mod effects;
+pub mod error;
+pub mod fault;
+pub mod memory;
+pub mod runtime;
+pub mod scheduler;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use config::{EffectConfig, RuntimeConfig, SchedulerConfig};
+pub use effects::{
+    CapabilityType, EffectCap, EffectHandler, EffectInvocation, EffectSystem,
+    register_scoped_handler, with_effects,
+};
+pub use error::{Result, RuntimeError};
+pub use fault::{FaultConfig, FaultManager, FaultRecord, FaultType, RetryPolicy};
+pub use memory::{MemoryConfig, MemoryManager, MemoryRegion, QBox, QRc, QWeak};
+pub use runtime::{UartRuntime, global, init_global};
+pub use scheduler::{CancellationToken, Priority, Scheduler, TaskHandle, TaskId, TypedTaskHandle};
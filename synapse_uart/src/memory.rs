@@ -0,0 +1,655 @@
+//! Tracked memory management for the UART runtime.
+//!
+//! Every allocation made through a [`MemoryManager`] is recorded as a
+//! [`MemoryBlock`], which lets the runtime enforce a memory budget and report
+//! leaks at shutdown. Two ownership disciplines are offered:
+//!
+//! * [`QBox`] uniquely owns its value and releases its block on drop.
+//! * [`QRc`] is shared and reference counted; the block is released when the
+//!   last strong handle goes away. [`QWeak`] observes a `QRc` without keeping
+//!   it alive, which is how runtime data structures express back-references
+//!   (parent pointers, observer lists) without leaking cycles.
+//!
+//! Allocations can also be grouped in a [`MemoryRegion`], which frees the
+//! blocks of its unique allocations in one go when it is dropped. A shared
+//! block still held by a [`QRc`] is left alone and freed by its last handle.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+
+use serde::Deserialize;
+
+use crate::error::{Result, RuntimeError};
+
+/// How a tracked block is owned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationStrategy {
+    /// Owned by exactly one [`QBox`].
+    Unique,
+    /// Shared between [`QRc`] handles and freed when the count reaches zero.
+    ReferenceCounted,
+}
+
+/// Configuration for a [`MemoryManager`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Upper bound on the total bytes tracked at once, if any.
+    pub max_memory: Option<usize>,
+    /// Whether reference-counted allocations ([`QRc`]) are permitted.
+    pub reference_counting: bool,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            max_memory: None,
+            reference_counting: true,
+        }
+    }
+}
+
+/// Bookkeeping record for a live allocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryBlock {
+    /// Unique identifier of the block within its manager.
+    pub id: u64,
+    /// Size of the allocated value in bytes.
+    pub size: usize,
+    /// Ownership discipline of the block.
+    pub strategy: AllocationStrategy,
+    /// Number of strong handles currently referring to the block.
+    pub ref_count: usize,
+}
+
+/// State shared by a manager and every handle it has produced.
+struct Inner {
+    config: MemoryConfig,
+    blocks: RwLock<HashMap<u64, MemoryBlock>>,
+    next_block_id: AtomicU64,
+    allocated_bytes: AtomicUsize,
+    /// The blocks allocated in each live region, by region id.
+    regions: RwLock<HashMap<u64, HashSet<u64>>>,
+    next_region_id: AtomicU64,
+}
+
+impl Inner {
+    fn register_block(&self, size: usize, strategy: AllocationStrategy) -> Result<u64> {
+        // Check and reserve in one step, so concurrent allocations cannot
+        // all pass the check and overshoot the limit together.
+        let limit = self.config.max_memory.unwrap_or(usize::MAX);
+        self.allocated_bytes
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+                current.checked_add(size).filter(|&total| total <= limit)
+            })
+            .map_err(|current| {
+                RuntimeError::MemoryError(format!(
+                    "allocation of {} bytes exceeds limit of {} bytes ({} in use)",
+                    size, limit, current
+                ))
+            })?;
+
+        let id = self.next_block_id.fetch_add(1, Ordering::SeqCst);
+        self.blocks.write().unwrap().insert(
+            id,
+            MemoryBlock {
+                id,
+                size,
+                strategy,
+                ref_count: 1,
+            },
+        );
+        Ok(id)
+    }
+
+    fn release_block(&self, id: u64) {
+        if let Some(block) = self.blocks.write().unwrap().remove(&id) {
+            self.allocated_bytes.fetch_sub(block.size, Ordering::SeqCst);
+        }
+    }
+
+    /// Releases a block when its region goes away, unless it is shared and
+    /// some [`QRc`] still holds it.
+    fn release_region_block(&self, id: u64) {
+        let mut blocks = self.blocks.write().unwrap();
+        let held = blocks.get(&id).is_some_and(|block| {
+            block.strategy == AllocationStrategy::ReferenceCounted && block.ref_count > 0
+        });
+        if !held && let Some(block) = blocks.remove(&id) {
+            self.allocated_bytes.fetch_sub(block.size, Ordering::SeqCst);
+        }
+    }
+
+    fn allocate<T>(self: &Arc<Self>, value: T) -> Result<QBox<T>> {
+        let block_id = self.register_block(std::mem::size_of::<T>(), AllocationStrategy::Unique)?;
+        Ok(QBox {
+            value: Box::new(value),
+            block_id,
+            manager: Arc::clone(self),
+        })
+    }
+
+    fn allocate_rc<T>(self: &Arc<Self>, value: T) -> Result<QRc<T>> {
+        if !self.config.reference_counting {
+            return Err(RuntimeError::ConfigError(
+                "reference counting is disabled in the memory configuration".to_string(),
+            ));
+        }
+
+        let block_id = self.register_block(
+            std::mem::size_of::<T>(),
+            AllocationStrategy::ReferenceCounted,
+        )?;
+        Ok(QRc {
+            cell: Arc::new(RcCell {
+                value,
+                block_id,
+                manager: Arc::clone(self),
+            }),
+        })
+    }
+
+    fn adjust_ref_count(&self, id: u64, increment: bool) {
+        if let Some(block) = self.blocks.write().unwrap().get_mut(&id) {
+            if increment {
+                block.ref_count += 1;
+            } else {
+                block.ref_count = block.ref_count.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Allocator that tracks every block it hands out.
+///
+/// Cloning a manager is cheap and yields a handle to the same tracking state.
+#[derive(Clone)]
+pub struct MemoryManager {
+    inner: Arc<Inner>,
+}
+
+impl MemoryManager {
+    /// Creates a manager with the given configuration.
+    pub fn new(config: MemoryConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                blocks: RwLock::new(HashMap::new()),
+                next_block_id: AtomicU64::new(1),
+                allocated_bytes: AtomicUsize::new(0),
+                regions: RwLock::new(HashMap::new()),
+                next_region_id: AtomicU64::new(1),
+            }),
+        }
+    }
+
+    /// Returns the configuration this manager was created with.
+    pub fn config(&self) -> &MemoryConfig {
+        &self.inner.config
+    }
+
+    /// Moves `value` into a uniquely owned, tracked allocation.
+    pub fn allocate<T>(&self, value: T) -> Result<QBox<T>> {
+        self.inner.allocate(value)
+    }
+
+    /// Moves `value` into a shared, reference-counted allocation.
+    ///
+    /// Fails if the manager was configured without reference counting.
+    pub fn allocate_rc<T>(&self, value: T) -> Result<QRc<T>> {
+        self.inner.allocate_rc(value)
+    }
+
+    /// Opens a region whose blocks are freed together when it is dropped.
+    pub fn region(&self) -> MemoryRegion {
+        let id = self.inner.next_region_id.fetch_add(1, Ordering::SeqCst);
+        self.inner
+            .regions
+            .write()
+            .unwrap()
+            .insert(id, HashSet::new());
+        MemoryRegion {
+            id,
+            manager: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Total bytes currently tracked.
+    pub fn allocated_bytes(&self) -> usize {
+        self.inner.allocated_bytes.load(Ordering::SeqCst)
+    }
+
+    /// Number of blocks currently live.
+    pub fn live_blocks(&self) -> usize {
+        self.inner.blocks.read().unwrap().len()
+    }
+
+    /// Returns the bookkeeping record for a live block.
+    pub fn block(&self, id: u64) -> Option<MemoryBlock> {
+        self.inner.blocks.read().unwrap().get(&id).cloned()
+    }
+
+    /// Returns every block that is still live, ordered by id.
+    ///
+    /// Called at shutdown, a non-empty result indicates leaked allocations.
+    pub fn check_leaks(&self) -> Vec<MemoryBlock> {
+        let mut leaked: Vec<MemoryBlock> =
+            self.inner.blocks.read().unwrap().values().cloned().collect();
+        leaked.sort_by_key(|block| block.id);
+        leaked
+    }
+}
+
+impl Default for MemoryManager {
+    fn default() -> Self {
+        Self::new(MemoryConfig::default())
+    }
+}
+
+impl fmt::Debug for MemoryManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryManager")
+            .field("config", &self.inner.config)
+            .field("live_blocks", &self.live_blocks())
+            .field("allocated_bytes", &self.allocated_bytes())
+            .finish()
+    }
+}
+
+/// A group of allocations released together.
+///
+/// Dropping the region releases the blocks of everything allocated in it
+/// that is still tracked, except shared blocks that a [`QRc`] still holds:
+/// those stay tracked until their last handle is dropped.
+pub struct MemoryRegion {
+    id: u64,
+    manager: Arc<Inner>,
+}
+
+impl MemoryRegion {
+    /// Like [`MemoryManager::allocate`], with the block owned by the region.
+    pub fn allocate<T>(&self, value: T) -> Result<QBox<T>> {
+        let boxed = self.manager.allocate(value)?;
+        self.add(boxed.block_id);
+        Ok(boxed)
+    }
+
+    /// Like [`MemoryManager::allocate_rc`], with the block owned by the
+    /// region for as long as no handle holds it.
+    pub fn allocate_rc<T>(&self, value: T) -> Result<QRc<T>> {
+        let shared = self.manager.allocate_rc(value)?;
+        self.add(shared.block_id());
+        Ok(shared)
+    }
+
+    fn add(&self, block_id: u64) {
+        if let Some(blocks) = self.manager.regions.write().unwrap().get_mut(&self.id) {
+            blocks.insert(block_id);
+        }
+    }
+}
+
+impl Drop for MemoryRegion {
+    fn drop(&mut self) {
+        let blocks = self.manager.regions.write().unwrap().remove(&self.id);
+        for block_id in blocks.into_iter().flatten() {
+            self.manager.release_region_block(block_id);
+        }
+    }
+}
+
+impl fmt::Debug for MemoryRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryRegion")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+/// A uniquely owned, tracked allocation.
+pub struct QBox<T> {
+    value: Box<T>,
+    block_id: u64,
+    manager: Arc<Inner>,
+}
+
+impl<T> QBox<T> {
+    /// Identifier of the block backing this allocation.
+    pub fn block_id(&self) -> u64 {
+        self.block_id
+    }
+}
+
+impl<T> Deref for QBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for QBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Drop for QBox<T> {
+    fn drop(&mut self) {
+        self.manager.release_block(self.block_id);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for QBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QBox")
+            .field("block_id", &self.block_id)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+/// Storage shared by all handles of one reference-counted allocation.
+///
+/// The block is released when the cell itself is dropped, i.e. once the last
+/// strong handle is gone.
+struct RcCell<T> {
+    value: T,
+    block_id: u64,
+    manager: Arc<Inner>,
+}
+
+impl<T> Drop for RcCell<T> {
+    fn drop(&mut self) {
+        self.manager.release_block(self.block_id);
+    }
+}
+
+/// A shared, reference-counted, tracked allocation.
+///
+/// The strong count is mirrored into the backing [`MemoryBlock`] so leak
+/// reports show how many handles are keeping a block alive.
+pub struct QRc<T> {
+    cell: Arc<RcCell<T>>,
+}
+
+impl<T> QRc<T> {
+    /// Identifier of the block backing this allocation.
+    pub fn block_id(&self) -> u64 {
+        self.cell.block_id
+    }
+
+    /// Number of strong handles to this allocation.
+    pub fn strong_count(this: &Self) -> usize {
+        Arc::strong_count(&this.cell)
+    }
+
+    /// Creates a weak handle that does not keep the allocation alive.
+    pub fn downgrade(this: &Self) -> QWeak<T> {
+        QWeak {
+            cell: Arc::downgrade(&this.cell),
+        }
+    }
+
+    /// Returns `true` if both handles refer to the same allocation.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(&this.cell, &other.cell)
+    }
+}
+
+impl<T> Clone for QRc<T> {
+    fn clone(&self) -> Self {
+        self.cell.manager.adjust_ref_count(self.cell.block_id, true);
+        Self {
+            cell: Arc::clone(&self.cell),
+        }
+    }
+}
+
+impl<T> Drop for QRc<T> {
+    fn drop(&mut self) {
+        self.cell.manager.adjust_ref_count(self.cell.block_id, false);
+    }
+}
+
+impl<T> Deref for QRc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.cell.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for QRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QRc")
+            .field("block_id", &self.cell.block_id)
+            .field("value", &self.cell.value)
+            .finish()
+    }
+}
+
+/// A non-owning reference to a [`QRc`] allocation.
+pub struct QWeak<T> {
+    cell: Weak<RcCell<T>>,
+}
+
+impl<T> QWeak<T> {
+    /// Returns a strong handle if the allocation is still live.
+    pub fn upgrade(&self) -> Option<QRc<T>> {
+        let cell = self.cell.upgrade()?;
+        cell.manager.adjust_ref_count(cell.block_id, true);
+        Some(QRc { cell })
+    }
+}
+
+impl<T> Clone for QWeak<T> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: Weak::clone(&self.cell),
+        }
+    }
+}
+
+impl<T> fmt::Debug for QWeak<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("QWeak")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct TreeNode {
+        parent: Mutex<Option<QWeak<TreeNode>>>,
+        children: Mutex<Vec<QRc<TreeNode>>>,
+    }
+
+    impl TreeNode {
+        fn new() -> Self {
+            Self {
+                parent: Mutex::new(None),
+                children: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[test]
+    fn qbox_releases_block_on_drop() {
+        let manager = MemoryManager::default();
+        let boxed = manager.allocate(42u64).unwrap();
+        assert_eq!(*boxed, 42);
+        assert_eq!(manager.live_blocks(), 1);
+        assert_eq!(manager.allocated_bytes(), 8);
+
+        drop(boxed);
+        assert!(manager.check_leaks().is_empty());
+        assert_eq!(manager.allocated_bytes(), 0);
+    }
+
+    #[test]
+    fn clones_share_tracking_so_leaks_are_reported() {
+        let manager = MemoryManager::default();
+        let clone = manager.clone();
+        let kept = clone.allocate(1u32).unwrap();
+        let leaked = clone.allocate(2u64).unwrap();
+        let leaked_id = leaked.block_id();
+        assert_eq!(manager.live_blocks(), 2);
+
+        drop(kept);
+        std::mem::forget(leaked);
+        assert_eq!(
+            manager.check_leaks(),
+            [MemoryBlock {
+                id: leaked_id,
+                size: 8,
+                strategy: AllocationStrategy::Unique,
+                ref_count: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn allocation_respects_memory_limit() {
+        let manager = MemoryManager::new(MemoryConfig {
+            max_memory: Some(8),
+            ..MemoryConfig::default()
+        });
+        let _first = manager.allocate(1u64).unwrap();
+        assert!(matches!(
+            manager.allocate(2u64),
+            Err(RuntimeError::MemoryError(_))
+        ));
+    }
+
+    #[test]
+    fn concurrent_allocations_never_exceed_the_limit() {
+        let manager = MemoryManager::new(MemoryConfig {
+            max_memory: Some(8 * 10),
+            ..MemoryConfig::default()
+        });
+        let barrier = std::sync::Barrier::new(8);
+        let granted: Vec<Vec<QBox<u64>>> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        (0..5)
+                            .filter_map(|i| manager.allocate(i as u64).ok())
+                            .collect()
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+        assert_eq!(granted.iter().map(Vec::len).sum::<usize>(), 10);
+        assert_eq!(manager.allocated_bytes(), 80);
+    }
+
+    #[test]
+    fn rc_requires_reference_counting() {
+        let manager = MemoryManager::new(MemoryConfig {
+            reference_counting: false,
+            ..MemoryConfig::default()
+        });
+        assert!(matches!(
+            manager.allocate_rc(1u8),
+            Err(RuntimeError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn rc_count_is_mirrored_into_block() {
+        let manager = MemoryManager::default();
+        let first = manager.allocate_rc(7i32).unwrap();
+        let second = first.clone();
+        assert_eq!(manager.block(first.block_id()).unwrap().ref_count, 2);
+
+        drop(second);
+        assert_eq!(manager.block(first.block_id()).unwrap().ref_count, 1);
+
+        drop(first);
+        assert!(manager.check_leaks().is_empty());
+    }
+
+    #[test]
+    fn leaked_rc_handles_are_reported_with_their_count() {
+        let manager = MemoryManager::default();
+        let first = manager.allocate_rc(3u16).unwrap();
+        let id = first.block_id();
+        std::mem::forget(first.clone());
+        drop(first);
+
+        let leaked = manager.check_leaks();
+        assert_eq!(leaked.len(), 1);
+        assert_eq!(leaked[0].id, id);
+        assert_eq!(leaked[0].strategy, AllocationStrategy::ReferenceCounted);
+        assert_eq!(leaked[0].ref_count, 1);
+    }
+
+    #[test]
+    fn region_drop_spares_rc_blocks_that_are_still_held() {
+        let manager = MemoryManager::default();
+        let region = manager.region();
+        let boxed = region.allocate(1u64).unwrap();
+        let shared = region.allocate_rc(2u32).unwrap();
+        let released = region.allocate_rc(3u16).unwrap();
+        drop(released);
+        assert_eq!(manager.live_blocks(), 2);
+
+        drop(region);
+        // The unique block goes with the region; the held one stays.
+        assert_eq!(manager.live_blocks(), 1);
+        assert_eq!(manager.allocated_bytes(), 4);
+        // Its handle outlives the block without releasing it twice.
+        drop(boxed);
+        assert_eq!(manager.allocated_bytes(), 4);
+        let block = manager.block(shared.block_id()).unwrap();
+        assert_eq!(block.ref_count, 1);
+        assert_eq!(*shared, 2);
+
+        drop(shared);
+        assert!(manager.check_leaks().is_empty());
+        assert_eq!(manager.allocated_bytes(), 0);
+    }
+
+    #[test]
+    fn weak_upgrade_fails_once_freed() {
+        let manager = MemoryManager::default();
+        let strong = manager.allocate_rc(String::from("value")).unwrap();
+        let weak = QRc::downgrade(&strong);
+
+        let upgraded = weak.upgrade().expect("allocation is still live");
+        assert_eq!(&*upgraded, "value");
+        assert_eq!(QRc::strong_count(&strong), 2);
+        drop(upgraded);
+
+        drop(strong);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(manager.live_blocks(), 0);
+    }
+
+    #[test]
+    fn parent_child_cycle_via_weak_is_freed() {
+        let manager = MemoryManager::default();
+        let parent = manager.allocate_rc(TreeNode::new()).unwrap();
+        let child = manager.allocate_rc(TreeNode::new()).unwrap();
+
+        *child.parent.lock().unwrap() = Some(QRc::downgrade(&parent));
+        parent.children.lock().unwrap().push(child.clone());
+        assert_eq!(manager.live_blocks(), 2);
+
+        let back_ref = child.parent.lock().unwrap().as_ref().unwrap().upgrade();
+        assert!(QRc::ptr_eq(&back_ref.unwrap(), &parent));
+
+        drop(child);
+        drop(parent);
+        assert!(manager.check_leaks().is_empty());
+        assert_eq!(manager.allocated_bytes(), 0);
+    }
+}
@@ -0,0 +1,174 @@
+//! The runtime instance and the process-wide global.
+
+use std::sync::OnceLock;
+
+use crate::config::RuntimeConfig;
+use crate::effects::EffectSystem;
+use crate::error::{Result, RuntimeError};
+use crate::fault::FaultManager;
+use crate::memory::MemoryManager;
+use crate::scheduler::Scheduler;
+
+/// A configured set of runtime services.
+pub struct UartRuntime {
+    config: RuntimeConfig,
+    memory: MemoryManager,
+    effects: EffectSystem,
+    faults: FaultManager,
+    scheduler: Scheduler,
+}
+
+impl UartRuntime {
+    /// Creates a runtime, rejecting invalid configurations, and starts its
+    /// scheduler's workers. Panics are recorded as faults unless the fault
+    /// configuration disables it.
+    pub fn new(config: RuntimeConfig) -> Result<Self> {
+        config.validate()?;
+        let scheduler = Scheduler::new(config.scheduler_config.clone())?;
+        let faults = FaultManager::new(config.fault_config.clone());
+        if config.fault_config.recover_from_panics {
+            faults.start()?;
+        }
+        scheduler.start();
+        Ok(Self {
+            faults,
+            scheduler,
+            memory: MemoryManager::new(config.memory_config.clone()),
+            effects: EffectSystem::with_builtins(config.effect_config.clone()),
+            config,
+        })
+    }
+
+    /// The configuration the runtime was started with.
+    pub fn config(&self) -> &RuntimeConfig {
+        &self.config
+    }
+
+    /// The runtime's memory manager.
+    pub fn memory(&self) -> &MemoryManager {
+        &self.memory
+    }
+
+    /// The runtime's effect handlers and capability policy.
+    pub fn effects(&self) -> &EffectSystem {
+        &self.effects
+    }
+
+    /// The runtime's fault records.
+    pub fn faults(&self) -> &FaultManager {
+        &self.faults
+    }
+
+    /// The scheduler running the runtime's tasks.
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+}
+
+/// A runtime that is created once, either explicitly or on first use.
+struct GlobalRuntime {
+    cell: OnceLock<UartRuntime>,
+}
+
+impl GlobalRuntime {
+    const fn new() -> Self {
+        Self {
+            cell: OnceLock::new(),
+        }
+    }
+
+    fn init(&self, config: RuntimeConfig) -> Result<()> {
+        // Checked first so a rejected init does not start workers and a
+        // panic hook only to throw them away.
+        if self.cell.get().is_some() {
+            return Err(already_initialized());
+        }
+        let runtime = UartRuntime::new(config)?;
+        self.cell.set(runtime).map_err(|_| already_initialized())
+    }
+
+    fn get(&self) -> &UartRuntime {
+        self.cell.get_or_init(|| {
+            UartRuntime::new(RuntimeConfig::default()).expect("default configuration is valid")
+        })
+    }
+}
+
+fn already_initialized() -> RuntimeError {
+    RuntimeError::ConfigError("the global runtime is already initialized".to_string())
+}
+
+static GLOBAL: GlobalRuntime = GlobalRuntime::new();
+
+/// Installs the process-wide runtime with `config`.
+///
+/// Must run before the first call to [`global`]; afterwards (or on a second
+/// call) it fails with [`RuntimeError::ConfigError`] and leaves the existing
+/// runtime in place.
+pub fn init_global(config: RuntimeConfig) -> Result<()> {
+    GLOBAL.init(config)
+}
+
+/// The process-wide runtime, started with the default configuration if
+/// [`init_global`] was never called.
+pub fn global() -> &'static UartRuntime {
+    GLOBAL.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SchedulerConfig;
+
+    fn with_workers(worker_threads: usize) -> RuntimeConfig {
+        RuntimeConfig {
+            scheduler_config: SchedulerConfig {
+                worker_threads,
+                ..SchedulerConfig::default()
+            },
+            ..RuntimeConfig::default()
+        }
+    }
+
+    #[test]
+    fn init_before_first_use_takes_effect() {
+        let global = GlobalRuntime::new();
+        assert_eq!(global.init(with_workers(3)), Ok(()));
+        assert_eq!(global.get().scheduler().worker_count(), 3);
+        assert!(matches!(
+            global.init(with_workers(5)),
+            Err(RuntimeError::ConfigError(_))
+        ));
+        // The slot is checked before the configuration, so no second
+        // runtime is built just to be rejected.
+        assert_eq!(global.init(with_workers(0)), Err(already_initialized()));
+        let handle = global.get().scheduler().spawn(|| Ok(()));
+        assert_eq!(handle.join(), Ok(()));
+    }
+
+    #[test]
+    fn init_after_first_use_is_an_error() {
+        let global = GlobalRuntime::new();
+        let default_workers = global.get().scheduler().worker_count();
+        assert_eq!(
+            default_workers,
+            RuntimeConfig::default().scheduler_config.worker_threads
+        );
+        assert!(matches!(
+            global.init(with_workers(default_workers + 1)),
+            Err(RuntimeError::ConfigError(_))
+        ));
+        assert_eq!(global.get().scheduler().worker_count(), default_workers);
+    }
+
+    #[test]
+    fn invalid_configuration_is_rejected() {
+        let global = GlobalRuntime::new();
+        assert!(matches!(
+            global.init(with_workers(0)),
+            Err(RuntimeError::ConfigError(_))
+        ));
+        // A failed init leaves the slot free for a valid one.
+        assert_eq!(global.init(with_workers(1)), Ok(()));
+    }
+}
@@ -0,0 +1,881 @@
+//! A pool of worker threads running submitted tasks.
+//!
+//! Every task carries a [`CancellationToken`]. Cancelling a task through its
+//! [`TaskHandle`] sets the token; a task that has not started yet is never
+//! run, and a running task observes the token through
+//! [`helpers::current_cancellation`], which effect invocations pass on to
+//! their handlers.
+//!
+//! Tasks spawned by a task go to the deque of the worker running it, and
+//! all others to a shared injector; each holds one FIFO per [`Priority`].
+//! A worker looks for a task at the highest priority anything is queued
+//! at, counted across all deques, and takes the oldest of its own, then of
+//! the injector, then steals the oldest of another worker's. So a
+//! `Critical` task never waits behind `Background` ones that have not
+//! started, wherever either is queued, and only the deques holding work at
+//! that priority are locked. [`TaskHandle::set_priority`] moves a task
+//! that has not started to another priority's FIFO of the same deque.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::config::SchedulerConfig;
+use crate::error::{Result, RuntimeError};
+
+/// A shared flag telling in-flight work to stop.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation of everything holding a clone of this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Interruption point: fails with [`RuntimeError::Cancelled`] once the
+    /// token has been cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(RuntimeError::Cancelled(
+                "cancellation was requested".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Identifier of a submitted task.
+pub type TaskId = u64;
+
+/// How urgently a task should run, from lowest to highest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Priority {
+    Background,
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+impl Priority {
+    /// Every priority, from lowest to highest.
+    pub const ALL: [Priority; 5] = [
+        Priority::Background,
+        Priority::Low,
+        Priority::Normal,
+        Priority::High,
+        Priority::Critical,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+type TaskBody = Box<dyn FnOnce() -> Result<()> + Send>;
+
+/// Completion state shared between a task and its handle.
+struct TaskState {
+    token: CancellationToken,
+    priority: Mutex<Priority>,
+    result: Mutex<Option<Result<()>>>,
+    finished: Condvar,
+}
+
+impl TaskState {
+    fn complete(&self, result: Result<()>) {
+        *self.result.lock().unwrap() = Some(result);
+        self.finished.notify_all();
+    }
+}
+
+/// A handle to a submitted task.
+#[derive(Clone)]
+pub struct TaskHandle {
+    id: TaskId,
+    state: Arc<TaskState>,
+    shared: Weak<Shared>,
+}
+
+impl TaskHandle {
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Cancels the task. A task that has not started is taken off the queue
+    /// and finishes at once with [`RuntimeError::Cancelled`]; a running task
+    /// stops at its next interruption point, such as a poll of
+    /// [`helpers::is_current_cancelled`]. Cancelling a finished task does
+    /// nothing.
+    pub fn cancel(&self) {
+        if self.is_finished() {
+            return;
+        }
+        self.state.token.cancel();
+        let Some(shared) = self.shared.upgrade() else {
+            return;
+        };
+        let priority = self.state.priority.lock().unwrap();
+        if let Some(task) = shared.remove(self.id, *priority) {
+            task.state.complete(Err(not_started(task.id)));
+            shared.notify_if_idle();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.token.is_cancelled()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.result.lock().unwrap().is_some()
+    }
+
+    pub fn priority(&self) -> Priority {
+        *self.state.priority.lock().unwrap()
+    }
+
+    /// Moves the task to the back of the `priority` FIFO of the deque it is
+    /// queued in. Returns `false`, leaving the priority unchanged, if the
+    /// task has already started.
+    pub fn set_priority(&self, priority: Priority) -> bool {
+        let Some(shared) = self.shared.upgrade() else {
+            return false;
+        };
+        let mut current = self.state.priority.lock().unwrap();
+        if !shared.requeue(self.id, *current, priority) {
+            return false;
+        }
+        *current = priority;
+        true
+    }
+
+    /// Blocks until the task has finished and returns its result.
+    pub fn join(&self) -> Result<()> {
+        let mut result = self.state.result.lock().unwrap();
+        while result.is_none() {
+            result = self.state.finished.wait(result).unwrap();
+        }
+        result.clone().expect("checked above")
+    }
+
+    /// Like [`TaskHandle::join`], but gives up after `timeout`, returning
+    /// `None` if the task is still unfinished. The result is kept, so a
+    /// later join still receives it.
+    pub fn join_timeout(&self, timeout: Duration) -> Option<Result<()>> {
+        let result = self.state.result.lock().unwrap();
+        let (result, _) = self
+            .state
+            .finished
+            .wait_timeout_while(result, timeout, |result| result.is_none())
+            .unwrap();
+        result.clone()
+    }
+}
+
+/// A handle to a task that produces a value of type `T`.
+pub struct TypedTaskHandle<T> {
+    handle: TaskHandle,
+    value: Arc<Mutex<Option<T>>>,
+}
+
+impl<T> TypedTaskHandle<T> {
+    /// The untyped handle, for cancellation and priority changes.
+    pub fn handle(&self) -> &TaskHandle {
+        &self.handle
+    }
+
+    /// Blocks until the task has finished and returns its value.
+    pub fn join(self) -> Result<T> {
+        self.handle.join()?;
+        Ok(self.take_value())
+    }
+
+    /// Like [`TypedTaskHandle::join`], but gives up after `timeout` and
+    /// hands the handle back, so the join can be retried.
+    pub fn join_timeout(self, timeout: Duration) -> std::result::Result<Result<T>, Self> {
+        match self.handle.join_timeout(timeout) {
+            None => Err(self),
+            Some(Err(error)) => Ok(Err(error)),
+            Some(Ok(())) => Ok(Ok(self.take_value())),
+        }
+    }
+
+    fn take_value(&self) -> T {
+        self.value
+            .lock()
+            .unwrap()
+            .take()
+            .expect("a task that succeeded stored its value")
+    }
+}
+
+struct Task {
+    id: TaskId,
+    body: TaskBody,
+    state: Arc<TaskState>,
+}
+
+impl Task {
+    fn execute(self) {
+        // Cancelled after a worker took it off the queue.
+        let result = if self.state.token.is_cancelled() {
+            Err(not_started(self.id))
+        } else {
+            helpers::with_current_token(self.state.token.clone(), || {
+                panic::catch_unwind(AssertUnwindSafe(self.body)).unwrap_or_else(|payload| {
+                    Err(RuntimeError::TaskPanicked(panic_message(&payload)))
+                })
+            })
+        };
+        self.state.complete(result);
+    }
+}
+
+fn not_started(id: TaskId) -> RuntimeError {
+    RuntimeError::Cancelled(format!("task {} was cancelled before it started", id))
+}
+
+fn panic_message(payload: &Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
+
+/// Tasks not yet started, one FIFO per priority.
+type Deque = [VecDeque<Task>; Priority::ALL.len()];
+
+/// Takes task `id` out of the `priority` FIFO of `deque`, if it is there.
+fn take_out(deque: &mut Deque, id: TaskId, priority: Priority) -> Option<Task> {
+    let tasks = &mut deque[priority.index()];
+    let index = tasks.iter().position(|task| task.id == id)?;
+    tasks.remove(index)
+}
+
+struct Shared {
+    /// The injector, for tasks spawned outside the workers, followed by
+    /// each worker's own deque.
+    deques: Vec<Mutex<Deque>>,
+    /// Tasks queued at each priority, over all deques. Raised after a task
+    /// is pushed and lowered as it is taken, under the deque's lock.
+    queued: [AtomicUsize; Priority::ALL.len()],
+    running_count: AtomicUsize,
+    /// Set when the scheduler shuts down. Its lock is held to check for
+    /// work before sleeping and for idleness before waiting on `idle`.
+    shutdown: Mutex<bool>,
+    /// Signalled when a task is queued or the scheduler shuts down.
+    available: Condvar,
+    /// Signalled when no task is queued or running.
+    idle: Condvar,
+    next_task_id: AtomicU64,
+}
+
+/// Index of the injector in [`Shared::deques`].
+const INJECTOR: usize = 0;
+
+thread_local! {
+    /// The scheduler, by address, and deque of the worker on this thread.
+    static CURRENT_WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+impl Shared {
+    fn queue_depth(&self) -> usize {
+        self.queued
+            .iter()
+            .map(|count| count.load(Ordering::SeqCst))
+            .sum()
+    }
+
+    fn is_idle(&self) -> bool {
+        self.queue_depth() == 0 && self.running_count.load(Ordering::SeqCst) == 0
+    }
+
+    /// The deque a task spawned on this thread goes to: the worker's own,
+    /// or the injector off the workers.
+    fn local_deque(&self) -> usize {
+        match CURRENT_WORKER.get() {
+            Some((scheduler, deque)) if scheduler == self as *const Shared as usize => deque,
+            _ => INJECTOR,
+        }
+    }
+
+    fn push(&self, deque: usize, priority: Priority, task: Task) {
+        self.deques[deque].lock().unwrap()[priority.index()].push_back(task);
+        self.queued[priority.index()].fetch_add(1, Ordering::SeqCst);
+        drop(self.shutdown.lock().unwrap());
+        self.available.notify_one();
+    }
+
+    /// Takes the next task for the worker owning deque `own`, counted as
+    /// running: the oldest at the highest priority queued anywhere, from
+    /// its own deque, the injector or, failing both, another worker's.
+    fn pop(&self, own: usize) -> Option<Task> {
+        let others = (own + 1..self.deques.len()).chain(1..own);
+        let order: Vec<usize> = [own, INJECTOR].into_iter().chain(others).collect();
+        Priority::ALL.iter().rev().find_map(|&priority| {
+            if self.queued[priority.index()].load(Ordering::SeqCst) == 0 {
+                return None;
+            }
+            order.iter().find_map(|&deque| {
+                let mut deque = self.deques[deque].lock().unwrap();
+                let task = deque[priority.index()].pop_front()?;
+                // Count the task as running before it leaves the queued
+                // count, so `is_idle` never sees it in neither.
+                self.running_count.fetch_add(1, Ordering::SeqCst);
+                self.queued[priority.index()].fetch_sub(1, Ordering::SeqCst);
+                Some(task)
+            })
+        })
+    }
+
+    /// Takes task `id` off whichever deque holds it, if it has not started.
+    fn remove(&self, id: TaskId, priority: Priority) -> Option<Task> {
+        self.deques.iter().find_map(|deque| {
+            let task = take_out(&mut deque.lock().unwrap(), id, priority)?;
+            self.queued[priority.index()].fetch_sub(1, Ordering::SeqCst);
+            Some(task)
+        })
+    }
+
+    /// Moves task `id` from the `from` FIFO to the back of the `to` FIFO
+    /// of its deque. Returns `false` if it has started.
+    fn requeue(&self, id: TaskId, from: Priority, to: Priority) -> bool {
+        self.deques.iter().any(|deque| {
+            let mut deque = deque.lock().unwrap();
+            let Some(task) = take_out(&mut deque, id, from) else {
+                return false;
+            };
+            deque[to.index()].push_back(task);
+            self.queued[to.index()].fetch_add(1, Ordering::SeqCst);
+            self.queued[from.index()].fetch_sub(1, Ordering::SeqCst);
+            true
+        })
+    }
+
+    fn notify_if_idle(&self) {
+        if self.is_idle() {
+            drop(self.shutdown.lock().unwrap());
+            self.idle.notify_all();
+        }
+    }
+}
+
+/// Runs tasks on a fixed number of worker threads.
+pub struct Scheduler {
+    config: SchedulerConfig,
+    shared: Arc<Shared>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Scheduler {
+    /// Creates a scheduler; no threads run until [`Scheduler::start`].
+    pub fn new(config: SchedulerConfig) -> Result<Self> {
+        if config.worker_threads == 0 {
+            return Err(RuntimeError::ConfigError(
+                "scheduler needs at least one worker thread".to_string(),
+            ));
+        }
+        let worker_threads = config.worker_threads;
+        Ok(Self {
+            config,
+            shared: Arc::new(Shared {
+                deques: (0..=worker_threads).map(|_| Mutex::default()).collect(),
+                queued: Default::default(),
+                running_count: AtomicUsize::new(0),
+                shutdown: Mutex::new(false),
+                available: Condvar::new(),
+                idle: Condvar::new(),
+                next_task_id: AtomicU64::new(1),
+            }),
+            workers: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// Starts the worker threads. Calling it again has no effect.
+    pub fn start(&self) {
+        let mut workers = self.workers.lock().unwrap();
+        if !workers.is_empty() {
+            return;
+        }
+        for deque in 1..=self.config.worker_threads {
+            let shared = Arc::clone(&self.shared);
+            workers.push(std::thread::spawn(move || worker_loop(&shared, deque)));
+        }
+    }
+
+    /// Queues `body` to run on a worker at [`Priority::Normal`].
+    pub fn spawn(&self, body: impl FnOnce() -> Result<()> + Send + 'static) -> TaskHandle {
+        self.spawn_with_priority(Priority::default(), body)
+    }
+
+    /// Queues `body` to run on a worker at [`Priority::Normal`], keeping the
+    /// value it returns for [`TypedTaskHandle::join`].
+    pub fn spawn_returning<T: Send + 'static>(
+        &self,
+        body: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> TypedTaskHandle<T> {
+        let value = Arc::new(Mutex::new(None));
+        let slot = Arc::clone(&value);
+        let handle = self.spawn(move || {
+            *slot.lock().unwrap() = Some(body()?);
+            Ok(())
+        });
+        TypedTaskHandle { handle, value }
+    }
+
+    /// Queues `body` to run on a worker once no higher-priority task waits.
+    /// Called from a task, it queues on the deque of that task's worker.
+    pub fn spawn_with_priority(
+        &self,
+        priority: Priority,
+        body: impl FnOnce() -> Result<()> + Send + 'static,
+    ) -> TaskHandle {
+        let id = self.shared.next_task_id.fetch_add(1, Ordering::SeqCst);
+        let state = Arc::new(TaskState {
+            token: CancellationToken::new(),
+            priority: Mutex::new(priority),
+            result: Mutex::new(None),
+            finished: Condvar::new(),
+        });
+        let task = Task {
+            id,
+            body: Box::new(body),
+            state: Arc::clone(&state),
+        };
+        self.shared.push(self.shared.local_deque(), priority, task);
+        TaskHandle {
+            id,
+            state,
+            shared: Arc::downgrade(&self.shared),
+        }
+    }
+
+    /// Worker threads currently running; zero before [`Scheduler::start`]
+    /// and after [`Scheduler::shutdown`].
+    pub fn worker_count(&self) -> usize {
+        self.workers.lock().unwrap().len()
+    }
+
+    /// Tasks submitted but not yet started.
+    pub fn queue_depth(&self) -> usize {
+        self.shared.queue_depth()
+    }
+
+    /// Tasks currently executing on a worker.
+    pub fn running_count(&self) -> usize {
+        self.shared.running_count.load(Ordering::SeqCst)
+    }
+
+    /// Whether no task is queued or running.
+    pub fn is_idle(&self) -> bool {
+        self.shared.is_idle()
+    }
+
+    /// Blocks until every queued task has finished.
+    pub fn run_until_idle(&self) {
+        let mut shutdown = self.shared.shutdown.lock().unwrap();
+        while !self.shared.is_idle() {
+            shutdown = self.shared.idle.wait(shutdown).unwrap();
+        }
+    }
+
+    /// Stops the workers after the tasks already queued have run.
+    pub fn shutdown(&self) {
+        *self.shared.shutdown.lock().unwrap() = true;
+        self.shared.available.notify_all();
+        for worker in self.workers.lock().unwrap().drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn worker_loop(shared: &Shared, deque: usize) {
+    CURRENT_WORKER.set(Some((shared as *const Shared as usize, deque)));
+    loop {
+        let Some(task) = shared.pop(deque) else {
+            let shutdown = shared.shutdown.lock().unwrap();
+            if shared.queue_depth() > 0 {
+                continue;
+            }
+            if *shutdown {
+                return;
+            }
+            drop(shared.available.wait(shutdown).unwrap());
+            continue;
+        };
+        task.execute();
+        shared.running_count.fetch_sub(1, Ordering::SeqCst);
+        shared.notify_if_idle();
+    }
+}
+
+/// Access to the task running on the current thread.
+pub mod helpers {
+    use super::*;
+
+    thread_local! {
+        static CURRENT_TOKEN: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+    }
+
+    pub(super) fn with_current_token<R>(token: CancellationToken, f: impl FnOnce() -> R) -> R {
+        let previous = CURRENT_TOKEN.with(|current| current.replace(Some(token)));
+        let result = f();
+        CURRENT_TOKEN.with(|current| *current.borrow_mut() = previous);
+        result
+    }
+
+    /// The cancellation token of the task running on this thread, or a
+    /// token that is never cancelled outside of a task.
+    pub fn current_cancellation() -> CancellationToken {
+        CURRENT_TOKEN.with(|current| current.borrow().clone().unwrap_or_default())
+    }
+
+    /// Whether the task running on this thread has been cancelled.
+    pub fn is_current_cancelled() -> bool {
+        CURRENT_TOKEN.with(|current| {
+            current
+                .borrow()
+                .as_ref()
+                .is_some_and(CancellationToken::is_cancelled)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn scheduler(worker_threads: usize) -> Scheduler {
+        let scheduler = Scheduler::new(SchedulerConfig {
+            worker_threads,
+            ..SchedulerConfig::default()
+        })
+        .unwrap();
+        scheduler.start();
+        scheduler
+    }
+
+    #[test]
+    fn spawned_tasks_all_run() {
+        let scheduler = scheduler(4);
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..50)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                scheduler.spawn(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                })
+            })
+            .collect();
+        scheduler.run_until_idle();
+        assert_eq!(counter.load(Ordering::SeqCst), 50);
+        assert!(handles.iter().all(|handle| handle.join().is_ok()));
+    }
+
+    #[test]
+    fn tasks_spawned_from_many_threads_all_reach_the_workers() {
+        let scheduler = Arc::new(scheduler(4));
+        let counter = Arc::new(AtomicUsize::new(0));
+        let submitters: Vec<_> = (0..4)
+            .map(|_| {
+                let (scheduler, counter) = (Arc::clone(&scheduler), Arc::clone(&counter));
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        let counter = Arc::clone(&counter);
+                        scheduler.spawn(move || {
+                            counter.fetch_add(1, Ordering::SeqCst);
+                            Ok(())
+                        });
+                    }
+                })
+            })
+            .collect();
+        for submitter in submitters {
+            submitter.join().unwrap();
+        }
+        scheduler.run_until_idle();
+        assert_eq!(counter.load(Ordering::SeqCst), 100);
+        assert!(scheduler.is_idle());
+    }
+
+    #[test]
+    fn queue_depth_drains_to_zero() {
+        let scheduler = scheduler(1);
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let (started, running) = std::sync::mpsc::channel();
+        scheduler.spawn(move || {
+            started.send(()).unwrap();
+            blocked.recv().unwrap();
+            Ok(())
+        });
+        running.recv().unwrap();
+        let quick: Vec<_> = (0..10).map(|_| scheduler.spawn(|| Ok(()))).collect();
+
+        assert_eq!(scheduler.running_count(), 1);
+        assert_eq!(scheduler.queue_depth(), 10);
+        assert!(!scheduler.is_idle());
+
+        release.send(()).unwrap();
+        scheduler.run_until_idle();
+        assert!(quick.iter().all(TaskHandle::is_finished));
+        assert_eq!(scheduler.queue_depth(), 0);
+        assert_eq!(scheduler.running_count(), 0);
+        assert!(scheduler.is_idle());
+    }
+
+    /// Runs `spawn` while the only worker is busy, then returns the order
+    /// in which the tasks it queued ran, by label.
+    fn dispatch_order(
+        spawn: impl FnOnce(&Scheduler, &dyn Fn(&'static str) -> TaskBody),
+    ) -> Vec<&'static str> {
+        let scheduler = scheduler(1);
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let (started, running) = std::sync::mpsc::channel();
+        scheduler.spawn(move || {
+            started.send(()).unwrap();
+            blocked.recv().unwrap();
+            Ok(())
+        });
+        running.recv().unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let record = |label: &'static str| -> TaskBody {
+            let order = Arc::clone(&order);
+            Box::new(move || {
+                order.lock().unwrap().push(label);
+                Ok(())
+            })
+        };
+        spawn(&scheduler, &record);
+        release.send(()).unwrap();
+        scheduler.run_until_idle();
+        Arc::try_unwrap(order).unwrap().into_inner().unwrap()
+    }
+
+    #[test]
+    fn higher_priorities_run_first_and_fifo_within_one() {
+        let order = dispatch_order(|scheduler, record| {
+            scheduler.spawn_with_priority(Priority::Background, record("background"));
+            scheduler.spawn(record("normal 1"));
+            scheduler.spawn_with_priority(Priority::Critical, record("critical"));
+            scheduler.spawn(record("normal 2"));
+            scheduler.spawn_with_priority(Priority::High, record("high"));
+        });
+        assert_eq!(
+            order,
+            ["critical", "high", "normal 1", "normal 2", "background"]
+        );
+    }
+
+    #[test]
+    fn set_priority_moves_only_queued_tasks() {
+        let order = dispatch_order(|scheduler, record| {
+            scheduler.spawn_with_priority(Priority::Low, record("first"));
+            let second = scheduler.spawn_with_priority(Priority::Low, record("second"));
+            assert!(second.set_priority(Priority::Critical));
+            assert_eq!(second.priority(), Priority::Critical);
+        });
+        assert_eq!(order, ["second", "first"]);
+
+        let order = dispatch_order(|scheduler, record| {
+            let urgent = scheduler.spawn_with_priority(Priority::Critical, record("demoted"));
+            scheduler.spawn_with_priority(Priority::Low, record("low"));
+            assert!(urgent.set_priority(Priority::Background));
+        });
+        assert_eq!(order, ["low", "demoted"]);
+
+        let scheduler = scheduler(1);
+        let handle = scheduler.spawn(|| Ok(()));
+        handle.join().unwrap();
+        assert!(!handle.set_priority(Priority::High));
+        assert_eq!(handle.priority(), Priority::Normal);
+    }
+
+    #[test]
+    fn tasks_queued_on_a_busy_worker_are_stolen_by_priority() {
+        let scheduler = Arc::new(scheduler(2));
+        let (release_blocker, blocked) = std::sync::mpsc::channel::<()>();
+        let (started, running) = std::sync::mpsc::channel();
+        scheduler.spawn(move || {
+            started.send(()).unwrap();
+            blocked.recv().unwrap();
+            Ok(())
+        });
+        running.recv().unwrap();
+
+        // Spawned from a task, so queued on that task's worker, which then
+        // stays busy: only the other worker can run them.
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (release_parent, parent_blocked) = std::sync::mpsc::channel::<()>();
+        let (spawned, children) = std::sync::mpsc::channel();
+        let (inner, recorded) = (Arc::clone(&scheduler), Arc::clone(&order));
+        scheduler.spawn(move || {
+            let handles: Vec<_> = [(Priority::Low, "low"), (Priority::Critical, "critical")]
+                .into_iter()
+                .map(|(priority, label)| {
+                    let order = Arc::clone(&recorded);
+                    inner.spawn_with_priority(priority, move || {
+                        order.lock().unwrap().push(label);
+                        Ok(())
+                    })
+                })
+                .collect();
+            spawned.send(handles).unwrap();
+            parent_blocked.recv().unwrap();
+            Ok(())
+        });
+        let children = children.recv().unwrap();
+        assert_eq!(scheduler.queue_depth(), 2);
+
+        release_blocker.send(()).unwrap();
+        assert!(children.iter().all(|child| child.join().is_ok()));
+        assert_eq!(*order.lock().unwrap(), ["critical", "low"]);
+        release_parent.send(()).unwrap();
+        scheduler.run_until_idle();
+    }
+
+    #[test]
+    fn task_cancelled_before_start_never_runs() {
+        let scheduler = Scheduler::new(SchedulerConfig {
+            worker_threads: 1,
+            ..SchedulerConfig::default()
+        })
+        .unwrap();
+        let ran = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&ran);
+        let handle = scheduler.spawn(move || {
+            flag.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        handle.cancel();
+        scheduler.start();
+        assert!(matches!(handle.join(), Err(RuntimeError::Cancelled(_))));
+        assert!(!ran.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn cancelling_a_queued_task_finishes_it_without_running_it() {
+        let scheduler = scheduler(1);
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let (started, running) = std::sync::mpsc::channel();
+        let busy = scheduler.spawn(move || {
+            started.send(()).unwrap();
+            blocked.recv().unwrap();
+            Ok(())
+        });
+        running.recv().unwrap();
+        let ran = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&ran);
+        let queued = scheduler.spawn(move || {
+            flag.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        assert_eq!(scheduler.queue_depth(), 1);
+
+        queued.cancel();
+        // Joinable while the only worker is still busy.
+        assert!(matches!(queued.join(), Err(RuntimeError::Cancelled(_))));
+        assert_eq!(scheduler.queue_depth(), 0);
+
+        release.send(()).unwrap();
+        scheduler.run_until_idle();
+        assert!(!ran.load(Ordering::SeqCst));
+        busy.cancel();
+        assert!(!busy.is_cancelled());
+        assert_eq!(busy.join(), Ok(()));
+    }
+
+    #[test]
+    fn running_tasks_observe_cancellation_cooperatively() {
+        let scheduler = scheduler(1);
+        let (started, running) = std::sync::mpsc::channel();
+        let handle = scheduler.spawn(move || {
+            started.send(()).unwrap();
+            while !helpers::is_current_cancelled() {
+                std::thread::yield_now();
+            }
+            helpers::current_cancellation().check()
+        });
+        running.recv().unwrap();
+        handle.cancel();
+        assert!(matches!(handle.join(), Err(RuntimeError::Cancelled(_))));
+    }
+
+    #[test]
+    fn timed_out_joins_can_be_retried() {
+        let scheduler = scheduler(1);
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let handle = scheduler.spawn(move || {
+            blocked.recv().unwrap();
+            Ok(())
+        });
+        assert_eq!(handle.join_timeout(Duration::from_millis(10)), None);
+        release.send(()).unwrap();
+        assert_eq!(handle.join_timeout(Duration::from_secs(10)), Some(Ok(())));
+        assert_eq!(handle.join(), Ok(()));
+    }
+
+    #[test]
+    fn typed_tasks_return_their_value() {
+        let scheduler = scheduler(2);
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let answer = scheduler.spawn_returning(move || {
+            blocked.recv().unwrap();
+            Ok(vec![4, 2])
+        });
+        let answer = answer
+            .join_timeout(Duration::from_millis(10))
+            .expect_err("the task is blocked");
+        release.send(()).unwrap();
+        assert_eq!(answer.join(), Ok(vec![4, 2]));
+
+        let failed =
+            scheduler.spawn_returning::<u8>(|| Err(RuntimeError::Cancelled("gave up".to_string())));
+        assert_eq!(
+            failed.join(),
+            Err(RuntimeError::Cancelled("gave up".to_string()))
+        );
+    }
+
+    #[test]
+    fn panicking_task_reports_an_error() {
+        let scheduler = scheduler(1);
+        let handle = scheduler.spawn(|| panic!("boom"));
+        assert_eq!(
+            handle.join(),
+            Err(RuntimeError::TaskPanicked("boom".to_string()))
+        );
+        assert!(!helpers::is_current_cancelled());
+    }
+}
@@ -0,0 +1,180 @@
+//! Recording inferred types in the graph.
+//!
+//! Each inferred [`Type`] is materialized as `TypeNode`s and linked from the
+//! term's metadata, so later passes can read types off the graph instead of
+//! re-running inference. Structurally equal types share nodes, including
+//! the nodes of source annotations.
+
+use std::collections::HashMap;
+
+use asg_core::{AsgGraph, NodeContent, TypeKind, TypeNode};
+
+use crate::error::Result;
+use crate::infer::{TypeCheckMap, check_graph};
+use crate::types::Type;
+
+/// Finds or adds the type nodes for types, reusing structurally equal ones.
+#[derive(Debug, Default)]
+pub struct TypeInterner {
+    nodes: HashMap<Type, u64>,
+}
+
+impl TypeInterner {
+    /// An interner that reuses the type nodes already in `graph`.
+    pub fn new(graph: &AsgGraph) -> Self {
+        let mut type_ids: Vec<u64> = graph
+            .nodes()
+            .filter(|node| matches!(node.content, NodeContent::TypeNode(_)))
+            .map(|node| node.node_id)
+            .collect();
+        // The lowest id wins, so which node is reused does not depend on
+        // map iteration order.
+        type_ids.sort_unstable();
+        let mut nodes = HashMap::new();
+        for type_id in type_ids {
+            if let Some(ty) = node_type(graph, type_id, 0) {
+                nodes.entry(ty).or_insert(type_id);
+            }
+        }
+        Self { nodes }
+    }
+
+    /// The type node for `ty`, added to `graph` if there is none yet.
+    ///
+    /// Returns `None` if `ty` contains type variables or quantifiers, which
+    /// type nodes cannot express.
+    pub fn intern(&mut self, graph: &mut AsgGraph, ty: &Type) -> Option<u64> {
+        if let Some(&type_id) = self.nodes.get(ty) {
+            return Some(type_id);
+        }
+        let kind = match ty {
+            Type::Int => TypeKind::Int,
+            Type::Bool => TypeKind::Bool,
+            Type::Unit => TypeKind::Unit,
+            Type::Function(param, ret) => TypeKind::Function {
+                param_type_id: self.intern(graph, param)?,
+                return_type_id: self.intern(graph, ret)?,
+            },
+            Type::Ref(inner) => TypeKind::Ref {
+                element_type_id: self.intern(graph, inner)?,
+            },
+            Type::Var(_) | Type::ForAll(..) => return None,
+        };
+        let type_id = graph.add_node(NodeContent::TypeNode(TypeNode { kind }));
+        self.nodes.insert(ty.clone(), type_id);
+        Some(type_id)
+    }
+}
+
+/// The type node for `ty` in `graph`; see [`TypeInterner::intern`].
+pub fn intern_type(graph: &mut AsgGraph, ty: &Type) -> Option<u64> {
+    TypeInterner::new(graph).intern(graph, ty)
+}
+
+/// Infers types like [`check_graph`] and, if the graph checks, links every
+/// node in the returned map to a type node for its type. Nodes whose type
+/// is still polymorphic are left unannotated. On error the graph is not
+/// changed.
+pub fn check_and_annotate_graph(graph: &mut AsgGraph) -> Result<TypeCheckMap> {
+    let types = check_graph(graph)?;
+    annotate_graph(graph, &types);
+    Ok(types)
+}
+
+/// Links every node in `types` to a type node for its type and returns how
+/// many were linked.
+pub fn annotate_graph(graph: &mut AsgGraph, types: &TypeCheckMap) -> usize {
+    let mut interner = TypeInterner::new(graph);
+    let mut node_ids: Vec<u64> = types.keys().copied().collect();
+    node_ids.sort_unstable();
+    let mut annotated = 0;
+    for node_id in node_ids {
+        if let Some(type_id) = interner.intern(graph, &types[&node_id])
+            && graph.set_inferred_type(node_id, type_id)
+        {
+            annotated += 1;
+        }
+    }
+    annotated
+}
+
+/// The type a type node denotes. `depth` bounds the recursion, since a
+/// malformed graph may contain cyclic type nodes.
+fn node_type(graph: &AsgGraph, type_id: u64, depth: usize) -> Option<Type> {
+    const MAX_DEPTH: usize = 64;
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let NodeContent::TypeNode(ty) = &graph.get_node(type_id)?.content else {
+        return None;
+    };
+    Some(match ty.kind {
+        TypeKind::Int => Type::Int,
+        TypeKind::Bool => Type::Bool,
+        TypeKind::Unit => Type::Unit,
+        TypeKind::Function {
+            param_type_id,
+            return_type_id,
+        } => Type::function(
+            node_type(graph, param_type_id, depth + 1)?,
+            node_type(graph, return_type_id, depth + 1)?,
+        ),
+        TypeKind::Ref { element_type_id } => {
+            Type::Ref(Box::new(node_type(graph, element_type_id, depth + 1)?))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inferred_types_share_nodes_with_annotations() {
+        let mut graph = parser_core::parse_str("(f: Int -> Int)(x) => f(x) + 1").unwrap();
+        let types = check_graph(&graph).unwrap();
+        let type_nodes = |graph: &AsgGraph| {
+            graph
+                .nodes()
+                .filter(|node| matches!(node.content, NodeContent::TypeNode(_)))
+                .count()
+        };
+        // `Int -> Int` and its two `Int`s.
+        assert_eq!(type_nodes(&graph), 3);
+
+        let annotated = annotate_graph(&mut graph, &types);
+        assert_eq!(annotated, types.len());
+        // Every part of `(Int -> Int) -> Int -> Int` but the whole is reused.
+        assert_eq!(type_nodes(&graph), 4);
+
+        let root = graph.root().unwrap();
+        let root_type = graph.inferred_type(root).unwrap();
+        assert_eq!(node_type(&graph, root_type, 0), Some(types[&root].clone()));
+        assert_eq!(
+            intern_type(&mut graph, &Type::function(Type::Int, Type::Int)),
+            intern_type(&mut graph, &Type::function(Type::Int, Type::Int))
+        );
+    }
+
+    #[test]
+    fn polymorphic_types_are_not_recorded() {
+        let mut graph = parser_core::parse_str("(x) => x").unwrap();
+        let types = check_graph(&graph).unwrap();
+        assert_eq!(annotate_graph(&mut graph, &types), 0);
+        assert_eq!(intern_type(&mut graph, &Type::Var(0)), None);
+    }
+
+    #[test]
+    fn checking_annotates_only_graphs_that_check() {
+        let mut graph = parser_core::parse_str("(x: Int) => x + 1").unwrap();
+        let types = check_and_annotate_graph(&mut graph).unwrap();
+        let root = graph.root().unwrap();
+        let root_type = graph.inferred_type(root).unwrap();
+        assert_eq!(node_type(&graph, root_type, 0), Some(types[&root].clone()));
+
+        let mut graph = parser_core::parse_str("1 + true").unwrap();
+        let before = graph.clone();
+        assert!(check_and_annotate_graph(&mut graph).is_err());
+        assert_eq!(graph, before);
+    }
+}
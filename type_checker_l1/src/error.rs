@@ -0,0 +1,107 @@
+//! Type errors reported by the Level 1 checker.
+
+use thiserror::Error;
+
+use crate::types::{Type, TypeVarId};
+
+/// A type error, tagged with the ASG node where it was detected.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TypeError {
+    /// Two types that must be equal could not be unified.
+    #[error("type mismatch at node {node_id}: expected {expected}, found {found}")]
+    UnificationFail {
+        node_id: u64,
+        expected: Type,
+        found: Type,
+    },
+
+    /// Unification would build an infinite type.
+    #[error("infinite type at node {node_id}: T{var} occurs in {ty}")]
+    OccursCheck {
+        node_id: u64,
+        var: TypeVarId,
+        ty: Type,
+    },
+
+    /// A variable does not resolve to a binder in scope.
+    #[error("undefined variable '{name}' at node {node_id}")]
+    UndefinedVariable { node_id: u64, name: String },
+
+    /// A value that is not a function was applied to an argument.
+    #[error("node {0} applies a value that is not a function")]
+    ApplicationMismatch(u64),
+
+    /// A lambda's parameter annotation disagrees with how the parameter is used.
+    #[error(
+        "parameter annotation at node {annotation_id} says {annotated}, but the body uses it as {inferred}"
+    )]
+    AnnotationMismatch {
+        /// The lambda whose annotation is wrong.
+        node_id: u64,
+        /// The annotating type node.
+        annotation_id: u64,
+        annotated: Type,
+        inferred: Type,
+    },
+
+    /// A pattern match does not cover every constructor of the scrutinee.
+    ///
+    /// Reported by checkers for levels that support pattern matching.
+    #[error("match at node {node_id} does not cover: {}", missing.join(", "))]
+    NonExhaustiveMatch { node_id: u64, missing: Vec<String> },
+
+    /// An effect was performed where it is not permitted.
+    ///
+    /// Reported by the Level 2 effect checker.
+    #[error("effect '{effect}' at node {node_id} is not allowed (allowed: {})", allowed.join(", "))]
+    EffectNotAllowed {
+        node_id: u64,
+        effect: String,
+        allowed: Vec<String>,
+    },
+
+    /// A node id referenced by the graph does not exist.
+    #[error("node {0} referenced but not present in the graph")]
+    MissingNode(u64),
+
+    /// The construct is valid ASG but not supported by this checker.
+    #[error("unimplemented: {0}")]
+    Unimplemented(String),
+}
+
+impl TypeError {
+    /// Stable diagnostic code, shared with the explanations in
+    /// `proof_synthesis_assist`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypeError::Unimplemented(_) => "T000",
+            TypeError::UnificationFail { .. } => "T001",
+            TypeError::OccursCheck { .. } => "T002",
+            TypeError::UndefinedVariable { .. } => "T003",
+            TypeError::ApplicationMismatch(_) => "T004",
+            TypeError::AnnotationMismatch { .. } => "T005",
+            TypeError::NonExhaustiveMatch { .. } => "T006",
+            TypeError::MissingNode(_) => "T007",
+            TypeError::EffectNotAllowed { .. } => "E001",
+        }
+    }
+
+    /// The node the error is reported against, if any.
+    pub fn node_id(&self) -> Option<u64> {
+        match self {
+            TypeError::UnificationFail { node_id, .. }
+            | TypeError::OccursCheck { node_id, .. }
+            | TypeError::UndefinedVariable { node_id, .. }
+            | TypeError::AnnotationMismatch { node_id, .. }
+            | TypeError::NonExhaustiveMatch { node_id, .. }
+            | TypeError::EffectNotAllowed { node_id, .. } => Some(*node_id),
+            TypeError::ApplicationMismatch(node_id) | TypeError::MissingNode(node_id) => {
+                Some(*node_id)
+            }
+            TypeError::Unimplemented(_) => None,
+        }
+    }
+}
+
+/// Convenience alias for type checking results.
+pub type Result<T> = std::result::Result<T, TypeError>;
@@ -0,0 +1,725 @@
+//! Constraint-based type inference (Algorithm W over ASG nodes).
+
+use std::collections::{BTreeSet, HashMap};
+
+use asg_core::{AsgGraph, NodeContent, TermLambda, TypeKind};
+
+use crate::error::{Result, TypeError};
+use crate::types::{Type, TypeVarId};
+
+/// Inferred types keyed by ASG node id.
+pub type TypeCheckMap = HashMap<u64, Type>;
+
+/// Infers types for every term reachable from the graph's root.
+///
+/// Lambda binder nodes are included in the map with their parameter type.
+/// A graph without a root yields an empty map. Fails with the first error
+/// [`check_graph_collect`] finds. The graph itself is left unchanged; see
+/// [`crate::check_and_annotate_graph`] to record the types in it.
+pub fn check_graph(graph: &AsgGraph) -> Result<TypeCheckMap> {
+    let (types, errors) = check_graph_collect(graph);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(types),
+    }
+}
+
+/// Like [`check_graph`], but carries on past errors and returns all of
+/// them, in the order they were found.
+///
+/// A term that fails to check gets a fresh type variable in place of its
+/// type, so the terms around it are still checked against something and
+/// one mistake is not reported again by every term that uses it.
+pub fn check_graph_collect(graph: &AsgGraph) -> (TypeCheckMap, Vec<TypeError>) {
+    let Some(root) = graph.root() else {
+        return (TypeCheckMap::new(), Vec::new());
+    };
+    let mut inferencer = Inferencer::new(graph);
+    inferencer.infer(root);
+    let errors = std::mem::take(&mut inferencer.errors);
+    (inferencer.finish(), errors)
+}
+
+/// Checks the term at `node_id` on its own, with the parameters of the
+/// lambdas in `bindings` in scope at their given types and new type
+/// variables numbered from `first_var`. Returns the term's types and the
+/// bindings as checking left them, or the first error.
+pub(crate) fn check_in_scope(
+    graph: &AsgGraph,
+    node_id: u64,
+    bindings: HashMap<u64, Type>,
+    first_var: TypeVarId,
+) -> Result<(TypeCheckMap, HashMap<u64, Type>)> {
+    let mut inferencer = Inferencer::new(graph);
+    inferencer.next_var = first_var;
+    inferencer.env = bindings;
+    inferencer.infer(node_id);
+    if let Some(error) = inferencer.errors.drain(..).next() {
+        return Err(error);
+    }
+    let bindings = inferencer
+        .env
+        .iter()
+        .map(|(&lambda_id, ty)| (lambda_id, inferencer.resolve(ty)))
+        .collect();
+    Ok((inferencer.finish(), bindings))
+}
+
+/// Why two types failed to unify.
+enum UnifyFailure {
+    Mismatch,
+    Occurs(TypeVarId, Type),
+}
+
+struct Inferencer<'g> {
+    graph: &'g AsgGraph,
+    substitution: HashMap<TypeVarId, Type>,
+    next_var: TypeVarId,
+    /// Types of bound variables, keyed by the id of their binding node.
+    env: HashMap<u64, Type>,
+    node_types: HashMap<u64, Type>,
+    errors: Vec<TypeError>,
+}
+
+impl<'g> Inferencer<'g> {
+    fn new(graph: &'g AsgGraph) -> Self {
+        Self {
+            graph,
+            substitution: HashMap::new(),
+            next_var: 0,
+            env: HashMap::new(),
+            node_types: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn fresh(&mut self) -> Type {
+        let var = self.next_var;
+        self.next_var += 1;
+        Type::Var(var)
+    }
+
+    /// Applies the current substitution to `ty` until no bound variables remain.
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(var) => match self.substitution.get(var) {
+                Some(bound) => self.resolve(bound),
+                None => ty.clone(),
+            },
+            Type::Function(param, ret) => Type::function(self.resolve(param), self.resolve(ret)),
+            Type::Ref(inner) => Type::Ref(Box::new(self.resolve(inner))),
+            Type::ForAll(vars, body) => Type::ForAll(vars.clone(), Box::new(self.resolve(body))),
+            Type::Int | Type::Bool | Type::Unit => ty.clone(),
+        }
+    }
+
+    fn unify(&mut self, node_id: u64, expected: &Type, found: &Type) -> Result<()> {
+        match self.unify_inner(expected, found) {
+            Ok(()) => Ok(()),
+            Err(UnifyFailure::Mismatch) => Err(TypeError::UnificationFail {
+                node_id,
+                expected: self.resolve(expected),
+                found: self.resolve(found),
+            }),
+            Err(UnifyFailure::Occurs(var, ty)) => Err(TypeError::OccursCheck { node_id, var, ty }),
+        }
+    }
+
+    fn unify_inner(&mut self, a: &Type, b: &Type) -> std::result::Result<(), UnifyFailure> {
+        let a = self.resolve(a);
+        let b = self.resolve(b);
+        match (&a, &b) {
+            _ if a == b => Ok(()),
+            (Type::Var(var), other) | (other, Type::Var(var)) => {
+                if other.free_vars().contains(var) {
+                    return Err(UnifyFailure::Occurs(*var, other.clone()));
+                }
+                self.substitution.insert(*var, other.clone());
+                Ok(())
+            }
+            (Type::Function(p1, r1), Type::Function(p2, r2)) => {
+                self.unify_inner(p1, p2)?;
+                self.unify_inner(r1, r2)
+            }
+            (Type::Ref(i1), Type::Ref(i2)) => self.unify_inner(i1, i2),
+            _ => Err(UnifyFailure::Mismatch),
+        }
+    }
+
+    fn content(&self, node_id: u64) -> Result<&'g NodeContent> {
+        self.graph
+            .get_node(node_id)
+            .map(|node| &node.content)
+            .ok_or(TypeError::MissingNode(node_id))
+    }
+
+    /// Infers the type of `node_id`. A node that fails to check has its
+    /// error recorded and gets a fresh type variable instead.
+    fn infer(&mut self, node_id: u64) -> Type {
+        let ty = match self.infer_content(node_id) {
+            Ok(ty) => ty,
+            Err(error) => {
+                self.errors.push(error);
+                self.fresh()
+            }
+        };
+        self.node_types.insert(node_id, ty.clone());
+        ty
+    }
+
+    /// Records `error` for the node being inferred, then checks `rest`, the
+    /// children that do not depend on the failure, and gives the node a
+    /// fresh type.
+    fn fail_then_check(&mut self, error: TypeError, rest: &[u64]) -> Result<Type> {
+        self.errors.push(error);
+        for &node_id in rest {
+            self.infer(node_id);
+        }
+        Ok(self.fresh())
+    }
+
+    fn infer_content(&mut self, node_id: u64) -> Result<Type> {
+        match self.content(node_id)? {
+            NodeContent::LiteralInt(_) => Ok(Type::Int),
+            NodeContent::LiteralBool(_) => Ok(Type::Bool),
+            NodeContent::LiteralUnit(_) => Ok(Type::Unit),
+            NodeContent::TermVariable(var) => match self.env.get(&var.definition_node_id) {
+                Some(scheme) => {
+                    let scheme = scheme.clone();
+                    Ok(self.instantiate(&scheme))
+                }
+                None => Err(TypeError::UndefinedVariable {
+                    node_id,
+                    name: var.name.clone(),
+                }),
+            },
+            NodeContent::TermLambda(lambda) => {
+                let param = self.fresh();
+                let shadowed = self.env.insert(node_id, param.clone());
+                self.node_types
+                    .insert(lambda.binder_variable_node_id, param.clone());
+                let body = self.infer(lambda.body_node_id);
+                match shadowed {
+                    Some(previous) => self.env.insert(node_id, previous),
+                    None => self.env.remove(&node_id),
+                };
+
+                if let Some(annotation_id) = lambda.type_annotation_id {
+                    let annotated = self.type_from_node(annotation_id)?;
+                    if self.unify_inner(&annotated, &param).is_err() {
+                        return Err(TypeError::AnnotationMismatch {
+                            node_id,
+                            annotation_id,
+                            annotated,
+                            inferred: self.resolve(&param),
+                        });
+                    }
+                }
+                Ok(Type::function(param, body))
+            }
+            NodeContent::TermApplication(app) => {
+                if let NodeContent::TermLambda(lambda) = self.content(app.function_node_id)? {
+                    return self.infer_let(app.function_node_id, lambda, app.argument_node_id);
+                }
+                let function = self.infer(app.function_node_id);
+                if matches!(
+                    self.resolve(&function),
+                    Type::Int | Type::Bool | Type::Unit | Type::Ref(_)
+                ) {
+                    return self.fail_then_check(
+                        TypeError::ApplicationMismatch(node_id),
+                        &[app.argument_node_id],
+                    );
+                }
+                let argument = self.infer(app.argument_node_id);
+                let result = self.fresh();
+                self.unify(
+                    node_id,
+                    &Type::function(argument, result.clone()),
+                    &function,
+                )?;
+                Ok(result)
+            }
+            NodeContent::PrimitiveOp(op) => {
+                let Some((params, result)) = self.primitive_signature(&op.op_name) else {
+                    return self.fail_then_check(
+                        TypeError::Unimplemented(format!("unknown primitive '{}'", op.op_name)),
+                        &op.argument_node_ids,
+                    );
+                };
+                if params.len() != op.argument_node_ids.len() {
+                    return self.fail_then_check(
+                        TypeError::Unimplemented(format!(
+                            "primitive '{}' expects {} arguments, got {}",
+                            op.op_name,
+                            params.len(),
+                            op.argument_node_ids.len()
+                        )),
+                        &op.argument_node_ids,
+                    );
+                }
+                // Each argument is checked against its parameter on its own,
+                // so a bad argument does not hide a bad sibling.
+                for (expected, &arg) in params.iter().zip(&op.argument_node_ids) {
+                    let found = self.infer(arg);
+                    if let Err(error) = self.unify(arg, expected, &found) {
+                        self.errors.push(error);
+                    }
+                }
+                Ok(result)
+            }
+            NodeContent::TermRef(term) => {
+                let init = self.infer(term.init_value_node_id);
+                Ok(Type::Ref(Box::new(init)))
+            }
+            NodeContent::TermDeref(term) => {
+                let reference = self.infer(term.ref_node_id);
+                let element = self.fresh();
+                self.unify(node_id, &Type::Ref(Box::new(element.clone())), &reference)?;
+                Ok(element)
+            }
+            NodeContent::TermAssign(term) => {
+                let reference = self.infer(term.ref_node_id);
+                let value = self.infer(term.value_node_id);
+                self.unify(node_id, &Type::Ref(Box::new(value)), &reference)?;
+                Ok(Type::Unit)
+            }
+            NodeContent::EffectPerform(perform) => {
+                self.infer(perform.value_node_id);
+                Ok(Type::Unit)
+            }
+            NodeContent::TermMacroDefinition(_) | NodeContent::TermMacroInvocation(_) => {
+                Err(TypeError::Unimplemented(format!(
+                    "macro node {} was not expanded before type checking",
+                    node_id
+                )))
+            }
+            NodeContent::TypeNode(_) => Err(TypeError::Unimplemented(format!(
+                "type node {} used in term position",
+                node_id
+            ))),
+        }
+    }
+
+    /// Infers `((x) => body)(value)`, the form `let x = value in body`
+    /// takes, with `lambda_id` the lambda. When `value` is a syntactic
+    /// value its type is generalized, so each use of `x` in `body` gets a
+    /// fresh instance, and the binder `x` is recorded with the scheme. Anything else, such as a `ref`, stays monomorphic,
+    /// since generalizing a reference's contents would be unsound.
+    fn infer_let(&mut self, lambda_id: u64, lambda: &TermLambda, value_id: u64) -> Result<Type> {
+        let mut value = self.infer(value_id);
+        if let Some(annotation_id) = lambda.type_annotation_id {
+            let annotated = self.type_from_node(annotation_id)?;
+            if self.unify_inner(&annotated, &value).is_err() {
+                self.errors.push(TypeError::AnnotationMismatch {
+                    node_id: lambda_id,
+                    annotation_id,
+                    annotated: annotated.clone(),
+                    inferred: self.resolve(&value),
+                });
+                value = annotated;
+            }
+        }
+        let scheme = if self.is_syntactic_value(value_id) {
+            self.generalize(&value)
+        } else {
+            value.clone()
+        };
+
+        let shadowed = self.env.insert(lambda_id, scheme.clone());
+        self.node_types
+            .insert(lambda.binder_variable_node_id, scheme);
+        let body = self.infer(lambda.body_node_id);
+        match shadowed {
+            Some(previous) => self.env.insert(lambda_id, previous),
+            None => self.env.remove(&lambda_id),
+        };
+        self.node_types
+            .insert(lambda_id, Type::function(value, body.clone()));
+        Ok(body)
+    }
+
+    /// Whether evaluating `node_id` is immediate and allocates nothing.
+    fn is_syntactic_value(&self, node_id: u64) -> bool {
+        matches!(
+            self.content(node_id),
+            Ok(NodeContent::TermLambda(_)
+                | NodeContent::TermVariable(_)
+                | NodeContent::LiteralInt(_)
+                | NodeContent::LiteralBool(_)
+                | NodeContent::LiteralUnit(_))
+        )
+    }
+
+    /// Quantifies `ty` over its type variables that are not free in the
+    /// environment. Variables of enclosing lambda parameters stay free, so
+    /// uses of them elsewhere are still constrained.
+    fn generalize(&self, ty: &Type) -> Type {
+        let ty = self.resolve(ty);
+        let in_env: BTreeSet<TypeVarId> = self
+            .env
+            .values()
+            .flat_map(|bound| self.resolve(bound).free_vars())
+            .collect();
+        let vars: Vec<TypeVarId> = ty.free_vars().difference(&in_env).copied().collect();
+        if vars.is_empty() {
+            ty
+        } else {
+            Type::ForAll(vars, Box::new(ty))
+        }
+    }
+
+    /// Replaces the quantified variables of a scheme with fresh ones.
+    fn instantiate(&mut self, ty: &Type) -> Type {
+        let Type::ForAll(vars, body) = ty else {
+            return ty.clone();
+        };
+        let fresh: HashMap<TypeVarId, Type> = vars.iter().map(|&var| (var, self.fresh())).collect();
+        substitute(body, &fresh)
+    }
+
+    /// Parameter and result types of a primitive operation.
+    fn primitive_signature(&mut self, op_name: &str) -> Option<(Vec<Type>, Type)> {
+        Some(match op_name {
+            "add" | "sub" | "mul" | "div" | "mod" => (vec![Type::Int, Type::Int], Type::Int),
+            "lt" | "le" | "gt" | "ge" => (vec![Type::Int, Type::Int], Type::Bool),
+            "eq" | "ne" => {
+                let operand = self.fresh();
+                (vec![operand.clone(), operand], Type::Bool)
+            }
+            "and" | "or" => (vec![Type::Bool, Type::Bool], Type::Bool),
+            "not" => (vec![Type::Bool], Type::Bool),
+            "if" => {
+                let branch = self.fresh();
+                (vec![Type::Bool, branch.clone(), branch.clone()], branch)
+            }
+            // `letrec f = v` is `fix((f) => v)`: `f` has the type of `v`.
+            "fix" => {
+                let fixed = self.fresh();
+                (vec![Type::function(fixed.clone(), fixed.clone())], fixed)
+            }
+            _ => return None,
+        })
+    }
+
+    /// Reads a type annotation subgraph into a [`Type`].
+    fn type_from_node(&self, type_id: u64) -> Result<Type> {
+        let NodeContent::TypeNode(type_node) = self.content(type_id)? else {
+            return Err(TypeError::Unimplemented(format!(
+                "node {} is not a type node",
+                type_id
+            )));
+        };
+        Ok(match type_node.kind {
+            TypeKind::Int => Type::Int,
+            TypeKind::Bool => Type::Bool,
+            TypeKind::Unit => Type::Unit,
+            TypeKind::Function {
+                param_type_id,
+                return_type_id,
+            } => Type::function(
+                self.type_from_node(param_type_id)?,
+                self.type_from_node(return_type_id)?,
+            ),
+            TypeKind::Ref { element_type_id } => {
+                Type::Ref(Box::new(self.type_from_node(element_type_id)?))
+            }
+        })
+    }
+
+    fn finish(self) -> TypeCheckMap {
+        self.node_types
+            .iter()
+            .map(|(&node_id, ty)| (node_id, self.resolve(ty)))
+            .collect()
+    }
+}
+
+/// `ty` with the variables in `vars` replaced.
+pub(crate) fn substitute(ty: &Type, vars: &HashMap<TypeVarId, Type>) -> Type {
+    match ty {
+        Type::Var(var) => vars.get(var).cloned().unwrap_or_else(|| ty.clone()),
+        Type::Function(param, ret) => {
+            Type::function(substitute(param, vars), substitute(ret, vars))
+        }
+        Type::Ref(inner) => Type::Ref(Box::new(substitute(inner, vars))),
+        Type::ForAll(bound, body) => {
+            let inner: HashMap<TypeVarId, Type> = vars
+                .iter()
+                .filter(|(var, _)| !bound.contains(var))
+                .map(|(&var, ty)| (var, ty.clone()))
+                .collect();
+            Type::ForAll(bound.clone(), Box::new(substitute(body, &inner)))
+        }
+        Type::Int | Type::Bool | Type::Unit => ty.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asg_core::{
+        LambdaBuilder, LiteralBool, LiteralInt, PrimitiveOp, TermApplication, TermVariable,
+        TypeNode,
+    };
+
+    fn var(graph: &mut AsgGraph, name: &str) -> u64 {
+        graph.add_node(NodeContent::TermVariable(TermVariable {
+            name: name.to_string(),
+            definition_node_id: 0,
+        }))
+    }
+
+    /// Builds `(x[: annotation]) => body(x)` where `body` receives the use site of `x`.
+    fn lambda(
+        graph: &mut AsgGraph,
+        annotation: Option<TypeKind>,
+        body: impl FnOnce(&mut AsgGraph, u64) -> u64,
+    ) -> u64 {
+        LambdaBuilder::build(graph, "x", |graph, x| {
+            let use_site = x.reference(graph);
+            let body = body(graph, use_site);
+            x.set_annotation(
+                annotation.map(|kind| graph.add_node(NodeContent::TypeNode(TypeNode { kind }))),
+            );
+            body
+        })
+    }
+
+    fn int(graph: &mut AsgGraph, value: i64) -> u64 {
+        graph.add_node(NodeContent::LiteralInt(LiteralInt { value }))
+    }
+
+    fn add(graph: &mut AsgGraph, lhs: u64, rhs: u64) -> u64 {
+        graph.add_node(NodeContent::PrimitiveOp(PrimitiveOp {
+            op_name: "add".to_string(),
+            argument_node_ids: vec![lhs, rhs],
+        }))
+    }
+
+    #[test]
+    fn identity_is_polymorphic_in_its_parameter() {
+        let mut graph = AsgGraph::new();
+        let id = lambda(&mut graph, None, |_, x| x);
+        graph.set_root(id);
+
+        let types = check_graph(&graph).unwrap();
+        match &types[&id] {
+            Type::Function(param, ret) => {
+                assert!(matches!(**param, Type::Var(_)));
+                assert_eq!(param, ret);
+            }
+            other => panic!("expected a function type, got {}", other),
+        }
+    }
+
+    #[test]
+    fn increment_infers_int_to_int() {
+        let mut graph = AsgGraph::new();
+        let inc = lambda(&mut graph, None, |g, x| {
+            let one = int(g, 1);
+            add(g, x, one)
+        });
+        let arg = int(&mut graph, 41);
+        let app = graph.add_node(NodeContent::TermApplication(TermApplication {
+            function_node_id: inc,
+            argument_node_id: arg,
+        }));
+        graph.set_root(app);
+
+        let types = check_graph(&graph).unwrap();
+        assert_eq!(types[&inc], Type::function(Type::Int, Type::Int));
+        assert_eq!(types[&app], Type::Int);
+    }
+
+    #[test]
+    fn applying_a_literal_is_an_application_mismatch() {
+        let mut graph = AsgGraph::new();
+        let func = int(&mut graph, 1);
+        let arg = int(&mut graph, 2);
+        let app = graph.add_node(NodeContent::TermApplication(TermApplication {
+            function_node_id: func,
+            argument_node_id: arg,
+        }));
+        graph.set_root(app);
+
+        assert_eq!(
+            check_graph(&graph),
+            Err(TypeError::ApplicationMismatch(app))
+        );
+    }
+
+    #[test]
+    fn wrong_annotation_is_reported_against_the_annotation() {
+        let mut graph = AsgGraph::new();
+        let func = lambda(&mut graph, Some(TypeKind::Bool), |g, x| {
+            let one = int(g, 1);
+            add(g, x, one)
+        });
+        graph.set_root(func);
+
+        match check_graph(&graph) {
+            Err(TypeError::AnnotationMismatch {
+                node_id,
+                annotated,
+                inferred,
+                ..
+            }) => {
+                assert_eq!(node_id, func);
+                assert_eq!(annotated, Type::Bool);
+                assert_eq!(inferred, Type::Int);
+            }
+            other => panic!("expected annotation mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn adding_a_bool_fails_unification() {
+        let mut graph = AsgGraph::new();
+        let one = int(&mut graph, 1);
+        let yes = graph.add_node(NodeContent::LiteralBool(LiteralBool { value: true }));
+        let sum = add(&mut graph, one, yes);
+        graph.set_root(sum);
+
+        assert_eq!(
+            check_graph(&graph),
+            Err(TypeError::UnificationFail {
+                node_id: yes,
+                expected: Type::Int,
+                found: Type::Bool,
+            })
+        );
+    }
+
+    #[test]
+    fn self_application_fails_occurs_check() {
+        let mut graph = AsgGraph::new();
+        let omega = LambdaBuilder::build(&mut graph, "x", |graph, x| {
+            let func = x.reference(graph);
+            let arg = x.reference(graph);
+            graph.add_node(NodeContent::TermApplication(TermApplication {
+                function_node_id: func,
+                argument_node_id: arg,
+            }))
+        });
+        graph.set_root(omega);
+
+        assert!(matches!(
+            check_graph(&graph),
+            Err(TypeError::OccursCheck { .. })
+        ));
+    }
+
+    #[test]
+    fn unbound_variable_is_reported() {
+        let mut graph = AsgGraph::new();
+        let free = var(&mut graph, "y");
+        graph.set_root(free);
+
+        assert_eq!(
+            check_graph(&graph),
+            Err(TypeError::UndefinedVariable {
+                node_id: free,
+                name: "y".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn unit_literal_has_type_unit() {
+        let graph = parser_core::parse_str("()").unwrap();
+        let types = check_graph(&graph).unwrap();
+        assert_eq!(types[&graph.root().unwrap()], Type::Unit);
+
+        let graph = parser_core::parse_str("(u: Unit) => u").unwrap();
+        let types = check_graph(&graph).unwrap();
+        assert_eq!(
+            types[&graph.root().unwrap()],
+            Type::function(Type::Unit, Type::Unit)
+        );
+    }
+
+    #[test]
+    fn two_parameter_sugar_infers_a_curried_type() {
+        let graph = parser_core::parse_str("(x: Int)(y: Int) => x - y").unwrap();
+        let types = check_graph(&graph).unwrap();
+        assert_eq!(
+            types[&graph.root().unwrap()],
+            Type::function(Type::Int, Type::function(Type::Int, Type::Int))
+        );
+    }
+
+    #[test]
+    fn collecting_reports_errors_in_independent_subterms() {
+        let graph = parser_core::parse_str("((x: Int) => x + true)(1) + false(2) + y").unwrap();
+        let (types, errors) = check_graph_collect(&graph);
+        let kinds: Vec<_> = errors
+            .iter()
+            .map(|error| match error {
+                TypeError::UnificationFail { found, .. } => format!("mismatch {}", found),
+                TypeError::ApplicationMismatch(_) => "application".to_string(),
+                TypeError::UndefinedVariable { name, .. } => format!("undefined {}", name),
+                other => panic!("unexpected error {:?}", other),
+            })
+            .collect();
+        assert_eq!(kinds, ["mismatch Bool", "application", "undefined y"]);
+        // The failures do not spread: the whole sum is still an Int.
+        assert_eq!(types[&graph.root().unwrap()], Type::Int);
+
+        assert_eq!(check_graph(&graph), Err(errors[0].clone()));
+    }
+
+    fn root_type(source: &str) -> Result<Type> {
+        let graph = parser_core::parse_str(source).unwrap();
+        check_graph(&graph).map(|types| types[&graph.root().unwrap()].clone())
+    }
+
+    #[test]
+    fn let_bound_values_are_polymorphic() {
+        let Type::Function(param, ret) = root_type("let id = (x) => x in id(id)").unwrap() else {
+            panic!("id(id) should be a function");
+        };
+        assert_eq!(param, ret);
+        assert_eq!(
+            root_type("let id = (x) => x in let n = id(1) in id(true) && n == 1"),
+            Ok(Type::Bool)
+        );
+    }
+
+    #[test]
+    fn enclosing_parameters_are_not_generalized() {
+        // `f` is polymorphic in its own parameter but not in `y`.
+        assert_eq!(
+            root_type("(y) => let f = (z) => y in f(1) + f(true)"),
+            Ok(Type::function(Type::Int, Type::Int))
+        );
+        assert!(matches!(
+            root_type("(y) => let g = y in g + 1 == 2 && g"),
+            Err(TypeError::UnificationFail { .. })
+        ));
+    }
+
+    #[test]
+    fn references_are_not_generalized() {
+        assert!(matches!(
+            root_type("let r = ref ((x) => x) in let u = (r := (n) => n + 1) in (!r)(true)"),
+            Err(TypeError::UnificationFail { .. })
+        ));
+    }
+
+    #[test]
+    fn letrec_binds_the_function_in_its_own_body() {
+        assert_eq!(
+            root_type("letrec f = (x) => if x == 0 then 0 else f(x - 1) in f"),
+            Ok(Type::function(Type::Int, Type::Int))
+        );
+        assert!(matches!(
+            root_type("if 1 then 2 else 3"),
+            Err(TypeError::UnificationFail { .. })
+        ));
+        assert!(matches!(
+            root_type("letrec f = (x) => f in f"),
+            Err(TypeError::OccursCheck { .. })
+        ));
+    }
+}
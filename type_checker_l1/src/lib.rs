@@ -1,14 +1,19 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Level 1 type checker: Hindley-Milner style inference over the ASG.
+//!
+//! [`check_graph`] infers a [`Type`] for every term reachable
+//! from the graph's root and returns them keyed by node id.
+//! [`check_and_annotate_graph`] also records those types in the graph
+//! itself. After an edit, [`recheck_subterm`] checks the edited term alone
+//! when that provably leaves the rest of the program's types unchanged.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod annotate;
+pub mod error;
+pub mod infer;
+pub mod recheck;
+pub mod types;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use annotate::{TypeInterner, annotate_graph, check_and_annotate_graph, intern_type};
+pub use error::{Result, TypeError};
+pub use infer::{TypeCheckMap, check_graph, check_graph_collect};
+pub use recheck::recheck_subterm;
+pub use types::Type;
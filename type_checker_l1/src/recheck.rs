@@ -0,0 +1,252 @@
+//! Re-checking one edited term of a program that was checked before.
+//!
+//! Inference unifies across the whole program, so an edited term can in
+//! general change types far away from it. [`recheck_subterm`] checks the
+//! term alone, with the parameters in scope typed as before, and keeps the
+//! result only when the edit provably changed nothing outside the term:
+//! every parameter in scope is left as it was, and the term has its old
+//! type up to renaming type variables that occur nowhere else. The term
+//! then constrains the rest of the program exactly as the old one did.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use asg_core::{AsgGraph, NodeContent};
+
+use crate::infer::{TypeCheckMap, check_in_scope, substitute};
+use crate::types::{Type, TypeVarId};
+
+/// Re-checks the term at `node_id` after an edit inside it.
+///
+/// `types` holds the types found before the edit, keyed by the ids in
+/// `graph`: those of every term outside `node_id` and of `node_id` itself.
+/// Returns `types` with the term's own types replaced, or `None` if the
+/// term does not check or the edit may have changed types outside it, in
+/// which case the whole graph must be checked again.
+///
+/// A `let` value, a lambda's binder or a type annotation means something
+/// only with its parent, so the enclosing term is re-checked instead.
+pub fn recheck_subterm(
+    graph: &AsgGraph,
+    node_id: u64,
+    types: &TypeCheckMap,
+) -> Option<TypeCheckMap> {
+    let mut path = path_to(graph, node_id)?;
+    while path.len() > 1 && !stands_alone(graph, &path) {
+        path.pop();
+    }
+    let node_id = *path.last()?;
+
+    // The parameters in scope are those of the lambdas whose body the path
+    // passes through, typed as their binders were.
+    let mut bindings = HashMap::new();
+    for pair in path.windows(2) {
+        if let Some(NodeContent::TermLambda(lambda)) = content(graph, pair[0])
+            && lambda.body_node_id == pair[1]
+        {
+            bindings.insert(pair[0], types.get(&lambda.binder_variable_node_id)?.clone());
+        }
+    }
+    let first_var = types
+        .values()
+        .filter_map(max_var)
+        .max()
+        .map_or(0, |var| var + 1);
+    let (checked, after) = check_in_scope(graph, node_id, bindings.clone(), first_var).ok()?;
+    if after != bindings {
+        return None;
+    }
+
+    // Rename the term's variables to the old ones, so its types fit the
+    // types around it.
+    let fixed: BTreeSet<TypeVarId> = bindings.values().flat_map(Type::free_vars).collect();
+    let mut renaming = HashMap::new();
+    if !rename_to_match(
+        &checked[&node_id],
+        types.get(&node_id)?,
+        &fixed,
+        &mut renaming,
+    ) {
+        return None;
+    }
+    let distinct: HashSet<TypeVarId> = renaming.values().copied().collect();
+    if distinct.len() != renaming.len() {
+        return None;
+    }
+    let renaming: HashMap<TypeVarId, Type> = renaming
+        .into_iter()
+        .map(|(var, old)| (var, Type::Var(old)))
+        .collect();
+    let mut rechecked = types.clone();
+    rechecked.extend(
+        checked
+            .iter()
+            .map(|(&id, ty)| (id, substitute(ty, &renaming))),
+    );
+    Some(rechecked)
+}
+
+/// Whether the last node of `path` can be checked without its parent.
+fn stands_alone(graph: &AsgGraph, path: &[u64]) -> bool {
+    let [.., parent, node_id] = *path else {
+        return true;
+    };
+    match (content(graph, parent), content(graph, node_id)) {
+        (_, Some(NodeContent::TypeNode(_))) => false,
+        (Some(NodeContent::TermLambda(lambda)), _) => lambda.binder_variable_node_id != node_id,
+        (Some(NodeContent::TermApplication(app)), _) => {
+            app.argument_node_id != node_id
+                || !matches!(
+                    content(graph, app.function_node_id),
+                    Some(NodeContent::TermLambda(_))
+                )
+        }
+        _ => true,
+    }
+}
+
+fn content(graph: &AsgGraph, node_id: u64) -> Option<&NodeContent> {
+    graph.get_node(node_id).map(|node| &node.content)
+}
+
+/// The nodes from the root down to `target`, both included.
+fn path_to(graph: &AsgGraph, target: u64) -> Option<Vec<u64>> {
+    fn search(
+        graph: &AsgGraph,
+        node_id: u64,
+        target: u64,
+        path: &mut Vec<u64>,
+        seen: &mut HashSet<u64>,
+    ) -> bool {
+        if !seen.insert(node_id) {
+            return false;
+        }
+        path.push(node_id);
+        if node_id == target
+            || content(graph, node_id).is_some_and(|content| {
+                content
+                    .child_ids()
+                    .into_iter()
+                    .any(|child| search(graph, child, target, path, seen))
+            })
+        {
+            return true;
+        }
+        path.pop();
+        false
+    }
+    let mut path = Vec::new();
+    search(graph, graph.root()?, target, &mut path, &mut HashSet::new()).then_some(path)
+}
+
+/// Whether renaming the variables of `new` that are not `fixed` as in
+/// `renaming`, extended as needed, turns it into `old`.
+fn rename_to_match(
+    new: &Type,
+    old: &Type,
+    fixed: &BTreeSet<TypeVarId>,
+    renaming: &mut HashMap<TypeVarId, TypeVarId>,
+) -> bool {
+    match (new, old) {
+        (Type::Var(var), Type::Var(old_var)) => {
+            if fixed.contains(var) || fixed.contains(old_var) {
+                var == old_var
+            } else {
+                *renaming.entry(*var).or_insert(*old_var) == *old_var
+            }
+        }
+        (Type::Function(param, ret), Type::Function(old_param, old_ret)) => {
+            rename_to_match(param, old_param, fixed, renaming)
+                && rename_to_match(ret, old_ret, fixed, renaming)
+        }
+        (Type::Ref(inner), Type::Ref(old_inner)) => {
+            rename_to_match(inner, old_inner, fixed, renaming)
+        }
+        _ => new == old,
+    }
+}
+
+/// The highest type variable in `ty`, quantified ones included.
+fn max_var(ty: &Type) -> Option<TypeVarId> {
+    match ty {
+        Type::Int | Type::Bool | Type::Unit => None,
+        Type::Var(var) => Some(*var),
+        Type::Function(param, ret) => max_var(param).max(max_var(ret)),
+        Type::Ref(inner) => max_var(inner),
+        Type::ForAll(vars, body) => vars.iter().copied().max().max(max_var(body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infer::check_graph;
+
+    /// Re-checks the term of `after` picked by `edited`, given the types of
+    /// `before`. The edits keep the number of nodes, so the two graphs
+    /// share node ids.
+    fn recheck(
+        before: &str,
+        after: &str,
+        edited: impl Fn(&AsgGraph, &NodeContent) -> bool,
+    ) -> (Option<TypeCheckMap>, AsgGraph) {
+        let old = parser_core::parse_str(before).unwrap();
+        let new = parser_core::parse_str(after).unwrap();
+        let node_id = new
+            .nodes()
+            .find(|node| edited(&new, &node.content))
+            .unwrap()
+            .node_id;
+        let types = recheck_subterm(&new, node_id, &check_graph(&old).unwrap());
+        (types, new)
+    }
+
+    fn is_op(_: &AsgGraph, content: &NodeContent) -> bool {
+        matches!(content, NodeContent::PrimitiveOp(_))
+    }
+
+    #[test]
+    fn an_edit_that_keeps_the_term_type_is_rechecked_alone() {
+        let before = "let f = (x) => x + 1 in f(3)";
+        let (types, graph) = recheck(before, "let f = (x) => x * 2 in f(3)", is_op);
+        assert_eq!(types.unwrap(), check_graph(&graph).unwrap());
+    }
+
+    #[test]
+    fn an_edit_that_changes_types_elsewhere_is_refused() {
+        let before = "let f = (x) => x + 1 in f(3)";
+        // `x` would become Bool.
+        let (types, _) = recheck(before, "let f = (x) => x && true in f(3)", is_op);
+        assert_eq!(types, None);
+        // The term no longer checks.
+        let (types, _) = recheck(before, "let f = (x) => x + true in f(3)", is_op);
+        assert_eq!(types, None);
+        // A `let` value is re-checked with its `let`, here the whole
+        // program, whose type changed.
+        let is_literal =
+            |_: &AsgGraph, content: &NodeContent| matches!(content, NodeContent::LiteralBool(_));
+        let (types, _) = recheck("let y = 1 in y", "let y = true in y", is_literal);
+        assert_eq!(types, None);
+    }
+
+    #[test]
+    fn new_type_variables_are_renamed_to_the_old_ones() {
+        let before = "let g = (x) => (y) => y in g";
+        let after = "let g = (x) => (z) => z in g";
+        let binds_z = |graph: &AsgGraph, node: &NodeContent| match node {
+            NodeContent::TermLambda(lambda) => matches!(
+                content(graph, lambda.binder_variable_node_id),
+                Some(NodeContent::TermVariable(var)) if var.name == "z"
+            ),
+            _ => false,
+        };
+        let (types, graph) = recheck(before, after, binds_z);
+        assert_eq!(types.unwrap(), check_graph(&graph).unwrap());
+
+        // A binder is re-checked with its lambda.
+        let (types, graph) = recheck(before, after, |_, content| match content {
+            NodeContent::TermVariable(var) => var.name == "z",
+            _ => false,
+        });
+        assert_eq!(types.unwrap(), check_graph(&graph).unwrap());
+    }
+}
@@ -0,0 +1,109 @@
+//! Types inferred by the Level 1 checker.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Identifier of a type variable.
+pub type TypeVarId = u64;
+
+/// A monotype or (for let-bound names) a type scheme.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    Int,
+    Bool,
+    Unit,
+    Var(TypeVarId),
+    Function(Box<Type>, Box<Type>),
+    Ref(Box<Type>),
+    /// A polymorphic type quantified over the listed variables.
+    ForAll(Vec<TypeVarId>, Box<Type>),
+}
+
+impl Type {
+    /// Shorthand for a function type `param -> ret`.
+    pub fn function(param: Type, ret: Type) -> Type {
+        Type::Function(Box::new(param), Box::new(ret))
+    }
+
+    /// Free type variables of the type, in ascending order.
+    pub fn free_vars(&self) -> BTreeSet<TypeVarId> {
+        let mut vars = BTreeSet::new();
+        self.collect_free_vars(&mut vars);
+        vars
+    }
+
+    fn collect_free_vars(&self, vars: &mut BTreeSet<TypeVarId>) {
+        match self {
+            Type::Int | Type::Bool | Type::Unit => {}
+            Type::Var(id) => {
+                vars.insert(*id);
+            }
+            Type::Function(param, ret) => {
+                param.collect_free_vars(vars);
+                ret.collect_free_vars(vars);
+            }
+            Type::Ref(inner) => inner.collect_free_vars(vars),
+            Type::ForAll(bound, body) => {
+                let mut inner = BTreeSet::new();
+                body.collect_free_vars(&mut inner);
+                vars.extend(inner.into_iter().filter(|var| !bound.contains(var)));
+            }
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "Int"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Unit => write!(f, "Unit"),
+            Type::Var(id) => write!(f, "T{}", id),
+            Type::Function(param, ret) => match **param {
+                Type::Function(..) | Type::ForAll(..) => write!(f, "({}) -> {}", param, ret),
+                _ => write!(f, "{} -> {}", param, ret),
+            },
+            Type::Ref(inner) => match **inner {
+                Type::Function(..) | Type::Ref(..) | Type::ForAll(..) => {
+                    write!(f, "Ref ({})", inner)
+                }
+                _ => write!(f, "Ref {}", inner),
+            },
+            Type::ForAll(vars, body) => {
+                write!(f, "forall")?;
+                for var in vars {
+                    write!(f, " T{}", var)?;
+                }
+                write!(f, ". {}", body)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_parenthesizes_function_arguments() {
+        let ty = Type::function(Type::function(Type::Int, Type::Bool), Type::Int);
+        assert_eq!(ty.to_string(), "(Int -> Bool) -> Int");
+        let curried = Type::function(Type::Int, Type::function(Type::Int, Type::Int));
+        assert_eq!(curried.to_string(), "Int -> Int -> Int");
+        let scheme = Type::ForAll(
+            vec![0],
+            Box::new(Type::function(Type::Var(0), Type::Var(0))),
+        );
+        assert_eq!(scheme.to_string(), "forall T0. T0 -> T0");
+        assert_eq!(Type::Ref(Box::new(Type::Int)).to_string(), "Ref Int");
+    }
+
+    #[test]
+    fn free_vars_exclude_quantified() {
+        let scheme = Type::ForAll(
+            vec![0],
+            Box::new(Type::function(Type::Var(0), Type::Var(1))),
+        );
+        assert_eq!(scheme.free_vars().into_iter().collect::<Vec<_>>(), vec![1]);
+    }
+}
@@ -0,0 +1,152 @@
+//! Effect checking against a lexically scoped allowed set.
+//!
+//! The globally allowed effects, typically given on the command line, hold
+//! everywhere. A lambda annotated `with [IO, State]` adds its effects to the
+//! allowed set for its body.
+//!
+//! Effects are inferred first (see [`crate::infer`]), so an application must
+//! also be allowed the latent effects of the function it calls: declaring
+//! `with [IO]` lets a lambda's body perform `IO`, but its callers still need
+//! `IO` themselves.
+
+use std::collections::BTreeSet;
+
+use asg_core::{AsgGraph, NodeContent, TermLambda};
+use type_checker_l1::{Result, TypeError};
+
+use crate::infer::Inference;
+
+/// Checks that every effect performed by the term reachable from the root is
+/// either in `allowed` or declared by an enclosing lambda, including the
+/// effects of every function it calls.
+pub fn check_effects(graph: &AsgGraph, allowed: &[String]) -> Result<()> {
+    let Some(root) = graph.root() else {
+        return Ok(());
+    };
+    let mut inference = Inference::new(graph);
+    inference.summary(root)?;
+    let allowed: BTreeSet<&str> = allowed.iter().map(String::as_str).collect();
+    check_node(graph, &inference, root, &allowed)
+}
+
+fn check_node(
+    graph: &AsgGraph,
+    inference: &Inference,
+    node_id: u64,
+    allowed: &BTreeSet<&str>,
+) -> Result<()> {
+    let node = graph
+        .get_node(node_id)
+        .ok_or(TypeError::MissingNode(node_id))?;
+    let not_allowed = |effect: &str| TypeError::EffectNotAllowed {
+        node_id,
+        effect: effect.to_string(),
+        allowed: allowed.iter().map(|name| name.to_string()).collect(),
+    };
+    match &node.content {
+        NodeContent::TermLambda(TermLambda {
+            effect_annotation: Some(effects),
+            body_node_id,
+            ..
+        }) => {
+            let mut local = allowed.clone();
+            local.extend(effects.iter().map(String::as_str));
+            return check_node(graph, inference, *body_node_id, &local);
+        }
+        NodeContent::EffectPerform(perform) if !allowed.contains(perform.effect_name.as_str()) => {
+            return Err(not_allowed(&perform.effect_name));
+        }
+        // A directly applied lambda without a `with [..]` annotation has its
+        // body checked in this same allowed set, which reports more precisely.
+        NodeContent::TermApplication(app)
+            if !matches!(
+                graph
+                    .get_node(app.function_node_id)
+                    .map(|function| &function.content),
+                Some(NodeContent::TermLambda(TermLambda {
+                    effect_annotation: None,
+                    ..
+                }))
+            ) =>
+        {
+            let called = inference.summaries[&app.function_node_id].call_effects();
+            if let Some(effect) = called
+                .iter()
+                .find(|effect| !allowed.contains(effect.as_str()))
+            {
+                return Err(not_allowed(effect));
+            }
+        }
+        NodeContent::TypeNode(_) => return Ok(()),
+        _ => {}
+    }
+    node.content
+        .child_ids()
+        .into_iter()
+        .try_for_each(|child| check_node(graph, inference, child, allowed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str, allowed: &[&str]) -> Result<()> {
+        let graph = parser_core::parse_str(source).unwrap();
+        let allowed: Vec<String> = allowed.iter().map(|name| name.to_string()).collect();
+        check_effects(&graph, &allowed)
+    }
+
+    #[test]
+    fn performs_need_a_global_or_declared_allowance() {
+        assert_eq!(
+            check("(x: Int) => perform('IO', x)", &[]),
+            Err(TypeError::EffectNotAllowed {
+                node_id: 3,
+                effect: "IO".to_string(),
+                allowed: Vec::new(),
+            })
+        );
+        assert_eq!(check("(x: Int) => perform('IO', x)", &["IO"]), Ok(()));
+        assert_eq!(check("(x: Int) with [IO] => perform('IO', x)", &[]), Ok(()));
+    }
+
+    #[test]
+    fn declared_effects_compose_with_global_ones() {
+        let source = "((r) with [State] => perform('State', r))(perform('IO', 1))";
+        assert_eq!(check(source, &["IO", "State"]), Ok(()));
+        assert!(matches!(
+            check(source, &["State"]),
+            Err(TypeError::EffectNotAllowed { effect, .. }) if effect == "IO"
+        ));
+        assert!(matches!(
+            check("(x) with [IO] => perform('State', x)", &["Net"]),
+            Err(TypeError::EffectNotAllowed { allowed, .. }) if allowed == ["IO", "Net"]
+        ));
+    }
+
+    #[test]
+    fn callers_need_the_effects_of_the_functions_they_call() {
+        let source = "let log = (x) with [IO] => perform('IO', x) in log(1)";
+        assert_eq!(
+            check(source, &[]),
+            Err(TypeError::EffectNotAllowed {
+                node_id: 4,
+                effect: "IO".to_string(),
+                allowed: Vec::new(),
+            })
+        );
+        assert_eq!(check(source, &["IO"]), Ok(()));
+        assert_eq!(
+            check("(y) with [IO] => (log) => log(y)", &[]),
+            Ok(()),
+            "a parameter's effects are unknown"
+        );
+        assert_eq!(
+            check(
+                "let log = (x) with [IO] => perform('IO', x) in (y) with [IO] => log(y)",
+                &[]
+            ),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Bottom-up effect inference.
+//!
+//! Every node reachable from the root gets the set of effects evaluating it
+//! may perform. A `perform` adds its effect to its value's, and most other
+//! nodes union their children. A lambda is a value, so evaluating it
+//! performs nothing; its body's effects are *latent* and surface at each
+//! application, which unions the function's, the argument's and the latent
+//! effects of the function being called.
+//!
+//! The callee is known when the applied term is a lambda, a let-bound
+//! variable, or an application returning one of those. A lambda parameter
+//! stands for an unknown function and is taken to perform nothing when
+//! called.
+
+use std::collections::{BTreeSet, HashMap};
+
+use asg_core::{AsgGraph, NodeContent};
+use type_checker_l1::{Result, TypeError};
+
+/// The effects inferred for each node, keyed by node id.
+pub type EffectMap = HashMap<u64, BTreeSet<String>>;
+
+/// Infers the effects of every node reachable from the graph's root.
+///
+/// Nodes with no effectful descendants map to an empty set.
+pub fn infer_effects(graph: &AsgGraph) -> Result<EffectMap> {
+    let mut inference = Inference::new(graph);
+    if let Some(root) = graph.root() {
+        inference.summary(root)?;
+    }
+    Ok(inference
+        .summaries
+        .into_iter()
+        .map(|(node_id, summary)| (node_id, summary.effects))
+        .collect())
+}
+
+/// What evaluating a node does, and what calling its value would do.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Summary {
+    pub(crate) effects: BTreeSet<String>,
+    /// Latent effects of successive calls: the first entry is performed when
+    /// the node's value is applied, the second when that result is applied,
+    /// and so on. Missing entries are empty.
+    pub(crate) latent: Vec<BTreeSet<String>>,
+}
+
+impl Summary {
+    /// The effects performed by applying the node's value once.
+    pub(crate) fn call_effects(&self) -> BTreeSet<String> {
+        self.latent.first().cloned().unwrap_or_default()
+    }
+}
+
+pub(crate) struct Inference<'a> {
+    graph: &'a AsgGraph,
+    /// The value bound by each let, i.e. each lambda that is applied directly.
+    let_values: HashMap<u64, u64>,
+    pub(crate) summaries: HashMap<u64, Summary>,
+}
+
+impl<'a> Inference<'a> {
+    pub(crate) fn new(graph: &'a AsgGraph) -> Self {
+        let let_values = graph
+            .nodes()
+            .filter_map(|node| match &node.content {
+                NodeContent::TermApplication(app) => match graph
+                    .get_node(app.function_node_id)
+                    .map(|function| &function.content)
+                {
+                    Some(NodeContent::TermLambda(_)) => {
+                        Some((app.function_node_id, app.argument_node_id))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        Self {
+            graph,
+            let_values,
+            summaries: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn summary(&mut self, node_id: u64) -> Result<Summary> {
+        if let Some(summary) = self.summaries.get(&node_id) {
+            return Ok(summary.clone());
+        }
+        let graph = self.graph;
+        let node = graph
+            .get_node(node_id)
+            .ok_or(TypeError::MissingNode(node_id))?;
+        let mut children = BTreeSet::new();
+        for child in node.content.child_ids() {
+            children.extend(self.summary(child)?.effects);
+        }
+        let summary = match &node.content {
+            NodeContent::TermLambda(lambda) => {
+                let body = self.summary(lambda.body_node_id)?;
+                let mut latent = vec![body.effects];
+                latent.extend(body.latent);
+                Summary {
+                    effects: BTreeSet::new(),
+                    latent,
+                }
+            }
+            NodeContent::TermVariable(variable) => {
+                match self.let_values.get(&variable.definition_node_id) {
+                    Some(&value) => Summary {
+                        effects: BTreeSet::new(),
+                        latent: self.summary(value)?.latent,
+                    },
+                    None => Summary::default(),
+                }
+            }
+            NodeContent::TermApplication(app) => {
+                let function = self.summary(app.function_node_id)?;
+                let mut effects = children;
+                effects.extend(function.call_effects());
+                Summary {
+                    effects,
+                    latent: function.latent.into_iter().skip(1).collect(),
+                }
+            }
+            NodeContent::EffectPerform(perform) => {
+                let mut effects = children;
+                effects.insert(perform.effect_name.clone());
+                Summary {
+                    effects,
+                    latent: Vec::new(),
+                }
+            }
+            NodeContent::TypeNode(_) => Summary::default(),
+            _ => Summary {
+                effects: children,
+                latent: Vec::new(),
+            },
+        };
+        self.summaries.insert(node_id, summary.clone());
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effects_of_root(source: &str) -> BTreeSet<String> {
+        let graph = parser_core::parse_str(source).unwrap();
+        let effects = infer_effects(&graph).unwrap();
+        effects[&graph.root().unwrap()].clone()
+    }
+
+    fn set(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn lambdas_defer_their_effects_to_applications() {
+        assert_eq!(effects_of_root("(x) => perform('IO', x)"), set(&[]));
+        assert_eq!(
+            effects_of_root("((x) => perform('IO', x))(1)"),
+            set(&["IO"])
+        );
+        assert_eq!(
+            effects_of_root("((x) => perform('IO', x))(perform('State', 1))"),
+            set(&["IO", "State"])
+        );
+    }
+
+    #[test]
+    fn let_bound_functions_carry_their_effects_to_each_call() {
+        assert_eq!(
+            effects_of_root("let f = (x) => perform('IO', x) in 1"),
+            set(&[])
+        );
+        assert_eq!(
+            effects_of_root("let f = (x) => perform('IO', x) in f(1)"),
+            set(&["IO"])
+        );
+        assert_eq!(
+            effects_of_root("let f = (x) => (y) => perform('Net', y) in let g = f(1) in g(2)"),
+            set(&["Net"])
+        );
+        assert_eq!(
+            effects_of_root("let f = (x) => (y) => perform('Net', y) in f(1)"),
+            set(&[])
+        );
+    }
+
+    #[test]
+    fn structural_nodes_without_effects_get_empty_sets() {
+        let graph =
+            parser_core::parse_str("let r = ref 1 in ((ignored) => !r + 1)(r := 2)").unwrap();
+        let effects = infer_effects(&graph).unwrap();
+        assert!(!effects.is_empty());
+        assert!(effects.values().all(BTreeSet::is_empty));
+    }
+}
@@ -1,14 +1,13 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Level 2 type checker: tracks which effects a program may perform.
+//!
+//! [`check_effects`] walks the graph from its root and rejects any
+//! `perform` whose effect is not allowed where it appears, and any call to a
+//! function whose inferred effects are not allowed there. Errors are the
+//! Level 1 [`TypeError`]s, so both levels report through the same channel.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod effects;
+pub mod infer;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use effects::check_effects;
+pub use infer::{EffectMap, infer_effects};
+pub use type_checker_l1::{Result, TypeError};
@@ -0,0 +1,94 @@
+//! Errors produced while reading UPIR text or verifying modules.
+
+use thiserror::Error;
+
+use crate::ir::ValueId;
+use crate::types::Type;
+
+/// Reasons UPIR text cannot be parsed. Offsets are byte offsets into the
+/// input.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    /// Something other than what the grammar allows at this point.
+    #[error("expected {expected} at offset {offset}, found '{found}'")]
+    Unexpected {
+        offset: usize,
+        expected: &'static str,
+        found: String,
+    },
+
+    /// The input ended early.
+    #[error("expected {expected}, found end of input")]
+    UnexpectedEnd { expected: &'static str },
+}
+
+/// A well-formedness problem found by [`crate::verify`], with the function,
+/// block and operation index it was found at. `op` is `None` for problems
+/// with a block as a whole.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error(
+    "in @{function} ^{block}{}: {kind}",
+    op.map(|index| format!(", operation {}", index)).unwrap_or_default()
+)]
+pub struct VerifyError {
+    pub function: String,
+    pub block: String,
+    pub op: Option<usize>,
+    pub kind: VerifyErrorKind,
+}
+
+/// What is wrong with a module.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum VerifyErrorKind {
+    /// The function has no blocks at all.
+    #[error("function has no blocks")]
+    NoBlocks,
+
+    /// Two blocks of the function share a label.
+    #[error("block label is used more than once")]
+    DuplicateBlock,
+
+    /// The block is empty or its last operation is not a terminator.
+    #[error("block does not end in a terminator")]
+    MissingTerminator,
+
+    /// A terminator is followed by more operations.
+    #[error("'{0}' is not the last operation of its block")]
+    MisplacedTerminator(String),
+
+    /// A value is defined more than once.
+    #[error("value %{0} is defined more than once")]
+    DuplicateValue(ValueId),
+
+    /// An operand is not a parameter or the result of a dominating
+    /// operation.
+    #[error("value %{0} is used where it is not defined")]
+    UndefinedValue(ValueId),
+
+    /// A branch names a block the function does not have.
+    #[error("branch to unknown block '{0}'")]
+    UnknownBlock(String),
+
+    /// A branch passes a different number of values than its target takes.
+    #[error("branch to ^{target} passes {found} value(s), but the block takes {expected}")]
+    BranchArity {
+        target: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// A `return` does not pass exactly one value.
+    #[error("return passes {0} values instead of one")]
+    ReturnArity(usize),
+
+    /// A `return` passes a value of the wrong type.
+    #[error("return of {found} from a function returning {expected}")]
+    ReturnType { expected: Type, found: Type },
+
+    /// A `callee` names a function the module does not have.
+    #[error("call to unknown function @{0}")]
+    UnknownFunction(String),
+}
+
+/// Convenience alias for parsing results.
+pub type Result<T> = std::result::Result<T, ParseError>;
@@ -0,0 +1,142 @@
+//! Core UPIR data structures.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::types::Type;
+
+/// Identifier of an SSA value within a function, printed as `%N`.
+pub type ValueId = u32;
+
+/// A typed SSA value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Value {
+    pub id: ValueId,
+    pub ty: Type,
+}
+
+/// A position in the original source program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A compile-time constant attached to an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attribute {
+    Int(i64),
+    Bool(bool),
+    String(String),
+    /// A reference to a module-level symbol such as a function.
+    Symbol(String),
+    /// A reference to a block in the same function, by label.
+    Block(String),
+    Location(Location),
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Attribute::Int(value) => write!(f, "{}", value),
+            Attribute::Bool(value) => write!(f, "{}", value),
+            Attribute::String(value) => write!(f, "{:?}", value),
+            Attribute::Symbol(name) => write!(f, "@{}", name),
+            Attribute::Block(label) => write!(f, "^{}", label),
+            Attribute::Location(loc) => write!(f, "loc({:?}:{}:{})", loc.file, loc.line, loc.column),
+        }
+    }
+}
+
+/// A single instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    /// Operation name, e.g. `const`, `add` or `call`.
+    pub name: String,
+    pub operands: Vec<ValueId>,
+    /// The value defined by this operation, if it produces one.
+    pub result: Option<Value>,
+    pub attributes: BTreeMap<String, Attribute>,
+}
+
+impl Operation {
+    /// Creates an operation with no operands, result or attributes.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            operands: Vec::new(),
+            result: None,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the operands of the operation.
+    pub fn with_operands(mut self, operands: Vec<ValueId>) -> Self {
+        self.operands = operands;
+        self
+    }
+
+    /// Sets the value defined by the operation.
+    pub fn with_result(mut self, result: Value) -> Self {
+        self.result = Some(result);
+        self
+    }
+
+    /// Adds or replaces an attribute.
+    pub fn with_attribute(mut self, key: impl Into<String>, value: Attribute) -> Self {
+        self.attributes.insert(key.into(), value);
+        self
+    }
+
+    /// Source location recorded on the operation, if any.
+    pub fn location(&self) -> Option<&Location> {
+        match self.attributes.get("location") {
+            Some(Attribute::Location(loc)) => Some(loc),
+            _ => None,
+        }
+    }
+}
+
+/// A labelled sequence of operations ending in a terminator.
+///
+/// Terminators are `return`, `br` to a `target` block and `cond_br` on a
+/// `bool` operand to a `then` or `else` block. A `br` passes its operands to
+/// the target's parameters, which take the place of phi nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub label: String,
+    pub params: Vec<Value>,
+    pub operations: Vec<Operation>,
+}
+
+/// A function definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Value>,
+    pub return_type: Type,
+    pub blocks: Vec<Block>,
+}
+
+/// A compilation unit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Module {
+    pub name: String,
+    pub functions: Vec<Function>,
+}
+
+impl Module {
+    /// Creates an empty module.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            functions: Vec::new(),
+        }
+    }
+
+    /// Looks up a function by name.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|func| func.name == name)
+    }
+}
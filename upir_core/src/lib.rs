@@ -1,14 +1,21 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Universal Polymorphic Intermediate Representation (UPIR).
+//!
+//! UPIR is the target-neutral IR that sits between the ASG and the code
+//! generation backends (LLVM, SPIR-V, quantum simulation). A [`Module`] holds
+//! functions made of labelled blocks of SSA-style [`Operation`]s.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod error;
+pub mod ir;
+pub mod parser;
+pub mod passes;
+pub mod printer;
+pub mod types;
+pub mod verify;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use error::{ParseError, Result, VerifyError, VerifyErrorKind};
+pub use ir::{Attribute, Block, Function, Location, Module, Operation, Value, ValueId};
+pub use parser::parse_module;
+pub use passes::{ConstFold, DeadCodeElim, Pass, PassManager};
+pub use printer::print_module;
+pub use types::{Type, parse_type};
+pub use verify::verify;
@@ -0,0 +1,555 @@
+//! Reading modules back from the text written by [`print_module`].
+//!
+//! The format is line-oriented: every function header, block label,
+//! operation and closing brace sits on a line of its own, and blank lines
+//! are ignored. Within a line, tokens may be separated by any amount of
+//! whitespace.
+//!
+//! ```text
+//! module    ::= "module" "@" ident "{" NL function* "}" NL
+//! function  ::= "func" "@" ident "(" params? ")" "->" type "{" NL block* "}" NL
+//! block     ::= "^" ident ("(" params ")")? ":" NL (operation NL)*
+//! params    ::= value ":" type ("," value ":" type)*
+//! operation ::= (value "=")? ident operands? attributes? (":" type)?
+//! operands  ::= value ("," value)*
+//! attributes ::= "{" (ident "=" attribute ("," ident "=" attribute)*)? "}"
+//! attribute ::= int | "true" | "false" | string | "@" ident | "^" ident
+//!             | "loc" "(" string ":" int ":" int ")"
+//! value     ::= "%" int
+//! ident     ::= [A-Za-z0-9_.$]+
+//! ```
+//!
+//! An operation has a result type exactly when it defines a value. Strings
+//! use Rust's escapes (`\"`, `\\`, `\n`, `\u{..}`, ...); `type` is the
+//! grammar of [`crate::types`].
+//!
+//! [`print_module`]: crate::printer::print_module
+
+use crate::error::{ParseError, Result};
+use crate::ir::{Attribute, Block, Function, Location, Module, Operation, Value, ValueId};
+use crate::types::{Type, TypeReader};
+
+/// Parses a module in the format produced by [`print_module`], so that
+/// `parse_module(&print_module(&m)) == Ok(m)` for every module whose names
+/// are identifiers.
+///
+/// [`print_module`]: crate::printer::print_module
+pub fn parse_module(text: &str) -> Result<Module> {
+    let mut lines = Lines { text, offset: 0 };
+    let mut header = lines.next("'module'")?;
+    header.expect("module")?;
+    header.expect("@")?;
+    let mut module = Module::new(header.ident("a module name")?);
+    header.expect("{")?;
+    header.end()?;
+    loop {
+        let mut line = lines.next("'func' or '}'")?;
+        if line.eat("}") {
+            line.end()?;
+            break;
+        }
+        module.functions.push(function(line, &mut lines)?);
+    }
+    match lines.next("end of input") {
+        Ok(line) => Err(line.unexpected("end of input")),
+        Err(_) => Ok(module),
+    }
+}
+
+fn function<'a>(mut header: Line<'a>, lines: &mut Lines<'a>) -> Result<Function> {
+    header.expect("func")?;
+    header.expect("@")?;
+    let name = header.ident("a function name")?;
+    header.expect("(")?;
+    let params = header.params()?;
+    header.expect("->")?;
+    let return_type = header.ty()?;
+    header.expect("{")?;
+    header.end()?;
+
+    let mut blocks: Vec<Block> = Vec::new();
+    loop {
+        let mut line = lines.next("an operation, a block label or '}'")?;
+        if line.eat("}") {
+            line.end()?;
+            break;
+        }
+        if line.eat("^") {
+            let label = line.ident("a block label")?;
+            let params = if line.eat("(") {
+                line.params()?
+            } else {
+                Vec::new()
+            };
+            line.expect(":")?;
+            line.end()?;
+            blocks.push(Block {
+                label,
+                params,
+                operations: Vec::new(),
+            });
+            continue;
+        }
+        let Some(block) = blocks.last_mut() else {
+            return Err(line.unexpected("a block label"));
+        };
+        block.operations.push(line.operation()?);
+    }
+    Ok(Function {
+        name,
+        params,
+        return_type,
+        blocks,
+    })
+}
+
+/// The non-blank lines of the input.
+struct Lines<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn next(&mut self, expected: &'static str) -> Result<Line<'a>> {
+        while self.offset < self.text.len() {
+            let start = self.offset;
+            let end = self.text[start..]
+                .find('\n')
+                .map_or(self.text.len(), |len| start + len);
+            self.offset = (end + 1).min(self.text.len());
+            if !self.text[start..end].trim().is_empty() {
+                return Ok(Line {
+                    text: self.text,
+                    offset: start,
+                    end,
+                });
+            }
+        }
+        Err(ParseError::UnexpectedEnd { expected })
+    }
+}
+
+/// A cursor over one line, `text[offset..end]`. Offsets are into the
+/// whole input so errors point at the right place.
+struct Line<'a> {
+    text: &'a str,
+    offset: usize,
+    end: usize,
+}
+
+impl<'a> Line<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.offset..self.end]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.offset += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &'static str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(token))
+        }
+    }
+
+    /// Fails unless only whitespace is left on the line.
+    fn end(&mut self) -> Result<()> {
+        self.skip_whitespace();
+        if self.rest().is_empty() {
+            Ok(())
+        } else {
+            Err(self.unexpected("end of line"))
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        let found: String = self
+            .rest()
+            .chars()
+            .take_while(|c| !c.is_whitespace())
+            .take(16)
+            .collect();
+        if found.is_empty() {
+            ParseError::Unexpected {
+                offset: self.offset,
+                expected,
+                found: "end of line".to_string(),
+            }
+        } else {
+            ParseError::Unexpected {
+                offset: self.offset,
+                expected,
+                found,
+            }
+        }
+    }
+
+    /// Consumes the longest run of characters matching `accept`.
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !accept(c)).unwrap_or(rest.len());
+        self.offset += len;
+        &rest[..len]
+    }
+
+    fn ident(&mut self, expected: &'static str) -> Result<String> {
+        self.skip_whitespace();
+        let ident = self.take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$'));
+        if ident.is_empty() {
+            return Err(self.unexpected(expected));
+        }
+        Ok(ident.to_string())
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, expected: &'static str) -> Result<T> {
+        self.skip_whitespace();
+        let start = self.offset;
+        let sign = usize::from(self.rest().starts_with('-'));
+        self.offset += sign;
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        let text = &self.text[start..self.offset];
+        match text.parse() {
+            Ok(number) if !digits.is_empty() => Ok(number),
+            _ => {
+                self.offset = start;
+                Err(self.unexpected(expected))
+            }
+        }
+    }
+
+    fn value_id(&mut self) -> Result<ValueId> {
+        self.expect("%")?;
+        self.number("a value number")
+    }
+
+    fn ty(&mut self) -> Result<Type> {
+        let mut reader = TypeReader {
+            text: &self.text[..self.end],
+            offset: self.offset,
+        };
+        let ty = reader.ty()?;
+        self.offset = reader.offset;
+        Ok(ty)
+    }
+
+    /// Typed values up to and including the closing parenthesis.
+    fn params(&mut self) -> Result<Vec<Value>> {
+        let mut params = Vec::new();
+        if self.eat(")") {
+            return Ok(params);
+        }
+        loop {
+            let id = self.value_id()?;
+            self.expect(":")?;
+            params.push(Value { id, ty: self.ty()? });
+            if self.eat(")") {
+                return Ok(params);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn operation(&mut self) -> Result<Operation> {
+        let result = if self.eat("%") {
+            let id = self.number("a value number")?;
+            self.expect("=")?;
+            Some(id)
+        } else {
+            None
+        };
+        let mut op = Operation::new(self.ident("an operation name")?);
+        self.skip_whitespace();
+        if self.rest().starts_with('%') {
+            op.operands.push(self.value_id()?);
+            while self.eat(",") {
+                op.operands.push(self.value_id()?);
+            }
+        }
+        if self.eat("{") && !self.eat("}") {
+            loop {
+                let key = self.ident("an attribute name")?;
+                self.expect("=")?;
+                let value = self.attribute()?;
+                op.attributes.insert(key, value);
+                if self.eat("}") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        if let Some(id) = result {
+            self.expect(":")?;
+            op.result = Some(Value { id, ty: self.ty()? });
+        }
+        self.end()?;
+        Ok(op)
+    }
+
+    fn attribute(&mut self) -> Result<Attribute> {
+        self.skip_whitespace();
+        if self.rest().starts_with('"') {
+            return Ok(Attribute::String(self.string()?));
+        }
+        if self.eat("@") {
+            return Ok(Attribute::Symbol(self.ident("a symbol name")?));
+        }
+        if self.eat("^") {
+            return Ok(Attribute::Block(self.ident("a block label")?));
+        }
+        if self.eat("loc(") {
+            let file = self.string()?;
+            self.expect(":")?;
+            let line = self.number("a line number")?;
+            self.expect(":")?;
+            let column = self.number("a column number")?;
+            self.expect(")")?;
+            return Ok(Attribute::Location(Location { file, line, column }));
+        }
+        if self.eat("true") {
+            return Ok(Attribute::Bool(true));
+        }
+        if self.eat("false") {
+            return Ok(Attribute::Bool(false));
+        }
+        self.number("an attribute value").map(Attribute::Int)
+    }
+
+    /// A double-quoted string with Rust's escapes, as written by `{:?}`.
+    fn string(&mut self) -> Result<String> {
+        self.expect("\"")?;
+        let mut value = String::new();
+        loop {
+            let rest = self.rest();
+            let Some(c) = rest.chars().next() else {
+                return Err(self.unexpected("a closing '\"'"));
+            };
+            match c {
+                '"' => {
+                    self.offset += 1;
+                    return Ok(value);
+                }
+                '\\' => {
+                    let (escaped, len) = match rest[1..].chars().next() {
+                        Some('n') => ('\n', 2),
+                        Some('r') => ('\r', 2),
+                        Some('t') => ('\t', 2),
+                        Some('0') => ('\0', 2),
+                        Some(c @ ('\\' | '"' | '\'')) => (c, 2),
+                        Some('u') => {
+                            let code = rest[2..]
+                                .strip_prefix('{')
+                                .and_then(|braced| braced.split_once('}'))
+                                .and_then(|(hex, _)| {
+                                    let code = u32::from_str_radix(hex, 16).ok()?;
+                                    Some((char::from_u32(code)?, hex.len() + 4))
+                                });
+                            code.ok_or_else(|| self.unexpected("a unicode escape"))?
+                        }
+                        _ => return Err(self.unexpected("a string escape")),
+                    };
+                    value.push(escaped);
+                    self.offset += len;
+                }
+                c => {
+                    value.push(c);
+                    self.offset += c.len_utf8();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::print_module;
+
+    #[test]
+    fn hand_written_ir_parses() {
+        let text = "
+module @demo {
+  func @main(%0: i64) -> bool {
+  ^entry:
+    %1 = const {value = -3} : i64
+    %2 = lt %0,%1 {location = loc(\"a \\\"b\\\".syn\":2:5)} : bool
+    br %2 {target = ^exit}
+
+  ^exit(%3: bool):
+    return %3
+  }
+}
+";
+        let module = parse_module(text).unwrap();
+        let main = module.function("main").unwrap();
+        assert_eq!(main.blocks.len(), 2);
+        let lt = &main.blocks[0].operations[1];
+        assert_eq!(lt.operands, [0, 1]);
+        assert_eq!(lt.location().unwrap().file, "a \"b\".syn");
+        assert_eq!(main.blocks[1].params[0].ty, Type::Bool);
+        assert_eq!(parse_module(&print_module(&module)), Ok(module));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        let text =
+            "module @m {\n  func @f() -> i64 {\n  ^entry:\n    %0 = const {value = x} : i64\n";
+        assert_eq!(
+            parse_module(text),
+            Err(ParseError::Unexpected {
+                offset: text.find("x}").unwrap(),
+                expected: "an attribute value",
+                found: "x}".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_module("module @m {\n}\n}"),
+            Err(ParseError::Unexpected {
+                offset: 14,
+                expected: "end of input",
+                found: "}".to_string(),
+            })
+        );
+        assert!(matches!(
+            parse_module("module @m {\n  func @f() -> i64 {\n    return %0\n  }\n}\n"),
+            Err(ParseError::Unexpected {
+                expected: "a block label",
+                ..
+            })
+        ));
+    }
+
+    /// A xorshift generator, so failures reproduce from the seed.
+    struct Gen(u64);
+
+    impl Gen {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+
+        fn pick<T: Clone>(&mut self, items: &[T]) -> T {
+            items[self.below(items.len())].clone()
+        }
+
+        fn ident(&mut self) -> String {
+            let len = 1 + self.below(6);
+            (0..len)
+                .map(|_| self.pick(&['a', 'z', 'A', '0', '9', '_', '.', '$']))
+                .collect()
+        }
+
+        fn string(&mut self) -> String {
+            let len = self.below(6);
+            (0..len)
+                .map(|_| {
+                    self.pick(&[
+                        'a', ' ', '"', '\\', '\n', '\r', '\t', '\0', '\'', 'é', '\u{301}', '\u{1}',
+                        '{', '}', ',', ':',
+                    ])
+                })
+                .collect()
+        }
+
+        fn ty(&mut self, depth: usize) -> Type {
+            match self.below(if depth == 0 { 4 } else { 7 }) {
+                0 => Type::Unit,
+                1 => Type::Bool,
+                2 => Type::I32,
+                3 => Type::I64,
+                4 => Type::Ref(Box::new(self.ty(depth - 1))),
+                5 => Type::Function {
+                    params: (0..self.below(3)).map(|_| self.ty(depth - 1)).collect(),
+                    ret: Box::new(self.ty(depth - 1)),
+                },
+                _ => Type::Tuple((0..self.below(3)).map(|_| self.ty(depth - 1)).collect()),
+            }
+        }
+
+        fn values(&mut self, max: usize) -> Vec<Value> {
+            (0..self.below(max + 1))
+                .map(|_| Value {
+                    id: self.below(100) as ValueId,
+                    ty: self.ty(2),
+                })
+                .collect()
+        }
+
+        fn attribute(&mut self) -> Attribute {
+            match self.below(6) {
+                0 => Attribute::Int(self.pick(&[0, -1, 42, i64::MIN, i64::MAX])),
+                1 => Attribute::Bool(self.below(2) == 0),
+                2 => Attribute::String(self.string()),
+                3 => Attribute::Symbol(self.ident()),
+                4 => Attribute::Block(self.ident()),
+                _ => Attribute::Location(Location {
+                    file: self.string(),
+                    line: self.below(1000) as u32,
+                    column: u32::MAX - self.below(2) as u32,
+                }),
+            }
+        }
+
+        fn operation(&mut self) -> Operation {
+            let mut op = Operation::new(self.ident());
+            op.operands = (0..self.below(3))
+                .map(|_| self.below(100) as ValueId)
+                .collect();
+            for _ in 0..self.below(3) {
+                let key = self.ident();
+                op.attributes.insert(key, self.attribute());
+            }
+            op.result = self.values(1).pop();
+            op
+        }
+
+        fn module(&mut self) -> Module {
+            let mut module = Module::new(self.ident());
+            for _ in 0..self.below(3) {
+                module.functions.push(Function {
+                    name: self.ident(),
+                    params: self.values(2),
+                    return_type: self.ty(2),
+                    blocks: (0..self.below(3))
+                        .map(|_| Block {
+                            label: self.ident(),
+                            params: self.values(2),
+                            operations: (0..self.below(4)).map(|_| self.operation()).collect(),
+                        })
+                        .collect(),
+                });
+            }
+            module
+        }
+    }
+
+    #[test]
+    fn printed_modules_parse_back_to_themselves() {
+        for seed in 1..=500u64 {
+            let module = Gen(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15)).module();
+            let text = print_module(&module);
+            assert_eq!(
+                parse_module(&text).as_ref(),
+                Ok(&module),
+                "seed {} printed as:\n{}",
+                seed,
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,332 @@
+//! Module-to-module UPIR transformations run before code generation.
+//!
+//! Each [`Pass`] rewrites a [`Module`] in place and reports whether it
+//! changed anything. A [`PassManager`] runs a sequence of passes until none
+//! of them changes the module any more; [`PassManager::for_level`] picks
+//! the passes for an optimization level: `0` runs none, `1` folds
+//! constants, `2` also removes dead code.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::{Attribute, Function, Module, Operation, ValueId};
+
+/// A transformation over a whole module.
+pub trait Pass {
+    /// The name reported for the pass, e.g. in `--verbose` output.
+    fn name(&self) -> &'static str;
+
+    /// Rewrites `module`, returning whether anything changed.
+    fn run(&self, module: &mut Module) -> bool;
+}
+
+/// Replaces arithmetic and comparisons on constant integers with their
+/// result.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConstFold;
+
+impl Pass for ConstFold {
+    fn name(&self) -> &'static str {
+        "constant-folding"
+    }
+
+    fn run(&self, module: &mut Module) -> bool {
+        module
+            .functions
+            .iter_mut()
+            .fold(false, |changed, func| fold_constants(func) | changed)
+    }
+}
+
+/// Removes side-effect-free operations whose result is never used.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeadCodeElim;
+
+impl Pass for DeadCodeElim {
+    fn name(&self) -> &'static str {
+        "dead-code-elimination"
+    }
+
+    fn run(&self, module: &mut Module) -> bool {
+        module
+            .functions
+            .iter_mut()
+            .fold(false, |changed, func| eliminate_dead_code(func) | changed)
+    }
+}
+
+/// An ordered sequence of passes.
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl PassManager {
+    /// Creates a manager with no passes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The passes run at optimization level `level`; levels above 2 are
+    /// treated as 2.
+    pub fn for_level(level: u8) -> Self {
+        match level {
+            0 => Self::new(),
+            1 => Self::new().with_pass(ConstFold),
+            _ => Self::new().with_pass(ConstFold).with_pass(DeadCodeElim),
+        }
+    }
+
+    /// Appends `pass` to the sequence.
+    pub fn with_pass(mut self, pass: impl Pass + 'static) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// The names of the passes, in the order they run.
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Runs every pass in order, and the whole sequence again as long as
+    /// one of them changed the module, since one pass can open up work for
+    /// another. Returns whether anything changed.
+    pub fn run(&self, module: &mut Module) -> bool {
+        let mut changed = false;
+        loop {
+            let round = self
+                .passes
+                .iter()
+                .fold(false, |changed, pass| pass.run(module) | changed);
+            if !round {
+                return changed;
+            }
+            changed = true;
+        }
+    }
+}
+
+/// Folds until no operation has only constant operands left. Each value is
+/// defined once, so a constant found in any block holds wherever it is used.
+fn fold_constants(func: &mut Function) -> bool {
+    let mut constants: HashMap<ValueId, i64> = HashMap::new();
+    let mut changed = false;
+    loop {
+        let mut folded = false;
+        for op in func
+            .blocks
+            .iter_mut()
+            .flat_map(|block| &mut block.operations)
+        {
+            let Some(result) = &op.result else {
+                continue;
+            };
+            if op.name == "const" {
+                if let Some(Attribute::Int(value)) = op.attributes.get("value") {
+                    constants.insert(result.id, *value);
+                }
+                continue;
+            }
+            let operands: Option<Vec<i64>> = op
+                .operands
+                .iter()
+                .map(|operand| constants.get(operand).copied())
+                .collect();
+            let Some(value) = operands.and_then(|operands| evaluate(&op.name, &operands)) else {
+                continue;
+            };
+            op.name = "const".to_string();
+            op.operands.clear();
+            op.attributes.insert("value".to_string(), value);
+            folded = true;
+        }
+        if !folded {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+/// The constant result of `name` applied to `operands`, if it is one this
+/// pass folds. Arithmetic wraps, as it does in generated code; division is
+/// left alone so a division by zero still happens at run time.
+fn evaluate(name: &str, operands: &[i64]) -> Option<Attribute> {
+    let &[lhs, rhs] = operands else {
+        return None;
+    };
+    Some(match name {
+        "add" => Attribute::Int(lhs.wrapping_add(rhs)),
+        "sub" => Attribute::Int(lhs.wrapping_sub(rhs)),
+        "mul" => Attribute::Int(lhs.wrapping_mul(rhs)),
+        "eq" => Attribute::Bool(lhs == rhs),
+        "ne" => Attribute::Bool(lhs != rhs),
+        "lt" => Attribute::Bool(lhs < rhs),
+        "le" => Attribute::Bool(lhs <= rhs),
+        "gt" => Attribute::Bool(lhs > rhs),
+        "ge" => Attribute::Bool(lhs >= rhs),
+        _ => return None,
+    })
+}
+
+/// Operations that only compute their result, so dropping an unused one
+/// cannot change what the program does.
+fn is_pure(op: &Operation) -> bool {
+    matches!(
+        op.name.as_str(),
+        "const"
+            | "unit"
+            | "func_ref"
+            | "add"
+            | "sub"
+            | "mul"
+            | "eq"
+            | "ne"
+            | "lt"
+            | "le"
+            | "gt"
+            | "ge"
+            | "not"
+    )
+}
+
+/// Removes unused pure operations until none are left, since removing one
+/// can leave its operands unused.
+fn eliminate_dead_code(func: &mut Function) -> bool {
+    let mut changed = false;
+    loop {
+        let used: HashSet<ValueId> = func
+            .blocks
+            .iter()
+            .flat_map(|block| &block.operations)
+            .flat_map(|op| op.operands.iter().copied())
+            .collect();
+        let mut removed = false;
+        for block in &mut func.blocks {
+            block.operations.retain(|op| {
+                let dead = is_pure(op)
+                    && op
+                        .result
+                        .as_ref()
+                        .is_some_and(|result| !used.contains(&result.id));
+                removed |= dead;
+                !dead
+            });
+        }
+        if !removed {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{Block, Value};
+    use crate::printer::print_module;
+    use crate::types::Type;
+
+    fn value(id: ValueId, ty: Type) -> Value {
+        Value { id, ty }
+    }
+
+    fn constant(id: ValueId, n: i64) -> Operation {
+        Operation::new("const")
+            .with_result(value(id, Type::I64))
+            .with_attribute("value", Attribute::Int(n))
+    }
+
+    /// `main(%0) = ((2 * 3) + %0, (2 * 3) < 7)` with an unused `4 - 1`.
+    fn module() -> Module {
+        let ops = vec![
+            constant(1, 2),
+            constant(2, 3),
+            Operation::new("mul")
+                .with_operands(vec![1, 2])
+                .with_result(value(3, Type::I64)),
+            Operation::new("add")
+                .with_operands(vec![3, 0])
+                .with_result(value(4, Type::I64)),
+            constant(5, 7),
+            Operation::new("lt")
+                .with_operands(vec![3, 5])
+                .with_result(value(6, Type::Bool)),
+            constant(7, 4),
+            constant(8, 1),
+            Operation::new("sub")
+                .with_operands(vec![7, 8])
+                .with_result(value(9, Type::I64)),
+            Operation::new("call")
+                .with_operands(vec![6])
+                .with_attribute("callee", Attribute::Symbol("log".to_string())),
+            Operation::new("return").with_operands(vec![4]),
+        ];
+        let mut module = Module::new("test");
+        module.functions.push(Function {
+            name: "main".to_string(),
+            params: vec![value(0, Type::I64)],
+            return_type: Type::I64,
+            blocks: vec![Block {
+                label: "entry".to_string(),
+                params: Vec::new(),
+                operations: ops,
+            }],
+        });
+        module
+    }
+
+    #[test]
+    fn level_zero_runs_nothing() {
+        let mut optimized = module();
+        assert!(!PassManager::for_level(0).run(&mut optimized));
+        assert_eq!(optimized, module());
+    }
+
+    #[test]
+    fn constants_fold_through_chains() {
+        let mut folded = module();
+        assert!(ConstFold.run(&mut folded));
+        let text = print_module(&folded);
+        assert!(text.contains("%3 = const {value = 6} : i64"), "{}", text);
+        assert!(text.contains("%4 = add %3, %0 : i64"), "{}", text);
+        assert!(
+            text.contains("%6 = const {value = true} : bool"),
+            "{}",
+            text
+        );
+        assert!(text.contains("%9 = const {value = 3} : i64"), "{}", text);
+        assert!(!ConstFold.run(&mut folded));
+    }
+
+    #[test]
+    fn the_manager_runs_to_a_fixpoint() {
+        // Dead code elimination alone only drops the unused `sub` and then
+        // its operands; the folded constants need folding to go first.
+        let mut pruned = module();
+        assert!(DeadCodeElim.run(&mut pruned));
+        assert_eq!(names(&pruned).len(), 8);
+
+        // Running elimination before folding still reaches the fixpoint.
+        let manager = PassManager::new()
+            .with_pass(DeadCodeElim)
+            .with_pass(ConstFold);
+        let mut optimized = module();
+        assert!(manager.run(&mut optimized));
+        assert_eq!(
+            names(&optimized),
+            ["const", "add", "const", "call", "return"]
+        );
+        assert!(!manager.run(&mut optimized));
+        assert_eq!(
+            PassManager::for_level(2).pass_names(),
+            ["constant-folding", "dead-code-elimination"]
+        );
+    }
+
+    fn names(module: &Module) -> Vec<&str> {
+        module.functions[0].blocks[0]
+            .operations
+            .iter()
+            .map(|op| op.name.as_str())
+            .collect()
+    }
+}
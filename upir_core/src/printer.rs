@@ -0,0 +1,133 @@
+//! Textual rendering of UPIR modules.
+
+use std::fmt::Write;
+
+use crate::ir::{Function, Module, Operation, Value};
+
+/// Renders a module in the UPIR textual format.
+///
+/// Functions and operations are printed in module order and attributes in
+/// key order, so equal modules always render to identical text. The format
+/// is described in [`crate::parser`], and [`crate::parse_module`] reads it
+/// back.
+///
+/// ```text
+/// module @example {
+///   func @main() -> i64 {
+///   ^entry:
+///     %0 = const {value = 1} : i64
+///     return %0
+///   }
+/// }
+/// ```
+pub fn print_module(module: &Module) -> String {
+    let mut out = String::new();
+    writeln!(out, "module @{} {{", module.name).unwrap();
+    for func in &module.functions {
+        print_function(&mut out, func);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn print_function(out: &mut String, func: &Function) {
+    writeln!(
+        out,
+        "  func @{}({}) -> {} {{",
+        func.name,
+        print_params(&func.params),
+        func.return_type
+    )
+    .unwrap();
+    for block in &func.blocks {
+        if block.params.is_empty() {
+            writeln!(out, "  ^{}:", block.label).unwrap();
+        } else {
+            writeln!(out, "  ^{}({}):", block.label, print_params(&block.params)).unwrap();
+        }
+        for op in &block.operations {
+            writeln!(out, "    {}", print_operation(op)).unwrap();
+        }
+    }
+    out.push_str("  }\n");
+}
+
+fn print_params(params: &[Value]) -> String {
+    let params: Vec<String> = params
+        .iter()
+        .map(|param| format!("%{}: {}", param.id, param.ty))
+        .collect();
+    params.join(", ")
+}
+
+/// Renders a single operation on one line.
+pub fn print_operation(op: &Operation) -> String {
+    let mut line = String::new();
+    if let Some(result) = &op.result {
+        write!(line, "%{} = ", result.id).unwrap();
+    }
+    line.push_str(&op.name);
+    if !op.operands.is_empty() {
+        let operands: Vec<String> = op.operands.iter().map(|id| format!("%{}", id)).collect();
+        write!(line, " {}", operands.join(", ")).unwrap();
+    }
+    if !op.attributes.is_empty() {
+        let attrs: Vec<String> = op
+            .attributes
+            .iter()
+            .map(|(key, value)| format!("{} = {}", key, value))
+            .collect();
+        write!(line, " {{{}}}", attrs.join(", ")).unwrap();
+    }
+    if let Some(result) = &op.result {
+        write!(line, " : {}", result.ty).unwrap();
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{Attribute, Block, Location};
+    use crate::types::Type;
+
+    #[test]
+    fn prints_function_with_attributes() {
+        let module = Module {
+            name: "example".to_string(),
+            functions: vec![Function {
+                name: "main".to_string(),
+                params: vec![],
+                return_type: Type::I64,
+                blocks: vec![Block {
+                    label: "entry".to_string(),
+                    params: Vec::new(),
+                    operations: vec![
+                        Operation::new("const")
+                            .with_result(Value {
+                                id: 0,
+                                ty: Type::I64,
+                            })
+                            .with_attribute("value", Attribute::Int(1))
+                            .with_attribute(
+                                "location",
+                                Attribute::Location(Location {
+                                    file: "main.syn".to_string(),
+                                    line: 3,
+                                    column: 7,
+                                }),
+                            ),
+                        Operation::new("return").with_operands(vec![0]),
+                    ],
+                }],
+            }],
+        };
+
+        assert_eq!(
+            print_module(&module),
+            "module @example {\n  func @main() -> i64 {\n  ^entry:\n    \
+             %0 = const {location = loc(\"main.syn\":3:7), value = 1} : i64\n    \
+             return %0\n  }\n}\n"
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! UPIR value types and their textual form.
+//!
+//! ```text
+//! type   ::= "unit" | "bool" | "i32" | "i64"
+//!          | "ref" "<" type ">"
+//!          | "fn" "(" types? ")" "->" type
+//!          | "(" ")" | "(" type "," ")" | "(" type ("," type)+ ")"
+//! types  ::= type ("," type)*
+//! ```
+//!
+//! Parentheses only ever denote tuples, so no type needs grouping; a
+//! one-element tuple is written with a trailing comma to tell it apart.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{ParseError, Result};
+
+/// The type of a UPIR value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    Unit,
+    Bool,
+    I32,
+    I64,
+    /// A pointer to a mutable cell holding the element type.
+    Ref(Box<Type>),
+    /// A function taking `params` and returning `ret`.
+    Function { params: Vec<Type>, ret: Box<Type> },
+    Tuple(Vec<Type>),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Unit => write!(f, "unit"),
+            Type::Bool => write!(f, "bool"),
+            Type::I32 => write!(f, "i32"),
+            Type::I64 => write!(f, "i64"),
+            Type::Ref(elem) => write!(f, "ref<{}>", elem),
+            Type::Function { params, ret } => {
+                write!(f, "fn(")?;
+                write_list(f, params)?;
+                write!(f, ") -> {}", ret)
+            }
+            Type::Tuple(elems) => {
+                write!(f, "(")?;
+                write_list(f, elems)?;
+                if elems.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, types: &[Type]) -> fmt::Result {
+    for (i, ty) in types.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", ty)?;
+    }
+    Ok(())
+}
+
+impl FromStr for Type {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Type> {
+        parse_type(text)
+    }
+}
+
+/// Parses a type in the form produced by its `Display` implementation.
+pub fn parse_type(text: &str) -> Result<Type> {
+    let mut reader = TypeReader { text, offset: 0 };
+    let ty = reader.ty()?;
+    reader.skip_whitespace();
+    match reader.rest().chars().next() {
+        None => Ok(ty),
+        Some(found) => Err(ParseError::Unexpected {
+            offset: reader.offset,
+            expected: "end of type",
+            found: found.to_string(),
+        }),
+    }
+}
+
+/// Reads types from the front of `text[offset..]`.
+pub(crate) struct TypeReader<'a> {
+    pub(crate) text: &'a str,
+    pub(crate) offset: usize,
+}
+
+impl<'a> TypeReader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.offset..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.offset += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &'static str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(token))
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        let found: String = self
+            .rest()
+            .chars()
+            .take_while(|c| !c.is_whitespace())
+            .take(16)
+            .collect();
+        if found.is_empty() {
+            ParseError::UnexpectedEnd { expected }
+        } else {
+            ParseError::Unexpected {
+                offset: self.offset,
+                expected,
+                found,
+            }
+        }
+    }
+
+    fn keyword(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        &rest[..len]
+    }
+
+    pub(crate) fn ty(&mut self) -> Result<Type> {
+        if self.eat("(") {
+            return self.tuple();
+        }
+        let keyword = self.keyword();
+        let ty = match keyword {
+            "unit" => Type::Unit,
+            "bool" => Type::Bool,
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "ref" => {
+                self.offset += keyword.len();
+                return self.reference();
+            }
+            "fn" => {
+                self.offset += keyword.len();
+                return self.function();
+            }
+            _ => return Err(self.unexpected("a type")),
+        };
+        self.offset += keyword.len();
+        Ok(ty)
+    }
+
+    fn reference(&mut self) -> Result<Type> {
+        self.expect("<")?;
+        let elem = self.ty()?;
+        self.expect(">")?;
+        Ok(Type::Ref(Box::new(elem)))
+    }
+
+    fn function(&mut self) -> Result<Type> {
+        self.expect("(")?;
+        let mut params = Vec::new();
+        if !self.eat(")") {
+            loop {
+                params.push(self.ty()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        self.expect("->")?;
+        let ret = self.ty()?;
+        Ok(Type::Function {
+            params,
+            ret: Box::new(ret),
+        })
+    }
+
+    /// The rest of a tuple after its opening parenthesis.
+    fn tuple(&mut self) -> Result<Type> {
+        let mut elems = Vec::new();
+        if self.eat(")") {
+            return Ok(Type::Tuple(elems));
+        }
+        elems.push(self.ty()?);
+        if !self.eat(",") {
+            return Err(self.unexpected("',' after a tuple element"));
+        }
+        while !self.eat(")") {
+            elems.push(self.ty()?);
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        Ok(Type::Tuple(elems))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(params: Vec<Type>, ret: Type) -> Type {
+        Type::Function {
+            params,
+            ret: Box::new(ret),
+        }
+    }
+
+    #[test]
+    fn every_variant_round_trips_through_text() {
+        let cases = [
+            (Type::Unit, "unit"),
+            (Type::Bool, "bool"),
+            (Type::I32, "i32"),
+            (Type::I64, "i64"),
+            (Type::Ref(Box::new(Type::I64)), "ref<i64>"),
+            (function(vec![], Type::Unit), "fn() -> unit"),
+            (
+                function(
+                    vec![function(vec![Type::I64], Type::Bool), Type::I32],
+                    function(vec![Type::I64], Type::I64),
+                ),
+                "fn(fn(i64) -> bool, i32) -> fn(i64) -> i64",
+            ),
+            (Type::Tuple(vec![]), "()"),
+            (Type::Tuple(vec![Type::I64]), "(i64,)"),
+            (
+                Type::Tuple(vec![Type::Bool, Type::Ref(Box::new(Type::Tuple(vec![])))]),
+                "(bool, ref<()>)",
+            ),
+        ];
+        for (ty, text) in cases {
+            assert_eq!(ty.to_string(), text);
+            assert_eq!(parse_type(text), Ok(ty.clone()), "parsing {}", text);
+            assert_eq!(ty.to_string().parse::<Type>(), Ok(ty));
+        }
+    }
+
+    #[test]
+    fn malformed_types_are_rejected() {
+        assert_eq!(
+            parse_type("ref<i64"),
+            Err(ParseError::UnexpectedEnd { expected: ">" })
+        );
+        assert!(matches!(
+            parse_type("(i64)"),
+            Err(ParseError::Unexpected { offset: 4, .. })
+        ));
+        assert!(matches!(
+            parse_type("i64 i64"),
+            Err(ParseError::Unexpected { offset: 4, .. })
+        ));
+        assert!(parse_type("i640").is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! Well-formedness checks run before a module reaches a backend.
+//!
+//! Backends assume a module is well formed and produce nonsense when it is
+//! not, so [`verify`] checks the invariants they rely on:
+//!
+//! - every block ends in exactly one terminator (`return`, `br` or
+//!   `cond_br`) and its labels are unique within the function;
+//! - every value is defined once, and every operand is a function or
+//!   block parameter or the result of an operation that dominates its use;
+//! - branches name existing blocks and pass as many operands as the target
+//!   takes parameters;
+//! - `return` passes one value of the function's return type;
+//! - `callee` symbols name functions of the module.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::error::{VerifyError, VerifyErrorKind};
+use crate::ir::{Attribute, Block, Function, Module, Operation, ValueId};
+use crate::types::Type;
+
+/// Checks every function of `module`, returning every problem found.
+pub fn verify(module: &Module) -> Result<(), Vec<VerifyError>> {
+    let functions: HashSet<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+    let mut errors = Vec::new();
+    for func in &module.functions {
+        FunctionVerifier {
+            func,
+            functions: &functions,
+            errors: &mut errors,
+        }
+        .verify();
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn is_terminator(op: &Operation) -> bool {
+    matches!(op.name.as_str(), "return" | "br" | "cond_br")
+}
+
+/// The blocks `op` may branch to, with the attribute naming each.
+fn branch_targets(op: &Operation) -> Vec<&Attribute> {
+    let keys: &[&str] = match op.name.as_str() {
+        "br" => &["target"],
+        "cond_br" => &["then", "else"],
+        _ => &[],
+    };
+    keys.iter()
+        .filter_map(|key| op.attributes.get(*key))
+        .collect()
+}
+
+struct FunctionVerifier<'a> {
+    func: &'a Function,
+    functions: &'a HashSet<&'a str>,
+    errors: &'a mut Vec<VerifyError>,
+}
+
+impl<'a> FunctionVerifier<'a> {
+    fn report(&mut self, block: &Block, op: Option<usize>, kind: VerifyErrorKind) {
+        self.errors.push(VerifyError {
+            function: self.func.name.clone(),
+            block: block.label.clone(),
+            op,
+            kind,
+        });
+    }
+
+    fn verify(mut self) {
+        if self.func.blocks.is_empty() {
+            self.errors.push(VerifyError {
+                function: self.func.name.clone(),
+                block: String::new(),
+                op: None,
+                kind: VerifyErrorKind::NoBlocks,
+            });
+            return;
+        }
+        let mut labels = HashMap::new();
+        for (index, block) in self.func.blocks.iter().enumerate() {
+            if labels.insert(block.label.as_str(), index).is_some() {
+                self.report(block, None, VerifyErrorKind::DuplicateBlock);
+            }
+        }
+        let types = self.value_types();
+        let dominators = self.dominators(&labels);
+
+        for (index, block) in self.func.blocks.iter().enumerate() {
+            // Values visible on entry to the block: the function's
+            // parameters and everything defined in a dominating block.
+            let mut visible: HashSet<ValueId> =
+                self.func.params.iter().map(|param| param.id).collect();
+            for &dominator in &dominators[index] {
+                if dominator != index {
+                    visible.extend(self.definitions(&self.func.blocks[dominator]));
+                }
+            }
+            visible.extend(block.params.iter().map(|param| param.id));
+            self.verify_block(block, &labels, &types, &mut visible);
+        }
+    }
+
+    fn verify_block(
+        &mut self,
+        block: &Block,
+        labels: &HashMap<&str, usize>,
+        types: &HashMap<ValueId, &'a Type>,
+        visible: &mut HashSet<ValueId>,
+    ) {
+        match block.operations.last() {
+            Some(last) if is_terminator(last) => {}
+            _ => self.report(block, None, VerifyErrorKind::MissingTerminator),
+        }
+        for (index, op) in block.operations.iter().enumerate() {
+            let at = Some(index);
+            if is_terminator(op) && index + 1 != block.operations.len() {
+                self.report(
+                    block,
+                    at,
+                    VerifyErrorKind::MisplacedTerminator(op.name.clone()),
+                );
+            }
+            for &operand in &op.operands {
+                if !visible.contains(&operand) {
+                    self.report(block, at, VerifyErrorKind::UndefinedValue(operand));
+                }
+            }
+            for target in branch_targets(op) {
+                let Attribute::Block(label) = target else {
+                    self.report(block, at, VerifyErrorKind::UnknownBlock(target.to_string()));
+                    continue;
+                };
+                let Some(&target) = labels.get(label.as_str()) else {
+                    self.report(block, at, VerifyErrorKind::UnknownBlock(label.clone()));
+                    continue;
+                };
+                let expected = self.func.blocks[target].params.len();
+                let found = if op.name == "br" {
+                    op.operands.len()
+                } else {
+                    0
+                };
+                if expected != found {
+                    let kind = VerifyErrorKind::BranchArity {
+                        target: label.clone(),
+                        expected,
+                        found,
+                    };
+                    self.report(block, at, kind);
+                }
+            }
+            if op.name == "return" {
+                self.verify_return(block, index, op, types);
+            }
+            if let Some(Attribute::Symbol(callee)) = op.attributes.get("callee")
+                && !self.functions.contains(callee.as_str())
+            {
+                self.report(block, at, VerifyErrorKind::UnknownFunction(callee.clone()));
+            }
+            if let Some(result) = &op.result {
+                visible.insert(result.id);
+            }
+        }
+    }
+
+    fn verify_return(
+        &mut self,
+        block: &Block,
+        index: usize,
+        op: &Operation,
+        types: &HashMap<ValueId, &'a Type>,
+    ) {
+        let [value] = op.operands[..] else {
+            let kind = VerifyErrorKind::ReturnArity(op.operands.len());
+            return self.report(block, Some(index), kind);
+        };
+        if let Some(&found) = types.get(&value)
+            && *found != self.func.return_type
+        {
+            let kind = VerifyErrorKind::ReturnType {
+                expected: self.func.return_type.clone(),
+                found: found.clone(),
+            };
+            self.report(block, Some(index), kind);
+        }
+    }
+
+    /// The values defined in `block`: its parameters and operation results.
+    fn definitions(&self, block: &'a Block) -> impl Iterator<Item = ValueId> + 'a {
+        let params = block.params.iter().map(|param| param.id);
+        let results = block
+            .operations
+            .iter()
+            .filter_map(|op| op.result.as_ref().map(|result| result.id));
+        params.chain(results)
+    }
+
+    /// The type of every value, reporting values defined more than once.
+    fn value_types(&mut self) -> HashMap<ValueId, &'a Type> {
+        let mut types = HashMap::new();
+        for param in &self.func.params {
+            types.insert(param.id, &param.ty);
+        }
+        for block in &self.func.blocks {
+            for param in &block.params {
+                if types.insert(param.id, &param.ty).is_some() {
+                    self.report(block, None, VerifyErrorKind::DuplicateValue(param.id));
+                }
+            }
+            for (index, op) in block.operations.iter().enumerate() {
+                if let Some(result) = &op.result
+                    && types.insert(result.id, &result.ty).is_some()
+                {
+                    self.report(
+                        block,
+                        Some(index),
+                        VerifyErrorKind::DuplicateValue(result.id),
+                    );
+                }
+            }
+        }
+        types
+    }
+
+    /// The blocks dominating each block, by index, found by iterating the
+    /// dataflow equations to a fixpoint. Blocks unreachable from the entry
+    /// are dominated by every block.
+    fn dominators(&self, labels: &HashMap<&str, usize>) -> Vec<BTreeSet<usize>> {
+        let count = self.func.blocks.len();
+        let mut predecessors = vec![Vec::new(); count];
+        for (index, block) in self.func.blocks.iter().enumerate() {
+            let targets = block.operations.iter().flat_map(branch_targets);
+            for target in targets {
+                if let Attribute::Block(label) = target
+                    && let Some(&target) = labels.get(label.as_str())
+                {
+                    predecessors[target].push(index);
+                }
+            }
+        }
+        let all: BTreeSet<usize> = (0..count).collect();
+        let mut dominators = vec![all; count];
+        dominators[0] = BTreeSet::from([0]);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 1..count {
+                let mut next = predecessors[index]
+                    .iter()
+                    .map(|&pred| dominators[pred].clone())
+                    .reduce(|a, b| &a & &b)
+                    .unwrap_or_else(|| (0..count).collect());
+                next.insert(index);
+                if next != dominators[index] {
+                    dominators[index] = next;
+                    changed = true;
+                }
+            }
+        }
+        dominators
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Value;
+
+    fn value(id: ValueId, ty: Type) -> Value {
+        Value { id, ty }
+    }
+
+    fn block(label: &str, params: Vec<Value>, operations: Vec<Operation>) -> Block {
+        Block {
+            label: label.to_string(),
+            params,
+            operations,
+        }
+    }
+
+    fn module(blocks: Vec<Block>) -> Module {
+        let mut module = Module::new("test");
+        module.functions.push(Function {
+            name: "main".to_string(),
+            params: vec![value(0, Type::Bool)],
+            return_type: Type::Bool,
+            blocks,
+        });
+        module
+    }
+
+    fn br(target: &str, operands: Vec<ValueId>) -> Operation {
+        Operation::new("br")
+            .with_operands(operands)
+            .with_attribute("target", Attribute::Block(target.to_string()))
+    }
+
+    fn ret(value: ValueId) -> Operation {
+        Operation::new("return").with_operands(vec![value])
+    }
+
+    /// `main(%0) = %0 and (%0 == %0)`, shaped the way `asg_to_upir` lowers
+    /// a short-circuit.
+    fn short_circuit() -> Vec<Block> {
+        vec![
+            block(
+                "entry",
+                Vec::new(),
+                vec![
+                    Operation::new("cond_br")
+                        .with_operands(vec![0])
+                        .with_attribute("then", Attribute::Block("rhs".to_string()))
+                        .with_attribute("else", Attribute::Block("short".to_string())),
+                ],
+            ),
+            block(
+                "rhs",
+                Vec::new(),
+                vec![
+                    Operation::new("eq")
+                        .with_operands(vec![0, 0])
+                        .with_result(value(1, Type::Bool)),
+                    br("end", vec![1]),
+                ],
+            ),
+            block("short", Vec::new(), vec![br("end", vec![0])]),
+            block("end", vec![value(2, Type::Bool)], vec![ret(2)]),
+        ]
+    }
+
+    #[test]
+    fn lowered_short_circuit_is_well_formed() {
+        assert_eq!(verify(&module(short_circuit())), Ok(()));
+    }
+
+    #[test]
+    fn values_must_dominate_their_uses() {
+        let mut blocks = short_circuit();
+        // `%1` is defined on only one of the paths into `end`.
+        blocks[3].operations.insert(
+            0,
+            Operation::new("not")
+                .with_operands(vec![1])
+                .with_result(value(3, Type::Bool)),
+        );
+        let errors = verify(&module(blocks)).unwrap_err();
+        assert_eq!(
+            errors,
+            [VerifyError {
+                function: "main".to_string(),
+                block: "end".to_string(),
+                op: Some(0),
+                kind: VerifyErrorKind::UndefinedValue(1),
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "in @main ^end, operation 0: value %1 is used where it is not defined"
+        );
+    }
+
+    #[test]
+    fn structural_problems_are_all_reported() {
+        let mut blocks = short_circuit();
+        blocks[1].operations.pop();
+        blocks[2].operations = vec![br("nowhere", Vec::new())];
+        blocks[3].operations = vec![
+            Operation::new("call")
+                .with_operands(vec![2])
+                .with_attribute("callee", Attribute::Symbol("missing".to_string()))
+                .with_result(value(4, Type::I64)),
+            ret(4),
+            ret(2),
+        ];
+        let kinds: Vec<_> = verify(&module(blocks))
+            .unwrap_err()
+            .into_iter()
+            .map(|error| (error.block, error.op, error.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("rhs".to_string(), None, VerifyErrorKind::MissingTerminator),
+                (
+                    "short".to_string(),
+                    Some(0),
+                    VerifyErrorKind::UnknownBlock("nowhere".to_string())
+                ),
+                (
+                    "end".to_string(),
+                    Some(0),
+                    VerifyErrorKind::UnknownFunction("missing".to_string())
+                ),
+                (
+                    "end".to_string(),
+                    Some(1),
+                    VerifyErrorKind::MisplacedTerminator("return".to_string())
+                ),
+                (
+                    "end".to_string(),
+                    Some(1),
+                    VerifyErrorKind::ReturnType {
+                        expected: Type::Bool,
+                        found: Type::I64,
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,682 @@
+//! The UPIR → LLVM IR text emitter.
+//!
+//! Each UPIR value `%N` becomes the LLVM value `%vN`. Constants and function
+//! references produce no instruction; their uses are replaced by the
+//! constant or `@symbol` directly. `unit` is the empty struct `{}`, `ref<T>`
+//! and function values are opaque pointers, and `ref` allocates its cell with
+//! `synapse_runtime::alloc::synapse_alloc`. A program that allocates runs
+//! `main` in a region, so its cells are freed when `main` returns unless the
+//! result is itself a reference. Arithmetic follows the integer semantics in `DESIGN_LOG.md`:
+//! it wraps, and `div` and `mod` trap on a zero divisor. Block parameters
+//! become `phi` nodes, so every branch to a block must come before it.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use upir_core::{Attribute, Block, Function, Location, Module, Operation, Type, ValueId};
+
+use crate::error::{CodegenError, Result};
+
+/// Knobs for [`emit_module`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EmitOptions {
+    /// Emit DWARF debug metadata: a compile unit, one `DISubprogram` per
+    /// function and a `!dbg` `DILocation` on every located instruction.
+    pub debug_info: bool,
+}
+
+/// Translates `module` into textual LLVM IR.
+pub fn emit_module(module: &Module, options: EmitOptions) -> Result<String> {
+    let mut emitter = ModuleEmitter {
+        output: format!("; ModuleID = '{}'\n", module.name),
+        allocates: module
+            .functions
+            .iter()
+            .flat_map(operations)
+            .any(|op| op.name == "ref"),
+        uses_trap: false,
+        debug: options.debug_info.then(|| DebugInfo::new(module)),
+    };
+    if let Some(debug) = &emitter.debug {
+        writeln!(emitter.output, "source_filename = {:?}", debug.filename).unwrap();
+    }
+    for function in &module.functions {
+        emitter.output.push('\n');
+        emitter.function(function)?;
+    }
+    if emitter.allocates {
+        emitter.output.push_str(
+            "\ndeclare ptr @synapse_alloc(i64)\n\
+             declare void @synapse_region_enter()\n\
+             declare void @synapse_region_exit()\n",
+        );
+    }
+    if emitter.uses_trap {
+        emitter.output.push_str("\ndeclare void @llvm.trap()\n");
+    }
+    if let Some(debug) = emitter.debug {
+        emitter.output.push('\n');
+        emitter.output.push_str(&debug.finish());
+    }
+    Ok(emitter.output)
+}
+
+struct ModuleEmitter {
+    output: String,
+    /// Whether any function allocates a reference cell.
+    allocates: bool,
+    uses_trap: bool,
+    debug: Option<DebugInfo>,
+}
+
+impl ModuleEmitter {
+    fn function(&mut self, function: &Function) -> Result<()> {
+        let mut body = FunctionEmitter {
+            function,
+            operands: HashMap::new(),
+            types: HashMap::new(),
+            scope: self.debug.as_mut().map(|debug| debug.subprogram(function)),
+            lines: Vec::new(),
+            label: String::new(),
+            incoming: HashMap::new(),
+            region: self.allocates
+                && function.name == "main"
+                && !matches!(function.return_type, Type::Ref(_)),
+        };
+        let mut params = Vec::new();
+        for param in &function.params {
+            let ty = llvm_type(&param.ty)?;
+            params.push(format!("{} %v{}", ty, param.id));
+            body.define(param.id, format!("%v{}", param.id), param.ty.clone());
+        }
+        for block in &function.blocks {
+            body.lines.push(format!("{}:", block.label));
+            body.label = block.label.clone();
+            body.block_params(block)?;
+            if body.region && body.lines.len() == 1 {
+                body.lines
+                    .push("  call void @synapse_region_enter()".to_string());
+            }
+            for op in &block.operations {
+                body.operation(op, self)?;
+            }
+        }
+
+        let attachment = body
+            .scope
+            .map(|scope| format!(" !dbg !{}", scope))
+            .unwrap_or_default();
+        writeln!(
+            self.output,
+            "define {} @{}({}){} {{",
+            llvm_type(&function.return_type)?,
+            function.name,
+            params.join(", "),
+            attachment
+        )
+        .unwrap();
+        for line in body.lines {
+            self.output.push_str(&line);
+            self.output.push('\n');
+        }
+        self.output.push_str("}\n");
+        Ok(())
+    }
+}
+
+struct FunctionEmitter<'f> {
+    function: &'f Function,
+    /// How each UPIR value is written as an LLVM operand.
+    operands: HashMap<ValueId, String>,
+    types: HashMap<ValueId, Type>,
+    /// The function's `DISubprogram`, when emitting debug info.
+    scope: Option<usize>,
+    lines: Vec<String>,
+    /// The LLVM block instructions are currently appended to.
+    label: String,
+    /// For each block label, the branches seen so far into it: the LLVM block
+    /// they leave from and the operands they pass.
+    incoming: HashMap<String, Vec<(String, Vec<String>)>>,
+    /// Whether the function runs in a region of its own, exited on return.
+    region: bool,
+}
+
+impl FunctionEmitter<'_> {
+    fn define(&mut self, id: ValueId, operand: String, ty: Type) {
+        self.operands.insert(id, operand);
+        self.types.insert(id, ty);
+    }
+
+    /// The LLVM operand for `id`, without its type.
+    fn value(&self, id: ValueId) -> Result<String> {
+        self.operands
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| CodegenError::UndefinedValue {
+                function: self.function.name.clone(),
+                value: id,
+            })
+    }
+
+    fn type_of(&self, id: ValueId) -> Result<&Type> {
+        self.types
+            .get(&id)
+            .ok_or_else(|| CodegenError::UndefinedValue {
+                function: self.function.name.clone(),
+                value: id,
+            })
+    }
+
+    /// The LLVM operand for `id` preceded by its type, e.g. `i64 %v3`.
+    fn typed(&self, id: ValueId) -> Result<String> {
+        Ok(format!(
+            "{} {}",
+            llvm_type(self.type_of(id)?)?,
+            self.value(id)?
+        ))
+    }
+
+    /// Emits a `phi` for each of `block`'s parameters, merging the operands
+    /// of the branches already emitted into it.
+    fn block_params(&mut self, block: &Block) -> Result<()> {
+        if block.params.is_empty() {
+            return Ok(());
+        }
+        let incoming = self.incoming.remove(&block.label).unwrap_or_default();
+        if incoming.is_empty() {
+            return Err(CodegenError::UnreachableBlock {
+                function: self.function.name.clone(),
+                label: block.label.clone(),
+            });
+        }
+        for (index, param) in block.params.iter().enumerate() {
+            let name = format!("%v{}", param.id);
+            let sources: Vec<String> = incoming
+                .iter()
+                .map(|(from, operands)| {
+                    let operand = operands.get(index).map_or("undef", String::as_str);
+                    format!("[ {}, %{} ]", operand, from)
+                })
+                .collect();
+            self.lines.push(format!(
+                "  {} = phi {} {}",
+                name,
+                llvm_type(&param.ty)?,
+                sources.join(", ")
+            ));
+            self.define(param.id, name, param.ty.clone());
+        }
+        Ok(())
+    }
+
+    /// The label named by a block-reference attribute of `op`.
+    fn block_label<'o>(&self, op: &'o Operation, attribute: &'static str) -> Result<&'o str> {
+        match op.attributes.get(attribute) {
+            Some(Attribute::Block(label)) => Ok(label),
+            _ => Err(CodegenError::MissingAttribute {
+                function: self.function.name.clone(),
+                op: op.name.clone(),
+                attribute,
+            }),
+        }
+    }
+
+    fn unsupported(&self, op: &Operation) -> CodegenError {
+        CodegenError::UnsupportedOperation {
+            function: self.function.name.clone(),
+            op: op.name.clone(),
+        }
+    }
+
+    fn operation(&mut self, op: &Operation, module: &mut ModuleEmitter) -> Result<()> {
+        let Some(result) = &op.result else {
+            return match op.name.as_str() {
+                "return" => {
+                    let [value] = op.operands[..] else {
+                        return Err(self.unsupported(op));
+                    };
+                    let value = self.typed(value)?;
+                    if self.region {
+                        self.instruction(
+                            "call void @synapse_region_exit()".to_string(),
+                            op,
+                            module,
+                        );
+                    }
+                    self.instruction(format!("ret {}", value), op, module);
+                    Ok(())
+                }
+                "br" => {
+                    let target = self.block_label(op, "target")?;
+                    let operands = op
+                        .operands
+                        .iter()
+                        .map(|&operand| self.value(operand))
+                        .collect::<Result<Vec<_>>>()?;
+                    self.incoming
+                        .entry(target.to_string())
+                        .or_default()
+                        .push((self.label.clone(), operands));
+                    self.instruction(format!("br label %{}", target), op, module);
+                    Ok(())
+                }
+                "cond_br" => {
+                    let [condition] = op.operands[..] else {
+                        return Err(self.unsupported(op));
+                    };
+                    let condition = self.typed(condition)?;
+                    let (then, otherwise) =
+                        (self.block_label(op, "then")?, self.block_label(op, "else")?);
+                    for target in [then, otherwise] {
+                        self.incoming
+                            .entry(target.to_string())
+                            .or_default()
+                            .push((self.label.clone(), Vec::new()));
+                    }
+                    self.instruction(
+                        format!("br {}, label %{}, label %{}", condition, then, otherwise),
+                        op,
+                        module,
+                    );
+                    Ok(())
+                }
+                _ => Err(self.unsupported(op)),
+            };
+        };
+        let name = format!("%v{}", result.id);
+        let ty = llvm_type(&result.ty)?;
+        match (op.name.as_str(), &op.operands[..]) {
+            ("const", []) => {
+                let constant = match op.attributes.get("value") {
+                    Some(Attribute::Int(value)) => value.to_string(),
+                    Some(Attribute::Bool(value)) => value.to_string(),
+                    _ => {
+                        return Err(CodegenError::MissingAttribute {
+                            function: self.function.name.clone(),
+                            op: op.name.clone(),
+                            attribute: "value",
+                        });
+                    }
+                };
+                self.define(result.id, constant, result.ty.clone());
+                return Ok(());
+            }
+            ("unit", []) => {
+                self.define(result.id, "zeroinitializer".to_string(), Type::Unit);
+                return Ok(());
+            }
+            ("func_ref", []) => {
+                let callee = self.callee(op)?;
+                self.define(result.id, callee, result.ty.clone());
+                return Ok(());
+            }
+            ("add" | "sub" | "mul" | "and" | "or", [lhs, rhs]) => {
+                let text = format!(
+                    "{} = {} {}, {}",
+                    name,
+                    op.name,
+                    self.typed(*lhs)?,
+                    self.value(*rhs)?
+                );
+                self.instruction(text, op, module);
+            }
+            ("div" | "mod", [lhs, rhs]) => {
+                let instruction = if op.name == "div" { "sdiv" } else { "srem" };
+                let (lhs, rhs) = (self.value(*lhs)?, self.value(*rhs)?);
+                self.checked_division(&name, instruction, &lhs, &rhs, op, module);
+            }
+            ("eq" | "ne" | "lt" | "le" | "gt" | "ge", [lhs, _])
+                if *self.type_of(*lhs)? == Type::Unit =>
+            {
+                // `icmp` cannot compare structs; all unit values are equal.
+                let equal = matches!(op.name.as_str(), "eq" | "le" | "ge");
+                self.define(result.id, equal.to_string(), Type::Bool);
+                return Ok(());
+            }
+            ("eq" | "ne" | "lt" | "le" | "gt" | "ge", [lhs, rhs]) => {
+                let predicate = match op.name.as_str() {
+                    "lt" => "slt",
+                    "le" => "sle",
+                    "gt" => "sgt",
+                    "ge" => "sge",
+                    other => other,
+                };
+                let text = format!(
+                    "{} = icmp {} {}, {}",
+                    name,
+                    predicate,
+                    self.typed(*lhs)?,
+                    self.value(*rhs)?
+                );
+                self.instruction(text, op, module);
+            }
+            ("not", [operand]) => {
+                let text = format!("{} = xor {}, true", name, self.typed(*operand)?);
+                self.instruction(text, op, module);
+            }
+            ("call", [argument]) => {
+                let text = format!(
+                    "{} = call {} {}({})",
+                    name,
+                    ty,
+                    self.callee(op)?,
+                    self.typed(*argument)?
+                );
+                self.instruction(text, op, module);
+            }
+            ("call_indirect", [function, argument]) => {
+                let text = format!(
+                    "{} = call {} {}({})",
+                    name,
+                    ty,
+                    self.value(*function)?,
+                    self.typed(*argument)?
+                );
+                self.instruction(text, op, module);
+            }
+            ("ref", [init]) => {
+                let size = size_of(self.type_of(*init)?)?;
+                let store = format!("store {}, ptr {}", self.typed(*init)?, name);
+                self.instruction(
+                    format!("{} = call ptr @synapse_alloc(i64 {})", name, size),
+                    op,
+                    module,
+                );
+                self.instruction(store, op, module);
+            }
+            ("deref", [cell]) => {
+                let text = format!("{} = load {}, {}", name, ty, self.typed(*cell)?);
+                self.instruction(text, op, module);
+            }
+            ("assign", [cell, value]) => {
+                let text = format!("store {}, {}", self.typed(*value)?, self.typed(*cell)?);
+                self.instruction(text, op, module);
+                self.define(result.id, "zeroinitializer".to_string(), Type::Unit);
+                return Ok(());
+            }
+            _ => return Err(self.unsupported(op)),
+        }
+        self.define(result.id, name, result.ty.clone());
+        Ok(())
+    }
+
+    /// Emits `name = instruction i64 lhs, rhs` with the semantics the
+    /// interpreter gives `div` and `mod`: a zero divisor traps, and
+    /// `i64::MIN / -1` wraps instead of being undefined.
+    fn checked_division(
+        &mut self,
+        name: &str,
+        instruction: &str,
+        lhs: &str,
+        rhs: &str,
+        op: &Operation,
+        module: &mut ModuleEmitter,
+    ) {
+        let label = &name[1..];
+        module.uses_trap = true;
+        self.instruction(format!("{name}.zero = icmp eq i64 {rhs}, 0"), op, module);
+        self.instruction(
+            format!("br i1 {name}.zero, label {name}.trap, label {name}.ok"),
+            op,
+            module,
+        );
+        self.lines.push(format!("{label}.trap:"));
+        self.instruction("call void @llvm.trap()".to_string(), op, module);
+        self.instruction("unreachable".to_string(), op, module);
+        self.lines.push(format!("{label}.ok:"));
+        self.label = format!("{label}.ok");
+        self.instruction(
+            format!("{name}.min = icmp eq i64 {lhs}, {}", i64::MIN),
+            op,
+            module,
+        );
+        self.instruction(format!("{name}.neg = icmp eq i64 {rhs}, -1"), op, module);
+        self.instruction(
+            format!("{name}.wraps = and i1 {name}.min, {name}.neg"),
+            op,
+            module,
+        );
+        // Dividing by 1 instead yields the wrapped quotient and remainder.
+        self.instruction(
+            format!("{name}.divisor = select i1 {name}.wraps, i64 1, i64 {rhs}"),
+            op,
+            module,
+        );
+        self.instruction(
+            format!("{name} = {instruction} i64 {lhs}, {name}.divisor"),
+            op,
+            module,
+        );
+    }
+
+    fn callee(&self, op: &Operation) -> Result<String> {
+        match op.attributes.get("callee") {
+            Some(Attribute::Symbol(symbol)) => Ok(format!("@{}", symbol)),
+            _ => Err(CodegenError::MissingAttribute {
+                function: self.function.name.clone(),
+                op: op.name.clone(),
+                attribute: "callee",
+            }),
+        }
+    }
+
+    /// Appends an instruction, attaching `op`'s location when emitting debug
+    /// info.
+    fn instruction(&mut self, text: String, op: &Operation, module: &mut ModuleEmitter) {
+        let attachment = match (self.scope, op.location(), module.debug.as_mut()) {
+            (Some(scope), Some(location), Some(debug)) => {
+                format!(", !dbg !{}", debug.location(location, scope))
+            }
+            _ => String::new(),
+        };
+        self.lines.push(format!("  {}{}", text, attachment));
+    }
+}
+
+/// Debug metadata collected while emitting, numbered in creation order.
+struct DebugInfo {
+    filename: String,
+    nodes: Vec<String>,
+    compile_unit: usize,
+    file: usize,
+    subroutine_type: usize,
+    locations: HashMap<(u32, u32, usize), usize>,
+}
+
+impl DebugInfo {
+    /// Starts the metadata for `module`, naming the file after the first
+    /// location found in it.
+    fn new(module: &Module) -> Self {
+        let filename = module
+            .functions
+            .iter()
+            .flat_map(operations)
+            .find_map(Operation::location)
+            .map_or_else(|| format!("{}.syn", module.name), |loc| loc.file.clone());
+        let mut debug = Self {
+            filename,
+            nodes: Vec::new(),
+            compile_unit: 0,
+            file: 0,
+            subroutine_type: 0,
+            locations: HashMap::new(),
+        };
+        debug.file = debug.add(format!(
+            "!DIFile(filename: {:?}, directory: \".\")",
+            debug.filename
+        ));
+        debug.compile_unit = debug.add(format!(
+            "distinct !DICompileUnit(language: DW_LANG_C, file: !{}, producer: \"synapse\", \
+             isOptimized: false, runtimeVersion: 0, emissionKind: FullDebug)",
+            debug.file
+        ));
+        debug.subroutine_type = debug.add("!DISubroutineType(types: !{})".to_string());
+        debug
+    }
+
+    fn add(&mut self, node: String) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Adds the `DISubprogram` for `function`, placed at its first located
+    /// operation.
+    fn subprogram(&mut self, function: &Function) -> usize {
+        let line = operations(function)
+            .filter_map(Operation::location)
+            .map(|loc| loc.line)
+            .min()
+            .unwrap_or(0);
+        self.add(format!(
+            "distinct !DISubprogram(name: {:?}, scope: !{file}, file: !{file}, line: {line}, \
+             type: !{}, scopeLine: {line}, spFlags: DISPFlagDefinition, unit: !{})",
+            function.name,
+            self.subroutine_type,
+            self.compile_unit,
+            file = self.file,
+        ))
+    }
+
+    fn location(&mut self, location: &Location, scope: usize) -> usize {
+        let key = (location.line, location.column, scope);
+        if let Some(&id) = self.locations.get(&key) {
+            return id;
+        }
+        let id = self.add(format!(
+            "!DILocation(line: {}, column: {}, scope: !{})",
+            location.line, location.column, scope
+        ));
+        self.locations.insert(key, id);
+        id
+    }
+
+    fn finish(mut self) -> String {
+        let dwarf_version = self.add("!{i32 7, !\"Dwarf Version\", i32 5}".to_string());
+        let debug_version = self.add("!{i32 2, !\"Debug Info Version\", i32 3}".to_string());
+        let mut text = format!(
+            "!llvm.dbg.cu = !{{!{}}}\n!llvm.module.flags = !{{!{}, !{}}}\n",
+            self.compile_unit, dwarf_version, debug_version
+        );
+        for (id, node) in self.nodes.iter().enumerate() {
+            writeln!(text, "!{} = {}", id, node).unwrap();
+        }
+        text
+    }
+}
+
+fn operations(function: &Function) -> impl Iterator<Item = &Operation> {
+    function.blocks.iter().flat_map(|block| &block.operations)
+}
+
+fn llvm_type(ty: &Type) -> Result<&'static str> {
+    match ty {
+        Type::Unit => Ok("{}"),
+        Type::Bool => Ok("i1"),
+        Type::I32 => Ok("i32"),
+        Type::I64 => Ok("i64"),
+        Type::Ref(_) | Type::Function { .. } => Ok("ptr"),
+        Type::Tuple(_) => Err(CodegenError::UnsupportedType(ty.clone())),
+    }
+}
+
+/// Bytes `malloc` must provide for a cell holding a value of type `ty`.
+fn size_of(ty: &Type) -> Result<u64> {
+    match ty {
+        Type::Unit => Ok(0),
+        Type::Bool => Ok(1),
+        Type::I32 => Ok(4),
+        Type::I64 | Type::Ref(_) | Type::Function { .. } => Ok(8),
+        Type::Tuple(_) => Err(CodegenError::UnsupportedType(ty.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lower(source: &str) -> Module {
+        let graph = parser_core::parse_source("main.syn", source).unwrap();
+        asg_to_upir::lower_graph_to_upir(&graph).unwrap()
+    }
+
+    #[test]
+    fn functions_translate_to_llvm_instructions() {
+        let module = lower("((x: Int) => x * 2 + 1)(20) < 50");
+        let ir = emit_module(&module, EmitOptions::default()).unwrap();
+        assert!(ir.contains("define i1 @main() {"));
+        assert!(ir.contains("  %v1 = call i64 @lambda_"));
+        assert!(ir.contains("(i64 20)"));
+        assert!(ir.contains("  %v3 = icmp slt i64 %v1, 50"));
+        assert!(ir.contains("  %v2 = mul i64 %v0, 2"));
+        assert!(!ir.contains("!dbg"));
+    }
+
+    #[test]
+    fn debug_locations_match_source_lines() {
+        let source = "((x: Int) =>\n  x * 2\n)(\n  20\n) + 1";
+        let ir = emit_module(&lower(source), EmitOptions { debug_info: true }).unwrap();
+
+        let line_of = |instruction: &str| -> u32 {
+            let line = ir
+                .lines()
+                .find(|line| line.contains(instruction))
+                .unwrap_or_else(|| panic!("no instruction containing '{}'", instruction));
+            let (_, id) = line.rsplit_once("!dbg !").expect("instruction has !dbg");
+            let node = ir
+                .lines()
+                .find_map(|line| line.strip_prefix(&format!("!{} = !DILocation(line: ", id)))
+                .expect("DILocation is defined");
+            node.split(',').next().unwrap().parse().unwrap()
+        };
+        assert_eq!(line_of("= mul i64"), 2);
+        assert_eq!(line_of("= call i64 @lambda_"), 1);
+        assert_eq!(line_of("= add i64"), 1);
+        assert!(ir.contains("source_filename = \"main.syn\""));
+        assert!(ir.contains("!DISubprogram(name: \"main\""));
+        assert!(ir.contains("!llvm.dbg.cu = "));
+    }
+
+    #[test]
+    fn division_checks_for_zero_and_wraps_on_overflow() {
+        let ir = emit_module(&lower("((x: Int) => 7 / x)(0)"), EmitOptions::default()).unwrap();
+        assert!(ir.contains("  %v2.zero = icmp eq i64 %v0, 0\n"));
+        assert!(ir.contains("v2.trap:\n  call void @llvm.trap()\n  unreachable\nv2.ok:\n"));
+        assert!(ir.contains("  %v2 = sdiv i64 7, %v2.divisor\n"));
+        assert!(ir.contains("declare void @llvm.trap()"));
+    }
+
+    #[test]
+    fn references_allocate_their_cell_in_the_runtime() {
+        let ir = emit_module(&lower("!(ref 5)"), EmitOptions::default()).unwrap();
+        assert!(ir.contains("entry:\n  call void @synapse_region_enter()\n"));
+        assert!(ir.contains("  %v1 = call ptr @synapse_alloc(i64 8)\n  store i64 5, ptr %v1"));
+        assert!(ir.contains("  %v2 = load i64, ptr %v1"));
+        assert!(ir.contains("  call void @synapse_region_exit()\n  ret i64 %v2\n"));
+        assert!(ir.contains("declare ptr @synapse_alloc(i64)"));
+
+        let escaping = emit_module(&lower("ref true"), EmitOptions::default()).unwrap();
+        assert!(escaping.contains("  %v1 = call ptr @synapse_alloc(i64 1)"));
+        assert!(!escaping.contains("call void @synapse_region"));
+
+        let pure = emit_module(&lower("1 + 2"), EmitOptions::default()).unwrap();
+        assert!(!pure.contains("synapse_"));
+    }
+
+    #[test]
+    fn short_circuit_operands_merge_through_a_phi() {
+        let ir = emit_module(
+            &lower("((x: Int) => x > 0 && 10 / x > 1)(5)"),
+            EmitOptions::default(),
+        )
+        .unwrap();
+        let body = ir.split("define i1 @lambda_").nth(1).unwrap();
+        assert!(body.contains("  br i1 %v2, label %and_rhs_"));
+        assert!(body.contains(".ok:\n"));
+        let phi = body
+            .lines()
+            .find(|line| line.contains(" = phi i1 "))
+            .expect("the result is merged with a phi");
+        assert!(phi.contains(", %v4.ok ]"), "{}", phi);
+        assert!(phi.contains("[ %v2, %and_short_"), "{}", phi);
+    }
+}
@@ -0,0 +1,49 @@
+//! Errors produced while generating LLVM IR.
+
+use thiserror::Error;
+
+use upir_core::{Type, ValueId};
+
+/// Reasons code generation can fail.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CodegenError {
+    /// The operation has no LLVM translation.
+    #[error("cannot translate operation '{op}' in function '{function}'")]
+    UnsupportedOperation { function: String, op: String },
+
+    /// The type has no LLVM representation yet.
+    #[error("no LLVM representation for type {0}")]
+    UnsupportedType(Type),
+
+    /// An operand names a value that is not defined before its use.
+    #[error("value %{value} used in function '{function}' is not defined")]
+    UndefinedValue { function: String, value: ValueId },
+
+    /// An operation lacks an attribute it needs, such as a `const` value.
+    #[error("operation '{op}' in function '{function}' is missing its '{attribute}' attribute")]
+    MissingAttribute {
+        function: String,
+        op: String,
+        attribute: &'static str,
+    },
+
+    /// A block takes parameters but no branch into it precedes it.
+    #[error("block '{label}' in function '{function}' has parameters but no earlier branch to it")]
+    UnreachableBlock { function: String, label: String },
+}
+
+impl CodegenError {
+    /// Stable diagnostic code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CodegenError::UnsupportedOperation { .. } => "G001",
+            CodegenError::UnsupportedType(_) => "G002",
+            CodegenError::UndefinedValue { .. } => "G003",
+            CodegenError::MissingAttribute { .. } => "G004",
+            CodegenError::UnreachableBlock { .. } => "G005",
+        }
+    }
+}
+
+/// Convenience alias for code generation results.
+pub type Result<T> = std::result::Result<T, CodegenError>;
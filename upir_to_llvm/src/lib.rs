@@ -1,14 +1,12 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Code generation from UPIR to textual LLVM IR.
+//!
+//! [`emit_module`] turns a [`upir_core::Module`] into the contents of a `.ll`
+//! file. With [`EmitOptions::debug_info`] set, every function gets a
+//! `DISubprogram` and every operation with a `location` attribute a `!dbg`
+//! attachment, so native debuggers can map instructions back to source lines.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod emit;
+pub mod error;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use emit::{EmitOptions, emit_module};
+pub use error::{CodegenError, Result};
@@ -0,0 +1,102 @@
+//! The quantum circuit IR and its OpenQASM export.
+
+use std::fmt::Write;
+
+/// A single gate, acting on qubits by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gate {
+    /// Hadamard.
+    H(usize),
+    /// Pauli X (NOT).
+    X(usize),
+    /// Controlled NOT.
+    Cnot { control: usize, target: usize },
+    /// Measures `qubit` into the classical bit `bit`.
+    Measure { qubit: usize, bit: usize },
+}
+
+impl Gate {
+    /// The qubits the gate acts on.
+    pub fn qubits(&self) -> Vec<usize> {
+        match *self {
+            Gate::H(qubit) | Gate::X(qubit) | Gate::Measure { qubit, .. } => vec![qubit],
+            Gate::Cnot { control, target } => vec![control, target],
+        }
+    }
+}
+
+/// A circuit over `qubits` qubits, applying `gates` in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuantumCircuit {
+    pub qubits: usize,
+    pub gates: Vec<Gate>,
+}
+
+impl QuantumCircuit {
+    /// Appends `gate`, growing the register to cover its qubits.
+    pub fn push(&mut self, gate: Gate) {
+        let highest = gate.qubits().into_iter().max().unwrap_or(0);
+        self.qubits = self.qubits.max(highest + 1);
+        self.gates.push(gate);
+    }
+
+    /// Number of classical bits measurements write to.
+    pub fn classical_bits(&self) -> usize {
+        self.gates
+            .iter()
+            .filter_map(|gate| match gate {
+                Gate::Measure { bit, .. } => Some(bit + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Renders the circuit as an OpenQASM 2.0 program over the registers
+    /// `q` and, if anything is measured, `c`.
+    pub fn to_openqasm(&self) -> String {
+        let mut out = String::from("OPENQASM 2.0;\ninclude \"qelib1.inc\";\n");
+        writeln!(out, "qreg q[{}];", self.qubits).unwrap();
+        let bits = self.classical_bits();
+        if bits > 0 {
+            writeln!(out, "creg c[{}];", bits).unwrap();
+        }
+        for gate in &self.gates {
+            match gate {
+                Gate::H(qubit) => writeln!(out, "h q[{}];", qubit),
+                Gate::X(qubit) => writeln!(out, "x q[{}];", qubit),
+                Gate::Cnot { control, target } => {
+                    writeln!(out, "cx q[{}],q[{}];", control, target)
+                }
+                Gate::Measure { qubit, bit } => {
+                    writeln!(out, "measure q[{}] -> c[{}];", qubit, bit)
+                }
+            }
+            .unwrap();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bell_pair_exports_to_openqasm() {
+        let mut circuit = QuantumCircuit::default();
+        circuit.push(Gate::H(0));
+        circuit.push(Gate::Cnot {
+            control: 0,
+            target: 1,
+        });
+        circuit.push(Gate::Measure { qubit: 0, bit: 0 });
+        circuit.push(Gate::Measure { qubit: 1, bit: 1 });
+        assert_eq!(circuit.qubits, 2);
+        assert_eq!(
+            circuit.to_openqasm(),
+            "OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[2];\ncreg c[2];\n\
+             h q[0];\ncx q[0],q[1];\nmeasure q[0] -> c[0];\nmeasure q[1] -> c[1];\n"
+        );
+    }
+}
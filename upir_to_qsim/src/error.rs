@@ -0,0 +1,68 @@
+//! Errors produced while lowering UPIR to a quantum circuit.
+
+use thiserror::Error;
+
+/// Reasons lowering can fail.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum QsimError {
+    /// The module contains no quantum intrinsics, so there is no circuit.
+    #[error("module '{0}' has no quantum operations to build a circuit from")]
+    ClassicalModule(String),
+
+    /// A `quantum.` operation names a gate this backend does not know.
+    #[error("unknown quantum operation '{op}' in function '{function}'")]
+    UnknownGate { function: String, op: String },
+
+    /// A gate lacks an attribute it needs, such as the qubit it acts on.
+    #[error("operation '{op}' in function '{function}' needs an integer '{attribute}' attribute")]
+    MissingAttribute {
+        function: String,
+        op: String,
+        attribute: &'static str,
+    },
+
+    /// A qubit or bit index is negative.
+    #[error("operation '{op}' in function '{function}' has negative index {index}")]
+    NegativeIndex {
+        function: String,
+        op: String,
+        index: i64,
+    },
+
+    /// A two-qubit gate names the same qubit twice.
+    #[error("operation '{op}' in function '{function}' uses qubit {qubit} twice")]
+    RepeatedQubit {
+        function: String,
+        op: String,
+        qubit: usize,
+    },
+
+    /// A gate sits outside the entry block of `main`. Only straight-line
+    /// code in `main` is lowered until calls and branches are followed.
+    #[error(
+        "quantum operation '{op}' in block '{block}' of function '{function}' is outside \
+         the entry block of 'main'; calls and control flow are not lowered yet"
+    )]
+    OutsideEntryBlock {
+        function: String,
+        block: String,
+        op: String,
+    },
+}
+
+impl QsimError {
+    /// Stable diagnostic code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            QsimError::ClassicalModule(_) => "Q001",
+            QsimError::UnknownGate { .. } => "Q002",
+            QsimError::MissingAttribute { .. } => "Q003",
+            QsimError::NegativeIndex { .. } => "Q004",
+            QsimError::RepeatedQubit { .. } => "Q005",
+            QsimError::OutsideEntryBlock { .. } => "Q006",
+        }
+    }
+}
+
+/// Convenience alias for lowering results.
+pub type Result<T> = std::result::Result<T, QsimError>;
@@ -1,14 +1,14 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Quantum circuit backend for UPIR.
+//!
+//! [`lower_upir_to_qsim`] collects the quantum intrinsics in the entry block of
+//! a module's `main`, the operations named `quantum.h`, `quantum.x`,
+//! `quantum.cnot` and `quantum.measure`, into a [`QuantumCircuit`], which
+//! [`QuantumCircuit::to_openqasm`] exports for external simulators.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod circuit;
+pub mod error;
+pub mod lower;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use circuit::{Gate, QuantumCircuit};
+pub use error::{QsimError, Result};
+pub use lower::lower_upir_to_qsim;
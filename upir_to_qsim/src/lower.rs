@@ -0,0 +1,255 @@
+//! The UPIR → quantum circuit lowering.
+//!
+//! Quantum intrinsics are operations whose name starts with `quantum.`.
+//! They name their qubits with integer attributes:
+//!
+//! ```text
+//! quantum.h {qubit = 0}
+//! quantum.x {qubit = 1}
+//! quantum.cnot {control = 0, target = 1}
+//! quantum.measure {bit = 0, qubit = 0}
+//! ```
+//!
+//! `bit` defaults to the measured qubit's index. Every other operation is
+//! classical and ignored, so the circuit holds the gates in the order they
+//! appear in the entry block of `main`. Calls and branches are not followed,
+//! so a gate anywhere else is an error rather than being run out of order
+//! or not at all.
+
+use upir_core::{Attribute, Module, Operation};
+
+use crate::circuit::{Gate, QuantumCircuit};
+use crate::error::{QsimError, Result};
+
+/// Name prefix marking an operation as a quantum intrinsic.
+const QUANTUM_PREFIX: &str = "quantum.";
+
+/// The function whose entry block is lowered.
+const ENTRY_FUNCTION: &str = "main";
+
+/// Builds the circuit made of the quantum intrinsics in the entry block of
+/// `main`.
+///
+/// Fails with [`QsimError::OutsideEntryBlock`] if any other block, in `main`
+/// or elsewhere, holds one, and with [`QsimError::ClassicalModule`] if there
+/// are none.
+pub fn lower_upir_to_qsim(module: &Module) -> Result<QuantumCircuit> {
+    let main = module.function(ENTRY_FUNCTION);
+    for function in &module.functions {
+        let skip = usize::from(main.is_some_and(|main| std::ptr::eq(main, function)));
+        for block in function.blocks.iter().skip(skip) {
+            if let Some(op) = block.operations.iter().find(|op| is_quantum(op)) {
+                return Err(QsimError::OutsideEntryBlock {
+                    function: function.name.clone(),
+                    block: block.label.clone(),
+                    op: op.name.clone(),
+                });
+            }
+        }
+    }
+
+    let mut circuit = QuantumCircuit::default();
+    if let Some(function) = main
+        && let Some(entry) = function.blocks.first()
+    {
+        for op in &entry.operations {
+            let Some(gate) = op.name.strip_prefix(QUANTUM_PREFIX) else {
+                continue;
+            };
+            let index = |attribute| index(&function.name, op, attribute);
+            let gate = match gate {
+                "h" => Gate::H(index("qubit")?),
+                "x" => Gate::X(index("qubit")?),
+                "cnot" => {
+                    let (control, target) = (index("control")?, index("target")?);
+                    if control == target {
+                        return Err(QsimError::RepeatedQubit {
+                            function: function.name.clone(),
+                            op: op.name.clone(),
+                            qubit: control,
+                        });
+                    }
+                    Gate::Cnot { control, target }
+                }
+                "measure" => {
+                    let qubit = index("qubit")?;
+                    let bit = match op.attributes.get("bit") {
+                        Some(_) => index("bit")?,
+                        None => qubit,
+                    };
+                    Gate::Measure { qubit, bit }
+                }
+                _ => {
+                    return Err(QsimError::UnknownGate {
+                        function: function.name.clone(),
+                        op: op.name.clone(),
+                    });
+                }
+            };
+            circuit.push(gate);
+        }
+    }
+    if circuit.gates.is_empty() {
+        return Err(QsimError::ClassicalModule(module.name.clone()));
+    }
+    Ok(circuit)
+}
+
+fn is_quantum(op: &Operation) -> bool {
+    op.name.starts_with(QUANTUM_PREFIX)
+}
+
+/// The non-negative integer attribute `attribute` of `op`.
+fn index(function: &str, op: &Operation, attribute: &'static str) -> Result<usize> {
+    let value = match op.attributes.get(attribute) {
+        Some(Attribute::Int(value)) => *value,
+        _ => {
+            return Err(QsimError::MissingAttribute {
+                function: function.to_string(),
+                op: op.name.clone(),
+                attribute,
+            });
+        }
+    };
+    usize::try_from(value).map_err(|_| QsimError::NegativeIndex {
+        function: function.to_string(),
+        op: op.name.clone(),
+        index: value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use upir_core::{Block, Function, Type, Value};
+
+    fn module(operations: Vec<Operation>) -> Module {
+        let mut module = Module::new("circuit");
+        module.functions.push(Function {
+            name: "main".to_string(),
+            params: Vec::new(),
+            return_type: Type::Unit,
+            blocks: vec![Block {
+                label: "entry".to_string(),
+                params: Vec::new(),
+                operations,
+            }],
+        });
+        module
+    }
+
+    fn gate(name: &str, attributes: &[(&str, i64)]) -> Operation {
+        attributes
+            .iter()
+            .fold(Operation::new(name), |op, &(key, value)| {
+                op.with_attribute(key, Attribute::Int(value))
+            })
+    }
+
+    #[test]
+    fn intrinsics_become_gates_in_order() {
+        let circuit = lower_upir_to_qsim(&module(vec![
+            gate("quantum.x", &[("qubit", 2)]),
+            Operation::new("unit").with_result(Value {
+                id: 0,
+                ty: Type::Unit,
+            }),
+            gate("quantum.h", &[("qubit", 0)]),
+            gate("quantum.cnot", &[("control", 0), ("target", 1)]),
+            gate("quantum.measure", &[("qubit", 1)]),
+            gate("quantum.measure", &[("qubit", 2), ("bit", 0)]),
+        ]))
+        .unwrap();
+        assert_eq!(circuit.qubits, 3);
+        assert_eq!(
+            circuit.gates,
+            [
+                Gate::X(2),
+                Gate::H(0),
+                Gate::Cnot {
+                    control: 0,
+                    target: 1
+                },
+                Gate::Measure { qubit: 1, bit: 1 },
+                Gate::Measure { qubit: 2, bit: 0 },
+            ]
+        );
+        assert!(
+            circuit
+                .to_openqasm()
+                .contains("qreg q[3];\ncreg c[2];\nx q[2];\n")
+        );
+    }
+
+    #[test]
+    fn classical_modules_and_malformed_gates_are_errors() {
+        let classical = module(vec![Operation::new("return")]);
+        assert_eq!(
+            lower_upir_to_qsim(&classical),
+            Err(QsimError::ClassicalModule("circuit".to_string()))
+        );
+        assert!(matches!(
+            lower_upir_to_qsim(&module(vec![gate("quantum.toffoli", &[])])),
+            Err(QsimError::UnknownGate { op, .. }) if op == "quantum.toffoli"
+        ));
+        assert!(matches!(
+            lower_upir_to_qsim(&module(vec![gate("quantum.cnot", &[("control", 1)])])),
+            Err(QsimError::MissingAttribute {
+                attribute: "target",
+                ..
+            })
+        ));
+        assert!(matches!(
+            lower_upir_to_qsim(&module(vec![gate("quantum.h", &[("qubit", -1)])])),
+            Err(QsimError::NegativeIndex { index: -1, .. })
+        ));
+        assert!(matches!(
+            lower_upir_to_qsim(&module(vec![gate(
+                "quantum.cnot",
+                &[("control", 1), ("target", 1)]
+            )])),
+            Err(QsimError::RepeatedQubit { qubit: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn gates_outside_the_entry_block_of_main_are_errors() {
+        let mut branching = module(vec![gate("quantum.h", &[("qubit", 0)])]);
+        branching.functions[0].blocks.push(Block {
+            label: "then".to_string(),
+            params: Vec::new(),
+            operations: vec![gate("quantum.x", &[("qubit", 0)])],
+        });
+        assert_eq!(
+            lower_upir_to_qsim(&branching),
+            Err(QsimError::OutsideEntryBlock {
+                function: "main".to_string(),
+                block: "then".to_string(),
+                op: "quantum.x".to_string(),
+            })
+        );
+
+        let mut calling = module(vec![gate("quantum.h", &[("qubit", 0)])]);
+        calling.functions.push(Function {
+            name: "prepare".to_string(),
+            params: Vec::new(),
+            return_type: Type::Unit,
+            blocks: vec![Block {
+                label: "entry".to_string(),
+                params: Vec::new(),
+                operations: vec![gate("quantum.x", &[("qubit", 1)])],
+            }],
+        });
+        assert!(matches!(
+            lower_upir_to_qsim(&calling),
+            Err(QsimError::OutsideEntryBlock { function, .. }) if function == "prepare"
+        ));
+
+        // Without `main`, every gate is outside it.
+        calling.functions.remove(0);
+        assert!(matches!(
+            lower_upir_to_qsim(&calling),
+            Err(QsimError::OutsideEntryBlock { function, .. }) if function == "prepare"
+        ));
+    }
+}
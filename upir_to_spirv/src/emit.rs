@@ -0,0 +1,459 @@
+//! The UPIR → SPIR-V binary emitter.
+//!
+//! The output is a little-endian SPIR-V 1.0 module for the `Shader`
+//! capability with the `Logical`/`GLSL450` memory model. Each UPIR function
+//! becomes a SPIR-V function, and a `GLCompute` entry point named `main`
+//! calls the UPIR `main` and discards its result.
+//!
+//! Only straight-line integer and boolean code is supported for now:
+//! constants, arithmetic, comparisons, `not` and direct calls. `div` and
+//! `mod` are rejected with [`SpirvError::LoweringError`]: `OpSDiv` and
+//! `OpSRem` are undefined for a zero divisor, and `OpSDiv` for `MIN / -1`,
+//! where `DESIGN_LOG.md` requires a trap and wrapping respectively, and no
+//! guarded lowering is emitted yet.
+
+use std::collections::HashMap;
+
+use upir_core::{Attribute, Function, Module, Operation, Type, ValueId};
+
+use crate::error::{Result, SpirvError};
+
+const MAGIC: u32 = 0x0723_0203;
+const VERSION_1_0: u32 = 0x0001_0000;
+
+const OP_CAPABILITY: u16 = 17;
+const OP_MEMORY_MODEL: u16 = 14;
+const OP_ENTRY_POINT: u16 = 15;
+const OP_EXECUTION_MODE: u16 = 16;
+const OP_TYPE_VOID: u16 = 19;
+const OP_TYPE_BOOL: u16 = 20;
+const OP_TYPE_INT: u16 = 21;
+const OP_TYPE_FUNCTION: u16 = 33;
+const OP_CONSTANT_TRUE: u16 = 41;
+const OP_CONSTANT_FALSE: u16 = 42;
+const OP_CONSTANT: u16 = 43;
+const OP_FUNCTION: u16 = 54;
+const OP_FUNCTION_PARAMETER: u16 = 55;
+const OP_FUNCTION_END: u16 = 56;
+const OP_FUNCTION_CALL: u16 = 57;
+const OP_LABEL: u16 = 248;
+const OP_RETURN: u16 = 253;
+const OP_RETURN_VALUE: u16 = 254;
+
+const CAPABILITY_SHADER: u32 = 1;
+const CAPABILITY_INT64: u32 = 11;
+const ADDRESSING_LOGICAL: u32 = 0;
+const MEMORY_MODEL_GLSL450: u32 = 1;
+const EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+/// Translates `module` into a SPIR-V binary.
+///
+/// The module must have a parameterless `main` function, which becomes the
+/// body of the compute entry point.
+pub fn lower_upir_to_spirv(module: &Module) -> Result<Vec<u8>> {
+    let main = module
+        .function("main")
+        .filter(|main| main.params.is_empty())
+        .ok_or(SpirvError::MissingEntryPoint)?;
+
+    let mut emitter = Emitter {
+        next_id: 1,
+        globals: Vec::new(),
+        code: Vec::new(),
+        types: HashMap::new(),
+        void: None,
+        function_types: HashMap::new(),
+        constants: HashMap::new(),
+        functions: HashMap::new(),
+        uses_int64: false,
+    };
+    for function in &module.functions {
+        let id = emitter.fresh_id();
+        emitter.functions.insert(function.name.clone(), id);
+    }
+    for function in &module.functions {
+        emitter.function(function)?;
+    }
+    let entry = emitter.entry_point(main)?;
+
+    let mut words = vec![MAGIC, VERSION_1_0, 0, emitter.next_id, 0];
+    instruction(&mut words, OP_CAPABILITY, &[CAPABILITY_SHADER]);
+    if emitter.uses_int64 {
+        instruction(&mut words, OP_CAPABILITY, &[CAPABILITY_INT64]);
+    }
+    instruction(
+        &mut words,
+        OP_MEMORY_MODEL,
+        &[ADDRESSING_LOGICAL, MEMORY_MODEL_GLSL450],
+    );
+    let mut entry_operands = vec![EXECUTION_MODEL_GL_COMPUTE, entry];
+    entry_operands.extend(string_words("main"));
+    instruction(&mut words, OP_ENTRY_POINT, &entry_operands);
+    instruction(
+        &mut words,
+        OP_EXECUTION_MODE,
+        &[entry, EXECUTION_MODE_LOCAL_SIZE, 1, 1, 1],
+    );
+    words.extend(emitter.globals);
+    words.extend(emitter.code);
+    Ok(words.into_iter().flat_map(u32::to_le_bytes).collect())
+}
+
+/// Appends an instruction: its word count and opcode, then its operands.
+fn instruction(words: &mut Vec<u32>, opcode: u16, operands: &[u32]) {
+    let word_count = u32::try_from(operands.len() + 1).expect("instruction fits in a module");
+    words.push(word_count << 16 | u32::from(opcode));
+    words.extend_from_slice(operands);
+}
+
+/// A literal string: UTF-8, nul-terminated and padded to whole words.
+fn string_words(text: &str) -> Vec<u32> {
+    let mut bytes = text.as_bytes().to_vec();
+    bytes.resize(bytes.len() / 4 * 4 + 4, 0);
+    bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().expect("chunks of four")))
+        .collect()
+}
+
+/// The opcode of a primitive operation on operands of `operand` type.
+fn primitive_opcode(name: &str, operand: &Type) -> Option<u16> {
+    let integer = matches!(operand, Type::I32 | Type::I64);
+    Some(match (name, integer) {
+        ("add", true) => 128,  // OpIAdd
+        ("sub", true) => 130,  // OpISub
+        ("mul", true) => 132,  // OpIMul
+        ("eq", true) => 170,   // OpIEqual
+        ("ne", true) => 171,   // OpINotEqual
+        ("gt", true) => 173,   // OpSGreaterThan
+        ("ge", true) => 175,   // OpSGreaterThanEqual
+        ("lt", true) => 177,   // OpSLessThan
+        ("le", true) => 179,   // OpSLessThanEqual
+        ("eq", false) => 164,  // OpLogicalEqual
+        ("ne", false) => 165,  // OpLogicalNotEqual
+        ("or", false) => 166,  // OpLogicalOr
+        ("and", false) => 167, // OpLogicalAnd
+        ("not", false) => 168, // OpLogicalNot
+        _ => return None,
+    })
+}
+
+struct Emitter {
+    next_id: u32,
+    /// Types and constants, which precede every function.
+    globals: Vec<u32>,
+    /// Function definitions.
+    code: Vec<u32>,
+    types: HashMap<Type, u32>,
+    /// `void`, which only the entry point returns.
+    void: Option<u32>,
+    /// Function types by return and parameter type ids; the void type is
+    /// keyed as `None`.
+    function_types: HashMap<(Option<u32>, Vec<u32>), u32>,
+    /// Constants by type id and value bits.
+    constants: HashMap<(u32, u64), u32>,
+    functions: HashMap<String, u32>,
+    uses_int64: bool,
+}
+
+impl Emitter {
+    fn fresh_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// The id of `ty`, declaring it on first use.
+    fn type_id(&mut self, ty: &Type) -> Result<u32> {
+        if let Some(&id) = self.types.get(ty) {
+            return Ok(id);
+        }
+        let id = self.fresh_id();
+        match ty {
+            Type::Bool => instruction(&mut self.globals, OP_TYPE_BOOL, &[id]),
+            Type::I32 => instruction(&mut self.globals, OP_TYPE_INT, &[id, 32, 1]),
+            Type::I64 => {
+                self.uses_int64 = true;
+                instruction(&mut self.globals, OP_TYPE_INT, &[id, 64, 1]);
+            }
+            other => return Err(SpirvError::UnsupportedType(other.clone())),
+        }
+        self.types.insert(ty.clone(), id);
+        Ok(id)
+    }
+
+    /// The id of a function type; a `ret` of `None` means `void`.
+    fn function_type_id(&mut self, ret: Option<u32>, params: Vec<u32>) -> u32 {
+        if let Some(&id) = self.function_types.get(&(ret, params.clone())) {
+            return id;
+        }
+        let ret_id = match ret {
+            Some(id) => id,
+            None => self.void_type_id(),
+        };
+        let id = self.fresh_id();
+        let mut operands = vec![id, ret_id];
+        operands.extend(&params);
+        instruction(&mut self.globals, OP_TYPE_FUNCTION, &operands);
+        self.function_types.insert((ret, params), id);
+        id
+    }
+
+    fn void_type_id(&mut self) -> u32 {
+        if let Some(id) = self.void {
+            return id;
+        }
+        let id = self.fresh_id();
+        instruction(&mut self.globals, OP_TYPE_VOID, &[id]);
+        self.void = Some(id);
+        id
+    }
+
+    fn constant(&mut self, ty: &Type, attribute: Option<&Attribute>) -> Option<Result<u32>> {
+        let bits = match (ty, attribute?) {
+            (Type::I32 | Type::I64, Attribute::Int(value)) => *value as u64,
+            (Type::Bool, Attribute::Bool(value)) => u64::from(*value),
+            _ => return None,
+        };
+        let type_id = match self.type_id(ty) {
+            Ok(id) => id,
+            Err(error) => return Some(Err(error)),
+        };
+        if let Some(&id) = self.constants.get(&(type_id, bits)) {
+            return Some(Ok(id));
+        }
+        let id = self.fresh_id();
+        match ty {
+            Type::Bool if bits == 1 => {
+                instruction(&mut self.globals, OP_CONSTANT_TRUE, &[type_id, id])
+            }
+            Type::Bool => instruction(&mut self.globals, OP_CONSTANT_FALSE, &[type_id, id]),
+            // Wider literals take more words, low-order word first.
+            Type::I64 => instruction(
+                &mut self.globals,
+                OP_CONSTANT,
+                &[type_id, id, bits as u32, (bits >> 32) as u32],
+            ),
+            _ => instruction(&mut self.globals, OP_CONSTANT, &[type_id, id, bits as u32]),
+        }
+        self.constants.insert((type_id, bits), id);
+        Some(Ok(id))
+    }
+
+    fn function(&mut self, function: &Function) -> Result<()> {
+        let unsupported = |op: &Operation| SpirvError::UnsupportedOperation {
+            function: function.name.clone(),
+            op: op.name.clone(),
+        };
+        let ret = self.type_id(&function.return_type)?;
+        let params = function
+            .params
+            .iter()
+            .map(|param| self.type_id(&param.ty))
+            .collect::<Result<Vec<_>>>()?;
+        let function_type = self.function_type_id(Some(ret), params.clone());
+        let id = self.functions[&function.name];
+
+        let mut code = Vec::new();
+        instruction(&mut code, OP_FUNCTION, &[ret, id, 0, function_type]);
+        // Each UPIR value's SPIR-V id and type.
+        let mut values: HashMap<ValueId, (u32, Type)> = HashMap::new();
+        for (param, type_id) in function.params.iter().zip(params) {
+            let param_id = self.fresh_id();
+            instruction(&mut code, OP_FUNCTION_PARAMETER, &[type_id, param_id]);
+            values.insert(param.id, (param_id, param.ty.clone()));
+        }
+
+        for block in &function.blocks {
+            let label = self.fresh_id();
+            instruction(&mut code, OP_LABEL, &[label]);
+            for op in &block.operations {
+                let operands = op
+                    .operands
+                    .iter()
+                    .map(|operand| {
+                        values
+                            .get(operand)
+                            .cloned()
+                            .ok_or(SpirvError::UndefinedValue {
+                                function: function.name.clone(),
+                                value: *operand,
+                            })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let Some(result) = &op.result else {
+                    match (op.name.as_str(), &operands[..]) {
+                        ("return", [(value, _)]) => {
+                            instruction(&mut code, OP_RETURN_VALUE, &[*value])
+                        }
+                        _ => return Err(unsupported(op)),
+                    }
+                    continue;
+                };
+                let id = match (op.name.as_str(), &operands[..]) {
+                    ("const", []) => self
+                        .constant(&result.ty, op.attributes.get("value"))
+                        .ok_or_else(|| unsupported(op))??,
+                    ("call", _) => {
+                        let callee = match op.attributes.get("callee") {
+                            Some(Attribute::Symbol(name)) => self.functions.get(name).copied(),
+                            _ => None,
+                        };
+                        let callee = callee.ok_or_else(|| unsupported(op))?;
+                        let result_type = self.type_id(&result.ty)?;
+                        let id = self.fresh_id();
+                        let mut call = vec![result_type, id, callee];
+                        call.extend(operands.iter().map(|(value, _)| *value));
+                        instruction(&mut code, OP_FUNCTION_CALL, &call);
+                        id
+                    }
+                    ("div" | "mod", _) => {
+                        return Err(SpirvError::LoweringError {
+                            function: function.name.clone(),
+                            op: op.name.clone(),
+                            reason: "SPIR-V leaves the result undefined where Synapse traps \
+                                     on a zero divisor or wraps"
+                                .to_string(),
+                        });
+                    }
+                    (name, [(first, operand_type), rest @ ..]) if rest.len() <= 1 => {
+                        let opcode = primitive_opcode(name, operand_type)
+                            .filter(|_| (name == "not") == rest.is_empty())
+                            .ok_or_else(|| unsupported(op))?;
+                        let result_type = self.type_id(&result.ty)?;
+                        let id = self.fresh_id();
+                        let mut operation = vec![result_type, id, *first];
+                        operation.extend(rest.iter().map(|(value, _)| *value));
+                        instruction(&mut code, opcode, &operation);
+                        id
+                    }
+                    _ => return Err(unsupported(op)),
+                };
+                values.insert(result.id, (id, result.ty.clone()));
+            }
+        }
+        instruction(&mut code, OP_FUNCTION_END, &[]);
+        self.code.extend(code);
+        Ok(())
+    }
+
+    /// Emits the `void()` entry point calling `main`, returning its id.
+    fn entry_point(&mut self, main: &Function) -> Result<u32> {
+        let function_type = self.function_type_id(None, Vec::new());
+        let void = self.void_type_id();
+        let result_type = self.type_id(&main.return_type)?;
+        let (id, label, call) = (self.fresh_id(), self.fresh_id(), self.fresh_id());
+        instruction(&mut self.code, OP_FUNCTION, &[void, id, 0, function_type]);
+        instruction(&mut self.code, OP_LABEL, &[label]);
+        instruction(
+            &mut self.code,
+            OP_FUNCTION_CALL,
+            &[result_type, call, self.functions["main"]],
+        );
+        instruction(&mut self.code, OP_RETURN, &[]);
+        instruction(&mut self.code, OP_FUNCTION_END, &[]);
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::validate_spirv;
+
+    fn lower(source: &str) -> Result<Vec<u8>> {
+        let graph = parser_core::parse_source("kernel.syn", source).unwrap();
+        lower_upir_to_spirv(&asg_to_upir::lower_graph_to_upir(&graph).unwrap())
+    }
+
+    fn words(bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    /// The opcodes of the module's instructions, in order.
+    fn opcodes(bytes: &[u8]) -> Vec<u16> {
+        let words = words(bytes);
+        let mut offset = 5;
+        let mut opcodes = Vec::new();
+        while offset < words.len() {
+            opcodes.push((words[offset] & 0xffff) as u16);
+            offset += (words[offset] >> 16) as usize;
+        }
+        opcodes
+    }
+
+    #[test]
+    fn adding_two_ints_produces_a_valid_compute_module() {
+        let bytes = lower("1 + 2").unwrap();
+        validate_spirv(&bytes).unwrap();
+        assert_eq!(words(&bytes)[..3], [MAGIC, VERSION_1_0, 0]);
+        assert_eq!(
+            opcodes(&bytes),
+            [
+                OP_CAPABILITY,
+                OP_CAPABILITY,
+                OP_MEMORY_MODEL,
+                OP_ENTRY_POINT,
+                OP_EXECUTION_MODE,
+                OP_TYPE_INT,
+                OP_TYPE_FUNCTION,
+                OP_CONSTANT,
+                OP_CONSTANT,
+                OP_TYPE_VOID,
+                OP_TYPE_FUNCTION,
+                OP_FUNCTION,
+                OP_LABEL,
+                128,
+                OP_RETURN_VALUE,
+                OP_FUNCTION_END,
+                OP_FUNCTION,
+                OP_LABEL,
+                OP_FUNCTION_CALL,
+                OP_RETURN,
+                OP_FUNCTION_END,
+            ]
+        );
+    }
+
+    #[test]
+    fn helper_functions_are_called_with_their_arguments() {
+        let bytes = lower("((x: Int) => x * 2 < 9)(4) == true").unwrap();
+        validate_spirv(&bytes).unwrap();
+        let opcodes = opcodes(&bytes);
+        for expected in [132, 177, 164, OP_FUNCTION_PARAMETER, OP_CONSTANT_TRUE] {
+            assert!(opcodes.contains(&expected), "missing opcode {}", expected);
+        }
+        assert_eq!(opcodes.iter().filter(|&&op| op == OP_FUNCTION).count(), 3);
+    }
+
+    #[test]
+    fn unsupported_operations_are_named() {
+        assert_eq!(
+            lower("!(ref 1)"),
+            Err(SpirvError::UnsupportedOperation {
+                function: "main".to_string(),
+                op: "ref".to_string(),
+            })
+        );
+        assert_eq!(
+            lower_upir_to_spirv(&Module::new("empty")),
+            Err(SpirvError::MissingEntryPoint)
+        );
+    }
+
+    #[test]
+    fn division_is_not_lowered_to_undefined_instructions() {
+        for (source, op) in [("7 / 2", "div"), ("7 % 2", "mod")] {
+            let error = lower(source);
+            let Err(SpirvError::LoweringError { op: found, .. }) = &error else {
+                panic!("{} lowered: {:?}", source, error);
+            };
+            assert_eq!(found, op);
+            assert_eq!(error.unwrap_err().code(), "S012");
+        }
+    }
+}
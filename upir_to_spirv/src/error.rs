@@ -0,0 +1,90 @@
+//! Errors reported while emitting SPIR-V or validating a binary.
+
+use thiserror::Error;
+
+use upir_core::{Type, ValueId};
+
+/// Ways emission can fail, or a binary can fail structural validation.
+/// Offsets count 32-bit words from the start of the module.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SpirvError {
+    /// The binary is shorter than a header or not a whole number of words.
+    #[error("a SPIR-V module must be a whole number of words and at least 5 long, found {0} bytes")]
+    Truncated(usize),
+
+    /// The first word is not the SPIR-V magic number in either byte order.
+    #[error("bad magic number {0:#010x}")]
+    BadMagic(u32),
+
+    /// The version word is malformed or names an unknown version.
+    #[error("unsupported version word {0:#010x}")]
+    UnsupportedVersion(u32),
+
+    /// The reserved schema word is not zero.
+    #[error("reserved schema word is {0}, expected 0")]
+    NonzeroSchema(u32),
+
+    /// An instruction declares a word count of zero.
+    #[error("instruction at word {offset} has a word count of 0")]
+    ZeroWordCount { offset: usize },
+
+    /// An instruction's word count runs past the end of the module.
+    #[error("instruction at word {offset} needs {word_count} words but only {remaining} remain")]
+    InstructionOverrun {
+        offset: usize,
+        word_count: usize,
+        remaining: usize,
+    },
+
+    /// An instruction defines an id that is not below the header's bound.
+    #[error("instruction at word {offset} defines id {id}, but the bound is {bound}")]
+    IdOutOfBound { offset: usize, id: u32, bound: u32 },
+
+    /// The module has no parameterless `main` to run as the entry point.
+    #[error("module has no parameterless 'main' function to use as the entry point")]
+    MissingEntryPoint,
+
+    /// The operation has no SPIR-V translation yet.
+    #[error("cannot translate operation '{op}' in function '{function}' to SPIR-V")]
+    UnsupportedOperation { function: String, op: String },
+
+    /// The operation has a SPIR-V instruction, but not one with the
+    /// semantics Synapse defines for it.
+    #[error("cannot lower '{op}' in function '{function}' to SPIR-V: {reason}")]
+    LoweringError {
+        function: String,
+        op: String,
+        reason: String,
+    },
+
+    /// The type has no SPIR-V representation yet.
+    #[error("no SPIR-V representation for type {0}")]
+    UnsupportedType(Type),
+
+    /// An operand names a value that is not defined before its use.
+    #[error("value %{value} used in function '{function}' is not defined")]
+    UndefinedValue { function: String, value: ValueId },
+}
+
+impl SpirvError {
+    /// Stable diagnostic code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SpirvError::Truncated(_) => "S001",
+            SpirvError::BadMagic(_) => "S002",
+            SpirvError::UnsupportedVersion(_) => "S003",
+            SpirvError::NonzeroSchema(_) => "S004",
+            SpirvError::ZeroWordCount { .. } => "S005",
+            SpirvError::InstructionOverrun { .. } => "S006",
+            SpirvError::IdOutOfBound { .. } => "S007",
+            SpirvError::MissingEntryPoint => "S008",
+            SpirvError::UnsupportedOperation { .. } => "S009",
+            SpirvError::UnsupportedType(_) => "S010",
+            SpirvError::UndefinedValue { .. } => "S011",
+            SpirvError::LoweringError { .. } => "S012",
+        }
+    }
+}
+
+/// Convenience alias for emission and validation results.
+pub type Result<T> = std::result::Result<T, SpirvError>;
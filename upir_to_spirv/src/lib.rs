@@ -1,14 +1,14 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! SPIR-V backend for UPIR.
+//!
+//! [`lower_upir_to_spirv`] emits a compute-shader module for straight-line
+//! integer and boolean code. [`validate_spirv`] performs the structural
+//! checks every SPIR-V binary must pass, so artifacts can be checked in tests
+//! without an external `spirv-val`.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod emit;
+pub mod error;
+pub mod validate;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use emit::lower_upir_to_spirv;
+pub use error::{Result, SpirvError};
+pub use validate::validate_spirv;
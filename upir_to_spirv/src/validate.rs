@@ -0,0 +1,179 @@
+//! Structural validation of SPIR-V binaries.
+//!
+//! This is not a replacement for `spirv-val`: it checks the header and the
+//! instruction stream framing, and that every id defined by a common
+//! instruction lies below the header's bound. Semantic rules such as type
+//! agreement or the logical layout of a module are not checked.
+
+use crate::error::{Result, SpirvError};
+
+/// The SPIR-V magic number, as read in the module's own byte order.
+const MAGIC: u32 = 0x0723_0203;
+
+/// Words in the module header: magic, version, generator, bound, schema.
+const HEADER_WORDS: usize = 5;
+
+/// Highest minor version of SPIR-V 1.x accepted.
+const MAX_MINOR_VERSION: u32 = 6;
+
+/// Checks that `bytes` is a structurally well-formed SPIR-V module.
+///
+/// The byte order is taken from the magic number, so both little- and
+/// big-endian encodings are accepted.
+pub fn validate_spirv(bytes: &[u8]) -> Result<()> {
+    if !bytes.len().is_multiple_of(4) || bytes.len() < HEADER_WORDS * 4 {
+        return Err(SpirvError::Truncated(bytes.len()));
+    }
+    let little: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().expect("chunks of four")))
+        .collect();
+    let words = match little[0] {
+        MAGIC => little,
+        swapped if swapped.swap_bytes() == MAGIC => {
+            little.into_iter().map(u32::swap_bytes).collect()
+        }
+        other => return Err(SpirvError::BadMagic(other)),
+    };
+
+    let version = words[1];
+    let (major, minor) = ((version >> 16) & 0xff, (version >> 8) & 0xff);
+    if version & 0xff00_00ff != 0 || major != 1 || minor > MAX_MINOR_VERSION {
+        return Err(SpirvError::UnsupportedVersion(version));
+    }
+    let bound = words[3];
+    if words[4] != 0 {
+        return Err(SpirvError::NonzeroSchema(words[4]));
+    }
+
+    let mut offset = HEADER_WORDS;
+    while offset < words.len() {
+        let word_count = (words[offset] >> 16) as usize;
+        let opcode = words[offset] & 0xffff;
+        let remaining = words.len() - offset;
+        if word_count == 0 {
+            return Err(SpirvError::ZeroWordCount { offset });
+        }
+        if word_count > remaining {
+            return Err(SpirvError::InstructionOverrun {
+                offset,
+                word_count,
+                remaining,
+            });
+        }
+        if let Some(index) = result_id_index(opcode)
+            && let Some(&id) = words[offset..offset + word_count].get(index)
+            && id >= bound
+        {
+            return Err(SpirvError::IdOutOfBound { offset, id, bound });
+        }
+        offset += word_count;
+    }
+    Ok(())
+}
+
+/// The word index of the result id within instructions of `opcode`, for the
+/// opcodes this validator knows to define one.
+fn result_id_index(opcode: u32) -> Option<usize> {
+    match opcode {
+        // OpString, OpExtInstImport, OpType*, OpDecorationGroup, OpLabel.
+        7 | 11 | 19..=39 | 73 | 248 => Some(1),
+        // OpUndef, OpExtInst, constants and spec constants, OpFunction,
+        // OpFunctionParameter, OpFunctionCall, OpVariable, memory access,
+        // composites, conversions, arithmetic, relational, logical and bit
+        // instructions, OpPhi.
+        1
+        | 12
+        | 41..=46
+        | 48..=52
+        | 54
+        | 55
+        | 57
+        | 59..=61
+        | 65..=67
+        | 79..=84
+        | 109..=124
+        | 126..=152
+        | 154..=191
+        | 194..=205
+        | 245 => Some(2),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let word_count = (operands.len() as u32 + 1) << 16;
+        std::iter::once(word_count | opcode)
+            .chain(operands.iter().copied())
+            .collect()
+    }
+
+    /// `void main() {}` as a minimal shader module with the given bound.
+    fn module(bound: u32) -> Vec<u32> {
+        let mut words = vec![MAGIC, 0x0001_0300, 0, bound, 0];
+        words.extend(instruction(17, &[1])); // OpCapability Shader
+        words.extend(instruction(14, &[0, 1])); // OpMemoryModel Logical GLSL450
+        words.extend(instruction(19, &[1])); // %1 = OpTypeVoid
+        words.extend(instruction(33, &[2, 1])); // %2 = OpTypeFunction %1
+        words.extend(instruction(54, &[1, 3, 0, 2])); // %3 = OpFunction %1 None %2
+        words.extend(instruction(248, &[4])); // %4 = OpLabel
+        words.extend(instruction(253, &[])); // OpReturn
+        words.extend(instruction(56, &[])); // OpFunctionEnd
+        words
+    }
+
+    fn to_bytes(words: &[u32], encode: fn(u32) -> [u8; 4]) -> Vec<u8> {
+        words.iter().flat_map(|&word| encode(word)).collect()
+    }
+
+    #[test]
+    fn well_formed_module_passes_in_either_byte_order() {
+        assert_eq!(
+            validate_spirv(&to_bytes(&module(5), u32::to_le_bytes)),
+            Ok(())
+        );
+        assert_eq!(
+            validate_spirv(&to_bytes(&module(5), u32::to_be_bytes)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn bound_too_small_for_the_defined_ids_fails() {
+        assert_eq!(
+            validate_spirv(&to_bytes(&module(4), u32::to_le_bytes)),
+            Err(SpirvError::IdOutOfBound {
+                offset: 20,
+                id: 4,
+                bound: 4
+            })
+        );
+    }
+
+    #[test]
+    fn broken_framing_fails_fast() {
+        let mut words = module(5);
+        words.truncate(words.len() - 1);
+        words.push(5 << 16 | 56); // OpFunctionEnd claiming five words
+        assert_eq!(
+            validate_spirv(&to_bytes(&words, u32::to_le_bytes)),
+            Err(SpirvError::InstructionOverrun {
+                offset: 23,
+                word_count: 5,
+                remaining: 1
+            })
+        );
+
+        let mut bytes = to_bytes(&module(5), u32::to_le_bytes);
+        bytes[0] = 0;
+        assert!(matches!(
+            validate_spirv(&bytes),
+            Err(SpirvError::BadMagic(_))
+        ));
+        assert_eq!(validate_spirv(&bytes[..7]), Err(SpirvError::Truncated(7)));
+    }
+}
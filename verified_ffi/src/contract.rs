@@ -0,0 +1,486 @@
+//! Pre- and postconditions of foreign functions.
+//!
+//! A contract is a list of clauses separated by `;`. Each clause is
+//! `requires` or `ensures` followed by a boolean expression over the
+//! function's parameters; `ensures` clauses may also name the `result`.
+//!
+//! ```text
+//! requires n >= 0 && n < 64; ensures result >= n
+//! ```
+//!
+//! Expressions have integer literals, `true` and `false`, parentheses, the
+//! arithmetic operators `+ - *`, comparisons, `!`, `&&` and `||`. Integer
+//! and boolean parameters and results can be named; contracts are type
+//! checked when the function is registered, so a clause naming anything
+//! else is rejected before any call is made.
+
+use std::fmt;
+
+use serde_json::Value;
+use upir_core::Type;
+
+/// Name the result is bound to in `ensures` clauses.
+pub const RESULT: &str = "result";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClauseKind {
+    /// Checked against the arguments before the call.
+    Requires,
+    /// Checked against the arguments and the result after the call.
+    Ensures,
+}
+
+/// One clause of a contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    pub kind: ClauseKind,
+    /// The clause's source text, without its keyword.
+    pub text: String,
+    expr: Expr,
+}
+
+impl fmt::Display for Clause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keyword = match self.kind {
+            ClauseKind::Requires => "requires",
+            ClauseKind::Ensures => "ensures",
+        };
+        write!(f, "{} {}", keyword, self.text)
+    }
+}
+
+/// A parsed, type-checked contract.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Contract {
+    pub clauses: Vec<Clause>,
+}
+
+impl Contract {
+    /// Parses `text` for a function with the named `params` returning `ret`.
+    pub fn parse(
+        text: &str,
+        params: &[(String, Type)],
+        ret: &Type,
+    ) -> std::result::Result<Self, String> {
+        let mut clauses = Vec::new();
+        for source in text.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            let (kind, body) = if let Some(body) = keyword(source, "requires") {
+                (ClauseKind::Requires, body)
+            } else if let Some(body) = keyword(source, "ensures") {
+                (ClauseKind::Ensures, body)
+            } else {
+                return Err(format!(
+                    "clause '{}' must start with 'requires' or 'ensures'",
+                    source
+                ));
+            };
+            let expr = Parser::new(body)?.parse()?;
+            let scope = Scope {
+                params,
+                result: (kind == ClauseKind::Ensures).then_some(ret),
+            };
+            if scope.type_of(&expr)? != Kind::Bool {
+                return Err(format!("clause '{}' is not a condition", body));
+            }
+            clauses.push(Clause {
+                kind,
+                text: body.to_string(),
+                expr,
+            });
+        }
+        Ok(Self { clauses })
+    }
+
+    /// The first clause of `kind` that does not hold, given the named
+    /// arguments and, for `ensures` clauses, the result. A clause whose
+    /// values do not have their declared types fails.
+    pub fn violated(
+        &self,
+        kind: ClauseKind,
+        args: &[(&str, &Value)],
+        result: Option<&Value>,
+    ) -> Option<&Clause> {
+        let lookup = |name: &str| match name {
+            RESULT if kind == ClauseKind::Ensures => result,
+            _ => args
+                .iter()
+                .find(|(arg, _)| *arg == name)
+                .map(|(_, value)| *value),
+        };
+        self.clauses
+            .iter()
+            .filter(|clause| clause.kind == kind)
+            .find(|clause| clause.expr.eval(&lookup) != Some(Scalar::Bool(true)))
+    }
+}
+
+fn keyword<'a>(source: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = source.strip_prefix(keyword)?;
+    rest.starts_with(char::is_whitespace).then(|| rest.trim())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Int(i64),
+    Bool(bool),
+    Name(String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int,
+    Bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Int(i64),
+    Bool(bool),
+}
+
+impl Expr {
+    /// The expression's value, or `None` if a name is unbound or has the
+    /// wrong kind of value, or the arithmetic overflows.
+    fn eval<'v>(&self, lookup: &impl Fn(&str) -> Option<&'v Value>) -> Option<Scalar> {
+        Some(match self {
+            Expr::Int(n) => Scalar::Int(*n),
+            Expr::Bool(b) => Scalar::Bool(*b),
+            Expr::Name(name) => match lookup(name)? {
+                Value::Bool(b) => Scalar::Bool(*b),
+                Value::Number(n) => Scalar::Int(n.as_i64()?),
+                _ => return None,
+            },
+            Expr::Not(operand) => match operand.eval(lookup)? {
+                Scalar::Bool(b) => Scalar::Bool(!b),
+                Scalar::Int(_) => return None,
+            },
+            Expr::Neg(operand) => match operand.eval(lookup)? {
+                Scalar::Int(n) => Scalar::Int(n.checked_neg()?),
+                Scalar::Bool(_) => return None,
+            },
+            Expr::Binary(BinaryOp::And, lhs, rhs) => {
+                let holds = lhs.eval(lookup)? == Scalar::Bool(true);
+                Scalar::Bool(holds && rhs.eval(lookup)? == Scalar::Bool(true))
+            }
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => {
+                let holds = lhs.eval(lookup)? == Scalar::Bool(true);
+                Scalar::Bool(holds || rhs.eval(lookup)? == Scalar::Bool(true))
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(lookup)?, rhs.eval(lookup)?);
+                match (op, lhs, rhs) {
+                    (BinaryOp::Eq, _, _) => Scalar::Bool(lhs == rhs),
+                    (BinaryOp::Ne, _, _) => Scalar::Bool(lhs != rhs),
+                    (_, Scalar::Int(a), Scalar::Int(b)) => match op {
+                        BinaryOp::Add => Scalar::Int(a.checked_add(b)?),
+                        BinaryOp::Sub => Scalar::Int(a.checked_sub(b)?),
+                        BinaryOp::Mul => Scalar::Int(a.checked_mul(b)?),
+                        BinaryOp::Lt => Scalar::Bool(a < b),
+                        BinaryOp::Le => Scalar::Bool(a <= b),
+                        BinaryOp::Gt => Scalar::Bool(a > b),
+                        BinaryOp::Ge => Scalar::Bool(a >= b),
+                        _ => return None,
+                    },
+                    _ => return None,
+                }
+            }
+        })
+    }
+}
+
+/// The names a clause may use, with their types.
+struct Scope<'a> {
+    params: &'a [(String, Type)],
+    result: Option<&'a Type>,
+}
+
+impl Scope<'_> {
+    fn type_of(&self, expr: &Expr) -> std::result::Result<Kind, String> {
+        let expect = |expr: &Expr, kind: Kind| {
+            let actual = self.type_of(expr)?;
+            if actual == kind {
+                Ok(())
+            } else {
+                Err(format!("expected {:?} but found {:?}", kind, actual).to_lowercase())
+            }
+        };
+        Ok(match expr {
+            Expr::Int(_) => Kind::Int,
+            Expr::Bool(_) => Kind::Bool,
+            Expr::Name(name) => {
+                let ty = if name == RESULT {
+                    self.result
+                        .ok_or("'result' can only be used in ensures clauses")?
+                } else {
+                    self.params
+                        .iter()
+                        .find(|(param, _)| param == name)
+                        .map(|(_, ty)| ty)
+                        .ok_or_else(|| format!("unknown name '{}'", name))?
+                };
+                match ty {
+                    Type::I32 | Type::I64 => Kind::Int,
+                    Type::Bool => Kind::Bool,
+                    _ => {
+                        return Err(format!(
+                            "'{}' has type {}, which contracts cannot use",
+                            name, ty
+                        ));
+                    }
+                }
+            }
+            Expr::Not(operand) => {
+                expect(operand, Kind::Bool)?;
+                Kind::Bool
+            }
+            Expr::Neg(operand) => {
+                expect(operand, Kind::Int)?;
+                Kind::Int
+            }
+            Expr::Binary(op, lhs, rhs) => match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul => {
+                    expect(lhs, Kind::Int)?;
+                    expect(rhs, Kind::Int)?;
+                    Kind::Int
+                }
+                BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                    expect(lhs, Kind::Int)?;
+                    expect(rhs, Kind::Int)?;
+                    Kind::Bool
+                }
+                BinaryOp::Eq | BinaryOp::Ne => {
+                    expect(rhs, self.type_of(lhs)?)?;
+                    Kind::Bool
+                }
+                BinaryOp::And | BinaryOp::Or => {
+                    expect(lhs, Kind::Bool)?;
+                    expect(rhs, Kind::Bool)?;
+                    Kind::Bool
+                }
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Int(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+/// Operators, longest first so `<=` is not read as `<`.
+const SYMBOLS: [&str; 15] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "!", "(", ")", "=",
+];
+
+/// Precedence-climbing parser over the tokens of one clause.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> std::result::Result<Self, String> {
+        let mut tokens = Vec::new();
+        let mut rest = source.trim_start();
+        while let Some(c) = rest.chars().next() {
+            let len = if c.is_ascii_digit() {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let n = rest[..len]
+                    .parse()
+                    .map_err(|_| format!("integer {} is too large", &rest[..len]))?;
+                tokens.push(Token::Int(n));
+                len
+            } else if c.is_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                tokens.push(Token::Name(rest[..len].to_string()));
+                len
+            } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+                if *symbol == "=" {
+                    return Err("use '==' to compare".to_string());
+                }
+                tokens.push(Token::Symbol(symbol));
+                symbol.len()
+            } else {
+                return Err(format!("unexpected character '{}'", c));
+            };
+            rest = rest[len..].trim_start();
+        }
+        Ok(Self { tokens, pos: 0 })
+    }
+
+    fn parse(mut self) -> std::result::Result<Expr, String> {
+        let expr = self.expr(0)?;
+        match self.tokens.get(self.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {}", describe(token))),
+        }
+    }
+
+    fn expr(&mut self, min_precedence: u8) -> std::result::Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Symbol(symbol)) = self.tokens.get(self.pos) {
+            let Some((op, precedence)) = binary(symbol) else {
+                break;
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            // Comparisons do not chain; everything else is left-associative.
+            let rhs = self.expr(precedence + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+            if precedence == COMPARISON
+                && matches!(self.tokens.get(self.pos), Some(Token::Symbol(s)) if binary(s).is_some_and(|(_, p)| p == COMPARISON))
+            {
+                return Err("comparisons cannot be chained".to_string());
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> std::result::Result<Expr, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or("expected an expression")?;
+        self.pos += 1;
+        Ok(match token {
+            Token::Int(n) => Expr::Int(n),
+            Token::Name(name) => match name.as_str() {
+                "true" => Expr::Bool(true),
+                "false" => Expr::Bool(false),
+                _ => Expr::Name(name),
+            },
+            Token::Symbol("!") => Expr::Not(Box::new(self.unary()?)),
+            Token::Symbol("-") => Expr::Neg(Box::new(self.unary()?)),
+            Token::Symbol("(") => {
+                let inner = self.expr(0)?;
+                if self.tokens.get(self.pos) != Some(&Token::Symbol(")")) {
+                    return Err("expected ')'".to_string());
+                }
+                self.pos += 1;
+                inner
+            }
+            token => return Err(format!("unexpected {}", describe(&token))),
+        })
+    }
+}
+
+const COMPARISON: u8 = 2;
+
+fn binary(symbol: &str) -> Option<(BinaryOp, u8)> {
+    Some(match symbol {
+        "||" => (BinaryOp::Or, 0),
+        "&&" => (BinaryOp::And, 1),
+        "==" => (BinaryOp::Eq, COMPARISON),
+        "!=" => (BinaryOp::Ne, COMPARISON),
+        "<" => (BinaryOp::Lt, COMPARISON),
+        "<=" => (BinaryOp::Le, COMPARISON),
+        ">" => (BinaryOp::Gt, COMPARISON),
+        ">=" => (BinaryOp::Ge, COMPARISON),
+        "+" => (BinaryOp::Add, 3),
+        "-" => (BinaryOp::Sub, 3),
+        "*" => (BinaryOp::Mul, 4),
+        _ => return None,
+    })
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Int(n) => format!("'{}'", n),
+        Token::Name(name) => format!("'{}'", name),
+        Token::Symbol(symbol) => format!("'{}'", symbol),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn params() -> Vec<(String, Type)> {
+        vec![
+            ("n".to_string(), Type::I32),
+            ("flag".to_string(), Type::Bool),
+        ]
+    }
+
+    fn parse(text: &str) -> std::result::Result<Contract, String> {
+        Contract::parse(text, &params(), &Type::I64)
+    }
+
+    #[test]
+    fn clauses_are_checked_against_arguments_and_results() {
+        let contract =
+            parse("requires n >= 0 && (flag || n < 10); ensures result == n * 2").unwrap();
+        let args = |n: i64, flag: bool| [json!(n), json!(flag)];
+        let check = |kind, n, flag, result: Option<Value>| {
+            let values = args(n, flag);
+            let named = [("n", &values[0]), ("flag", &values[1])];
+            contract
+                .violated(kind, &named, result.as_ref())
+                .map(ToString::to_string)
+        };
+
+        assert_eq!(check(ClauseKind::Requires, 3, false, None), None);
+        assert_eq!(check(ClauseKind::Requires, 30, true, None), None);
+        assert_eq!(
+            check(ClauseKind::Requires, 30, false, None),
+            Some("requires n >= 0 && (flag || n < 10)".to_string())
+        );
+        assert_eq!(check(ClauseKind::Ensures, 4, true, Some(json!(8))), None);
+        assert_eq!(
+            check(ClauseKind::Ensures, 4, true, Some(json!("8"))),
+            Some("ensures result == n * 2".to_string())
+        );
+    }
+
+    #[test]
+    fn malformed_contracts_are_rejected() {
+        let error = |text| parse(text).unwrap_err();
+        assert_eq!(
+            error("n > 0"),
+            "clause 'n > 0' must start with 'requires' or 'ensures'"
+        );
+        assert_eq!(error("requires m > 0"), "unknown name 'm'");
+        assert_eq!(
+            error("requires result > 0"),
+            "'result' can only be used in ensures clauses"
+        );
+        assert_eq!(error("requires n + 1"), "clause 'n + 1' is not a condition");
+        assert_eq!(error("requires flag > 0"), "expected int but found bool");
+        assert_eq!(error("requires n = 1"), "use '==' to compare");
+        assert_eq!(error("requires 0 < n < 9"), "comparisons cannot be chained");
+        assert_eq!(error("ensures (result > n"), "expected ')'");
+        assert_eq!(
+            Contract::parse("ensures result > 0", &[], &Type::Unit).unwrap_err(),
+            "'result' has type unit, which contracts cannot use"
+        );
+        assert_eq!(parse(" ; ").unwrap(), Contract::default());
+    }
+}
@@ -0,0 +1,524 @@
+//! Calling registered foreign functions.
+//!
+//! Every foreign function is called through one marshalled ABI,
+//! [`MarshalledFn`]. The arguments go in as a byte buffer the caller owns.
+//! The callee returns its result through two out-parameters: a buffer it
+//! allocated with `synapse_alloc` from `synapse_runtime`, and the number of
+//! result bytes in it. The caller checks that length against the size the
+//! buffer was allocated with, copies the bytes out, and frees the buffer
+//! with `synapse_free`, so results of any size round-trip and a callee can
+//! never make the caller read past its allocation.
+//!
+//! Runtime cells belong to the thread that made them, so the callee must
+//! allocate on the calling thread, as it does when it simply returns.
+//!
+//! [`FfiEngine::call`] takes JSON values, checks them against the declared
+//! parameter types and the function's `requires` clauses, and passes them on
+//! as a JSON array. It reads the result back as JSON and checks it against
+//! the `ensures` clauses.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::PathBuf;
+
+use serde_json::Value;
+use synapse_runtime::alloc::{synapse_alloc_size, synapse_free};
+
+use crate::contract::ClauseKind;
+use crate::error::{CallError, RegistrationError};
+use crate::library::Library;
+use crate::registry::FfiRegistry;
+use upir_core::Type;
+
+/// The native ABI of a foreign function: `args_len` argument bytes at
+/// `args` in, a result buffer and its length out through `ret` and
+/// `ret_len`, and zero returned on success. Both out-parameters start as
+/// null and zero; a callee with nothing to return may leave them so.
+pub type MarshalledFn = unsafe extern "C" fn(
+    args: *const u8,
+    args_len: usize,
+    ret: *mut *mut u8,
+    ret_len: *mut usize,
+) -> i32;
+
+/// Calls the functions of a registry, loading their libraries on first use.
+pub struct FfiEngine {
+    registry: FfiRegistry,
+    /// Open libraries, keyed by the registered path; `None` is the running
+    /// program. Resolved functions stay valid while their library is here.
+    libraries: HashMap<Option<PathBuf>, Library>,
+    functions: HashMap<String, MarshalledFn>,
+}
+
+impl FfiEngine {
+    pub fn new(registry: FfiRegistry) -> Self {
+        Self {
+            registry,
+            libraries: HashMap::new(),
+            functions: HashMap::new(),
+        }
+    }
+
+    pub fn registry(&self) -> &FfiRegistry {
+        &self.registry
+    }
+
+    /// Implements the registered function `name` with `function` instead of
+    /// its native symbol.
+    pub fn bind(&mut self, name: &str, function: MarshalledFn) -> Result<(), CallError> {
+        if self.registry.get(name).is_none() {
+            return Err(CallError::UnknownFunction(name.to_string()));
+        }
+        self.functions.insert(name.to_string(), function);
+        Ok(())
+    }
+
+    /// Calls `name` with `args` encoded as a JSON array, after checking them
+    /// against its declared parameters and preconditions, and returns its
+    /// JSON result once it passes the postconditions. An empty result reads
+    /// as `null`. Badly typed calls fail before the function's library is
+    /// loaded.
+    ///
+    /// # Safety
+    ///
+    /// As for [`call_bytes`](Self::call_bytes).
+    pub unsafe fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, CallError> {
+        self.check_arguments(name, args)?;
+        self.check_contract(name, ClauseKind::Requires, args, None)?;
+        let encoded = serde_json::to_vec(args).expect("JSON values always serialize");
+        // SAFETY: forwarded from the caller.
+        let bytes = unsafe { self.call_bytes(name, &encoded) }?;
+        let result = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).map_err(|error| CallError::MalformedResult {
+                name: name.to_string(),
+                reason: error.to_string(),
+            })?
+        };
+        self.check_contract(name, ClauseKind::Ensures, args, Some(&result))?;
+        Ok(result)
+    }
+
+    fn check_contract(
+        &self,
+        name: &str,
+        kind: ClauseKind,
+        args: &[Value],
+        result: Option<&Value>,
+    ) -> Result<(), CallError> {
+        let (Some(declared), Some(contract)) =
+            (self.registry.get(name), self.registry.contract(name))
+        else {
+            return Err(CallError::UnknownFunction(name.to_string()));
+        };
+        let names = declared.param_names();
+        let named: Vec<(&str, &Value)> = names.iter().map(String::as_str).zip(args).collect();
+        match contract.violated(kind, &named, result) {
+            Some(clause) => Err(CallError::ContractError {
+                name: name.to_string(),
+                clause: clause.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that `args` match the declared parameters of `name` in number
+    /// and type.
+    pub fn check_arguments(&self, name: &str, args: &[Value]) -> Result<(), CallError> {
+        let declared = self
+            .registry
+            .get(name)
+            .ok_or_else(|| CallError::UnknownFunction(name.to_string()))?;
+        let params = &declared.signature.params;
+        if params.len() != args.len() {
+            return Err(CallError::ArgumentCount {
+                name: name.to_string(),
+                expected: params.len(),
+                got: args.len(),
+            });
+        }
+        match params
+            .iter()
+            .zip(args)
+            .position(|(ty, arg)| !accepts(ty, arg))
+        {
+            Some(index) => Err(CallError::TypeMismatch {
+                name: name.to_string(),
+                index,
+                expected: params[index].clone(),
+                got: describe(&args[index]),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Calls `name` with the marshalled `args` and returns its result bytes.
+    ///
+    /// # Safety
+    ///
+    /// The function's symbol, or the function bound to it, must follow the
+    /// [`MarshalledFn`] ABI and return its result in a runtime cell.
+    pub unsafe fn call_bytes(&mut self, name: &str, args: &[u8]) -> Result<Vec<u8>, CallError> {
+        let function = self.resolve(name)?;
+        let mut ret = std::ptr::null_mut();
+        let mut ret_len = 0;
+        // SAFETY: the caller guarantees the ABI; `args` is valid for its
+        // length and the out-parameters for writes.
+        let status = unsafe { function(args.as_ptr(), args.len(), &mut ret, &mut ret_len) };
+        // Zero for anything but a live cell, so a null or foreign pointer
+        // only passes with an empty result.
+        let allocated = synapse_alloc_size(ret);
+        let result = if status != 0 {
+            Err(CallError::Failed {
+                name: name.to_string(),
+                status,
+            })
+        } else if ret_len > allocated {
+            Err(CallError::ReturnOverrun {
+                name: name.to_string(),
+                len: ret_len,
+                allocated,
+            })
+        } else if ret_len == 0 {
+            Ok(Vec::new())
+        } else {
+            // SAFETY: `ret` is a live cell of at least `ret_len` bytes.
+            Ok(unsafe { std::slice::from_raw_parts(ret, ret_len) }.to_vec())
+        };
+        // SAFETY: the buffer is not used again; pointers that are not live
+        // cells are ignored.
+        unsafe { synapse_free(ret) };
+        result
+    }
+
+    fn resolve(&mut self, name: &str) -> Result<MarshalledFn, CallError> {
+        if let Some(&function) = self.functions.get(name) {
+            return Ok(function);
+        }
+        let declared = self
+            .registry
+            .get(name)
+            .ok_or_else(|| CallError::UnknownFunction(name.to_string()))?;
+        let library = match self.libraries.entry(declared.library.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(match &declared.library {
+                Some(path) => Library::open(path)?,
+                None => Library::current_process()?,
+            }),
+        };
+        let address =
+            library
+                .symbol(&declared.symbol)
+                .ok_or_else(|| RegistrationError::SymbolNotFound {
+                    symbol: declared.symbol.clone(),
+                    library: library.path().to_path_buf(),
+                })?;
+        // SAFETY: a non-null symbol address; `call`'s caller guarantees it
+        // is a function with this signature.
+        let function =
+            unsafe { std::mem::transmute::<*mut std::ffi::c_void, MarshalledFn>(address.as_ptr()) };
+        self.functions.insert(name.to_string(), function);
+        Ok(function)
+    }
+}
+
+/// Whether `value` can be passed where `ty` is declared. Cells and
+/// functions have no JSON form, so nothing is accepted for them.
+fn accepts(ty: &Type, value: &Value) -> bool {
+    match (ty, value) {
+        (Type::Unit, Value::Null) | (Type::Bool, Value::Bool(_)) => true,
+        (Type::I32, Value::Number(n)) => n.as_i64().is_some_and(|n| i32::try_from(n).is_ok()),
+        (Type::I64, Value::Number(n)) => n.as_i64().is_some(),
+        (Type::Tuple(types), Value::Array(values)) => {
+            types.len() == values.len() && types.iter().zip(values).all(|(t, v)| accepts(t, v))
+        }
+        _ => false,
+    }
+}
+
+/// A short description of `value` for type mismatch errors.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(_) => "a boolean".to_string(),
+        Value::Number(n) if n.is_i64() || n.is_u64() => format!("the integer {}", n),
+        Value::Number(n) => format!("the number {}", n),
+        Value::String(_) => "a string".to_string(),
+        Value::Array(values) => format!("an array of {} values", values.len()),
+        Value::Object(_) => "an object".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{ForeignFunction, ForeignSignature};
+    use serde_json::json;
+    use synapse_runtime::alloc::{live_cells, synapse_alloc};
+
+    unsafe extern "C" fn reverse(
+        args: *const u8,
+        args_len: usize,
+        ret: *mut *mut u8,
+        ret_len: *mut usize,
+    ) -> i32 {
+        // SAFETY: the engine passes valid arguments and out-parameters.
+        unsafe {
+            let input = std::slice::from_raw_parts(args, args_len);
+            let buffer = synapse_alloc(args_len);
+            for (i, byte) in input.iter().rev().enumerate() {
+                buffer.add(i).write(*byte);
+            }
+            *ret = buffer;
+            *ret_len = args_len;
+        }
+        0
+    }
+
+    unsafe extern "C" fn overrun(
+        _: *const u8,
+        _: usize,
+        ret: *mut *mut u8,
+        ret_len: *mut usize,
+    ) -> i32 {
+        // SAFETY: the engine passes valid out-parameters.
+        unsafe {
+            *ret = synapse_alloc(4);
+            *ret_len = 4096;
+        }
+        0
+    }
+
+    unsafe extern "C" fn foreign_buffer(
+        _: *const u8,
+        _: usize,
+        ret: *mut *mut u8,
+        ret_len: *mut usize,
+    ) -> i32 {
+        static BYTES: [u8; 3] = [1, 2, 3];
+        // SAFETY: the engine passes valid out-parameters.
+        unsafe {
+            *ret = BYTES.as_ptr().cast_mut();
+            *ret_len = BYTES.len();
+        }
+        0
+    }
+
+    /// Doubles its single JSON argument, then adds `BIAS`.
+    unsafe extern "C" fn double<const BIAS: i64>(
+        args: *const u8,
+        args_len: usize,
+        ret: *mut *mut u8,
+        ret_len: *mut usize,
+    ) -> i32 {
+        // SAFETY: the engine passes valid arguments and out-parameters.
+        unsafe {
+            let args: Vec<i64> =
+                serde_json::from_slice(std::slice::from_raw_parts(args, args_len)).unwrap();
+            let result = (args[0] * 2 + BIAS).to_string();
+            let buffer = synapse_alloc(result.len());
+            buffer.copy_from_nonoverlapping(result.as_ptr(), result.len());
+            *ret = buffer;
+            *ret_len = result.len();
+        }
+        0
+    }
+
+    unsafe extern "C" fn fail(_: *const u8, _: usize, _: *mut *mut u8, _: *mut usize) -> i32 {
+        7
+    }
+
+    fn engine(names: &[&str]) -> FfiEngine {
+        let mut registry = FfiRegistry::new();
+        for name in names {
+            let signature = ForeignSignature::new(vec![Type::I64], Type::I64);
+            registry
+                .register(ForeignFunction::new(*name, signature))
+                .unwrap();
+        }
+        FfiEngine::new(registry)
+    }
+
+    #[test]
+    fn results_of_any_length_round_trip_and_are_freed() {
+        let mut engine = engine(&["reverse"]);
+        engine.bind("reverse", reverse).unwrap();
+        let cells = live_cells();
+        let args: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
+
+        // SAFETY: `reverse` follows the marshalled ABI.
+        let result = unsafe { engine.call_bytes("reverse", &args) }.unwrap();
+        assert_eq!(result.len(), args.len());
+        assert!(result.iter().eq(args.iter().rev()));
+        assert_eq!(unsafe { engine.call_bytes("reverse", &[]) }, Ok(Vec::new()));
+        assert_eq!(live_cells(), cells);
+    }
+
+    #[test]
+    fn lengths_beyond_the_buffer_are_rejected() {
+        let mut engine = engine(&["overrun", "foreign", "fail"]);
+        engine.bind("overrun", overrun).unwrap();
+        engine.bind("foreign", foreign_buffer).unwrap();
+        engine.bind("fail", fail).unwrap();
+        let cells = live_cells();
+
+        // SAFETY: the bound functions follow the marshalled ABI.
+        unsafe {
+            assert_eq!(
+                engine.call_bytes("overrun", &[]),
+                Err(CallError::ReturnOverrun {
+                    name: "overrun".to_string(),
+                    len: 4096,
+                    allocated: 4,
+                })
+            );
+            assert_eq!(
+                engine.call_bytes("foreign", &[]),
+                Err(CallError::ReturnOverrun {
+                    name: "foreign".to_string(),
+                    len: 3,
+                    allocated: 0,
+                })
+            );
+            assert_eq!(
+                engine.call_bytes("fail", &[]),
+                Err(CallError::Failed {
+                    name: "fail".to_string(),
+                    status: 7,
+                })
+            );
+        }
+        assert_eq!(live_cells(), cells);
+    }
+
+    #[test]
+    fn unregistered_and_unexported_functions_are_errors() {
+        let mut engine = engine(&["synapse_no_such_symbol"]);
+        assert_eq!(
+            engine.bind("reverse", reverse),
+            Err(CallError::UnknownFunction("reverse".to_string()))
+        );
+        // SAFETY: neither call reaches native code.
+        unsafe {
+            assert_eq!(
+                engine.call_bytes("reverse", &[]),
+                Err(CallError::UnknownFunction("reverse".to_string()))
+            );
+            #[cfg(unix)]
+            assert!(matches!(
+                engine.call_bytes("synapse_no_such_symbol", &[]),
+                Err(CallError::Unavailable(
+                    RegistrationError::SymbolNotFound { .. }
+                ))
+            ));
+        }
+    }
+
+    #[test]
+    fn arguments_are_checked_before_the_library_is_loaded() {
+        let mut registry = FfiRegistry::new();
+        let signature = ForeignSignature::new(
+            vec![Type::I32, Type::Tuple(vec![Type::Bool, Type::Unit])],
+            Type::I64,
+        );
+        registry
+            .register(ForeignFunction::new("pair", signature).with_symbol("synapse_no_such_symbol"))
+            .unwrap();
+        let mut engine = FfiEngine::new(registry);
+
+        // SAFETY: none of these calls reaches native code.
+        unsafe {
+            assert_eq!(
+                engine.call("pair", &[json!(1)]),
+                Err(CallError::ArgumentCount {
+                    name: "pair".to_string(),
+                    expected: 2,
+                    got: 1,
+                })
+            );
+            assert_eq!(
+                engine.call("pair", &[json!("1"), json!([true, null])]),
+                Err(CallError::TypeMismatch {
+                    name: "pair".to_string(),
+                    index: 0,
+                    expected: Type::I32,
+                    got: "a string".to_string(),
+                })
+            );
+            let error = engine
+                .call("pair", &[json!(1i64 << 40), json!([true, null])])
+                .unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "argument 0 of 'pair' should be i32, not the integer 1099511627776"
+            );
+            assert!(matches!(
+                engine.call("pair", &[json!(1), json!([true, 0])]),
+                Err(CallError::TypeMismatch { index: 1, .. })
+            ));
+            #[cfg(unix)]
+            assert!(matches!(
+                engine.call("pair", &[json!(-1), json!([false, null])]),
+                Err(CallError::Unavailable(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn contracts_are_checked_around_the_call() {
+        let mut registry = FfiRegistry::new();
+        for name in ["double", "off_by_one"] {
+            let signature = ForeignSignature::new(vec![Type::I64], Type::I64);
+            let function = ForeignFunction::new(name, signature)
+                .with_param_names(["n"])
+                .with_contract("requires n >= 0; ensures result == n * 2");
+            registry.register(function).unwrap();
+        }
+        let mut engine = FfiEngine::new(registry);
+        engine.bind("double", double::<0>).unwrap();
+        engine.bind("off_by_one", double::<1>).unwrap();
+
+        // SAFETY: the bound functions follow the marshalled ABI.
+        unsafe {
+            assert_eq!(engine.call("double", &[json!(21)]), Ok(json!(42)));
+            assert_eq!(
+                engine.call("double", &[json!(-1)]),
+                Err(CallError::ContractError {
+                    name: "double".to_string(),
+                    clause: "requires n >= 0".to_string(),
+                })
+            );
+            assert_eq!(
+                engine
+                    .call("off_by_one", &[json!(21)])
+                    .unwrap_err()
+                    .to_string(),
+                "call to 'off_by_one' violates its contract: ensures result == n * 2"
+            );
+        }
+    }
+
+    #[test]
+    fn contracts_naming_undefined_values_are_not_registered() {
+        let mut registry = FfiRegistry::new();
+        let signature = || ForeignSignature::new(vec![Type::I64], Type::I64);
+        assert_eq!(
+            registry
+                .register(ForeignFunction::new("f", signature()).with_contract("requires x > 0")),
+            Err(RegistrationError::ContractError {
+                name: "f".to_string(),
+                reason: "unknown name 'x'".to_string(),
+            })
+        );
+        assert!(matches!(
+            registry.register(ForeignFunction::new("g", signature()).with_param_names(["a", "b"])),
+            Err(RegistrationError::ContractError { .. })
+        ));
+        registry
+            .register(ForeignFunction::new("h", signature()).with_contract("requires arg0 > 0"))
+            .unwrap();
+        assert_eq!(registry.len(), 1);
+        assert!(registry.get("h").is_some());
+    }
+}
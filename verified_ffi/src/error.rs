@@ -0,0 +1,93 @@
+//! Errors reported while registering and calling foreign functions.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+use upir_core::Type;
+
+use crate::registry::ForeignSignature;
+
+/// Why a foreign function could not be registered.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RegistrationError {
+    /// A function with this Synapse name is already registered.
+    #[error("foreign function '{0}' is already registered")]
+    DuplicateName(String),
+
+    /// Another function already binds the same native symbol with a
+    /// different signature.
+    #[error(
+        "'{name}' binds symbol '{symbol}' as {signature}, but '{existing_name}' already binds it as {existing_signature}"
+    )]
+    SignatureConflict {
+        name: String,
+        symbol: String,
+        signature: Box<ForeignSignature>,
+        existing_name: String,
+        existing_signature: Box<ForeignSignature>,
+    },
+
+    /// The function's contract does not parse or names undefined values.
+    #[error("malformed contract for '{name}': {reason}")]
+    ContractError { name: String, reason: String },
+
+    /// The library could not be loaded.
+    #[error("cannot load library {}: {reason}", library.display())]
+    LibraryUnavailable { library: PathBuf, reason: String },
+
+    /// The library does not export the symbol.
+    #[error("symbol '{symbol}' is not exported by {}", library.display())]
+    SymbolNotFound { symbol: String, library: PathBuf },
+}
+
+/// Convenience alias for registration results.
+pub type Result<T> = std::result::Result<T, RegistrationError>;
+
+/// Why a call to a foreign function failed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CallError {
+    /// No function of this name is registered.
+    #[error("foreign function '{0}' is not registered")]
+    UnknownFunction(String),
+
+    /// The call passes the wrong number of arguments.
+    #[error("'{name}' takes {expected} arguments but was given {got}")]
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
+
+    /// An argument does not fit the declared parameter type.
+    #[error("argument {index} of '{name}' should be {expected}, not {got}")]
+    TypeMismatch {
+        name: String,
+        index: usize,
+        expected: Type,
+        got: String,
+    },
+
+    /// The function's library or symbol could not be loaded.
+    #[error(transparent)]
+    Unavailable(#[from] RegistrationError),
+
+    /// A clause of the function's contract did not hold.
+    #[error("call to '{name}' violates its contract: {clause}")]
+    ContractError { name: String, clause: String },
+
+    /// The native code returned bytes that are not a JSON value.
+    #[error("foreign function '{name}' returned malformed JSON: {reason}")]
+    MalformedResult { name: String, reason: String },
+
+    /// The native code reported failure with a non-zero status.
+    #[error("foreign function '{name}' failed with status {status}")]
+    Failed { name: String, status: i32 },
+
+    /// The native code claimed more result bytes than its buffer holds.
+    #[error("foreign function '{name}' returned {len} bytes in a buffer of {allocated}")]
+    ReturnOverrun {
+        name: String,
+        len: usize,
+        allocated: usize,
+    },
+}
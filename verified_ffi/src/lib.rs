@@ -1,14 +1,20 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Declarations of foreign (C ABI) functions callable from Synapse.
+//!
+//! An [`FfiRegistry`] holds every [`ForeignFunction`] a program may call.
+//! Registration catches mistakes early: duplicate names, two declarations of
+//! one native symbol with different signatures, and, when the library is
+//! known, symbols the library does not export. An [`FfiEngine`] then calls
+//! them, marshalling arguments and results as byte buffers and checking
+//! each function's [`contract`] around the call.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod contract;
+pub mod engine;
+pub mod error;
+pub mod library;
+pub mod registry;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use contract::{ClauseKind, Contract};
+pub use engine::{FfiEngine, MarshalledFn};
+pub use error::{CallError, RegistrationError, Result};
+pub use library::Library;
+pub use registry::{FfiRegistry, ForeignFunction, ForeignSignature};
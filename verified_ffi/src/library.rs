@@ -0,0 +1,127 @@
+//! Looking up symbols in native shared libraries.
+
+use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+
+use crate::error::{RegistrationError, Result};
+
+/// A shared library opened for symbol lookup, closed on drop.
+#[derive(Debug)]
+pub struct Library {
+    path: PathBuf,
+    handle: *mut std::ffi::c_void,
+}
+
+impl Library {
+    /// Opens the library at `path`. A bare file name such as `libc.so.6` is
+    /// searched for the way the platform's dynamic loader does.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let unavailable = |reason: String| RegistrationError::LibraryUnavailable {
+            library: path.clone(),
+            reason,
+        };
+        let name = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|_| unavailable("path contains a NUL byte".to_string()))?;
+        let handle = sys::open(&name).map_err(unavailable)?;
+        Ok(Self { path, handle })
+    }
+
+    /// Opens the running program itself, for symbols it exports or has
+    /// loaded from its dependencies.
+    pub fn current_process() -> Result<Self> {
+        let path = std::env::current_exe().unwrap_or_default();
+        let handle = sys::open_self().map_err(|reason| RegistrationError::LibraryUnavailable {
+            library: path.clone(),
+            reason,
+        })?;
+        Ok(Self { path, handle })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the library exports `symbol`.
+    pub fn has_symbol(&self, symbol: &str) -> bool {
+        self.symbol(symbol).is_some()
+    }
+
+    /// The address of `symbol`, valid for as long as the library is open.
+    pub fn symbol(&self, symbol: &str) -> Option<NonNull<std::ffi::c_void>> {
+        let symbol = CString::new(symbol).ok()?;
+        NonNull::new(sys::symbol(self.handle, &symbol))
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        sys::close(self.handle);
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::*;
+
+    pub fn open(name: &CStr) -> std::result::Result<*mut std::ffi::c_void, String> {
+        // SAFETY: `name` is a valid C string; a null result is handled below.
+        let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_LAZY | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(last_error());
+        }
+        Ok(handle)
+    }
+
+    pub fn open_self() -> std::result::Result<*mut std::ffi::c_void, String> {
+        // SAFETY: a null name opens the main program; a null result is
+        // handled below.
+        let handle = unsafe { libc::dlopen(std::ptr::null(), libc::RTLD_LAZY) };
+        if handle.is_null() {
+            return Err(last_error());
+        }
+        Ok(handle)
+    }
+
+    pub fn symbol(handle: *mut std::ffi::c_void, symbol: &CStr) -> *mut std::ffi::c_void {
+        // SAFETY: `handle` came from a successful `dlopen` and is still open.
+        unsafe { libc::dlsym(handle, symbol.as_ptr()) }
+    }
+
+    pub fn close(handle: *mut std::ffi::c_void) {
+        // SAFETY: `handle` came from a successful `dlopen` and is closed once.
+        unsafe { libc::dlclose(handle) };
+    }
+
+    fn last_error() -> String {
+        // SAFETY: `dlerror` returns null or a C string valid until the next
+        // `dl*` call on this thread, and it is copied out immediately.
+        let message = unsafe { libc::dlerror() };
+        if message.is_null() {
+            return "unknown error".to_string();
+        }
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use super::*;
+
+    pub fn open(_name: &CStr) -> std::result::Result<*mut std::ffi::c_void, String> {
+        Err("dynamic library lookup is only supported on Unix".to_string())
+    }
+
+    pub fn open_self() -> std::result::Result<*mut std::ffi::c_void, String> {
+        Err("dynamic library lookup is only supported on Unix".to_string())
+    }
+
+    pub fn symbol(_handle: *mut std::ffi::c_void, _symbol: &CStr) -> *mut std::ffi::c_void {
+        std::ptr::null_mut()
+    }
+
+    pub fn close(_handle: *mut std::ffi::c_void) {}
+}
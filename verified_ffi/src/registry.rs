@@ -0,0 +1,272 @@
+//! The registry of declared foreign functions.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use upir_core::Type;
+
+use crate::contract::Contract;
+use crate::error::{RegistrationError, Result};
+use crate::library::Library;
+
+/// The C-level signature a foreign function is declared with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignSignature {
+    pub params: Vec<Type>,
+    pub ret: Type,
+}
+
+impl ForeignSignature {
+    pub fn new(params: Vec<Type>, ret: Type) -> Self {
+        Self { params, ret }
+    }
+}
+
+impl fmt::Display for ForeignSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let function = Type::Function {
+            params: self.params.clone(),
+            ret: Box::new(self.ret.clone()),
+        };
+        write!(f, "{}", function)
+    }
+}
+
+/// A native function made available to Synapse under `name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignFunction {
+    /// The name Synapse code calls the function by.
+    pub name: String,
+    /// The symbol exported by the native code.
+    pub symbol: String,
+    /// The shared library defining the symbol, if known. Without one the
+    /// symbol is resolved against the running process at call time.
+    pub library: Option<PathBuf>,
+    pub signature: ForeignSignature,
+    /// Names of the parameters in contracts; `arg0`, `arg1`, ... if empty.
+    pub param_names: Vec<String>,
+    /// Conditions checked around every call, in the language of
+    /// [`contract`](crate::contract).
+    pub contract: Option<String>,
+}
+
+impl ForeignFunction {
+    /// Declares `name` as binding the native symbol of the same name.
+    pub fn new(name: impl Into<String>, signature: ForeignSignature) -> Self {
+        let name = name.into();
+        Self {
+            symbol: name.clone(),
+            name,
+            library: None,
+            signature,
+            param_names: Vec::new(),
+            contract: None,
+        }
+    }
+
+    /// Binds a differently named native symbol.
+    pub fn with_symbol(mut self, symbol: impl Into<String>) -> Self {
+        self.symbol = symbol.into();
+        self
+    }
+
+    /// Resolves the symbol in the library at `library`.
+    pub fn with_library(mut self, library: impl Into<PathBuf>) -> Self {
+        self.library = Some(library.into());
+        self
+    }
+
+    /// Names the parameters for the contract.
+    pub fn with_param_names<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.param_names = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Attaches a contract, parsed when the function is registered.
+    pub fn with_contract(mut self, contract: impl Into<String>) -> Self {
+        self.contract = Some(contract.into());
+        self
+    }
+
+    /// The contract name of each parameter.
+    pub fn param_names(&self) -> Vec<String> {
+        if self.param_names.is_empty() {
+            (0..self.signature.params.len())
+                .map(|i| format!("arg{}", i))
+                .collect()
+        } else {
+            self.param_names.clone()
+        }
+    }
+
+    fn parse_contract(&self) -> Result<Contract> {
+        let malformed = |reason| RegistrationError::ContractError {
+            name: self.name.clone(),
+            reason,
+        };
+        let names = self.param_names();
+        if names.len() != self.signature.params.len() {
+            return Err(malformed(format!(
+                "{} parameter names for {} parameters",
+                names.len(),
+                self.signature.params.len()
+            )));
+        }
+        let params: Vec<(String, Type)> = names
+            .into_iter()
+            .zip(self.signature.params.iter().cloned())
+            .collect();
+        match &self.contract {
+            Some(text) => Contract::parse(text, &params, &self.signature.ret).map_err(malformed),
+            None => Ok(Contract::default()),
+        }
+    }
+}
+
+/// Every foreign function a program may call, keyed by Synapse name.
+#[derive(Debug, Default)]
+pub struct FfiRegistry {
+    functions: BTreeMap<String, ForeignFunction>,
+    contracts: BTreeMap<String, Contract>,
+}
+
+impl FfiRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `function`. Several names may bind one symbol, but only with the
+    /// same signature. When the function names its library, the library is
+    /// opened and must export the symbol, so a missing symbol is reported
+    /// here rather than when the function is first called. So is a
+    /// malformed contract.
+    pub fn register(&mut self, function: ForeignFunction) -> Result<()> {
+        if self.functions.contains_key(&function.name) {
+            return Err(RegistrationError::DuplicateName(function.name));
+        }
+        let contract = function.parse_contract()?;
+        if let Some(existing) = self.functions.values().find(|existing| {
+            existing.symbol == function.symbol
+                && existing.library == function.library
+                && existing.signature != function.signature
+        }) {
+            return Err(RegistrationError::SignatureConflict {
+                name: function.name,
+                symbol: function.symbol,
+                signature: Box::new(function.signature),
+                existing_name: existing.name.clone(),
+                existing_signature: Box::new(existing.signature.clone()),
+            });
+        }
+        if let Some(path) = &function.library {
+            let library = Library::open(path)?;
+            if !library.has_symbol(&function.symbol) {
+                return Err(RegistrationError::SymbolNotFound {
+                    symbol: function.symbol,
+                    library: path.clone(),
+                });
+            }
+        }
+        self.contracts.insert(function.name.clone(), contract);
+        self.functions.insert(function.name.clone(), function);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ForeignFunction> {
+        self.functions.get(name)
+    }
+
+    /// The parsed contract of `name`; empty if it declared none.
+    pub fn contract(&self, name: &str) -> Option<&Contract> {
+        self.contracts.get(name)
+    }
+
+    /// Registered functions in name order.
+    pub fn functions(&self) -> impl Iterator<Item = &ForeignFunction> {
+        self.functions.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    const LIBC: &str = "libc.so.6";
+
+    fn int_to_int() -> ForeignSignature {
+        ForeignSignature::new(vec![Type::I64], Type::I64)
+    }
+
+    #[test]
+    fn conflicting_signatures_for_one_symbol_are_rejected() {
+        let mut registry = FfiRegistry::new();
+        registry
+            .register(ForeignFunction::new("abs", int_to_int()))
+            .unwrap();
+        registry
+            .register(ForeignFunction::new("magnitude", int_to_int()).with_symbol("abs"))
+            .unwrap();
+
+        let error = registry
+            .register(
+                ForeignFunction::new(
+                    "abs_bool",
+                    ForeignSignature::new(vec![Type::Bool], Type::Bool),
+                )
+                .with_symbol("abs"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "'abs_bool' binds symbol 'abs' as fn(bool) -> bool, but 'abs' already binds it as fn(i64) -> i64"
+        );
+        assert_eq!(
+            registry.register(ForeignFunction::new("abs", int_to_int())),
+            Err(RegistrationError::DuplicateName("abs".to_string()))
+        );
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn missing_symbol_is_reported_at_registration() {
+        let mut registry = FfiRegistry::new();
+        registry
+            .register(ForeignFunction::new("labs", int_to_int()).with_library(LIBC))
+            .unwrap();
+
+        let missing = ForeignFunction::new("frobnicate", int_to_int())
+            .with_symbol("synapse_no_such_symbol")
+            .with_library(LIBC);
+        let error = registry.register(missing).unwrap_err();
+        assert_eq!(
+            error,
+            RegistrationError::SymbolNotFound {
+                symbol: "synapse_no_such_symbol".to_string(),
+                library: PathBuf::from(LIBC),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "symbol 'synapse_no_such_symbol' is not exported by libc.so.6"
+        );
+        assert!(registry.get("frobnicate").is_none());
+
+        let no_library =
+            ForeignFunction::new("f", int_to_int()).with_library("libsynapse_missing.so");
+        assert!(matches!(
+            registry.register(no_library),
+            Err(RegistrationError::LibraryUnavailable { .. })
+        ));
+    }
+}